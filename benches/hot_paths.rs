@@ -0,0 +1,227 @@
+//! Benchmarks for the hot paths of the shortening and redirect flows.
+//!
+//! Run with `cargo bench`; compare against a saved baseline with
+//! `cargo bench -- --save-baseline main` / `--baseline main`.
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use tokio::runtime::Runtime;
+
+use actix_web::{test, web, App};
+use url_map::handlers::{redirect, CreateUrlResponse, UrlStats};
+use url_map::redirect::{RedirectConfig, Redirector};
+use url_map::models::ShortenedUrl;
+use url_map::services::{normalize_url, CodeGenerator, RandomCodeGenerator};
+use url_map::storage::{MemoryStorage, PostgresStorage, Storage, StorageConfig};
+
+const SAMPLE_URLS: &[(&str, &str)] = &[
+    ("short", "https://example.com"),
+    ("typical", "https://news.example.org/articles/2024/how-to-benchmark-rust?utm_source=newsletter&utm_campaign=spring"),
+    ("idn", "https://bücher.example/straße/übersicht?q=äöü"),
+];
+
+fn bench_code_generation(c: &mut Criterion) {
+    let generator = RandomCodeGenerator::default();
+    c.bench_function("generate_short_code", |b| b.iter(|| generator.generate()));
+}
+
+fn bench_url_normalization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("normalize_url");
+    for (name, url) in SAMPLE_URLS {
+        group.bench_with_input(BenchmarkId::from_parameter(name), url, |b, url| {
+            b.iter(|| normalize_url(black_box(url)))
+        });
+    }
+
+    let long_url = format!("https://example.com/{}", "a".repeat(2000));
+    group.bench_function("near_limit", |b| b.iter(|| normalize_url(black_box(&long_url))));
+    group.finish();
+}
+
+fn seeded_storage(links: usize) -> (Arc<MemoryStorage>, Vec<String>) {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let rt = Runtime::new().unwrap();
+    let generator = RandomCodeGenerator::default();
+    let codes: Vec<String> = (0..links).map(|_| generator.generate()).collect();
+    rt.block_on(async {
+        for code in &codes {
+            storage
+                .save_url(ShortenedUrl {
+                    original_url: format!("https://example.com/{}", code),
+                    short_url: code.clone(),
+                    ..ShortenedUrl::default()
+                })
+                .await
+                .unwrap();
+        }
+    });
+    (storage, codes)
+}
+
+fn bench_memory_storage_concurrency(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let (storage, codes) = seeded_storage(10_000);
+
+    let mut group = c.benchmark_group("memory_storage_get_url");
+    for concurrency in [1usize, 8, 64] {
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &concurrency| {
+                b.to_async(&rt).iter(|| {
+                    let tasks = (0..concurrency).map(|i| {
+                        let storage = storage.clone();
+                        let code = codes[i % codes.len()].clone();
+                        tokio::spawn(async move { storage.get_url(&code).await.unwrap() })
+                    });
+                    join_all(tasks)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// The redirect fast path: cache hits vs. storage lookups, and the full handler on a cache hit
+fn bench_redirect(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let (storage, codes) = seeded_storage(10_000);
+    let code = codes[0].clone();
+
+    let (cached, uncached) = rt.block_on(async {
+        let cached = Redirector::new(storage.clone(), RedirectConfig::default());
+        cached.resolve(&code).await.unwrap();
+        let uncached = Redirector::new(
+            storage.clone(),
+            RedirectConfig { cache_capacity: 0, ..RedirectConfig::default() },
+        );
+        (web::Data::new(cached), uncached)
+    });
+
+    let mut group = c.benchmark_group("redirect");
+    group.bench_function("resolve_cache_hit", |b| {
+        b.to_async(&rt).iter(|| async { cached.resolve(black_box(&code)).await.unwrap() })
+    });
+    group.bench_function("resolve_cache_miss", |b| {
+        b.to_async(&rt).iter(|| async { uncached.resolve(black_box(&code)).await.unwrap() })
+    });
+
+    let app = rt.block_on(test::init_service(
+        App::new()
+            .app_data(cached.clone())
+            .route("/{short_code}", web::get().to(redirect)),
+    ));
+    let uri = format!("/{}", code);
+    group.bench_function("handler_cache_hit", |b| {
+        b.to_async(&rt).iter(|| async {
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            test::call_service(&app, req).await
+        })
+    });
+    group.finish();
+}
+
+/// Concurrent visit increments on a single link, with the counter in one row vs. spread over shards.
+///
+/// Needs a scratch database: set `BENCH_DATABASE_URL`, otherwise the group is skipped.
+fn bench_postgres_hot_link(c: &mut Criterion) {
+    let Ok(database_url) = std::env::var("BENCH_DATABASE_URL") else {
+        return;
+    };
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+
+    let mut group = c.benchmark_group("postgres_hot_link_visits");
+    for shards in [1u8, 16] {
+        let storage = rt.block_on(async {
+            let config = StorageConfig {
+                connection_string: database_url.clone(),
+                max_connections: Some(32),
+                visit_counter_shards: shards,
+                ..StorageConfig::default()
+            };
+            let storage = Arc::new(PostgresStorage::new(config).await.unwrap());
+            let code = format!("bench-hot-{}", shards);
+            if storage.get_stats(&code).await.is_err() {
+                storage
+                    .save_url(ShortenedUrl {
+                        original_url: "https://example.com/viral".to_string(),
+                        short_url: code,
+                        ..ShortenedUrl::default()
+                    })
+                    .await
+                    .unwrap();
+            }
+            storage
+        });
+
+        let concurrency = 32;
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(BenchmarkId::new("shards", shards), &shards, |b, &shards| {
+            b.to_async(&rt).iter(|| {
+                let tasks = (0..concurrency).map(|_| {
+                    let storage = storage.clone();
+                    let code = format!("bench-hot-{}", shards);
+                    tokio::spawn(async move { storage.record_visits(&code, 1).await.unwrap() })
+                });
+                join_all(tasks)
+            })
+        });
+        rt.block_on(storage.fold_visit_shards()).unwrap();
+    }
+    group.finish();
+}
+
+fn bench_response_serialization(c: &mut Criterion) {
+    let created = CreateUrlResponse {
+        short_url: "abcDEF1234".to_string(),
+        original_url: SAMPLE_URLS[1].1.to_string(),
+        warnings: Vec::new(),
+    };
+    let stats = UrlStats {
+        short_url: "abcDEF1234".to_string(),
+        original_url: SAMPLE_URLS[1].1.to_string(),
+        idn_host: None,
+        visits: 123_456,
+        created_at: Utc::now().to_rfc3339(),
+        title: Some("Spring launch".to_string()),
+        notes: None,
+        created_by: None,
+        collection_id: None,
+        click_goal: None,
+        goal_reached_at: None,
+        pass_through: false,
+        content_type: None,
+        disabled_at: None,
+        signed_only: false,
+        tracking_snippet: None,
+        redirect_headers: None,
+        hide_referrer: false,
+        version: 1,
+        first_visited_at: Some(Utc::now().to_rfc3339()),
+        last_visited_at: Some(Utc::now().to_rfc3339()),
+        visits_last_24h: Some(42),
+        visits_last_7d: Some(310),
+        retired_codes: Vec::new(),
+        aliases: Vec::new(),
+    };
+
+    let mut group = c.benchmark_group("serialize_response");
+    group.bench_function("create_url", |b| b.iter(|| serde_json::to_vec(black_box(&created))));
+    group.bench_function("stats", |b| b.iter(|| serde_json::to_vec(black_box(&stats))));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_code_generation,
+    bench_url_normalization,
+    bench_memory_storage_concurrency,
+    bench_redirect,
+    bench_postgres_hot_link,
+    bench_response_serialization
+);
+criterion_main!(benches);
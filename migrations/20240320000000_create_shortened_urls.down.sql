@@ -0,0 +1,2 @@
+-- Drop shortened_urls table
+DROP TABLE IF EXISTS shortened_urls;
@@ -0,0 +1,3 @@
+-- Remove URL ownership tracking
+DROP INDEX IF EXISTS idx_shortened_urls_owner_created_at;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS owner;
@@ -0,0 +1,5 @@
+-- Track which API key owner created each shortened URL
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS owner TEXT;
+
+-- Supports listing an owner's most recent links
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_created_at ON shortened_urls(owner, created_at DESC);
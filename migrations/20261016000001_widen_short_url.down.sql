@@ -0,0 +1,2 @@
+-- Fails while aliases longer than 10 characters exist; remove or shorten them first
+ALTER TABLE shortened_urls ALTER COLUMN short_url TYPE VARCHAR(10);
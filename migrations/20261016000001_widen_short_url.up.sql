@@ -0,0 +1,2 @@
+-- Custom aliases may be up to 64 characters, including emoji and other Unicode
+ALTER TABLE shortened_urls ALTER COLUMN short_url TYPE VARCHAR(64);
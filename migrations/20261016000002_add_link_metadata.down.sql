@@ -0,0 +1,4 @@
+-- Remove descriptive link fields
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS created_by;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS notes;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS title;
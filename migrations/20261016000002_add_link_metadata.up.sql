@@ -0,0 +1,4 @@
+-- Descriptive fields that keep links identifiable long after creation
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS title TEXT;
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS notes TEXT;
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS created_by TEXT;
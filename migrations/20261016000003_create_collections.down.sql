@@ -0,0 +1,4 @@
+-- Remove link collections
+DROP INDEX IF EXISTS idx_shortened_urls_collection_id;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS collection_id;
+DROP TABLE IF EXISTS collections;
@@ -0,0 +1,14 @@
+-- Named groups of links, e.g. one per campaign
+CREATE TABLE IF NOT EXISTS collections (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (owner, name)
+);
+
+-- Deleting a collection keeps its links, unassigned
+ALTER TABLE shortened_urls
+    ADD COLUMN IF NOT EXISTS collection_id BIGINT REFERENCES collections(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_collection_id ON shortened_urls(collection_id);
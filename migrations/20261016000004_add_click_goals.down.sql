@@ -0,0 +1,4 @@
+-- Remove click goals
+DROP INDEX IF EXISTS idx_shortened_urls_pending_goals;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS goal_reached_at;
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS click_goal;
@@ -0,0 +1,7 @@
+-- Visit threshold an owner wants to be notified about, and when it was reached
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS click_goal BIGINT;
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS goal_reached_at TIMESTAMPTZ;
+
+-- Supports finding links whose goal is pending
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_pending_goals
+    ON shortened_urls(id) WHERE click_goal IS NOT NULL AND goal_reached_at IS NULL;
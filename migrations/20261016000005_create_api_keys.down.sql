@@ -0,0 +1,2 @@
+-- Remove self-service API keys
+DROP TABLE IF EXISTS api_keys;
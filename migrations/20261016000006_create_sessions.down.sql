@@ -0,0 +1,2 @@
+-- Remove dashboard sessions
+DROP TABLE IF EXISTS sessions;
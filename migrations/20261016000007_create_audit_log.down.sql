@@ -0,0 +1,2 @@
+-- Remove the audit log
+DROP TABLE IF EXISTS audit_log;
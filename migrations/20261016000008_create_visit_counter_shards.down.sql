@@ -0,0 +1,7 @@
+-- Fold outstanding shard counts back into the links, then remove the shards
+UPDATE shortened_urls u
+SET visits = u.visits + s.visits
+FROM (SELECT short_url, SUM(visits)::BIGINT AS visits FROM visit_counter_shards GROUP BY short_url) s
+WHERE u.short_url = s.short_url;
+
+DROP TABLE IF EXISTS visit_counter_shards;
@@ -0,0 +1,8 @@
+-- Visit counts split over several rows per link, so concurrent redirects of one hot
+-- link don't all wait on the same row lock; folded into shortened_urls.visits periodically
+CREATE TABLE IF NOT EXISTS visit_counter_shards (
+    short_url TEXT NOT NULL REFERENCES shortened_urls(short_url) ON DELETE CASCADE,
+    shard SMALLINT NOT NULL,
+    visits BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (short_url, shard)
+);
@@ -0,0 +1,2 @@
+-- Remove per-link path pass-through
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS pass_through;
@@ -0,0 +1,2 @@
+-- Links that forward any path and query after the code to their destination
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS pass_through BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Fails while namespaced codes longer than 64 characters exist; remove or shorten them first
+ALTER TABLE shortened_urls ALTER COLUMN short_url TYPE VARCHAR(64);
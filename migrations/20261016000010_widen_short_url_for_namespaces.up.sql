@@ -0,0 +1,2 @@
+-- Namespaced codes are stored as `prefix/code`: up to 32 + 1 + 64 characters
+ALTER TABLE shortened_urls ALTER COLUMN short_url TYPE VARCHAR(128);
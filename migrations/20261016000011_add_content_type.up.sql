@@ -0,0 +1,2 @@
+-- Content type the destination reported when the link was created, if it was checked
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS content_type TEXT;
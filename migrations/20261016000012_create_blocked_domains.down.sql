@@ -0,0 +1,2 @@
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS disabled_at;
+DROP TABLE IF EXISTS blocked_domains;
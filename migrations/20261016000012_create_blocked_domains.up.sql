@@ -0,0 +1,10 @@
+-- Destination domains banned by operators; links to them or their subdomains are refused
+CREATE TABLE IF NOT EXISTS blocked_domains (
+    domain TEXT PRIMARY KEY,
+    reason TEXT,
+    blocked_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Disabled links are kept for their history but no longer redirect
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS disabled_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Links that only redirect with a valid, unexpired signature in the query string
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS signed_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,13 @@
+-- Codes a link answered to before it was given a new one. A retired code keeps redirecting
+-- to the link until redirects_until, and keeps the visits that were forked off with it.
+CREATE TABLE IF NOT EXISTS retired_codes (
+    id BIGSERIAL PRIMARY KEY,
+    short_code TEXT NOT NULL,
+    url_id BIGINT NOT NULL REFERENCES shortened_urls(id) ON DELETE CASCADE,
+    visits BIGINT NOT NULL DEFAULT 0,
+    retired_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    redirects_until TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_retired_codes_short_code ON retired_codes(short_code, redirects_until);
+CREATE INDEX IF NOT EXISTS idx_retired_codes_url_id ON retired_codes(url_id);
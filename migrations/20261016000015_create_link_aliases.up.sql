@@ -0,0 +1,8 @@
+-- Extra short codes of a link; they redirect to it for good and count visits towards it
+CREATE TABLE IF NOT EXISTS link_aliases (
+    short_code TEXT PRIMARY KEY,
+    url_id BIGINT NOT NULL REFERENCES shortened_urls(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_link_aliases_url_id ON link_aliases(url_id);
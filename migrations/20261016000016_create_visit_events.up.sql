@@ -0,0 +1,10 @@
+-- One row per redirect, for analytics beyond the links' visit counters
+CREATE TABLE IF NOT EXISTS visit_events (
+    id BIGSERIAL PRIMARY KEY,
+    url_id BIGINT NOT NULL REFERENCES shortened_urls(id) ON DELETE CASCADE,
+    visited_at TIMESTAMPTZ NOT NULL,
+    -- The visitor's address as the IP privacy mode allows it to be kept
+    visitor TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_visit_events_url_id_visited_at ON visit_events(url_id, visited_at);
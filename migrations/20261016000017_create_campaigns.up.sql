@@ -0,0 +1,17 @@
+-- Named groups of an owner's links whose visits are reported together; a link may be in
+-- several campaigns
+CREATE TABLE IF NOT EXISTS campaigns (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (owner, name)
+);
+
+CREATE TABLE IF NOT EXISTS campaign_links (
+    campaign_id BIGINT NOT NULL REFERENCES campaigns(id) ON DELETE CASCADE,
+    url_id BIGINT NOT NULL REFERENCES shortened_urls(id) ON DELETE CASCADE,
+    PRIMARY KEY (campaign_id, url_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_campaign_links_url_id ON campaign_links(url_id);
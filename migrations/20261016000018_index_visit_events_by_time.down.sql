@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_visit_events_visited_at;
@@ -0,0 +1,2 @@
+-- Supports reading all links' visit events for a time range, e.g. for exports
+CREATE INDEX IF NOT EXISTS idx_visit_events_visited_at ON visit_events(visited_at, id);
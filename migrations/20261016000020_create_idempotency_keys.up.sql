@@ -0,0 +1,15 @@
+-- Responses to link creation requests, replayed when a client retries with the same Idempotency-Key
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    -- Hash of the API key the request was made with; empty for anonymous requests
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    -- Unset while the first request is still being handled
+    status_code INTEGER,
+    content_type TEXT,
+    body TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys(created_at);
@@ -0,0 +1,2 @@
+-- Revision of a link's editable fields, bumped on every update, for conditional updates
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+-- Attribution tag a visit arrived with, from the short link's `src` query parameter
+ALTER TABLE visit_events ADD COLUMN IF NOT EXISTS source TEXT;
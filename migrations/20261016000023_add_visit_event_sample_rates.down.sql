@@ -0,0 +1 @@
+ALTER TABLE visit_events DROP COLUMN IF EXISTS sample_rate;
@@ -0,0 +1,2 @@
+-- Visit events may be sampled on busy links: each event stands for `sample_rate` visits
+ALTER TABLE visit_events ADD COLUMN IF NOT EXISTS sample_rate INTEGER NOT NULL DEFAULT 1 CHECK (sample_rate >= 1);
@@ -0,0 +1,13 @@
+-- Daily usage per link owner, rolled up from links and visit events for billing
+CREATE TABLE IF NOT EXISTS usage_records (
+    day DATE NOT NULL,
+    owner TEXT NOT NULL,
+    -- Links created that day
+    creations BIGINT NOT NULL,
+    -- Redirects recorded as visit events that day, sampled events counting for their rate
+    redirects BIGINT NOT NULL,
+    -- Links stored at the end of the day, or at the last roll-up for the current day
+    stored_links BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (day, owner)
+);
@@ -0,0 +1,2 @@
+-- Owner's analytics snippet, fired on a page that forwards visitors instead of a bare redirect
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS tracking_snippet TEXT;
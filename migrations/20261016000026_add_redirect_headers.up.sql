@@ -0,0 +1,2 @@
+-- Extra response headers sent with a link's redirects, as an object of lowercase names to values
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS redirect_headers JSONB;
@@ -0,0 +1,2 @@
+-- Redirects of the link ask browsers not to tell the destination where it was clicked
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS hide_referrer BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_created_at ON shortened_urls(owner, created_at DESC);
+DROP INDEX IF EXISTS idx_shortened_urls_owner_created_at_id;
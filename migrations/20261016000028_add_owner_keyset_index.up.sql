@@ -0,0 +1,4 @@
+-- Lets recent-link pages continue after the last link of the previous one with an index seek,
+-- ties in creation time broken by ID
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_created_at_id ON shortened_urls(owner, created_at DESC, id DESC);
+DROP INDEX IF EXISTS idx_shortened_urls_owner_created_at;
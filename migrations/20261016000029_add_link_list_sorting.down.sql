@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_shortened_urls_owner_host;
+DROP INDEX IF EXISTS idx_shortened_urls_owner_last_visited_id;
+DROP INDEX IF EXISTS idx_shortened_urls_owner_visits_id;
+DROP FUNCTION IF EXISTS url_host(TEXT);
+ALTER TABLE shortened_urls DROP COLUMN IF EXISTS last_visited_at;
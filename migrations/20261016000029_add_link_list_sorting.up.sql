@@ -0,0 +1,14 @@
+-- When a link was last visited, kept as visits are counted; folded shard counts set it when
+-- they are folded
+ALTER TABLE shortened_urls ADD COLUMN IF NOT EXISTS last_visited_at TIMESTAMPTZ;
+
+-- Lowercase host of a URL, as GET /api/urls?domain= compares it
+CREATE OR REPLACE FUNCTION url_host(url TEXT) RETURNS TEXT
+    LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE
+    AS $$ SELECT lower(substring(url FROM '^[A-Za-z][A-Za-z0-9+.-]*://(?:[^@/?#]*@)?(\[[^]]*\]|[^:/?#]*)')) $$;
+
+-- Support the sorts of GET /api/urls, ties broken by ID, and its domain filter
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_visits_id ON shortened_urls(owner, visits DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_last_visited_id
+    ON shortened_urls(owner, COALESCE(last_visited_at, to_timestamp(0)) DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_shortened_urls_owner_host ON shortened_urls(owner, url_host(original_url));
@@ -0,0 +1,273 @@
+//! Abusive client penalties.
+//!
+//! Clients scanning for short codes or hammering rate limits produce a stream of 404s and 429s.
+//! The [`AbuseGuard`] counts those per IP and, past a threshold, tarpits or blocks the client
+//! for a cooldown period. Admins can list and lift penalties.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Number of independently locked shards, to keep concurrent requests from contending
+const SHARDS: usize = 16;
+
+/// Clients tracked per shard; the least recently seen are forgotten first
+const CLIENTS_PER_SHARD: usize = 4_096;
+
+/// Responses counted against a client: unknown codes from scans, and rate limit hits
+const COUNTED_STATUSES: &[StatusCode] = &[StatusCode::NOT_FOUND, StatusCode::TOO_MANY_REQUESTS];
+
+/// What happens to requests from a penalized client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbusePenalty {
+    /// Delay each request before handling it
+    #[default]
+    Tarpit,
+    /// Answer with 429 without handling the request
+    Block,
+}
+
+impl FromStr for AbusePenalty {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "tarpit" => Ok(Self::Tarpit),
+            "block" => Ok(Self::Block),
+            other => Err(format!("unknown abuse penalty '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AbuseConfig {
+    /// Counted errors a client may cause within `window` before it is penalized
+    pub max_errors: u32,
+    pub window: Duration,
+    /// How long a penalty lasts
+    pub cooldown: Duration,
+    pub penalty: AbusePenalty,
+    /// Delay added to each request of a tarpitted client
+    pub tarpit_delay: Duration,
+    /// Identify clients by the right-most `X-Forwarded-For`/`Forwarded` hop instead of the peer
+    /// address; only safe behind a proxy that sets them
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for AbuseConfig {
+    fn default() -> Self {
+        Self {
+            max_errors: 50,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(600),
+            penalty: AbusePenalty::default(),
+            tarpit_delay: Duration::from_secs(2),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// How a request from a penalized client is to be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Delay(Duration),
+    /// Refuse, telling the client when to come back
+    Block { retry_after: Duration },
+}
+
+/// A client currently under a penalty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PenalizedClient {
+    pub ip: IpAddr,
+    pub penalty: AbusePenalty,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// Errors counted in the window that triggered or last renewed the penalty
+    pub errors: u32,
+}
+
+/// Counters since startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AbuseStats {
+    /// Clients under a penalty right now
+    pub penalized_clients: usize,
+    /// Penalties imposed or renewed
+    pub penalties: u64,
+    pub requests_delayed: u64,
+    pub requests_blocked: u64,
+}
+
+#[derive(Debug, Clone)]
+struct ClientRecord {
+    window_started: Instant,
+    errors: u32,
+    penalty: Option<Penalty>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Penalty {
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    errors: u32,
+}
+
+/// Tracks error responses per client IP in a fixed window, penalizing clients that exceed
+/// the limit for a cooldown period: a fail2ban built into the service
+pub struct AbuseGuard {
+    config: AbuseConfig,
+    clients: Vec<Mutex<LruCache<IpAddr, ClientRecord>>>,
+    penalties: AtomicU64,
+    requests_delayed: AtomicU64,
+    requests_blocked: AtomicU64,
+}
+
+impl AbuseGuard {
+    pub fn new(config: AbuseConfig) -> Self {
+        let per_shard = NonZeroUsize::new(CLIENTS_PER_SHARD).expect("shard capacity is non-zero");
+        Self {
+            config,
+            clients: (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+            penalties: AtomicU64::new(0),
+            requests_delayed: AtomicU64::new(0),
+            requests_blocked: AtomicU64::new(0),
+        }
+    }
+
+    pub fn trusts_forwarded_for(&self) -> bool {
+        self.config.trust_forwarded_for
+    }
+
+    fn shard(&self, ip: &IpAddr) -> &Mutex<LruCache<IpAddr, ClientRecord>> {
+        let mut hasher = DefaultHasher::new();
+        ip.hash(&mut hasher);
+        &self.clients[hasher.finish() as usize % SHARDS]
+    }
+
+    /// Decides how to handle a request from `ip`, counting delayed and blocked requests
+    pub fn check(&self, ip: IpAddr) -> Verdict {
+        let Ok(mut shard) = self.shard(&ip).lock() else { return Verdict::Allow };
+        let now = Utc::now();
+        let Some(penalty) = shard.get(&ip).and_then(|record| record.penalty).filter(|penalty| penalty.until > now) else {
+            return Verdict::Allow;
+        };
+
+        match self.config.penalty {
+            AbusePenalty::Tarpit => {
+                self.requests_delayed.fetch_add(1, Ordering::Relaxed);
+                Verdict::Delay(self.config.tarpit_delay)
+            }
+            AbusePenalty::Block => {
+                self.requests_blocked.fetch_add(1, Ordering::Relaxed);
+                let retry_after = (penalty.until - now).to_std().unwrap_or_default();
+                Verdict::Block { retry_after }
+            }
+        }
+    }
+
+    /// Counts a response to `ip`, penalizing the client once it causes too many errors
+    pub fn record(&self, ip: IpAddr, status: StatusCode) {
+        if !COUNTED_STATUSES.contains(&status) {
+            return;
+        }
+        let Ok(mut shard) = self.shard(&ip).lock() else { return };
+
+        let now = Instant::now();
+        let record = shard.get_or_insert_mut(ip, || ClientRecord { window_started: now, errors: 0, penalty: None });
+        if now.duration_since(record.window_started) >= self.config.window {
+            record.window_started = now;
+            record.errors = 0;
+        }
+        record.errors = record.errors.saturating_add(1);
+        if record.errors <= self.config.max_errors {
+            return;
+        }
+
+        // Penalties are imposed, or extended while errors keep coming, once per window
+        let errors = record.errors;
+        record.window_started = now;
+        record.errors = 0;
+        let started = Utc::now();
+        let since = match record.penalty {
+            Some(penalty) if penalty.until > started => penalty.since,
+            _ => started,
+        };
+        let until = started + chrono::Duration::from_std(self.config.cooldown).unwrap_or_default();
+        record.penalty = Some(Penalty { since, until, errors });
+        self.penalties.fetch_add(1, Ordering::Relaxed);
+        warn!(ip = %ip, errors, penalty = ?self.config.penalty, until = %until, "Penalizing abusive client");
+    }
+
+    /// Clients under a penalty right now, longest penalized first
+    pub fn penalized(&self) -> Vec<PenalizedClient> {
+        let now = Utc::now();
+        let mut clients: Vec<PenalizedClient> = self
+            .clients
+            .iter()
+            .filter_map(|shard| shard.lock().ok())
+            .flat_map(|shard| {
+                shard
+                    .iter()
+                    .filter_map(|(ip, record)| Some((*ip, record.penalty?)))
+                    .filter(|(_, penalty)| penalty.until > now)
+                    .map(|(ip, penalty)| PenalizedClient {
+                        ip,
+                        penalty: self.config.penalty,
+                        since: penalty.since,
+                        until: penalty.until,
+                        errors: penalty.errors,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        clients.sort_by_key(|client| (client.since, client.ip));
+        clients
+    }
+
+    /// Lifts the penalty of one client, or of all clients for `None`, and forgets their errors;
+    /// returns how many penalized clients were cleared
+    pub fn clear(&self, ip: Option<IpAddr>) -> usize {
+        let now = Utc::now();
+        let is_penalized = |record: &ClientRecord| record.penalty.is_some_and(|penalty| penalty.until > now);
+        match ip {
+            Some(ip) => {
+                let Ok(mut shard) = self.shard(&ip).lock() else { return 0 };
+                shard.pop(&ip).is_some_and(|record| is_penalized(&record)).into()
+            }
+            None => self
+                .clients
+                .iter()
+                .filter_map(|shard| shard.lock().ok())
+                .map(|mut shard| {
+                    let cleared = shard.iter().filter(|(_, record)| is_penalized(record)).count();
+                    shard.clear();
+                    cleared
+                })
+                .sum(),
+        }
+    }
+
+    pub fn stats(&self) -> AbuseStats {
+        AbuseStats {
+            penalized_clients: self.penalized().len(),
+            penalties: self.penalties.load(Ordering::Relaxed),
+            requests_delayed: self.requests_delayed.load(Ordering::Relaxed),
+            requests_blocked: self.requests_blocked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,91 @@
+use super::*;
+
+fn guard(penalty: AbusePenalty) -> AbuseGuard {
+    AbuseGuard::new(AbuseConfig {
+        max_errors: 3,
+        window: Duration::from_secs(60),
+        cooldown: Duration::from_secs(600),
+        penalty,
+        tarpit_delay: Duration::from_millis(250),
+        trust_forwarded_for: false,
+    })
+}
+
+fn ip(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+#[test]
+fn test_clients_are_penalized_past_the_error_limit() {
+    let guard = guard(AbusePenalty::Tarpit);
+    let scanner = ip("203.0.113.7");
+
+    // Successful responses and other errors are not counted
+    for status in [StatusCode::OK, StatusCode::FOUND, StatusCode::UNPROCESSABLE_ENTITY, StatusCode::NOT_FOUND] {
+        guard.record(scanner, status);
+    }
+    guard.record(scanner, StatusCode::TOO_MANY_REQUESTS);
+    guard.record(scanner, StatusCode::NOT_FOUND);
+    assert_eq!(guard.check(scanner), Verdict::Allow);
+
+    guard.record(scanner, StatusCode::NOT_FOUND);
+    assert_eq!(guard.check(scanner), Verdict::Delay(Duration::from_millis(250)));
+    assert_eq!(guard.check(ip("203.0.113.8")), Verdict::Allow);
+
+    let penalized = guard.penalized();
+    assert_eq!(penalized.len(), 1);
+    assert_eq!((penalized[0].ip, penalized[0].errors, penalized[0].penalty), (scanner, 4, AbusePenalty::Tarpit));
+    let stats = guard.stats();
+    assert_eq!((stats.penalized_clients, stats.penalties, stats.requests_delayed), (1, 1, 1));
+}
+
+#[test]
+fn test_blocked_clients_are_told_when_to_retry() {
+    let guard = guard(AbusePenalty::Block);
+    let scanner = ip("2001:db8::1");
+    for _ in 0..4 {
+        guard.record(scanner, StatusCode::NOT_FOUND);
+    }
+
+    match guard.check(scanner) {
+        Verdict::Block { retry_after } => assert!(retry_after > Duration::from_secs(590)),
+        verdict => panic!("Expected Block, got {:?}", verdict),
+    }
+    assert_eq!(guard.stats().requests_blocked, 1);
+}
+
+#[test]
+fn test_errors_are_counted_per_window() {
+    let guard = AbuseGuard::new(AbuseConfig { max_errors: 1, window: Duration::from_millis(20), ..AbuseConfig::default() });
+    let client = ip("198.51.100.1");
+
+    guard.record(client, StatusCode::NOT_FOUND);
+    std::thread::sleep(Duration::from_millis(30));
+    guard.record(client, StatusCode::NOT_FOUND);
+    assert_eq!(guard.check(client), Verdict::Allow);
+}
+
+#[test]
+fn test_penalties_can_be_cleared() {
+    let guard = guard(AbusePenalty::Block);
+    for client in ["192.0.2.1", "192.0.2.2", "192.0.2.3"] {
+        for _ in 0..4 {
+            guard.record(ip(client), StatusCode::NOT_FOUND);
+        }
+    }
+    guard.record(ip("192.0.2.4"), StatusCode::NOT_FOUND);
+
+    assert_eq!(guard.clear(Some(ip("192.0.2.1"))), 1);
+    assert_eq!(guard.check(ip("192.0.2.1")), Verdict::Allow);
+    assert_eq!(guard.clear(Some(ip("192.0.2.1"))), 0);
+    // Clients with errors but no penalty are forgotten without being counted
+    assert_eq!(guard.clear(None), 2);
+    assert!(guard.penalized().is_empty());
+}
+
+#[test]
+fn test_penalty_parses_from_config() {
+    assert_eq!("Block".parse::<AbusePenalty>(), Ok(AbusePenalty::Block));
+    assert_eq!(" tarpit ".parse::<AbusePenalty>(), Ok(AbusePenalty::Tarpit));
+    assert!("drop".parse::<AbusePenalty>().is_err());
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpMessage, HttpRequest};
+use futures::future::LocalBoxFuture;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::storage::StorageRef;
+
+pub(crate) mod session;
+
+pub use session::{verify_origin, DashboardSession, SessionConfig, Sessions, CSRF_FIELD, SESSION_COOKIE};
+
+/// Header carrying the API key
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Marks keys issued by the service, so leaked keys are easy to spot
+const ISSUED_KEY_PREFIX: &str = "um_";
+
+/// Random bytes in an issued key
+const ISSUED_KEY_BYTES: usize = 24;
+
+/// Leading characters of an issued key kept in the clear for display
+const DISPLAY_PREFIX_LEN: usize = 11;
+
+/// The set of API keys accepted by the service
+#[derive(Clone, Default)]
+pub struct ApiKeys {
+    /// Maps each configured key to the owner it authenticates
+    keys: HashMap<String, String>,
+    /// Self-service keys, looked up by hash
+    storage: Option<StorageRef>,
+    /// Owners whose keys may call admin endpoints
+    admins: HashSet<String>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: HashMap<String, String>) -> Self {
+        Self { keys, storage: None, admins: HashSet::new() }
+    }
+
+    /// Grants the given owners access to admin endpoints
+    pub fn with_admins<S: AsRef<str>>(mut self, owners: &[S]) -> Self {
+        self.admins = owners.iter().map(|owner| owner.as_ref().to_string()).collect();
+        self
+    }
+
+    pub fn is_admin(&self, owner: &str) -> bool {
+        self.admins.contains(owner)
+    }
+
+    /// Also accepts the self-service keys kept in `storage`
+    pub fn with_storage(mut self, storage: StorageRef) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Returns the owner of the given configured key, if the key is valid
+    pub fn owner_of(&self, key: &str) -> Option<&str> {
+        self.keys.get(key).map(String::as_str)
+    }
+
+    /// Returns the owner of the given configured or self-service key, recording
+    /// when a self-service key was last used
+    pub async fn authenticate(&self, key: &str) -> UrlShortenerResult<Option<String>> {
+        if let Some(owner) = self.owner_of(key) {
+            return Ok(Some(owner.to_string()));
+        }
+
+        let Some(storage) = &self.storage else {
+            return Ok(None);
+        };
+        match storage.authenticate_api_key(&hash_key(key)).await {
+            Ok(stored) => Ok(Some(stored.owner)),
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A newly issued key; the plaintext is never stored
+#[derive(Debug, Clone)]
+pub struct IssuedKey {
+    pub key: String,
+    pub hash: String,
+    pub prefix: String,
+}
+
+/// Generates a random key
+pub fn issue_key() -> IssuedKey {
+    let key = format!("{ISSUED_KEY_PREFIX}{}", random_token(ISSUED_KEY_BYTES));
+
+    IssuedKey {
+        hash: hash_key(&key),
+        prefix: key[..DISPLAY_PREFIX_LEN].to_string(),
+        key,
+    }
+}
+
+/// Hex-encoded random bytes from a cryptographically secure generator
+pub(crate) fn random_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+/// Hashes a key for storage and lookup
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Extractor for an authenticated API key caller.
+///
+/// The key is read from the `X-API-Key` header, falling back to an
+/// `Authorization: Bearer <key>` header.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    /// Owner the key belongs to
+    pub owner: String,
+}
+
+impl ApiKey {
+    /// The key a request carries, from `X-API-Key` or else an `Authorization: Bearer` header
+    pub(crate) fn key_from_request(req: &HttpRequest) -> Option<&str> {
+        req.headers()
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                req.headers()
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+            })
+            .map(str::trim)
+    }
+}
+
+impl FromRequest for ApiKey {
+    type Error = UrlShortenerError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let keys = req.app_data::<web::Data<ApiKeys>>().cloned();
+        let key = Self::key_from_request(req).map(str::to_string);
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            let owner = match keys.zip(key) {
+                Some((keys, key)) => keys.authenticate(&key).await?,
+                None => None,
+            };
+
+            match owner {
+                Some(owner) => Ok(ApiKey { owner }),
+                None => {
+                    warn!(path = %path, "Rejected request with missing or invalid API key");
+                    Err(UrlShortenerErrorType::Unauthorized.into())
+                }
+            }
+        })
+    }
+}
+
+/// Extractor for a caller whose API key belongs to an admin owner
+#[derive(Debug, Clone)]
+pub struct AdminKey {
+    /// Admin the key belongs to
+    pub owner: String,
+}
+
+impl FromRequest for AdminKey {
+    type Error = UrlShortenerError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let keys = req.app_data::<web::Data<ApiKeys>>().cloned();
+        let api_key = ApiKey::from_request(req, payload);
+        let req = req.clone();
+
+        Box::pin(async move {
+            let ApiKey { owner } = api_key.await?;
+            if !keys.is_some_and(|keys| keys.is_admin(&owner)) {
+                warn!(path = %req.path(), owner = %owner, "Rejected admin request from non-admin key");
+                return Err(UrlShortenerErrorType::Forbidden("Admin access required".to_string()).into());
+            }
+            // Kept on the request, so the audit middleware knows who made the call
+            let admin = AdminKey { owner };
+            req.extensions_mut().insert(admin.clone());
+            Ok(admin)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use tracing::{debug, info, warn};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::Session;
+use crate::storage::StorageRef;
+use super::{hash_key, random_token};
+
+/// Cookie carrying the session token
+pub const SESSION_COOKIE: &str = "url_map_session";
+
+/// Form field carrying the CSRF token on state-changing dashboard posts
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// Random bytes in session and CSRF tokens
+const TOKEN_BYTES: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// Sessions unused for this long are signed out
+    pub idle_timeout: Duration,
+    /// Only send the session cookie over HTTPS
+    pub secure_cookie: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(30 * 60),
+            secure_cookie: true,
+        }
+    }
+}
+
+/// Server-side dashboard sessions.
+///
+/// The cookie holds a random token; storage only keeps its hash, so a leaked
+/// sessions table cannot be replayed.
+pub struct Sessions {
+    storage: StorageRef,
+    config: SessionConfig,
+}
+
+impl Sessions {
+    pub fn new(storage: StorageRef, config: SessionConfig) -> Self {
+        Self { storage, config }
+    }
+
+    /// Signs `owner` in, returning the session and the token to put in its cookie
+    pub async fn start(&self, owner: &str) -> UrlShortenerResult<(Session, String)> {
+        // Sweeping on sign-in keeps abandoned sessions from piling up without a background task
+        let purged = self.storage.purge_idle_sessions(self.idle_cutoff()).await?;
+        if purged > 0 {
+            debug!(purged, "Purged idle sessions");
+        }
+
+        let token = random_token(TOKEN_BYTES);
+        let now = Utc::now();
+        let session = Session {
+            id: hash_key(&token),
+            owner: owner.to_string(),
+            csrf_token: random_token(TOKEN_BYTES),
+            created_at: now,
+            last_seen_at: now,
+        };
+        self.storage.create_session(&session).await?;
+        info!(owner, "Started dashboard session");
+        Ok((session, token))
+    }
+
+    /// Looks up the session for a cookie token, unless it has been idle too long
+    pub async fn resume(&self, token: &str) -> UrlShortenerResult<Option<Session>> {
+        match self.storage.touch_session(&hash_key(token), self.idle_cutoff()).await {
+            Ok(session) => Ok(Some(session)),
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Signs a session out
+    pub async fn end(&self, session: &Session) -> UrlShortenerResult<()> {
+        self.storage.delete_session(&session.id).await?;
+        info!(owner = %session.owner, "Ended dashboard session");
+        Ok(())
+    }
+
+    /// Cookie identifying a session; it is not readable from scripts and lasts until the browser closes
+    pub fn cookie(&self, token: &str) -> Cookie<'static> {
+        Cookie::build(SESSION_COOKIE, token.to_string())
+            .path("/")
+            .http_only(true)
+            .secure(self.config.secure_cookie)
+            .same_site(SameSite::Lax)
+            .finish()
+    }
+
+    /// Cookie that clears the session cookie from the browser
+    pub fn removal_cookie(&self) -> Cookie<'static> {
+        let mut cookie = self.cookie("");
+        cookie.make_removal();
+        cookie
+    }
+
+    fn idle_cutoff(&self) -> DateTime<Utc> {
+        let idle_timeout = chrono::Duration::from_std(self.config.idle_timeout).unwrap_or(chrono::Duration::MAX);
+        Utc::now().checked_sub_signed(idle_timeout).unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+/// Rejects form posts sent from another site.
+///
+/// Browsers send `Origin` on cross-site posts; requests without one (e.g. from
+/// non-browser clients) are let through.
+pub fn verify_origin(req: &HttpRequest) -> UrlShortenerResult<()> {
+    let Some(origin) = req.headers().get(header::ORIGIN) else {
+        return Ok(());
+    };
+
+    let host = req.connection_info().host().to_string();
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| url::Url::parse(origin).ok())
+        .and_then(|origin| {
+            let host = origin.host_str()?.to_string();
+            Some(match origin.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host,
+            })
+        });
+
+    if origin_host.as_deref() != Some(host.as_str()) {
+        warn!(path = %req.path(), "Rejected cross-origin form post");
+        return Err(UrlShortenerErrorType::Forbidden("Cross-origin form post".to_string()).into());
+    }
+    Ok(())
+}
+
+/// Extractor for a signed-in dashboard user, identified by the session cookie
+#[derive(Debug, Clone)]
+pub struct DashboardSession {
+    /// Owner the session belongs to
+    pub owner: String,
+    session: Session,
+}
+
+impl DashboardSession {
+    /// Token to embed in the session's forms
+    pub fn csrf_token(&self) -> &str {
+        &self.session.csrf_token
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Checks the CSRF token submitted with a form
+    pub fn verify_csrf(&self, submitted: &str) -> UrlShortenerResult<()> {
+        if !constant_time_eq(submitted.as_bytes(), self.session.csrf_token.as_bytes()) {
+            warn!(owner = %self.owner, "Rejected form post with invalid CSRF token");
+            return Err(UrlShortenerErrorType::Forbidden("Missing or invalid CSRF token".to_string()).into());
+        }
+        Ok(())
+    }
+}
+
+impl FromRequest for DashboardSession {
+    type Error = UrlShortenerError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let sessions = req.app_data::<web::Data<Sessions>>().cloned();
+        let token = req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_string());
+
+        Box::pin(async move {
+            let session = match sessions.zip(token) {
+                Some((sessions, token)) => sessions.resume(&token).await?,
+                None => None,
+            };
+
+            session
+                .map(|session| DashboardSession { owner: session.owner.clone(), session })
+                .ok_or_else(|| UrlShortenerErrorType::Unauthorized.into())
+        })
+    }
+}
+
+/// Compares in time independent of where the inputs first differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+use super::*;
+
+#[test]
+fn test_issued_keys_are_unique_and_stored_by_hash() {
+    let first = issue_key();
+    let second = issue_key();
+
+    assert_ne!(first.key, second.key);
+    assert!(first.key.starts_with(ISSUED_KEY_PREFIX));
+    assert!(first.key.starts_with(&first.prefix));
+    assert_eq!(first.hash, hash_key(&first.key));
+    assert_ne!(first.hash, first.key);
+}
+
+#[actix_rt::test]
+async fn test_stored_keys_authenticate_alongside_configured_ones() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let keys = ApiKeys::new(HashMap::from([("static-key".to_string(), "alice".to_string())]))
+        .with_storage(storage.clone());
+    let issued = issue_key();
+    storage.create_api_key("bob", None, &issued.hash, &issued.prefix).await.unwrap();
+
+    assert_eq!(keys.authenticate("static-key").await.unwrap().as_deref(), Some("alice"));
+    assert_eq!(keys.authenticate(&issued.key).await.unwrap().as_deref(), Some("bob"));
+    assert_eq!(keys.authenticate(&issued.hash).await.unwrap(), None);
+    assert_eq!(ApiKeys::new(HashMap::new()).authenticate(&issued.key).await.unwrap(), None);
+}
+
+#[actix_rt::test]
+async fn test_sessions_expire_when_idle() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let sessions = Sessions::new(storage.clone(), SessionConfig::default());
+    let (session, token) = sessions.start("alice").await.unwrap();
+    assert_ne!(session.id, token);
+
+    let resumed = sessions.resume(&token).await.unwrap().unwrap();
+    assert_eq!((resumed.owner.as_str(), resumed.csrf_token.as_str()), ("alice", session.csrf_token.as_str()));
+    assert!(sessions.resume("unknown").await.unwrap().is_none());
+
+    let idle = Sessions::new(storage, SessionConfig { idle_timeout: Duration::ZERO, ..SessionConfig::default() });
+    assert!(idle.resume(&token).await.unwrap().is_none());
+
+    sessions.end(&session).await.unwrap();
+    assert!(sessions.resume(&token).await.unwrap().is_none());
+}
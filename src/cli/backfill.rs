@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::config::Config;
+use crate::storage::{MigratingStorage, PostgresStorage};
+
+/// Copies what the new database is missing from the one named by `MIGRATE_FROM_DATABASE_URL`
+pub async fn run(config: &Config) -> anyhow::Result<()> {
+    let source = config
+        .to_migration_source_config()
+        .context("MIGRATE_FROM_DATABASE_URL must name the database to copy from")?;
+    let old = Arc::new(PostgresStorage::new(source).await?);
+    let new = Arc::new(PostgresStorage::new(config.to_storage_config()).await?);
+
+    let summary = MigratingStorage::new(old, new).backfill().await?;
+    println!(
+        "Copied {} collections, {} API keys and {} links; skipped {} already present",
+        summary.collections, summary.api_keys, summary.links, summary.skipped
+    );
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::http_client::HttpClient;
+use crate::redirect::StaticLinks;
+use crate::services::{MAX_REGIONS, MAX_WORKERS};
+use crate::storage::{MigrationState, Migrations};
+
+/// Codes generated to check that the generator produces usable, distinct codes
+const GENERATED_CODES: usize = 1_000;
+
+/// Options for the `check` subcommand
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// The outcome of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn from_result(name: &'static str, result: Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self { name, ok: true, detail },
+            Err(detail) => Self { name, ok: false, detail },
+        }
+    }
+}
+
+/// Checks the configuration, the database and its schema, and code generation, then prints a
+/// report. Fails when any check does, so a deploy pipeline can stop on it.
+pub async fn run(args: CheckArgs, config: &Config) -> anyhow::Result<()> {
+    let report = checks(config).await;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for check in &report {
+            println!("{:<4}  {:<10}  {}", if check.ok { "ok" } else { "FAIL" }, check.name, check.detail);
+        }
+    }
+
+    let failed = report.iter().filter(|check| !check.ok).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, report.len());
+    }
+    Ok(())
+}
+
+/// Runs every check; the schema is only checked once the database could be reached
+pub async fn checks(config: &Config) -> Vec<CheckResult> {
+    let mut report = vec![
+        CheckResult::from_result("config", check_config(config)),
+        CheckResult::from_result("codes", check_code_generation(config)),
+    ];
+
+    match Migrations::connect(&config.to_storage_config()).await {
+        Ok(migrations) => {
+            report.push(CheckResult::from_result("storage", Ok("connected to DATABASE_URL".to_string())));
+            report.push(CheckResult::from_result("migrations", check_migrations(&migrations).await));
+        }
+        Err(e) => report.push(CheckResult::from_result("storage", Err(e.to_string()))),
+    }
+    report
+}
+
+/// Settings the server would refuse or panic on at startup
+pub fn check_config(config: &Config) -> Result<String, String> {
+    let mut problems = Vec::new();
+    if let Err(e) = url::Url::parse(&config.public_base_url) {
+        problems.push(format!("PUBLIC_BASE_URL is not a URL: {}", e));
+    }
+    if let Err(e) = config.url_cipher() {
+        problems.push(format!("URL encryption key: {}", e));
+    }
+    if let Err(e) = HttpClient::new(config.outbound_config()) {
+        problems.push(format!("outbound HTTP: {}", e));
+    }
+    if let Some(problem) = snowflake_range_problem(config) {
+        problems.push(problem);
+    }
+    if let Err(e) = StaticLinks::load(config.static_links_file.as_deref()) {
+        problems.push(format!("static links: {}", e));
+    }
+
+    if problems.is_empty() {
+        Ok("valid".to_string())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+/// Generates a batch of codes and checks they are non-empty, fit in a path segment and differ
+pub fn check_code_generation(config: &Config) -> Result<String, String> {
+    // The generator panics on an out-of-range region or worker
+    if let Some(problem) = snowflake_range_problem(config) {
+        return Err(format!("skipped, as {}", problem));
+    }
+    let generator = config.code_generator();
+    let mut seen = HashSet::with_capacity(GENERATED_CODES);
+    for _ in 0..GENERATED_CODES {
+        let code = generator.generate();
+        if code.is_empty() || code.contains(['/', '?', '#']) {
+            return Err(format!("generated an unusable code {:?}", code));
+        }
+        if !seen.insert(code.clone()) {
+            return Err(format!("generated {} twice in {} codes", code, GENERATED_CODES));
+        }
+    }
+    Ok(format!("{} distinct codes generated", GENERATED_CODES))
+}
+
+/// Snowflake codes need a region and worker within the bits they are given
+fn snowflake_range_problem(config: &Config) -> Option<String> {
+    match config.code_region {
+        Some(region) if region >= MAX_REGIONS => Some(format!("CODE_REGION must be below {}", MAX_REGIONS)),
+        Some(_) if config.code_worker >= MAX_WORKERS => Some(format!("CODE_WORKER must be below {}", MAX_WORKERS)),
+        _ => None,
+    }
+}
+
+async fn check_migrations(migrations: &Migrations) -> Result<String, String> {
+    let status = migrations.status().await.map_err(|e| e.to_string())?;
+    let behind: Vec<String> = status
+        .iter()
+        .filter_map(|migration| match migration.state {
+            MigrationState::Applied => None,
+            MigrationState::Pending => Some(format!("{} pending", migration.version)),
+            MigrationState::ChecksumMismatch => Some(format!("{} checksum mismatch", migration.version)),
+        })
+        .collect();
+
+    if behind.is_empty() {
+        Ok(format!("all {} applied", status.len()))
+    } else {
+        Err(behind.join(", "))
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Context};
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+use crate::auth::API_KEY_HEADER;
+use crate::errors::ApiResponse;
+use crate::handlers::{CreateUrlRequest, CreateUrlResponse, UrlStats};
+use crate::services::CodeStyle;
+
+/// HTTP client for a running url-map instance
+pub struct ApiClient {
+    base_url: String,
+    api_key: Option<String>,
+    http: reqwest::Client,
+}
+
+impl ApiClient {
+    pub fn new(base_url: String, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Builds the fully-qualified short link for a short code
+    pub fn short_link(&self, short_code: &str) -> String {
+        format!("{}/{}", self.base_url, short_code)
+    }
+
+    pub async fn shorten(
+        &self,
+        original_url: &str,
+        custom_alias: Option<String>,
+        style: Option<CodeStyle>,
+    ) -> anyhow::Result<CreateUrlResponse> {
+        let request = self
+            .http
+            .post(format!("{}/api/shorten", self.base_url))
+            .json(&CreateUrlRequest {
+                original_url: original_url.to_string(),
+                custom_alias,
+                style,
+                title: None,
+                notes: None,
+                created_by: None,
+                collection_id: None,
+                pass_through: false,
+                signed_only: false,
+                tracking_snippet: None,
+                redirect_headers: None,
+                hide_referrer: false,
+                namespace: None,
+            });
+        self.send(request).await
+    }
+
+    pub async fn stats(&self, short_code: &str) -> anyhow::Result<UrlStats> {
+        let request = self
+            .http
+            .get(format!("{}/api/stats/{}", self.base_url, short_code));
+        self.send(request).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> anyhow::Result<T> {
+        let request = match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        };
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", self.base_url))?;
+        Self::parse(response).await
+    }
+
+    /// Unwraps the `data` of a successful response, or turns the error envelope into an error
+    async fn parse<T: DeserializeOwned>(response: Response) -> anyhow::Result<T> {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+
+        match serde_json::from_str::<ApiResponse<T>>(&body) {
+            Ok(ApiResponse { data: Some(data), .. }) if status.is_success() => Ok(data),
+            Ok(ApiResponse { error: Some(error), .. }) => Err(anyhow!(
+                "Request failed with status {}: {} ({})",
+                status,
+                error.message,
+                error.code
+            )),
+            _ if status.is_success() => Err(anyhow!("Invalid response body: {}", body)),
+            _ => Err(anyhow!("Request failed with status {}: {}", status, body)),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Args;
+
+use crate::config::Config;
+use crate::dump;
+use crate::storage::PostgresStorage;
+
+/// Options for the `export` subcommand
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// File to write the dump to; it is replaced if it exists
+    #[arg(long, short)]
+    pub output: PathBuf,
+}
+
+/// Writes every link, collection and active API key to an NDJSON dump
+pub async fn run(args: ExportArgs, config: &Config) -> anyhow::Result<()> {
+    let file = File::create(&args.output).with_context(|| format!("Cannot create {}", args.output.display()))?;
+    let storage = PostgresStorage::new(config.to_storage_config()).await?;
+    let summary = dump::export(&storage, BufWriter::new(file)).await?;
+
+    println!(
+        "Exported {} collections, {} API keys and {} links to {}",
+        summary.collections,
+        summary.api_keys,
+        summary.links,
+        args.output.display()
+    );
+    Ok(())
+}
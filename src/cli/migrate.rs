@@ -0,0 +1,52 @@
+use clap::Subcommand;
+
+use crate::config::Config;
+use crate::storage::{MigrationState, Migrations};
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the most recently applied migration
+    Down,
+    /// List migrations and whether they are applied
+    Status,
+}
+
+pub async fn run(action: MigrateAction, config: &Config) -> anyhow::Result<()> {
+    let migrations = Migrations::connect(&config.to_storage_config()).await?;
+
+    match action {
+        MigrateAction::Up => {
+            migrations.up().await?;
+            println!("Migrations are up to date");
+        }
+        MigrateAction::Down => match migrations.down().await? {
+            Some(version) => println!("Reverted migration {}", version),
+            None => println!("No applied migrations to revert"),
+        },
+        MigrateAction::Status => {
+            let mut pending = 0;
+            for migration in migrations.status().await? {
+                let state = match migration.state {
+                    MigrationState::Applied => "applied",
+                    MigrationState::Pending => {
+                        pending += 1;
+                        "pending"
+                    }
+                    MigrationState::ChecksumMismatch => "checksum mismatch",
+                };
+                println!(
+                    "{}  {:<18} {}{}",
+                    migration.version,
+                    state,
+                    migration.description,
+                    if migration.reversible { "" } else { " (irreversible)" }
+                );
+            }
+            println!("{} pending", pending);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,137 @@
+mod backfill;
+mod check;
+mod client;
+mod export;
+mod import;
+mod migrate;
+mod seed;
+
+pub use check::{CheckArgs, CheckResult};
+pub use client::ApiClient;
+pub use export::ExportArgs;
+pub use import::ImportArgs;
+pub use migrate::MigrateAction;
+pub use seed::SeedArgs;
+
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::Config;
+use crate::services::CodeStyle;
+use crate::storage::{EncryptedStorage, PostgresStorage, StorageRef};
+
+/// Command line interface for the URL shortener
+#[derive(Debug, Parser)]
+#[command(name = "url-map", version, about = "A URL shortening service")]
+pub struct Cli {
+    /// Subcommand to run; starts the server when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server
+    Serve,
+    /// Shorten a URL using a running instance
+    Shorten {
+        /// The URL to shorten
+        url: String,
+        /// Custom alias to use instead of a generated code
+        #[arg(long)]
+        alias: Option<String>,
+        /// Generate a memorable word code like `blue-tiger-42`
+        #[arg(long, conflicts_with = "alias")]
+        words: bool,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// Show visit statistics for a short code from a running instance
+    Stats {
+        /// The short code to look up
+        short_code: String,
+        #[command(flatten)]
+        client: ClientArgs,
+    },
+    /// Manage database migrations explicitly (pair with RUN_MIGRATIONS=false)
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Bulk-generate links directly in storage for load testing
+    Seed(SeedArgs),
+    /// Import links from another shortener's export directly into storage
+    Import(ImportArgs),
+    /// Dump links, collections and API keys to NDJSON, for `import --format ndjson` elsewhere
+    Export(ExportArgs),
+    /// Copy links, collections and API keys from MIGRATE_FROM_DATABASE_URL into DATABASE_URL
+    Backfill,
+    /// Check the configuration, database, schema and code generation before a deploy; exits
+    /// non-zero when any check fails
+    Check(CheckArgs),
+}
+
+/// Connection settings for client subcommands
+#[derive(Debug, Args)]
+pub struct ClientArgs {
+    /// Base URL of the running instance [default: PUBLIC_BASE_URL]
+    #[arg(long, env = "URL_MAP_BASE_URL")]
+    pub base_url: Option<String>,
+    /// API key sent with every request
+    #[arg(long, env = "URL_MAP_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+}
+
+impl ClientArgs {
+    /// Builds an API client, falling back to the service configuration for the base URL
+    pub fn into_client(self, config: &Config) -> ApiClient {
+        ApiClient::new(
+            self.base_url.unwrap_or_else(|| config.public_base_url.clone()),
+            self.api_key,
+        )
+    }
+}
+
+/// Connects to the configured database, encrypting destinations written through it when a
+/// key is configured
+async fn connect_storage(config: &Config) -> anyhow::Result<StorageRef> {
+    let storage: StorageRef = Arc::new(PostgresStorage::new(config.to_storage_config()).await?);
+    Ok(match config.url_cipher()? {
+        Some(cipher) => Arc::new(EncryptedStorage::new(storage, cipher)),
+        None => storage,
+    })
+}
+
+/// Runs a non-server subcommand, printing the result to stdout
+pub async fn run(command: Command, config: &Config) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Shorten { url, alias, words, client } => {
+            let client = client.into_client(config);
+            let style = words.then_some(CodeStyle::Words);
+            let created = client.shorten(&url, alias, style).await?;
+            for warning in &created.warnings {
+                eprintln!("warning: {}", warning.message);
+            }
+            println!("{}", client.short_link(&created.short_url));
+        }
+        Command::Stats { short_code, client } => {
+            let stats = client.into_client(config).stats(&short_code).await?;
+            println!("short_url:    {}", stats.short_url);
+            println!("original_url: {}", stats.original_url);
+            println!("visits:       {}", stats.visits);
+            println!("created_at:   {}", stats.created_at);
+        }
+        Command::Migrate { action } => migrate::run(action, config).await?,
+        Command::Seed(args) => seed::run(args, config).await?,
+        Command::Import(args) => import::run(args, config).await?,
+        Command::Export(args) => export::run(args, config).await?,
+        Command::Backfill => backfill::run(config).await?,
+        Command::Check(args) => check::run(args, config).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
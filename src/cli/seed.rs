@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::connect_storage;
+use crate::config::Config;
+use crate::models::{first_version, ShortenedUrl};
+use crate::services::SHORT_CODE_LENGTH;
+
+/// Shape parameter of the Pareto distribution used for visit counts; a few links get most traffic
+const VISITS_PARETO_ALPHA: f64 = 1.5;
+
+const DOMAINS: &[&str] = &[
+    "example.com",
+    "news.example.org",
+    "shop.example.net",
+    "blog.example.io",
+    "docs.example.dev",
+    "video.example.tv",
+    "github.com",
+    "en.wikipedia.org",
+];
+
+const PATH_SEGMENTS: &[&str] = &[
+    "articles", "products", "2024", "2025", "guides", "release-notes", "category", "item",
+    "summer-sale", "how-to", "watch", "users", "settings", "reports",
+];
+
+const QUERY_PARAMS: &[&str] = &["utm_source", "utm_campaign", "ref", "id", "page", "lang"];
+
+/// Options for the `seed` subcommand
+#[derive(Debug, Args)]
+pub struct SeedArgs {
+    /// Number of links to generate
+    #[arg(long, default_value_t = 1_000)]
+    pub links: u64,
+    /// Average visit count per link (heavy-tailed around this mean)
+    #[arg(long, default_value_t = 100)]
+    pub visits_per_link: u64,
+    /// Spread creation dates over this many days before now
+    #[arg(long, default_value_t = 365)]
+    pub days: i64,
+    /// Links written per storage call
+    #[arg(long, default_value_t = 10_000)]
+    pub batch_size: u64,
+    /// Random seed; the same seed produces the same data set
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+    /// Owner recorded on every generated link
+    #[arg(long)]
+    pub owner: Option<String>,
+}
+
+/// Generates realistic-looking links for performance testing
+pub struct SeedGenerator {
+    rng: StdRng,
+    visits_per_link: u64,
+    days: i64,
+    owner: Option<String>,
+    now: DateTime<Utc>,
+}
+
+impl SeedGenerator {
+    pub fn new(args: &SeedArgs) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(args.seed),
+            visits_per_link: args.visits_per_link,
+            days: args.days.max(1),
+            owner: args.owner.clone(),
+            now: Utc::now(),
+        }
+    }
+
+    pub fn batch(&mut self, size: usize) -> Vec<ShortenedUrl> {
+        (0..size).map(|_| self.link()).collect()
+    }
+
+    fn link(&mut self) -> ShortenedUrl {
+        let seconds_ago = self.rng.gen_range(0..self.days * 24 * 60 * 60);
+
+        ShortenedUrl {
+            id: 0,
+            original_url: self.original_url(),
+            short_url: self.short_code(),
+            created_at: self.now - Duration::seconds(seconds_ago),
+            visits: self.visits(),
+            last_visited_at: None,
+            owner: self.owner.clone(),
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            click_goal: None,
+            goal_reached_at: None,
+            pass_through: false,
+            content_type: None,
+            disabled_at: None,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            version: first_version(),
+        }
+    }
+
+    fn short_code(&mut self) -> String {
+        let alphabet = &nanoid::alphabet::SAFE;
+        (0..SHORT_CODE_LENGTH)
+            .map(|_| alphabet[self.rng.gen_range(0..alphabet.len())])
+            .collect()
+    }
+
+    fn original_url(&mut self) -> String {
+        let domain = DOMAINS[self.rng.gen_range(0..DOMAINS.len())];
+        let mut url = format!("https://{}", domain);
+
+        for _ in 0..self.rng.gen_range(1..=4) {
+            url.push('/');
+            url.push_str(PATH_SEGMENTS[self.rng.gen_range(0..PATH_SEGMENTS.len())]);
+        }
+        url.push_str(&format!("/{}", self.rng.gen_range(1..100_000)));
+
+        if self.rng.gen_bool(0.4) {
+            let params = self.rng.gen_range(1..=3);
+            for i in 0..params {
+                let key = QUERY_PARAMS[self.rng.gen_range(0..QUERY_PARAMS.len())];
+                url.push(if i == 0 { '?' } else { '&' });
+                url.push_str(&format!("{}={}", key, self.rng.gen_range(1..1_000)));
+            }
+        }
+
+        url
+    }
+
+    /// Draws a Pareto-distributed visit count whose mean is `visits_per_link`
+    fn visits(&mut self) -> i64 {
+        if self.visits_per_link == 0 {
+            return 0;
+        }
+        let mean = self.visits_per_link as f64;
+        let scale = mean * (VISITS_PARETO_ALPHA - 1.0) / VISITS_PARETO_ALPHA;
+        let u: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let visits = scale / u.powf(1.0 / VISITS_PARETO_ALPHA);
+        visits.min(mean * 1_000.0).round() as i64
+    }
+}
+
+/// Writes generated links to the configured storage in batches
+pub async fn run(args: SeedArgs, config: &Config) -> anyhow::Result<()> {
+    let storage = connect_storage(config).await?;
+    let mut generator = SeedGenerator::new(&args);
+    let batch_size = args.batch_size.max(1);
+
+    let mut written = 0;
+    while written < args.links {
+        let size = batch_size.min(args.links - written);
+        written += storage.bulk_insert(generator.batch(size as usize)).await?;
+        println!("Seeded {}/{} links", written, args.links);
+    }
+
+    Ok(())
+}
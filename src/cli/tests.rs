@@ -0,0 +1,165 @@
+use clap::{CommandFactory, Parser};
+use super::*;
+
+#[test]
+fn test_cli_definition_is_valid() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn test_no_subcommand_defaults_to_server() {
+    let cli = Cli::try_parse_from(["url-map"]).unwrap();
+    assert!(cli.command.is_none());
+}
+
+#[test]
+fn test_parse_shorten() {
+    let cli = Cli::try_parse_from([
+        "url-map",
+        "shorten",
+        "https://example.com",
+        "--base-url",
+        "http://short.example",
+        "--api-key",
+        "secret",
+    ])
+    .unwrap();
+
+    match cli.command {
+        Some(Command::Shorten { url, client, .. }) => {
+            assert_eq!(url, "https://example.com");
+            assert_eq!(client.base_url.as_deref(), Some("http://short.example"));
+            assert_eq!(client.api_key.as_deref(), Some("secret"));
+        }
+        other => panic!("Expected shorten command, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_client_falls_back_to_public_base_url() {
+    let args = ClientArgs {
+        base_url: None,
+        api_key: None,
+    };
+    let client = args.into_client(&Config::default());
+    assert_eq!(client.short_link("abc"), "http://localhost:8080/abc");
+}
+
+#[test]
+fn test_parse_migrate_actions() {
+    let cli = Cli::try_parse_from(["url-map", "migrate", "down"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Migrate { action: MigrateAction::Down })
+    ));
+
+    assert!(Cli::try_parse_from(["url-map", "migrate", "sideways"]).is_err());
+}
+
+fn seed_args(seed: u64) -> SeedArgs {
+    SeedArgs {
+        links: 100,
+        visits_per_link: 100,
+        days: 30,
+        batch_size: 10,
+        seed,
+        owner: Some("loadtest".to_string()),
+    }
+}
+
+#[test]
+fn test_seed_generator_is_reproducible() {
+    let first = seed::SeedGenerator::new(&seed_args(7)).batch(20);
+    let second = seed::SeedGenerator::new(&seed_args(7)).batch(20);
+    let other = seed::SeedGenerator::new(&seed_args(8)).batch(20);
+
+    let codes = |urls: &[crate::models::ShortenedUrl]| {
+        urls.iter().map(|u| u.short_url.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(codes(&first), codes(&second));
+    assert_ne!(codes(&first), codes(&other));
+}
+
+#[test]
+fn test_seed_generator_produces_valid_links() {
+    let links = seed::SeedGenerator::new(&seed_args(1)).batch(1_000);
+    let oldest_allowed = chrono::Utc::now() - chrono::Duration::days(30);
+
+    for link in &links {
+        assert_eq!(link.short_url.len(), 10);
+        assert!(url::Url::parse(&link.original_url).is_ok());
+        assert!(link.visits >= 0);
+        assert!(link.created_at >= oldest_allowed);
+        assert_eq!(link.owner.as_deref(), Some("loadtest"));
+    }
+
+    // Heavy-tailed, but the sample mean stays in the right ballpark
+    let mean = links.iter().map(|l| l.visits).sum::<i64>() as f64 / links.len() as f64;
+    assert!(mean > 30.0 && mean < 1_000.0, "unexpected mean {}", mean);
+}
+
+#[test]
+fn test_parse_import() {
+    let cli = Cli::try_parse_from(["url-map", "import", "--format", "bitly", "links.csv", "--owner", "alice"]).unwrap();
+    match cli.command {
+        Some(Command::Import(args)) => {
+            assert_eq!(args.format, crate::import::ImportFormat::Bitly);
+            assert_eq!(args.file.to_str(), Some("links.csv"));
+            assert_eq!(args.owner.as_deref(), Some("alice"));
+        }
+        other => panic!("Expected import command, got {:?}", other),
+    }
+
+    assert!(Cli::try_parse_from(["url-map", "import", "--format", "rebrandly", "links.csv"]).is_err());
+}
+
+#[test]
+fn test_parse_export() {
+    let cli = Cli::try_parse_from(["url-map", "export", "--output", "dump.ndjson"]).unwrap();
+    match cli.command {
+        Some(Command::Export(args)) => assert_eq!(args.output.to_str(), Some("dump.ndjson")),
+        other => panic!("Expected export command, got {:?}", other),
+    }
+
+    assert!(Cli::try_parse_from(["url-map", "export"]).is_err());
+}
+
+#[test]
+fn test_parse_backfill() {
+    let cli = Cli::try_parse_from(["url-map", "backfill"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Backfill)));
+}
+
+#[test]
+fn test_parse_check() {
+    let cli = Cli::try_parse_from(["url-map", "check", "--json"]).unwrap();
+    match cli.command {
+        Some(Command::Check(args)) => assert!(args.json),
+        other => panic!("Expected check command, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_check_config_reports_each_problem() {
+    assert!(check::check_config(&Config::default()).is_ok());
+
+    let config = Config {
+        public_base_url: "not a url".to_string(),
+        code_region: Some(crate::services::MAX_REGIONS),
+        static_links_file: Some("/nonexistent/links.toml".to_string()),
+        ..Config::default()
+    };
+    let problems = check::check_config(&config).unwrap_err();
+    assert!(problems.contains("PUBLIC_BASE_URL"), "{}", problems);
+    assert!(problems.contains("CODE_REGION"), "{}", problems);
+    assert!(problems.contains("static links"), "{}", problems);
+}
+
+#[test]
+fn test_check_code_generation() {
+    assert!(check::check_code_generation(&Config::default()).is_ok());
+    assert!(check::check_code_generation(&Config { code_region: Some(1), ..Config::default() }).is_ok());
+
+    let config = Config { code_region: Some(1), code_worker: crate::services::MAX_WORKERS, ..Config::default() };
+    assert!(check::check_code_generation(&config).unwrap_err().contains("CODE_WORKER"));
+}
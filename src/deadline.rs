@@ -0,0 +1,68 @@
+//! Per-request deadlines.
+//!
+//! A request's deadline comes from the time budget of its route class, or from a shorter
+//! `X-Request-Timeout` sent by the client. It is kept for the request's task, and storage
+//! operations run under it, so a slow query is cancelled once the client has given up
+//! instead of holding a connection for nobody.
+
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::HttpRequest;
+use tokio::time::Instant;
+
+use crate::throttle::RouteClass;
+
+/// Request header with the time the client is willing to wait, in milliseconds
+pub const REQUEST_TIMEOUT_HEADER: &str = "X-Request-Timeout";
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs a request future with its deadline available to storage operations
+pub async fn with_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Deadline of the request being handled, if it has one
+pub fn current_deadline() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Time budget of each route class; `None` leaves requests of a class without a deadline
+/// unless the client sends one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeadlineConfig {
+    pub redirect: Option<Duration>,
+    pub read_api: Option<Duration>,
+    pub write_api: Option<Duration>,
+    pub admin: Option<Duration>,
+}
+
+impl DeadlineConfig {
+    pub fn budget(&self, class: RouteClass) -> Option<Duration> {
+        match class {
+            RouteClass::Redirect => self.redirect,
+            RouteClass::ReadApi => self.read_api,
+            RouteClass::WriteApi => self.write_api,
+            RouteClass::Admin => self.admin,
+        }
+    }
+
+    /// Time a request may take: the shorter of its class's budget and the client's timeout.
+    /// Health checks never get one, as they time their own checks.
+    pub fn time_allowed(&self, req: &HttpRequest) -> Option<Duration> {
+        let class = RouteClass::of(req.method(), req.path())?;
+        let requested = req
+            .headers()
+            .get(REQUEST_TIMEOUT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_millis);
+        match (self.budget(class), requested) {
+            (Some(budget), Some(requested)) => Some(budget.min(requested)),
+            (budget, requested) => budget.or(requested),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Whole-instance dumps for moving between storage backends and restoring from backups.
+//!
+//! A dump is newline-delimited JSON: a header line followed by one record per line, so
+//! large instances can be written and read without holding everything in memory.
+//! Records refer to collections by their ID in the source instance; restore maps those to
+//! the IDs the target assigns. Configuration lives in the environment and is not dumped.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{Collection, ExportedApiKey, ShortenedUrl};
+use crate::storage::Storage;
+
+/// Format version written to the header; restore refuses other versions
+pub const DUMP_VERSION: u32 = 1;
+
+/// Links read from storage, and written to it, per round trip
+const BATCH_SIZE: usize = 1000;
+
+/// One line of a dump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DumpRecord {
+    /// First line of every dump
+    Header {
+        version: u32,
+        exported_at: DateTime<Utc>,
+    },
+    Collection(Collection),
+    ApiKey(ExportedApiKey),
+    Link(Box<ShortenedUrl>),
+}
+
+/// Record counts of an export or restore
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DumpSummary {
+    pub collections: u64,
+    pub api_keys: u64,
+    pub links: u64,
+    /// Records left out of a restore because the target already had them
+    pub skipped: u64,
+}
+
+/// Writes every collection, active API key and link in `storage` to `writer`
+pub async fn export<S: Storage + ?Sized>(storage: &S, mut writer: impl Write) -> UrlShortenerResult<DumpSummary> {
+    let mut summary = DumpSummary::default();
+    write_record(&mut writer, &DumpRecord::Header { version: DUMP_VERSION, exported_at: Utc::now() })?;
+
+    for collection in storage.export_collections().await? {
+        write_record(&mut writer, &DumpRecord::Collection(collection))?;
+        summary.collections += 1;
+    }
+    for key in storage.export_api_keys().await? {
+        write_record(&mut writer, &DumpRecord::ApiKey(key))?;
+        summary.api_keys += 1;
+    }
+
+    let mut after: Option<String> = None;
+    loop {
+        let page = storage.export_urls(after.as_deref(), BATCH_SIZE as i64).await?;
+        let Some(last) = page.last() else { break };
+        after = Some(last.short_url.clone());
+        for url in page {
+            write_record(&mut writer, &DumpRecord::Link(Box::new(url)))?;
+            summary.links += 1;
+        }
+    }
+
+    writer.flush().map_err(dump_io_error)?;
+    Ok(summary)
+}
+
+/// Loads a dump written by [`export`] into `storage`.
+///
+/// Records the target already has (collections by owner and name, keys by hash, links by
+/// short code) are kept as they are, so an interrupted restore can be run again.
+pub async fn restore<S: Storage + ?Sized>(storage: &S, reader: impl BufRead) -> UrlShortenerResult<DumpSummary> {
+    let mut summary = DumpSummary::default();
+    let mut collection_ids: HashMap<i64, i64> = HashMap::new();
+    let mut links: Vec<ShortenedUrl> = Vec::new();
+    let mut header_seen = false;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(dump_io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: DumpRecord = serde_json::from_str(&line).map_err(|e| {
+            let message = format!("Unreadable dump record on line {}: {}", line_number, e);
+            UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput(message), e)
+        })?;
+
+        match record {
+            DumpRecord::Header { version, .. } => {
+                if header_seen {
+                    return Err(invalid_dump(format!("Unexpected header on line {}", line_number)));
+                }
+                if version != DUMP_VERSION {
+                    return Err(invalid_dump(format!("Unsupported dump version {}", version)));
+                }
+                header_seen = true;
+            }
+            _ if !header_seen => return Err(invalid_dump("Dump does not start with a header".to_string())),
+            DumpRecord::Collection(collection) => {
+                let (restored, created) = storage.restore_collection(&collection).await?;
+                if created {
+                    summary.collections += 1;
+                } else {
+                    summary.skipped += 1;
+                }
+                collection_ids.insert(collection.id, restored.id);
+            }
+            DumpRecord::ApiKey(key) => {
+                if storage.restore_api_key(&key).await? {
+                    summary.api_keys += 1;
+                } else {
+                    summary.skipped += 1;
+                }
+            }
+            DumpRecord::Link(mut url) => {
+                // Links may only point at collections restored from the same dump
+                url.collection_id = url.collection_id.and_then(|id| collection_ids.get(&id).copied());
+                links.push(*url);
+                if links.len() >= BATCH_SIZE {
+                    restore_links(storage, std::mem::take(&mut links), &mut summary).await?;
+                }
+            }
+        }
+    }
+
+    if !header_seen {
+        return Err(invalid_dump("Dump is empty".to_string()));
+    }
+    restore_links(storage, links, &mut summary).await?;
+    Ok(summary)
+}
+
+async fn restore_links<S: Storage + ?Sized>(
+    storage: &S,
+    links: Vec<ShortenedUrl>,
+    summary: &mut DumpSummary,
+) -> UrlShortenerResult<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    let total = links.len() as u64;
+    let inserted = storage.import_urls(links).await?;
+    summary.links += inserted;
+    summary.skipped += total - inserted;
+    Ok(())
+}
+
+fn write_record(writer: &mut impl Write, record: &DumpRecord) -> UrlShortenerResult<()> {
+    serde_json::to_writer(&mut *writer, record).map_err(|e| {
+        UrlShortenerError::with_source(UrlShortenerErrorType::InternalError("Failed to encode dump record".to_string()), e)
+    })?;
+    writer.write_all(b"\n").map_err(dump_io_error)
+}
+
+fn dump_io_error(err: std::io::Error) -> UrlShortenerError {
+    let message = format!("Dump I/O failed: {}", err);
+    UrlShortenerError::with_source(UrlShortenerErrorType::InternalError(message), err)
+}
+
+fn invalid_dump(message: String) -> UrlShortenerError {
+    UrlShortenerErrorType::InvalidInput(message).into()
+}
+
+#[cfg(test)]
+mod tests;
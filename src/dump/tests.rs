@@ -0,0 +1,104 @@
+use chrono::TimeZone;
+
+use super::*;
+use crate::storage::{MemoryStorage, StorageConfig};
+
+fn link(short_url: &str, collection_id: Option<i64>) -> ShortenedUrl {
+    ShortenedUrl {
+        original_url: format!("https://example.com/{}", short_url),
+        short_url: short_url.to_string(),
+        created_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        visits: 42,
+        owner: Some("alice".to_string()),
+        title: Some("Launch".to_string()),
+        notes: Some("Used in the newsletter".to_string()),
+        created_by: Some("marketing".to_string()),
+        collection_id,
+        click_goal: Some(100),
+        ..ShortenedUrl::default()
+    }
+}
+
+async fn populated_storage() -> MemoryStorage {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    // An unrelated collection first, so IDs differ between source and target
+    storage.create_collection("bob", "Misc").await.unwrap();
+    let campaign = storage.create_collection("alice", "Campaign").await.unwrap();
+    storage.create_api_key("alice", Some("laptop"), "hash-1", "um_1234abcd").await.unwrap();
+    let revoked = storage.create_api_key("alice", None, "hash-2", "um_5678efgh").await.unwrap();
+    storage.revoke_api_key(revoked.id).await.unwrap();
+    storage.import_urls(vec![link("b-link", Some(campaign.id)), link("a-link", None)]).await.unwrap();
+    storage
+}
+
+async fn export_to_vec(storage: &MemoryStorage) -> (Vec<u8>, DumpSummary) {
+    let mut dump = Vec::new();
+    let summary = export(storage, &mut dump).await.unwrap();
+    (dump, summary)
+}
+
+#[tokio::test]
+async fn test_export_writes_header_then_records() {
+    let (dump, summary) = export_to_vec(&populated_storage().await).await;
+    let records: Vec<DumpRecord> = dump
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+
+    assert_eq!(summary, DumpSummary { collections: 2, api_keys: 1, links: 2, skipped: 0 });
+    assert!(matches!(records[0], DumpRecord::Header { version: DUMP_VERSION, .. }));
+    assert_eq!(records.len(), 6);
+    match &records[4] {
+        DumpRecord::Link(url) => assert_eq!(url.short_url, "a-link"),
+        other => panic!("Expected a link, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_restore_round_trip() {
+    let (dump, _) = export_to_vec(&populated_storage().await).await;
+
+    let target = MemoryStorage::new(StorageConfig::default());
+    let summary = restore(&target, dump.as_slice()).await.unwrap();
+    assert_eq!(summary, DumpSummary { collections: 2, api_keys: 1, links: 2, skipped: 0 });
+
+    let restored = target.get_stats("b-link").await.unwrap();
+    let collections = target.list_collections("alice").await.unwrap();
+    assert_eq!(restored.collection_id, Some(collections[0].id));
+    assert_eq!(restored.notes.as_deref(), Some("Used in the newsletter"));
+    assert_eq!((restored.visits, restored.click_goal), (42, Some(100)));
+
+    let key = target.authenticate_api_key("hash-1").await.unwrap();
+    assert_eq!((key.owner.as_str(), key.name.as_deref()), ("alice", Some("laptop")));
+    assert!(target.authenticate_api_key("hash-2").await.is_err());
+}
+
+#[tokio::test]
+async fn test_restore_twice_skips_existing_records() {
+    let (dump, _) = export_to_vec(&populated_storage().await).await;
+    let target = MemoryStorage::new(StorageConfig::default());
+    restore(&target, dump.as_slice()).await.unwrap();
+
+    let summary = restore(&target, dump.as_slice()).await.unwrap();
+    assert_eq!(summary, DumpSummary { collections: 0, api_keys: 0, links: 0, skipped: 5 });
+    assert_eq!(target.list_collections("alice").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_restore_rejects_invalid_dumps() {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    let link = serde_json::to_string(&DumpRecord::Link(Box::new(link("abc", None)))).unwrap();
+
+    for dump in [
+        String::new(),
+        link.clone(),
+        r#"{"type":"header","version":99,"exported_at":"2024-05-01T12:00:00Z"}"#.to_string(),
+        concat!(r#"{"type":"header","version":1,"exported_at":"2024-05-01T12:00:00Z"}"#, "\n", r#"{"type":"link"}"#)
+            .to_string(),
+    ] {
+        let err = restore(&storage, dump.as_bytes()).await.unwrap_err();
+        assert!(matches!(err.error_type, UrlShortenerErrorType::InvalidInput(_)), "{:?}", dump);
+    }
+    assert!(storage.get_stats("abc").await.is_err());
+}
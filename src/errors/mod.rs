@@ -1,7 +1,10 @@
 use std::fmt;
-use actix_web::http::StatusCode;
+use actix_web::http::{header, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::backtrace::Backtrace;
+use std::time::Duration;
+use crate::i18n;
+use crate::validation::FieldError;
 
 /// Result type alias for URL Shortener operations
 pub type UrlShortenerResult<T> = Result<T, UrlShortenerError>;
@@ -18,10 +21,22 @@ pub enum UrlShortenerErrorType {
     #[serde(rename = "url_too_long")]
     UrlTooLong(String),
     
+    /// URL uses a scheme that runs script or embeds content, like `javascript:` or `data:`
+    #[serde(rename = "blocked_scheme")]
+    BlockedScheme(String),
+    
     /// Resource not found
     #[serde(rename = "not_found")]
     NotFound,
     
+    /// The short link exists but was disabled by an operator
+    #[serde(rename = "link_disabled")]
+    LinkDisabled,
+    
+    /// The short link only redirects with a valid, unexpired signature
+    #[serde(rename = "invalid_signature")]
+    InvalidSignature(String),
+    
     /// Database errors
     #[serde(rename = "database_error")]
     DatabaseError(String),
@@ -34,10 +49,54 @@ pub enum UrlShortenerErrorType {
     #[serde(rename = "invalid_input")]
     InvalidInput(String),
     
+    /// Request fields failed validation
+    #[serde(rename = "validation_failed")]
+    ValidationFailed(Vec<FieldError>),
+    
+    /// Resource already exists (e.g. a taken short code)
+    #[serde(rename = "conflict")]
+    Conflict(String),
+    
+    /// A custom alias is in use; carries free alternatives
+    #[serde(rename = "alias_taken")]
+    AliasTaken(Box<TakenAlias>),
+    
+    /// A go link keyword is in use; carries who owns it and free alternatives
+    #[serde(rename = "keyword_taken")]
+    KeywordTaken(Box<TakenKeyword>),
+    
+    /// A conditional update's version no longer matches the resource
+    #[serde(rename = "precondition_failed")]
+    PreconditionFailed(String),
+    
+    /// The update must be made conditional on the version the client last read
+    #[serde(rename = "precondition_required")]
+    PreconditionRequired,
+    
+    /// Missing or invalid API key
+    #[serde(rename = "unauthorized")]
+    Unauthorized,
+    
+    /// Authenticated, but the request may not be carried out (e.g. a failed CSRF check)
+    #[serde(rename = "forbidden")]
+    Forbidden(String),
+    
+    /// Storing another link would go over a link quota
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded(String),
+    
     /// Rate limiting errors
     #[serde(rename = "rate_limit_exceeded")]
     RateLimitExceeded,
     
+    /// The request's deadline passed before storage answered
+    #[serde(rename = "deadline_exceeded")]
+    DeadlineExceeded,
+    
+    /// The instance does not accept writes, e.g. while its database schema is ahead of it
+    #[serde(rename = "read_only")]
+    ReadOnly,
+    
     /// Security related errors
     #[serde(rename = "blocked_url")]
     BlockedUrl(String),
@@ -47,6 +106,194 @@ pub enum UrlShortenerErrorType {
     InternalError(String),
 }
 
+/// A custom alias that is already a link's code
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TakenAlias {
+    pub alias: String,
+    /// Similar aliases that are still free
+    pub suggestions: Vec<String>,
+}
+
+/// A go link keyword someone else got first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TakenKeyword {
+    pub keyword: String,
+    /// Owner of the existing link, to ask about sharing or handing it over
+    pub owner: Option<String>,
+    /// Similar keywords that are still free
+    pub suggestions: Vec<String>,
+}
+
+/// Retry-After sent with 429 responses
+const RATE_LIMIT_RETRY_AFTER_SECS: u64 = 60;
+/// Retry-After sent with 503 responses while the database is unreachable
+const UNAVAILABLE_RETRY_AFTER_SECS: u64 = 5;
+/// Retry-After sent with writes to a read-only instance; a maintenance window or rollout
+/// takes longer than a lost connection
+const READ_ONLY_RETRY_AFTER_SECS: u64 = 30;
+/// Retry-After sent with 504 responses; a new request gets a new deadline, so it may go
+/// through once the database has caught up
+const DEADLINE_RETRY_AFTER_SECS: u64 = 1;
+
+impl UrlShortenerErrorType {
+    /// Stable machine-readable code; clients should branch on this rather than the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidUrl(_) => "invalid_url",
+            Self::UrlTooLong(_) => "url_too_long",
+            Self::BlockedScheme(_) => "blocked_scheme",
+            Self::NotFound => "not_found",
+            Self::LinkDisabled => "link_disabled",
+            Self::InvalidSignature(_) => "invalid_signature",
+            Self::DatabaseError(_) => "database_error",
+            Self::ConnectionError(_) => "connection_error",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::ValidationFailed(_) => "validation_failed",
+            Self::Conflict(_) => "conflict",
+            Self::AliasTaken(_) => "alias_taken",
+            Self::KeywordTaken(_) => "keyword_taken",
+            Self::PreconditionFailed(_) => "precondition_failed",
+            Self::PreconditionRequired => "precondition_required",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::QuotaExceeded(_) => "quota_exceeded",
+            Self::RateLimitExceeded => "rate_limit_exceeded",
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::ReadOnly => "read_only",
+            Self::BlockedUrl(_) => "blocked_url",
+            Self::InternalError(_) => "internal_error",
+        }
+    }
+
+    /// Underlying detail carried by the error, if any
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            Self::InvalidUrl(detail) |
+            Self::UrlTooLong(detail) |
+            Self::BlockedScheme(detail) |
+            Self::InvalidSignature(detail) |
+            Self::DatabaseError(detail) |
+            Self::ConnectionError(detail) |
+            Self::InvalidInput(detail) |
+            Self::Conflict(detail) |
+            Self::PreconditionFailed(detail) |
+            Self::Forbidden(detail) |
+            Self::QuotaExceeded(detail) |
+            Self::BlockedUrl(detail) |
+            Self::InternalError(detail) => Some(detail),
+            Self::AliasTaken(taken) => Some(&taken.alias),
+            Self::KeywordTaken(taken) => Some(&taken.keyword),
+            Self::NotFound |
+            Self::LinkDisabled |
+            Self::ValidationFailed(_) |
+            Self::PreconditionRequired |
+            Self::Unauthorized |
+            Self::RateLimitExceeded |
+            Self::DeadlineExceeded |
+            Self::ReadOnly => None,
+        }
+    }
+
+    /// Human-readable description of the error, in English
+    pub fn message(&self) -> String {
+        match self {
+            Self::NotFound => "Short URL not found".to_string(),
+            Self::LinkDisabled => "This short link has been disabled".to_string(),
+            Self::ValidationFailed(_) => "Request validation failed".to_string(),
+            Self::PreconditionRequired => "An If-Match header or version is required".to_string(),
+            Self::Unauthorized => "Missing or invalid API key".to_string(),
+            Self::RateLimitExceeded => "Rate limit exceeded".to_string(),
+            Self::DeadlineExceeded => "Request deadline exceeded".to_string(),
+            Self::ReadOnly => "This instance is read-only".to_string(),
+            _ => self.detail().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Description of the error for clients, from the message catalog of `language`
+    pub fn localized_message(&self, language: &str) -> String {
+        i18n::translate(language, self.code(), self.detail()).unwrap_or_else(|| self.message())
+    }
+
+    /// Whether the same request may succeed if retried later; permanent errors will fail again
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::ConnectionError(_) | Self::RateLimitExceeded | Self::DeadlineExceeded | Self::ReadOnly)
+    }
+
+    /// Suggested delay before retrying, sent to clients as `Retry-After`
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimitExceeded => Some(Duration::from_secs(RATE_LIMIT_RETRY_AFTER_SECS)),
+            Self::ConnectionError(_) => Some(Duration::from_secs(UNAVAILABLE_RETRY_AFTER_SECS)),
+            Self::DeadlineExceeded => Some(Duration::from_secs(DEADLINE_RETRY_AFTER_SECS)),
+            Self::ReadOnly => Some(Duration::from_secs(READ_ONLY_RETRY_AFTER_SECS)),
+            _ => None,
+        }
+    }
+
+    /// Structured context for errors that carry more than a message
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::ValidationFailed(errors) => serde_json::to_value(errors).ok(),
+            Self::AliasTaken(taken) => serde_json::to_value(taken).ok(),
+            Self::KeywordTaken(taken) => serde_json::to_value(taken).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Envelope wrapping every JSON API response: `data` on success, `error` on failure
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub data: Option<T>,
+    pub error: Option<ApiError>,
+    /// For paged lists, the `cursor` to pass for the next page; absent on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn success(data: T) -> Self {
+        Self { data: Some(data), error: None, next_cursor: None }
+    }
+
+    /// A page of a list, followed by the one at the encoded `next_cursor` if there is one
+    pub fn page(data: T, next_cursor: Option<String>) -> Self {
+        Self { next_cursor, ..Self::success(data) }
+    }
+
+    pub fn failure(error: ApiError) -> Self {
+        Self { data: None, error: Some(error), next_cursor: None }
+    }
+}
+
+/// Error half of the response envelope
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiError {
+    /// Machine-readable error code, see [`UrlShortenerErrorType::code`]
+    pub code: String,
+    pub message: String,
+    /// Whether retrying the same request later may succeed
+    pub retryable: bool,
+    /// Correlation ID of the failed request, matching the `X-Correlation-Id` header and logs
+    pub correlation_id: Option<String>,
+    /// Error-specific context, e.g. field errors for `validation_failed`
+    pub details: Option<serde_json::Value>,
+}
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/// Runs a request future with its correlation ID available to error responses
+pub async fn with_correlation_id<F: std::future::Future>(correlation_id: String, future: F) -> F::Output {
+    CORRELATION_ID.scope(correlation_id, future).await
+}
+
+/// Correlation ID of the request currently being handled, if any
+pub fn current_correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(Clone::clone).ok()
+}
+
 /// Main error structure that includes context and backtrace
 pub struct UrlShortenerError {
     /// The type of error that occurred
@@ -114,25 +361,57 @@ impl actix_web::ResponseError for UrlShortenerError {
     fn status_code(&self) -> StatusCode {
         match &self.error_type {
             UrlShortenerErrorType::NotFound => StatusCode::NOT_FOUND,
+            UrlShortenerErrorType::LinkDisabled => StatusCode::GONE,
             UrlShortenerErrorType::InvalidUrl(_) |
             UrlShortenerErrorType::UrlTooLong(_) |
+            UrlShortenerErrorType::BlockedScheme(_) |
             UrlShortenerErrorType::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            UrlShortenerErrorType::ValidationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            UrlShortenerErrorType::Conflict(_) |
+            UrlShortenerErrorType::AliasTaken(_) |
+            UrlShortenerErrorType::KeywordTaken(_) => StatusCode::CONFLICT,
+            UrlShortenerErrorType::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            UrlShortenerErrorType::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            UrlShortenerErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            UrlShortenerErrorType::Forbidden(_) |
+            UrlShortenerErrorType::QuotaExceeded(_) |
+            UrlShortenerErrorType::InvalidSignature(_) |
             UrlShortenerErrorType::BlockedUrl(_) => StatusCode::FORBIDDEN,
             UrlShortenerErrorType::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
-            UrlShortenerErrorType::DatabaseError(_) |
+            UrlShortenerErrorType::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
             UrlShortenerErrorType::ConnectionError(_) |
+            UrlShortenerErrorType::ReadOnly => StatusCode::SERVICE_UNAVAILABLE,
+            UrlShortenerErrorType::DatabaseError(_) |
             UrlShortenerErrorType::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
     fn error_response(&self) -> actix_web::HttpResponse {
-        let json = serde_json::json!({
-            "error": self.error_type,
-            "status": self.status_code().as_u16(),
+        self.response_with_details(self.error_type.details())
+    }
+}
+
+impl UrlShortenerError {
+    /// The error's response with `details` in place of its own, e.g. suggestions for a
+    /// short code that does not exist
+    pub fn response_with_details(&self, details: Option<serde_json::Value>) -> actix_web::HttpResponse {
+        use actix_web::ResponseError;
+
+        let language = i18n::current_language();
+        let body = ApiResponse::<()>::failure(ApiError {
+            code: self.error_type.code().to_string(),
+            message: self.error_type.localized_message(language),
+            retryable: self.error_type.is_retryable(),
+            correlation_id: current_correlation_id(),
+            details,
         });
 
-        actix_web::HttpResponse::build(self.status_code())
-            .json(json)
+        let mut response = actix_web::HttpResponse::build(self.status_code());
+        response.insert_header((header::CONTENT_LANGUAGE, language));
+        if let Some(retry_after) = self.error_type.retry_after() {
+            response.insert_header((header::RETRY_AFTER, retry_after.as_secs().to_string()));
+        }
+        response.json(body)
     }
 }
 
@@ -152,6 +431,12 @@ impl From<sqlx::Error> for UrlShortenerError {
                     "Database connection pool timeout".to_string(),
                 ))
             }
+            sqlx::Error::Io(_) | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed => {
+                Self::with_source(
+                    UrlShortenerErrorType::ConnectionError("Database connection lost".to_string()),
+                    err,
+                )
+            }
             _ => Self::with_source(
                 UrlShortenerErrorType::DatabaseError("Database error".to_string()),
                 err,
@@ -183,4 +468,7 @@ impl From<UrlShortenerErrorType> for UrlShortenerError {
     fn from(error_type: UrlShortenerErrorType) -> Self {
         Self::new(error_type)
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
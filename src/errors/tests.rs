@@ -0,0 +1,56 @@
+use super::*;
+use actix_web::ResponseError;
+
+#[test]
+fn test_retryable_classification() {
+    assert!(UrlShortenerErrorType::RateLimitExceeded.is_retryable());
+    assert!(UrlShortenerErrorType::ConnectionError("down".to_string()).is_retryable());
+
+    assert!(!UrlShortenerErrorType::NotFound.is_retryable());
+    assert!(!UrlShortenerErrorType::Conflict("taken".to_string()).is_retryable());
+    assert!(!UrlShortenerErrorType::DatabaseError("syntax".to_string()).is_retryable());
+    // A new request gets a new deadline; the storage layer does not retry within the old one
+    assert!(UrlShortenerErrorType::DeadlineExceeded.is_retryable());
+    assert!(UrlShortenerErrorType::ReadOnly.is_retryable());
+}
+
+#[test]
+fn test_retryable_responses_carry_retry_after() {
+    let error = UrlShortenerError::from(UrlShortenerErrorType::ConnectionError("down".to_string()));
+    let response = error.error_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "5");
+
+    let error = UrlShortenerError::from(UrlShortenerErrorType::RateLimitExceeded);
+    let response = error.error_response();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "60");
+
+    let error = UrlShortenerError::from(UrlShortenerErrorType::DeadlineExceeded);
+    let response = error.error_response();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "1");
+
+    let error = UrlShortenerError::from(UrlShortenerErrorType::NotFound);
+    assert!(error.error_response().headers().get(header::RETRY_AFTER).is_none());
+}
+
+#[test]
+fn test_sqlx_connection_errors_are_retryable() {
+    let error = UrlShortenerError::from(sqlx::Error::PoolTimedOut);
+    assert!(error.error_type.is_retryable());
+
+    let error = UrlShortenerError::from(sqlx::Error::RowNotFound);
+    assert!(!error.error_type.is_retryable());
+}
+
+#[test]
+fn test_read_only_is_unavailable_with_retry_after() {
+    let error = UrlShortenerError::from(UrlShortenerErrorType::ReadOnly);
+    assert_eq!(error.error_type.code(), "read_only");
+    // Read-only spells are maintenance windows and rollouts, so writes succeed again later
+    assert!(error.error_type.is_retryable());
+    let response = error.error_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+}
@@ -0,0 +1,98 @@
+//! Visit event exports for analytics warehouses.
+//!
+//! Events are written as Parquet, one row group per page read from storage, and handed out
+//! as soon as each row group is complete, so an export of any size is never held in memory.
+
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use arrow_array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::RecordedVisit;
+use crate::storage::StorageRef;
+
+/// Events read from storage per row group
+const PAGE_SIZE: i64 = 10_000;
+
+/// Columns of exported visit events
+pub fn visit_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("short_code", DataType::Utf8, false),
+        Field::new("visited_at", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("visitor", DataType::Utf8, true),
+        Field::new("source", DataType::Utf8, true),
+        Field::new("sample_rate", DataType::Int32, false),
+    ]))
+}
+
+fn visit_batch(visits: &[RecordedVisit]) -> UrlShortenerResult<RecordBatch> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(visits.iter().map(|visit| visit.id))),
+        Arc::new(StringArray::from_iter_values(visits.iter().map(|visit| visit.short_code.as_str()))),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(visits.iter().map(|visit| visit.visited_at.timestamp_micros()))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(StringArray::from_iter(visits.iter().map(|visit| visit.visitor.as_deref()))),
+        Arc::new(StringArray::from_iter(visits.iter().map(|visit| visit.source.as_deref()))),
+        Arc::new(Int32Array::from_iter_values(visits.iter().map(|visit| visit.sample_rate))),
+    ];
+    RecordBatch::try_new(visit_schema(), columns).map_err(export_error)
+}
+
+fn export_error(e: impl std::error::Error + Send + Sync + 'static) -> UrlShortenerError {
+    UrlShortenerError::with_source(
+        UrlShortenerErrorType::InternalError("Failed to encode the visit export".to_string()),
+        e,
+    )
+}
+
+struct ExportState {
+    storage: StorageRef,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    after: Option<(DateTime<Utc>, i64)>,
+    /// Unset once the file is complete
+    writer: Option<ArrowWriter<Vec<u8>>>,
+}
+
+/// Streams the visit events of every link from `from` until before `to` as a Parquet file
+pub fn visits_parquet(
+    storage: StorageRef,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> UrlShortenerResult<impl Stream<Item = UrlShortenerResult<Bytes>>> {
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let writer = ArrowWriter::try_new(Vec::new(), visit_schema(), Some(properties)).map_err(export_error)?;
+    let state = ExportState { storage, from, to, after: None, writer: Some(writer) };
+
+    Ok(stream::try_unfold(state, |mut state| async move {
+        let Some(mut writer) = state.writer.take() else {
+            return Ok(None);
+        };
+        let visits = state.storage.export_visit_events(state.from, state.to, state.after, PAGE_SIZE).await?;
+        let Some(last) = visits.last() else {
+            // The footer goes last, after every row group
+            let rest = writer.into_inner().map_err(export_error)?;
+            return Ok(Some((Bytes::from(rest), state)));
+        };
+
+        state.after = Some((last.visited_at, last.id));
+        writer.write(&visit_batch(&visits)?).map_err(export_error)?;
+        writer.flush().map_err(export_error)?;
+        let written = std::mem::take(writer.inner_mut());
+        state.writer = Some(writer);
+        Ok(Some((Bytes::from(written), state)))
+    }))
+}
+
+#[cfg(test)]
+mod tests;
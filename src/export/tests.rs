@@ -0,0 +1,69 @@
+use super::*;
+use arrow_array::Array;
+use chrono::Duration;
+use futures::TryStreamExt;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::models::{ShortenedUrl, VisitEvent};
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+fn link(short_url: &str) -> ShortenedUrl {
+    ShortenedUrl {
+        original_url: "https://example.com/".to_string(),
+        short_url: short_url.to_string(),
+        ..ShortenedUrl::default()
+    }
+}
+
+#[tokio::test]
+async fn test_visits_are_exported_in_row_groups_for_the_range() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    storage.save_url(link("abc")).await.unwrap();
+    let start = "2026-10-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+    let events: Vec<VisitEvent> = (0..25_000)
+        .map(|i| VisitEvent {
+            short_code: "abc".to_string(),
+            visited_at: start + Duration::seconds(i),
+            visitor: (i % 2 == 0).then(|| format!("visitor-{}", i % 7)),
+            source: (i % 3 == 0).then(|| "newsletter".to_string()),
+            sample_rate: if i % 5 == 0 { 10 } else { 1 },
+        })
+        .collect();
+    storage.record_visit_events(&events).await.unwrap();
+
+    let to = start + Duration::seconds(22_000);
+    let chunks: Vec<Bytes> = visits_parquet(storage, start + Duration::seconds(1), to)
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let file: Bytes = chunks.concat().into();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 3);
+    assert_eq!(reader.schema(), &visit_schema());
+    let batches: Vec<RecordBatch> = reader.build().unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 21_999);
+
+    let first = &batches[0];
+    let visited_at = first.column(2).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+    assert_eq!(visited_at.value(0), (start + Duration::seconds(1)).timestamp_micros());
+    let visitors = first.column(3).as_any().downcast_ref::<StringArray>().unwrap();
+    assert!(visitors.is_null(0));
+    assert_eq!(visitors.value(1), "visitor-2");
+    let sources = first.column(4).as_any().downcast_ref::<StringArray>().unwrap();
+    assert!(sources.is_null(0));
+    assert_eq!(sources.value(2), "newsletter");
+    let sample_rates = first.column(5).as_any().downcast_ref::<Int32Array>().unwrap();
+    assert_eq!((sample_rates.value(0), sample_rates.value(4)), (1, 10));
+}
+
+#[tokio::test]
+async fn test_empty_ranges_export_a_valid_file() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let now = Utc::now();
+    let chunks: Vec<Bytes> = visits_parquet(storage, now, now).unwrap().try_collect().await.unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(chunks.concat())).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+}
@@ -0,0 +1,262 @@
+//! Favicon proxy for dashboard listings.
+//!
+//! Dashboards show the icon of each link's destination. Rather than have every browser ask the
+//! destination hosts, the server fetches a domain's icon once, shrinks it to a small PNG and
+//! keeps it, on disk when a cache directory is configured. Only public hosts are fetched: a
+//! domain must resolve to public addresses only, and the connection is pinned to the addresses
+//! that were checked, so a second lookup cannot point it elsewhere. Redirects are followed one
+//! hop at a time and each hop is checked the same way.
+
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+use url::{Host, Url};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::preview::{self, parse_og_tags, resolve};
+
+/// Width and height of served icons; larger icons are scaled down to fit
+pub const ICON_SIZE: u32 = 32;
+
+/// Domains whose icons, or lack of one, are remembered in memory
+const CACHE_CAPACITY: usize = 1_000;
+/// How long an icon is served before it is fetched again
+pub const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a domain without a readable icon is not asked again
+const MISS_TTL: Duration = Duration::from_secs(60 * 60);
+/// Time allowed for each request to a destination host
+const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+const FETCH_USER_AGENT: &str = concat!("url-map/", env!("CARGO_PKG_VERSION"), " (favicon)");
+/// Redirects followed from each URL
+const MAX_REDIRECTS: usize = 3;
+/// Largest icon file read; larger ones are refused rather than truncated
+const MAX_ICON_BYTES: usize = 100 * 1024;
+/// Bytes of a home page read while looking for the icon it links
+const MAX_PAGE_BYTES: usize = 64 * 1024;
+/// Largest icon dimensions decoded
+const MAX_DECODED_SIZE: u32 = 1024;
+
+/// A domain's shrunk icon, or `None` for a domain without one, and when it was fetched
+type CachedIcon = (Instant, Option<Arc<[u8]>>);
+
+/// Fetches, shrinks and caches destination favicons
+pub struct Favicons {
+    http: HttpClient,
+    cache_dir: Option<PathBuf>,
+    cache: Mutex<LruCache<String, CachedIcon>>,
+}
+
+impl Favicons {
+    /// Icons are kept in `cache_dir` when given, so they survive restarts, and in memory
+    pub fn new(http: HttpClient, cache_dir: Option<PathBuf>) -> Self {
+        Self {
+            http,
+            cache_dir,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN))),
+        }
+    }
+
+    /// PNG icon of a normalized domain, at most [`ICON_SIZE`] pixels wide and high; `None` when
+    /// the domain has no icon that can be read
+    pub async fn icon(&self, domain: &str) -> Option<Arc<[u8]>> {
+        if let Some(icon) = self.cached(domain) {
+            return icon;
+        }
+        if let Some(icon) = self.read_disk(domain).await {
+            self.remember(domain, Some(icon.clone()));
+            return Some(icon);
+        }
+
+        let icon = match self.fetch(domain).await {
+            Ok(icon) => Some(Arc::from(icon)),
+            Err(reason) => {
+                debug!(domain = %domain, reason = %reason, "No favicon");
+                None
+            }
+        };
+        if let Some(icon) = &icon {
+            self.write_disk(domain, icon).await;
+        }
+        self.remember(domain, icon.clone());
+        icon
+    }
+
+    fn cached(&self, domain: &str) -> Option<Option<Arc<[u8]>>> {
+        let mut cache = self.cache.lock().ok()?;
+        match cache.get(domain) {
+            Some((fetched_at, Some(icon))) if fetched_at.elapsed() < CACHE_TTL => Some(Some(icon.clone())),
+            Some((fetched_at, None)) if fetched_at.elapsed() < MISS_TTL => Some(None),
+            _ => None,
+        }
+    }
+
+    fn remember(&self, domain: &str, icon: Option<Arc<[u8]>>) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.put(domain.to_string(), (Instant::now(), icon));
+        }
+    }
+
+    /// File an icon is kept in; named by a hash, so domains never form paths
+    fn disk_path(&self, domain: &str) -> Option<PathBuf> {
+        let name = hex::encode(Sha256::digest(domain.as_bytes()));
+        self.cache_dir.as_ref().map(|dir| dir.join(format!("{}.png", name)))
+    }
+
+    async fn read_disk(&self, domain: &str) -> Option<Arc<[u8]>> {
+        let path = self.disk_path(domain)?;
+        let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age >= CACHE_TTL {
+            return None;
+        }
+        tokio::fs::read(&path).await.ok().map(Arc::from)
+    }
+
+    async fn write_disk(&self, domain: &str, icon: &[u8]) {
+        let Some(path) = self.disk_path(domain) else { return };
+        if let Some(dir) = path.parent() {
+            if let Err(e) = tokio::fs::create_dir_all(dir).await {
+                warn!(error = %e, "Failed to create favicon cache directory");
+                return;
+            }
+        }
+        if let Err(e) = tokio::fs::write(&path, icon).await {
+            warn!(error = %e, "Failed to write favicon to cache");
+        }
+    }
+
+    /// The domain's `/favicon.ico`, else the icon its home page links
+    async fn fetch(&self, domain: &str) -> Result<Vec<u8>, String> {
+        let base = Url::parse(&format!("https://{}/", domain)).map_err(|e| e.to_string())?;
+        let ico = base.join("/favicon.ico").map_err(|e| e.to_string())?;
+        let icon = match self.get(ico, MAX_ICON_BYTES, is_image).await {
+            Ok(icon) => icon,
+            Err(reason) => {
+                debug!(domain = %domain, reason = %reason, "No /favicon.ico, reading the home page");
+                let page = self.get(base.clone(), MAX_PAGE_BYTES, is_html).await?;
+                let tags = parse_og_tags(&String::from_utf8_lossy(&page));
+                let linked = tags
+                    .icon
+                    .as_deref()
+                    .and_then(|icon| resolve(base.as_str(), icon))
+                    .ok_or("home page links no icon")?;
+                self.get(linked, MAX_ICON_BYTES, is_image).await?
+            }
+        };
+
+        // Decoding is CPU-bound; keep it off the async workers
+        tokio::task::spawn_blocking(move || shrink(&icon))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+
+    /// Reads a response body of at most `max_bytes` whose content type passes `accept`,
+    /// following redirects between public hosts
+    async fn get(&self, mut url: Url, max_bytes: usize, accept: fn(&str) -> bool) -> Result<Vec<u8>, String> {
+        for _ in 0..=MAX_REDIRECTS {
+            let addrs = public_addrs(&url).await?;
+            let domain = url.host_str().unwrap_or_default().to_string();
+            let http = self.http.pinned(&domain, &addrs).map_err(|e| e.to_string())?;
+            let request = http
+                .get(url.clone())
+                .timeout(http.timeout_within(FETCH_TIMEOUT))
+                .header(reqwest::header::USER_AGENT, FETCH_USER_AGENT);
+            let mut response = http.send("favicon", request).await.map_err(|e| e.to_string())?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or("redirect without a location")?;
+                url = url.join(location).map_err(|e| e.to_string())?;
+                continue;
+            }
+            if !response.status().is_success() {
+                return Err(format!("status {}", response.status()));
+            }
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !accept(&content_type) {
+                return Err(format!("unexpected content type '{}'", content_type));
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+                body.extend_from_slice(&chunk);
+                if body.len() > max_bytes {
+                    return Err(format!("larger than {} bytes", max_bytes));
+                }
+            }
+            return Ok(body);
+        }
+        Err("too many redirects".to_string())
+    }
+}
+
+/// Icons are often served as `application/octet-stream`, or without a content type
+fn is_image(content_type: &str) -> bool {
+    content_type.is_empty() || content_type.starts_with("image/") || content_type.starts_with("application/octet-stream")
+}
+
+fn is_html(content_type: &str) -> bool {
+    content_type.starts_with("text/html") || content_type.starts_with("application/xhtml")
+}
+
+/// Addresses of an https URL's domain, when it is on the default port and every address it
+/// resolves to is public. IP literals are refused outright.
+pub async fn public_addrs(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") || url.port().is_some() {
+        return Err("not an http(s) URL on the default port".to_string());
+    }
+    let Some(Host::Domain(domain)) = url.host() else {
+        return Err("not a domain name".to_string());
+    };
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    if domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".internal") || !domain.contains('.') {
+        return Err("not a public domain".to_string());
+    }
+
+    preview::public_addrs(url).await
+}
+
+/// Decodes an icon in any supported format and re-encodes it as a PNG of at most
+/// [`ICON_SIZE`] pixels a side, keeping its aspect ratio
+pub fn shrink(icon: &[u8]) -> UrlShortenerResult<Vec<u8>> {
+    let invalid = |e: image::ImageError| {
+        UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput("Unreadable icon".to_string()), e)
+    };
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODED_SIZE);
+    limits.max_image_height = Some(MAX_DECODED_SIZE);
+    let mut reader = ImageReader::new(Cursor::new(icon))
+        .with_guessed_format()
+        .map_err(|e| UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput("Unreadable icon".to_string()), e))?;
+    reader.limits(limits);
+    let mut image = reader.decode().map_err(invalid)?;
+    if image.width() > ICON_SIZE || image.height() > ICON_SIZE {
+        image = image.resize(ICON_SIZE, ICON_SIZE, FilterType::Lanczos3);
+    }
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png).map_err(invalid)?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use super::*;
+use image::{DynamicImage, RgbaImage};
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(RgbaImage::new(width, height)).write_to(&mut png, ImageFormat::Png).unwrap();
+    png.into_inner()
+}
+
+fn dimensions(png: &[u8]) -> (u32, u32) {
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png).unwrap();
+    (image.width(), image.height())
+}
+
+#[test]
+fn test_icons_are_shrunk_to_png() {
+    assert_eq!(dimensions(&shrink(&png(64, 32)).unwrap()), (32, 16));
+    assert_eq!(dimensions(&shrink(&png(16, 16)).unwrap()), (16, 16));
+
+    let mut ico = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(RgbaImage::new(48, 48)).write_to(&mut ico, ImageFormat::Ico).unwrap();
+    assert_eq!(dimensions(&shrink(ico.get_ref()).unwrap()), (32, 32));
+
+    assert!(shrink(b"<svg></svg>").is_err());
+    assert!(shrink(&png(4096, 16)).is_err());
+}
+
+#[tokio::test]
+async fn test_only_public_domains_are_fetched() {
+    for url in [
+        "https://localhost/favicon.ico",
+        "https://app.localhost/favicon.ico",
+        "https://db.internal/favicon.ico",
+        "https://intranet/favicon.ico",
+        "https://127.0.0.1/favicon.ico",
+        "https://93.184.216.34/favicon.ico",
+        "https://[::1]/favicon.ico",
+        "https://example.com:8443/favicon.ico",
+        "ftp://example.com/favicon.ico",
+    ] {
+        assert!(public_addrs(&Url::parse(url).unwrap()).await.is_err(), "{} was allowed", url);
+    }
+}
+
+#[tokio::test]
+async fn test_icons_are_served_from_the_disk_cache() {
+    let dir = std::env::temp_dir().join(format!("url-map-favicons-{}", std::process::id()));
+    let favicons = Favicons::new(HttpClient::default(), Some(dir.clone()));
+    let icon = shrink(&png(64, 64)).unwrap();
+    favicons.write_disk("example.com", &icon).await;
+
+    // A fresh instance reads it from disk rather than fetching it
+    let favicons = Favicons::new(HttpClient::default(), Some(dir.clone()));
+    assert_eq!(favicons.icon("example.com").await.as_deref(), Some(&icon[..]));
+    assert_eq!(favicons.cached("example.com"), Some(Some(Arc::from(icon))));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
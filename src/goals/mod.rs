@@ -0,0 +1,96 @@
+//! Click goals.
+//!
+//! Owners set a visit threshold per link. A background checker claims links whose visits
+//! reached their goal and notifies about each one once; the claim is stored on the link, so
+//! restarts and additional instances do not notify twice.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use crate::storage::StorageRef;
+use crate::webhooks::{EventKind, GoalReached, Notifier, WebhookEvent};
+
+/// Name of the webhook click goal notifications are delivered to
+pub const GOAL_WEBHOOK: &str = "goals";
+
+/// Reached goals claimed per storage round trip
+const CLAIM_BATCH_SIZE: i64 = 100;
+
+/// Finds reached click goals and sends one notification per goal
+pub struct GoalChecker {
+    storage: StorageRef,
+    notifier: Arc<dyn Notifier>,
+}
+
+impl GoalChecker {
+    pub fn new(storage: StorageRef, notifier: Arc<dyn Notifier>) -> Self {
+        Self { storage, notifier }
+    }
+
+    /// Notifies about every goal reached since the last check, returning how many were delivered.
+    /// Goals whose notification fails are released and retried on the next check.
+    pub async fn check(&self) -> usize {
+        let mut delivered = 0;
+        loop {
+            let reached = match self.storage.claim_reached_goals(CLAIM_BATCH_SIZE).await {
+                Ok(reached) => reached,
+                Err(e) => {
+                    warn!(error = %e, "Failed to claim reached click goals");
+                    break;
+                }
+            };
+            let claimed = reached.len();
+
+            for url in reached {
+                let event = WebhookEvent::new(EventKind::GoalReached(GoalReached {
+                    short_code: url.short_url.clone(),
+                    original_url: url.original_url,
+                    title: url.title,
+                    owner: url.owner,
+                    click_goal: url.click_goal.unwrap_or_default(),
+                    visits: url.visits,
+                }));
+
+                match self.notifier.notify(&event).await {
+                    Ok(()) => {
+                        info!(short_code = %url.short_url, "Click goal reached");
+                        delivered += 1;
+                    }
+                    Err(e) => {
+                        warn!(error = %e, short_code = %url.short_url, "Failed to notify click goal, will retry");
+                        if let Err(e) = self.storage.release_goal(&url.short_url).await {
+                            warn!(error = %e, short_code = %url.short_url, "Failed to release click goal");
+                        }
+                    }
+                }
+            }
+
+            // Released goals would be claimed again right away; leave them for the next check
+            if (claimed as i64) < CLAIM_BATCH_SIZE || delivered == 0 {
+                break;
+            }
+        }
+
+        if delivered > 0 {
+            debug!(delivered, "Sent click goal notifications");
+        }
+        delivered
+    }
+
+    /// Checks every `interval` for the lifetime of the process
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.check().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests;
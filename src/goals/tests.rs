@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::*;
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{MetadataUpdate, ShortenedUrl};
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+/// Records delivered events, optionally failing instead
+#[derive(Default)]
+struct RecordingNotifier {
+    events: Mutex<Vec<WebhookEvent>>,
+    failing: AtomicBool,
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn notify(&self, event: &WebhookEvent) -> UrlShortenerResult<()> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(UrlShortenerErrorType::InternalError("webhook down".to_string()).into());
+        }
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+async fn link_with_goal(storage: &MemoryStorage, code: &str, goal: i64) {
+    storage
+        .save_url(ShortenedUrl {
+            original_url: format!("https://example.com/{}", code),
+            short_url: code.to_string(),
+            owner: Some("alice".to_string()),
+            ..ShortenedUrl::default()
+        })
+        .await
+        .unwrap();
+    let update = MetadataUpdate { click_goal: Some(Some(goal)), ..MetadataUpdate::default() };
+    storage.update_metadata(code, update).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_goal_fires_once_when_crossed() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let notifier = Arc::new(RecordingNotifier::default());
+    let checker = GoalChecker::new(storage.clone(), notifier.clone());
+    link_with_goal(&storage, "launch", 10).await;
+
+    storage.record_visits("launch", 9).await.unwrap();
+    assert_eq!(checker.check().await, 0);
+
+    storage.record_visits("launch", 3).await.unwrap();
+    assert_eq!(checker.check().await, 1);
+    assert_eq!(checker.check().await, 0);
+    storage.record_visits("launch", 100).await.unwrap();
+    assert_eq!(checker.check().await, 0);
+
+    assert!(storage.get_stats("launch").await.unwrap().goal_reached_at.is_some());
+    let events = notifier.events.lock().unwrap();
+    let EventKind::GoalReached(reached) = &events[0].kind else { panic!("expected a goal event") };
+    assert_eq!((reached.short_code.as_str(), reached.click_goal, reached.visits), ("launch", 10, 12));
+}
+
+#[tokio::test]
+async fn test_failed_delivery_is_retried() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let notifier = Arc::new(RecordingNotifier::default());
+    let checker = GoalChecker::new(storage.clone(), notifier.clone());
+    link_with_goal(&storage, "launch", 1).await;
+    storage.record_visits("launch", 1).await.unwrap();
+
+    notifier.failing.store(true, Ordering::SeqCst);
+    assert_eq!(checker.check().await, 0);
+    assert!(storage.get_stats("launch").await.unwrap().goal_reached_at.is_none());
+
+    notifier.failing.store(false, Ordering::SeqCst);
+    assert_eq!(checker.check().await, 1);
+}
+
+#[tokio::test]
+async fn test_changing_goal_rearms_it() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let notifier = Arc::new(RecordingNotifier::default());
+    let checker = GoalChecker::new(storage.clone(), notifier.clone());
+    link_with_goal(&storage, "launch", 1).await;
+    storage.record_visits("launch", 1).await.unwrap();
+    assert_eq!(checker.check().await, 1);
+
+    link_with_goal(&storage, "other", 5).await;
+    let update = MetadataUpdate { click_goal: Some(Some(2)), ..MetadataUpdate::default() };
+    storage.update_metadata("launch", update).await.unwrap();
+    storage.record_visits("launch", 1).await.unwrap();
+    assert_eq!(checker.check().await, 1);
+}
+
+#[test]
+fn test_event_payload_format() {
+    let event = WebhookEvent::new(EventKind::GoalReached(GoalReached {
+        short_code: "launch".to_string(),
+        original_url: "https://example.com/".to_string(),
+        title: None,
+        owner: Some("alice".to_string()),
+        click_goal: 10,
+        visits: 12,
+    }));
+    let json = serde_json::to_value(&event).unwrap();
+
+    assert_eq!(json["event"], "link.goal_reached");
+    assert_eq!(json["data"]["short_code"], "launch");
+    assert_eq!(json["data"]["click_goal"], 10);
+    assert!(json["occurred_at"].is_string());
+    assert!(json["data"].get("title").is_none());
+}
@@ -0,0 +1,442 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+use crate::abuse::{AbuseGuard, AbuseStats, PenalizedClient};
+use crate::auth::AdminKey;
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::logging::LogFilter;
+use crate::middleware::panics_total;
+use crate::models::{VisitChange, VisitCounts};
+use crate::redirect::{BufferedVisits, CacheStats, Redirector};
+use crate::services::{normalize_domain, UrlService};
+use crate::slo::SloCounters;
+use crate::throttle::RouteThrottle;
+use crate::validation::{check_max_length, check_required, Validate, ValidationErrors};
+
+/// Largest visit adjustment accepted in one call, in either direction
+pub const MAX_VISIT_ADJUSTMENT: i64 = 1_000_000_000_000;
+
+/// Maximum length of the reason recorded with an admin action, in characters
+pub const MAX_REASON_LENGTH: usize = 500;
+
+/// Maximum length of log filter directives, in characters
+pub const MAX_LOG_FILTER_LENGTH: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheFlushResponse {
+    /// Cached mappings dropped
+    pub evicted: usize,
+}
+
+/// Empties the redirect cache, e.g. after destinations were edited directly in the database
+pub async fn flush_cache(
+    admin: AdminKey,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    let evicted = redirector.clear_cache();
+    info!(admin = %admin.owner, evicted, "Flushed redirect cache");
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CacheFlushResponse { evicted })))
+}
+
+/// Drops one short code from the redirect cache
+pub async fn invalidate_cached_code(
+    short_code: web::Path<String>,
+    admin: AdminKey,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    let evicted = usize::from(redirector.invalidate(&short_code));
+    info!(admin = %admin.owner, short_code = %short_code, evicted, "Invalidated cached short code");
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CacheFlushResponse { evicted })))
+}
+
+/// Prepared statement reuse on the redirect path; 404 for backends without prepared statements
+pub async fn statement_cache_stats(
+    _admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let stats = service.statement_cache_stats().ok_or(UrlShortenerErrorType::NotFound)?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
+}
+
+/// Storage calls over the slow query threshold since startup; 404 when calls are not timed
+pub async fn slow_query_stats(
+    _admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let stats = service.slow_query_stats().ok_or(UrlShortenerErrorType::NotFound)?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PanicCountResponse {
+    pub panics_total: u64,
+}
+
+/// Requests whose handler panicked since startup
+pub async fn panic_count(_admin: AdminKey) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse::success(PanicCountResponse { panics_total: panics_total() }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogLevelRequest {
+    /// `RUST_LOG`-style directives, e.g. `info,url_map::redirect=debug`
+    pub filter: String,
+}
+
+impl Validate for LogLevelRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if check_required(&mut errors, "filter", &self.filter)
+            && check_max_length(&mut errors, "filter", &self.filter, MAX_LOG_FILTER_LENGTH)
+        {
+            if let Err(e) = EnvFilter::try_new(&self.filter) {
+                errors.add("filter", "invalid_filter", format!("filter is not valid: {}", e));
+            }
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogLevelResponse {
+    /// Directives in effect
+    pub filter: String,
+}
+
+/// The log filter in effect
+pub async fn log_level(_admin: AdminKey, log_filter: web::Data<LogFilter>) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse::success(LogLevelResponse { filter: log_filter.directives() }))
+}
+
+/// Replaces the log filter until the next change or restart
+pub async fn set_log_level(
+    request: web::Json<LogLevelRequest>,
+    admin: AdminKey,
+    log_filter: web::Data<LogFilter>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+    let previous = log_filter.directives();
+    log_filter.set(&request.filter)?;
+    warn!(owner = %admin.owner, previous = %previous, filter = %request.filter, "Changed log filter");
+    Ok(HttpResponse::Ok().json(ApiResponse::success(LogLevelResponse { filter: log_filter.directives() })))
+}
+
+/// Budgets of each route class with the requests they turned away; 404 when throttling is off
+pub async fn throttle_stats(
+    _admin: AdminKey,
+    throttle: Option<web::Data<RouteThrottle>>,
+) -> UrlShortenerResult<HttpResponse> {
+    let throttle = throttle.ok_or(UrlShortenerErrorType::NotFound)?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(throttle.stats())))
+}
+
+/// Good and total requests of each route class against its latency objective; 404 when the
+/// requests are not counted
+pub async fn slo_stats(
+    _admin: AdminKey,
+    counters: Option<web::Data<SloCounters>>,
+) -> UrlShortenerResult<HttpResponse> {
+    let counters = counters.ok_or(UrlShortenerErrorType::NotFound)?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(counters.stats())))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeResponse {
+    /// Resident memory of the process; `None` where `/proc` is not available
+    pub rss_bytes: Option<u64>,
+    /// The Tokio runtime of the HTTP worker that answered
+    pub tokio: TokioStats,
+    pub redirect_cache: CacheStats,
+    /// `None` when the response micro-cache is disabled
+    pub response_cache: Option<CacheStats>,
+    pub buffered_visits: BufferedVisits,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokioStats {
+    pub workers: usize,
+    /// Tasks spawned and not yet finished
+    pub alive_tasks: usize,
+    /// Tasks waiting in the runtime's shared queue
+    pub queued_tasks: usize,
+}
+
+/// Memory, runtime, cache and visit buffer figures of this instance, for a quick look
+/// without a metrics stack
+pub async fn runtime_stats(
+    _admin: AdminKey,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    let metrics = tokio::runtime::Handle::current().metrics();
+    Ok(HttpResponse::Ok().json(ApiResponse::success(RuntimeResponse {
+        rss_bytes: resident_memory_bytes(),
+        tokio: TokioStats {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            queued_tasks: metrics.global_queue_depth(),
+        },
+        redirect_cache: redirector.cache().stats(),
+        response_cache: redirector.response_cache_stats(),
+        buffered_visits: redirector.buffered_visits(),
+    })))
+}
+
+/// Resident set size from `/proc/self/status`, which reports it in kB
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kb: u64 = line.trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Outbound HTTP usage of this instance, per kind of call
+pub async fn outbound_stats(
+    _admin: AdminKey,
+    http: web::Data<HttpClient>,
+) -> UrlShortenerResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ApiResponse::success(http.stats())))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetVisitsRequest {
+    /// Why the count is being changed, kept in the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Validate for ResetVisitsRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(reason) = &self.reason {
+            check_max_length(&mut errors, "reason", reason, MAX_REASON_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnableLinkRequest {
+    /// Why the link may redirect again, kept in the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Validate for EnableLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(reason) = &self.reason {
+            check_max_length(&mut errors, "reason", reason, MAX_REASON_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnableLinkResponse {
+    pub short_code: String,
+    /// Whether the link was disabled; re-enabling an active link changes nothing
+    pub was_disabled: bool,
+}
+
+/// Lets a disabled link redirect again, such as one the runaway guard stopped
+pub async fn enable_link(
+    short_code: web::Path<String>,
+    request: web::Json<EnableLinkRequest>,
+    admin: AdminKey,
+    service: web::Data<UrlService>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let was_disabled = service.enable_link(&short_code, &admin.owner, request.reason.as_deref()).await?;
+    // The runaway guard keeps stopping the link on this instance until its rate is forgotten
+    redirector.invalidate(&short_code);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(EnableLinkResponse {
+        short_code: short_code.into_inner(),
+        was_disabled,
+    })))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdjustVisitsRequest {
+    /// Visits to add; negative values subtract, stopping at zero
+    pub delta: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Validate for AdjustVisitsRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.delta == 0 || self.delta.unsigned_abs() > MAX_VISIT_ADJUSTMENT as u64 {
+            errors.add(
+                "delta",
+                "out_of_range",
+                format!("delta must be non-zero and at most {} in either direction", MAX_VISIT_ADJUSTMENT),
+            );
+        }
+        if let Some(reason) = &self.reason {
+            check_max_length(&mut errors, "reason", reason, MAX_REASON_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VisitCountResponse {
+    pub short_code: String,
+    /// Count before the change
+    pub previous: u64,
+    pub visits: u64,
+}
+
+impl VisitCountResponse {
+    fn new(short_code: String, counts: VisitCounts) -> Self {
+        Self {
+            short_code,
+            previous: counts.previous as u64,
+            visits: counts.visits as u64,
+        }
+    }
+}
+
+/// Sets a link's visit count back to zero
+pub async fn reset_visits(
+    short_code: web::Path<String>,
+    request: web::Json<ResetVisitsRequest>,
+    admin: AdminKey,
+    service: web::Data<UrlService>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    // Visits counted but not yet written would otherwise land on top of the new count
+    redirector.flush_visits().await;
+    let counts = service
+        .change_visits(&short_code, VisitChange::Reset, &admin.owner, request.reason.as_deref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(VisitCountResponse::new(short_code.into_inner(), counts))))
+}
+
+/// Adds to or subtracts from a link's visit count
+pub async fn adjust_visits(
+    short_code: web::Path<String>,
+    request: web::Json<AdjustVisitsRequest>,
+    admin: AdminKey,
+    service: web::Data<UrlService>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    redirector.flush_visits().await;
+    let counts = service
+        .change_visits(&short_code, VisitChange::Adjust(request.delta), &admin.owner, request.reason.as_deref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(VisitCountResponse::new(short_code.into_inner(), counts))))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BanDomainRequest {
+    /// Hostname whose links are disabled, subdomains included, e.g. `evil.example`
+    pub domain: String,
+    /// Why the domain is banned, kept with the ban and in the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Validate for BanDomainRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if normalize_domain(&self.domain).is_none() {
+            errors.add("domain", "invalid_domain", "domain must be a hostname like example.com");
+        }
+        if let Some(reason) = &self.reason {
+            check_max_length(&mut errors, "reason", reason, MAX_REASON_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+/// Blocks new links to a domain and disables the existing ones in one step
+pub async fn ban_domain(
+    request: web::Json<BanDomainRequest>,
+    admin: AdminKey,
+    service: web::Data<UrlService>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let domain = normalize_domain(&request.domain).unwrap_or_default();
+    let ban = service.ban_domain(&domain, &admin.owner, request.reason.as_deref()).await?;
+    // Cached mappings would keep redirecting until evicted
+    for short_code in &ban.disabled {
+        redirector.invalidate(short_code);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ban)))
+}
+
+pub async fn blocked_domains(
+    _admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let domains = service.blocked_domains().await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(domains)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PenalizedClientsResponse {
+    #[serde(flatten)]
+    pub stats: AbuseStats,
+    pub clients: Vec<PenalizedClient>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClearPenaltiesResponse {
+    /// Penalized clients let back in
+    pub cleared: usize,
+}
+
+/// Clients currently tarpitted or blocked for causing too many errors; 404 when penalties are off
+pub async fn penalized_clients(
+    _admin: AdminKey,
+    guard: Option<web::Data<AbuseGuard>>,
+) -> UrlShortenerResult<HttpResponse> {
+    let guard = guard.ok_or(UrlShortenerErrorType::NotFound)?;
+    let response = PenalizedClientsResponse { stats: guard.stats(), clients: guard.penalized() };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+/// Lifts every client's penalty
+pub async fn clear_penalties(
+    admin: AdminKey,
+    guard: Option<web::Data<AbuseGuard>>,
+) -> UrlShortenerResult<HttpResponse> {
+    let guard = guard.ok_or(UrlShortenerErrorType::NotFound)?;
+    let cleared = guard.clear(None);
+    info!(admin = %admin.owner, cleared, "Cleared client penalties");
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ClearPenaltiesResponse { cleared })))
+}
+
+/// Lifts one client's penalty
+pub async fn clear_client_penalty(
+    ip: web::Path<String>,
+    admin: AdminKey,
+    guard: Option<web::Data<AbuseGuard>>,
+) -> UrlShortenerResult<HttpResponse> {
+    let guard = guard.ok_or(UrlShortenerErrorType::NotFound)?;
+    let ip = ip.parse().map_err(|_| UrlShortenerErrorType::InvalidInput(format!("'{}' is not an IP address", ip)))?;
+    let cleared = guard.clear(Some(ip));
+    info!(admin = %admin.owner, ip = %ip, cleared, "Cleared client penalty");
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ClearPenaltiesResponse { cleared })))
+}
@@ -0,0 +1,47 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::services::{UrlService, MAX_ALIAS_LENGTH};
+use crate::validation::{check_max_length, check_required, Validate, ValidationErrors};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AliasRequest {
+    pub custom_alias: String,
+}
+
+impl Validate for AliasRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_required(&mut errors, "custom_alias", &self.custom_alias);
+        check_max_length(&mut errors, "custom_alias", &self.custom_alias, MAX_ALIAS_LENGTH);
+        errors.into_result()
+    }
+}
+
+/// Gives one of the caller's links an extra code
+pub async fn add_alias(
+    short_code: web::Path<String>,
+    request: web::Json<AliasRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let alias = service.add_alias(&short_code, &api_key.owner, &request.custom_alias).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(alias)))
+}
+
+/// Takes an extra code off one of the caller's links
+pub async fn remove_alias(
+    path: web::Path<(String, String)>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (short_code, alias) = path.into_inner();
+    service.remove_alias(&short_code, &api_key.owner, &alias).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,103 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::services::UrlService;
+use crate::validation::{check_max_length, check_required, Validate, ValidationErrors};
+
+/// Maximum length of a campaign name, in characters
+pub const MAX_CAMPAIGN_NAME_LENGTH: usize = 100;
+
+/// Default and maximum number of days the campaign stats endpoint reports
+const DEFAULT_CAMPAIGN_DAYS: u32 = 30;
+pub const MAX_CAMPAIGN_DAYS: u32 = 366;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampaignRequest {
+    pub name: String,
+}
+
+impl Validate for CampaignRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if check_required(&mut errors, "name", &self.name) {
+            check_max_length(&mut errors, "name", self.name.trim(), MAX_CAMPAIGN_NAME_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampaignStatsQuery {
+    /// Days of visit events to report, including today
+    pub days: Option<u32>,
+}
+
+pub async fn create_campaign(
+    request: web::Json<CampaignRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let campaign = service.create_campaign(&api_key.owner, &request.name).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(campaign)))
+}
+
+pub async fn list_campaigns(
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let campaigns = service.list_campaigns(&api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(campaigns)))
+}
+
+/// Deletes one of the caller's campaigns; its links are kept
+pub async fn delete_campaign(
+    id: web::Path<i64>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    service.delete_campaign(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Adds one of the caller's links to one of their campaigns
+pub async fn add_campaign_link(
+    path: web::Path<(i64, String)>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (id, short_code) = path.into_inner();
+    service.add_campaign_link(id, &api_key.owner, &short_code).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn remove_campaign_link(
+    path: web::Path<(i64, String)>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (id, short_code) = path.into_inner();
+    service.remove_campaign_link(id, &api_key.owner, &short_code).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Visits across the links of one of the caller's campaigns
+pub async fn campaign_stats(
+    id: web::Path<i64>,
+    query: web::Query<CampaignStatsQuery>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let days = query.days.unwrap_or(DEFAULT_CAMPAIGN_DAYS).clamp(1, MAX_CAMPAIGN_DAYS);
+    let stats = service.campaign_stats(*id, &api_key.owner, days).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
+}
@@ -0,0 +1,122 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::{Collection, CollectionStats};
+use crate::services::UrlService;
+use crate::validation::{check_max_length, check_required, Validate, ValidationErrors};
+
+/// Maximum length of a collection name, in characters
+pub const MAX_COLLECTION_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionRequest {
+    pub name: String,
+}
+
+impl Validate for CollectionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if check_required(&mut errors, "name", &self.name) {
+            check_max_length(&mut errors, "name", self.name.trim(), MAX_COLLECTION_NAME_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectionResponse {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    /// Number of links in the collection
+    pub links: u64,
+    /// Visits across all links in the collection
+    pub visits: u64,
+}
+
+impl From<CollectionStats> for CollectionResponse {
+    fn from(stats: CollectionStats) -> Self {
+        Self {
+            id: stats.id,
+            name: stats.name,
+            created_at: stats.created_at.to_rfc3339(),
+            links: stats.links as u64,
+            visits: stats.visits as u64,
+        }
+    }
+}
+
+impl From<Collection> for CollectionResponse {
+    fn from(collection: Collection) -> Self {
+        CollectionStats {
+            id: collection.id,
+            owner: collection.owner,
+            name: collection.name,
+            created_at: collection.created_at,
+            links: 0,
+            visits: 0,
+        }
+        .into()
+    }
+}
+
+pub async fn create_collection(
+    request: web::Json<CollectionRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let collection = service.create_collection(&api_key.owner, &request.name).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CollectionResponse::from(collection))))
+}
+
+/// Lists the caller's collections with per-collection aggregates
+pub async fn list_collections(
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let collections = service.list_collections(&api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        collections.into_iter().map(CollectionResponse::from).collect::<Vec<_>>(),
+    )))
+}
+
+pub async fn get_collection(
+    id: web::Path<i64>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let collection = service.get_collection(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CollectionResponse::from(collection))))
+}
+
+pub async fn rename_collection(
+    id: web::Path<i64>,
+    request: web::Json<CollectionRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    service.rename_collection(*id, &api_key.owner, &request.name).await?;
+    let collection = service.get_collection(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CollectionResponse::from(collection))))
+}
+
+/// Deletes one of the caller's collections; its links are kept, unassigned
+pub async fn delete_collection(
+    id: web::Path<i64>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    service.delete_collection(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
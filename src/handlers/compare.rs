@@ -0,0 +1,68 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::services::UrlService;
+use crate::validation::{Validate, ValidationErrors};
+
+/// Most links one comparison can include
+pub const MAX_COMPARED_LINKS: usize = 10;
+
+/// Default and maximum number of days a comparison covers
+const DEFAULT_COMPARE_DAYS: u32 = 30;
+pub const MAX_COMPARE_DAYS: u32 = 366;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompareQuery {
+    /// Comma-separated short codes
+    pub codes: String,
+    /// Days to compare, ending today, like `30d`
+    pub period: Option<String>,
+}
+
+impl CompareQuery {
+    fn short_codes(&self) -> Vec<&str> {
+        self.codes.split(',').map(str::trim).filter(|code| !code.is_empty()).collect()
+    }
+
+    /// Days in `period`, if it is a whole number of days
+    fn days(&self) -> Option<u32> {
+        match &self.period {
+            Some(period) => period.trim().strip_suffix('d')?.parse().ok(),
+            None => Some(DEFAULT_COMPARE_DAYS),
+        }
+    }
+}
+
+impl Validate for CompareQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self.short_codes().len() {
+            0 => errors.add("codes", "required", "codes must list at least one short code"),
+            count if count > MAX_COMPARED_LINKS => {
+                errors.add("codes", "too_many", format!("At most {} links can be compared", MAX_COMPARED_LINKS))
+            }
+            _ => {}
+        }
+        if !self.days().is_some_and(|days| (1..=MAX_COMPARE_DAYS).contains(&days)) {
+            errors.add("period", "invalid", format!("Must be a number of days from 1d to {}d", MAX_COMPARE_DAYS));
+        }
+        errors.into_result()
+    }
+}
+
+/// Daily visits of several links side by side
+pub async fn compare_links(
+    query: web::Query<CompareQuery>,
+    api_key: Option<ApiKey>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let days = query.days().unwrap_or(DEFAULT_COMPARE_DAYS);
+    let viewer = api_key.map(|key| key.owner);
+    let comparison = service.compare_links(&query.short_codes(), days, viewer.as_deref()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(comparison)))
+}
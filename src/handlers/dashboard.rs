@@ -0,0 +1,82 @@
+use actix_web::{
+    http::header::{self, CacheControl, CacheDirective, ContentType},
+    web, HttpRequest, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::auth::{verify_origin, ApiKeys, DashboardSession, Sessions};
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::preview::escape_html;
+
+/// Where the dashboard is served; sign-in and sign-out redirect back to it
+pub const DASHBOARD_PATH: &str = "/dashboard";
+
+const LOGIN_PAGE: &str = include_str!("pages/login.html");
+const DASHBOARD_PAGE: &str = include_str!("pages/dashboard.html");
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginForm {
+    pub api_key: String,
+}
+
+/// Body of state-changing dashboard forms
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CsrfForm {
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+/// Shows the dashboard when signed in, and the sign-in form otherwise
+pub async fn dashboard(session: Option<DashboardSession>) -> HttpResponse {
+    let page = match session {
+        // The owner goes in last, so placeholders in its name are not filled
+        Some(session) => DASHBOARD_PAGE
+            .replace("{{csrf_token}}", session.csrf_token())
+            .replace("{{owner}}", &escape_html(&session.owner)),
+        None => LOGIN_PAGE.to_string(),
+    };
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(page)
+}
+
+/// Signs in with an API key, starting a new session
+pub async fn login(
+    req: HttpRequest,
+    form: web::Form<LoginForm>,
+    api_keys: web::Data<ApiKeys>,
+    sessions: web::Data<Sessions>,
+) -> UrlShortenerResult<HttpResponse> {
+    verify_origin(&req)?;
+
+    let Some(owner) = api_keys.authenticate(form.api_key.trim()).await? else {
+        warn!("Rejected dashboard sign-in with invalid API key");
+        return Err(UrlShortenerErrorType::Unauthorized.into());
+    };
+    let (_, token) = sessions.start(&owner).await?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, DASHBOARD_PATH))
+        .cookie(sessions.cookie(&token))
+        .finish())
+}
+
+pub async fn logout(
+    req: HttpRequest,
+    form: web::Form<CsrfForm>,
+    session: DashboardSession,
+    sessions: web::Data<Sessions>,
+) -> UrlShortenerResult<HttpResponse> {
+    verify_origin(&req)?;
+    session.verify_csrf(&form.csrf_token)?;
+
+    sessions.end(session.session()).await?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, DASHBOARD_PATH))
+        .cookie(sessions.removal_cookie())
+        .finish())
+}
@@ -0,0 +1,46 @@
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{Days, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AdminKey;
+use crate::errors::UrlShortenerResult;
+use crate::services::UrlService;
+use crate::validation::{Validate, ValidationErrors};
+
+/// Days of visit events, in UTC, both included
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VisitExportQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl Validate for VisitExportQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.to < self.from {
+            errors.add("to", "out_of_range", "to must not be before from");
+        }
+        errors.into_result()
+    }
+}
+
+/// Streams every link's visit events for a range of days as Parquet, for analytics warehouses
+pub async fn export_visits(
+    query: web::Query<VisitExportQuery>,
+    admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let from = query.from.and_time(NaiveTime::MIN).and_utc();
+    let to = (query.to + Days::new(1)).and_time(NaiveTime::MIN).and_utc();
+    let export = service.export_visits(from, to, &admin.owner).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.apache.parquet")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"visits-{}-{}.parquet\"", query.from, query.to),
+        ))
+        .streaming(export))
+}
@@ -0,0 +1,49 @@
+use actix_web::{http::header, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{ApiKey, DashboardSession};
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::favicons::{Favicons, CACHE_TTL};
+use crate::services::normalize_domain;
+use crate::validation::{check_max_length, check_required, Validate, ValidationErrors};
+
+/// Longest domain name DNS allows
+pub const MAX_DOMAIN_LENGTH: usize = 253;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FaviconQuery {
+    pub domain: String,
+}
+
+impl Validate for FaviconQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_required(&mut errors, "domain", &self.domain);
+        check_max_length(&mut errors, "domain", &self.domain, MAX_DOMAIN_LENGTH);
+        if !self.domain.trim().is_empty() && normalize_domain(&self.domain).is_none() {
+            errors.add("domain", "invalid_domain", "domain must be a domain name, like example.com");
+        }
+        errors.into_result()
+    }
+}
+
+/// A destination's favicon as a small PNG, for dashboards listing links. Open to dashboard
+/// sessions, whose image requests carry no API key, and to API keys.
+pub async fn favicon(
+    query: web::Query<FaviconQuery>,
+    session: Option<DashboardSession>,
+    api_key: Option<ApiKey>,
+    favicons: web::Data<Favicons>,
+) -> UrlShortenerResult<HttpResponse> {
+    if session.is_none() && api_key.is_none() {
+        return Err(UrlShortenerErrorType::Unauthorized.into());
+    }
+    query.validate()?;
+    let domain = normalize_domain(&query.domain).unwrap_or_default();
+
+    let icon = favicons.icon(&domain).await.ok_or(UrlShortenerErrorType::NotFound)?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header((header::CACHE_CONTROL, format!("private, max-age={}", CACHE_TTL.as_secs())))
+        .body(icon.to_vec()))
+}
@@ -0,0 +1,129 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::services::{UrlService, MAX_ALIAS_LENGTH, MAX_GO_LINKS, MAX_KEYWORD_DEPTH};
+use crate::validation::{check_max_length, check_required, check_url, Validate, ValidationErrors};
+
+/// Maximum length of a go link title
+pub const MAX_GO_TITLE_LENGTH: usize = 200;
+
+/// Maximum length of the owner a link is transferred to
+pub const MAX_OWNER_LENGTH: usize = 128;
+
+/// Default number of go links returned by a search or listing
+const DEFAULT_GO_LINKS: i64 = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoLinkRequest {
+    /// Human keyword, e.g. `payroll`; whitespace and underscores become dashes
+    pub keyword: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl Validate for GoLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_required(&mut errors, "keyword", &self.keyword);
+        // Each level is checked against the alias length when the keyword is created
+        check_max_length(&mut errors, "keyword", &self.keyword, MAX_ALIAS_LENGTH * MAX_KEYWORD_DEPTH);
+        check_url(&mut errors, "url", &self.url);
+        if let Some(title) = &self.title {
+            check_max_length(&mut errors, "title", title, MAX_GO_TITLE_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoLinksQuery {
+    /// Text to find in keywords and titles; every go link without one
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl GoLinksQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_GO_LINKS).clamp(1, MAX_GO_LINKS)
+    }
+}
+
+/// Creates a go link owned by the caller
+pub async fn create_go_link(
+    request: web::Json<GoLinkRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+    let request = request.into_inner();
+
+    let link = service.create_go_link(&request.keyword, request.url, request.title, &api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(link)))
+}
+
+/// Searches go links by keyword and title, most used first
+pub async fn search_go_links(
+    query: web::Query<GoLinksQuery>,
+    _api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let links = match query.q.as_deref().filter(|q| !q.trim().is_empty()) {
+        Some(q) => service.search_go_links(q, query.limit()).await?,
+        None => service.popular_go_links(query.limit()).await?,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(links)))
+}
+
+/// The most used go links
+pub async fn popular_go_links(
+    query: web::Query<GoLinksQuery>,
+    _api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let links = service.popular_go_links(query.limit()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(links)))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferRequest {
+    /// Owner the link is handed to, as their API keys name them
+    pub to: String,
+}
+
+impl Validate for TransferRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_required(&mut errors, "to", &self.to);
+        check_max_length(&mut errors, "to", &self.to, MAX_OWNER_LENGTH);
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferResponse {
+    pub short_code: String,
+    pub owner: String,
+}
+
+/// Hands one of the caller's links to another owner
+pub async fn transfer_link(
+    short_code: web::Path<String>,
+    request: web::Json<TransferRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let link = service.transfer_link(&short_code, &api_key.owner, &request.to).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(TransferResponse {
+        short_code: link.short_code,
+        owner: link.owner.unwrap_or_default(),
+    })))
+}
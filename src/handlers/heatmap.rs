@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::VisitHeatmap;
+use crate::services::UrlService;
+
+/// Default and maximum number of days of visits the heatmap counts
+const DEFAULT_HEATMAP_DAYS: u32 = 90;
+pub const MAX_HEATMAP_DAYS: u32 = 366;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeatmapQuery {
+    /// Days of visit events to count, up to now
+    pub days: Option<u32>,
+}
+
+/// A link's visits by weekday and hour
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkHeatmap {
+    pub short_url: String,
+    pub days: u32,
+    #[serde(flatten)]
+    pub heatmap: VisitHeatmap,
+}
+
+/// Visits of a link by day of the week and hour of the day, in UTC
+pub async fn visit_heatmap(
+    short_code: web::Path<String>,
+    query: web::Query<HeatmapQuery>,
+    api_key: Option<ApiKey>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let link = service.get_url_stats(&short_code).await?;
+    // As with its other statistics, only the owner sees a signed-only link's
+    if link.signed_only && link.owner != api_key.map(|key| key.owner) {
+        return Err(UrlShortenerErrorType::NotFound.into());
+    }
+
+    let days = query.days.unwrap_or(DEFAULT_HEATMAP_DAYS).clamp(1, MAX_HEATMAP_DAYS);
+    let heatmap = service.visit_heatmap(&link.short_code, days).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(LinkHeatmap { short_url: link.short_code, days, heatmap })))
+}
@@ -0,0 +1,28 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::import::{parse_export, ImportFormat};
+use crate::services::UrlService;
+
+/// Largest export accepted in one request, in bytes
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportQuery {
+    pub format: ImportFormat,
+}
+
+/// Imports a CSV export from another shortener, sent as the request body; links belong to the caller
+pub async fn import_links(
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let parsed = parse_export(query.format, body.as_ref())?;
+    let summary = service.import_links(parsed, Some(&api_key.owner)).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(summary)))
+}
@@ -0,0 +1,118 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::StoredApiKey;
+use crate::services::UrlService;
+use crate::validation::{check_max_length, Validate, ValidationErrors};
+
+/// Maximum length of an API key name, in characters
+pub const MAX_API_KEY_NAME_LENGTH: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyRequest {
+    /// Label for the key, e.g. the integration that uses it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl CreateApiKeyRequest {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref().map(str::trim).filter(|name| !name.is_empty())
+    }
+}
+
+impl Validate for CreateApiKeyRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(name) = self.name() {
+            check_max_length(&mut errors, "name", name, MAX_API_KEY_NAME_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyResponse {
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    pub created_at: String,
+    /// When the key last authenticated a request; `null` if it never has
+    pub last_used_at: Option<String>,
+}
+
+impl From<StoredApiKey> for ApiKeyResponse {
+    fn from(key: StoredApiKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name,
+            prefix: key.prefix,
+            created_at: key.created_at.to_rfc3339(),
+            last_used_at: key.last_used_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+/// A newly issued key, including its plaintext, which is never shown again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedApiKeyResponse {
+    #[serde(flatten)]
+    pub details: ApiKeyResponse,
+    pub key: String,
+}
+
+impl From<(StoredApiKey, String)> for IssuedApiKeyResponse {
+    fn from((stored, key): (StoredApiKey, String)) -> Self {
+        Self { details: stored.into(), key }
+    }
+}
+
+/// Issues a new API key for the caller
+pub async fn create_api_key(
+    request: web::Json<CreateApiKeyRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let issued = service.create_api_key(&api_key.owner, request.name()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(IssuedApiKeyResponse::from(issued))))
+}
+
+/// Lists the caller's active API keys, without their plaintext
+pub async fn list_api_keys(
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let keys = service.list_api_keys(&api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        keys.into_iter().map(ApiKeyResponse::from).collect::<Vec<_>>(),
+    )))
+}
+
+pub async fn revoke_api_key(
+    id: web::Path<i64>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    service.revoke_api_key(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Replaces one of the caller's keys with a freshly issued one
+pub async fn rotate_api_key(
+    id: web::Path<i64>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let issued = service.rotate_api_key(*id, &api_key.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(IssuedApiKeyResponse::from(issued))))
+}
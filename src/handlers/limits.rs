@@ -0,0 +1,25 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::ApiResponse;
+use crate::services::UrlService;
+
+/// Limits that apply to the caller's requests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiLimits {
+    /// Tier the caller is in: `anonymous` without an API key, `default` unless assigned one
+    pub tier: String,
+    /// Longest `original_url` the caller may shorten, in characters
+    pub max_url_length: usize,
+}
+
+/// Reports the limits in effect for the calling API key, or for callers without one
+pub async fn api_limits(api_key: Option<ApiKey>, service: web::Data<UrlService>) -> HttpResponse {
+    let owner = api_key.as_ref().map(|key| key.owner.as_str());
+    let limits = service.url_length_limits();
+    HttpResponse::Ok().json(ApiResponse::success(ApiLimits {
+        tier: limits.tier(owner).to_string(),
+        max_url_length: limits.for_owner(owner),
+    }))
+}
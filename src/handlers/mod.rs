@@ -1,65 +1,791 @@
-use actix_web::{web, HttpResponse};
-use serde::{Deserialize, Serialize};
-use crate::services::UrlService;
-use crate::errors::UrlShortenerResult;
+use actix_web::{
+    error::{JsonPayloadError, QueryPayloadError, UrlencodedError},
+    http::header::{self, ContentType, HeaderName, HeaderValue},
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use chrono::Utc;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use tracing::debug;
+use url::Url;
+use crate::auth::ApiKey;
+use crate::config::Config;
+use crate::models::{first_version, LinkAlias, MetadataUpdate, RedirectHeaders, RetiredCode, StatsOnRegenerate};
+use crate::middleware::anonymized_client;
+use crate::preview::{is_social_crawler_request, LinkPreviews};
+use crate::redirect::{
+    check_redirect_header, not_found_page, tracking_page, MissingCodePolicy, Redirector, BUSY_PAGE, MAX_REDIRECT_HEADERS,
+};
+use crate::services::{CodeStyle, CreateOptions, Namespace, ShortenedUrl, UrlService, MAX_ALIAS_LENGTH};
+use crate::errors::{ApiResponse, UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::validation::{
+    check_max_length, check_required, check_url, idn_host, IdnHost, UrlWarning, Validate, ValidationErrors,
+};
+
+mod admin;
+mod aliases;
+mod campaigns;
+mod collections;
+mod compare;
+mod dashboard;
+#[cfg(feature = "parquet-export")]
+mod export;
+#[cfg(feature = "favicon-proxy")]
+mod favicons;
+mod go_links;
+mod heatmap;
+mod import;
+mod keys;
+mod limits;
+mod previews;
+#[cfg(feature = "pprof")]
+mod profile;
+mod quick;
+mod receipts;
+mod resolve;
+mod robots;
+mod sources;
+mod urls;
+mod usage;
+mod version;
+mod webhooks;
+
+pub use admin::{
+    adjust_visits, ban_domain, blocked_domains, clear_client_penalty, clear_penalties, enable_link, flush_cache,
+    invalidate_cached_code, log_level, outbound_stats, panic_count, penalized_clients, reset_visits, runtime_stats,
+    set_log_level, slo_stats, slow_query_stats, statement_cache_stats, throttle_stats, AdjustVisitsRequest, BanDomainRequest,
+    CacheFlushResponse, ClearPenaltiesResponse, EnableLinkRequest, EnableLinkResponse, LogLevelRequest,
+    LogLevelResponse, PanicCountResponse,
+    PenalizedClientsResponse, ResetVisitsRequest, RuntimeResponse, TokioStats, VisitCountResponse,
+    MAX_LOG_FILTER_LENGTH, MAX_REASON_LENGTH, MAX_VISIT_ADJUSTMENT,
+};
+pub use aliases::{add_alias, remove_alias, AliasRequest};
+pub use campaigns::{
+    add_campaign_link, campaign_stats, create_campaign, delete_campaign, list_campaigns, remove_campaign_link,
+    CampaignRequest, CampaignStatsQuery, MAX_CAMPAIGN_DAYS, MAX_CAMPAIGN_NAME_LENGTH,
+};
+pub use collections::{
+    create_collection, delete_collection, get_collection, list_collections, rename_collection, CollectionRequest,
+    CollectionResponse, MAX_COLLECTION_NAME_LENGTH,
+};
+pub use compare::{compare_links, CompareQuery, MAX_COMPARED_LINKS, MAX_COMPARE_DAYS};
+pub use dashboard::{dashboard, login, logout, CsrfForm, LoginForm, DASHBOARD_PATH};
+#[cfg(feature = "parquet-export")]
+pub use export::{export_visits, VisitExportQuery};
+#[cfg(feature = "favicon-proxy")]
+pub use favicons::{favicon, FaviconQuery, MAX_DOMAIN_LENGTH};
+pub use go_links::{
+    create_go_link, popular_go_links, search_go_links, transfer_link, GoLinkRequest, GoLinksQuery, TransferRequest,
+    TransferResponse, MAX_GO_TITLE_LENGTH, MAX_OWNER_LENGTH,
+};
+pub use heatmap::{visit_heatmap, HeatmapQuery, LinkHeatmap, MAX_HEATMAP_DAYS};
+pub use import::{import_links, ImportQuery, MAX_IMPORT_BYTES};
+pub use keys::{
+    create_api_key, list_api_keys, revoke_api_key, rotate_api_key, ApiKeyResponse, CreateApiKeyRequest,
+    IssuedApiKeyResponse, MAX_API_KEY_NAME_LENGTH,
+};
+pub use limits::{api_limits, ApiLimits};
+#[cfg(feature = "og-image")]
+pub use previews::preview_image;
+pub use previews::{link_preview, LinkPreviewResponse};
+#[cfg(feature = "pprof")]
+pub use profile::{
+    cpu_profile, ProfileQuery, DEFAULT_PROFILE_FREQUENCY, DEFAULT_PROFILE_SECONDS, MAX_PROFILE_FREQUENCY,
+    MAX_PROFILE_SECONDS,
+};
+pub use quick::{quick_shorten, recent_links};
+pub use receipts::{verify_receipt, ReceiptQuery};
+pub use resolve::{
+    resolve_batch, resolve_link, ResolveBatchRequest, ResolveResponse, ResolveStatus, ResolvedCode, MAX_RESOLVE_BATCH,
+};
+pub use robots::{robots_txt, RobotsTxt, DEFAULT_ROBOTS_TXT};
+pub use sources::{visit_sources, LinkSources, SourcesQuery, MAX_SOURCE_DAYS};
+pub use urls::{list_urls, ListUrlsQuery};
+pub use usage::{usage, usage_csv, UsageQuery, UsageResponse};
+pub use version::{version, BuildInfo};
+pub use webhooks::{replay_webhook_delivery, webhook_deliveries, WebhookDeliveriesQuery};
 
 // Request/Response models
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateUrlRequest {
     pub original_url: String,
+    /// Short code to use instead of a generated one; may contain any Unicode letters or emoji
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_alias: Option<String>,
+    /// Kind of code to generate when no alias is given: `random` (default) or `words`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<CodeStyle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// One of the caller's collections to put the link in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<i64>,
+    /// Append any path and query after the short code to the destination
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pass_through: bool,
+    /// Only redirect through signed URLs from `POST /api/links/{code}/sign`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub signed_only: bool,
+    /// Analytics pixel or tag manager snippet fired on a page that forwards visitors at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracking_snippet: Option<String>,
+    /// Extra response headers to send with redirects, e.g. `{"Referrer-Policy": "no-referrer"}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_headers: Option<RedirectHeaders>,
+    /// Keep destinations from seeing the page the link was clicked on
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_referrer: bool,
+    /// Namespace to create the link in, served under `/{namespace}/{code}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+impl Validate for CreateUrlRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_url(&mut errors, "original_url", &self.original_url);
+        if let Some(alias) = &self.custom_alias {
+            check_required(&mut errors, "custom_alias", alias);
+            check_max_length(&mut errors, "custom_alias", alias, MAX_ALIAS_LENGTH);
+        }
+        check_metadata(&mut errors, &self.title, &self.notes, &self.created_by);
+        check_tracking_snippet(&mut errors, &self.tracking_snippet);
+        check_redirect_headers(&mut errors, &self.redirect_headers);
+        errors.into_result()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateUrlResponse {
     pub short_url: String,
     pub original_url: String,
+    /// Findings about the original URL worth surfacing to the client, such as a lookalike host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<UrlWarning>,
+}
+
+/// Changes to a link's descriptive fields; omitted fields are kept and empty strings clear them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UpdateLinkRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// Collection to move the link to; `null` removes it from its collection
+    #[serde(default, deserialize_with = "explicit_null", skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<Option<i64>>,
+    /// Visit count to be notified about once; `null` removes the goal
+    #[serde(default, deserialize_with = "explicit_null", skip_serializing_if = "Option::is_none")]
+    pub click_goal: Option<Option<i64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass_through: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_only: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracking_snippet: Option<String>,
+    /// Replaces the extra redirect headers; `{}` removes them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_headers: Option<RedirectHeaders>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hide_referrer: Option<bool>,
+    /// Version the changes were made against, for clients that cannot send `If-Match`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+}
+
+impl Validate for UpdateLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_metadata(&mut errors, &self.title, &self.notes, &self.created_by);
+        check_tracking_snippet(&mut errors, &self.tracking_snippet);
+        check_redirect_headers(&mut errors, &self.redirect_headers);
+        if let Some(Some(goal)) = self.click_goal {
+            if goal < 1 {
+                errors.add("click_goal", "out_of_range", "click_goal must be at least 1");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+impl From<UpdateLinkRequest> for MetadataUpdate {
+    fn from(request: UpdateLinkRequest) -> Self {
+        Self {
+            title: request.title.map(non_blank),
+            notes: request.notes.map(non_blank),
+            created_by: request.created_by.map(non_blank),
+            collection_id: request.collection_id,
+            click_goal: request.click_goal,
+            pass_through: request.pass_through,
+            signed_only: request.signed_only,
+            tracking_snippet: request.tracking_snippet.map(non_blank),
+            redirect_headers: request.redirect_headers.map(normalized_headers),
+            hide_referrer: request.hide_referrer,
+            expected_version: request.version,
+        }
+    }
+}
+
+/// Maximum lengths of the descriptive link fields, in characters
+pub const MAX_TITLE_LENGTH: usize = 200;
+pub const MAX_NOTES_LENGTH: usize = 2000;
+pub const MAX_CREATED_BY_LENGTH: usize = 100;
+/// Maximum length of a link's analytics snippet, in characters
+pub const MAX_TRACKING_SNIPPET_LENGTH: usize = 4000;
+
+fn check_metadata(
+    errors: &mut ValidationErrors,
+    title: &Option<String>,
+    notes: &Option<String>,
+    created_by: &Option<String>,
+) {
+    let fields = [
+        ("title", title, MAX_TITLE_LENGTH),
+        ("notes", notes, MAX_NOTES_LENGTH),
+        ("created_by", created_by, MAX_CREATED_BY_LENGTH),
+    ];
+    for (field, value, max) in fields {
+        if let Some(value) = value {
+            check_max_length(errors, field, value.trim(), max);
+        }
+    }
+}
+
+fn check_tracking_snippet(errors: &mut ValidationErrors, snippet: &Option<String>) {
+    if let Some(snippet) = snippet {
+        check_max_length(errors, "tracking_snippet", snippet.trim(), MAX_TRACKING_SNIPPET_LENGTH);
+    }
+}
+
+fn check_redirect_headers(errors: &mut ValidationErrors, headers: &Option<RedirectHeaders>) {
+    let Some(headers) = headers else { return };
+    if headers.len() > MAX_REDIRECT_HEADERS {
+        errors.add(
+            "redirect_headers",
+            "too_many",
+            format!("redirect_headers may set at most {} headers", MAX_REDIRECT_HEADERS),
+        );
+    }
+    for (name, value) in headers {
+        if let Some(message) = check_redirect_header(name, value.trim()) {
+            errors.add("redirect_headers", "not_allowed", message);
+        }
+    }
+}
+
+/// Redirect headers as stored: lowercase names and trimmed values, `None` when there are none
+fn normalized_headers(headers: RedirectHeaders) -> Option<RedirectHeaders> {
+    let headers: RedirectHeaders = headers
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    (!headers.is_empty()).then_some(headers)
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from an omitted field (`None`)
+fn explicit_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Trims a descriptive field, treating blank input as absent
+fn non_blank(value: String) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UrlStats {
     pub short_url: String,
     pub original_url: String,
+    /// Punycode and Unicode forms of the destination's host, when it is internationalized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idn_host: Option<IdnHost>,
     pub visits: u64,
     pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_goal: Option<i64>,
+    /// When the click goal was reached and notified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal_reached_at: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pass_through: bool,
+    /// Content type the destination reported when the link was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// When an admin disabled the link; it no longer redirects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub signed_only: bool,
+    /// Analytics snippet fired on the way to the destination
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracking_snippet: Option<String>,
+    /// Extra response headers sent with redirects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_headers: Option<RedirectHeaders>,
+    /// Redirects withhold the referrer from the destination
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_referrer: bool,
+    /// Revision of the editable fields, also sent as the `ETag`
+    #[serde(default = "first_version")]
+    pub version: i64,
+    /// First and most recent recorded redirect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_visited_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_visited_at: Option<String>,
+    /// Recorded redirects in the last day and week; only in a single link's statistics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visits_last_24h: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visits_last_7d: Option<u64>,
+    /// Codes the link was moved away from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired_codes: Vec<RetiredCode>,
+    /// Extra codes that redirect to the link
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<LinkAlias>,
+}
+
+impl From<ShortenedUrl> for UrlStats {
+    fn from(url: ShortenedUrl) -> Self {
+        Self {
+            idn_host: Url::parse(&url.original_url).ok().as_ref().and_then(idn_host),
+            short_url: url.short_code,
+            original_url: url.original_url,
+            visits: url.visits,
+            created_at: url.created_at.to_rfc3339(),
+            title: url.title,
+            notes: url.notes,
+            created_by: url.created_by,
+            collection_id: url.collection_id,
+            click_goal: url.click_goal,
+            goal_reached_at: url.goal_reached_at.map(|at| at.to_rfc3339()),
+            pass_through: url.pass_through,
+            content_type: url.content_type,
+            disabled_at: url.disabled_at.map(|at| at.to_rfc3339()),
+            signed_only: url.signed_only,
+            tracking_snippet: url.tracking_snippet,
+            redirect_headers: url.redirect_headers,
+            hide_referrer: url.hide_referrer,
+            version: url.version,
+            first_visited_at: None,
+            last_visited_at: url.last_visited_at.map(|at| at.to_rfc3339()),
+            visits_last_24h: None,
+            visits_last_7d: None,
+            retired_codes: Vec::new(),
+            aliases: Vec::new(),
+        }
+    }
+}
+
+/// Reports malformed JSON bodies through `UrlShortenerError` so they share the API error format
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    debug!(error = %err, "Rejected malformed JSON payload");
+    UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput(err.to_string()), err).into()
+}
+
+/// Reports malformed query strings through `UrlShortenerError` so they share the API error format
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    debug!(error = %err, "Rejected malformed query string");
+    UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput(err.to_string()), err).into()
+}
+
+/// Reports malformed form bodies through `UrlShortenerError` so they share the API error format
+pub fn form_error_handler(err: UrlencodedError, _req: &HttpRequest) -> actix_web::Error {
+    debug!(error = %err, "Rejected malformed form body");
+    UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput(err.to_string()), err).into()
 }
 
 // Handler functions
 pub async fn create_url(
     request: web::Json<CreateUrlRequest>,
+    api_key: Option<ApiKey>,
     service: web::Data<UrlService>,
 ) -> UrlShortenerResult<HttpResponse> {
-    let shortened_url = service.create_short_url(request.original_url.clone()).await?;
+    request.validate()?;
+    // Only the owner can sign a link, so a signed-only link needs one; third-party scripts
+    // are only served on behalf of a known owner
+    if (request.signed_only || request.tracking_snippet.is_some()) && api_key.is_none() {
+        return Err(UrlShortenerErrorType::Unauthorized.into());
+    }
+
+    // A valid API key is optional here, but attributes the link to its owner
+    let request = request.into_inner();
+    let options = CreateOptions {
+        owner: api_key.map(|key| key.owner),
+        custom_alias: request.custom_alias,
+        style: request.style.unwrap_or_default(),
+        title: request.title.and_then(non_blank),
+        notes: request.notes.and_then(non_blank),
+        created_by: request.created_by.and_then(non_blank),
+        collection_id: request.collection_id,
+        pass_through: request.pass_through,
+        signed_only: request.signed_only,
+        tracking_snippet: request.tracking_snippet.and_then(non_blank),
+        redirect_headers: request.redirect_headers.and_then(normalized_headers),
+        hide_referrer: request.hide_referrer,
+        namespace: request.namespace,
+    };
+    let shortened_url = service
+        .create_short_url_with(request.original_url, options)
+        .await?;
     
-    Ok(HttpResponse::Ok().json(CreateUrlResponse {
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CreateUrlResponse {
         short_url: shortened_url.short_code,
         original_url: shortened_url.original_url,
-    }))
+        warnings: shortened_url.warnings,
+    })))
+}
+
+/// Asks search engines not to index a response
+const X_ROBOTS_TAG: HeaderName = HeaderName::from_static("x-robots-tag");
+
+/// Short code a redirect request asks for, qualified with the namespace its route is mounted in
+pub fn requested_code(req: &HttpRequest) -> Option<Cow<'_, str>> {
+    let short_code = req.match_info().get("short_code")?;
+    Some(match req.app_data::<web::Data<Namespace>>() {
+        Some(namespace) => Cow::Owned(namespace.qualify(&namespace.keyword(short_code))),
+        None => Cow::Borrowed(short_code),
+    })
 }
 
+/// Redirect hot path: resolves through the redirect cache instead of `UrlService`
 pub async fn redirect(
-    short_code: web::Path<String>,
-    service: web::Data<UrlService>,
+    req: HttpRequest,
+    redirector: web::Data<Redirector>,
+    previews: Option<web::Data<LinkPreviews>>,
 ) -> UrlShortenerResult<HttpResponse> {
-    let original_url = service.get_original_url(&short_code).await?;
-    
-    Ok(HttpResponse::Found()
-        .append_header(("Location", original_url))
-        .finish())
+    let short_code = requested_code(&req).unwrap_or_default();
+    let mut response = match redirect_response(&req, &short_code, &redirector, previews).await {
+        Ok(response) => response,
+        // Visitors of unknown codes may get a page or a fallback site instead of the API's JSON,
+        // with the nearest existing codes when a typo index is configured
+        Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => match redirector.missing_code() {
+            MissingCodePolicy::Json => {
+                let suggestions = redirector.suggestions(&short_code);
+                if suggestions.is_empty() {
+                    return Err(e);
+                }
+                e.response_with_details(Some(serde_json::json!({ "suggestions": suggestions })))
+            }
+            MissingCodePolicy::Page => HttpResponse::NotFound()
+                .content_type(ContentType::html())
+                .body(not_found_page(&redirector.suggestions(&short_code)).into_owned()),
+            policy => HttpResponse::Found()
+                .insert_header((header::LOCATION, policy.fallback_url(&short_code).unwrap_or_default()))
+                // The code may be created any moment; never replay this redirect
+                .insert_header((header::CACHE_CONTROL, "no-store"))
+                .finish(),
+        },
+        Err(e) => return Err(e),
+    };
+
+    if redirector.noindex() {
+        response.headers_mut().insert(X_ROBOTS_TAG, HeaderValue::from_static("noindex"));
+    }
+    Ok(response)
+}
+
+async fn redirect_response(
+    req: &HttpRequest,
+    short_code: &str,
+    redirector: &Redirector,
+    previews: Option<web::Data<LinkPreviews>>,
+) -> UrlShortenerResult<HttpResponse> {
+    // Crawlers unfurling a shared link get the destination's preview tags rather than a redirect;
+    // signed-only links reveal nothing about their destination without a signature
+    if let Some(previews) = previews.filter(|_| is_social_crawler_request(req)) {
+        let url = redirector.link(short_code).await?;
+        if !url.signed_only {
+            return Ok(HttpResponse::Ok().content_type(ContentType::html()).body(previews.render(&url).await));
+        }
+    }
+
+    // Whatever follows the code is passed on raw, so its percent-encoding reaches the destination as sent;
+    // a namespace adds a path segment before the code, and keywords may span several
+    let code_segments = req.match_info().get("short_code").map_or(1, |code| code.split('/').count());
+    let segments = code_segments + usize::from(req.app_data::<web::Data<Namespace>>().is_some());
+    let mut rest = req.uri().path();
+    for _ in 0..segments {
+        rest = rest.get(1..).and_then(|tail| tail.find('/')).map_or("", |end| &rest[end + 1..]);
+    }
+    let visitor = anonymized_client(req);
+    let redirect = match redirector.resolve_path(short_code, rest, req.query_string(), visitor.as_deref()).await {
+        Ok(redirect) => redirect,
+        // Browsers following a flooded link get a page rather than a JSON error; letting caches
+        // keep it briefly absorbs the flood before it reaches us
+        Err(e) if matches!(e.error_type, UrlShortenerErrorType::RateLimitExceeded) => {
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, "1"))
+                .insert_header((header::CACHE_CONTROL, "public, max-age=1"))
+                .content_type(ContentType::html())
+                .body(BUSY_PAGE));
+        },
+        Err(e) => return Err(e),
+    };
+
+    let mut response = match redirect.tracking_snippet {
+        Some(_) => HttpResponse::Ok(),
+        None => HttpResponse::Found(),
+    };
+    // The link's own headers go first, so they never replace what the redirect itself needs
+    for (name, value) in redirect.headers.iter().flat_map(|headers| headers.iter()) {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::from_str(value)) {
+            response.insert_header((name, value));
+        }
+    }
+    // Browsers carry a redirect's policy over to the request it leads to, and a forwarding page's
+    // to its navigation, so the destination gets no referrer either way
+    if redirect.hide_referrer {
+        response.insert_header((header::REFERRER_POLICY, "no-referrer"));
+    }
+    // The owner's trackers fire on a page that forwards right after; it is never kept by caches,
+    // so every visit runs them. The sandbox gives the page an origin of its own, so the owner's
+    // script cannot read our cookies or make requests to the dashboard as the visitor.
+    if let Some(snippet) = &redirect.tracking_snippet {
+        return Ok(response
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .insert_header((header::CONTENT_SECURITY_POLICY, "sandbox allow-scripts"))
+            .content_type(ContentType::html())
+            .body(tracking_page(&redirect.url, snippet)));
+    }
+    response.insert_header((header::LOCATION, &*redirect.url));
+    // A receipt vouches for one redirect; replaying it would vouch for visits that never happened
+    if redirector.receipts().is_some() {
+        response.insert_header((header::CACHE_CONTROL, "no-store"));
+    }
+    Ok(response.finish())
 }
 
 pub async fn get_stats(
     short_code: web::Path<String>,
+    api_key: Option<ApiKey>,
     service: web::Data<UrlService>,
 ) -> UrlShortenerResult<HttpResponse> {
     let stats = service.get_url_stats(&short_code).await?;
-    
-    Ok(HttpResponse::Ok().json(UrlStats {
-        short_url: stats.short_code,
-        original_url: stats.original_url,
-        visits: stats.visits,
-        created_at: stats.created_at.to_rfc3339(),
-    }))
+    let is_owner = stats.owner.is_some() && stats.owner == api_key.map(|key| key.owner);
+    // The destination of a signed-only link is only shown to its owner
+    if stats.signed_only && !is_owner {
+        return Err(UrlShortenerErrorType::NotFound.into());
+    }
+
+    let retired_codes = service.retired_codes(&stats.short_code).await?;
+    let aliases = service.aliases(&stats.short_code).await?;
+    let timeline = service.visit_timeline(&stats.short_code).await?;
+    // The owner's analytics snippet and redirect headers are theirs alone
+    let tracking_snippet = stats.tracking_snippet.clone().filter(|_| is_owner);
+    let redirect_headers = stats.redirect_headers.clone().filter(|_| is_owner);
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_tag(stats.version))
+        .json(ApiResponse::success(UrlStats {
+            tracking_snippet,
+            redirect_headers,
+            first_visited_at: timeline.first_visited_at.map(|at| at.to_rfc3339()),
+            // Counted visits without recorded events still have a time
+            last_visited_at: timeline.last_visited_at.or(stats.last_visited_at).map(|at| at.to_rfc3339()),
+            visits_last_24h: Some(timeline.visits_last_24h as u64),
+            visits_last_7d: Some(timeline.visits_last_7d as u64),
+            retired_codes,
+            aliases,
+            ..UrlStats::from(stats)
+        })))
+}
+
+/// Changes the title, notes, creator, pass-through, signing, tracking, redirect headers or referrer
+/// hiding of one of the caller's links.
+/// With `If-Match` or a `version`, the change is only made while the link is at that version.
+pub async fn update_link(
+    req: HttpRequest,
+    short_code: web::Path<String>,
+    request: web::Json<UpdateLinkRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+    redirector: Option<web::Data<Redirector>>,
+    config: Option<web::Data<Config>>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let request = request.into_inner();
+    let changes_redirect = request.pass_through.is_some()
+        || request.signed_only.is_some()
+        || request.tracking_snippet.is_some()
+        || request.redirect_headers.is_some()
+        || request.hide_referrer.is_some();
+    let mut update = MetadataUpdate::from(request);
+    match req.get_header::<header::IfMatch>() {
+        // `If-Match: *` asks for no version check
+        Some(header::IfMatch::Any) => update.expected_version = None,
+        Some(header::IfMatch::Items(tags)) => update.expected_version = Some(matched_version(&tags)?),
+        None if update.expected_version.is_none() && config.is_some_and(|config| config.require_if_match) => {
+            return Err(UrlShortenerErrorType::PreconditionRequired.into());
+        }
+        None => {}
+    }
+
+    let updated = service.update_metadata(&short_code, &api_key.owner, update).await?;
+    // Redirects read pass-through, signing, tracking, headers and referrer hiding from the cached target
+    if let Some(redirector) = redirector.filter(|_| changes_redirect) {
+        redirector.invalidate(&updated.short_code);
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_tag(updated.version))
+        .json(ApiResponse::success(UrlStats::from(updated))))
+}
+
+/// A link's version as its `ETag`
+fn version_tag(version: i64) -> header::ETag {
+    header::ETag(header::EntityTag::new_strong(version.to_string()))
+}
+
+/// The version named by an `If-Match` header. Only one version can be matched, and weak tags
+/// never match, as `If-Match` compares strongly.
+fn matched_version(tags: &[header::EntityTag]) -> UrlShortenerResult<i64> {
+    match tags {
+        [tag] if !tag.weak => tag.tag().parse().map_err(|_| {
+            UrlShortenerErrorType::PreconditionFailed(format!("'{}' is not a version of the link", tag.tag())).into()
+        }),
+        _ => Err(UrlShortenerErrorType::PreconditionFailed("If-Match must name a single version".to_string()).into()),
+    }
+}
+
+/// Longest validity of a signed URL, in seconds
+pub const MAX_SIGNED_URL_TTL_SECS: u64 = 366 * 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignLinkRequest {
+    /// How long the signed URL stays valid
+    pub expires_in_secs: u64,
+}
+
+impl Validate for SignLinkRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if !(1..=MAX_SIGNED_URL_TTL_SECS).contains(&self.expires_in_secs) {
+            errors.add(
+                "expires_in_secs",
+                "out_of_range",
+                format!("expires_in_secs must be between 1 and {}", MAX_SIGNED_URL_TTL_SECS),
+            );
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedLinkResponse {
+    pub signed_url: String,
+    pub expires_at: String,
+}
+
+/// Issues a time-boxed signed URL for one of the caller's links; 404 unless signing is enabled
+pub async fn sign_link(
+    short_code: web::Path<String>,
+    request: web::Json<SignLinkRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+    redirector: web::Data<Redirector>,
+    config: web::Data<Config>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+    let signer = redirector.signer().ok_or(UrlShortenerErrorType::NotFound)?;
+
+    let link = service.get_owned_link(&short_code, &api_key.owner).await?;
+    let expires_at = Utc::now() + chrono::Duration::seconds(request.expires_in_secs as i64);
+    let signed_url = format!(
+        "{}?{}",
+        config.short_link(&link.short_code),
+        signer.signed_query(&link.short_code, expires_at)
+    );
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(SignedLinkResponse {
+        signed_url,
+        expires_at: expires_at.to_rfc3339(),
+    })))
+}
+
+/// Longest time the old code of a regenerated link keeps redirecting, in seconds
+pub const MAX_GRACE_PERIOD_SECS: u64 = 366 * 24 * 60 * 60;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RegenerateCodeRequest {
+    /// Whether the link keeps its visits or leaves them with the old code
+    #[serde(default)]
+    pub stats: StatsOnRegenerate,
+    /// How long the old code keeps redirecting; 0 retires it at once
+    #[serde(default)]
+    pub grace_period_secs: u64,
+    /// Kind of code to generate
+    #[serde(default)]
+    pub style: CodeStyle,
+}
+
+impl Validate for RegenerateCodeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.grace_period_secs > MAX_GRACE_PERIOD_SECS {
+            errors.add(
+                "grace_period_secs",
+                "out_of_range",
+                format!("grace_period_secs must be at most {}", MAX_GRACE_PERIOD_SECS),
+            );
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegeneratedCodeResponse {
+    #[serde(flatten)]
+    pub link: UrlStats,
+    /// The code the link left
+    pub retired: RetiredCode,
+}
+
+/// Gives one of the caller's links a new code, e.g. after the old one leaked
+pub async fn regenerate_code(
+    short_code: web::Path<String>,
+    request: web::Json<RegenerateCodeRequest>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+    redirector: Option<web::Data<Redirector>>,
+) -> UrlShortenerResult<HttpResponse> {
+    request.validate()?;
+
+    let grace_period = chrono::Duration::seconds(request.grace_period_secs as i64);
+    let (link, retired) = service
+        .regenerate_code(&short_code, &api_key.owner, request.style, request.stats, grace_period)
+        .await?;
+    // The old code's cached target would keep redirecting past the grace period
+    if let Some(redirector) = redirector {
+        redirector.invalidate(&retired.short_code);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(RegeneratedCodeResponse {
+        link: UrlStats::from(link),
+        retired,
+    })))
 }
 
 #[cfg(test)]
-mod tests; 
\ No newline at end of file
+mod tests; 
@@ -0,0 +1,87 @@
+use actix_web::{web, HttpResponse};
+#[cfg(feature = "og-image")]
+use actix_web::http::header;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+#[cfg(feature = "og-image")]
+use crate::errors::UrlShortenerError;
+use crate::preview::{favicon_url, resolve, LinkPreviews};
+#[cfg(feature = "og-image")]
+use crate::preview::{preview_title, render_preview_image};
+use crate::redirect::Redirector;
+use crate::services::{Safety, UrlService};
+
+/// Branded preview card of a link, referenced as `og:image` by its preview page
+#[cfg(feature = "og-image")]
+pub async fn preview_image(
+    short_code: web::Path<String>,
+    redirector: web::Data<Redirector>,
+    previews: web::Data<LinkPreviews>,
+) -> UrlShortenerResult<HttpResponse> {
+    let url = redirector.link(&short_code).await?;
+    let title = preview_title(&url, &previews.tags(&url.original_url).await);
+    let short_link = previews.short_link(&url.short_url);
+
+    // Rendering is CPU-bound; keep it off the async workers
+    let png = web::block(move || render_preview_image(&title, &short_link))
+        .await
+        .map_err(|e| {
+            UrlShortenerError::with_source(
+                UrlShortenerErrorType::InternalError("Preview image rendering was cancelled".to_string()),
+                e,
+            )
+        })??;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .body(png))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkPreviewResponse {
+    pub short_code: String,
+    pub short_url: String,
+    /// The destination
+    pub url: String,
+    /// The destination's title, else the link's own
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// The destination's `og:image`, made absolute
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+    pub favicon_url: Option<String>,
+    pub safety: Safety,
+}
+
+/// What a link leads to, for chat bots rendering a preview without following the redirect.
+/// Signed-only links reveal nothing, as for crawlers.
+pub async fn link_preview(
+    short_code: web::Path<String>,
+    redirector: web::Data<Redirector>,
+    previews: Option<web::Data<LinkPreviews>>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let Some(previews) = previews else {
+        return Err(UrlShortenerErrorType::NotFound.into());
+    };
+    let url = redirector.link(&short_code).await?;
+    if url.signed_only {
+        return Err(UrlShortenerErrorType::NotFound.into());
+    }
+
+    let tags = previews.tags(&url.original_url).await;
+    let safety = service.destination_safety(&url.original_url).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(LinkPreviewResponse {
+        short_url: previews.short_link(&url.short_url),
+        favicon_url: favicon_url(&url.original_url, &tags),
+        title: tags.title.or(url.title),
+        description: tags.description,
+        image: tags.image.as_deref().and_then(|image| resolve(&url.original_url, image)).map(String::from),
+        site_name: tags.site_name,
+        safety,
+        short_code: url.short_url,
+        url: url.original_url,
+    })))
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::AdminKey;
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+
+/// Seconds sampled when the query does not say
+pub const DEFAULT_PROFILE_SECONDS: u64 = 10;
+
+/// Longest profile taken; the request is held open while it runs
+pub const MAX_PROFILE_SECONDS: u64 = 60;
+
+/// Samples per second when the query does not say
+pub const DEFAULT_PROFILE_FREQUENCY: i32 = 99;
+
+/// Highest sampling frequency, to keep the profiler's own overhead low
+pub const MAX_PROFILE_FREQUENCY: i32 = 1000;
+
+/// Libraries whose frames are not unwound, as unwinding through them from a signal handler
+/// can deadlock
+const UNWIND_BLOCKLIST: &[&str] = &["libc", "libgcc", "pthread", "vdso"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileQuery {
+    /// How long to sample for
+    pub seconds: Option<u64>,
+    /// Samples per second
+    pub frequency: Option<i32>,
+}
+
+/// Samples the CPU of the whole process for a while and returns the profile as an SVG
+/// flamegraph, or no content when the process was idle. Only one profile runs at a time.
+pub async fn cpu_profile(query: web::Query<ProfileQuery>, admin: AdminKey) -> UrlShortenerResult<HttpResponse> {
+    let seconds = query.seconds.unwrap_or(DEFAULT_PROFILE_SECONDS).clamp(1, MAX_PROFILE_SECONDS);
+    let frequency = query.frequency.unwrap_or(DEFAULT_PROFILE_FREQUENCY).clamp(1, MAX_PROFILE_FREQUENCY);
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency)
+        .blocklist(UNWIND_BLOCKLIST)
+        .build()
+        .map_err(|e| match e {
+            pprof::Error::Running => UrlShortenerErrorType::Conflict("A profile is already being taken".to_string()).into(),
+            e => profile_error(e),
+        })?;
+    info!(owner = %admin.owner, seconds, frequency, "Taking a CPU profile");
+    tokio::time::sleep(Duration::from_secs(seconds)).await;
+
+    let report = guard.report().build().map_err(profile_error)?;
+    drop(guard);
+    if report.data.is_empty() {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+    let mut svg = Vec::new();
+    report.flamegraph(&mut svg).map_err(profile_error)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(svg))
+}
+
+fn profile_error(e: pprof::Error) -> UrlShortenerError {
+    UrlShortenerError::with_source(UrlShortenerErrorType::InternalError("Failed to take a CPU profile".to_string()), e)
+}
@@ -0,0 +1,83 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::config::Config;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::PageCursor;
+use crate::services::UrlService;
+use crate::validation::{check_cursor, check_url, Validate, ValidationErrors};
+use super::UrlStats;
+
+/// Default and maximum number of links returned by the recent links endpoint
+const DEFAULT_RECENT_LIMIT: i64 = 20;
+const MAX_RECENT_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuickShortenQuery {
+    pub url: String,
+}
+
+impl Validate for QuickShortenQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_url(&mut errors, "url", &self.url);
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentLinksQuery {
+    pub limit: Option<i64>,
+    /// Only list links in this collection of the caller
+    pub collection: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl Validate for RecentLinksQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_cursor(&mut errors, self.cursor.as_deref());
+        errors.into_result()
+    }
+}
+
+/// Shortens a URL and returns the full short link as plain text, for bookmarklets and extensions
+pub async fn quick_shorten(
+    query: web::Query<QuickShortenQuery>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+    config: web::Data<Config>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let shortened_url = service
+        .create_owned_short_url(query.into_inner().url, Some(api_key.owner))
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(config.short_link(&shortened_url.short_code)))
+}
+
+/// Lists the caller's most recently created links, a page at a time
+pub async fn recent_links(
+    query: web::Query<RecentLinksQuery>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RECENT_LIMIT)
+        .clamp(1, MAX_RECENT_LIMIT);
+    let after = query.cursor.as_deref().and_then(PageCursor::decode);
+    let page = service
+        .list_recent_urls_in(&api_key.owner, query.collection, after, limit)
+        .await?
+        .map(UrlStats::from);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::page(page.items, page.next_cursor.as_ref().map(PageCursor::encode))))
+}
@@ -0,0 +1,24 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+use crate::redirect::Redirector;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReceiptQuery {
+    /// The receipt as the destination received it
+    #[serde(rename = "ref")]
+    pub receipt: String,
+}
+
+/// Tells a destination site whether a receipt it was sent is genuine and fresh, and which link
+/// and host it was issued for; 404 unless receipts are enabled
+pub async fn verify_receipt(
+    query: web::Query<ReceiptQuery>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    let receipts = redirector.receipts().ok_or(UrlShortenerErrorType::NotFound)?;
+    let receipt = receipts.verify(&query.receipt, Utc::now())?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(receipt)))
+}
@@ -0,0 +1,155 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::auth::ApiKey;
+use crate::config::Config;
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+use crate::middleware::client_ip;
+use crate::redirect::{Expansion, Redirector, ResolveAccess};
+use crate::services::{Safety, UrlService};
+use crate::validation::{Validate, ValidationErrors};
+
+/// Most codes one batch can resolve
+pub const MAX_RESOLVE_BATCH: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveResponse {
+    /// The code the link answers to now, which differs for a retired code
+    pub short_code: String,
+    /// Where the link redirects
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveBatchRequest {
+    pub codes: Vec<String>,
+}
+
+impl Validate for ResolveBatchRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self.codes.len() {
+            0 => errors.add("codes", "required", "codes must list at least one short code"),
+            count if count > MAX_RESOLVE_BATCH => {
+                errors.add("codes", "too_many", format!("At most {} codes can be resolved at once", MAX_RESOLVE_BATCH))
+            }
+            _ if self.codes.iter().any(|code| code.trim().is_empty()) => {
+                errors.add("codes", "invalid", "Short codes cannot be empty")
+            }
+            _ => {}
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveStatus {
+    Found,
+    Disabled,
+    NotFound,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolvedCode {
+    /// The code as it was asked for
+    pub code: String,
+    pub status: ResolveStatus,
+    /// The code the link answers to now, when found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<Safety>,
+}
+
+/// Counts a resolve request against its client's limit, the owner of its API key or its
+/// address, turning it away when a key is required and missing or the limit is exceeded
+fn admit(req: &HttpRequest, api_key: Option<&ApiKey>, access: Option<&web::Data<ResolveAccess>>) -> UrlShortenerResult<()> {
+    let open = ResolveAccess::default();
+    let access = access.map_or(&open, |access| access.get_ref());
+    let client = match api_key {
+        Some(api_key) => format!("key:{}", api_key.owner),
+        None if access.requires_api_key() => return Err(UrlShortenerErrorType::Unauthorized.into()),
+        None => {
+            let trust_forwarded_for = req.app_data::<web::Data<Config>>().is_some_and(|config| config.trust_forwarded_for);
+            client_ip(req, trust_forwarded_for).map_or_else(String::new, |ip| ip.to_string())
+        }
+    };
+    if !access.check(&client) {
+        debug!(client = %client, "Resolve rate limit exceeded");
+        return Err(UrlShortenerErrorType::RateLimitExceeded.into());
+    }
+    Ok(())
+}
+
+/// Expands a short link without redirecting or counting a visit, for security tools checking
+/// links before users click them. Limited per API key owner, or per address without a key.
+pub async fn resolve_link(
+    req: HttpRequest,
+    short_code: web::Path<String>,
+    api_key: Option<ApiKey>,
+    access: Option<web::Data<ResolveAccess>>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    admit(&req, api_key.as_ref(), access.as_ref())?;
+
+    let (short_code, url) = redirector.expand(&short_code).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ResolveResponse { short_code, url: url.to_string() })))
+}
+
+/// Expands many short links at once with the safety verdict on each destination, for mail
+/// gateways vetting messages full of them. A batch counts once against the resolve limit.
+pub async fn resolve_batch(
+    req: HttpRequest,
+    body: web::Json<ResolveBatchRequest>,
+    api_key: Option<ApiKey>,
+    access: Option<web::Data<ResolveAccess>>,
+    redirector: web::Data<Redirector>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    admit(&req, api_key.as_ref(), access.as_ref())?;
+    body.validate()?;
+
+    let codes: Vec<String> = body.into_inner().codes.into_iter().map(|code| code.trim().to_string()).collect();
+    let expansions = redirector.expand_all(&codes).await?;
+    let destinations: Vec<&str> = expansions
+        .iter()
+        .filter_map(|expansion| match expansion {
+            Expansion::Found { url, .. } => Some(&**url),
+            _ => None,
+        })
+        .collect();
+    let mut safety = service.destinations_safety(&destinations).await?.into_iter();
+
+    let resolved: Vec<ResolvedCode> = codes
+        .into_iter()
+        .zip(expansions)
+        .map(|(code, expansion)| match expansion {
+            Expansion::Found { short_code, url } => ResolvedCode {
+                code,
+                status: ResolveStatus::Found,
+                short_code: Some(short_code),
+                url: Some(url.to_string()),
+                safety: safety.next(),
+            },
+            Expansion::Disabled => ResolvedCode {
+                code,
+                status: ResolveStatus::Disabled,
+                short_code: None,
+                url: None,
+                safety: None,
+            },
+            Expansion::Missing => ResolvedCode {
+                code,
+                status: ResolveStatus::NotFound,
+                short_code: None,
+                url: None,
+                safety: None,
+            },
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(ApiResponse::success(resolved)))
+}
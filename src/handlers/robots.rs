@@ -0,0 +1,34 @@
+use std::io;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+
+/// Served when no `ROBOTS_TXT_FILE` is configured: keeps crawlers off short links, which are
+/// only redirects, while leaving the public stats API crawlable
+pub const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nAllow: /api/stats/\nDisallow: /\n";
+
+/// Contents of `/robots.txt`, read once at startup
+#[derive(Clone, Debug)]
+pub struct RobotsTxt(String);
+
+impl RobotsTxt {
+    /// Reads the file at `path`, or uses [`DEFAULT_ROBOTS_TXT`] without one
+    pub fn load(path: Option<&str>) -> io::Result<Self> {
+        match path {
+            Some(path) => std::fs::read_to_string(path).map(Self),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+impl Default for RobotsTxt {
+    fn default() -> Self {
+        Self(DEFAULT_ROBOTS_TXT.to_string())
+    }
+}
+
+/// Serves the crawler policy
+pub async fn robots_txt(robots: web::Data<RobotsTxt>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(robots.0.clone())
+}
@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::SourceVisits;
+use crate::services::UrlService;
+
+/// Default and maximum number of days of visits the source breakdown counts
+const DEFAULT_SOURCE_DAYS: u32 = 30;
+pub const MAX_SOURCE_DAYS: u32 = 366;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourcesQuery {
+    /// Days of visit events to count, up to now
+    pub days: Option<u32>,
+}
+
+/// A link's visits by the `src` tag they arrived with
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkSources {
+    pub short_url: String,
+    pub days: u32,
+    /// Most visited first; visits without a tag have a null `source`
+    pub sources: Vec<SourceVisits>,
+}
+
+/// Visits of a link broken down by their `src` attribution tag
+pub async fn visit_sources(
+    short_code: web::Path<String>,
+    query: web::Query<SourcesQuery>,
+    api_key: Option<ApiKey>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let link = service.get_url_stats(&short_code).await?;
+    // As with its other statistics, only the owner sees a signed-only link's
+    if link.signed_only && link.owner != api_key.map(|key| key.owner) {
+        return Err(UrlShortenerErrorType::NotFound.into());
+    }
+
+    let days = query.days.unwrap_or(DEFAULT_SOURCE_DAYS).clamp(1, MAX_SOURCE_DAYS);
+    let sources = service.visit_sources(&link.short_code, days).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(LinkSources { short_url: link.short_code, days, sources })))
+}
@@ -1,14 +1,33 @@
 use actix_web::{test, web, App};
-use crate::services::UrlService;
-use crate::storage::{MemoryStorage, StorageConfig};
+use crate::auth::{ApiKeys, API_KEY_HEADER};
+use crate::config::Config;
+use crate::http_client::HttpClient;
+use crate::redirect::RedirectConfig;
+use crate::services::{GoLink, Namespaces, UrlLengthLimits, UrlService};
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+use std::collections::HashMap;
 use std::sync::Arc;
 use super::*;
 
+/// Reads the `data` of a successful response envelope
+async fn read_data<T: serde::de::DeserializeOwned>(resp: actix_web::dev::ServiceResponse) -> T {
+    let body: ApiResponse<T> = test::read_body_json(resp).await;
+    assert!(body.error.is_none());
+    body.data.expect("Response envelope has no data")
+}
+
 async fn create_test_service() -> web::Data<UrlService> {
     let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
     web::Data::new(UrlService::new(storage))
 }
 
+/// Service and redirector sharing one storage
+async fn create_test_service_and_redirector() -> (web::Data<UrlService>, web::Data<Redirector>) {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let redirector = Redirector::new(storage.clone(), RedirectConfig::default());
+    (web::Data::new(UrlService::new(storage)), web::Data::new(redirector))
+}
+
 #[actix_rt::test]
 async fn test_create_url_success() {
     // Setup
@@ -24,6 +43,18 @@ async fn test_create_url_success() {
         .uri("/api/shorten")
         .set_json(&CreateUrlRequest {
             original_url: "https://example.com".to_string(),
+            custom_alias: None,
+            style: None,
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            pass_through: false,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            namespace: None,
         })
         .to_request();
 
@@ -32,11 +63,46 @@ async fn test_create_url_success() {
 
     // Check response
     assert!(resp.status().is_success());
-    let body: CreateUrlResponse = test::read_body_json(resp).await;
+    let body: CreateUrlResponse = read_data(resp).await;
     assert!(!body.short_url.is_empty());
     assert_eq!(body.original_url, "https://example.com/");
 }
 
+#[actix_rt::test]
+async fn test_create_url_reports_lookalike_host() {
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .service(web::resource("/api/shorten").route(web::post().to(create_url)))
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(&CreateUrlRequest {
+            original_url: "https://xn--80ak6aa92e.com".to_string(),
+            custom_alias: None,
+            style: None,
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            pass_through: false,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            namespace: None,
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert!(resp.status().is_success());
+    let body: CreateUrlResponse = read_data(resp).await;
+    assert_eq!(body.warnings.len(), 1);
+    assert_eq!(body.warnings[0].code, "confusable_host");
+}
+
 #[actix_rt::test]
 async fn test_create_url_invalid() {
     // Setup
@@ -52,6 +118,18 @@ async fn test_create_url_invalid() {
         .uri("/api/shorten")
         .set_json(&CreateUrlRequest {
             original_url: "not-a-url".to_string(),
+            custom_alias: None,
+            style: None,
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            pass_through: false,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            namespace: None,
         })
         .to_request();
 
@@ -59,18 +137,46 @@ async fn test_create_url_invalid() {
     let resp = test::call_service(&app, req).await;
 
     // Check response
-    assert_eq!(resp.status().as_u16(), 400);
+    assert_eq!(resp.status().as_u16(), 422);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(body["error"]["details"][0]["field"], "original_url");
+    assert_eq!(body["error"]["details"][0]["code"], "invalid_url");
 }
 
 #[actix_rt::test]
-async fn test_redirect_success() {
+async fn test_create_url_blocked_scheme() {
     // Setup
     let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .service(web::resource("/api/shorten").route(web::post().to(create_url)))
+    ).await;
+
+    for url in ["javascript:alert(document.cookie)", "java\tscript:alert(1)", "data:text/html,<h1>hi</h1>"] {
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .set_json(serde_json::json!({ "original_url": url }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status().as_u16(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"]["code"], "blocked_scheme");
+    }
+}
+
+#[actix_rt::test]
+async fn test_redirect_success() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
     let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
 
     let app = test::init_service(
         App::new()
             .app_data(service.clone())
+            .app_data(redirector.clone())
             .service(web::resource("/{short_code}").route(web::get().to(redirect)))
     ).await;
 
@@ -88,15 +194,45 @@ async fn test_redirect_success() {
         resp.headers().get("Location").unwrap().to_str().unwrap(),
         "https://example.com/"
     );
+    assert!(resp.headers().get("X-Robots-Tag").is_none());
+}
+
+#[actix_rt::test]
+async fn test_read_only_instance_redirects_but_rejects_writes() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let link = UrlService::new(storage.clone()).create_short_url("https://example.com".to_string()).await.unwrap();
+    let storage: crate::storage::StorageRef = Arc::new(crate::storage::ReadOnlyStorage::new(storage));
+    let redirector = Redirector::new(storage.clone(), RedirectConfig { record_visits: false, ..RedirectConfig::default() });
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(web::Data::new(redirector))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri(&format!("/{}", link.short_code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", link.short_code)).to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(serde_json::json!({ "original_url": "https://example.com/new" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 503);
+    let body: ApiResponse<()> = test::read_body_json(resp).await;
+    assert_eq!(body.error.unwrap().code, "read_only");
 }
 
 #[actix_rt::test]
 async fn test_redirect_not_found() {
     // Setup
-    let service = create_test_service().await;
+    let (service, redirector) = create_test_service_and_redirector().await;
     let app = test::init_service(
         App::new()
             .app_data(service.clone())
+            .app_data(redirector.clone())
             .service(web::resource("/{short_code}").route(web::get().to(redirect)))
     ).await;
 
@@ -134,12 +270,142 @@ async fn test_get_stats_success() {
 
     // Check response
     assert!(resp.status().is_success());
-    let body: UrlStats = test::read_body_json(resp).await;
+    let body: UrlStats = read_data(resp).await;
     assert_eq!(body.short_url, shortened_url.short_code);
     assert_eq!(body.original_url, "https://example.com/");
     assert_eq!(body.visits, 0);
 }
 
+#[actix_rt::test]
+async fn test_stats_show_when_a_link_was_visited() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let link = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
+    ).await;
+    let stats = || async {
+        let req = test::TestRequest::get().uri(&format!("/api/stats/{}", link.short_code)).to_request();
+        read_data::<UrlStats>(test::call_service(&app, req).await).await
+    };
+
+    let unvisited = stats().await;
+    assert_eq!((unvisited.first_visited_at, unvisited.last_visited_at), (None, None));
+    assert_eq!((unvisited.visits_last_24h, unvisited.visits_last_7d), (Some(0), Some(0)));
+
+    let now = chrono::Utc::now();
+    let events: Vec<crate::models::VisitEvent> = [30, 2, 1, 0]
+        .into_iter()
+        .map(|days_ago| crate::models::VisitEvent {
+            short_code: link.short_code.clone(),
+            visited_at: now - chrono::Duration::days(days_ago) - chrono::Duration::minutes(1),
+            visitor: None,
+            source: None,
+            sample_rate: 1,
+        })
+        .collect();
+    storage.record_visit_events(&events).await.unwrap();
+
+    let visited = stats().await;
+    assert_eq!(visited.first_visited_at, Some(events[0].visited_at.to_rfc3339()));
+    assert_eq!(visited.last_visited_at, Some(events[3].visited_at.to_rfc3339()));
+    assert_eq!((visited.visits_last_24h, visited.visits_last_7d), (Some(1), Some(3)));
+}
+
+#[actix_rt::test]
+async fn test_heatmap_counts_visits_by_weekday_and_hour() {
+    use chrono::{Datelike, Timelike};
+
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let link = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let now = chrono::Utc::now();
+    let visit = |visited_at| crate::models::VisitEvent {
+        short_code: link.short_code.clone(),
+        visited_at,
+        visitor: None,
+        source: None,
+        sample_rate: 1,
+    };
+    let recent = now - chrono::Duration::hours(1);
+    let old = now - chrono::Duration::days(20);
+    storage.record_visit_events(&[visit(recent), visit(recent), visit(old)]).await.unwrap();
+
+    let heatmap = |query: &str| {
+        let req = test::TestRequest::get().uri(&format!("/api/stats/{}/heatmap{}", link.short_code, query)).to_request();
+        test::call_service(&app, req)
+    };
+    let cell = |at: chrono::DateTime<chrono::Utc>| (at.weekday().num_days_from_monday() as usize, at.hour() as usize);
+
+    let all: LinkHeatmap = read_data(heatmap("").await).await;
+    assert_eq!((all.short_url.as_str(), all.days), (link.short_code.as_str(), 90));
+    let (day, hour) = cell(recent);
+    assert_eq!(all.heatmap.visits[day][hour], 2);
+    let (day, hour) = cell(old);
+    assert_eq!(all.heatmap.visits[day][hour], 1);
+    assert_eq!(all.heatmap.visits.iter().flatten().sum::<i64>(), 3);
+
+    let week: LinkHeatmap = read_data(heatmap("?days=7").await).await;
+    assert_eq!(week.heatmap.visits.iter().flatten().sum::<i64>(), 2);
+
+    let req = test::TestRequest::get().uri("/api/stats/missing/heatmap").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_links_are_compared_over_aligned_days() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let a = service.create_short_url("https://example.com/a".to_string()).await.unwrap();
+    let b = service.create_short_url("https://example.com/b".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let now = chrono::Utc::now();
+    let visit = |link: &crate::services::ShortenedUrl, days_ago| crate::models::VisitEvent {
+        short_code: link.short_code.clone(),
+        visited_at: now - chrono::Duration::days(days_ago),
+        visitor: None,
+        source: None,
+        sample_rate: 1,
+    };
+    storage.record_visit_events(&[visit(&a, 0), visit(&a, 0), visit(&a, 2), visit(&b, 1), visit(&b, 40)]).await.unwrap();
+
+    let compare = |query: String| {
+        let req = test::TestRequest::get().uri(&format!("/api/stats/compare?{}", query)).to_request();
+        test::call_service(&app, req)
+    };
+    let resp = compare(format!("codes={},{}&period=3d", a.short_code, b.short_code)).await;
+    let comparison: crate::services::LinkComparison = read_data(resp).await;
+    assert_eq!(comparison.dates.len(), 3);
+    assert_eq!(comparison.dates[2], now.date_naive());
+    let series: Vec<_> = comparison.links.iter().map(|link| (link.short_url.as_str(), link.daily.clone(), link.period_visits)).collect();
+    assert_eq!(series, [(a.short_code.as_str(), vec![1, 0, 2], 3), (b.short_code.as_str(), vec![0, 1, 0], 1)]);
+
+    // The default period is 30 days
+    let resp = compare(format!("codes={}", b.short_code)).await;
+    let comparison: crate::services::LinkComparison = read_data(resp).await;
+    assert_eq!((comparison.dates.len(), comparison.links[0].period_visits), (30, 1));
+
+    for query in ["codes=".to_string(), format!("codes={}&period=3w", a.short_code), format!("codes={}&period=0d", a.short_code)] {
+        assert_eq!(compare(query).await.status().as_u16(), 422);
+    }
+    let too_many = vec![a.short_code.clone(); MAX_COMPARED_LINKS + 1].join(",");
+    assert_eq!(compare(format!("codes={}", too_many)).await.status().as_u16(), 422);
+    assert_eq!(compare(format!("codes={},missing", a.short_code)).await.status().as_u16(), 404);
+}
+
 #[actix_rt::test]
 async fn test_get_stats_not_found() {
     // Setup
@@ -165,12 +431,13 @@ async fn test_get_stats_not_found() {
 #[actix_rt::test]
 async fn test_visit_count_increment() {
     // Setup
-    let service = create_test_service().await;
+    let (service, redirector) = create_test_service_and_redirector().await;
     let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
 
     let app = test::init_service(
         App::new()
             .app_data(service.clone())
+            .app_data(redirector.clone())
             .service(web::resource("/{short_code}").route(web::get().to(redirect)))
             .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
     ).await;
@@ -180,12 +447,2627 @@ async fn test_visit_count_increment() {
         .uri(&format!("/{}", shortened_url.short_code))
         .to_request();
     let _ = test::call_service(&app, redirect_req).await;
+    // Visits are recorded asynchronously
+    redirector.flush_visits().await;
 
     // Check stats to verify visit count was incremented
     let stats_req = test::TestRequest::get()
         .uri(&format!("/api/stats/{}", shortened_url.short_code))
         .to_request();
     let stats_resp = test::call_service(&app, stats_req).await;
-    let stats: UrlStats = test::read_body_json(stats_resp).await;
+    let stats: UrlStats = read_data(stats_resp).await;
     assert_eq!(stats.visits, 1);
-} 
\ No newline at end of file
+}
+
+fn test_api_keys() -> web::Data<ApiKeys> {
+    let mut keys = HashMap::new();
+    keys.insert("alice-key".to_string(), "alice".to_string());
+    keys.insert("bob-key".to_string(), "bob".to_string());
+    web::Data::new(ApiKeys::new(keys))
+}
+
+#[actix_rt::test]
+async fn test_limits_report_the_callers_url_length() {
+    // Setup
+    let limits = UrlLengthLimits {
+        default: 100,
+        tiers: HashMap::from([("premium".to_string(), 4096)]),
+        owner_tiers: HashMap::from([("alice".to_string(), "premium".to_string())]),
+    };
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage).with_url_length_limits(limits)))
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/limits").insert_header((API_KEY_HEADER, "alice-key")).to_request();
+    let limits: ApiLimits = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((limits.tier.as_str(), limits.max_url_length), ("premium", 4096));
+    let req = test::TestRequest::get().uri("/api/limits").to_request();
+    let limits: ApiLimits = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((limits.tier.as_str(), limits.max_url_length), ("anonymous", 100));
+
+    // Over the caller's limit, the error names it
+    let long_url = format!("https://example.com/{}", "a".repeat(200));
+    let req = test::TestRequest::post().uri("/api/shorten")
+        .set_json(serde_json::json!({ "original_url": long_url })).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: ApiResponse<serde_json::Value> = test::read_body_json(resp).await;
+    assert!(body.error.unwrap().message.contains("100 characters"));
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "original_url": long_url })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
+
+#[actix_rt::test]
+async fn test_quick_shorten_returns_plain_text_link() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .service(web::resource("/api/quick").route(web::get().to(quick_shorten)))
+    ).await;
+
+    // Test request
+    let req = test::TestRequest::get()
+        .uri("/api/quick?url=https%3A%2F%2Fexample.com%2Fpage")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .to_request();
+
+    // Send request
+    let resp = test::call_service(&app, req).await;
+
+    // Check response
+    assert!(resp.status().is_success());
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap().to_str().unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    let short_code = body.strip_prefix("http://localhost:8080/").unwrap();
+    let stats = service.get_url_stats(short_code).await.unwrap();
+    assert_eq!(stats.original_url, "https://example.com/page");
+    assert_eq!(stats.owner.as_deref(), Some("alice"));
+}
+
+#[actix_rt::test]
+async fn test_quick_shorten_requires_api_key() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .service(web::resource("/api/quick").route(web::get().to(quick_shorten)))
+    ).await;
+
+    // Missing key
+    let req = test::TestRequest::get()
+        .uri("/api/quick?url=https%3A%2F%2Fexample.com")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 401);
+
+    // Unknown key
+    let req = test::TestRequest::get()
+        .uri("/api/quick?url=https%3A%2F%2Fexample.com")
+        .insert_header(("Authorization", "Bearer nope"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn test_recent_links_only_returns_callers_links() {
+    // Setup
+    let service = create_test_service().await;
+    service.create_owned_short_url("https://alice.example/1".to_string(), Some("alice".to_string())).await.unwrap();
+    service.create_owned_short_url("https://alice.example/2".to_string(), Some("alice".to_string())).await.unwrap();
+    service.create_owned_short_url("https://bob.example".to_string(), Some("bob".to_string())).await.unwrap();
+    service.create_short_url("https://anonymous.example".to_string()).await.unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .service(web::resource("/api/quick/recent").route(web::get().to(recent_links)))
+    ).await;
+
+    // Test request
+    let req = test::TestRequest::get()
+        .uri("/api/quick/recent")
+        .insert_header(("Authorization", "Bearer alice-key"))
+        .to_request();
+
+    // Send request
+    let resp = test::call_service(&app, req).await;
+
+    // Check response
+    assert!(resp.status().is_success());
+    let body: Vec<UrlStats> = read_data(resp).await;
+    assert_eq!(body.len(), 2);
+    assert!(body.iter().all(|url| url.original_url.starts_with("https://alice.example/")));
+}
+
+#[actix_rt::test]
+async fn test_recent_links_are_paged_with_cursors() {
+    // Setup: five links, likely some created in the same microsecond
+    let service = create_test_service().await;
+    for i in 0..5 {
+        let url = format!("https://alice.example/{}", i);
+        service.create_owned_short_url(url, Some("alice".to_string())).await.unwrap();
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let get = |uri: String| test::TestRequest::get().uri(&uri).insert_header((API_KEY_HEADER, "alice-key")).to_request();
+
+    // Pages of two follow each other without gaps or repeats, and the last has no cursor
+    let mut seen = Vec::new();
+    let mut uri = "/api/quick/recent?limit=2".to_string();
+    loop {
+        let body: ApiResponse<Vec<UrlStats>> = test::read_body_json(test::call_service(&app, get(uri)).await).await;
+        seen.extend(body.data.unwrap().into_iter().map(|url| url.original_url));
+        match body.next_cursor {
+            Some(cursor) => uri = format!("/api/quick/recent?limit=2&cursor={}", cursor),
+            None => break,
+        }
+    }
+    let expected: Vec<String> = (0..5).rev().map(|i| format!("https://alice.example/{}", i)).collect();
+    assert_eq!(seen, expected);
+
+    let resp = test::call_service(&app, get("/api/quick/recent?cursor=not-a-cursor".to_string())).await;
+    assert_eq!(resp.status().as_u16(), 422);
+}
+
+#[actix_rt::test]
+async fn test_links_are_listed_sorted_and_filtered() {
+    // Setup: visits counted in creation order, so the last link created was visited last
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let mut codes = HashMap::new();
+    for (name, url, visits) in [
+        ("a", "https://Example.com/a", 5),
+        ("b", "https://shop.example.com/b", 20),
+        ("c", "https://example.com/c", 1),
+        ("d", "https://other.test/d", 0),
+    ] {
+        let link = service.create_owned_short_url(url.to_string(), Some("alice".to_string())).await.unwrap();
+        if visits > 0 {
+            storage.record_visits(&link.short_code, visits).await.unwrap();
+        }
+        codes.insert(link.short_code, name);
+    }
+    storage.disable_urls(&[codes.iter().find(|(_, name)| **name == "c").unwrap().0.clone()]).await.unwrap();
+    service.create_owned_short_url("https://example.com/bob".to_string(), Some("bob".to_string())).await.unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let get = |query: &str| {
+        test::TestRequest::get().uri(&format!("/api/urls?{}", query)).insert_header((API_KEY_HEADER, "alice-key")).to_request()
+    };
+    let list = |query: &'static str| {
+        let (app, codes) = (&app, &codes);
+        async move {
+            let body: ApiResponse<Vec<UrlStats>> = test::read_body_json(test::call_service(app, get(query)).await).await;
+            let names: Vec<&str> = body.data.unwrap().iter().map(|url| codes[&url.short_url]).collect();
+            (names, body.next_cursor)
+        }
+    };
+
+    assert_eq!(list("").await.0, ["d", "c", "b", "a"]);
+    assert_eq!(list("sort=visits").await.0, ["b", "a", "c", "d"]);
+    assert_eq!(list("sort=last_visited").await.0, ["c", "b", "a", "d"]);
+    assert_eq!(list("domain=EXAMPLE.com").await.0, ["c", "a"]);
+    assert_eq!(list("sort=visits&min_visits=5").await.0, ["b", "a"]);
+    assert_eq!(list("status=disabled").await.0, ["c"]);
+    assert_eq!(list("status=active&domain=example.com").await.0, ["a"]);
+    assert!(list("created_after=2999-01-01T00:00:00Z").await.0.is_empty());
+
+    // Pages keep their order
+    let (first, cursor) = list("sort=visits&limit=3").await;
+    assert_eq!(first, ["b", "a", "c"]);
+    let cursor = cursor.unwrap();
+    let body: ApiResponse<Vec<UrlStats>> =
+        test::read_body_json(test::call_service(&app, get(&format!("sort=visits&limit=3&cursor={}", cursor))).await).await;
+    let rest: Vec<&str> = body.data.unwrap().iter().map(|url| codes[&url.short_url]).collect();
+    assert_eq!((rest, body.next_cursor), (vec!["d"], None));
+
+    // A cursor only continues the order it came from
+    let resp = test::call_service(&app, get(&format!("sort=created_at&cursor={}", cursor))).await;
+    assert_eq!(resp.status().as_u16(), 422);
+    assert_eq!(test::call_service(&app, get("sort=popularity")).await.status().as_u16(), 400);
+    assert_eq!(test::call_service(&app, get("domain=exa%20mple.com")).await.status().as_u16(), 422);
+}
+
+#[actix_rt::test]
+async fn test_idn_destinations_come_with_both_host_forms() {
+    // Setup
+    let service = create_test_service().await;
+    let owner = || Some("alice".to_string());
+    let books = service.create_owned_short_url("https://bücher.example/neu".to_string(), owner()).await.unwrap();
+    service.create_owned_short_url("https://pаypal.com/login".to_string(), owner()).await.unwrap();
+    service.create_owned_short_url("https://example.com/".to_string(), owner()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", books.short_code)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.original_url, "https://xn--bcher-kva.example/neu");
+    let host = stats.idn_host.unwrap();
+    assert_eq!((host.punycode.as_str(), host.unicode.as_str()), ("xn--bcher-kva.example", "bücher.example"));
+    assert!(!host.suspicious_idn);
+
+    let req = test::TestRequest::get().uri("/api/quick/recent").insert_header((API_KEY_HEADER, "alice-key")).to_request();
+    let links: Vec<UrlStats> = read_data(test::call_service(&app, req).await).await;
+    let hosts: HashMap<_, _> = links
+        .into_iter()
+        .map(|link| (link.original_url, link.idn_host.map(|host| (host.unicode, host.suspicious_idn))))
+        .collect();
+    assert_eq!(hosts["https://xn--pypal-4ve.com/login"], Some(("pаypal.com".to_string(), true)));
+    assert_eq!(hosts["https://example.com/"], None);
+}
+
+#[actix_rt::test]
+async fn test_malformed_payloads_use_error_format() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    // Body that is not valid JSON
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload("{\"original_url\":")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"]["code"], "invalid_input");
+
+    // Query string missing the url parameter
+    let req = test::TestRequest::get()
+        .uri("/api/quick")
+        .insert_header(("Authorization", "Bearer alice-key"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"]["code"], "invalid_input");
+}
+
+#[actix_rt::test]
+async fn test_error_envelope_carries_correlation_id() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .wrap(crate::middleware::RequestLogger)
+            .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
+    ).await;
+
+    // Test request
+    let req = test::TestRequest::get().uri("/api/stats/missing").to_request();
+    let resp = test::call_service(&app, req).await;
+
+    // Check response
+    assert_eq!(resp.status().as_u16(), 404);
+    let header = resp
+        .headers()
+        .get(crate::middleware::CORRELATION_ID_HEADER)
+        .expect("Missing correlation ID header")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: ApiResponse<()> = test::read_body_json(resp).await;
+    assert!(body.data.is_none());
+    let error = body.error.unwrap();
+    assert_eq!(error.code, "not_found");
+    assert_eq!(error.correlation_id, Some(header));
+}
+
+#[actix_rt::test]
+async fn test_handler_panics_become_internal_errors() {
+    async fn broken() -> HttpResponse {
+        panic!("handler bug")
+    }
+    let app = test::init_service(
+        App::new()
+            .wrap(crate::middleware::CatchPanic)
+            .wrap(crate::middleware::RequestLogger)
+            .route("/broken", web::get().to(broken))
+    ).await;
+    let panics_before = crate::middleware::panics_total();
+
+    // The server turns the error into its prebuilt response
+    let req = test::TestRequest::get().uri("/broken").to_request();
+    let resp = test::try_call_service(&app, req).await.unwrap_err().error_response();
+
+    assert_eq!(resp.status().as_u16(), 500);
+    let header = resp.headers().get(crate::middleware::CORRELATION_ID_HEADER).unwrap().to_str().unwrap().to_string();
+    let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+    let body: ApiResponse<()> = serde_json::from_slice(&body).unwrap();
+    let error = body.error.unwrap();
+    assert_eq!(error.code, "internal_error");
+    assert!(!error.message.contains("handler bug"));
+    assert_eq!(error.correlation_id, Some(header));
+    assert!(crate::middleware::panics_total() > panics_before);
+}
+
+#[actix_rt::test]
+async fn test_error_messages_follow_accept_language() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .wrap(crate::middleware::Localize)
+            .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
+    ).await;
+
+    // Test request
+    let req = test::TestRequest::get()
+        .uri("/api/stats/missing")
+        .insert_header(("Accept-Language", "es-ES,es;q=0.9,en;q=0.5"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    // Check response
+    assert_eq!(resp.status().as_u16(), 404);
+    assert_eq!(resp.headers().get("Content-Language").unwrap(), "es");
+    let body: ApiResponse<()> = test::read_body_json(resp).await;
+    let error = body.error.unwrap();
+    assert_eq!(error.code, "not_found");
+    assert_eq!(error.message, "No se encontró la URL corta");
+}
+
+#[actix_rt::test]
+async fn test_redirect_with_lenient_code_lookup() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let lookup = crate::services::CodeLookup { case_insensitive: true, trim: true };
+    let service = web::Data::new(UrlService::new(storage.clone()).with_code_lookup(lookup));
+    let redirector = web::Data::new(Redirector::new(
+        storage,
+        RedirectConfig { code_lookup: lookup, ..RedirectConfig::default() },
+    ));
+    let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    assert_eq!(shortened_url.short_code, shortened_url.short_code.to_lowercase());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    // Mangled the way chat apps do: different case, trailing slash and whitespace
+    for path in [
+        format!("/{}", shortened_url.short_code.to_uppercase()),
+        format!("/{}/", shortened_url.short_code),
+        format!("/{}%20", shortened_url.short_code),
+    ] {
+        let req = test::TestRequest::get().uri(&path).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), 302, "path {}", path);
+    }
+}
+
+#[actix_rt::test]
+async fn test_flooded_redirect_serves_busy_page() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(
+        storage,
+        RedirectConfig { rate_limit: Some(1), ..RedirectConfig::default() },
+    ));
+    let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let path = format!("/{}", shortened_url.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert_eq!(resp.headers().get("Retry-After").unwrap(), "1");
+    assert!(resp.headers().get("Location").is_none());
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html; charset=utf-8");
+    let body = test::read_body(resp).await;
+    assert!(std::str::from_utf8(&body).unwrap().contains("This link is busy"));
+}
+
+#[actix_rt::test]
+async fn test_redirect_responses_are_replayed_within_ttl() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(
+        storage.clone(),
+        RedirectConfig { response_cache_ttl: std::time::Duration::from_secs(60), ..RedirectConfig::default() },
+    ));
+    let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let path = format!("/{}", shortened_url.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+
+    // The replayed response never reaches the handler, so the destination cache stays empty
+    redirector.cache().clear();
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/");
+    assert!(redirector.cache().is_empty());
+
+    // Replayed redirects still count as visits
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats(&shortened_url.short_code).await.unwrap().visits, 2);
+
+    redirector.invalidate(&shortened_url.short_code);
+    test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(redirector.cache().len(), 1);
+
+    // Misses are answered by the handler and not kept
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/missing").to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+    assert!(redirector.replay("missing", None).is_none());
+}
+
+#[actix_rt::test]
+async fn test_robots_txt_and_noindex_redirects() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(
+        storage,
+        RedirectConfig { noindex: true, ..RedirectConfig::default() },
+    ));
+    let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(RobotsTxt::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/robots.txt").to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/plain; charset=utf-8");
+    assert_eq!(test::read_body(resp).await, DEFAULT_ROBOTS_TXT);
+
+    let path = format!("/{}", shortened_url.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("X-Robots-Tag").unwrap(), "noindex");
+}
+
+#[actix_rt::test]
+async fn test_social_crawlers_get_link_preview() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    // Private destinations are never fetched, so the page falls back to the link's title
+    let created = service
+        .create_short_url_with(
+            "http://127.0.0.1:9/launch".to_string(),
+            CreateOptions { title: Some("Launch day".to_string()), ..CreateOptions::default() },
+        )
+        .await
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(crate::preview::LinkPreviews::new("https://sho.rt/", HttpClient::default())))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let path = format!("/{}", created.short_code);
+    let req = test::TestRequest::get()
+        .uri(&path)
+        .insert_header(("User-Agent", "Twitterbot/1.0"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let page = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(page.contains(r#"<meta property="og:title" content="Launch day">"#));
+    assert!(page.contains(&format!(r#"<meta property="og:url" content="https://sho.rt/{}">"#, created.short_code)));
+
+    // Browsers are still redirected, and only their visits count
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats(&created.short_code).await.unwrap().visits, 1);
+}
+
+#[actix_rt::test]
+async fn test_resolve_returns_destination_without_counting() {
+    use crate::redirect::ResolveAccess;
+
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let created = service.create_short_url("https://example.com/landing".to_string()).await.unwrap();
+    let options = CreateOptions { signed_only: true, ..CreateOptions::default() };
+    let signed = service.create_short_url_with("https://example.com/secret".to_string(), options).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(ResolveAccess::new(false, Some(3))))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let resolve = |code: &str| test::TestRequest::get().uri(&format!("/api/resolve/{}", code));
+
+    let resp = test::call_service(&app, resolve(&created.short_code).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: ApiResponse<ResolveResponse> = test::read_body_json(resp).await;
+    let resolved = body.data.unwrap();
+    assert_eq!(resolved.short_code, created.short_code);
+    assert_eq!(resolved.url, "https://example.com/landing");
+    redirector.flush_visits().await;
+    assert_eq!(service.get_url_stats(&created.short_code).await.unwrap().visits, 0);
+
+    // Signed-only links are not expanded
+    let resp = test::call_service(&app, resolve(&signed.short_code).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+
+    // The fourth lookup within a second is refused; API keys have their own budget
+    let resp = test::call_service(&app, resolve("missing").to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+    let resp = test::call_service(&app, resolve(&created.short_code).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    let req = resolve(&created.short_code).insert_header((API_KEY_HEADER, "alice-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // Gated deployments need a key
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(ResolveAccess::new(true, None)))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let resp = test::call_service(&app, resolve(&created.short_code).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 401);
+    let req = resolve(&created.short_code).insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
+
+#[actix_rt::test]
+async fn test_resolve_batch_reports_each_code() {
+    use crate::redirect::ResolveAccess;
+    use crate::services::Verdict;
+
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let created = service.create_short_url("https://example.com/landing".to_string()).await.unwrap();
+    let lookalike = service.create_short_url("https://xn--pypal-4ve.com/login".to_string()).await.unwrap();
+    let banned = service.create_short_url("https://banned.example/".to_string()).await.unwrap();
+    service.ban_domain("banned.example", "admin", None).await.unwrap();
+    let options = CreateOptions { signed_only: true, ..CreateOptions::default() };
+    let signed = service.create_short_url_with("https://example.com/secret".to_string(), options).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(ResolveAccess::new(false, Some(2))))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let batch = |codes: Vec<String>| {
+        test::TestRequest::post().uri("/api/resolve/batch").set_json(ResolveBatchRequest { codes })
+    };
+
+    let codes = vec![
+        created.short_code.clone(),
+        format!(" {} ", lookalike.short_code),
+        banned.short_code.clone(),
+        signed.short_code.clone(),
+        "missing".to_string(),
+    ];
+    let resp = test::call_service(&app, batch(codes).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: ApiResponse<Vec<ResolvedCode>> = test::read_body_json(resp).await;
+    let resolved = body.data.unwrap();
+    let statuses: Vec<ResolveStatus> = resolved.iter().map(|entry| entry.status).collect();
+    assert_eq!(statuses, [
+        ResolveStatus::Found,
+        ResolveStatus::Found,
+        ResolveStatus::Disabled,
+        ResolveStatus::NotFound,
+        ResolveStatus::NotFound,
+    ]);
+    assert_eq!(resolved[0].url.as_deref(), Some("https://example.com/landing"));
+    assert_eq!(resolved[0].safety.as_ref().unwrap().verdict, Verdict::Safe);
+    assert_eq!(resolved[1].code, lookalike.short_code);
+    assert_eq!(resolved[1].safety.as_ref().unwrap().verdict, Verdict::Suspicious);
+    assert!(resolved[3].url.is_none() && resolved[3].safety.is_none());
+    redirector.flush_visits().await;
+    assert_eq!(service.get_url_stats(&created.short_code).await.unwrap().visits, 0);
+
+    // Batches are bounded, and each counts once against the limit
+    let resp = test::call_service(&app, batch(Vec::new()).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 422);
+    let resp = test::call_service(&app, batch(vec!["missing".to_string(); MAX_RESOLVE_BATCH + 1]).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    let req = batch(vec!["missing".to_string(); MAX_RESOLVE_BATCH + 1]).insert_header((API_KEY_HEADER, "alice-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+}
+
+#[actix_rt::test]
+async fn test_link_preview_endpoint() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    // Private destinations are never fetched, so the preview falls back to the link's title
+    let options = CreateOptions { title: Some("Launch day".to_string()), ..CreateOptions::default() };
+    let created = service.create_short_url_with("http://127.0.0.1:9/launch".to_string(), options).await.unwrap();
+    let options = CreateOptions { signed_only: true, ..CreateOptions::default() };
+    let signed = service.create_short_url_with("http://127.0.0.1:9/secret".to_string(), options).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(crate::preview::LinkPreviews::new("https://sho.rt/", HttpClient::default())))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let path = format!("/api/preview/{}", created.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: ApiResponse<LinkPreviewResponse> = test::read_body_json(resp).await;
+    let preview = body.data.unwrap();
+    assert_eq!(preview.short_url, format!("https://sho.rt/{}", created.short_code));
+    assert_eq!(preview.url, "http://127.0.0.1:9/launch");
+    assert_eq!(preview.title.as_deref(), Some("Launch day"));
+    assert_eq!(preview.favicon_url.as_deref(), Some("http://127.0.0.1:9/favicon.ico"));
+    assert_eq!(preview.safety.verdict, crate::services::Verdict::Safe);
+
+    // Previewing does not count as a visit
+    redirector.flush_visits().await;
+    assert_eq!(service.get_url_stats(&created.short_code).await.unwrap().visits, 0);
+
+    // Signed-only links reveal nothing about their destination
+    let path = format!("/api/preview/{}", signed.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+
+    // Nor does any link while previews are disabled
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let path = format!("/api/preview/{}", created.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[cfg(feature = "favicon-proxy")]
+#[actix_rt::test]
+async fn test_favicon_endpoint() {
+    let favicons = crate::favicons::Favicons::new(HttpClient::default(), None);
+    let app = test::init_service(
+        App::new()
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(favicons))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let get = |domain: &str, key: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(&format!("/api/favicon?domain={}", domain));
+        if let Some(key) = key {
+            req = req.insert_header((API_KEY_HEADER, key));
+        }
+        req.to_request()
+    };
+
+    // Not an open proxy
+    let resp = test::call_service(&app, get("example.com", None)).await;
+    assert_eq!(resp.status().as_u16(), 401);
+
+    for domain in ["127.0.0.1", "localhost", "example.com%2Fpath"] {
+        let resp = test::call_service(&app, get(domain, Some("alice-key"))).await;
+        assert_eq!(resp.status().as_u16(), 422, "{}", domain);
+    }
+
+    // Domains without a readable icon are 404
+    let resp = test::call_service(&app, get("nothing-here.invalid", Some("alice-key"))).await;
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[cfg(feature = "og-image")]
+#[actix_rt::test]
+async fn test_preview_image_endpoint() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let shortened_url = service.create_short_url("http://127.0.0.1:9/".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(crate::preview::LinkPreviews::new("https://sho.rt", HttpClient::default())))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let path = format!("/api/links/{}/preview.png", shortened_url.short_code);
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&path).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "image/png");
+    assert!(test::read_body(resp).await.starts_with(b"\x89PNG"));
+
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/api/links/missing/preview.png").to_request()).await;
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_unknown_codes_follow_missing_code_policy() {
+    use crate::redirect::MissingCodePolicy;
+
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let fallback = MissingCodePolicy::Redirect("https://example.com/?missing={code}".to_string());
+    for (policy, status) in [(MissingCodePolicy::Page, 404), (fallback, 302)] {
+        let redirector = web::Data::new(Redirector::new(
+            storage.clone(),
+            RedirectConfig { missing_code: policy, ..RedirectConfig::default() },
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(service.clone())
+                .app_data(redirector.clone())
+                .configure(crate::routes::configure_routes)
+        ).await;
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/nope%20x").to_request()).await;
+        assert_eq!(resp.status().as_u16(), status);
+        match status {
+            302 => assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/?missing=nope+x"),
+            _ => {
+                assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html; charset=utf-8");
+                assert!(String::from_utf8(test::read_body(resp).await.to_vec()).unwrap().contains("Link not found"));
+            }
+        }
+
+        // The API keeps its structured errors
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/api/stats/nope").to_request()).await;
+        assert_eq!(resp.status().as_u16(), 404);
+        let body: ApiResponse<UrlStats> = test::read_body_json(resp).await;
+        assert!(body.error.is_some());
+    }
+}
+
+#[actix_rt::test]
+async fn test_unknown_codes_suggest_near_codes() {
+    use crate::redirect::{MissingCodePolicy, TypoIndex};
+
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let options = CreateOptions { custom_alias: Some("handbook".to_string()), ..CreateOptions::default() };
+    service.create_short_url_with("https://hr.example.com".to_string(), options).await.unwrap();
+    let index = Arc::new(TypoIndex::new(storage.clone(), String::new(), Vec::new()));
+    index.refresh().await.unwrap();
+
+    for policy in [MissingCodePolicy::Json, MissingCodePolicy::Page] {
+        let redirector = web::Data::new(
+            Redirector::new(storage.clone(), RedirectConfig { missing_code: policy.clone(), ..RedirectConfig::default() })
+                .with_typo_index(index.clone()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(service.clone())
+                .app_data(redirector.clone())
+                .configure(crate::routes::configure_routes)
+        ).await;
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/handbok").to_request()).await;
+        assert_eq!(resp.status().as_u16(), 404);
+        if policy == MissingCodePolicy::Json {
+            let body: ApiResponse<()> = test::read_body_json(resp).await;
+            let error = body.error.unwrap();
+            assert_eq!(error.code, "not_found");
+            assert_eq!(error.details.unwrap()["suggestions"], serde_json::json!(["handbook"]));
+        } else {
+            let page = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            assert!(page.contains("<a href=\"/handbook\">handbook</a>"));
+        }
+
+        // Nothing near enough keeps the plain 404
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/zzzzzzz").to_request()).await;
+        assert_eq!(resp.status().as_u16(), 404);
+        if policy == MissingCodePolicy::Json {
+            let body: ApiResponse<()> = test::read_body_json(resp).await;
+            assert!(body.error.unwrap().details.is_none());
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_redirect_trailing_slash_is_strict_by_default() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let shortened_url = service.create_short_url("https://example.com".to_string()).await.unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    // Test request
+    let req = test::TestRequest::get()
+        .uri(&format!("/{}/", shortened_url.short_code))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    // Check response
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_redirect_with_emoji_alias() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    // Create with a custom alias
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .set_json(&CreateUrlRequest {
+            original_url: "https://example.com/party".to_string(),
+            custom_alias: Some("🎉party".to_string()),
+            style: None,
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            pass_through: false,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            namespace: None,
+        })
+        .to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(created.short_url, "🎉party");
+
+    // Browsers send the alias percent-encoded
+    let req = test::TestRequest::get()
+        .uri("/%F0%9F%8E%89party")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/party");
+}
+
+#[actix_rt::test]
+async fn test_redirect_passes_path_and_query_through() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://docs.example.com/v2?lang=en", "pass_through": true }))
+        .to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", created.short_url)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.pass_through);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/{}/guide/getting%20started?section=install", created.short_url))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(
+        resp.headers().get("Location").unwrap(),
+        "https://docs.example.com/v2/guide/getting%20started?lang=en&section=install"
+    );
+
+    // Turning pass-through off takes effect on the next redirect
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", created.short_url)).insert_header(alice)
+        .set_json(serde_json::json!({ "pass_through": false }))
+        .to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(!updated.pass_through);
+
+    let req = test::TestRequest::get().uri(&format!("/{}/guide", created.short_url)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let req = test::TestRequest::get().uri(&format!("/{}?section=install", created.short_url)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://docs.example.com/v2?lang=en");
+}
+
+#[actix_rt::test]
+async fn test_signed_only_links_redirect_through_signed_urls() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let config = RedirectConfig { signer: Some(crate::redirect::LinkSigner::new("secret")), ..RedirectConfig::default() };
+    let redirector = web::Data::new(Redirector::new(storage.clone(), config));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    // Signed-only links need an owner
+    let req = test::TestRequest::post().uri("/api/shorten")
+        .set_json(serde_json::json!({ "original_url": "https://example.com/private", "signed_only": true })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/private", "signed_only": true })).to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let code = created.short_url;
+
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Only the owner can sign the link or see its stats
+    let req = test::TestRequest::post().uri(&format!("/api/links/{}/sign", code)).insert_header((API_KEY_HEADER, "bob-key"))
+        .set_json(serde_json::json!({ "expires_in_secs": 60 })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).insert_header(alice).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.signed_only);
+
+    let req = test::TestRequest::post().uri(&format!("/api/links/{}/sign", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "expires_in_secs": 0 })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+    let req = test::TestRequest::post().uri(&format!("/api/links/{}/sign", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "expires_in_secs": 60 })).to_request();
+    let signed: SignedLinkResponse = read_data(test::call_service(&app, req).await).await;
+    let path = signed.signed_url.split_once(&format!("/{}?", code)).map(|(_, query)| query).unwrap();
+
+    let req = test::TestRequest::get().uri(&format!("/{}?{}", code, path)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/private");
+
+    // A tampered signature is refused, and lifting the restriction opens the link
+    let req = test::TestRequest::get().uri(&format!("/{}?{}", code, path.replace("exp=", "exp=1"))).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "signed_only": false })).to_request();
+    let _: UrlStats = read_data(test::call_service(&app, req).await).await;
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+}
+
+#[actix_rt::test]
+async fn test_redirect_receipts_verify_through_the_api() {
+    use crate::redirect::{Receipt, ReceiptSigner};
+
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let receipts = ReceiptSigner::new("secret", std::time::Duration::from_secs(300));
+    let config = RedirectConfig { receipts: Some(receipts), ..RedirectConfig::default() };
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let created = service.create_short_url("https://example.com/landing?utm=mail".to_string()).await.unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(web::Data::new(Redirector::new(storage.clone(), config)))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri(&format!("/{}", created.short_code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "no-store");
+    let location = resp.headers().get("Location").unwrap().to_str().unwrap();
+    let (landing, receipt) = location.split_once("&ref=").unwrap();
+    assert_eq!(landing, "https://example.com/landing?utm=mail");
+
+    let verify = |receipt: &str| test::TestRequest::get().uri(&format!("/api/receipts/verify?ref={}", receipt)).to_request();
+    let verified: Receipt = read_data(test::call_service(&app, verify(receipt)).await).await;
+    assert_eq!((verified.short_code, verified.host), (created.short_code, "example.com".to_string()));
+    let tampered = format!("{}x", receipt);
+    assert_eq!(test::call_service(&app, verify(&tampered)).await.status().as_u16(), 403);
+
+    // Without a secret there is nothing to verify
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Redirector::new(storage, RedirectConfig::default())))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    assert_eq!(test::call_service(&app, verify(receipt)).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_tracking_snippets_fire_on_a_forwarding_page() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let config = RedirectConfig { tracking_pages: true, ..RedirectConfig::default() };
+    let redirector = web::Data::new(Redirector::new(storage.clone(), config));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+    let snippet = r#"<img src="https://px.example/p.gif" alt="">"#;
+    let create = serde_json::json!({ "original_url": "https://example.com/launch?a=1&b=2", "tracking_snippet": snippet });
+
+    // Snippets need an owner, and are bounded
+    let req = test::TestRequest::post().uri("/api/shorten").set_json(&create).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+    let oversized = serde_json::json!({
+        "original_url": "https://example.com/",
+        "tracking_snippet": "x".repeat(MAX_TRACKING_SNIPPET_LENGTH + 1),
+    });
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(oversized).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(&create).to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let code = created.short_url;
+
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "no-store");
+    assert_eq!(resp.headers().get("Content-Security-Policy").unwrap(), "sandbox allow-scripts");
+    let page = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(page.contains(snippet));
+    assert!(page.contains(r#"location.replace("https://example.com/launch?a=1&b=2")"#));
+    assert!(page.contains(r#"url=https://example.com/launch?a=1&amp;b=2"#));
+    redirector.flush_visits().await;
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((stats.visits, stats.tracking_snippet), (1, None));
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.tracking_snippet.is_none());
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).insert_header(alice).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.tracking_snippet.as_deref(), Some(snippet));
+
+    // Clearing the snippet brings the plain redirect back
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "tracking_snippet": "" })).to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(updated.tracking_snippet.is_none());
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+}
+
+#[actix_rt::test]
+async fn test_redirect_headers_are_sent_from_an_allowlist() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(redirector)
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    // Headers that would change the redirect itself are refused
+    for name in ["Location", "Set-Cookie", "Cache-Control", "X-", "Bad Name"] {
+        let create = serde_json::json!({ "original_url": "https://example.com/", "redirect_headers": { name: "x" } });
+        let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(create).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422, "{}", name);
+    }
+    let create = serde_json::json!({ "original_url": "https://example.com/", "redirect_headers": { "X-Campaign": "a\nb" } });
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(create).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+
+    let create = serde_json::json!({
+        "original_url": "https://example.com/",
+        "redirect_headers": { "Referrer-Policy": "no-referrer", "X-Campaign": " spring " },
+    });
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(create).to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let code = created.short_url;
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/");
+    assert_eq!(resp.headers().get("Referrer-Policy").unwrap(), "no-referrer");
+    assert_eq!(resp.headers().get("X-Campaign").unwrap(), "spring");
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.redirect_headers.is_none());
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.redirect_headers.is_none());
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", code)).insert_header(alice).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    let headers = stats.redirect_headers.unwrap();
+    assert_eq!(headers.keys().collect::<Vec<_>>(), ["referrer-policy", "x-campaign"]);
+
+    // `{}` removes them, and the next redirect goes without
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "redirect_headers": {} })).to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(updated.redirect_headers.is_none());
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get("Referrer-Policy").is_none());
+}
+
+#[actix_rt::test]
+async fn test_hidden_referrers_override_the_links_own_policy() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(redirector)
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(Config::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+    let create = serde_json::json!({
+        "original_url": "https://example.com/",
+        "hide_referrer": true,
+        "redirect_headers": { "Referrer-Policy": "unsafe-url" },
+    });
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice).set_json(create).to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let code = created.short_url;
+
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    assert_eq!(resp.headers().get("Referrer-Policy").unwrap(), "no-referrer");
+
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", code)).insert_header(alice)
+        .set_json(serde_json::json!({ "hide_referrer": false })).to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(!updated.hide_referrer);
+    let req = test::TestRequest::get().uri(&format!("/{}", code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Referrer-Policy").unwrap(), "unsafe-url");
+}
+
+#[actix_rt::test]
+async fn test_regenerated_links_keep_old_codes_for_a_grace_period() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/leaked" })).to_request();
+    let old_code = read_data::<CreateUrlResponse>(test::call_service(&app, req).await).await.short_url;
+    let req = test::TestRequest::get().uri(&format!("/{}", old_code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    redirector.flush_visits().await;
+
+    // Only the owner can regenerate, within the grace period limit
+    let regenerate = |code: &str, key: &'static str, body: serde_json::Value| {
+        test::TestRequest::post().uri(&format!("/api/urls/{}/regenerate", code))
+            .insert_header((API_KEY_HEADER, key)).set_json(body).to_request()
+    };
+    let req = regenerate(&old_code, "bob-key", serde_json::json!({}));
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let req = regenerate(&old_code, "alice-key", serde_json::json!({ "grace_period_secs": MAX_GRACE_PERIOD_SECS + 1 }));
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+
+    let req = regenerate(&old_code, "alice-key", serde_json::json!({ "stats": "fork", "grace_period_secs": 3600 }));
+    let regenerated: RegeneratedCodeResponse = read_data(test::call_service(&app, req).await).await;
+    let new_code = regenerated.link.short_url;
+    assert_ne!(new_code, old_code);
+    assert_eq!((regenerated.link.visits, regenerated.retired.visits), (0, 1));
+
+    // The old code still redirects, counting visits for the link under its new code
+    let req = test::TestRequest::get().uri(&format!("/{}", old_code)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/leaked");
+    redirector.flush_visits().await;
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", new_code)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.visits, 1);
+    assert_eq!(stats.retired_codes, vec![regenerated.retired]);
+
+    // Without a grace period the old code stops working at once
+    let req = regenerate(&new_code, "alice-key", serde_json::json!({ "style": "words" }));
+    let regenerated: RegeneratedCodeResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(regenerated.link.visits, 1);
+    assert_eq!(regenerated.link.short_url.split('-').count(), 3);
+    let req = test::TestRequest::get().uri(&format!("/{}", new_code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_taken_custom_alias_is_a_conflict_with_suggestions() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let shorten = |alias: &str| test::TestRequest::post().uri("/api/shorten")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "original_url": "https://example.com/launch", "custom_alias": alias }))
+        .to_request();
+    assert_eq!(test::call_service(&app, shorten("launch")).await.status().as_u16(), 200);
+
+    let resp = test::call_service(&app, shorten("launch")).await;
+    assert_eq!(resp.status().as_u16(), 409);
+    let body: ApiResponse<()> = test::read_body_json(resp).await;
+    let error = body.error.unwrap();
+    assert_eq!(error.code, "alias_taken");
+    let details = error.details.unwrap();
+    assert_eq!((details["alias"].as_str(), details["suggestions"][0].as_str()), (Some("launch"), Some("launch-2")));
+
+    // Route names are refused before they reach storage
+    let resp = test::call_service(&app, shorten("metrics")).await;
+    assert_eq!(resp.status().as_u16(), 422);
+}
+
+#[actix_rt::test]
+async fn test_aliases_redirect_to_their_link_and_share_its_stats() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/launch", "custom_alias": "launch" }))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let add_alias = |key: &'static str, alias: &str| {
+        test::TestRequest::post().uri("/api/urls/launch/aliases")
+            .insert_header((API_KEY_HEADER, key)).set_json(serde_json::json!({ "custom_alias": alias })).to_request()
+    };
+    let added: LinkAlias = read_data(test::call_service(&app, add_alias("alice-key", "promo")).await).await;
+    assert_eq!(added.short_code, "promo");
+    assert_eq!(test::call_service(&app, add_alias("alice-key", "launch")).await.status().as_u16(), 409);
+    assert_eq!(test::call_service(&app, add_alias("alice-key", "bad alias!")).await.status().as_u16(), 422);
+    assert_eq!(test::call_service(&app, add_alias("bob-key", "spare")).await.status().as_u16(), 404);
+
+    // Visits through the alias count towards the link
+    let req = test::TestRequest::get().uri("/promo").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Location").unwrap(), "https://example.com/launch");
+    redirector.flush_visits().await;
+    let req = test::TestRequest::get().uri("/api/stats/launch").to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.visits, 1);
+    assert_eq!(stats.aliases, vec![added]);
+
+    let req = test::TestRequest::delete().uri("/api/urls/launch/aliases/promo").insert_header(alice).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let req = test::TestRequest::get().uri("/promo").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_campaign_stats_aggregate_visits_of_member_links() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(crate::privacy::IpAnonymizer::default()))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+    for alias in ["spring", "summer"] {
+        let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+            .set_json(serde_json::json!({ "original_url": "https://example.com/launch", "custom_alias": alias }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+    let req = test::TestRequest::post().uri("/api/campaigns").insert_header(alice)
+        .set_json(serde_json::json!({ "name": "Launch" })).to_request();
+    let campaign: crate::models::Campaign = read_data(test::call_service(&app, req).await).await;
+    let req = test::TestRequest::post().uri("/api/campaigns").insert_header(alice)
+        .set_json(serde_json::json!({ "name": "Launch" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 409);
+
+    // Only the owner's links can be added, and only by the owner
+    for (key, code, status) in [("alice-key", "spring", 204), ("alice-key", "summer", 204), ("bob-key", "spring", 404)] {
+        let req = test::TestRequest::put().uri(&format!("/api/campaigns/{}/links/{}", campaign.id, code))
+            .insert_header((API_KEY_HEADER, key)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), status);
+    }
+
+    for (code, client) in [("spring", "203.0.113.7:4000"), ("summer", "203.0.113.7:4001"), ("summer", "198.51.100.1:4000")] {
+        let req = test::TestRequest::get().uri(&format!("/{}", code)).peer_addr(client.parse().unwrap()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    }
+    redirector.flush_visits().await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/campaigns/{}/stats?days=7", campaign.id))
+        .insert_header(alice).to_request();
+    let stats: crate::models::CampaignStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((stats.links, stats.visits, stats.period_visits, stats.uniques), (2, 3, 3, 2));
+    assert_eq!(stats.daily.len(), 1);
+    assert_eq!((stats.daily[0].visits, stats.daily[0].uniques), (3, 2));
+
+    let req = test::TestRequest::get().uri(&format!("/api/campaigns/{}/stats", campaign.id))
+        .insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[cfg(feature = "parquet-export")]
+#[actix_rt::test]
+async fn test_admins_export_visit_events_as_parquet() {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let keys = HashMap::from([("admin-key".to_string(), "ops".to_string()), ("bob-key".to_string(), "bob".to_string())]);
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+    for _ in 0..3 {
+        let req = test::TestRequest::get().uri(&format!("/{}", short_code)).to_request();
+        test::call_service(&app, req).await;
+    }
+    redirector.flush_visits().await;
+
+    let today = chrono::Utc::now().date_naive();
+    let export = |key: &'static str, from: chrono::NaiveDate, to: chrono::NaiveDate| {
+        test::TestRequest::get().uri(&format!("/api/export/visits.parquet?from={}&to={}", from, to))
+            .insert_header((API_KEY_HEADER, key)).to_request()
+    };
+    assert_eq!(test::call_service(&app, export("bob-key", today, today)).await.status().as_u16(), 403);
+    let yesterday = today.pred_opt().unwrap();
+    assert_eq!(test::call_service(&app, export("admin-key", today, yesterday)).await.status().as_u16(), 422);
+
+    let resp = test::call_service(&app, export("admin-key", yesterday, today)).await;
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/vnd.apache.parquet");
+    let file = test::read_body(resp).await;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+}
+
+#[cfg(feature = "pprof")]
+#[actix_rt::test]
+async fn test_admins_take_cpu_profiles() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let keys = HashMap::from([("admin-key".to_string(), "ops".to_string()), ("bob-key".to_string(), "bob".to_string())]);
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(redirector)
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let profile = |key: &'static str| {
+        test::TestRequest::get().uri("/api/admin/debug/pprof?seconds=1").insert_header((API_KEY_HEADER, key)).to_request()
+    };
+
+    assert_eq!(test::call_service(&app, profile("bob-key")).await.status().as_u16(), 403);
+    // Something has to be running for the profile to sample it
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let busy = std::thread::spawn({
+        let done = done.clone();
+        move || {
+            let mut spins: u64 = 0;
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                spins = std::hint::black_box(spins.wrapping_add(1));
+            }
+        }
+    });
+    let resp = test::call_service(&app, profile("admin-key")).await;
+    done.store(true, std::sync::atomic::Ordering::Relaxed);
+    busy.join().unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "image/svg+xml");
+    assert!(test::read_body(resp).await.starts_with(b"<?xml"));
+}
+
+#[actix_rt::test]
+async fn test_admins_list_and_replay_webhook_deliveries() {
+    use crate::webhooks::{EventKind, GoalReached, Notifier, WebhookEvent, WebhookNotifier, Webhooks};
+
+    // Setup: one failed delivery to an unreachable receiver
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let notifier = Arc::new(WebhookNotifier::new("goals", "http://127.0.0.1:9/".to_string(), HttpClient::default(), storage.clone()));
+    let event = WebhookEvent::new(EventKind::GoalReached(GoalReached {
+        short_code: "launch".to_string(),
+        original_url: "https://example.com".to_string(),
+        title: None,
+        owner: None,
+        click_goal: 10,
+        visits: 10,
+    }));
+    assert!(notifier.notify(&event).await.is_err());
+    let mut webhooks = Webhooks::default();
+    webhooks.register(notifier);
+
+    let keys = HashMap::from([("admin-key".to_string(), "ops".to_string()), ("bob-key".to_string(), "bob".to_string())]);
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .app_data(web::Data::new(webhooks))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let get = |key: &'static str, uri: &str| {
+        test::TestRequest::get().uri(uri).insert_header((API_KEY_HEADER, key)).to_request()
+    };
+
+    assert_eq!(test::call_service(&app, get("bob-key", "/api/webhooks/goals/deliveries")).await.status().as_u16(), 403);
+    assert_eq!(test::call_service(&app, get("admin-key", "/api/webhooks/other/deliveries")).await.status().as_u16(), 404);
+    let deliveries: Vec<crate::models::WebhookDelivery> =
+        read_data(test::call_service(&app, get("admin-key", "/api/webhooks/goals/deliveries")).await).await;
+    assert_eq!((deliveries.len(), deliveries[0].delivered), (1, false));
+
+    let replay = |id: i64| {
+        test::TestRequest::post().uri(&format!("/api/webhooks/goals/deliveries/{}/replay", id))
+            .insert_header((API_KEY_HEADER, "admin-key")).to_request()
+    };
+    assert_eq!(test::call_service(&app, replay(99)).await.status().as_u16(), 404);
+    let replayed: crate::models::WebhookDelivery = read_data(test::call_service(&app, replay(deliveries[0].id)).await).await;
+    assert_eq!(replayed.replay_of, Some(deliveries[0].id));
+}
+
+#[actix_rt::test]
+async fn test_route_classes_are_throttled_separately() {
+    use crate::middleware::Throttle;
+    use crate::throttle::{ClassStats, RouteThrottle, ThrottleConfig};
+
+    // Setup: one redirect per second, and one admin request at a time
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let throttle = web::Data::new(RouteThrottle::new(&ThrottleConfig {
+        redirect: "/1".parse().unwrap(),
+        admin: "1".parse().unwrap(),
+        ..ThrottleConfig::default()
+    }));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector)
+            .app_data(throttle.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .wrap(Throttle)
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+
+    let redirect = || test::TestRequest::get().uri(&format!("/{}", short_code)).to_request();
+    assert_eq!(test::call_service(&app, redirect()).await.status().as_u16(), 302);
+    let resp = test::call_service(&app, redirect()).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert_eq!(resp.headers().get("retry-after").unwrap(), "1");
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", short_code)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    // An admin response holds its slot until its body has been read
+    let admin = || {
+        test::TestRequest::get().uri("/api/admin/throttle").insert_header((API_KEY_HEADER, "admin-key")).to_request()
+    };
+    let pending = test::call_service(&app, admin()).await;
+    assert_eq!(test::call_service(&app, admin()).await.status().as_u16(), 429);
+    let stats: Vec<ClassStats> = read_data(pending.map_into_boxed_body()).await;
+    assert_eq!((stats[0].rejected_rate, stats[3].in_flight), (1, Some(1)));
+    assert_eq!(test::call_service(&app, admin()).await.status().as_u16(), 200);
+}
+
+#[actix_rt::test]
+async fn test_requests_count_against_their_class_latency_objective() {
+    use crate::middleware::RecordSlo;
+    use crate::slo::{ClassSlo, SloConfig, SloCounters};
+    use crate::throttle::RouteClass;
+    use std::time::Duration;
+
+    // Setup: API reads are good within 50ms
+    async fn slow() -> HttpResponse {
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        HttpResponse::Ok().finish()
+    }
+    async fn failing() -> HttpResponse {
+        HttpResponse::ServiceUnavailable().finish()
+    }
+    let counters = web::Data::new(SloCounters::new(SloConfig { read_api: Duration::from_millis(50), ..SloConfig::default() }));
+    let app = test::init_service(
+        App::new()
+            .app_data(counters.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .wrap(RecordSlo)
+            .route("/api/slow", web::get().to(slow))
+            .route("/api/failing", web::get().to(failing))
+            .route("/health", web::get().to(HttpResponse::Ok))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    for uri in ["/api/slow", "/api/failing", "/api/missing", "/health"] {
+        test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    }
+
+    // Slow and failed requests count only towards the total; health checks are not counted
+    let req = test::TestRequest::get().uri("/api/admin/slo").insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let stats: Vec<ClassSlo> = read_data(test::call_service(&app, req).await).await;
+    let reads = stats.iter().find(|stats| stats.class == RouteClass::ReadApi).unwrap();
+    assert_eq!((reads.good, reads.total, reads.threshold_ms), (1, 3, 50));
+    assert_eq!(stats.iter().map(|stats| stats.total).sum::<u64>(), 3);
+
+    let req = test::TestRequest::get().uri("/api/admin/slo").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn test_admins_inspect_the_runtime() {
+    // Setup: visits stay buffered until flushed
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let config = RedirectConfig { visit_flush_interval: std::time::Duration::from_secs(3600), ..RedirectConfig::default() };
+    let redirector = web::Data::new(Redirector::new(storage.clone(), config));
+    let service = web::Data::new(UrlService::new(storage));
+    let keys = HashMap::from([("admin-key".to_string(), "ops".to_string()), ("bob-key".to_string(), "bob".to_string())]);
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector)
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+    for _ in 0..2 {
+        let req = test::TestRequest::get().uri(&format!("/{}", short_code)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    }
+
+    let runtime = |key: &'static str| {
+        test::TestRequest::get().uri("/api/admin/runtime").insert_header((API_KEY_HEADER, key)).to_request()
+    };
+    assert_eq!(test::call_service(&app, runtime("bob-key")).await.status().as_u16(), 403);
+    let stats: RuntimeResponse = read_data(test::call_service(&app, runtime("admin-key")).await).await;
+    // The first redirect missed the cache and filled it for the second
+    assert_eq!((stats.redirect_cache.entries, stats.redirect_cache.hits, stats.redirect_cache.misses), (1, 1, 1));
+    assert_eq!(stats.redirect_cache.hit_ratio, Some(0.5));
+    assert!(stats.response_cache.is_none());
+    assert_eq!((stats.buffered_visits.visits, stats.buffered_visits.links, stats.buffered_visits.events), (2, 1, 2));
+    assert!(stats.tokio.workers >= 1);
+    assert!(stats.rss_bytes.is_some_and(|rss| rss > 0));
+}
+
+#[actix_rt::test]
+async fn test_namespaced_links_redirect_under_their_prefix() {
+    // Setup
+    let namespaces = Namespaces::new(&["t"]);
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    let service = web::Data::new(UrlService::new(storage).with_namespaces(namespaces.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_namespaces(&namespaces))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    for (namespace, url) in [(Some("t"), "https://example.com/team"), (None, "https://example.com/root")] {
+        let req = test::TestRequest::post().uri("/api/shorten")
+            .set_json(serde_json::json!({ "original_url": url, "custom_alias": "docs", "namespace": namespace }))
+            .to_request();
+        let _: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    }
+
+    let req = test::TestRequest::get().uri("/t/docs").to_request();
+    assert_eq!(test::call_service(&app, req).await.headers().get("Location").unwrap(), "https://example.com/team");
+    let req = test::TestRequest::get().uri("/docs").to_request();
+    assert_eq!(test::call_service(&app, req).await.headers().get("Location").unwrap(), "https://example.com/root");
+    let req = test::TestRequest::get().uri("/t/missing").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // The API addresses namespaced codes by their full path
+    let req = test::TestRequest::get().uri("/api/stats/t/docs").to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.short_url, "t/docs");
+    assert_eq!(stats.original_url, "https://example.com/team");
+
+    // Admins can drop a cached namespaced code
+    assert!(redirector.cache().get("t/docs").is_some());
+    let req = test::TestRequest::post().uri("/api/admin/cache/invalidate/t/docs")
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let invalidated: CacheFlushResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(invalidated.evicted, 1);
+    assert!(redirector.cache().get("t/docs").is_none());
+}
+
+#[actix_rt::test]
+async fn test_go_links_are_created_searched_and_visited_by_keyword() {
+    // Setup
+    let namespaces = Namespaces::default().with_keywords("go");
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    let service = web::Data::new(UrlService::new(storage).with_namespaces(namespaces.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_namespaces(&namespaces))
+            .configure(crate::routes::configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post().uri("/api/go")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "keyword": "Payroll", "url": "https://hr.example.com", "title": "Payroll portal" }))
+        .to_request();
+    let created: GoLink = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((created.keyword.as_str(), created.owner.as_deref()), ("payroll", Some("alice")));
+
+    // Visitors may write the keyword loosely
+    let req = test::TestRequest::get().uri("/go/PAYROLL").to_request();
+    assert_eq!(test::call_service(&app, req).await.headers().get("Location").unwrap(), "https://hr.example.com/");
+
+    let req = test::TestRequest::get().uri("/api/go?q=portal").insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    let found: Vec<GoLink> = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(found, [created]);
+    let req = test::TestRequest::get().uri("/api/go/popular?limit=5").insert_header((API_KEY_HEADER, "bob-key")).to_request();
+    let popular: Vec<GoLink> = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(popular.len(), 1);
+
+    // Keywords and destinations are validated; the API needs a key
+    let req = test::TestRequest::post().uri("/api/go")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "keyword": "", "url": "not a url" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+    let req = test::TestRequest::get().uri("/api/go/popular").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn test_nested_go_links_redirect_and_change_hands() {
+    // Setup
+    let namespaces = Namespaces::default().with_keywords("go");
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    let service = web::Data::new(UrlService::new(storage).with_namespaces(namespaces.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_namespaces(&namespaces))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let create = |key: &str| test::TestRequest::post().uri("/api/go")
+        .insert_header((API_KEY_HEADER, key.to_string()))
+        .set_json(serde_json::json!({ "keyword": "team/runbook", "url": "https://wiki.example.com/runbook" }))
+        .to_request();
+
+    let _: GoLink = read_data(test::call_service(&app, create("alice-key")).await).await;
+    let req = test::TestRequest::get().uri("/go/Team/Runbook").to_request();
+    assert_eq!(test::call_service(&app, req).await.headers().get("Location").unwrap(), "https://wiki.example.com/runbook");
+
+    // A taken keyword is a conflict naming the owner, with free alternatives
+    let resp = test::call_service(&app, create("bob-key")).await;
+    assert_eq!(resp.status().as_u16(), 409);
+    let body: ApiResponse<()> = test::read_body_json(resp).await;
+    let error = body.error.unwrap();
+    assert_eq!(error.code, "keyword_taken");
+    let details = error.details.unwrap();
+    assert_eq!((details["owner"].as_str(), details["suggestions"][0].as_str()), (Some("alice"), Some("team/runbook-2")));
+
+    // Nested codes are addressed by their full path in the API
+    let req = test::TestRequest::post().uri("/api/links/go/team/runbook/transfer")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "to": "bob" }))
+        .to_request();
+    let transferred: TransferResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((transferred.short_code.as_str(), transferred.owner.as_str()), ("go/team/runbook", "bob"));
+    let req = test::TestRequest::get().uri("/api/stats/go/team/runbook").to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.short_url, "go/team/runbook");
+
+    // The old owner can no longer hand it on
+    let req = test::TestRequest::post().uri("/api/links/go/team/runbook/transfer")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "to": "carol" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_link_metadata_is_set_updated_and_returned() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .service(web::resource("/api/shorten").route(web::post().to(create_url)))
+            .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
+            .service(web::resource("/api/links/{short_code}").route(web::patch().to(update_link)))
+    ).await;
+
+    // Create with metadata
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({
+            "original_url": "https://example.com/spring",
+            "title": "  Spring launch ",
+            "notes": "Newsletter footer",
+        }))
+        .to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", created.short_url)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(stats.title.as_deref(), Some("Spring launch"));
+    assert_eq!(stats.notes.as_deref(), Some("Newsletter footer"));
+    assert_eq!(stats.created_by, None);
+
+    // Omitted fields are kept, empty ones cleared
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/links/{}", created.short_url))
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "notes": "", "created_by": "growth team" }))
+        .to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(updated.title.as_deref(), Some("Spring launch"));
+    assert_eq!(updated.notes, None);
+    assert_eq!(updated.created_by.as_deref(), Some("growth team"));
+
+    // Other owners cannot see or change the link
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/links/{}", created.short_url))
+        .insert_header((API_KEY_HEADER, "bob-key"))
+        .set_json(serde_json::json!({ "title": "mine now" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Oversized fields are rejected
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/links/{}", created.short_url))
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_json(serde_json::json!({ "title": "t".repeat(MAX_TITLE_LENGTH + 1) }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+}
+
+#[actix_rt::test]
+async fn test_link_updates_can_be_conditional_on_a_version() {
+    let service = create_test_service().await;
+    let config = Config { require_if_match: true, ..Config::default() };
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(config))
+            .service(web::resource("/api/shorten").route(web::post().to(create_url)))
+            .service(web::resource("/api/stats/{short_code}").route(web::get().to(get_stats)))
+            .service(web::resource("/api/links/{short_code}").route(web::patch().to(update_link)))
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/spring" })).to_request();
+    let created: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let link = format!("/api/links/{}", created.short_url);
+
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", created.short_url)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"1\"");
+    assert_eq!(read_data::<UrlStats>(resp).await.version, 1);
+
+    // Unconditional updates are refused when preconditions are required
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice)
+        .set_json(serde_json::json!({ "title": "Spring" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 428);
+
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice).insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(serde_json::json!({ "title": "Spring" })).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"2\"");
+    assert_eq!(read_data::<UrlStats>(resp).await.version, 2);
+
+    // A second editor still working from version 1 does not overwrite the change
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice).insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(serde_json::json!({ "title": "Autumn" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice)
+        .set_json(serde_json::json!({ "title": "Autumn", "version": 1 })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice).insert_header((header::IF_MATCH, "W/\"2\""))
+        .set_json(serde_json::json!({ "title": "Autumn" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 412);
+
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice)
+        .set_json(serde_json::json!({ "title": "Autumn", "version": 2 })).to_request();
+    let updated: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((updated.title.as_deref(), updated.version), (Some("Autumn"), 3));
+
+    // `If-Match: *` opts out of the check
+    let req = test::TestRequest::patch().uri(&link).insert_header(alice).insert_header((header::IF_MATCH, "*"))
+        .set_json(serde_json::json!({ "title": "Winter" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
+
+#[actix_rt::test]
+async fn test_collections_organize_links() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    // Create, with duplicate names rejected
+    let req = test::TestRequest::post().uri("/api/collections").insert_header(alice)
+        .set_json(serde_json::json!({ "name": "Spring campaign" })).to_request();
+    let spring: CollectionResponse = read_data(test::call_service(&app, req).await).await;
+    let req = test::TestRequest::post().uri("/api/collections").insert_header(alice)
+        .set_json(serde_json::json!({ "name": "Spring campaign" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 409);
+
+    // Assign links at creation and later
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(alice)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/a", "collection_id": spring.id }))
+        .to_request();
+    let first: CreateUrlResponse = read_data(test::call_service(&app, req).await).await;
+    let second = service
+        .create_owned_short_url("https://example.com/b".to_string(), Some("alice".to_string()))
+        .await
+        .unwrap();
+    service.create_owned_short_url("https://example.com/c".to_string(), Some("alice".to_string())).await.unwrap();
+    let req = test::TestRequest::patch().uri(&format!("/api/links/{}", second.short_code)).insert_header(alice)
+        .set_json(serde_json::json!({ "collection_id": spring.id })).to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    service.get_original_url(&first.short_url).await.unwrap();
+
+    // Filter the link list and aggregate per collection
+    let req = test::TestRequest::get().uri(&format!("/api/quick/recent?collection={}", spring.id))
+        .insert_header(alice).to_request();
+    let links: Vec<UrlStats> = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(links.len(), 2);
+    assert!(links.iter().all(|link| link.collection_id == Some(spring.id)));
+
+    let req = test::TestRequest::get().uri("/api/collections").insert_header(alice).to_request();
+    let collections: Vec<CollectionResponse> = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((collections.len(), collections[0].links, collections[0].visits), (1, 2, 1));
+
+    // Rename
+    let req = test::TestRequest::patch().uri(&format!("/api/collections/{}", spring.id)).insert_header(alice)
+        .set_json(serde_json::json!({ "name": "Spring 2026" })).to_request();
+    let renamed: CollectionResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((renamed.name.as_str(), renamed.links), ("Spring 2026", 2));
+
+    // Other owners can neither see nor use the collection
+    let bob = (API_KEY_HEADER, "bob-key");
+    let req = test::TestRequest::get().uri(&format!("/api/collections/{}", spring.id)).insert_header(bob).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    let req = test::TestRequest::post().uri("/api/shorten").insert_header(bob)
+        .set_json(serde_json::json!({ "original_url": "https://example.com/d", "collection_id": spring.id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Deleting keeps the links, unassigned
+    let req = test::TestRequest::delete().uri(&format!("/api/collections/{}", spring.id)).insert_header(alice).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let stats = service.get_url_stats(&first.short_url).await.unwrap();
+    assert_eq!(stats.collection_id, None);
+}
+
+#[actix_rt::test]
+async fn test_api_key_lifecycle() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let api_keys = ApiKeys::new(HashMap::from([
+        ("alice-key".to_string(), "alice".to_string()),
+        ("bob-key".to_string(), "bob".to_string()),
+    ]))
+    .with_storage(storage.clone());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(web::Data::new(api_keys))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let alice = (API_KEY_HEADER, "alice-key");
+
+    // Issue a key; its plaintext is only in this response
+    let req = test::TestRequest::post().uri("/api/keys").insert_header(alice)
+        .set_json(serde_json::json!({ "name": "ci" })).to_request();
+    let issued: IssuedApiKeyResponse = read_data(test::call_service(&app, req).await).await;
+    assert!(issued.key.starts_with(&issued.details.prefix));
+    assert_eq!(issued.details.last_used_at, None);
+
+    // The new key authenticates and its use is recorded
+    let req = test::TestRequest::get().uri("/api/keys").insert_header((API_KEY_HEADER, issued.key.as_str())).to_request();
+    let resp = test::call_service(&app, req).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(!body.contains(&issued.key));
+    let keys: Vec<ApiKeyResponse> = serde_json::from_str::<ApiResponse<_>>(&body).unwrap().data.unwrap();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].last_used_at.is_some());
+
+    // Rotating replaces the key under the same name
+    let req = test::TestRequest::post().uri(&format!("/api/keys/{}/rotate", issued.details.id)).insert_header(alice)
+        .to_request();
+    let rotated: IssuedApiKeyResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(rotated.details.name.as_deref(), Some("ci"));
+    assert_ne!(rotated.key, issued.key);
+    let req = test::TestRequest::get().uri("/api/keys").insert_header((API_KEY_HEADER, issued.key.as_str())).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+
+    // Other owners can neither see nor revoke the key
+    let bob = (API_KEY_HEADER, "bob-key");
+    let req = test::TestRequest::get().uri("/api/keys").insert_header(bob).to_request();
+    let keys: Vec<ApiKeyResponse> = read_data(test::call_service(&app, req).await).await;
+    assert!(keys.is_empty());
+    let req = test::TestRequest::delete().uri(&format!("/api/keys/{}", rotated.details.id)).insert_header(bob)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Revoking stops the key from working
+    let req = test::TestRequest::delete().uri(&format!("/api/keys/{}", rotated.details.id)).insert_header(alice)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 204);
+    let req = test::TestRequest::get().uri("/api/keys").insert_header((API_KEY_HEADER, rotated.key.as_str())).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+    let req = test::TestRequest::delete().uri(&format!("/api/keys/{}", rotated.details.id)).insert_header(alice)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_dashboard_session_flow() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let sessions = crate::auth::Sessions::new(storage.clone(), crate::auth::SessionConfig::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(test_api_keys())
+            .app_data(web::Data::new(sessions))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let page = |resp: actix_web::dev::ServiceResponse| async {
+        String::from_utf8(test::read_body(resp).await.to_vec()).unwrap()
+    };
+
+    // Signed out, the dashboard shows the sign-in form
+    let req = test::TestRequest::get().uri("/dashboard").to_request();
+    assert!(page(test::call_service(&app, req).await).await.contains("action=\"/dashboard/login\""));
+
+    // Invalid keys and cross-origin posts cannot sign in
+    let req = test::TestRequest::post().uri("/dashboard/login")
+        .set_form([("api_key", "wrong-key")]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+    let req = test::TestRequest::post().uri("/dashboard/login")
+        .insert_header((header::ORIGIN, "https://evil.example"))
+        .set_form([("api_key", "alice-key")]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Signing in sets a hardened session cookie
+    let req = test::TestRequest::post().uri("/dashboard/login")
+        .set_form([("api_key", "alice-key")]).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 303);
+    let cookie = resp.response().cookies().find(|c| c.name() == crate::auth::SESSION_COOKIE).unwrap().into_owned();
+    assert_eq!((cookie.http_only(), cookie.secure()), (Some(true), Some(true)));
+
+    let req = test::TestRequest::get().uri("/dashboard").cookie(cookie.clone()).to_request();
+    let body = page(test::call_service(&app, req).await).await;
+    assert!(body.contains("Signed in as alice"));
+    let csrf_token = body.split("name=\"csrf_token\" value=\"").nth(1).unwrap().split('"').next().unwrap().to_string();
+
+    // Signing out requires the session's CSRF token
+    let req = test::TestRequest::post().uri("/dashboard/logout").cookie(cookie.clone())
+        .set_form([(crate::auth::CSRF_FIELD, "forged")]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+    let req = test::TestRequest::post().uri("/dashboard/logout").cookie(cookie.clone())
+        .set_form([(crate::auth::CSRF_FIELD, csrf_token.as_str())]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 303);
+
+    // The old cookie no longer works
+    let req = test::TestRequest::post().uri("/dashboard/logout").cookie(cookie)
+        .set_form([(crate::auth::CSRF_FIELD, csrf_token.as_str())]).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn test_dashboard_shows_owner_names_verbatim() {
+    // Setup: an owner named after a page placeholder
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let sessions = crate::auth::Sessions::new(storage.clone(), crate::auth::SessionConfig::default());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage)))
+            .app_data(web::Data::new(ApiKeys::new(HashMap::from([(
+                "mallory-key".to_string(),
+                "{{csrf_token}}".to_string(),
+            )]))))
+            .app_data(web::Data::new(sessions))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let req = test::TestRequest::post().uri("/dashboard/login")
+        .set_form([("api_key", "mallory-key")]).to_request();
+    let resp = test::call_service(&app, req).await;
+    let cookie = resp.response().cookies().find(|c| c.name() == crate::auth::SESSION_COOKIE).unwrap().into_owned();
+
+    let req = test::TestRequest::get().uri("/dashboard").cookie(cookie).to_request();
+    let body = String::from_utf8(test::read_body(test::call_service(&app, req).await).await.to_vec()).unwrap();
+    assert!(body.contains("Signed in as {{csrf_token}}."));
+}
+
+#[actix_rt::test]
+async fn test_admin_cache_endpoints() {
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let mut keys = HashMap::new();
+    keys.insert("admin-key".to_string(), "ops".to_string());
+    keys.insert("alice-key".to_string(), "alice".to_string());
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    for url in ["https://example.com/a", "https://example.com/b"] {
+        let short_code = service.create_short_url(url.to_string()).await.unwrap().short_code;
+        let req = test::TestRequest::get().uri(&format!("/{}", short_code)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    }
+    let cached = service.create_short_url("https://example.com/c".to_string()).await.unwrap().short_code;
+    redirector.resolve(&cached).await.unwrap();
+    assert_eq!(redirector.cache().len(), 3);
+
+    // Only admin keys may use the endpoints
+    let req = test::TestRequest::post().uri("/api/admin/cache/flush").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+    let req = test::TestRequest::post().uri("/api/admin/cache/flush")
+        .insert_header((API_KEY_HEADER, "alice-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    // Invalidate a single code, then flush the rest
+    let req = test::TestRequest::post().uri(&format!("/api/admin/cache/invalidate/{}", cached))
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let invalidated: CacheFlushResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(invalidated.evicted, 1);
+    assert!(redirector.cache().get(&cached).is_none());
+
+    let req = test::TestRequest::post().uri("/api/admin/cache/flush")
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let flushed: CacheFlushResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(flushed.evicted, 2);
+    assert!(redirector.cache().is_empty());
+
+    // The in-memory backend has no prepared statements to report
+    let req = test::TestRequest::get().uri("/api/admin/storage/statement-cache")
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_admin_visit_changes_are_audited() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let admin = (API_KEY_HEADER, "admin-key");
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+    for _ in 0..3 {
+        redirector.resolve(&short_code).await.unwrap();
+    }
+
+    // Pending redirect visits are included before resetting
+    let req = test::TestRequest::post().uri(&format!("/api/admin/urls/{}/reset-visits", short_code)).insert_header(admin)
+        .set_json(serde_json::json!({ "reason": "bot storm" })).to_request();
+    let reset: VisitCountResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((reset.previous, reset.visits), (3, 0));
+
+    // Adjustments add, and subtract down to zero at most
+    let req = test::TestRequest::post().uri(&format!("/api/admin/urls/{}/adjust-visits", short_code)).insert_header(admin)
+        .set_json(serde_json::json!({ "delta": 1200, "reason": "migrated from old shortener" })).to_request();
+    let adjusted: VisitCountResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((adjusted.previous, adjusted.visits), (0, 1200));
+    let req = test::TestRequest::post().uri(&format!("/api/admin/urls/{}/adjust-visits", short_code)).insert_header(admin)
+        .set_json(serde_json::json!({ "delta": -5000 })).to_request();
+    let adjusted: VisitCountResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(adjusted.visits, 0);
+
+    let req = test::TestRequest::post().uri(&format!("/api/admin/urls/{}/adjust-visits", short_code)).insert_header(admin)
+        .set_json(serde_json::json!({ "delta": 0 })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+    let req = test::TestRequest::post().uri("/api/admin/urls/missing/reset-visits").insert_header(admin)
+        .set_json(serde_json::json!({})).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    // Each change is in the audit log, next to the raw admin calls
+    let mut entries = storage.list_audit_entries(20).await.unwrap();
+    entries.retain(|entry| entry.action != "admin.request");
+    let actions: Vec<_> = entries.iter().map(|entry| (entry.actor.as_str(), entry.action.as_str())).collect();
+    assert_eq!(actions, [("ops", "visits.adjust"), ("ops", "visits.adjust"), ("ops", "visits.reset")]);
+    assert_eq!(entries[2].target.as_deref(), Some(short_code.as_str()));
+    assert_eq!(entries[2].details["reason"], "bot storm");
+    assert_eq!(entries[2].details["previous"], 3);
+}
+
+#[actix_rt::test]
+async fn test_admin_mutations_are_audited_with_redacted_payloads() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default())))
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([
+                    ("admin-key".to_string(), "ops".to_string()),
+                    ("user-key".to_string(), "alice".to_string()),
+                ]))
+                .with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+
+    // The handler still sees the whole body
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/admin/urls/{}/adjust-visits?session_token=abc&dry=no", short_code))
+        .insert_header((API_KEY_HEADER, "admin-key"))
+        .set_json(serde_json::json!({ "delta": 7, "reason": "recount", "extra": { "api_key": "sk-123" } }))
+        .to_request();
+    let adjusted: VisitCountResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(adjusted.visits, 7);
+
+    // Reads and calls from non-admins are not recorded
+    let req = test::TestRequest::get().uri("/api/admin/blocked-domains").insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::post().uri(&format!("/api/admin/urls/{}/reset-visits", short_code))
+        .insert_header((API_KEY_HEADER, "user-key")).set_json(serde_json::json!({})).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let entries = storage.list_audit_entries(10).await.unwrap();
+    let requests: Vec<_> = entries.iter().filter(|entry| entry.action == "admin.request").collect();
+    assert_eq!(requests.len(), 1);
+    let entry = requests[0];
+    assert_eq!(entry.actor, "ops");
+    assert_eq!(entry.target.as_deref(), Some(format!("/api/admin/urls/{}/adjust-visits", short_code).as_str()));
+    assert_eq!(entry.details["method"], "POST");
+    assert_eq!(entry.details["status"], 200);
+    assert_eq!(entry.details["query"], serde_json::json!({ "session_token": "[redacted]", "dry": "no" }));
+    assert_eq!(entry.details["request"]["reason"], "recount");
+    assert_eq!(entry.details["request"]["extra"]["api_key"], "[redacted]");
+    assert_eq!(entry.details["response"]["data"]["visits"], 7);
+
+    // Entries past their retention are purged
+    assert_eq!(service.purge_audit_log(chrono::Duration::days(1)).await.unwrap(), 0);
+    assert_eq!(service.purge_audit_log(chrono::Duration::zero()).await.unwrap(), entries.len() as u64);
+    assert!(storage.list_audit_entries(10).await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn test_scanning_clients_are_blocked_until_cleared() {
+    use crate::abuse::{AbuseConfig, AbuseGuard, AbusePenalty};
+    use crate::middleware::Tarpit;
+
+    // Setup
+    let (service, redirector) = create_test_service_and_redirector().await;
+    let guard = web::Data::new(AbuseGuard::new(AbuseConfig {
+        max_errors: 2,
+        penalty: AbusePenalty::Block,
+        ..AbuseConfig::default()
+    }));
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(redirector)
+            .app_data(guard)
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .wrap(Tarpit)
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let scanner: std::net::SocketAddr = "203.0.113.7:40000".parse().unwrap();
+    let admin: std::net::SocketAddr = "192.0.2.10:40000".parse().unwrap();
+
+    for code in ["aaaa", "aaab", "aaac"] {
+        let req = test::TestRequest::get().uri(&format!("/{}", code)).peer_addr(scanner).to_request();
+        assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+    }
+    let req = test::TestRequest::get().uri("/aaad").peer_addr(scanner).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert!(resp.headers().get("retry-after").unwrap().to_str().unwrap().parse::<u64>().unwrap() > 500);
+
+    let req = test::TestRequest::get().uri("/api/admin/penalized-clients").peer_addr(admin)
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let penalized: PenalizedClientsResponse = read_data(test::call_service(&app, req).await.map_into_boxed_body()).await;
+    assert_eq!((penalized.stats.requests_blocked, penalized.clients.len()), (1, 1));
+    assert_eq!(penalized.clients[0].ip, scanner.ip());
+
+    let req = test::TestRequest::delete().uri("/api/admin/penalized-clients/not-an-ip").peer_addr(admin)
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+    let req = test::TestRequest::delete().uri("/api/admin/penalized-clients/203.0.113.7").peer_addr(admin)
+        .insert_header((API_KEY_HEADER, "admin-key")).to_request();
+    let cleared: ClearPenaltiesResponse = read_data(test::call_service(&app, req).await.map_into_boxed_body()).await;
+    assert_eq!(cleared.cleared, 1);
+    let req = test::TestRequest::get().uri("/aaad").peer_addr(scanner).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn test_banning_a_domain_disables_its_links() {
+    // Setup
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let admin = (API_KEY_HEADER, "admin-key");
+    let create = |url: &str| service.create_short_url(url.to_string());
+    let phishing = create("https://login.Evil.example/verify").await.unwrap().short_code;
+    let apex = create("http://user@evil.example:8080/").await.unwrap().short_code;
+    let unrelated = create("https://notevil.example/").await.unwrap().short_code;
+    // Cached mappings are dropped by the ban
+    redirector.resolve(&phishing).await.unwrap();
+
+    let req = test::TestRequest::post().uri("/api/admin/blocked-domains").insert_header(admin)
+        .set_json(serde_json::json!({ "domain": "not a domain" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+
+    let req = test::TestRequest::post().uri("/api/admin/blocked-domains").insert_header(admin)
+        .set_json(serde_json::json!({ "domain": "EVIL.example.", "reason": "phishing campaign" })).to_request();
+    let ban: crate::models::DomainBan = read_data(test::call_service(&app, req).await).await;
+    let mut expected = vec![phishing.clone(), apex.clone()];
+    expected.sort();
+    assert_eq!((ban.blocked.domain.as_str(), ban.disabled), ("evil.example", expected));
+
+    // Disabled links are gone, the rest keep working
+    let req = test::TestRequest::get().uri(&format!("/{}", phishing)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 410);
+    let req = test::TestRequest::get().uri(&format!("/{}", unrelated)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 302);
+    let req = test::TestRequest::get().uri(&format!("/api/stats/{}", apex)).to_request();
+    let stats: UrlStats = read_data(test::call_service(&app, req).await).await;
+    assert!(stats.disabled_at.is_some());
+
+    // New links to the domain are refused
+    let req = test::TestRequest::post().uri("/api/shorten")
+        .set_json(serde_json::json!({ "original_url": "https://cdn.evil.example/x" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 403);
+
+    let req = test::TestRequest::get().uri("/api/admin/blocked-domains").insert_header(admin).to_request();
+    let domains: Vec<crate::models::BlockedDomain> = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(domains.len(), 1);
+    assert_eq!((domains[0].blocked_by.as_str(), domains[0].reason.as_deref()), ("ops", Some("phishing campaign")));
+    // The ban is recorded before the admin call that made it
+    let entries = storage.list_audit_entries(10).await.unwrap();
+    assert_eq!((entries[0].action.as_str(), entries[0].target.as_deref()), ("admin.request", Some("/api/admin/blocked-domains")));
+    assert_eq!((entries[1].action.as_str(), entries[1].target.as_deref()), ("domain.ban", Some("evil.example")));
+}
+
+#[actix_rt::test]
+async fn test_import_links_from_export() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(test_api_keys())
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let taken = service
+        .create_short_url_with("https://example.com/mine".to_string(), CreateOptions {
+            custom_alias: Some("taken".to_string()),
+            ..CreateOptions::default()
+        })
+        .await
+        .unwrap();
+    let export = "alias,url,created_at,hits\n\
+                  launch,https://example.com/launch,2021-06-01 12:00:00,4200\n\
+                  taken,https://example.com/theirs,,\n\
+                  broken,not a url,,\n";
+
+    let req = test::TestRequest::post().uri("/api/import?format=tinyurl")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .insert_header((header::CONTENT_TYPE, "text/csv"))
+        .set_payload(export)
+        .to_request();
+    let summary: crate::services::ImportSummary = read_data(test::call_service(&app, req).await).await;
+    assert_eq!((summary.imported, summary.skipped), (1, 1));
+    assert_eq!(summary.rejected.iter().map(|row| row.line).collect::<Vec<_>>(), [4]);
+
+    // Imported links keep their history; existing links are untouched
+    let launch = service.get_url_stats("launch").await.unwrap();
+    assert_eq!((launch.visits, launch.owner.as_deref()), (4200, Some("alice")));
+    assert_eq!(launch.created_at.to_rfc3339(), "2021-06-01T12:00:00+00:00");
+    assert_eq!(service.get_original_url(&taken.short_code).await.unwrap(), "https://example.com/mine");
+
+    // Exports must match the declared format
+    let req = test::TestRequest::post().uri("/api/import?format=bitly")
+        .insert_header((API_KEY_HEADER, "alice-key"))
+        .set_payload(export)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn test_version_reports_build_details() {
+    // Setup
+    let service = create_test_service().await;
+    let app = test::init_service(App::new().app_data(service).configure(crate::routes::configure_routes)).await;
+
+    let req = test::TestRequest::get().uri("/api/version").to_request();
+    let info: BuildInfo = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert!(!info.git_commit.is_empty());
+    assert!(info.built_at.is_some_and(|built_at| built_at <= Utc::now()));
+    assert_eq!(info.features.contains(&"telegram".to_string()), cfg!(feature = "telegram"));
+    assert_eq!(info.storage_backend, "memory");
+}
+
+#[actix_rt::test]
+async fn test_log_filter_changes_at_runtime() {
+    use crate::logging::LogFilter;
+    use tracing_subscriber::prelude::*;
+
+    // Setup
+    let (log_filter, layer) = LogFilter::new("info").unwrap();
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+    let app = test::init_service(
+        App::new()
+            .app_data(create_test_service().await)
+            .app_data(web::Data::new(log_filter))
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let admin = (API_KEY_HEADER, "admin-key");
+    assert!(!tracing::enabled!(target: "url_map::redirect", tracing::Level::DEBUG));
+
+    let req = test::TestRequest::put().uri("/api/admin/log-level").insert_header(admin)
+        .set_json(serde_json::json!({ "filter": "info,url_map::redirect=debug" })).to_request();
+    let changed: LogLevelResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(changed.filter, "info,url_map::redirect=debug");
+    assert!(tracing::enabled!(target: "url_map::redirect", tracing::Level::DEBUG));
+    assert!(!tracing::enabled!(target: "url_map::storage", tracing::Level::DEBUG));
+
+    // Invalid directives leave the filter as it is
+    let req = test::TestRequest::put().uri("/api/admin/log-level").insert_header(admin)
+        .set_json(serde_json::json!({ "filter": "url_map=loudest" })).to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 422);
+    let req = test::TestRequest::get().uri("/api/admin/log-level").insert_header(admin).to_request();
+    let current: LogLevelResponse = read_data(test::call_service(&app, req).await).await;
+    assert_eq!(current.filter, "info,url_map::redirect=debug");
+}
+
+#[actix_rt::test]
+async fn test_request_deadline_follows_budget_and_client_timeout() {
+    use crate::deadline::{current_deadline, DeadlineConfig, REQUEST_TIMEOUT_HEADER};
+    use crate::middleware::Deadline;
+    use std::time::Duration;
+
+    // Setup: the handler reports how much time its request has left
+    async fn remaining() -> HttpResponse {
+        let left = current_deadline().map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()));
+        HttpResponse::Ok().json(left.map(|left| left.as_millis() as u64))
+    }
+    let config = DeadlineConfig { read_api: Some(Duration::from_secs(2)), ..DeadlineConfig::default() };
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .wrap(Deadline)
+            .route("/api/remaining", web::get().to(remaining))
+            .route("/api/remaining", web::post().to(remaining))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/remaining").to_request();
+    let left: Option<u64> = test::call_and_read_body_json(&app, req).await;
+    assert!(left.is_some_and(|left| left > 1000 && left <= 2000));
+
+    // Clients may shorten the budget, but not extend it
+    let req = test::TestRequest::get().uri("/api/remaining").insert_header((REQUEST_TIMEOUT_HEADER, "300")).to_request();
+    let left: Option<u64> = test::call_and_read_body_json(&app, req).await;
+    assert!(left.is_some_and(|left| left <= 300));
+    let req = test::TestRequest::get().uri("/api/remaining").insert_header((REQUEST_TIMEOUT_HEADER, "60000")).to_request();
+    let left: Option<u64> = test::call_and_read_body_json(&app, req).await;
+    assert!(left.is_some_and(|left| left <= 2000));
+
+    // Classes without a budget only get the client's timeout
+    let req = test::TestRequest::post().uri("/api/remaining").to_request();
+    let left: Option<u64> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(left, None);
+    let req = test::TestRequest::post().uri("/api/remaining").insert_header((REQUEST_TIMEOUT_HEADER, "500")).to_request();
+    let left: Option<u64> = test::call_and_read_body_json(&app, req).await;
+    assert!(left.is_some_and(|left| left <= 500));
+}
+
+#[actix_rt::test]
+async fn test_admins_read_monthly_usage_as_json_and_csv() {
+    // Setup: one owner's link created and rolled up today
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let keys = HashMap::from([("admin-key".to_string(), "ops".to_string()), ("bob-key".to_string(), "bob".to_string())]);
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(web::Data::new(ApiKeys::new(keys).with_admins(&["ops"])))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let req = test::TestRequest::post()
+        .uri("/api/shorten")
+        .insert_header((API_KEY_HEADER, "bob-key"))
+        .set_json(serde_json::json!({ "original_url": "https://example.com" }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    crate::usage::UsageMeter::new(storage).roll_up(chrono::Utc::now()).await;
+    let month = chrono::Utc::now().format("%Y-%m").to_string();
+
+    let get = |uri: &str, key: &'static str| test::TestRequest::get().uri(uri).insert_header((API_KEY_HEADER, key)).to_request();
+    assert_eq!(test::call_service(&app, get("/api/admin/usage", "bob-key")).await.status().as_u16(), 403);
+    let bad = test::call_service(&app, get("/api/admin/usage?month=2026-13", "admin-key")).await;
+    assert_eq!(bad.status().as_u16(), 422);
+
+    let report: UsageResponse = read_data(test::call_service(&app, get("/api/admin/usage", "admin-key")).await).await;
+    assert_eq!(report.month, month);
+    let rows: Vec<_> = report.records.iter().map(|r| (r.owner.as_str(), r.creations, r.stored_links)).collect();
+    assert_eq!(rows, [("bob", 1, 1)]);
+
+    let resp = test::call_service(&app, get(&format!("/api/admin/usage.csv?month={}", month), "admin-key")).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let disposition = resp.headers().get("content-disposition").unwrap().to_str().unwrap().to_string();
+    assert_eq!(disposition, format!("attachment; filename=\"usage-{}.csv\"", month));
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.starts_with("day,owner,creations,redirects,stored_links\n"));
+    assert!(body.contains(",bob,1,0,1\n"));
+
+    // The file is named after the parsed month, without the whitespace around it
+    let resp = test::call_service(&app, get(&format!("/api/admin/usage.csv?month=%20{}%0A", month), "admin-key")).await;
+    let disposition = resp.headers().get("content-disposition").unwrap().to_str().unwrap();
+    assert_eq!(disposition, format!("attachment; filename=\"usage-{}.csv\"", month));
+}
+
+#[actix_rt::test]
+async fn test_admins_re_enable_links_the_runaway_guard_stopped() {
+    // Setup: a guard stopping any link past two redirects a minute
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage.clone()));
+    let guard = Arc::new(crate::runaway::RunawayGuard::new(
+        crate::runaway::RunawayConfig { multiple: 2.0, min_rate: 1 },
+        storage.clone(),
+    ));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()).with_runaway_guard(guard.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service.clone())
+            .app_data(redirector.clone())
+            .app_data(web::Data::new(
+                ApiKeys::new(HashMap::from([("admin-key".to_string(), "ops".to_string())])).with_admins(&["ops"]),
+            ))
+            .configure(crate::routes::configure_routes)
+    ).await;
+    let admin = (API_KEY_HEADER, "admin-key");
+    let short_code = service.create_short_url("https://example.com".to_string()).await.unwrap().short_code;
+    for _ in 0..2 {
+        redirector.resolve(&short_code).await.unwrap();
+    }
+    assert!(redirector.resolve(&short_code).await.is_err());
+    guard.disable(&short_code, 3, 0.0).await.unwrap();
+
+    let enable = |code: &str| test::TestRequest::post().uri(&format!("/api/admin/urls/{}/enable", code)).insert_header(admin)
+        .set_json(serde_json::json!({ "reason": "product launch" })).to_request();
+    let enabled: EnableLinkResponse = read_data(test::call_service(&app, enable(&short_code)).await).await;
+    assert!(enabled.was_disabled);
+    assert!(storage.get_stats(&short_code).await.unwrap().disabled_at.is_none());
+    redirector.resolve(&short_code).await.unwrap();
+
+    let again: EnableLinkResponse = read_data(test::call_service(&app, enable(&short_code)).await).await;
+    assert!(!again.was_disabled);
+    assert_eq!(test::call_service(&app, enable("missing")).await.status().as_u16(), 404);
+
+    let mut entries = storage.list_audit_entries(20).await.unwrap();
+    entries.retain(|entry| entry.action != "admin.request");
+    let actions: Vec<_> = entries.iter().map(|entry| (entry.actor.as_str(), entry.action.as_str())).collect();
+    assert_eq!(actions, [("ops", "link.enable"), (crate::runaway::RUNAWAY_ACTOR, "link.auto_disable")]);
+    assert_eq!(entries[0].details["reason"], "product launch");
+}
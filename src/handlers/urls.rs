@@ -0,0 +1,81 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use url::Host;
+
+use crate::auth::ApiKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::{LinkStatus, UrlCursor, UrlFilter, UrlSort};
+use crate::services::UrlService;
+use crate::validation::{Validate, ValidationErrors};
+use super::UrlStats;
+
+/// Default and maximum number of links returned per page
+const DEFAULT_LIST_LIMIT: i64 = 20;
+const MAX_LIST_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListUrlsQuery {
+    /// `created_at` (newest first, the default), `visits` or `last_visited`
+    pub sort: Option<UrlSort>,
+    pub created_after: Option<DateTime<Utc>>,
+    /// Host of the destination, e.g. `example.com`; subdomains are not included
+    pub domain: Option<String>,
+    pub min_visits: Option<i64>,
+    /// `active` or `disabled`
+    pub status: Option<LinkStatus>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page, listed in the same order
+    pub cursor: Option<String>,
+}
+
+impl ListUrlsQuery {
+    /// The requested host, lowercase and in ASCII form as destinations are stored
+    fn domain(&self) -> Option<Result<String, url::ParseError>> {
+        let domain = self.domain.as_deref()?.trim().trim_end_matches('.');
+        Some(Host::parse(domain).map(|host| host.to_string()))
+    }
+
+    fn cursor(&self) -> Option<UrlCursor> {
+        self.cursor.as_deref().and_then(UrlCursor::decode)
+    }
+}
+
+impl Validate for ListUrlsQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(Err(_)) = self.domain() {
+            errors.add("domain", "invalid_domain", "domain must be a host name like example.com");
+        }
+        if self.min_visits.is_some_and(|min| min < 0) {
+            errors.add("min_visits", "out_of_range", "min_visits must not be negative");
+        }
+        if self.cursor.is_some() && self.cursor().is_none_or(|cursor| cursor.sort != self.sort.unwrap_or_default()) {
+            errors.add("cursor", "invalid_cursor", "cursor must be the next_cursor of a previous page in the same sort");
+        }
+        errors.into_result()
+    }
+}
+
+/// Lists the caller's links, a page at a time, sorted and filtered as asked
+pub async fn list_urls(
+    query: web::Query<ListUrlsQuery>,
+    api_key: ApiKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let filter = UrlFilter {
+        created_after: query.created_after,
+        domain: query.domain().and_then(Result::ok),
+        min_visits: query.min_visits,
+        status: query.status,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+    let page = service
+        .list_urls(&api_key.owner, query.sort.unwrap_or_default(), &filter, query.cursor(), limit)
+        .await?
+        .map(UrlStats::from);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::page(page.items, page.next_cursor.as_ref().map(UrlCursor::encode))))
+}
@@ -0,0 +1,71 @@
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AdminKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::UsageRecord;
+use crate::services::UrlService;
+use crate::usage::{month_range, to_csv};
+use crate::validation::{Validate, ValidationErrors};
+
+/// A month of usage, as `YYYY-MM`; the current UTC month when left out
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageQuery {
+    pub month: Option<String>,
+}
+
+impl UsageQuery {
+    fn month(&self) -> String {
+        self.month.clone().unwrap_or_else(|| Utc::now().format("%Y-%m").to_string())
+    }
+
+    fn range(&self) -> UrlShortenerResult<(NaiveDate, NaiveDate)> {
+        self.validate()?;
+        Ok(month_range(&self.month()).expect("validated month"))
+    }
+}
+
+impl Validate for UsageQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if month_range(&self.month()).is_none() {
+            errors.add("month", "invalid", "month must be YYYY-MM");
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageResponse {
+    pub month: String,
+    /// One record per owner and day with usage, by owner and then day
+    pub records: Vec<UsageRecord>,
+}
+
+/// Daily usage of every owner for a month
+pub async fn usage(
+    query: web::Query<UsageQuery>,
+    _admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (from, to) = query.range()?;
+    let records = service.usage(from, to).await?;
+    let month = from.format("%Y-%m").to_string();
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UsageResponse { month, records })))
+}
+
+/// Daily usage of every owner for a month as CSV, for importing into a billing system
+pub async fn usage_csv(
+    query: web::Query<UsageQuery>,
+    _admin: AdminKey,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (from, to) = query.range()?;
+    let csv = to_csv(&service.usage(from, to).await?)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        // Named after the parsed month; the query value may carry whitespace around it
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"usage-{}.csv\"", from.format("%Y-%m"))))
+        .body(csv))
+}
@@ -0,0 +1,37 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiResponse;
+use crate::services::UrlService;
+
+/// What is running, for telling instances apart in a fleet running several versions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit the binary was built from, or `unknown` when it was built outside a checkout
+    pub git_commit: String,
+    pub built_at: Option<DateTime<Utc>>,
+    /// Cargo features compiled in
+    pub features: Vec<String>,
+    /// Storage backend in use, with the backends it wraps
+    pub storage_backend: String,
+}
+
+impl BuildInfo {
+    /// Details embedded by the build script, with the given storage backend
+    pub fn new(storage_backend: String) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("BUILD_GIT_COMMIT").to_string(),
+            built_at: env!("BUILD_TIMESTAMP").parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            features: env!("BUILD_FEATURES").split(',').filter(|f| !f.is_empty()).map(str::to_string).collect(),
+            storage_backend,
+        }
+    }
+}
+
+/// Reports the version, commit, build time, features and storage backend of this instance
+pub async fn version(service: web::Data<UrlService>) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse::success(BuildInfo::new(service.storage_backend())))
+}
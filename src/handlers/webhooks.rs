@@ -0,0 +1,65 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AdminKey;
+use crate::errors::{ApiResponse, UrlShortenerResult};
+use crate::models::{Page, PageCursor};
+use crate::validation::{check_cursor, Validate, ValidationErrors};
+use crate::webhooks::Webhooks;
+
+/// Default and maximum number of deliveries returned per page
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+const MAX_DELIVERY_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    /// Only list deliveries older than the one with this ID, for paging
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page; takes precedence over `before`
+    pub cursor: Option<String>,
+}
+
+impl Validate for WebhookDeliveriesQuery {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_cursor(&mut errors, self.cursor.as_deref());
+        errors.into_result()
+    }
+}
+
+/// Lists a webhook's delivery attempts, newest first, for debugging missed events
+pub async fn webhook_deliveries(
+    webhook: web::Path<String>,
+    query: web::Query<WebhookDeliveriesQuery>,
+    _admin: AdminKey,
+    webhooks: web::Data<Webhooks>,
+) -> UrlShortenerResult<HttpResponse> {
+    query.validate()?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+        .clamp(1, MAX_DELIVERY_LIMIT);
+    let before = match query.cursor.as_deref().and_then(PageCursor::decode) {
+        Some(cursor) => Some(cursor.id),
+        None => query.before,
+    };
+    // One more than asked for tells whether there is a next page
+    let deliveries = webhooks.get(&webhook)?.deliveries(before, limit + 1).await?;
+    let page = Page::from_fetched(deliveries, limit, |delivery| PageCursor::after(delivery.attempted_at, delivery.id));
+
+    Ok(HttpResponse::Ok().json(ApiResponse::page(page.items, page.next_cursor.as_ref().map(PageCursor::encode))))
+}
+
+/// Sends a recorded delivery's event again; the response is the new attempt, delivered or not
+pub async fn replay_webhook_delivery(
+    path: web::Path<(String, i64)>,
+    admin: AdminKey,
+    webhooks: web::Data<Webhooks>,
+) -> UrlShortenerResult<HttpResponse> {
+    let (webhook, id) = path.into_inner();
+    let delivery = webhooks.get(&webhook)?.replay(id, &admin.owner).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(delivery)))
+}
@@ -0,0 +1,140 @@
+//! Readiness checks for load balancers.
+//!
+//! Each dependency is checked concurrently under its own timeout, and the combined result is
+//! kept for a short while, so frequent probes from several load balancers do not turn into a
+//! steady load on the database.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::redirect::Redirector;
+use crate::storage::StorageRef;
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// How long each dependency may take to answer before it counts as down
+    pub check_timeout: Duration,
+    /// How long a result answers probes before the dependencies are checked again; zero
+    /// checks on every probe
+    pub cache_ttl: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            check_timeout: Duration::from_secs(1),
+            cache_ttl: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Outcome of one dependency's check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Error,
+    Timeout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub latency_ms: u64,
+}
+
+/// Combined result of the dependency checks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Readiness {
+    /// `ready` when every check passed, `unavailable` otherwise
+    pub status: String,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.checks.values().all(|check| check.status == CheckStatus::Ok)
+    }
+}
+
+/// Checks the database and the redirect cache, answering from the last result while it is fresh
+pub struct HealthChecks {
+    storage: StorageRef,
+    redirector: web::Data<Redirector>,
+    config: HealthConfig,
+    /// Last result and when it was taken; held while checking, so concurrent probes wait for
+    /// one round of checks instead of starting their own
+    last: Mutex<Option<(Instant, Readiness)>>,
+}
+
+impl HealthChecks {
+    pub fn new(storage: StorageRef, redirector: web::Data<Redirector>, config: HealthConfig) -> Self {
+        Self { storage, redirector, config, last: Mutex::new(None) }
+    }
+
+    /// The current readiness, checked again once the cached result is older than the TTL
+    pub async fn readiness(&self) -> Readiness {
+        let mut last = self.last.lock().await;
+        if let Some((checked_at, readiness)) = last.as_ref() {
+            if checked_at.elapsed() < self.config.cache_ttl {
+                return readiness.clone();
+            }
+        }
+
+        let readiness = self.check().await;
+        *last = Some((Instant::now(), readiness.clone()));
+        readiness
+    }
+
+    async fn check(&self) -> Readiness {
+        let database = async {
+            let result = self.storage.ping().await;
+            if let Err(e) = &result {
+                warn!(error = %e, "Database ping failed");
+            }
+            result.is_ok()
+        };
+        let cache = async { self.redirector.cache().is_healthy() };
+        let (database, cache) = futures::join!(self.timed("database", database), self.timed("cache", cache));
+
+        let checks = BTreeMap::from([("database".to_string(), database), ("cache".to_string(), cache)]);
+        let ready = checks.values().all(|check| check.status == CheckStatus::Ok);
+        Readiness {
+            status: if ready { "ready" } else { "unavailable" }.to_string(),
+            checks,
+        }
+    }
+
+    async fn timed(&self, name: &str, check: impl Future<Output = bool>) -> CheckResult {
+        let started = Instant::now();
+        let status = match tokio::time::timeout(self.config.check_timeout, check).await {
+            Ok(true) => CheckStatus::Ok,
+            Ok(false) => CheckStatus::Error,
+            Err(_) => CheckStatus::Timeout,
+        };
+        let latency_ms = started.elapsed().as_millis() as u64;
+        if status != CheckStatus::Ok {
+            warn!(check = name, ?status, latency_ms, "Readiness check failed");
+        }
+        CheckResult { status, latency_ms }
+    }
+}
+
+/// Answers 200 while every dependency is up and 503 otherwise, with each check's outcome
+pub async fn ready(health: web::Data<HealthChecks>) -> HttpResponse {
+    let readiness = health.readiness().await;
+    if readiness.is_ready() {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::App;
+
+use super::*;
+use crate::redirect::RedirectConfig;
+use crate::storage::{MemoryStorage, StorageConfig};
+
+fn health_checks(config: HealthConfig) -> HealthChecks {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let redirector = web::Data::new(Redirector::new(storage.clone(), RedirectConfig::default()));
+    HealthChecks::new(storage, redirector, config)
+}
+
+#[actix_rt::test]
+async fn test_ready_when_every_dependency_answers() {
+    let health = web::Data::new(health_checks(HealthConfig::default()));
+    let app = init_service(App::new().app_data(health).route("/health/ready", web::get().to(ready))).await;
+
+    let resp = call_service(&app, TestRequest::get().uri("/health/ready").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let readiness: Readiness = read_body_json(resp).await;
+    assert_eq!(readiness.status, "ready");
+    assert_eq!(readiness.checks.keys().collect::<Vec<_>>(), ["cache", "database"]);
+}
+
+#[actix_rt::test]
+async fn test_slow_checks_time_out() {
+    let health = health_checks(HealthConfig {
+        check_timeout: Duration::from_millis(50),
+        ..HealthConfig::default()
+    });
+
+    let started = Instant::now();
+    let (slow, fast) = futures::join!(
+        health.timed("slow", std::future::pending()),
+        health.timed("fast", async { true }),
+    );
+    assert_eq!(slow.status, CheckStatus::Timeout);
+    assert_eq!(fast.status, CheckStatus::Ok);
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(health.timed("failing", async { false }).await.status, CheckStatus::Error);
+}
+
+#[actix_rt::test]
+async fn test_results_are_reused_until_they_expire() {
+    let health = health_checks(HealthConfig {
+        cache_ttl: Duration::from_secs(60),
+        ..HealthConfig::default()
+    });
+    health.readiness().await;
+    let first_check = health.last.lock().await.as_ref().unwrap().0;
+    health.readiness().await;
+    assert_eq!(health.last.lock().await.as_ref().unwrap().0, first_check);
+
+    let health = health_checks(HealthConfig {
+        cache_ttl: Duration::ZERO,
+        ..HealthConfig::default()
+    });
+    health.readiness().await;
+    let first_check = health.last.lock().await.as_ref().unwrap().0;
+    health.readiness().await;
+    assert!(health.last.lock().await.as_ref().unwrap().0 > first_check);
+}
@@ -0,0 +1,207 @@
+//! Shared client for outbound HTTP calls.
+//!
+//! Content checks, link previews, favicons, webhooks and chat integrations all send their requests through
+//! an [`HttpClient`], so they use the same proxy settings, timeout and connection limit and are
+//! counted in one place.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::redirect::Policy;
+use reqwest::{ClientBuilder, IntoUrl, Method, NoProxy, Proxy, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+
+#[derive(Debug, Clone)]
+pub struct OutboundConfig {
+    /// Proxy for `http://` destinations
+    pub http_proxy: Option<String>,
+    /// Proxy for `https://` destinations
+    pub https_proxy: Option<String>,
+    /// Hosts, domains and networks reached without a proxy, comma-separated as in `NO_PROXY`
+    pub no_proxy: Option<String>,
+    /// Longest an outbound request may take; callers may only shorten it
+    pub timeout: Duration,
+    /// Outbound requests in flight at once; further ones wait for a slot
+    pub max_connections: usize,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            timeout: Duration::from_secs(10),
+            max_connections: 64,
+        }
+    }
+}
+
+/// Counters of one kind of outbound call, e.g. `webhook`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurposeStats {
+    pub requests: u64,
+    /// Requests that got no response, timeouts included
+    pub failed: u64,
+    pub timed_out: u64,
+}
+
+/// Outbound HTTP usage since startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundStats {
+    pub max_connections: usize,
+    pub in_flight: usize,
+    /// Requests waiting for a free slot
+    pub waiting: usize,
+    pub timeout_secs: u64,
+    pub by_purpose: BTreeMap<String, PurposeStats>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    config: OutboundConfig,
+    slots: Semaphore,
+    waiting: AtomicUsize,
+    stats: Mutex<BTreeMap<&'static str, PurposeStats>>,
+}
+
+/// Sends outbound requests with the deployment's proxy, timeout and connection limit.
+/// Clones share the limit and the counters.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    shared: Arc<Shared>,
+}
+
+impl HttpClient {
+    /// Fails when a proxy URL is invalid
+    pub fn new(config: OutboundConfig) -> UrlShortenerResult<Self> {
+        let client = build_client(&config, Policy::default())?;
+        Ok(Self {
+            client,
+            shared: Arc::new(Shared {
+                slots: Semaphore::new(config.max_connections.max(1)),
+                waiting: AtomicUsize::new(0),
+                stats: Mutex::new(BTreeMap::new()),
+                config,
+            }),
+        })
+    }
+
+    /// A client sharing this one's settings, limit and counters that follows redirects by `policy`
+    pub fn with_redirect_policy(&self, policy: Policy) -> Self {
+        Self {
+            client: build_client(&self.shared.config, policy).unwrap_or_else(|_| self.client.clone()),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// A client sharing this one's settings, limit and counters that connects to `domain` only at
+    /// `addrs` and follows no redirects, for callers that checked those addresses themselves.
+    /// Requests through a proxy are still resolved by the proxy.
+    pub fn pinned(&self, domain: &str, addrs: &[SocketAddr]) -> UrlShortenerResult<Self> {
+        let builder = client_builder(&self.shared.config, Policy::none())?.resolve_to_addrs(domain, addrs);
+        Ok(Self {
+            client: builder.build().map_err(invalid_config)?,
+            shared: self.shared.clone(),
+        })
+    }
+
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn head<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.head(url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// A caller's own timeout, capped at the global one
+    pub fn timeout_within(&self, timeout: Duration) -> Duration {
+        timeout.min(self.shared.config.timeout)
+    }
+
+    /// Sends a request built from this client once a slot is free, counting it under `purpose`.
+    /// The slot is held until the response headers arrive.
+    pub async fn send(&self, purpose: &'static str, request: RequestBuilder) -> reqwest::Result<Response> {
+        self.shared.waiting.fetch_add(1, Ordering::Relaxed);
+        let slot = self.shared.slots.acquire().await;
+        self.shared.waiting.fetch_sub(1, Ordering::Relaxed);
+
+        let result = request.send().await;
+        drop(slot);
+
+        let mut stats = self.shared.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let stats = stats.entry(purpose).or_default();
+        stats.requests += 1;
+        if let Err(e) = &result {
+            stats.failed += 1;
+            stats.timed_out += u64::from(e.is_timeout());
+        }
+        result
+    }
+
+    pub fn stats(&self) -> OutboundStats {
+        let config = &self.shared.config;
+        let max_connections = config.max_connections.max(1);
+        let stats = self.shared.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        OutboundStats {
+            max_connections,
+            in_flight: max_connections - self.shared.slots.available_permits(),
+            waiting: self.shared.waiting.load(Ordering::Relaxed),
+            timeout_secs: config.timeout.as_secs(),
+            by_purpose: stats.iter().map(|(purpose, stats)| (purpose.to_string(), stats.clone())).collect(),
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(OutboundConfig::default()).expect("The default outbound config has no proxy to get wrong")
+    }
+}
+
+fn build_client(config: &OutboundConfig, policy: Policy) -> UrlShortenerResult<reqwest::Client> {
+    client_builder(config, policy)?.build().map_err(invalid_config)
+}
+
+fn invalid_config(e: reqwest::Error) -> UrlShortenerError {
+    UrlShortenerError::with_source(
+        UrlShortenerErrorType::InternalError("Invalid outbound proxy configuration".to_string()),
+        e,
+    )
+}
+
+/// Proxies come from the config only; reqwest's own lookup of the proxy variables is turned off
+fn client_builder(config: &OutboundConfig, policy: Policy) -> UrlShortenerResult<ClientBuilder> {
+    let no_proxy = || config.no_proxy.as_deref().and_then(NoProxy::from_string);
+
+    let mut builder = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(config.timeout)
+        .user_agent(concat!("url-map/", env!("CARGO_PKG_VERSION")))
+        .redirect(policy);
+    if let Some(proxy) = &config.http_proxy {
+        builder = builder.proxy(Proxy::http(proxy).map_err(invalid_config)?.no_proxy(no_proxy()));
+    }
+    if let Some(proxy) = &config.https_proxy {
+        builder = builder.proxy(Proxy::https(proxy).map_err(invalid_config)?.no_proxy(no_proxy()));
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+use super::*;
+
+/// Starts a server answering every request with the URI it was asked for, after `delay`
+fn echo_server(delay: Duration) -> String {
+    let server = HttpServer::new(move || {
+        App::new().default_service(web::to(move |req: HttpRequest| async move {
+            tokio::time::sleep(delay).await;
+            HttpResponse::Ok().body(req.uri().to_string())
+        }))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let address = server.addrs()[0].to_string();
+    actix_rt::spawn(server.run());
+    address
+}
+
+#[actix_rt::test]
+async fn test_requests_go_through_the_proxy_unless_exempted() {
+    let proxy = echo_server(Duration::ZERO);
+    let config = OutboundConfig {
+        http_proxy: Some(format!("http://{}", proxy)),
+        no_proxy: Some("direct.invalid".to_string()),
+        ..OutboundConfig::default()
+    };
+    let http = HttpClient::new(config).unwrap();
+
+    let resp = http.send("test", http.get("http://destination.invalid/hello")).await.unwrap();
+    assert_eq!(resp.text().await.unwrap(), "http://destination.invalid/hello");
+    assert!(http.send("test", http.get("http://direct.invalid/hello")).await.is_err());
+
+    let stats = http.stats().by_purpose.remove("test").unwrap();
+    assert_eq!(stats, PurposeStats { requests: 2, failed: 1, timed_out: 0 });
+}
+
+#[actix_rt::test]
+async fn test_slow_responses_time_out_and_are_counted() {
+    let server = echo_server(Duration::from_millis(500));
+    let config = OutboundConfig { timeout: Duration::from_millis(100), max_connections: 2, ..OutboundConfig::default() };
+    let http = HttpClient::new(config).unwrap();
+    assert_eq!(http.timeout_within(Duration::from_secs(5)), Duration::from_millis(100));
+
+    let err = http.send("slow", http.get(format!("http://{}/", server))).await.unwrap_err();
+    assert!(err.is_timeout());
+
+    let stats = http.stats();
+    assert_eq!((stats.max_connections, stats.in_flight, stats.waiting), (2, 0, 0));
+    assert_eq!(stats.by_purpose["slow"], PurposeStats { requests: 1, failed: 1, timed_out: 1 });
+}
+
+#[test]
+fn test_invalid_proxies_are_rejected() {
+    let config = OutboundConfig { https_proxy: Some("http://[::1".to_string()), ..OutboundConfig::default() };
+    assert!(HttpClient::new(config).is_err());
+}
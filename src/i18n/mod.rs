@@ -0,0 +1,97 @@
+//! Localized user-facing error messages.
+//!
+//! Catalogs are TOML files embedded at build time, mapping error codes to message templates.
+//! The language is negotiated per request from `Accept-Language` (see [`crate::middleware::Localize`])
+//! and falls back to English; log messages are never localized.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+
+/// Language used when a request does not ask for a supported one
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Embedded catalogs as `(language, TOML source)`
+const CATALOG_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.toml")),
+    ("es", include_str!("locales/es.toml")),
+    ("fr", include_str!("locales/fr.toml")),
+];
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static CATALOGS: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        CATALOG_SOURCES
+            .iter()
+            .map(|(language, source)| {
+                let catalog = toml::from_str(source)
+                    .unwrap_or_else(|e| panic!("Invalid {} message catalog: {}", language, e));
+                (*language, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Languages with a message catalog
+pub fn supported_languages() -> impl Iterator<Item = &'static str> {
+    CATALOG_SOURCES.iter().map(|(language, _)| *language)
+}
+
+/// Picks the best supported language from an `Accept-Language` header value.
+///
+/// Ranges are ordered by quality; regional variants match their primary language
+/// (`es-MX` selects `es`). Falls back to [`DEFAULT_LANGUAGE`].
+pub fn negotiate(accept_language: &str) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable sort keeps header order among equal qualities
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| {
+            let primary = tag.split('-').next().unwrap_or(tag);
+            supported_languages().find(|language| language.eq_ignore_ascii_case(primary))
+        })
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// Looks up the message for `code` in `language`, falling back to English.
+/// `{detail}` in the template is replaced by `detail`.
+pub fn translate(language: &str, code: &str, detail: Option<&str>) -> Option<String> {
+    let catalogs = catalogs();
+    let template = catalogs
+        .get(language)
+        .and_then(|catalog| catalog.get(code))
+        .or_else(|| catalogs.get(DEFAULT_LANGUAGE)?.get(code))?;
+
+    Some(template.replace("{detail}", detail.unwrap_or_default()))
+}
+
+tokio::task_local! {
+    static LANGUAGE: &'static str;
+}
+
+/// Runs a request future with its negotiated language available to error responses
+pub async fn with_language<F: Future>(language: &'static str, future: F) -> F::Output {
+    LANGUAGE.scope(language, future).await
+}
+
+/// Language of the request currently being handled, or English outside a request
+pub fn current_language() -> &'static str {
+    LANGUAGE.try_with(|language| *language).unwrap_or(DEFAULT_LANGUAGE)
+}
+
+#[cfg(test)]
+mod tests;
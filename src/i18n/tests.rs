@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn test_catalogs_cover_the_same_codes() {
+    let english: Vec<_> = {
+        let mut codes: Vec<_> = catalogs()[DEFAULT_LANGUAGE].keys().collect();
+        codes.sort();
+        codes
+    };
+
+    for language in supported_languages() {
+        let mut codes: Vec<_> = catalogs()[language].keys().collect();
+        codes.sort();
+        assert_eq!(codes, english, "{} catalog is out of sync with English", language);
+    }
+}
+
+#[test]
+fn test_negotiate() {
+    assert_eq!(negotiate("fr"), "fr");
+    assert_eq!(negotiate("es-MX,es;q=0.9,en;q=0.8"), "es");
+    assert_eq!(negotiate("de-DE, fr;q=0.5, en;q=0.7"), "en");
+    assert_eq!(negotiate("fr;q=0, es;q=0.1"), "es");
+    assert_eq!(negotiate("de, ja"), DEFAULT_LANGUAGE);
+    assert_eq!(negotiate(""), DEFAULT_LANGUAGE);
+    assert_eq!(negotiate("*"), DEFAULT_LANGUAGE);
+}
+
+#[test]
+fn test_translate() {
+    assert_eq!(translate("fr", "not_found", None).unwrap(), "URL courte introuvable");
+    assert_eq!(
+        translate("es", "invalid_url", Some("relative URL without a base")).unwrap(),
+        "La URL no es válida: relative URL without a base"
+    );
+    // Unknown languages fall back to English; unknown codes have no message
+    assert_eq!(translate("de", "not_found", None).unwrap(), "Short URL not found");
+    assert!(translate("en", "no_such_code", None).is_none());
+}
+
+#[tokio::test]
+async fn test_current_language_is_scoped_to_request() {
+    assert_eq!(current_language(), DEFAULT_LANGUAGE);
+    with_language("fr", async { assert_eq!(current_language(), "fr") }).await;
+}
@@ -0,0 +1,116 @@
+//! `Idempotency-Key` support for link creation.
+//!
+//! A client that sends an `Idempotency-Key` with a creation request can retry it after a
+//! network failure without minting a second link: the first response is kept, and repeats
+//! of the same request with the same key get it back instead of running again. Keys are
+//! scoped to the API key the request was made with, or the address of anonymous callers, and
+//! expire after the retention window.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::errors::UrlShortenerResult;
+use crate::models::IdempotentResponse;
+use crate::storage::StorageRef;
+
+/// Request header with the client's key for a request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Response header set on replayed responses
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// Longest key accepted
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// What to do with a request carrying an `Idempotency-Key`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reservation {
+    /// The key is new; handle the request and keep its response
+    Reserved,
+    /// The request was already handled; answer with its response
+    Replay(IdempotentResponse),
+    /// The first request with the key is still being handled
+    InProgress,
+    /// The key was used with a different request
+    Mismatch,
+}
+
+/// Keeps responses to requests made with an `Idempotency-Key` for the retention window
+pub struct Idempotency {
+    storage: StorageRef,
+    retention: chrono::Duration,
+}
+
+impl Idempotency {
+    pub fn new(storage: StorageRef, retention: chrono::Duration) -> Self {
+        Self { storage, retention }
+    }
+
+    /// Reserves `key` for a request, or tells how an earlier request with it went
+    pub async fn reserve(&self, scope: &str, key: &str, fingerprint: &str) -> UrlShortenerResult<Reservation> {
+        let expired_before = Utc::now() - self.retention;
+        let Some(record) = self.storage.reserve_idempotency_key(scope, key, fingerprint, expired_before).await? else {
+            return Ok(Reservation::Reserved);
+        };
+        Ok(match record.response {
+            _ if record.fingerprint != fingerprint => Reservation::Mismatch,
+            Some(response) => Reservation::Replay(response),
+            None => Reservation::InProgress,
+        })
+    }
+
+    /// Keeps the response to a reserved key's request
+    pub async fn complete(&self, scope: &str, key: &str, response: IdempotentResponse) -> UrlShortenerResult<()> {
+        self.storage.complete_idempotency_key(scope, key, response).await
+    }
+
+    /// Frees a reserved key whose request failed, so retrying it runs the request again
+    pub async fn release(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        self.storage.release_idempotency_key(scope, key).await
+    }
+
+    /// Deletes keys older than the retention window
+    pub async fn purge_expired(&self) -> UrlShortenerResult<u64> {
+        let purged = self.storage.purge_idempotency_keys(Utc::now() - self.retention).await?;
+        if purged > 0 {
+            info!(purged, "Purged expired idempotency keys");
+        }
+        Ok(purged)
+    }
+
+    /// Purges expired keys every hour for the lifetime of the process
+    pub fn spawn_purge(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(60 * 60));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.purge_expired().await {
+                    warn!(error = %e, "Failed to purge expired idempotency keys");
+                }
+            }
+        });
+    }
+}
+
+/// Whether a client's key is usable: non-empty, not too long and printable ASCII
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Identifies a request by its method, path, query and body, so a key reused for a
+/// different request is caught
+pub fn fingerprint(method: &str, path: &str, query: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [method.as_bytes(), path.as_bytes(), query.as_bytes(), body] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,144 @@
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{web, App};
+use serde_json::json;
+
+use super::*;
+use crate::handlers::create_url;
+use crate::middleware::Idempotent;
+use crate::services::UrlService;
+use crate::storage::{MemoryStorage, StorageConfig};
+
+fn idempotency(retention: chrono::Duration) -> (StorageRef, Idempotency) {
+    let storage: StorageRef = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    (storage.clone(), Idempotency::new(storage, retention))
+}
+
+#[actix_rt::test]
+async fn test_repeated_requests_replay_the_first_response() {
+    let (storage, idempotency) = idempotency(chrono::Duration::hours(24));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage.clone())))
+            .app_data(web::Data::new(idempotency))
+            .service(web::resource("/api/shorten").wrap(Idempotent).route(web::post().to(create_url))),
+    )
+    .await;
+    let shorten = |key: &str, url: &str| {
+        TestRequest::post()
+            .uri("/api/shorten")
+            .peer_addr("203.0.113.7:4000".parse().unwrap())
+            .insert_header((IDEMPOTENCY_KEY_HEADER, key))
+            .set_json(json!({ "original_url": url }))
+            .to_request()
+    };
+
+    let first = call_service(&app, shorten("retry-1", "https://example.com/a")).await;
+    assert_eq!(first.status(), 200);
+    assert!(first.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+    let first = read_body(first).await;
+
+    let repeat = call_service(&app, shorten("retry-1", "https://example.com/a")).await;
+    assert_eq!(repeat.status(), 200);
+    assert_eq!(repeat.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(), "true");
+    assert_eq!(repeat.headers().get("content-type").unwrap(), "application/json");
+    assert_eq!(read_body(repeat).await, first);
+    assert_eq!(storage.export_urls(None, 10).await.unwrap().len(), 1);
+
+    // The same key with another body is a client bug, not a retry
+    let reused = call_service(&app, shorten("retry-1", "https://example.com/b")).await;
+    assert_eq!(reused.status(), 422);
+
+    let invalid = call_service(&app, shorten("not a key", "https://example.com/b")).await;
+    assert_eq!(invalid.status(), 422);
+
+    let other = call_service(&app, shorten("retry-2", "https://example.com/a")).await;
+    assert_eq!(other.status(), 200);
+    assert_ne!(read_body(other).await, first);
+    assert_eq!(storage.export_urls(None, 10).await.unwrap().len(), 2);
+}
+
+#[actix_rt::test]
+async fn test_keys_are_scoped_to_the_caller() {
+    let (storage, idempotency) = idempotency(chrono::Duration::hours(24));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(UrlService::new(storage.clone())))
+            .app_data(web::Data::new(idempotency))
+            .service(web::resource("/api/shorten").wrap(Idempotent).route(web::post().to(create_url))),
+    )
+    .await;
+    let shorten = |peer: Option<&str>, auth: Option<(&str, &str)>| {
+        let mut req = TestRequest::post()
+            .uri("/api/shorten")
+            .insert_header((IDEMPOTENCY_KEY_HEADER, "retry-1"))
+            .set_json(json!({ "original_url": "https://example.com/a" }));
+        if let Some(peer) = peer {
+            req = req.peer_addr(peer.parse().unwrap());
+        }
+        if let Some(auth) = auth {
+            req = req.insert_header(auth);
+        }
+        req.to_request()
+    };
+    let replayed = |res: &actix_web::dev::ServiceResponse| res.headers().contains_key(IDEMPOTENT_REPLAYED_HEADER);
+
+    // Anonymous callers only share keys with themselves
+    assert!(!replayed(&call_service(&app, shorten(Some("203.0.113.7:4000"), None)).await));
+    assert!(!replayed(&call_service(&app, shorten(Some("198.51.100.9:4000"), None)).await));
+    assert!(replayed(&call_service(&app, shorten(Some("203.0.113.7:5000"), None)).await));
+
+    // An API key is the same client whichever header carries it, and never an anonymous one
+    let bearer = ("Authorization", "Bearer alice-key");
+    assert!(!replayed(&call_service(&app, shorten(Some("203.0.113.7:4000"), Some(bearer))).await));
+    assert!(replayed(&call_service(&app, shorten(None, Some(("X-API-Key", "alice-key")))).await));
+    assert!(!replayed(&call_service(&app, shorten(Some("203.0.113.7:4000"), Some(("Authorization", "Bearer bob-key")))).await));
+
+    // Without an address, anonymous requests are not kept at all
+    assert!(!replayed(&call_service(&app, shorten(None, None)).await));
+    assert!(!replayed(&call_service(&app, shorten(None, None)).await));
+    assert_eq!(storage.export_urls(None, 10).await.unwrap().len(), 6);
+}
+
+#[actix_rt::test]
+async fn test_reservations() {
+    let (_, idempotency) = idempotency(chrono::Duration::hours(24));
+    let fingerprint = fingerprint("POST", "/api/shorten", "", b"{}");
+
+    assert_eq!(idempotency.reserve("", "k", &fingerprint).await.unwrap(), Reservation::Reserved);
+    assert_eq!(idempotency.reserve("", "k", &fingerprint).await.unwrap(), Reservation::InProgress);
+    // Keys are per client
+    assert_eq!(idempotency.reserve("other", "k", &fingerprint).await.unwrap(), Reservation::Reserved);
+
+    // A released key can be used again
+    idempotency.release("", "k").await.unwrap();
+    assert_eq!(idempotency.reserve("", "k", &fingerprint).await.unwrap(), Reservation::Reserved);
+
+    let response = IdempotentResponse { status_code: 400, content_type: None, body: "bad".to_string() };
+    idempotency.complete("", "k", response.clone()).await.unwrap();
+    assert_eq!(idempotency.reserve("", "k", &fingerprint).await.unwrap(), Reservation::Replay(response));
+    // Completed keys stay until they expire
+    idempotency.release("", "k").await.unwrap();
+    assert_eq!(idempotency.reserve("", "k", "other").await.unwrap(), Reservation::Mismatch);
+}
+
+#[actix_rt::test]
+async fn test_expired_keys_are_taken_over_and_purged() {
+    let (_, idempotency) = idempotency(chrono::Duration::zero());
+    let fingerprint = fingerprint("POST", "/api/shorten", "", b"{}");
+
+    assert_eq!(idempotency.reserve("", "k", &fingerprint).await.unwrap(), Reservation::Reserved);
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    assert_eq!(idempotency.reserve("", "k", "other").await.unwrap(), Reservation::Reserved);
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    assert_eq!(idempotency.purge_expired().await.unwrap(), 1);
+}
+
+#[test]
+fn test_fingerprints_tell_requests_apart() {
+    let fingerprint_of = |path: &str, query: &str, body: &[u8]| fingerprint("POST", path, query, body);
+    assert_eq!(fingerprint_of("/api/shorten", "", b"{}"), fingerprint_of("/api/shorten", "", b"{}"));
+    assert_ne!(fingerprint_of("/api/shorten", "", b"{}"), fingerprint_of("/api/import", "", b"{}"));
+    // Parts are length-prefixed, so they cannot bleed into each other
+    assert_ne!(fingerprint_of("/a", "b", b""), fingerprint_of("/a", "", b"b"));
+}
@@ -0,0 +1,196 @@
+//! Reading link exports from other shorteners.
+//!
+//! Commercial shorteners export CSV files whose column names vary between products and
+//! over time, so columns are matched by a list of known names rather than by position.
+//! Rows that cannot be mapped are reported with their line number instead of failing
+//! the whole import.
+
+use std::io::Read;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+
+/// Export formats `import` understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// Bitly CSV link export
+    Bitly,
+    /// TinyURL CSV link export
+    Tinyurl,
+    /// Whole-instance dump written by `url-map export` (command line only)
+    Ndjson,
+}
+
+impl ImportFormat {
+    /// Header names of the short link column, normalized (see [`normalize_header`])
+    fn short_code_columns(self) -> &'static [&'static str] {
+        match self {
+            Self::Bitly => &["bitlink", "link", "short_link", "short_url", "custom_bitlink"],
+            Self::Tinyurl => &["alias", "tinyurl", "tiny_url", "short_link", "short_url"],
+            Self::Ndjson => &[],
+        }
+    }
+}
+
+const ORIGINAL_URL_COLUMNS: &[&str] = &["long_url", "original_url", "destination", "destination_url", "long_link", "url"];
+const TITLE_COLUMNS: &[&str] = &["title", "name"];
+const CREATED_AT_COLUMNS: &[&str] = &["created_at", "created", "date_created", "creation_date", "created_date"];
+const CLICKS_COLUMNS: &[&str] = &["clicks", "total_clicks", "click_count", "hits", "visits"];
+
+/// A link read from an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedLink {
+    /// Line of the export the link was read from
+    pub line: u64,
+    pub short_code: String,
+    pub original_url: String,
+    pub title: Option<String>,
+    /// Creation date, when the export has one
+    pub created_at: Option<DateTime<Utc>>,
+    /// Click count, when the export has one
+    pub clicks: Option<i64>,
+}
+
+/// A row that could not be imported, and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedRow {
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Rows read from an export, in file order
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    pub links: Vec<ImportedLink>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Positions of the mapped columns in the header
+struct Columns {
+    short_code: usize,
+    original_url: usize,
+    title: Option<usize>,
+    created_at: Option<usize>,
+    clicks: Option<usize>,
+}
+
+/// Reads a CSV export. Fails if the header lacks a short link or destination column;
+/// individual unreadable rows are returned in [`ParsedExport::rejected`].
+pub fn parse_export(format: ImportFormat, reader: impl Read) -> UrlShortenerResult<ParsedExport> {
+    if format == ImportFormat::Ndjson {
+        let message = "Instance dumps can only be restored with the import subcommand".to_string();
+        return Err(UrlShortenerErrorType::InvalidInput(message).into());
+    }
+
+    let mut csv = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(reader);
+    let headers = csv.headers().map_err(invalid_export)?.clone();
+    let headers: Vec<String> = headers.iter().map(normalize_header).collect();
+    let find = |names: &[&str]| names.iter().find_map(|name| headers.iter().position(|header| header == name));
+
+    let columns = Columns {
+        short_code: find(format.short_code_columns()).ok_or_else(|| missing_column("short link"))?,
+        original_url: find(ORIGINAL_URL_COLUMNS).ok_or_else(|| missing_column("destination URL"))?,
+        title: find(TITLE_COLUMNS),
+        created_at: find(CREATED_AT_COLUMNS),
+        clicks: find(CLICKS_COLUMNS),
+    };
+
+    let mut parsed = ParsedExport::default();
+    for record in csv.records() {
+        let record = record.map_err(invalid_export)?;
+        let line = record.position().map_or(0, |position| position.line());
+        match parse_row(&record, &columns, line) {
+            Ok(link) => parsed.links.push(link),
+            Err(reason) => parsed.rejected.push(RejectedRow { line, reason }),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_row(record: &csv::StringRecord, columns: &Columns, line: u64) -> Result<ImportedLink, String> {
+    let field = |index: Option<usize>| index.and_then(|index| record.get(index)).filter(|value| !value.is_empty());
+
+    let short_code = field(Some(columns.short_code))
+        .map(short_code_of)
+        .filter(|code| !code.is_empty())
+        .ok_or("missing short link")?;
+    let original_url = field(Some(columns.original_url)).ok_or("missing destination URL")?;
+    let created_at = field(columns.created_at)
+        .map(|value| parse_date(value).ok_or_else(|| format!("unreadable creation date '{}'", value)))
+        .transpose()?;
+    let clicks = field(columns.clicks)
+        .map(|value| {
+            value
+                .replace(',', "")
+                .parse::<i64>()
+                .ok()
+                .filter(|clicks| *clicks >= 0)
+                .ok_or_else(|| format!("unreadable click count '{}'", value))
+        })
+        .transpose()?;
+
+    Ok(ImportedLink {
+        line,
+        short_code: short_code.to_string(),
+        original_url: original_url.to_string(),
+        title: field(columns.title).map(str::to_string),
+        created_at,
+        clicks,
+    })
+}
+
+/// Lowercases a header and joins its words with underscores: `Long URL` becomes `long_url`
+fn normalize_header(header: &str) -> String {
+    header
+        .trim_start_matches('\u{feff}')
+        .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_lowercase()
+}
+
+/// Exports list short links as `bit.ly/abc`, `https://tinyurl.com/abc` or bare `abc`; keeps the code
+fn short_code_of(link: &str) -> &str {
+    let link = link.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/');
+    match link.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map_or("", |(_, path)| path),
+        None => link.split_once('/').map_or(link, |(_, path)| path),
+    }
+}
+
+/// Reads the date formats seen in exports; dates without a zone are taken as UTC
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f %z") {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%m/%d/%Y %H:%M:%S", "%m/%d/%Y %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc());
+        }
+    }
+    for format in ["%Y-%m-%d", "%m/%d/%Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return date.and_hms_opt(0, 0, 0).map(|date| date.and_utc());
+        }
+    }
+    None
+}
+
+fn invalid_export(err: csv::Error) -> UrlShortenerError {
+    let message = format!("Unreadable export: {}", err);
+    UrlShortenerError::with_source(UrlShortenerErrorType::InvalidInput(message), err)
+}
+
+fn missing_column(column: &str) -> UrlShortenerError {
+    UrlShortenerErrorType::InvalidInput(format!("Export has no {} column", column)).into()
+}
+
+#[cfg(test)]
+mod tests;
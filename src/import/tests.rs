@@ -0,0 +1,67 @@
+use super::*;
+
+#[test]
+fn test_parse_bitly_export() {
+    let export = "\u{feff}Title,Bitlink,Long URL,Date Created,Total Clicks\n\
+                  Spring sale,bit.ly/3xYzAbC,https://shop.example.com/spring,2023-03-01 09:30:00,\"1,204\"\n\
+                  ,https://bit.ly/docs-home,https://docs.example.com/,2022-11-15T08:00:00Z,0\n";
+    let parsed = parse_export(ImportFormat::Bitly, export.as_bytes()).unwrap();
+
+    assert!(parsed.rejected.is_empty());
+    assert_eq!(parsed.links[0], ImportedLink {
+        line: 2,
+        short_code: "3xYzAbC".to_string(),
+        original_url: "https://shop.example.com/spring".to_string(),
+        title: Some("Spring sale".to_string()),
+        created_at: Some("2023-03-01T09:30:00Z".parse().unwrap()),
+        clicks: Some(1204),
+    });
+    assert_eq!(parsed.links[1].short_code, "docs-home");
+    assert_eq!(parsed.links[1].title, None);
+}
+
+#[test]
+fn test_parse_tinyurl_export_without_optional_columns() {
+    let export = "alias,url\nmy-talk,https://talks.example.com/2024\n";
+    let parsed = parse_export(ImportFormat::Tinyurl, export.as_bytes()).unwrap();
+
+    assert_eq!(parsed.links.len(), 1);
+    assert_eq!(parsed.links[0].short_code, "my-talk");
+    assert_eq!((parsed.links[0].created_at, parsed.links[0].clicks), (None, None));
+}
+
+#[test]
+fn test_unreadable_rows_are_rejected_with_line_numbers() {
+    let export = "alias,url,created_at,hits\n\
+                  ok,https://example.com,03/14/2024,5\n\
+                  ,https://example.com,,\n\
+                  bad-date,https://example.com,yesterday,\n\
+                  bad-hits,https://example.com,,-3\n";
+    let parsed = parse_export(ImportFormat::Tinyurl, export.as_bytes()).unwrap();
+
+    assert_eq!(parsed.links.len(), 1);
+    assert_eq!(parsed.links[0].created_at, Some("2024-03-14T00:00:00Z".parse().unwrap()));
+    let lines: Vec<u64> = parsed.rejected.iter().map(|row| row.line).collect();
+    assert_eq!(lines, [3, 4, 5]);
+    assert_eq!(parsed.rejected[0].reason, "missing short link");
+}
+
+#[test]
+fn test_exports_without_required_columns_fail() {
+    let err = parse_export(ImportFormat::Bitly, "alias,url\nabc,https://example.com\n".as_bytes()).unwrap_err();
+    assert!(matches!(err.error_type, UrlShortenerErrorType::InvalidInput(_)));
+}
+
+#[test]
+fn test_short_code_of() {
+    assert_eq!(short_code_of("bit.ly/abc"), "abc");
+    assert_eq!(short_code_of("https://tinyurl.com/abc/?utm=x"), "abc");
+    assert_eq!(short_code_of("abc"), "abc");
+    assert_eq!(short_code_of("https://bit.ly"), "");
+}
+
+#[test]
+fn test_dumps_are_not_parsed_as_exports() {
+    let err = parse_export(ImportFormat::Ndjson, "{}\n".as_bytes()).unwrap_err();
+    assert!(matches!(err.error_type, UrlShortenerErrorType::InvalidInput(_)));
+}
@@ -0,0 +1,2 @@
+#[cfg(feature = "telegram")]
+pub mod telegram;
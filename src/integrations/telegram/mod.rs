@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::Cursor;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument, warn};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::i18n;
+use crate::services::UrlService;
+
+/// Header Telegram sends with every webhook call once a secret token is registered
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Maximum number of URLs shortened from a single message
+const MAX_URLS_PER_MESSAGE: usize = 5;
+
+/// Configuration for the Telegram bot integration
+#[derive(Clone, Debug)]
+pub struct TelegramConfig {
+    /// Bot token issued by BotFather
+    pub bot_token: String,
+    /// Secret token registered with `setWebhook`, checked on every update
+    pub webhook_secret: String,
+    /// Base URL of the Telegram Bot API
+    pub api_base_url: String,
+    /// Maximum number of messages handled per chat within `rate_limit_window`
+    pub rate_limit_per_chat: u32,
+    /// Length of the per-chat rate limiting window
+    pub rate_limit_window: Duration,
+}
+
+impl TelegramConfig {
+    /// Loads the Telegram configuration from the environment.
+    ///
+    /// Returns `None` when `TELEGRAM_BOT_TOKEN` is not set, which disables the integration.
+    /// Without `TELEGRAM_WEBHOOK_SECRET` anyone could post updates to the webhook, so the
+    /// integration stays disabled, with a warning, until it is set too.
+    pub fn from_env() -> Option<Self> {
+        let bot_token = env::var("TELEGRAM_BOT_TOKEN").ok()?;
+        let Some(webhook_secret) = env::var("TELEGRAM_WEBHOOK_SECRET").ok().filter(|v| !v.is_empty()) else {
+            warn!("TELEGRAM_BOT_TOKEN is set without TELEGRAM_WEBHOOK_SECRET; Telegram integration disabled");
+            return None;
+        };
+
+        Some(Self {
+            bot_token,
+            webhook_secret,
+            api_base_url: env::var("TELEGRAM_API_BASE_URL")
+                .unwrap_or_else(|_| "https://api.telegram.org".to_string()),
+            rate_limit_per_chat: env::var("TELEGRAM_RATE_LIMIT_PER_CHAT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            rate_limit_window: Duration::from_secs(
+                env::var("TELEGRAM_RATE_LIMIT_WINDOW_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60),
+            ),
+        })
+    }
+}
+
+/// An incoming update from the Telegram Bot API
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub message_id: i64,
+    pub chat: Chat,
+    pub from: Option<User>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    /// IETF language tag of the user's Telegram client, e.g. `es`
+    pub language_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct SendMessage<'a> {
+    chat_id: i64,
+    text: &'a str,
+    reply_to_message_id: i64,
+}
+
+/// Fixed-window rate limiter keyed by chat ID
+struct ChatRateLimiter {
+    limit: u32,
+    window: Duration,
+    windows: Mutex<HashMap<i64, (Instant, u32)>>,
+}
+
+impl ChatRateLimiter {
+    fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a message for the chat and returns whether it is within the limit
+    fn check(&self, chat_id: i64) -> bool {
+        let now = Instant::now();
+        let mut windows = match self.windows.lock() {
+            Ok(windows) => windows,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        // Drop expired windows so the map doesn't grow with every chat ever seen
+        windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+
+        let (_, count) = windows.entry(chat_id).or_insert((now, 0));
+        *count += 1;
+        *count <= self.limit
+    }
+}
+
+/// Telegram bot that shortens links sent to it
+pub struct TelegramBot {
+    config: TelegramConfig,
+    public_base_url: String,
+    http: HttpClient,
+    rate_limiter: ChatRateLimiter,
+}
+
+impl TelegramBot {
+    pub fn new(config: TelegramConfig, public_base_url: String, http: HttpClient) -> Self {
+        let rate_limiter = ChatRateLimiter::new(config.rate_limit_per_chat, config.rate_limit_window);
+        Self {
+            config,
+            public_base_url,
+            http,
+            rate_limiter,
+        }
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!(
+            "{}/bot{}/{}",
+            self.config.api_base_url.trim_end_matches('/'),
+            self.config.bot_token,
+            method
+        )
+    }
+
+    /// Checks the secret token header against the configured webhook secret
+    fn is_authorized(&self, req: &HttpRequest) -> bool {
+        req.headers()
+            .get(SECRET_TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v == self.config.webhook_secret)
+    }
+
+    async fn send_message(&self, chat_id: i64, reply_to: i64, text: &str) -> UrlShortenerResult<()> {
+        let request = self.http.post(self.method_url("sendMessage")).json(&SendMessage {
+            chat_id,
+            text,
+            reply_to_message_id: reply_to,
+        });
+        self.http
+            .send("telegram", request)
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| {
+                UrlShortenerError::with_source(
+                    UrlShortenerErrorType::InternalError("Failed to send Telegram message".to_string()),
+                    e,
+                )
+            })?;
+        Ok(())
+    }
+
+    async fn send_qr_code(&self, chat_id: i64, reply_to: i64, short_link: &str) -> UrlShortenerResult<()> {
+        let png = render_qr_png(short_link)?;
+        let photo = reqwest::multipart::Part::bytes(png)
+            .file_name("qr.png")
+            .mime_str("image/png")
+            .map_err(|e| {
+                UrlShortenerError::with_source(
+                    UrlShortenerErrorType::InternalError("Invalid QR code attachment".to_string()),
+                    e,
+                )
+            })?;
+        let form = reqwest::multipart::Form::new()
+            .text("chat_id", chat_id.to_string())
+            .text("reply_to_message_id", reply_to.to_string())
+            .text("caption", short_link.to_string())
+            .part("photo", photo);
+
+        let request = self.http.post(self.method_url("sendPhoto")).multipart(form);
+        self.http
+            .send("telegram", request)
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| {
+                UrlShortenerError::with_source(
+                    UrlShortenerErrorType::InternalError("Failed to send Telegram photo".to_string()),
+                    e,
+                )
+            })?;
+        Ok(())
+    }
+
+    /// Handles a single message, replying with one short link and QR code per URL found
+    #[instrument(skip(self, message, service), fields(chat_id = message.chat.id))]
+    async fn handle_message(&self, message: &Message, service: &UrlService) -> UrlShortenerResult<()> {
+        let chat_id = message.chat.id;
+
+        if !self.rate_limiter.check(chat_id) {
+            warn!("Telegram chat exceeded rate limit");
+            return self
+                .send_message(chat_id, message.message_id, "Too many requests, please slow down.")
+                .await;
+        }
+
+        let urls = extract_urls(message.text.as_deref().unwrap_or_default());
+        if urls.is_empty() {
+            return self
+                .send_message(chat_id, message.message_id, "Send me a link and I'll shorten it.")
+                .await;
+        }
+
+        for url in urls {
+            match service.create_short_url(url.to_string()).await {
+                Ok(shortened) => {
+                    let short_link = format!(
+                        "{}/{}",
+                        self.public_base_url.trim_end_matches('/'),
+                        shortened.short_code
+                    );
+                    info!(short_code = %shortened.short_code, "Shortened URL for Telegram chat");
+                    self.send_qr_code(chat_id, message.message_id, &short_link).await?;
+                }
+                Err(e) => {
+                    debug!(error = %e, "Rejected URL from Telegram chat");
+                    // The catalog's user-facing text, which leaves out internal details
+                    let language = message.from.as_ref().and_then(|from| from.language_code.as_deref());
+                    let reason = e.error_type.localized_message(i18n::negotiate(language.unwrap_or_default()));
+                    let reply = format!("Could not shorten {}: {}", url, reason);
+                    self.send_message(chat_id, message.message_id, &reply).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Extracts up to `MAX_URLS_PER_MESSAGE` http(s) URLs from a message text
+pub fn extract_urls(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .filter(|token| token.starts_with("http://") || token.starts_with("https://"))
+        .take(MAX_URLS_PER_MESSAGE)
+        .collect()
+}
+
+/// Renders a QR code for the given link as a PNG image
+pub fn render_qr_png(link: &str) -> UrlShortenerResult<Vec<u8>> {
+    let code = QrCode::new(link.as_bytes()).map_err(|e| {
+        UrlShortenerError::with_source(
+            UrlShortenerErrorType::InternalError("Failed to encode QR code".to_string()),
+            anyhow::anyhow!(e.to_string()),
+        )
+    })?;
+    let image = code.render::<Luma<u8>>().min_dimensions(256, 256).build();
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png).map_err(|e| {
+        UrlShortenerError::with_source(
+            UrlShortenerErrorType::InternalError("Failed to render QR code".to_string()),
+            e,
+        )
+    })?;
+    Ok(png.into_inner())
+}
+
+/// Webhook endpoint receiving updates from the Telegram Bot API.
+///
+/// Always acknowledges accepted updates with 200 so Telegram does not redeliver them;
+/// failures while replying are logged instead.
+pub async fn webhook(
+    req: HttpRequest,
+    update: web::Json<Update>,
+    bot: Option<web::Data<TelegramBot>>,
+    service: web::Data<UrlService>,
+) -> UrlShortenerResult<HttpResponse> {
+    let bot = bot.ok_or_else(|| UrlShortenerError::from(UrlShortenerErrorType::NotFound))?;
+
+    if !bot.is_authorized(&req) {
+        warn!(update_id = update.update_id, "Rejected Telegram update with invalid secret token");
+        return Err(UrlShortenerErrorType::Unauthorized.into());
+    }
+
+    if let Some(message) = &update.message {
+        if let Err(e) = bot.handle_message(message, &service).await {
+            warn!(error = %e, update_id = update.update_id, "Failed to handle Telegram update");
+        }
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(test)]
+mod tests;
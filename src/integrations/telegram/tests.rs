@@ -0,0 +1,92 @@
+use actix_web::{test, web, App};
+use crate::services::UrlService;
+use crate::storage::{MemoryStorage, StorageConfig};
+use std::sync::Arc;
+use super::*;
+
+fn test_config() -> TelegramConfig {
+    TelegramConfig {
+        bot_token: "test-token".to_string(),
+        webhook_secret: "secret".to_string(),
+        api_base_url: "http://127.0.0.1:9".to_string(),
+        rate_limit_per_chat: 2,
+        rate_limit_window: Duration::from_secs(60),
+    }
+}
+
+#[actix_rt::test]
+async fn test_extract_urls() {
+    let urls = extract_urls("check https://example.com and http://foo.bar/baz, not ftp://x");
+    assert_eq!(urls, vec!["https://example.com", "http://foo.bar/baz,"]);
+    assert!(extract_urls("no links here").is_empty());
+}
+
+#[actix_rt::test]
+async fn test_extract_urls_caps_per_message() {
+    let text = "https://a.com ".repeat(MAX_URLS_PER_MESSAGE + 3);
+    assert_eq!(extract_urls(&text).len(), MAX_URLS_PER_MESSAGE);
+}
+
+#[actix_rt::test]
+async fn test_chat_rate_limiter() {
+    let limiter = ChatRateLimiter::new(2, Duration::from_secs(60));
+    assert!(limiter.check(1));
+    assert!(limiter.check(1));
+    assert!(!limiter.check(1));
+    // Other chats have their own budget
+    assert!(limiter.check(2));
+}
+
+#[actix_rt::test]
+async fn test_render_qr_png() {
+    let png = render_qr_png("http://localhost:8080/abc123").unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[actix_rt::test]
+async fn test_webhook_rejects_invalid_secret() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage));
+    let bot = web::Data::new(TelegramBot::new(test_config(), "http://localhost:8080".to_string(), HttpClient::default()));
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .app_data(bot)
+            .service(web::resource("/integrations/telegram").route(web::post().to(webhook)))
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/integrations/telegram")
+        .insert_header((SECRET_TOKEN_HEADER, "wrong"))
+        .set_json(serde_json::json!({ "update_id": 1 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status().as_u16(), 401);
+
+    // Updates without the header are rejected too
+    let req = test::TestRequest::post()
+        .uri("/integrations/telegram")
+        .set_json(serde_json::json!({ "update_id": 2 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn test_webhook_not_configured() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let service = web::Data::new(UrlService::new(storage));
+    let app = test::init_service(
+        App::new()
+            .app_data(service)
+            .service(web::resource("/integrations/telegram").route(web::post().to(webhook)))
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/integrations/telegram")
+        .set_json(serde_json::json!({ "update_id": 1 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
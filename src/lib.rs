@@ -0,0 +1,41 @@
+//! URL shortening service: HTTP handlers, business logic and storage backends.
+//!
+//! The `url-map` binary wires these modules into an Actix-web server and CLI;
+//! the library target exists so benchmarks and integration tests can exercise them directly.
+
+pub mod abuse;
+pub mod auth;
+pub mod cli;
+pub mod config;
+pub mod deadline;
+pub mod dump;
+pub mod errors;
+#[cfg(feature = "parquet-export")]
+pub mod export;
+#[cfg(feature = "favicon-proxy")]
+pub mod favicons;
+pub mod goals;
+pub mod handlers;
+pub mod health;
+pub mod http_client;
+pub mod i18n;
+pub mod idempotency;
+pub mod import;
+pub mod integrations;
+pub mod logging;
+pub mod middleware;
+pub mod models;
+#[cfg(feature = "peer-cache")]
+pub mod peers;
+pub mod preview;
+pub mod privacy;
+pub mod redirect;
+pub mod routes;
+pub mod runaway;
+pub mod services;
+pub mod slo;
+pub mod storage;
+pub mod throttle;
+pub mod usage;
+pub mod validation;
+pub mod webhooks;
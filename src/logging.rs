@@ -1,13 +1,65 @@
+use std::backtrace::Backtrace;
+use std::env;
+use std::sync::Mutex;
+
 use tracing_subscriber::{
+    filter::ParseError,
     fmt,
     prelude::*,
-    EnvFilter,
+    reload,
+    EnvFilter, Registry,
 };
 
-/// Initialize the logging system with JSON formatting and environment-based configuration
-pub fn init_logging() {
-    let env_filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+use crate::errors::{current_correlation_id, UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+
+/// Filter used when `RUST_LOG` is unset or invalid
+const DEFAULT_FILTER: &str = "info";
+
+/// The log filter of the running process, which can be swapped without a restart, e.g. to turn
+/// on debug logging for one module during an incident
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    directives: Mutex<String>,
+}
+
+impl LogFilter {
+    /// A filter from `EnvFilter` directives, with the layer to install it through
+    pub fn new(directives: &str) -> Result<(Self, reload::Layer<EnvFilter, Registry>), ParseError> {
+        let (layer, handle) = reload::Layer::new(EnvFilter::try_new(directives)?);
+        let filter = Self {
+            handle,
+            directives: Mutex::new(directives.to_string()),
+        };
+        Ok((filter, layer))
+    }
+
+    /// Directives currently in effect
+    pub fn directives(&self) -> String {
+        self.directives.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Replaces the filter. Invalid directives are an internal error, so user input should be
+    /// checked with [`EnvFilter::try_new`] first.
+    pub fn set(&self, directives: &str) -> UrlShortenerResult<()> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::InternalError(e.to_string())))?;
+        let mut current = self.directives.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.handle
+            .reload(filter)
+            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::InternalError(e.to_string())))?;
+        *current = directives.to_string();
+        Ok(())
+    }
+}
+
+/// Initialize the logging system with JSON formatting and environment-based configuration.
+/// Returns the handle for changing the log filter at runtime.
+pub fn init_logging() -> LogFilter {
+    let directives = env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|directives| EnvFilter::try_new(directives).is_ok())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    let (log_filter, env_filter) = LogFilter::new(&directives).expect("log filter directives were checked");
 
     let formatting_layer = fmt::layer()
         .json()
@@ -25,7 +77,31 @@ pub fn init_logging() {
         .with(formatting_layer)
         .init();
 
+    install_panic_hook();
+
     tracing::info!("Logging system initialized");
+    log_filter
+}
+
+/// Logs panics as errors with their location, backtrace and, inside a request, its correlation
+/// ID, instead of printing them to stderr
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        let location = info.location().map(ToString::to_string).unwrap_or_default();
+        tracing::error!(
+            correlation_id = current_correlation_id().unwrap_or_default(),
+            location = %location,
+            backtrace = %Backtrace::force_capture(),
+            "Panicked: {}",
+            message
+        );
+    }));
 }
 
 /// Create a correlation ID for request tracing
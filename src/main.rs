@@ -1,22 +1,39 @@
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use tracing::info;
+use clap::Parser;
+use tracing::{info, warn};
 use std::sync::Arc;
+use std::time::Duration;
 
-mod config;
-mod errors;
-mod handlers;
-mod logging;
-mod middleware;
-mod models;
-mod routes;
-mod services;
-mod storage;
-
-use crate::config::Config;
-use crate::logging::init_logging;
-use crate::middleware::RequestLogger;
-use crate::services::UrlService;
-use crate::storage::PostgresStorage;
+use url_map::abuse::AbuseGuard;
+use url_map::auth::{ApiKeys, Sessions};
+use url_map::cli::{self, Cli, Command};
+use url_map::config::Config;
+#[cfg(feature = "telegram")]
+use url_map::integrations;
+use url_map::goals::{GoalChecker, GOAL_WEBHOOK};
+use url_map::handlers::RobotsTxt;
+use url_map::health::{self, HealthChecks};
+use url_map::http_client::HttpClient;
+use url_map::idempotency::Idempotency;
+use url_map::logging::init_logging;
+use url_map::middleware::{
+    AnonymizedRootSpan, CatchPanic, Deadline, Localize, RecordSlo, RequestLogger, Tarpit, Throttle,
+};
+#[cfg(feature = "peer-cache")]
+use url_map::peers::{PeerCache, PeerConfig};
+use url_map::preview::LinkPreviews;
+use url_map::redirect::{RedirectConfig, Redirector, StaticLinks, SuggestionScope, TypoIndex};
+use url_map::routes;
+use url_map::runaway::{RunawayGuard, RUNAWAY_WEBHOOK};
+use url_map::services::UrlService;
+use url_map::slo::SloCounters;
+use url_map::storage::{
+    EncryptedStorage, MigratingStorage, PostgresStorage, ReadOnlyStorage, SchemaCompatibility, SchemaMismatch, StorageRef,
+    TimedStorage,
+};
+use url_map::throttle::RouteThrottle;
+use url_map::usage::UsageMeter;
+use url_map::webhooks::{WebhookNotifier, Webhooks};
 
 #[derive(serde::Serialize)]
 struct HealthResponse {
@@ -32,23 +49,264 @@ async fn health_check() -> impl Responder {
 }
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    // Initialize logging with JSON formatting
-    init_logging();
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
 
     // Load configuration
     let config = Config::from_env();
+
+    match cli.command {
+        None | Some(Command::Serve) => serve(config).await?,
+        Some(command) => cli::run(command, &config).await?,
+    }
+
+    Ok(())
+}
+
+/// Runs the HTTP server until it is shut down
+async fn serve(config: Config) -> std::io::Result<()> {
+    // Initialize logging with JSON formatting
+    let log_filter = web::Data::new(init_logging());
+
     let server_config = config.clone();
 
     // Initialize PostgreSQL storage
-    let storage = Arc::new(
+    let postgres = Arc::new(
         PostgresStorage::new(config.to_storage_config())
             .await
             .expect("Failed to initialize PostgreSQL storage")
     );
 
-    // Create URL service with PostgreSQL storage
-    let url_service = web::Data::new(UrlService::new(storage));
+    // Never run against a schema missing what this release needs. One that is ahead, migrated
+    // by a newer release mid-rollout, may be served read-only so old instances keep redirecting
+    // without writing rows the new schema does not expect.
+    let read_only = match postgres.schema_compatibility().await.expect("Failed to check the database schema") {
+        SchemaCompatibility::Current => server_config.read_only,
+        SchemaCompatibility::Behind(versions) => {
+            return Err(std::io::Error::other(format!(
+                "Database schema is missing migrations {:?}; run `url-map migrate up` first",
+                versions
+            )));
+        }
+        SchemaCompatibility::Ahead(versions) if server_config.read_only => {
+            warn!(?versions, "Database schema is ahead of this release");
+            true
+        }
+        SchemaCompatibility::Ahead(versions) => match server_config.schema_mismatch {
+            SchemaMismatch::Refuse => {
+                return Err(std::io::Error::other(format!(
+                    "Database schema has migrations {:?} this release does not know; set SCHEMA_MISMATCH=read-only to serve it read-only",
+                    versions
+                )));
+            }
+            SchemaMismatch::ReadOnly => {
+                warn!(?versions, "Database schema is ahead of this release; starting read-only");
+                true
+            }
+        },
+    };
+    if server_config.read_only {
+        info!("Read-only mode: writes are rejected and visits are not counted");
+    }
+
+    let fold_interval = Duration::from_secs(server_config.visit_shard_fold_interval_secs.max(1));
+    if !read_only {
+        postgres.clone().spawn_shard_folding(fold_interval);
+    }
+
+    // While migrating databases, keep the old one authoritative and mirror writes to the new one
+    let storage: StorageRef = match config.to_migration_source_config() {
+        Some(source) => {
+            info!("Dual-writing to the migration source database");
+            let old = Arc::new(
+                PostgresStorage::new(source)
+                    .await
+                    .expect("Failed to initialize migration source storage")
+            );
+            if !read_only {
+                old.clone().spawn_shard_folding(fold_interval);
+            }
+            Arc::new(MigratingStorage::new(old, postgres))
+        }
+        None => postgres,
+    };
+
+    // Keep destination URLs encrypted at rest when a key is configured
+    let storage: StorageRef = match server_config.url_cipher().expect("Invalid URL encryption key") {
+        Some(cipher) => {
+            info!(key_id = cipher.key_id(), "Encrypting destination URLs at rest");
+            Arc::new(EncryptedStorage::new(storage, cipher))
+        }
+        None => storage,
+    };
+
+    // Turn away every write, whichever database it would go to
+    let storage: StorageRef = if read_only { Arc::new(ReadOnlyStorage::new(storage)) } else { storage };
+
+    // Log and count storage calls that are slower than the threshold
+    let storage: StorageRef = match server_config.slow_query_threshold_ms {
+        0 => storage,
+        threshold_ms => Arc::new(TimedStorage::new(storage, Duration::from_millis(threshold_ms))),
+    };
+
+    // Every outbound call shares one client, with its proxy, timeout and connection limit
+    let http_client = HttpClient::new(server_config.outbound_config()).expect("Invalid outbound HTTP configuration");
+
+    // Notify owners about reached click goals when a webhook is configured
+    let mut webhooks = Webhooks::default();
+    if let Some(webhook_url) = server_config.goal_webhook_url.clone().filter(|_| !read_only) {
+        info!("Click goal notifications enabled");
+        let notifier = Arc::new(WebhookNotifier::new(GOAL_WEBHOOK, webhook_url, http_client.clone(), storage.clone()));
+        webhooks.register(notifier.clone());
+        let checker = GoalChecker::new(storage.clone(), notifier);
+        Arc::new(checker).spawn(Duration::from_secs(server_config.goal_check_interval_secs));
+    }
+    // Disable links whose redirects run far above their usual rate, notifying when configured
+    let runaway_guard = server_config.runaway_config().filter(|_| !read_only).map(|config| {
+        info!(multiple = config.multiple, min_rate = config.min_rate, "Runaway link guard enabled");
+        let mut guard = RunawayGuard::new(config, storage.clone());
+        if let Some(webhook_url) = server_config.runaway_webhook_url.clone() {
+            let notifier = Arc::new(WebhookNotifier::new(RUNAWAY_WEBHOOK, webhook_url, http_client.clone(), storage.clone()));
+            webhooks.register(notifier.clone());
+            guard = guard.with_notifier(notifier);
+        }
+        Arc::new(guard)
+    });
+    let webhooks = web::Data::new(webhooks);
+
+    // Roll links and visits up into daily usage records per owner, for billing
+    if server_config.usage_rollup_interval_secs > 0 && !read_only {
+        Arc::new(UsageMeter::new(storage.clone())).spawn(Duration::from_secs(server_config.usage_rollup_interval_secs));
+    }
+
+    // Create URL service with PostgreSQL storage; redirects bypass it for a leaner hot path
+    let redirect_config = RedirectConfig { record_visits: !read_only, ..server_config.to_redirect_config() };
+    let mut redirector = Redirector::new(storage.clone(), redirect_config);
+    // Permanent links from a file, managed outside the API
+    let static_links = StaticLinks::load(server_config.static_links_file.as_deref())?;
+    if !static_links.is_empty() {
+        info!(links = static_links.len(), "Static links loaded");
+        for code in static_links.codes() {
+            if storage.get_stats(code).await.is_ok() {
+                warn!(short_code = %code, "Static link shadows a stored link with the same code");
+            }
+        }
+        redirector = redirector.with_static_links(static_links.clone());
+    }
+    if let Some(guard) = runaway_guard {
+        redirector = redirector.with_runaway_guard(guard);
+    }
+    // Unknown codes are answered with the nearest existing ones
+    match server_config.typo_index_prefix() {
+        Some(prefix) => {
+            let codes = static_links.codes().map(str::to_string).collect();
+            let index = Arc::new(TypoIndex::new(storage.clone(), prefix, codes));
+            index.clone().spawn(Duration::from_secs(server_config.typo_index_refresh_secs.max(1)));
+            redirector = redirector.with_typo_index(index);
+        }
+        None if server_config.typo_suggestions != SuggestionScope::Off => {
+            warn!("Typo suggestions are limited to keywords, but no GO_LINKS_PREFIX is set");
+        }
+        None => {}
+    }
+    // Cache misses go to the peer owning the code first, when a cluster is configured
+    #[cfg(feature = "peer-cache")]
+    let redirector = match PeerConfig::from_env() {
+        Some(peer_config) => {
+            info!(peers = peer_config.peers.len(), "Peer cache enabled");
+            redirector.with_peers(PeerCache::new(peer_config, http_client.clone()))
+        }
+        None => redirector,
+    };
+    let redirector = web::Data::new(redirector);
+    // Warm the cache before binding, so no traffic is routed here while it is still cold
+    if server_config.redirect_warmup_codes > 0 {
+        match redirector.warm_up(server_config.redirect_warmup_codes).await {
+            Ok(loaded) => info!(loaded, "Redirect cache warmed up"),
+            Err(e) => warn!(error = %e, "Redirect cache warm-up failed; starting cold"),
+        }
+    }
+    let api_keys = web::Data::new(
+        ApiKeys::new(server_config.api_keys.clone())
+            .with_storage(storage.clone())
+            .with_admins(&server_config.admin_owners),
+    );
+    let sessions = web::Data::new(Sessions::new(storage.clone(), server_config.session_config()));
+    let health_checks = web::Data::new(HealthChecks::new(
+        storage.clone(),
+        redirector.clone(),
+        server_config.health_config(),
+    ));
+    let idempotency = match server_config.idempotency_retention_hours {
+        0 => None,
+        _ if read_only => None,
+        hours => {
+            let idempotency = Arc::new(Idempotency::new(storage.clone(), chrono::Duration::hours(hours as i64)));
+            idempotency.clone().spawn_purge();
+            Some(web::Data::from(idempotency))
+        }
+    };
+    let namespaces = static_links.reserve(server_config.namespaces());
+    if let Some(region) = server_config.code_region {
+        info!(region, worker = server_config.code_worker, "Minting region-unique short codes");
+    }
+    let mut url_service = UrlService::with_code_generator(storage, server_config.code_generator())
+        .with_code_lookup(server_config.code_lookup())
+        .with_alias_policy(server_config.alias_policy())
+        .with_homograph_policy(server_config.homograph_policy)
+        .with_profanity_filter(server_config.profanity_filter())
+        .with_namespaces(namespaces.clone())
+        .with_link_quota(server_config.link_quota())
+        .with_url_length_limits(server_config.url_length_limits());
+    if let Some(content_check) = server_config.content_check() {
+        info!("Destination content check enabled");
+        url_service = url_service.with_content_check(content_check.with_http_client(&http_client));
+    }
+    let url_service = web::Data::new(url_service);
+    if server_config.audit_retention_days > 0 && !read_only {
+        let retention = chrono::Duration::days(server_config.audit_retention_days as i64);
+        url_service.clone().into_inner().spawn_audit_retention(retention);
+    }
+    let robots_txt = web::Data::new(RobotsTxt::load(server_config.robots_txt_file.as_deref())?);
+    let link_previews = server_config.social_previews.then(|| {
+        info!("Link previews for social crawlers enabled");
+        web::Data::new(LinkPreviews::new(&server_config.public_base_url, http_client.clone()))
+    });
+    let abuse_guard = server_config.abuse_config().map(|config| {
+        info!(max_errors = config.max_errors, penalty = ?config.penalty, "Abusive client penalties enabled");
+        web::Data::new(AbuseGuard::new(config))
+    });
+    let route_throttle = server_config.throttle_config().map(|config| {
+        info!(?config, "Route class throttling enabled");
+        web::Data::new(RouteThrottle::new(&config))
+    });
+    let deadlines = web::Data::new(server_config.deadline_config());
+    let slo_counters = web::Data::new(SloCounters::new(server_config.slo_config()));
+    let ip_anonymizer = web::Data::new(server_config.ip_anonymizer());
+    let resolve_access = web::Data::new(server_config.resolve_access());
+    info!(mode = ?ip_anonymizer.mode(), "Visitor IP privacy configured");
+    let shutdown_redirector = redirector.clone();
+    let app_config = web::Data::new(server_config.clone());
+
+    // Telegram bot is only active when compiled in and a bot token is configured
+    #[cfg(feature = "telegram")]
+    let telegram_bot = integrations::telegram::TelegramConfig::from_env().map(|telegram_config| {
+        info!("Telegram integration enabled");
+        web::Data::new(integrations::telegram::TelegramBot::new(
+            telegram_config,
+            server_config.public_base_url.clone(),
+            http_client.clone(),
+        ))
+    });
+
+    // Destination favicons are fetched once and kept, on disk when a directory is configured
+    #[cfg(feature = "favicon-proxy")]
+    let favicons = web::Data::new(url_map::favicons::Favicons::new(
+        http_client.clone(),
+        server_config.favicon_cache_dir.clone().map(std::path::PathBuf::from),
+    ));
+
+    let http_client = web::Data::new(http_client);
 
     info!(
         host = %server_config.host,
@@ -57,21 +315,87 @@ async fn main() -> std::io::Result<()> {
     );
 
     HttpServer::new(move || {
-        App::new()
+        let app = App::new()
             // Add URL service to application state
             .app_data(url_service.clone())
+            .app_data(redirector.clone())
+            .app_data(api_keys.clone())
+            .app_data(sessions.clone())
+            .app_data(robots_txt.clone())
+            .app_data(ip_anonymizer.clone())
+            .app_data(resolve_access.clone())
+            .app_data(webhooks.clone())
+            .app_data(http_client.clone())
+            .app_data(health_checks.clone())
+            .app_data(log_filter.clone())
+            .app_data(deadlines.clone())
+            .app_data(slo_counters.clone())
+            .app_data(app_config.clone());
+
+        let app = match &link_previews {
+            Some(previews) => app.app_data(previews.clone()),
+            None => app,
+        };
+
+        let app = match &idempotency {
+            Some(idempotency) => app.app_data(idempotency.clone()),
+            None => app,
+        };
+
+        let app = match &abuse_guard {
+            Some(guard) => app.app_data(guard.clone()),
+            None => app,
+        };
+
+        let app = match &route_throttle {
+            Some(throttle) => app.app_data(throttle.clone()),
+            None => app,
+        };
+
+        #[cfg(feature = "telegram")]
+        let app = match &telegram_bot {
+            Some(bot) => app.app_data(bot.clone()),
+            None => app,
+        };
+
+        #[cfg(feature = "favicon-proxy")]
+        let app = app.app_data(favicons.clone());
+
+        app
+            // Answer handler panics with a 500 instead of dropping the connection
+            .wrap(CatchPanic)
+            // Delay or refuse clients penalized for causing too many errors
+            .wrap(Tarpit)
+            // Turn away requests beyond their route class's budget; outside the tarpit, so
+            // overload does not count against clients
+            .wrap(Throttle)
+            // Cancel storage calls once the request's deadline has passed; outside the tarpit and
+            // throttle, so time spent there counts
+            .wrap(Deadline)
+            // Count requests against their route class's latency objective, as clients saw them
+            .wrap(RecordSlo)
+            // Localize error messages from Accept-Language
+            .wrap(Localize)
             // Add our custom request logger
             .wrap(RequestLogger)
-            // Add tracing integration
-            .wrap(tracing_actix_web::TracingLogger::default())
+            // Add tracing integration, with anonymized client addresses
+            .wrap(tracing_actix_web::TracingLogger::<AnonymizedRootSpan>::new())
             // Add compression middleware
             .wrap(actix_web::middleware::Compress::default())
             // Add health check endpoint
             .route("/health", web::get().to(health_check))
+            // Readiness for load balancers, with the database and cache checked
+            .route("/health/ready", web::get().to(health::ready))
+            // Namespaced redirects, ahead of the root redirect route
+            .configure(routes::configure_namespaces(&namespaces))
             // Configure API routes
             .configure(routes::configure_routes)
     })
     .bind((server_config.host, server_config.port))?
     .run()
-    .await
+    .await?;
+
+    // Persist visits counted since the last background flush
+    shutdown_redirector.flush_visits().await;
+    Ok(())
 }
@@ -0,0 +1,177 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::{self, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
+    web::{self, Bytes, BytesMut},
+    Error, HttpMessage,
+};
+use futures::{Future, StreamExt};
+use serde_json::{json, Map, Value};
+use tracing::{debug, error};
+
+use crate::auth::AdminKey;
+use crate::errors::current_correlation_id;
+use crate::services::UrlService;
+
+/// Largest request or response body kept in the audit log; larger ones are noted by size
+const MAX_AUDITED_BODY_BYTES: usize = 64 * 1024;
+
+/// Stands in for redacted values
+const REDACTED: &str = "[redacted]";
+
+/// Field names whose values never reach the audit log, matched as lowercase substrings
+const SENSITIVE_FIELDS: &[&str] = &["password", "secret", "token", "key", "authorization", "cookie", "signature"];
+
+/// Records every state-changing call that reaches a handler as an admin, with its method,
+/// path, status and redacted request and response bodies, as an `admin.request` audit entry.
+///
+/// Wraps the admin scope. Reads pass through untouched, as do calls that were not made with
+/// an admin key, which never reach an action.
+pub struct AuditMutations;
+
+impl<S, B> Transform<S, ServiceRequest> for AuditMutations
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuditMutationsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditMutationsMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct AuditMutationsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditMutationsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let url_service = req.app_data::<web::Data<UrlService>>().cloned();
+        let (true, Some(url_service)) = (is_mutation(req.method()), url_service) else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        };
+
+        let service = self.service.clone();
+        Box::pin(async move {
+            // The handler still needs the body, so it is read here and handed back
+            let mut payload = req.take_payload();
+            let mut request_body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                request_body.extend_from_slice(&chunk?);
+            }
+            let request_body = request_body.freeze();
+            req.set_payload(Payload::from(request_body.clone()));
+
+            let method = req.method().to_string();
+            let path = req.path().to_string();
+            let query = redact_query(req.query_string());
+            let res = service.call(req).await?;
+
+            let Some(actor) = res.request().extensions().get::<AdminKey>().map(|admin| admin.owner.clone()) else {
+                return Ok(res.map_into_left_body());
+            };
+            let (http_req, res) = res.into_parts();
+            let (res, response_body) = res.into_parts();
+            let response_body = body::to_bytes(response_body)
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+
+            let details = json!({
+                "method": method,
+                "query": query,
+                "status": res.status().as_u16(),
+                "request": audited_body(&request_body),
+                "response": audited_body(&response_body),
+                "correlation_id": current_correlation_id(),
+            });
+            // The action has already happened, so a failure to record it does not fail the call
+            if let Err(e) = url_service.record_admin_request(&actor, &path, details).await {
+                error!(error = %e, path = %path, actor = %actor, "Failed to record admin request in the audit log");
+            }
+
+            let res = res.set_body(BoxBody::new(response_body));
+            Ok(ServiceResponse::new(http_req, res).map_into_right_body())
+        })
+    }
+}
+
+fn is_mutation(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// A body as the audit log keeps it: JSON with sensitive fields redacted, or only its size
+/// when it is too large or not JSON
+fn audited_body(body: &Bytes) -> Value {
+    if body.is_empty() {
+        return Value::Null;
+    }
+    if body.len() > MAX_AUDITED_BODY_BYTES {
+        return json!({ "omitted_bytes": body.len() });
+    }
+    match serde_json::from_slice(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value
+        }
+        Err(_) => {
+            debug!(bytes = body.len(), "Audited body is not JSON; keeping its size only");
+            json!({ "omitted_bytes": body.len() })
+        }
+    }
+}
+
+/// Query parameters as an object with sensitive ones redacted; `null` without any
+fn redact_query(query: &str) -> Value {
+    if query.is_empty() {
+        return Value::Null;
+    }
+    let mut params: Map<String, Value> = url::form_urlencoded::parse(query.as_bytes())
+        .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+        .collect();
+    params.iter_mut().filter(|(name, _)| is_sensitive(name)).for_each(|(_, value)| *value = REDACTED.into());
+    Value::Object(params)
+}
+
+/// Replaces the values of sensitive fields at any depth
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                if is_sensitive(name) {
+                    *value = REDACTED.into();
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE_FIELDS.iter().any(|field| name.contains(field))
+}
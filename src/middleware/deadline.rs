@@ -0,0 +1,63 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error,
+};
+use futures::Future;
+use tokio::time::Instant;
+
+use crate::deadline::{with_deadline, DeadlineConfig};
+
+/// Gives each request the deadline its route class's budget or `X-Request-Timeout` allows;
+/// storage operations still running when it passes are cancelled and the request fails with
+/// 504 `deadline_exceeded`.
+///
+/// Without a [`DeadlineConfig`] in the app data, only client timeouts are applied.
+pub struct Deadline;
+
+impl<S, B> Transform<S, ServiceRequest> for Deadline
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = DeadlineMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeadlineMiddleware { service }))
+    }
+}
+
+pub struct DeadlineMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for DeadlineMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let config = req.app_data::<web::Data<DeadlineConfig>>().map(|config| *config.get_ref()).unwrap_or_default();
+        let deadline = config.time_allowed(req.request()).map(|allowed| Instant::now() + allowed);
+
+        let fut = self.service.call(req);
+        match deadline {
+            Some(deadline) => Box::pin(with_deadline(deadline, fut)),
+            None => Box::pin(fut),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::{self, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{header, Method, StatusCode},
+    web::{self, BytesMut},
+    Error, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use futures::{Future, StreamExt};
+use tracing::{debug, error};
+
+use crate::auth::{hash_key, ApiKey};
+use crate::config::Config;
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType};
+use crate::handlers::MAX_IMPORT_BYTES;
+use crate::idempotency::{self, Idempotency, Reservation, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER};
+use crate::models::IdempotentResponse;
+use crate::validation::ValidationErrors;
+
+/// Replays the kept response for a `POST` repeated with the same `Idempotency-Key`, instead
+/// of handling it again.
+///
+/// Wraps creation endpoints. Requests without the header pass through, as do anonymous ones
+/// whose address is unknown and everything when the app has no [`Idempotency`]. Server
+/// errors are not kept, so a retry after one runs the request again.
+pub struct Idempotent;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotent
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = IdempotentMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotentMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct IdempotentMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotentMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let idempotency = req.app_data::<web::Data<Idempotency>>().cloned();
+        let key = req.headers().get(IDEMPOTENCY_KEY_HEADER).map(|value| value.to_str().unwrap_or_default().to_string());
+        let (Method::POST, Some(idempotency), Some(key)) = (req.method().clone(), idempotency, key) else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        };
+
+        if !idempotency::is_valid_key(&key) {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                IDEMPOTENCY_KEY_HEADER,
+                "invalid",
+                format!("Must be 1 to {} printable ASCII characters", idempotency::MAX_IDEMPOTENCY_KEY_LENGTH),
+            );
+            let response = UrlShortenerError::from(errors).error_response();
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        // Keys are per client, so one client cannot replay another's response
+        let Some(scope) = replay_scope(req.request()) else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        };
+        let service = self.service.clone();
+        Box::pin(async move {
+            // The handler still needs the body, so it is read here and handed back
+            let mut payload = req.take_payload();
+            let mut request_body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                request_body.extend_from_slice(&chunk?);
+                // Imports are the largest bodies of the endpoints this wraps
+                if request_body.len() > MAX_IMPORT_BYTES {
+                    return Err(PayloadError::Overflow.into());
+                }
+            }
+            let request_body = request_body.freeze();
+            let fingerprint = idempotency::fingerprint(req.method().as_str(), req.path(), req.query_string(), &request_body);
+            req.set_payload(Payload::from(request_body));
+
+            let rejection = match idempotency.reserve(&scope, &key, &fingerprint).await {
+                Ok(Reservation::Reserved) => None,
+                Ok(Reservation::Replay(kept)) => {
+                    debug!(path = %req.path(), "Replaying the response to an earlier request with the same idempotency key");
+                    return Ok(req.into_response(replay(kept)).map_into_right_body());
+                }
+                Ok(Reservation::InProgress) => Some(UrlShortenerError::from(UrlShortenerErrorType::Conflict(
+                    "A request with this Idempotency-Key is still being processed".to_string(),
+                ))),
+                Ok(Reservation::Mismatch) => {
+                    let mut errors = ValidationErrors::new();
+                    errors.add(IDEMPOTENCY_KEY_HEADER, "reused", "Was already used for a different request");
+                    Some(errors.into())
+                }
+                Err(e) => Some(e),
+            };
+            if let Some(error) = rejection {
+                return Ok(req.into_response(error.error_response()).map_into_right_body());
+            }
+
+            let res = service.call(req).await;
+            let res = match res {
+                Ok(res) if !res.status().is_server_error() => res,
+                res => {
+                    if let Err(e) = idempotency.release(&scope, &key).await {
+                        error!(error = %e, "Failed to release an idempotency key after a failed request");
+                    }
+                    return res.map(ServiceResponse::map_into_left_body);
+                }
+            };
+
+            let (http_req, res) = res.into_parts();
+            let (res, response_body) = res.into_parts();
+            let response_body = body::to_bytes(response_body)
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+            let kept = IdempotentResponse {
+                status_code: res.status().as_u16() as i32,
+                content_type: res
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string),
+                body: String::from_utf8_lossy(&response_body).into_owned(),
+            };
+            // The request has been handled, so a failure to keep its response does not fail it;
+            // the key stays reserved, and repeats are told it is still being processed
+            if let Err(e) = idempotency.complete(&scope, &key, kept).await {
+                error!(error = %e, "Failed to keep the response for an idempotency key");
+            }
+
+            let res = res.set_body(BoxBody::new(response_body));
+            Ok(ServiceResponse::new(http_req, res).map_into_right_body())
+        })
+    }
+}
+
+/// The client an `Idempotency-Key` belongs to: a hash of the API key the request carries, read
+/// as the [`ApiKey`] extractor reads it, or else of the caller's address. `None` for anonymous
+/// callers whose address is unknown.
+fn replay_scope(req: &HttpRequest) -> Option<String> {
+    if let Some(key) = ApiKey::key_from_request(req) {
+        return Some(hash_key(key));
+    }
+    let trust_forwarded_for = req.app_data::<web::Data<Config>>().is_some_and(|config| config.trust_forwarded_for);
+    super::client_ip(req, trust_forwarded_for).map(|ip| format!("ip:{}", hash_key(&ip.to_string())))
+}
+
+/// A kept response as sent again, marked as a replay
+fn replay(kept: IdempotentResponse) -> HttpResponse {
+    let status = u16::try_from(kept.status_code)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+    let mut response = HttpResponse::build(status);
+    if let Some(content_type) = kept.content_type {
+        response.insert_header((header::CONTENT_TYPE, content_type));
+    }
+    response.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true")).body(kept.body)
+}
@@ -3,13 +3,82 @@ use std::pin::Pin;
 use std::time::Instant;
 
 use actix_web::{
+    body::MessageBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpMessage,
+    http::header::{HeaderName, HeaderValue},
+    web, Error, HttpMessage,
 };
 use futures::Future;
-use tracing::{info, error};
+use tracing::{field::Empty, info, error, Span};
+use tracing_actix_web::{DefaultRootSpanBuilder, RequestId, RootSpanBuilder};
 
+use crate::errors::with_correlation_id;
 use crate::logging::generate_correlation_id;
+use crate::privacy::{IpAnonymizer, IpPrivacy};
+
+/// Response header echoing the request's correlation ID
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+/// Request headers carrying client addresses, left out of logs unless IP privacy is off
+const FORWARDED_HEADERS: &[&str] = &["forwarded", "x-forwarded-for", "x-real-ip"];
+
+/// The client as the app's [`IpAnonymizer`] allows it to be logged; empty without one
+fn anonymized_client(req: &ServiceRequest) -> String {
+    super::anonymized_client(req.request()).unwrap_or_default()
+}
+
+fn loggable_headers(req: &ServiceRequest) -> String {
+    let private = req
+        .app_data::<web::Data<IpAnonymizer>>()
+        .is_some_and(|anonymizer| anonymizer.mode() != IpPrivacy::Off);
+    if !private {
+        return format!("{:?}", req.headers());
+    }
+    let mut headers = req.headers().clone();
+    for name in FORWARDED_HEADERS {
+        headers.remove(*name);
+    }
+    format!("{:?}", headers)
+}
+
+/// Root span for [`TracingLogger`](tracing_actix_web::TracingLogger) with the same fields as
+/// its default, except that the client address is anonymized like in [`RequestLogger`]
+pub struct AnonymizedRootSpan;
+
+impl RootSpanBuilder for AnonymizedRootSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let client = anonymized_client(request);
+        let request_id = request.extensions().get::<RequestId>().map(ToString::to_string).unwrap_or_default();
+        let route = request.match_pattern().unwrap_or_else(|| "default".to_string());
+        let user_agent = request
+            .headers()
+            .get("User-Agent")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let host = request.connection_info().host().to_string();
+
+        tracing::info_span!(
+            "HTTP request",
+            http.method = %request.method(),
+            http.route = %route,
+            http.host = %host,
+            http.client_ip = %client,
+            http.user_agent = %user_agent,
+            http.target = %request.uri().path_and_query().map_or("", |path| path.as_str()),
+            http.status_code = Empty,
+            otel.name = %format!("{} {}", request.method(), route),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            request_id = %request_id,
+            exception.message = Empty,
+            exception.details = Empty,
+        )
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
 
 pub struct RequestLogger;
 
@@ -51,7 +120,8 @@ where
         let start_time = Instant::now();
         let method = req.method().to_string();
         let uri = req.uri().to_string();
-        let headers = format!("{:?}", req.headers());
+        let client = anonymized_client(&req);
+        let headers = loggable_headers(&req);
 
         // Add correlation ID to request extensions
         req.extensions_mut().insert(correlation_id.clone());
@@ -59,15 +129,19 @@ where
         let fut = self.service.call(req);
 
         Box::pin(async move {
-            let result = fut.await;
+            let mut result = with_correlation_id(correlation_id.clone(), fut).await;
             let duration = start_time.elapsed();
 
-            match &result {
+            match &mut result {
                 Ok(res) => {
+                    if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+                        res.headers_mut().insert(HeaderName::from_static(CORRELATION_ID_HEADER), value);
+                    }
                     info!(
                         correlation_id = %correlation_id,
                         method = %method,
                         uri = %uri,
+                        client = %client,
                         status = %res.status().as_u16(),
                         duration_ms = %duration.as_millis(),
                         headers = %headers,
@@ -79,6 +153,7 @@ where
                         correlation_id = %correlation_id,
                         method = %method,
                         uri = %uri,
+                        client = %client,
                         error = %e,
                         duration_ms = %duration.as_millis(),
                         headers = %headers,
@@ -0,0 +1,109 @@
+use std::borrow::Cow;
+use std::future::{ready, Ready};
+use std::pin::Pin;
+
+use actix_web::{
+    body::{BodySize, EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header::{CACHE_CONTROL, SET_COOKIE}, StatusCode},
+    web, Error, HttpResponse,
+};
+use futures::Future;
+
+use crate::handlers::requested_code;
+use crate::preview::{is_social_crawler_request, LinkPreviews};
+use crate::redirect::{CachedResponse, Redirector};
+
+/// Replays recent redirect responses for the same short code without running the handler.
+///
+/// Wraps the redirect resource; responses are kept by the app's [`Redirector`] for its
+/// configured TTL, and the middleware passes every request through when that is zero.
+pub struct MicroCache;
+
+impl<S, B> Transform<S, ServiceRequest> for MicroCache
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MicroCacheMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MicroCacheMiddleware { service }))
+    }
+}
+
+pub struct MicroCacheMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for MicroCacheMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let redirector = req
+            .app_data::<web::Data<Redirector>>()
+            .filter(|redirector| redirector.caches_responses())
+            .cloned();
+        // Only the bare code is replayed; a path or query after it may change the destination
+        let bare = req.match_info().get("rest").unwrap_or_default().is_empty() && req.query_string().is_empty();
+        let short_code = requested_code(req.request()).filter(|_| bare).map(Cow::into_owned);
+
+        let (Some(redirector), Some(short_code)) = (redirector, short_code) else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        };
+
+        // Crawlers are shown a link preview instead of the redirect when previews are enabled
+        if req.app_data::<web::Data<LinkPreviews>>().is_some() && is_social_crawler_request(req.request()) {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        }
+
+        if let Some(cached) = redirector.replay(&short_code, super::anonymized_client(req.request()).as_deref()) {
+            let mut response = HttpResponse::new(cached.status);
+            *response.headers_mut() = cached.headers;
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            if is_replayable(&res) {
+                let cached = CachedResponse {
+                    status: res.status(),
+                    headers: res.headers().clone(),
+                };
+                redirector.store_response(&short_code, cached);
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+/// Only bodiless redirects that set no cookies are the same for every visitor; responses
+/// marked `no-store` are never kept
+fn is_replayable<B: MessageBody>(res: &ServiceResponse<B>) -> bool {
+    let no_store = res
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("no-store"));
+    matches!(res.status(), StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND)
+        && !no_store
+        && !res.headers().contains_key(SET_COOKIE)
+        && matches!(res.response().body().size(), BodySize::None | BodySize::Sized(0))
+}
@@ -1,3 +1,81 @@
+mod audit;
+mod deadline;
+mod idempotency;
+mod locale;
 mod logging;
+mod micro_cache;
+mod panics;
+mod slo;
+mod tarpit;
+mod throttle;
 
-pub use logging::RequestLogger; 
\ No newline at end of file
+pub use audit::AuditMutations;
+pub use deadline::Deadline;
+pub use idempotency::Idempotent;
+pub use locale::Localize;
+pub use micro_cache::MicroCache;
+pub use panics::{panics_total, CatchPanic};
+pub use slo::RecordSlo;
+pub use tarpit::Tarpit;
+pub use throttle::Throttle;
+pub use logging::{AnonymizedRootSpan, RequestLogger, CORRELATION_ID_HEADER};
+
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::http::header::{self, HeaderMap, HeaderName};
+use actix_web::{web, HttpRequest};
+
+use crate::config::Config;
+use crate::privacy::IpAnonymizer;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// The client's address: the peer, or the address the proxy in front reported when proxies are
+/// trusted
+pub(crate) fn client_ip(req: &HttpRequest, trust_forwarded_for: bool) -> Option<IpAddr> {
+    let peer = req.peer_addr().map(|peer| peer.ip());
+    if !trust_forwarded_for {
+        return peer;
+    }
+    forwarded_client(req.headers()).or(peer)
+}
+
+/// The right-most hop of `X-Forwarded-For`, else of `Forwarded`: the address our proxy saw
+/// connecting to it. Hops to its left were sent by the client, so they could be anything.
+fn forwarded_client(headers: &HeaderMap) -> Option<IpAddr> {
+    let hops = |name| {
+        headers
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+    };
+    let hop = match hops(X_FORWARDED_FOR).last() {
+        Some(hop) => *hop,
+        None => {
+            let elements = hops(header::FORWARDED);
+            let element = elements.last()?;
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                name.eq_ignore_ascii_case("for").then_some(value)
+            })?
+        }
+    };
+    let hop = hop.trim().trim_matches('"');
+    hop.parse()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|socket| socket.ip()))
+        .or_else(|| hop.strip_prefix('[')?.split_once(']')?.0.parse().ok())
+}
+
+/// The client as the app's [`IpAnonymizer`] allows it to be recorded; `None` without one
+pub(crate) fn anonymized_client(req: &HttpRequest) -> Option<String> {
+    let trust_forwarded_for = req.app_data::<web::Data<Config>>().is_some_and(|config| config.trust_forwarded_for);
+    req.app_data::<web::Data<IpAnonymizer>>()
+        .zip(client_ip(req, trust_forwarded_for))
+        .map(|(anonymizer, ip)| anonymizer.anonymize(ip))
+}
+
+#[cfg(test)]
+mod tests;
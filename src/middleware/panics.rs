@@ -0,0 +1,84 @@
+use std::future::{ready, Ready};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderName, HeaderValue},
+    Error, ResponseError,
+};
+use futures::{Future, FutureExt};
+
+use super::CORRELATION_ID_HEADER;
+use crate::errors::{current_correlation_id, UrlShortenerError, UrlShortenerErrorType};
+
+/// Requests answered with a 500 because their handler panicked, since startup
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+pub fn panics_total() -> u64 {
+    PANICS.load(Ordering::Relaxed)
+}
+
+/// Answers requests whose handler panicked with the standard `internal_error` response, instead
+/// of dropping the connection. The panic itself is logged by the panic hook installed with the
+/// logging system, with its backtrace and the request's correlation ID.
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CatchPanicMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware { service }))
+    }
+}
+
+pub struct CatchPanicMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = match panic::catch_unwind(AssertUnwindSafe(|| self.service.call(req))) {
+            Ok(fut) => fut,
+            Err(_) => return Box::pin(ready(Err(panicked()))),
+        };
+
+        Box::pin(async move { AssertUnwindSafe(fut).catch_unwind().await.unwrap_or_else(|_| Err(panicked())) })
+    }
+}
+
+/// The request is gone with the panic, so the response goes out as an error carrying it. It is
+/// built right away, while the request's correlation ID and language are still known.
+fn panicked() -> Error {
+    PANICS.fetch_add(1, Ordering::Relaxed);
+    let error = UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+        "the request could not be completed".to_string(),
+    ));
+    let mut response = error.error_response();
+    if let Some(value) = current_correlation_id().and_then(|id| HeaderValue::from_str(&id).ok()) {
+        response.headers_mut().insert(HeaderName::from_static(CORRELATION_ID_HEADER), value);
+    }
+    InternalError::from_response(error, response).into()
+}
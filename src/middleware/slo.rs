@@ -0,0 +1,72 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error,
+};
+use futures::Future;
+use tokio::time::Instant;
+
+use crate::slo::SloCounters;
+use crate::throttle::RouteClass;
+
+/// Counts each request against its route class's latency objective in the app's
+/// [`SloCounters`], timed until the response head is ready.
+///
+/// Requests pass through uncounted when no counters are configured, and health checks are
+/// never counted.
+pub struct RecordSlo;
+
+impl<S, B> Transform<S, ServiceRequest> for RecordSlo
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RecordSloMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RecordSloMiddleware { service }))
+    }
+}
+
+pub struct RecordSloMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RecordSloMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let counters = req.app_data::<web::Data<SloCounters>>().cloned();
+        let class = RouteClass::of(req.method(), req.path());
+        let started = Instant::now();
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            if let Some((counters, class)) = counters.zip(class) {
+                let status = match &res {
+                    Ok(res) => res.status(),
+                    Err(error) => error.as_response_error().status_code(),
+                };
+                counters.record(class, status, started.elapsed());
+            }
+            res
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web, Error, HttpResponse, ResponseError,
+};
+use futures::Future;
+
+use super::client_ip;
+use crate::abuse::{AbuseGuard, Verdict};
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType};
+
+/// Applies the app's [`AbuseGuard`]: delays or refuses requests from penalized clients, and
+/// counts every response towards the client's error rate.
+///
+/// Requests pass through untouched when no guard is configured.
+pub struct Tarpit;
+
+impl<S, B> Transform<S, ServiceRequest> for Tarpit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = TarpitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TarpitMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct TarpitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for TarpitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let guard = req.app_data::<web::Data<AbuseGuard>>().cloned();
+        let client = guard.as_ref().and_then(|guard| client_ip(req.request(), guard.trusts_forwarded_for()));
+        let (Some(guard), Some(ip)) = (guard, client) else {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) });
+        };
+
+        let delay = match guard.check(ip) {
+            Verdict::Allow => None,
+            Verdict::Delay(delay) => Some(delay),
+            Verdict::Block { retry_after } => {
+                let error = UrlShortenerError::from(UrlShortenerErrorType::RateLimitExceeded);
+                let mut response: HttpResponse = error.error_response();
+                // The penalty's remaining time is more useful than the generic retry delay
+                if let Ok(value) = header::HeaderValue::from_str(&retry_after.as_secs().max(1).to_string()) {
+                    response.headers_mut().insert(header::RETRY_AFTER, value);
+                }
+                return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+            }
+        };
+
+        let service = self.service.clone();
+        Box::pin(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            let res = service.call(req).await?;
+            guard.record(ip, res.status());
+            Ok(res.map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,29 @@
+use super::*;
+use actix_web::test::TestRequest;
+
+fn client(headers: &[(&str, &str)], trust_forwarded_for: bool) -> Option<IpAddr> {
+    let mut req = TestRequest::default().peer_addr("192.0.2.1:4000".parse().unwrap());
+    for header in headers {
+        req = req.append_header(*header);
+    }
+    client_ip(&req.to_http_request(), trust_forwarded_for)
+}
+
+#[test]
+fn test_client_ip_is_the_hop_the_proxy_reported() {
+    let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+
+    // Without trusted proxies the headers are ignored
+    assert_eq!(client(&[("X-Forwarded-For", "203.0.113.7")], false), ip("192.0.2.1"));
+    assert_eq!(client(&[], true), ip("192.0.2.1"));
+
+    // Whatever the client put in front of the proxy's entry is ignored
+    assert_eq!(client(&[("X-Forwarded-For", "10.9.9.9, 203.0.113.7")], true), ip("203.0.113.7"));
+    assert_eq!(client(&[("X-Forwarded-For", "10.9.9.9"), ("X-Forwarded-For", "203.0.113.7")], true), ip("203.0.113.7"));
+    assert_eq!(client(&[("X-Forwarded-For", "1.1.1.1, [2001:db8::7]:4711")], true), ip("2001:db8::7"));
+    assert_eq!(client(&[("Forwarded", "for=10.9.9.9, for=\"203.0.113.7:4711\";proto=https")], true), ip("203.0.113.7"));
+    assert_eq!(client(&[("Forwarded", "proto=https;for=\"[2001:db8::7]\"")], true), ip("2001:db8::7"));
+
+    // An unreadable hop falls back to the peer
+    assert_eq!(client(&[("X-Forwarded-For", "203.0.113.7, unknown")], true), ip("192.0.2.1"));
+}
@@ -0,0 +1,106 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use actix_web::{
+    body::{BodySize, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web::{self, Bytes},
+    Error, HttpResponse, ResponseError,
+};
+use futures::Future;
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::debug;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType};
+use crate::throttle::{RouteClass, RouteThrottle};
+
+/// Applies the app's [`RouteThrottle`]: requests beyond their route class's budget are
+/// answered with 429 and `Retry-After: 1`.
+///
+/// Requests pass through untouched when no throttle is configured.
+pub struct Throttle;
+
+impl<S, B> Transform<S, ServiceRequest> for Throttle
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ThrottleMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ThrottleMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct ThrottleMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ThrottleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let throttle = req.app_data::<web::Data<RouteThrottle>>();
+        let class = RouteClass::of(req.method(), req.path());
+        let admitted = throttle.zip(class).map(|(throttle, class)| (class, throttle.admit(class)));
+
+        let slot = match admitted {
+            None | Some((_, Ok(None))) => None,
+            Some((_, Ok(Some(slot)))) => Some(slot),
+            Some((class, Err(rejection))) => {
+                debug!(class = %class, ?rejection, "Request over its route class budget");
+                let error = UrlShortenerError::from(UrlShortenerErrorType::RateLimitExceeded);
+                let mut response: HttpResponse = error.error_response();
+                response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from_static("1"));
+                return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+            }
+        };
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            Ok(match slot {
+                None => res.map_into_left_body(),
+                // Streamed responses keep working after the handler returns
+                Some(slot) => res
+                    .map_body(|_, body| BoxBody::new(SlotBody { body: BoxBody::new(body), _slot: slot }))
+                    .map_into_right_body(),
+            })
+        })
+    }
+}
+
+/// A response body that holds its request's slot until it has been sent
+struct SlotBody {
+    body: BoxBody,
+    _slot: OwnedSemaphorePermit,
+}
+
+impl MessageBody for SlotBody {
+    type Error = <BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Pin::new(&mut self.get_mut().body).poll_next(cx)
+    }
+}
@@ -1,5 +1,10 @@
-use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 
 /// Represents a shortened URL in the system
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -14,26 +19,569 @@ pub struct ShortenedUrl {
     pub created_at: DateTime<Utc>,
     /// Number of times the URL has been visited
     pub visits: i64,
+    /// When a visit was last counted; unset until the first one
+    #[serde(default)]
+    pub last_visited_at: Option<DateTime<Utc>>,
+    /// Owner of the API key that created the URL, if any
+    pub owner: Option<String>,
+    /// Human-readable name of the link
+    pub title: Option<String>,
+    /// Free-form description, e.g. where the link is used
+    pub notes: Option<String>,
+    /// Person or team responsible for the link
+    pub created_by: Option<String>,
+    /// Collection the link is organized in, if any
+    pub collection_id: Option<i64>,
+    /// Visit count the owner wants to be notified about
+    pub click_goal: Option<i64>,
+    /// When the click goal was reached and notified; unset while it is pending
+    pub goal_reached_at: Option<DateTime<Utc>>,
+    /// Append any path and query after the short code to the destination
+    #[serde(default)]
+    pub pass_through: bool,
+    /// Content type the destination reported when the link was created, if it was checked
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// When an admin disabled the link; disabled links no longer redirect
+    #[serde(default)]
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// Only redirect requests carrying a valid, unexpired signature
+    #[serde(default)]
+    pub signed_only: bool,
+    /// Owner's analytics snippet, served on a page that forwards visitors instead of redirecting
+    #[serde(default)]
+    pub tracking_snippet: Option<String>,
+    /// Extra response headers sent with redirects, by lowercase name
+    #[serde(default)]
+    pub redirect_headers: Option<Json<RedirectHeaders>>,
+    /// Redirects ask browsers not to send the destination a referrer
+    #[serde(default)]
+    pub hide_referrer: bool,
+    /// Revision of the editable fields, bumped by every metadata update
+    #[serde(default = "first_version")]
+    pub version: i64,
 }
 
-/// Request payload for creating a new shortened URL
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateUrlRequest {
-    pub original_url: String,
+/// An unsaved link created now, with no destination or code yet and every option off; set
+/// the fields that matter with struct update syntax
+impl Default for ShortenedUrl {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            original_url: String::new(),
+            short_url: String::new(),
+            created_at: Utc::now(),
+            visits: 0,
+            last_visited_at: None,
+            owner: None,
+            title: None,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            click_goal: None,
+            goal_reached_at: None,
+            pass_through: false,
+            content_type: None,
+            disabled_at: None,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            version: first_version(),
+        }
+    }
 }
 
-/// Response payload for a created shortened URL
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateUrlResponse {
-    pub short_url: String,
-    pub original_url: String,
+/// Response headers a link adds to its redirects, by lowercase name
+pub type RedirectHeaders = BTreeMap<String, String>;
+
+/// Version of a link that was never updated
+pub fn first_version() -> i64 {
+    1
 }
 
-/// Response payload for URL statistics
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UrlStats {
-    pub short_url: String,
-    pub original_url: String,
+/// Sample rate of visit events recorded without sampling, or before it existed
+pub fn unsampled() -> i32 {
+    1
+}
+
+/// Changes to a link's descriptive fields: `None` leaves a field as is, `Some(None)` clears it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataUpdate {
+    pub title: Option<Option<String>>,
+    pub notes: Option<Option<String>>,
+    pub created_by: Option<Option<String>>,
+    pub collection_id: Option<Option<i64>>,
+    /// Setting a goal, or clearing it, also resets its notification
+    pub click_goal: Option<Option<i64>>,
+    pub pass_through: Option<bool>,
+    pub signed_only: Option<bool>,
+    pub tracking_snippet: Option<Option<String>>,
+    pub redirect_headers: Option<Option<RedirectHeaders>>,
+    pub hide_referrer: Option<bool>,
+    /// Only update the link while it is at this version; otherwise fail with `PreconditionFailed`
+    pub expected_version: Option<i64>,
+}
+
+/// A manual change to a link's visit count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitChange {
+    /// Sets the count back to zero
+    Reset,
+    /// Adds to the count, or subtracts for negative values; the count never drops below zero
+    Adjust(i64),
+}
+
+/// A link's visit count before and after a manual change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisitCounts {
+    pub previous: i64,
+    pub visits: i64,
+}
+
+/// What happens to a link's visit count when it is given a new code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsOnRegenerate {
+    /// The link keeps its visits under the new code
+    #[default]
+    Keep,
+    /// The visits so far stay with the old code and the new one starts from zero
+    Fork,
+}
+
+/// A code a link answered to before it was given a new one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RetiredCode {
+    pub short_code: String,
+    /// Visits that stayed with the code when the link's stats were forked
+    pub visits: i64,
+    pub retired_at: DateTime<Utc>,
+    /// Until when the code still redirects to the link, as a deprecated alias
+    pub redirects_until: DateTime<Utc>,
+}
+
+/// An extra short code of a link
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LinkAlias {
+    pub short_code: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A link moved to a new code, and the code it left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegeneratedLink {
+    pub link: ShortenedUrl,
+    pub retired: RetiredCode,
+}
+
+/// A named group of links belonging to one owner
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Collection {
+    pub id: i64,
+    pub owner: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A collection with aggregates over its links
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CollectionStats {
+    pub id: i64,
+    pub owner: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Number of links in the collection
+    pub links: i64,
+    /// Visits across all links in the collection
     pub visits: i64,
+}
+
+/// A named group of an owner's links whose visits are reported together; unlike a
+/// collection, a link may be in several campaigns
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Campaign {
+    pub id: i64,
+    pub owner: String,
+    pub name: String,
     pub created_at: DateTime<Utc>,
-} 
\ No newline at end of file
+}
+
+/// Visits across a campaign's links
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CampaignStats {
+    #[serde(flatten)]
+    pub campaign: Campaign,
+    /// Number of links in the campaign
+    pub links: i64,
+    /// All-time visits across the links
+    pub visits: i64,
+    /// Recorded visits since the start of the reported period
+    pub period_visits: i64,
+    /// Distinct visitors since the start of the reported period
+    pub uniques: i64,
+    /// Visits per day of the reported period, oldest first; days without visits are left out
+    pub daily: Vec<DailyVisits>,
+}
+
+/// Visits on one day, in UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DailyVisits {
+    pub date: NaiveDate,
+    pub visits: i64,
+    pub uniques: i64,
+}
+
+/// One owner's usage on one day, in UTC, as metered for billing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UsageRecord {
+    pub day: NaiveDate,
+    pub owner: String,
+    /// Links created that day
+    pub creations: i64,
+    /// Redirects recorded as visit events that day
+    pub redirects: i64,
+    /// Links stored at the end of the day, or at the last roll-up for the current day
+    pub stored_links: i64,
+}
+
+/// One redirect, kept for analytics
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisitEvent {
+    /// The code the link answered to at the time
+    pub short_code: String,
+    pub visited_at: DateTime<Utc>,
+    /// The visitor's address as the IP privacy mode allows it to be kept
+    pub visitor: Option<String>,
+    /// Attribution tag from the short link's `src` parameter
+    #[serde(default)]
+    pub source: Option<String>,
+    /// One in this many visits was kept as an event, so the event stands for that many
+    #[serde(default = "unsampled")]
+    pub sample_rate: i32,
+}
+
+/// Visits of one link on one day, in UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LinkDailyVisits {
+    pub short_code: String,
+    pub date: NaiveDate,
+    pub visits: i64,
+}
+
+/// Visits of a link with one attribution tag; `None` for visits without one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SourceVisits {
+    pub source: Option<String>,
+    pub visits: i64,
+}
+
+/// A stored visit event, as exported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RecordedVisit {
+    pub id: i64,
+    /// The code the link answers to now
+    pub short_code: String,
+    pub visited_at: DateTime<Utc>,
+    pub visitor: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default = "unsampled")]
+    pub sample_rate: i32,
+}
+
+/// When a link has been visited, from its recorded visit events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisitTimeline {
+    pub first_visited_at: Option<DateTime<Utc>>,
+    pub last_visited_at: Option<DateTime<Utc>>,
+    pub visits_last_24h: i64,
+    pub visits_last_7d: i64,
+}
+
+/// Visits by day of the week and hour of the day, in UTC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisitHeatmap {
+    /// Visits per hour, one row per day starting with Monday
+    pub visits: [[i64; 24]; 7],
+}
+
+/// A self-service API key; the key itself is only known to its holder
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StoredApiKey {
+    pub id: i64,
+    pub owner: String,
+    /// Label chosen by the owner, e.g. the integration using the key
+    pub name: Option<String>,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A signed-in dashboard session
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Session {
+    /// Hash of the session cookie; the cookie itself is never stored
+    pub id: String,
+    pub owner: String,
+    /// Token dashboard forms must echo back
+    pub csrf_token: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// An active API key as kept in storage, for moving it to another instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportedApiKey {
+    /// ID in the instance the key was read from
+    pub id: i64,
+    pub owner: String,
+    pub name: Option<String>,
+    pub key_hash: String,
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A destination domain banned by an operator, covering its subdomains too
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BlockedDomain {
+    /// Lowercase ASCII form, e.g. `xn--80ak6aa92e.com`
+    pub domain: String,
+    pub reason: Option<String>,
+    /// Owner of the admin key that banned it
+    pub blocked_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The outcome of banning a domain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainBan {
+    #[serde(flatten)]
+    pub blocked: BlockedDomain,
+    /// Short codes of the links disabled by the ban
+    pub disabled: Vec<String>,
+}
+
+/// An operator action recorded in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Owner of the admin key that performed the action
+    pub actor: String,
+    /// What was done, e.g. `visits.reset`
+    pub action: String,
+    /// What it was done to, e.g. a short code
+    pub target: Option<String>,
+    /// Action-specific context
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An audit log entry to be recorded
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: serde_json::Value,
+}
+
+/// One attempt to deliver a webhook event
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    /// Name of the configured webhook, e.g. `goals`
+    pub webhook: String,
+    /// Event type, e.g. `link.goal_reached`
+    pub event: String,
+    /// The event as it was sent
+    pub payload: serde_json::Value,
+    /// HTTP status of the response; `None` when no response was received
+    pub status_code: Option<i32>,
+    pub latency_ms: i64,
+    /// Start of the response body, or why the request failed
+    pub response: Option<String>,
+    /// Whether the receiver answered with a 2xx status
+    pub delivered: bool,
+    /// The delivery this one replayed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<i64>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// A webhook delivery attempt to be recorded
+#[derive(Debug, Clone)]
+pub struct NewWebhookDelivery {
+    pub webhook: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status_code: Option<i32>,
+    pub latency_ms: i64,
+    pub response: Option<String>,
+    pub delivered: bool,
+    pub replay_of: Option<i64>,
+}
+
+/// A response kept to be replayed for requests repeating its `Idempotency-Key`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotentResponse {
+    pub status_code: i32,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// An `Idempotency-Key` in use
+#[derive(Debug, Clone)]
+pub struct IdempotencyRecord {
+    /// Hash of the request the key was first used with
+    pub fingerprint: String,
+    /// The response to that request; `None` while it is still being handled
+    pub response: Option<IdempotentResponse>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where a list paged newest first continues: the creation time and ID of the last item of the
+/// previous page. Clients only see it as an opaque `cursor` token, see [`PageCursor::encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    /// Kept to the microsecond, as stored
+    pub created_at: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl PageCursor {
+    /// Cursor continuing after an item created at `created_at` with ID `id`
+    pub fn after(created_at: DateTime<Utc>, id: i64) -> Self {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap_or(created_at);
+        Self { created_at, id }
+    }
+
+    /// The cursor as a base64url token
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}.{}", self.created_at.timestamp_micros(), self.id))
+    }
+
+    /// Reads a token made by [`PageCursor::encode`]; `None` if it is not one
+    pub fn decode(token: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let (micros, id) = decoded.split_once('.')?;
+        let created_at = DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+        Some(Self { created_at, id: id.parse().ok()? })
+    }
+}
+
+/// Order of an owner's link list: newest, most visited or most recently visited first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlSort {
+    #[default]
+    CreatedAt,
+    Visits,
+    /// Links never visited come last
+    LastVisited,
+}
+
+impl UrlSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CreatedAt => "created_at",
+            Self::Visits => "visits",
+            Self::LastVisited => "last_visited",
+        }
+    }
+}
+
+/// Whether a link still redirects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    Active,
+    /// Disabled by an admin
+    Disabled,
+}
+
+/// Which of an owner's links to list; unset filters match every link
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlFilter {
+    /// Only links created after this time
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only links whose destination is on exactly this host, lowercase and in ASCII form
+    pub domain: Option<String>,
+    pub min_visits: Option<i64>,
+    pub status: Option<LinkStatus>,
+}
+
+/// Where a list of an owner's links in `sort` order continues: the sort key and ID of the last
+/// link of the previous page. Clients only see it as an opaque token, see [`UrlCursor::encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UrlCursor {
+    pub sort: UrlSort,
+    /// The visit count, or the creation or last visit time in microseconds; 0 for a link never
+    /// visited
+    pub key: i64,
+    pub id: i64,
+}
+
+impl UrlCursor {
+    /// Cursor continuing after `url` in `sort` order
+    pub fn after(sort: UrlSort, url: &ShortenedUrl) -> Self {
+        let key = match sort {
+            UrlSort::CreatedAt => url.created_at.timestamp_micros(),
+            UrlSort::Visits => url.visits,
+            UrlSort::LastVisited => url.last_visited_at.map_or(0, |at| at.timestamp_micros()),
+        };
+        Self { sort, key, id: url.id }
+    }
+
+    /// The key of a sort by time, as a time
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_micros(self.key).unwrap_or_default()
+    }
+
+    /// The cursor as a base64url token
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}.{}.{}", self.sort.as_str(), self.key, self.id))
+    }
+
+    /// Reads a token made by [`UrlCursor::encode`]; `None` if it is not one
+    pub fn decode(token: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let mut parts = decoded.split('.');
+        let sort = match parts.next()? {
+            "created_at" => UrlSort::CreatedAt,
+            "visits" => UrlSort::Visits,
+            "last_visited" => UrlSort::LastVisited,
+            _ => return None,
+        };
+        let cursor = Self { sort, key: parts.next()?.parse().ok()?, id: parts.next()?.parse().ok()? };
+        parts.next().is_none().then_some(cursor)
+    }
+}
+
+/// One page of a list, with the cursor of the next page if there is one
+#[derive(Debug, Clone)]
+pub struct Page<T, C = PageCursor> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<C>,
+}
+
+impl<T, C> Page<T, C> {
+    /// Cuts a page out of up to `limit + 1` items fetched after the previous page's cursor; the
+    /// extra item only tells whether another page follows
+    pub fn from_fetched(mut items: Vec<T>, limit: i64, cursor: impl Fn(&T) -> C) -> Self {
+        let limit = limit.max(0) as usize;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(cursor)
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U, C> {
+        Page { items: self.items.into_iter().map(f).collect(), next_cursor: self.next_cursor }
+    }
+}
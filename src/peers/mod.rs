@@ -0,0 +1,264 @@
+//! Peer cache for multi-node deployments.
+//!
+//! Every node knows the same static list of peers and places them on a consistent-hash ring,
+//! so each short code has one owner. On a redirect cache miss for a code owned by another
+//! node, the destination is asked from that node's cache over an internal HTTP path before
+//! falling back to the database. A popular code is then read from the database by its owner
+//! only, instead of by every node, and adding or removing a node moves few codes.
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::auth::session::constant_time_eq;
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::models::RedirectHeaders;
+use crate::redirect::{Redirector, Target};
+
+/// Header carrying the shared secret on lookups between peers
+pub const PEER_SECRET_HEADER: &str = "X-Peer-Secret";
+
+/// Path peers answer lookups on, followed by the short code
+pub const LOOKUP_PATH: &str = "/internal/peers/lookup";
+
+/// Points each node gets on the ring, to spread codes evenly
+const VIRTUAL_NODES: usize = 64;
+
+/// Configuration for the peer cache
+#[derive(Clone, Debug)]
+pub struct PeerConfig {
+    /// Base URL other nodes reach this one at; must appear in `peers` as written there
+    pub self_url: String,
+    /// Base URLs of every node in the cluster, this one included
+    pub peers: Vec<String>,
+    /// Secret every node sends and expects on lookups
+    pub secret: String,
+    /// How long to wait for a peer before reading from the database instead
+    pub timeout: Duration,
+}
+
+impl PeerConfig {
+    /// Loads the peer configuration from the environment.
+    ///
+    /// Returns `None` unless `PEER_SELF_URL`, `PEER_URLS` and `PEER_SECRET` are all set, which
+    /// disables the peer cache.
+    pub fn from_env() -> Option<Self> {
+        let self_url = env::var("PEER_SELF_URL").ok()?;
+        let peers = env::var("PEER_URLS").ok()?;
+        let secret = env::var("PEER_SECRET").ok().filter(|secret| !secret.is_empty())?;
+
+        Some(Self {
+            self_url: normalize(&self_url),
+            peers: peers.split(',').map(normalize).filter(|peer| !peer.is_empty()).collect(),
+            secret,
+            timeout: Duration::from_millis(
+                env::var("PEER_TIMEOUT_MS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(200),
+            ),
+        })
+    }
+}
+
+fn normalize(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+fn hash(key: &str) -> u64 {
+    let digest = Sha256::digest(key.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 digests are 32 bytes"))
+}
+
+/// Consistent-hash ring assigning short codes to nodes. The hash is the same on every node
+/// and across builds, so all nodes agree on owners as long as they share the peer list.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    nodes: Vec<String>,
+    /// Ring positions with the index of the node they belong to, sorted by position
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    pub fn new(nodes: &[String]) -> Self {
+        let mut nodes = nodes.to_vec();
+        nodes.sort();
+        nodes.dedup();
+        let mut points: Vec<(u64, usize)> = nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, node)| (0..VIRTUAL_NODES).map(move |i| (hash(&format!("{}#{}", node, i)), index)))
+            .collect();
+        points.sort_unstable();
+        Self { nodes, points }
+    }
+
+    /// The node owning a short code; `None` on an empty ring
+    pub fn owner(&self, short_code: &str) -> Option<&str> {
+        let key = hash(short_code);
+        let at = self.points.partition_point(|(point, _)| *point < key);
+        let (_, index) = self.points.get(at).or_else(|| self.points.first())?;
+        Some(&self.nodes[*index])
+    }
+
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+}
+
+/// A destination as one node hands it to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerTarget {
+    /// Code the link answers to now; differs from the requested one for a retired code
+    pub short_code: String,
+    pub url: String,
+    pub pass_through: bool,
+    pub signed_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracking_snippet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_headers: Option<RedirectHeaders>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hide_referrer: bool,
+}
+
+impl From<PeerTarget> for Target {
+    fn from(target: PeerTarget) -> Self {
+        Self {
+            url: target.url.into(),
+            pass_through: target.pass_through,
+            signed_only: target.signed_only,
+            tracking_snippet: target.tracking_snippet.map(Into::into),
+            redirect_headers: target.redirect_headers.map(Arc::new),
+            hide_referrer: target.hide_referrer,
+        }
+    }
+}
+
+/// Answer of a peer to a lookup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerLookup {
+    Found(PeerTarget),
+    /// The owner has no such code
+    Missing,
+    /// The owner could not be asked or could not answer; read from the database instead
+    Unavailable,
+}
+
+/// Asks the owners of short codes for their destinations
+#[derive(Debug, Clone)]
+pub struct PeerCache {
+    ring: HashRing,
+    self_url: String,
+    secret: String,
+    timeout: Duration,
+    http: HttpClient,
+}
+
+impl PeerCache {
+    pub fn new(config: PeerConfig, http: HttpClient) -> Self {
+        if !config.peers.contains(&config.self_url) {
+            warn!(self_url = %config.self_url, "PEER_SELF_URL is not in PEER_URLS; every code is owned by a peer");
+        }
+        Self {
+            ring: HashRing::new(&config.peers),
+            self_url: config.self_url,
+            secret: config.secret,
+            timeout: http.timeout_within(config.timeout),
+            http,
+        }
+    }
+
+    pub fn ring(&self) -> &HashRing {
+        &self.ring
+    }
+
+    /// The peer owning a short code; `None` when this node owns it
+    pub fn owner(&self, short_code: &str) -> Option<&str> {
+        self.ring.owner(short_code).filter(|owner| *owner != self.self_url)
+    }
+
+    /// Asks the owner of a short code for its destination; `Unavailable` when this node owns it
+    pub async fn lookup(&self, short_code: &str) -> PeerLookup {
+        let Some(owner) = self.owner(short_code) else {
+            return PeerLookup::Unavailable;
+        };
+        // Segments are encoded apart, so namespaced codes keep their slashes and match the route
+        let code: Vec<String> = short_code
+            .split('/')
+            .map(|segment| url::form_urlencoded::byte_serialize(segment.as_bytes()).collect())
+            .collect();
+        let code = code.join("/");
+        let request = self
+            .http
+            .get(format!("{}{}/{}", owner, LOOKUP_PATH, code))
+            .header(PEER_SECRET_HEADER, &self.secret)
+            .timeout(self.timeout);
+
+        let response = match self.http.send("peer_cache", request).await {
+            Ok(response) => response,
+            Err(e) => {
+                debug!(error = %e, peer = %owner, "Peer lookup failed");
+                return PeerLookup::Unavailable;
+            }
+        };
+        match response.status() {
+            StatusCode::OK => match response.json().await {
+                Ok(target) => PeerLookup::Found(target),
+                Err(e) => {
+                    warn!(error = %e, peer = %owner, "Peer sent a malformed lookup answer");
+                    PeerLookup::Unavailable
+                }
+            },
+            StatusCode::NOT_FOUND => PeerLookup::Missing,
+            status => {
+                debug!(status = %status, peer = %owner, "Peer could not answer lookup");
+                PeerLookup::Unavailable
+            }
+        }
+    }
+
+    fn is_authorized(&self, req: &HttpRequest) -> bool {
+        req.headers()
+            .get(PEER_SECRET_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| constant_time_eq(v.as_bytes(), self.secret.as_bytes()))
+    }
+}
+
+/// Answers a peer's lookup from this node's cache or database, without counting a visit
+pub async fn lookup(
+    req: HttpRequest,
+    path: web::Path<String>,
+    redirector: web::Data<Redirector>,
+) -> UrlShortenerResult<HttpResponse> {
+    let peers = redirector
+        .peers()
+        .ok_or_else(|| UrlShortenerError::from(UrlShortenerErrorType::NotFound))?;
+    if !peers.is_authorized(&req) {
+        warn!("Rejected peer lookup with invalid secret");
+        return Err(UrlShortenerErrorType::Unauthorized.into());
+    }
+
+    let short_code = path.into_inner();
+    let (target, current) = redirector.local_target(&short_code).await?;
+    Ok(HttpResponse::Ok().json(PeerTarget {
+        short_code: current.into_owned(),
+        url: target.url.to_string(),
+        pass_through: target.pass_through,
+        signed_only: target.signed_only,
+        tracking_snippet: target.tracking_snippet.as_deref().map(str::to_string),
+        redirect_headers: target.redirect_headers.as_deref().cloned(),
+        hide_referrer: target.hide_referrer,
+    }))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+use actix_web::{App, HttpServer};
+
+use super::*;
+use crate::models::ShortenedUrl;
+use crate::redirect::RedirectConfig;
+use crate::routes::CODE_PATTERN;
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+const NODE_B: &str = "http://node-b.invalid:8080";
+
+fn config(self_url: &str, peers: &[&str]) -> PeerConfig {
+    PeerConfig {
+        self_url: self_url.to_string(),
+        peers: peers.iter().map(|peer| peer.to_string()).collect(),
+        secret: "cluster-secret".to_string(),
+        timeout: Duration::from_secs(2),
+    }
+}
+
+async fn storage_with(codes: &[&str]) -> Arc<MemoryStorage> {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    for code in codes {
+        storage
+            .save_url(ShortenedUrl {
+                original_url: format!("https://example.com/{}", code),
+                short_url: code.to_string(),
+                ..ShortenedUrl::default()
+            })
+            .await
+            .unwrap();
+    }
+    storage
+}
+
+/// First `code{n}` the ring assigns to `node`
+fn code_owned_by(ring: &HashRing, node: &str) -> String {
+    (0..).map(|i| format!("code{}", i)).find(|code| ring.owner(code) == Some(node)).unwrap()
+}
+
+#[test]
+fn test_ring_spreads_codes_and_moves_few_on_change() {
+    let nodes: Vec<String> = ["http://a:1", "http://b:1", "http://c:1"].map(String::from).to_vec();
+    let ring = HashRing::new(&nodes);
+    let codes: Vec<String> = (0..3000).map(|i| format!("code{}", i)).collect();
+
+    let mut counts = HashMap::new();
+    for code in &codes {
+        *counts.entry(ring.owner(code).unwrap()).or_insert(0) += 1;
+    }
+    assert_eq!(counts.len(), 3);
+    assert!(counts.values().all(|&count| count > 600), "{:?}", counts);
+
+    // Order and duplicates in the peer list do not matter
+    let reordered = HashRing::new(&[nodes[2].clone(), nodes[0].clone(), nodes[1].clone(), nodes[0].clone()]);
+    assert!(codes.iter().all(|code| ring.owner(code) == reordered.owner(code)));
+
+    // Removing a node only moves the codes it owned
+    let shrunk = HashRing::new(&nodes[..2]);
+    for code in &codes {
+        if ring.owner(code) != Some("http://c:1") {
+            assert_eq!(ring.owner(code), shrunk.owner(code));
+        }
+    }
+    assert_eq!(HashRing::new(&[]).owner("abc"), None);
+}
+
+#[test]
+fn test_codes_owned_by_this_node_are_not_forwarded() {
+    let peers = PeerCache::new(config(NODE_B, &["http://node-a:8080", NODE_B]), HttpClient::default());
+    let own = code_owned_by(peers.ring(), NODE_B);
+    let other = code_owned_by(peers.ring(), "http://node-a:8080");
+
+    assert_eq!(peers.owner(&own), None);
+    assert_eq!(peers.owner(&other), Some("http://node-a:8080"));
+}
+
+#[actix_rt::test]
+async fn test_lookup_needs_the_cluster_secret() {
+    let storage = storage_with(&["abc", "go/team/runbook"]).await;
+    let peers = PeerCache::new(config(NODE_B, &[NODE_B]), HttpClient::default());
+    let redirector = web::Data::new(Redirector::new(storage, RedirectConfig::default()).with_peers(peers));
+    let app = init_service(
+        App::new()
+            .app_data(redirector)
+            .route(&format!("{}/{}", LOOKUP_PATH, CODE_PATTERN), web::get().to(lookup)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri(&format!("{}/abc", LOOKUP_PATH))
+        .insert_header((PEER_SECRET_HEADER, "wrong"))
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 401);
+    let req = TestRequest::get()
+        .uri(&format!("{}/abc", LOOKUP_PATH))
+        .insert_header((PEER_SECRET_HEADER, "cluster-secre"))
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 401);
+
+    let req = TestRequest::get()
+        .uri(&format!("{}/abc", LOOKUP_PATH))
+        .insert_header((PEER_SECRET_HEADER, "cluster-secret"))
+        .to_request();
+    let found: PeerTarget = call_and_read_body_json(&app, req).await;
+    assert_eq!(found.url, "https://example.com/abc");
+    assert_eq!(found.short_code, "abc");
+
+    // Keywords span several segments
+    let req = TestRequest::get()
+        .uri(&format!("{}/go/team/runbook", LOOKUP_PATH))
+        .insert_header((PEER_SECRET_HEADER, "cluster-secret"))
+        .to_request();
+    let found: PeerTarget = call_and_read_body_json(&app, req).await;
+    assert_eq!(found.short_code, "go/team/runbook");
+}
+
+#[actix_rt::test]
+async fn test_cache_misses_are_answered_by_the_owning_peer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let node_a = format!("http://{}", listener.local_addr().unwrap());
+    let cluster = [node_a.as_str(), NODE_B];
+    let ring = HashRing::new(&cluster.map(String::from));
+    let remote = code_owned_by(&ring, &node_a);
+    let local = code_owned_by(&ring, NODE_B);
+    let namespaced = (0..).map(|i| format!("t/code{}", i)).find(|code| ring.owner(code) == Some(&node_a)).unwrap();
+
+    // Node A stores every link; node B has an empty database
+    let storage_a = storage_with(&[&remote, &local, &namespaced]).await;
+    let peers_a = PeerCache::new(config(&node_a, &cluster), HttpClient::default());
+    let redirector_a = web::Data::new(Redirector::new(storage_a, RedirectConfig::default()).with_peers(peers_a));
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(redirector_a.clone())
+            .route(&format!("{}/{}", LOOKUP_PATH, CODE_PATTERN), web::get().to(lookup))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_rt::spawn(server);
+
+    let storage_b = storage_with(&[]).await;
+    let peers_b = PeerCache::new(config(NODE_B, &cluster), HttpClient::default());
+    let redirector_b = Redirector::new(storage_b.clone(), RedirectConfig::default()).with_peers(peers_b);
+
+    assert_eq!(&*redirector_b.resolve(&remote).await.unwrap(), format!("https://example.com/{}", remote));
+    assert!(redirector_b.cache().get(&remote).is_some());
+    assert_eq!(&*redirector_b.resolve(&namespaced).await.unwrap(), format!("https://example.com/{}", namespaced));
+
+    // A code owned by node B is read from its own database only
+    assert!(redirector_b.resolve(&local).await.is_err());
+
+    let missing = (0..).map(|i| format!("missing{}", i)).find(|code| ring.owner(code) == Some(&node_a)).unwrap();
+    let error = redirector_b.resolve(&missing).await.unwrap_err();
+    assert!(matches!(error.error_type, UrlShortenerErrorType::NotFound));
+}
+
+#[actix_rt::test]
+async fn test_unreachable_peers_fall_back_to_storage() {
+    let cluster = ["http://127.0.0.1:9", NODE_B];
+    let ring = HashRing::new(&cluster.map(String::from));
+    let code = code_owned_by(&ring, "http://127.0.0.1:9");
+
+    let storage = storage_with(&[&code]).await;
+    let peers = PeerCache::new(config(NODE_B, &cluster), HttpClient::default());
+    assert_eq!(peers.lookup(&code).await, PeerLookup::Unavailable);
+
+    let redirector = Redirector::new(storage, RedirectConfig::default()).with_peers(peers);
+    assert_eq!(&*redirector.resolve(&code).await.unwrap(), format!("https://example.com/{}", code));
+}
@@ -0,0 +1,205 @@
+//! Branded preview images: the link's title, its short link and a QR code on a 1200×630 card,
+//! the size social networks expect for `og:image`.
+
+use std::io::Cursor;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{ImageFormat, Rgb, RgbImage};
+use qrcode::{Color, QrCode};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+
+pub const IMAGE_WIDTH: u32 = 1200;
+pub const IMAGE_HEIGHT: u32 = 630;
+
+const FONT: &[u8] = include_bytes!("fonts/DejaVuSans-Bold.ttf");
+
+const BACKGROUND: Rgb<u8> = Rgb([15, 23, 42]);
+const TITLE_COLOR: Rgb<u8> = Rgb([248, 250, 252]);
+const LINK_COLOR: Rgb<u8> = Rgb([56, 189, 248]);
+const QR_DARK: Rgb<u8> = Rgb([15, 23, 42]);
+const QR_LIGHT: Rgb<u8> = Rgb([255, 255, 255]);
+
+const MARGIN: f32 = 80.0;
+const TITLE_SIZE: f32 = 64.0;
+const TITLE_MAX_LINES: usize = 3;
+/// Characters of the title considered; more would not fit on the card anyway
+const TITLE_MAX_CHARS: usize = 300;
+const LINK_SIZE: f32 = 40.0;
+/// Side of the white QR square, quiet zone included
+const QR_SIZE: u32 = 360;
+/// Text stops short of the QR square by this much
+const QR_GAP: f32 = 60.0;
+
+/// Renders the preview card of a link as a PNG
+pub fn render_preview_image(title: &str, short_link: &str) -> UrlShortenerResult<Vec<u8>> {
+    let font = FontRef::try_from_slice(FONT)
+        .map_err(|e| render_error("Failed to load preview font", anyhow::anyhow!(e.to_string())))?;
+    let mut canvas = RgbImage::from_pixel(IMAGE_WIDTH, IMAGE_HEIGHT, BACKGROUND);
+
+    let qr_x = IMAGE_WIDTH - MARGIN as u32 - QR_SIZE;
+    let qr_y = (IMAGE_HEIGHT - QR_SIZE) / 2;
+    draw_qr_code(&mut canvas, short_link, qr_x, qr_y)?;
+
+    let text_width = qr_x as f32 - QR_GAP - MARGIN;
+    let title: String = title.chars().take(TITLE_MAX_CHARS).collect();
+    let title_scale = PxScale::from(TITLE_SIZE);
+    let line_height = TITLE_SIZE * 1.25;
+    for (index, line) in wrap(&font, title_scale, &title, text_width, TITLE_MAX_LINES).iter().enumerate() {
+        let baseline = MARGIN + TITLE_SIZE + index as f32 * line_height;
+        draw_text(&mut canvas, &font, title_scale, line, MARGIN, baseline, TITLE_COLOR);
+    }
+
+    let link_scale = PxScale::from(LINK_SIZE);
+    let display_link = short_link.split_once("://").map_or(short_link, |(_, rest)| rest);
+    let display_link = truncate(&font, link_scale, display_link, text_width);
+    draw_text(&mut canvas, &font, link_scale, &display_link, MARGIN, IMAGE_HEIGHT as f32 - MARGIN, LINK_COLOR);
+
+    let mut png = Cursor::new(Vec::new());
+    canvas
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| render_error("Failed to encode preview image", e))?;
+    Ok(png.into_inner())
+}
+
+/// Draws the QR code of `link` on a white square, scaled to whole pixels per module
+fn draw_qr_code(canvas: &mut RgbImage, link: &str, x: u32, y: u32) -> UrlShortenerResult<()> {
+    let code = QrCode::new(link.as_bytes())
+        .map_err(|e| render_error("Failed to encode QR code", anyhow::anyhow!(e.to_string())))?;
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+
+    // Four modules of quiet zone on each side, as scanners expect
+    let module_size = (QR_SIZE / (modules + 8)).max(1);
+    let offset = (QR_SIZE - module_size * modules) / 2;
+    for dy in 0..QR_SIZE {
+        for dx in 0..QR_SIZE {
+            canvas.put_pixel(x + dx, y + dy, QR_LIGHT);
+        }
+    }
+    for (index, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let module_x = x + offset + (index as u32 % modules) * module_size;
+        let module_y = y + offset + (index as u32 / modules) * module_size;
+        for dy in 0..module_size {
+            for dx in 0..module_size {
+                canvas.put_pixel(module_x + dx, module_y + dy, QR_DARK);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Draws one line of text with its baseline at `baseline`, blending glyph coverage into the canvas
+fn draw_text(canvas: &mut RgbImage, font: &FontRef, scale: PxScale, text: &str, x: f32, baseline: f32, color: Rgb<u8>) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else { continue };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= canvas.width() as i32 || py >= canvas.height() as i32 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for channel in 0..3 {
+                let blended = pixel[channel] as f32 * (1.0 - coverage) + color[channel] as f32 * coverage;
+                pixel[channel] = blended.round() as u8;
+            }
+        });
+    }
+}
+
+fn text_width(font: &FontRef, scale: PxScale, text: &str) -> f32 {
+    let scaled = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Breaks text into at most `max_lines` lines of `max_width`; the last line ends in an
+/// ellipsis if text was left over
+fn wrap(font: &FontRef, scale: PxScale, text: &str, max_width: f32, max_lines: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+        if text_width(font, scale, &candidate) <= max_width {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // A word wider than a line is cut where it overflows
+        let mut word = word;
+        while text_width(font, scale, word) > max_width {
+            let (line, rest) = word.split_at(fitting_prefix(font, scale, word, max_width));
+            lines.push(line.to_string());
+            word = rest;
+        }
+        current = word.to_string();
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            *last = ellipsize(font, scale, last, max_width);
+        }
+    }
+    lines
+}
+
+/// Shortens text with an ellipsis if it does not fit `max_width`
+fn truncate(font: &FontRef, scale: PxScale, text: &str, max_width: f32) -> String {
+    if text_width(font, scale, text) <= max_width {
+        return text.to_string();
+    }
+    ellipsize(font, scale, text, max_width)
+}
+
+/// Ends text in an ellipsis, cutting it as far as needed to fit `max_width`
+fn ellipsize(font: &FontRef, scale: PxScale, text: &str, max_width: f32) -> String {
+    let fits = fitting_prefix(font, scale, text, max_width - text_width(font, scale, "…"));
+    format!("{}…", text[..fits].trim_end())
+}
+
+/// Byte length of the longest prefix of `text` that fits `max_width`, at least one character
+fn fitting_prefix(font: &FontRef, scale: PxScale, text: &str, max_width: f32) -> usize {
+    let mut end = text.chars().next().map_or(0, char::len_utf8);
+    for (index, c) in text.char_indices().skip(1) {
+        if text_width(font, scale, &text[..index + c.len_utf8()]) > max_width {
+            break;
+        }
+        end = index + c.len_utf8();
+    }
+    end
+}
+
+fn render_error(message: &str, err: impl Into<anyhow::Error>) -> UrlShortenerError {
+    UrlShortenerError::with_source(UrlShortenerErrorType::InternalError(message.to_string()), err.into())
+}
@@ -0,0 +1,454 @@
+//! Link previews for social crawlers.
+//!
+//! Chat apps and social networks unfurl a shared link by fetching it with a crawler and reading
+//! its OpenGraph tags. A bare redirect leaves some of them with nothing to show, so crawler
+//! requests can instead get a small HTML page carrying the destination's own OG tags, fetched
+//! once and cached, plus a refresh to the destination. Crawler requests are not counted as visits.
+//! Chat bots can ask for the same tags as JSON instead.
+
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{http::header, HttpRequest};
+use lru::LruCache;
+use tracing::{debug, warn};
+use url::{Host, Url};
+
+use crate::http_client::HttpClient;
+use crate::models::ShortenedUrl;
+
+#[cfg(feature = "og-image")]
+mod image;
+
+#[cfg(feature = "og-image")]
+pub use image::{render_preview_image, IMAGE_HEIGHT, IMAGE_WIDTH};
+
+/// User agent fragments of crawlers that render link previews
+const SOCIAL_CRAWLERS: &[&str] = &[
+    "twitterbot",
+    "facebookexternalhit",
+    "facebot",
+    "slackbot",
+    "linkedinbot",
+    "discordbot",
+    "telegrambot",
+    "whatsapp",
+];
+
+/// Destinations whose tags are remembered
+const CACHE_CAPACITY: usize = 1_000;
+/// How long fetched tags are reused before the destination is fetched again
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Time allowed for fetching a destination page
+const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+const FETCH_USER_AGENT: &str = concat!("url-map/", env!("CARGO_PKG_VERSION"), " (link preview)");
+/// Bytes of a destination page read while looking for its tags
+const MAX_PAGE_BYTES: usize = 256 * 1024;
+/// Redirects followed from a destination before giving up on its tags
+const MAX_REDIRECTS: usize = 3;
+
+/// Whether a user agent belongs to a crawler that renders link previews
+pub fn is_social_crawler(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_ascii_lowercase();
+    SOCIAL_CRAWLERS.iter().any(|crawler| user_agent.contains(crawler))
+}
+
+/// Whether a request comes from a crawler that renders link previews
+pub fn is_social_crawler_request(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(is_social_crawler)
+}
+
+/// OpenGraph tags read from a destination page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OgTags {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+    /// Icon linked from the page, as written there
+    pub icon: Option<String>,
+}
+
+/// Fetches destination OG tags and renders preview pages
+pub struct LinkPreviews {
+    public_base_url: String,
+    http: HttpClient,
+    cache: Mutex<LruCache<String, (Instant, OgTags)>>,
+}
+
+impl LinkPreviews {
+    /// Creates the previewer; `public_base_url` is used for the `og:url` of each page
+    pub fn new(public_base_url: &str, http: HttpClient) -> Self {
+        Self {
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+            http,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN))),
+        }
+    }
+
+    /// Renders the preview page of a link. Builds with the `og-image` feature show the link's
+    /// branded preview card instead of the destination's image.
+    pub async fn render(&self, url: &ShortenedUrl) -> String {
+        let mut tags = self.tags(&url.original_url).await;
+        if cfg!(feature = "og-image") {
+            tags.image = Some(self.preview_image_url(&url.short_url));
+        }
+        render_page(url, &self.short_link(&url.short_url), &tags)
+    }
+
+    pub fn short_link(&self, short_code: &str) -> String {
+        format!("{}/{}", self.public_base_url, short_code)
+    }
+
+    /// Where the branded preview card of a link is served
+    pub fn preview_image_url(&self, short_code: &str) -> String {
+        format!("{}/api/links/{}/preview.png", self.public_base_url, short_code)
+    }
+
+    /// Tags of a destination, from the cache or fetched; empty if the page cannot be read
+    pub async fn tags(&self, destination: &str) -> OgTags {
+        if let Some(tags) = self.cached(destination) {
+            return tags;
+        }
+        let tags = match self.fetch(destination).await {
+            Ok(html) => parse_og_tags(&html),
+            Err(reason) => {
+                debug!(destination = %destination, reason = %reason, "No link preview tags");
+                OgTags::default()
+            }
+        };
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.put(destination.to_string(), (Instant::now(), tags.clone()));
+        }
+        tags
+    }
+
+    fn cached(&self, destination: &str) -> Option<OgTags> {
+        let mut cache = self.cache.lock().ok()?;
+        match cache.get(destination) {
+            Some((fetched_at, tags)) if fetched_at.elapsed() < CACHE_TTL => Some(tags.clone()),
+            _ => None,
+        }
+    }
+
+    /// Reads the start of an HTML destination page
+    async fn fetch(&self, destination: &str) -> Result<String, String> {
+        self.fetch_with(destination, |url| async move { public_addrs(&url).await }).await
+    }
+
+    /// Reads the start of an HTML destination page, connecting to each hop only at the
+    /// addresses `addrs_of` approves. Redirects are followed one hop at a time, so every hop
+    /// is checked and a public page cannot forward the fetch to an internal service.
+    async fn fetch_with<F, Fut>(&self, destination: &str, addrs_of: F) -> Result<String, String>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = Result<Vec<SocketAddr>, String>>,
+    {
+        let mut url = Url::parse(destination).map_err(|e| e.to_string())?;
+        for _ in 0..=MAX_REDIRECTS {
+            let addrs = addrs_of(url.clone()).await?;
+            let http = self.http.pinned(url.host_str().unwrap_or_default(), &addrs).map_err(|e| e.to_string())?;
+            let request = http
+                .get(url.clone())
+                .timeout(http.timeout_within(FETCH_TIMEOUT))
+                .header(reqwest::header::USER_AGENT, FETCH_USER_AGENT);
+            let response = http.send("link_preview", request).await.map_err(|e| e.to_string())?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or("redirect without a location")?;
+                url = url.join(location).map_err(|e| e.to_string())?;
+                continue;
+            }
+            return read_page(response.error_for_status().map_err(|e| e.to_string())?).await;
+        }
+        Err("too many redirects".to_string())
+    }
+}
+
+/// The start of an HTML page, or an error for any other content
+async fn read_page(mut response: reqwest::Response) -> Result<String, String> {
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html") || value.starts_with("application/xhtml"));
+    if !is_html {
+        return Err("destination is not an HTML page".to_string());
+    }
+
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        page.extend_from_slice(&chunk);
+        if page.len() >= MAX_PAGE_BYTES {
+            page.truncate(MAX_PAGE_BYTES);
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&page).into_owned())
+}
+
+/// Addresses a public http(s) URL connects to, when every one of them is public. Host names
+/// are resolved, so a public name pointing at a private address is refused too.
+pub(crate) async fn public_addrs(url: &Url) -> Result<Vec<SocketAddr>, String> {
+    if !is_public_http(url) {
+        return Err("not a public http(s) URL".to_string());
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = match url.host() {
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| e.to_string())?
+            .collect(),
+        Some(Host::Ipv4(ip)) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+        Some(Host::Ipv6(ip)) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+        None => Vec::new(),
+    };
+    checked_addrs(url, addrs)
+}
+
+/// The addresses a URL's host resolved to, when there are some and every one of them is public
+pub(crate) fn checked_addrs(url: &Url, addrs: Vec<SocketAddr>) -> Result<Vec<SocketAddr>, String> {
+    if addrs.is_empty() {
+        return Err("host has no addresses".to_string());
+    }
+    if let Some(private) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        warn!(host = %url.host_str().unwrap_or_default(), address = %private.ip(), "Refusing to fetch from a private address");
+        return Err("host resolves to a private address".to_string());
+    }
+    Ok(addrs)
+}
+
+/// Only fetch http(s) destinations on public hosts, so crawlers cannot make us read internal services.
+/// Host names are not resolved here; [`public_addrs`] checks the addresses they resolve to.
+pub(crate) fn is_public_http(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost") && !domain.ends_with(".internal")
+        }
+        Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+/// Whether an address is reachable on the public internet. Private, loopback, link-local,
+/// shared (CGNAT), benchmarking and reserved ranges are not; IPv4 addresses embedded in IPv6
+/// ones, mapped or through NAT64, are judged as themselves.
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            let this_network = a == 0;
+            let shared = a == 100 && b & 0xc0 == 64;
+            let protocol_assignments = a == 192 && b == 0 && c == 0;
+            let benchmarking = a == 198 && b & 0xfe == 18;
+            let reserved = a >= 240;
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || this_network
+                || shared
+                || protocol_assignments
+                || benchmarking
+                || reserved)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let unique_local = segments[0] & 0xfe00 == 0xfc00;
+            let link_local = segments[0] & 0xffc0 == 0xfe80;
+            let site_local = segments[0] & 0xffc0 == 0xfec0;
+            let nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+            let embedded = match ip.to_ipv4_mapped() {
+                Some(mapped) => Some(mapped),
+                None if nat64 => Some(Ipv4Addr::from(ip.to_bits() as u32)),
+                None => None,
+            };
+            match embedded {
+                Some(v4) => is_public_ip(IpAddr::V4(v4)),
+                None => !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local || site_local),
+            }
+        }
+    }
+}
+
+/// Reads OpenGraph tags, falling back to `<title>` and the description meta tag
+pub fn parse_og_tags(html: &str) -> OgTags {
+    let mut tags = OgTags::default();
+    let mut title = None;
+    let mut description = None;
+    let lower = html.to_ascii_lowercase();
+
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find("<meta").map(|offset| rest + offset) {
+        let Some(end) = lower[start..].find('>').map(|offset| start + offset) else { break };
+        let tag = &html[start + "<meta".len()..end];
+        rest = end;
+
+        let attribute = |name: &str| attribute_value(tag, name);
+        let Some(content) = attribute("content").filter(|content| !content.is_empty()) else { continue };
+        let key = attribute("property").or_else(|| attribute("name")).unwrap_or_default().to_ascii_lowercase();
+        let slot = match key.as_str() {
+            "og:title" => &mut tags.title,
+            "og:description" => &mut tags.description,
+            "og:image" | "og:image:url" | "og:image:secure_url" => &mut tags.image,
+            "og:site_name" => &mut tags.site_name,
+            "description" => &mut description,
+            "twitter:title" => &mut title,
+            _ => continue,
+        };
+        slot.get_or_insert(content);
+    }
+
+    if title.is_none() {
+        if let Some(start) = lower.find("<title") {
+            let text_start = lower[start..].find('>').map(|offset| start + offset + 1);
+            let text_end = text_start.and_then(|text_start| lower[text_start..].find("</title").map(|offset| text_start + offset));
+            if let (Some(text_start), Some(text_end)) = (text_start, text_end) {
+                title = Some(decode_entities(html[text_start..text_end].trim())).filter(|title| !title.is_empty());
+            }
+        }
+    }
+
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find("<link").map(|offset| rest + offset) {
+        let Some(end) = lower[start..].find('>').map(|offset| start + offset) else { break };
+        let tag = &html[start + "<link".len()..end];
+        rest = end;
+
+        let is_icon = attribute_value(tag, "rel")
+            .is_some_and(|rel| rel.split_whitespace().any(|rel| rel.eq_ignore_ascii_case("icon")));
+        if let Some(href) = attribute_value(tag, "href").filter(|href| is_icon && !href.is_empty()) {
+            tags.icon.get_or_insert(href);
+        }
+    }
+
+    tags.title = tags.title.or(title);
+    tags.description = tags.description.or(description);
+    tags
+}
+
+/// A URL found on the destination page, made absolute; only http(s) URLs are kept
+pub(crate) fn resolve(destination: &str, reference: &str) -> Option<Url> {
+    Url::parse(destination)
+        .and_then(|base| base.join(reference))
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Icon of a destination: the one its page links, else `/favicon.ico` on its host
+pub fn favicon_url(destination: &str, tags: &OgTags) -> Option<String> {
+    tags.icon
+        .as_deref()
+        .and_then(|icon| resolve(destination, icon))
+        .or_else(|| resolve(destination, "/favicon.ico"))
+        .map(String::from)
+}
+
+/// Value of an attribute in the inside of a tag, quoted or not, with entities decoded
+fn attribute_value(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name).map(|offset| search + offset) {
+        search = found + name.len();
+        let preceded_by_space = found == 0 || lower.as_bytes()[found - 1].is_ascii_whitespace();
+        let after = lower[search..].trim_start();
+        if !preceded_by_space || !after.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - after.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split_whitespace().next().unwrap_or_default(),
+        };
+        return Some(decode_entities(value.trim()));
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Escapes text for use in HTML content and attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Title shown in a link's preview: the destination's, else the link's own, else the destination URL
+pub fn preview_title(url: &ShortenedUrl, tags: &OgTags) -> String {
+    tags.title
+        .clone()
+        .or_else(|| url.title.clone())
+        .unwrap_or_else(|| url.original_url.clone())
+}
+
+/// Preview page: the destination's tags, or the link's own title, and a refresh to the destination
+pub fn render_page(url: &ShortenedUrl, short_link: &str, tags: &OgTags) -> String {
+    let destination = &url.original_url;
+    let title = preview_title(url, tags);
+    // Relative image paths are relative to the destination page
+    let image = tags.image.as_deref().and_then(|image| resolve(destination, image));
+
+    let mut meta = vec![
+        ("og:type", "website".to_string()),
+        ("og:title", title.clone()),
+        ("og:url", short_link.to_string()),
+    ];
+    if let Some(description) = &tags.description {
+        meta.push(("og:description", description.clone()));
+    }
+    if let Some(site_name) = &tags.site_name {
+        meta.push(("og:site_name", site_name.clone()));
+    }
+    if let Some(image) = &image {
+        meta.push(("og:image", image.to_string()));
+    }
+    let card = if image.is_some() { "summary_large_image" } else { "summary" };
+    meta.push(("twitter:card", card.to_string()));
+
+    let mut page = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+    for (property, content) in meta {
+        page.push_str(&format!("<meta property=\"{}\" content=\"{}\">\n", property, escape_html(&content)));
+    }
+    let destination = escape_html(destination);
+    page.push_str(&format!("<link rel=\"canonical\" href=\"{}\">\n", destination));
+    page.push_str(&format!("<meta http-equiv=\"refresh\" content=\"0; url={}\">\n", destination));
+    page.push_str(&format!("</head>\n<body>\n<a href=\"{}\">{}</a>\n</body>\n</html>\n", destination, destination));
+    page
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,211 @@
+use super::*;
+
+fn link(original_url: &str, title: Option<&str>) -> ShortenedUrl {
+    ShortenedUrl {
+        id: 1,
+        original_url: original_url.to_string(),
+        short_url: "abc123".to_string(),
+        title: title.map(str::to_string),
+        ..ShortenedUrl::default()
+    }
+}
+
+#[test]
+fn test_social_crawlers_are_recognized() {
+    assert!(is_social_crawler("Twitterbot/1.0"));
+    assert!(is_social_crawler("facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)"));
+    assert!(is_social_crawler("Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)"));
+    assert!(!is_social_crawler("Mozilla/5.0 (X11; Linux x86_64) Firefox/120.0"));
+    assert!(!is_social_crawler("Googlebot/2.1"));
+}
+
+#[test]
+fn test_og_tags_are_parsed() {
+    let html = r#"<html><head>
+        <title>Fallback</title>
+        <META property="og:title" content="Launch &amp; Learn">
+        <meta content='A "quoted" description' property='og:description' />
+        <meta property=og:image content=/img/cover.png>
+        <meta property="og:site_name" content="Example">
+        <meta property="og:title" content="Second title">
+    </head></html>"#;
+
+    let tags = parse_og_tags(html);
+    assert_eq!(tags.title.as_deref(), Some("Launch & Learn"));
+    assert_eq!(tags.description.as_deref(), Some("A \"quoted\" description"));
+    assert_eq!(tags.image.as_deref(), Some("/img/cover.png"));
+    assert_eq!(tags.site_name.as_deref(), Some("Example"));
+}
+
+#[test]
+fn test_title_and_description_fall_back_to_plain_tags() {
+    let html = r#"<head><title> Plain page </title><meta name="description" content="About it"></head>"#;
+
+    let tags = parse_og_tags(html);
+    assert_eq!(tags.title.as_deref(), Some("Plain page"));
+    assert_eq!(tags.description.as_deref(), Some("About it"));
+    assert_eq!(tags.image, None);
+    assert_eq!(parse_og_tags("not html at all"), OgTags::default());
+}
+
+#[test]
+fn test_favicon_is_read_from_the_page_or_guessed() {
+    let html = r#"<head><link rel="stylesheet" href="/site.css"><link rel="Shortcut Icon" href="/static/icon.png"></head>"#;
+    let tags = parse_og_tags(html);
+    assert_eq!(tags.icon.as_deref(), Some("/static/icon.png"));
+    assert_eq!(
+        favicon_url("https://example.com/blog/post", &tags).as_deref(),
+        Some("https://example.com/static/icon.png"),
+    );
+
+    assert_eq!(
+        favicon_url("https://example.com/blog/post", &OgTags::default()).as_deref(),
+        Some("https://example.com/favicon.ico"),
+    );
+    assert_eq!(favicon_url("mailto:someone@example.com", &OgTags::default()), None);
+}
+
+#[test]
+fn test_page_carries_escaped_tags_and_absolute_image() {
+    let tags = OgTags {
+        title: Some("<script>alert(1)</script>".to_string()),
+        image: Some("/img/cover.png".to_string()),
+        ..OgTags::default()
+    };
+    let page = render_page(&link("https://example.com/post?a=1&b=2", None), "https://sho.rt/abc123", &tags);
+
+    assert!(page.contains(r#"<meta property="og:title" content="&lt;script&gt;alert(1)&lt;/script&gt;">"#));
+    assert!(page.contains(r#"<meta property="og:image" content="https://example.com/img/cover.png">"#));
+    assert!(page.contains(r#"<meta property="og:url" content="https://sho.rt/abc123">"#));
+    assert!(page.contains(r#"<meta property="twitter:card" content="summary_large_image">"#));
+    assert!(page.contains(r#"content="0; url=https://example.com/post?a=1&amp;b=2""#));
+    assert!(!page.contains("<script>"));
+}
+
+#[test]
+fn test_page_without_tags_uses_link_title() {
+    let page = render_page(&link("https://example.com/", Some("My link")), "https://sho.rt/abc123", &OgTags::default());
+    assert!(page.contains(r#"<meta property="og:title" content="My link">"#));
+    assert!(page.contains(r#"<meta property="twitter:card" content="summary">"#));
+    assert!(!page.contains("og:image"));
+}
+
+#[test]
+fn test_only_public_destinations_are_fetched() {
+    let public = |url: &str| is_public_http(&Url::parse(url).unwrap());
+    assert!(public("https://example.com/page"));
+    assert!(public("http://93.184.216.34/"));
+    assert!(!public("http://localhost:8080/"));
+    assert!(!public("http://127.0.0.1/"));
+    assert!(!public("http://10.1.2.3/"));
+    assert!(!public("http://169.254.169.254/latest/meta-data/"));
+    assert!(!public("http://[::1]/"));
+    assert!(!public("http://[::ffff:192.168.0.1]/"));
+    assert!(!public("ftp://example.com/file"));
+}
+
+#[test]
+fn test_internal_address_ranges_are_not_public() {
+    let cases = [
+        ("93.184.216.34", true),
+        ("100.63.255.255", true),
+        ("100.128.0.1", true),
+        ("198.20.0.1", true),
+        ("192.0.1.1", true),
+        ("223.255.255.255", true),
+        ("2606:2800:220:1:248:1893:25c8:1946", true),
+        ("64:ff9b::5db8:d822", true),
+        ("0.1.2.3", false),
+        ("10.0.0.1", false),
+        ("100.64.0.1", false),
+        ("100.127.255.254", false),
+        ("127.0.0.1", false),
+        ("169.254.169.254", false),
+        ("172.16.0.1", false),
+        ("192.0.0.8", false),
+        ("192.168.1.1", false),
+        ("198.18.0.1", false),
+        ("198.19.255.255", false),
+        ("240.0.0.1", false),
+        ("255.255.255.255", false),
+        ("::", false),
+        ("::1", false),
+        ("::ffff:10.0.0.1", false),
+        ("64:ff9b::7f00:1", false),
+        ("64:ff9b::a9fe:a9fe", false),
+        ("64:ff9b::6440:1", false),
+        ("fc00::1", false),
+        ("fe80::1", false),
+        ("fec0::1", false),
+        ("feff::1", false),
+    ];
+    for (ip, public) in cases {
+        assert_eq!(is_public_ip(ip.parse().unwrap()), public, "{}", ip);
+    }
+}
+
+#[actix_rt::test]
+async fn test_redirects_to_private_hosts_are_not_followed() {
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(|| {
+        App::new()
+            .route("/page", web::get().to(|| async {
+                HttpResponse::Ok().content_type("text/html").body(r#"<meta property="og:title" content="Public">"#)
+            }))
+            .route("/moved", web::get().to(|| async {
+                HttpResponse::Found().insert_header(("Location", "/page")).finish()
+            }))
+            .route("/internal", web::get().to(|| async {
+                HttpResponse::Found().insert_header(("Location", "http://10.0.0.5/admin")).finish()
+            }))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    let handle = server.handle();
+    tokio::spawn(server);
+
+    // The test server stands in for a public host; every other hop is checked as usual
+    let addrs_of = |url: Url| async move {
+        match url.host_str() {
+            Some("public.test") => Ok(vec![addr]),
+            _ => public_addrs(&url).await,
+        }
+    };
+    let previews = LinkPreviews::new("https://sho.rt/", HttpClient::default());
+    let destination = |path: &str| format!("http://public.test:{}{}", addr.port(), path);
+
+    let page = previews.fetch_with(&destination("/moved"), addrs_of).await.unwrap();
+    assert_eq!(parse_og_tags(&page).title.as_deref(), Some("Public"));
+    let refused = previews.fetch_with(&destination("/internal"), addrs_of).await.unwrap_err();
+    assert_eq!(refused, "not a public http(s) URL");
+    assert!(public_addrs(&Url::parse("http://localhost/admin").unwrap()).await.is_err());
+
+    handle.stop(false).await;
+}
+
+#[cfg(feature = "og-image")]
+#[test]
+fn test_preview_image_is_a_card_sized_png() {
+    let title = "A title long enough to wrap over several lines and then some more words ".repeat(10);
+    let png = render_preview_image(&title, "https://sho.rt/abc123").unwrap();
+
+    let card = ::image::load_from_memory(&png).unwrap().to_rgb8();
+    assert_eq!(card.dimensions(), (IMAGE_WIDTH, IMAGE_HEIGHT));
+    // The QR code square is white with dark modules
+    let qr_pixels: Vec<_> = (760..1120).map(|x| card.get_pixel(x, IMAGE_HEIGHT / 2).0).collect();
+    assert!(qr_pixels.contains(&[255, 255, 255]));
+    assert!(qr_pixels.iter().any(|pixel| pixel[0] < 64));
+}
+
+#[cfg(feature = "og-image")]
+#[tokio::test]
+async fn test_preview_page_uses_branded_image() {
+    let previews = LinkPreviews::new("https://sho.rt/", HttpClient::default());
+    let page = previews.render(&link("http://127.0.0.1:9/", None)).await;
+    assert!(page.contains(r#"<meta property="og:image" content="https://sho.rt/api/links/abc123/preview.png">"#));
+}
@@ -0,0 +1,146 @@
+//! Visitor IP privacy.
+//!
+//! Anything that records who sent a request goes through an [`IpAnonymizer`], so raw visitor
+//! addresses do not end up in logs or analytics unless a deployment opts out. Addresses are
+//! replaced by a salted hash whose salt changes every day, so one visitor's requests can be
+//! told apart within a day but not linked across days, or truncated to their network.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::{NaiveDate, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Bytes of the salted hash that are kept, as hex
+const HASH_LENGTH: usize = 8;
+
+/// How visitor addresses are recorded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpPrivacy {
+    /// A hash of the address with a daily rotating salt
+    #[default]
+    Hash,
+    /// The address's /24 (IPv4) or /48 (IPv6) network
+    Truncate,
+    /// The full address
+    Off,
+}
+
+impl FromStr for IpPrivacy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "hash" => Ok(Self::Hash),
+            "truncate" => Ok(Self::Truncate),
+            "off" => Ok(Self::Off),
+            other => Err(format!("unknown IP privacy mode '{}'", other)),
+        }
+    }
+}
+
+/// Turns visitor addresses into the form the deployment's [`IpPrivacy`] mode allows to be kept
+pub struct IpAnonymizer {
+    mode: IpPrivacy,
+    secret: Option<String>,
+    /// Random salt for the day it was drawn on, when no secret is configured
+    salt: Mutex<Option<(NaiveDate, [u8; 32])>>,
+}
+
+impl IpAnonymizer {
+    /// Salts are derived from `secret` and the date, so every instance sharing it hashes a
+    /// visitor the same way on a given day. Without one each instance draws a random salt per
+    /// day, which is never stored anywhere.
+    pub fn new(mode: IpPrivacy, secret: Option<&str>) -> Self {
+        Self {
+            mode,
+            secret: secret.map(str::to_string),
+            salt: Mutex::new(None),
+        }
+    }
+
+    pub fn mode(&self) -> IpPrivacy {
+        self.mode
+    }
+
+    pub fn anonymize(&self, ip: IpAddr) -> String {
+        self.anonymize_on(ip, Utc::now().date_naive())
+    }
+
+    /// Anonymizes an address as it would be on `day`, which picks the salt when hashing
+    pub fn anonymize_on(&self, ip: IpAddr, day: NaiveDate) -> String {
+        match self.mode {
+            IpPrivacy::Hash => {
+                let mut hasher = Sha256::new();
+                hasher.update(self.salt_for(day));
+                hasher.update(canonical(ip).to_string());
+                hex::encode(&hasher.finalize()[..HASH_LENGTH])
+            }
+            IpPrivacy::Truncate => truncate(ip).to_string(),
+            IpPrivacy::Off => ip.to_string(),
+        }
+    }
+
+    fn salt_for(&self, day: NaiveDate) -> [u8; 32] {
+        if let Some(secret) = &self.secret {
+            return Sha256::new()
+                .chain_update(secret.as_bytes())
+                .chain_update(day.to_string())
+                .finalize()
+                .into();
+        }
+
+        let mut salt = self.salt.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match *salt {
+            Some((salted_on, value)) if salted_on == day => value,
+            _ => {
+                let mut value = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut value);
+                *salt = Some((day, value));
+                value
+            }
+        }
+    }
+}
+
+impl Default for IpAnonymizer {
+    fn default() -> Self {
+        Self::new(IpPrivacy::default(), None)
+    }
+}
+
+impl fmt::Debug for IpAnonymizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpAnonymizer").field("mode", &self.mode).finish_non_exhaustive()
+    }
+}
+
+/// IPv4 addresses mapped into IPv6 are treated as the IPv4 address they carry
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+/// The network an address belongs to: its /24 for IPv4 and its /48 for IPv6
+pub fn truncate(ip: IpAddr) -> IpAddr {
+    match canonical(ip) {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
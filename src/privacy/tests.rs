@@ -0,0 +1,53 @@
+use super::*;
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+fn day(value: &str) -> NaiveDate {
+    value.parse().unwrap()
+}
+
+#[test]
+fn test_truncation_keeps_only_the_network() {
+    assert_eq!(truncate(ip("203.0.113.77")), ip("203.0.113.0"));
+    assert_eq!(truncate(ip("2001:db8:abcd:12::1")), ip("2001:db8:abcd::"));
+    assert_eq!(truncate(ip("::ffff:198.51.100.9")), ip("198.51.100.0"));
+
+    let anonymizer = IpAnonymizer::new(IpPrivacy::Truncate, None);
+    assert_eq!(anonymizer.anonymize(ip("203.0.113.77")), "203.0.113.0");
+}
+
+#[test]
+fn test_hashes_rotate_daily() {
+    let anonymizer = IpAnonymizer::new(IpPrivacy::Hash, Some("secret"));
+    let monday = anonymizer.anonymize_on(ip("203.0.113.7"), day("2026-10-12"));
+
+    assert_eq!(monday.len(), 2 * HASH_LENGTH);
+    assert!(!monday.contains("203"));
+    assert_eq!(monday, anonymizer.anonymize_on(ip("::ffff:203.0.113.7"), day("2026-10-12")));
+    assert_ne!(monday, anonymizer.anonymize_on(ip("203.0.113.8"), day("2026-10-12")));
+    assert_ne!(monday, anonymizer.anonymize_on(ip("203.0.113.7"), day("2026-10-13")));
+}
+
+#[test]
+fn test_hashes_match_across_instances_only_with_a_shared_secret() {
+    let today = day("2026-10-16");
+    let hash = |anonymizer: &IpAnonymizer| anonymizer.anonymize_on(ip("198.51.100.1"), today);
+
+    let shared = IpAnonymizer::new(IpPrivacy::Hash, Some("secret"));
+    assert_eq!(hash(&shared), hash(&IpAnonymizer::new(IpPrivacy::Hash, Some("secret"))));
+    assert_ne!(hash(&shared), hash(&IpAnonymizer::new(IpPrivacy::Hash, Some("other"))));
+
+    let random = IpAnonymizer::default();
+    assert_eq!(hash(&random), hash(&random));
+    assert_ne!(hash(&random), hash(&IpAnonymizer::default()));
+}
+
+#[test]
+fn test_privacy_modes_parse() {
+    assert_eq!("Truncate".parse(), Ok(IpPrivacy::Truncate));
+    assert_eq!(" off ".parse(), Ok(IpPrivacy::Off));
+    assert!("none".parse::<IpPrivacy>().is_err());
+    assert_eq!(IpAnonymizer::new(IpPrivacy::Off, None).anonymize(ip("203.0.113.7")), "203.0.113.7");
+}
@@ -0,0 +1,170 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::models::RedirectHeaders;
+
+/// Number of independently locked shards, to keep concurrent redirects from contending
+const SHARDS: usize = 16;
+
+/// What a short code redirects to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub url: Arc<str>,
+    /// Path and query after the short code are appended to the destination
+    pub pass_through: bool,
+    /// Only requests with a valid signature are redirected
+    pub signed_only: bool,
+    /// Owner's analytics snippet, fired on a page that forwards to the destination
+    pub tracking_snippet: Option<Arc<str>>,
+    /// Extra response headers sent with redirects
+    pub redirect_headers: Option<Arc<RedirectHeaders>>,
+    /// Browsers are asked not to send the destination a referrer
+    pub hide_referrer: bool,
+}
+
+impl Target {
+    /// Destination with a request's extra path and query appended, for pass-through links
+    pub fn with_path(&self, path: &str, query: &str) -> Arc<str> {
+        if !self.pass_through || (path.is_empty() && query.is_empty()) {
+            return self.url.clone();
+        }
+        let Ok(mut url) = Url::parse(&self.url) else {
+            return self.url.clone();
+        };
+        if !path.is_empty() {
+            let joined = format!("{}{}", url.path().trim_end_matches('/'), path);
+            url.set_path(&joined);
+        }
+        if !query.is_empty() {
+            let joined = match url.query().filter(|existing| !existing.is_empty()) {
+                Some(existing) => format!("{}&{}", existing, query),
+                None => query.to_string(),
+            };
+            url.set_query(Some(&joined));
+        }
+        url.as_str().into()
+    }
+}
+
+/// Size and effectiveness of an in-process cache since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Share of lookups answered from the cache; `None` before the first lookup
+    pub hit_ratio: Option<f64>,
+}
+
+impl CacheStats {
+    pub fn new(entries: usize, capacity: usize, hits: u64, misses: u64) -> Self {
+        let lookups = hits + misses;
+        Self {
+            entries,
+            capacity,
+            hits,
+            misses,
+            hit_ratio: (lookups > 0).then(|| hits as f64 / lookups as f64),
+        }
+    }
+}
+
+/// Bounded LRU cache of short code → destination for the redirect path
+pub struct RedirectCache {
+    shards: Vec<Mutex<LruCache<String, Target>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl RedirectCache {
+    /// Creates a cache holding roughly `capacity` entries; a capacity of 0 disables caching
+    pub fn new(capacity: usize) -> Self {
+        let shards = match NonZeroUsize::new(capacity.div_ceil(SHARDS)) {
+            Some(per_shard) => (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+            None => Vec::new(),
+        };
+        Self { shards, hits: AtomicU64::new(0), misses: AtomicU64::new(0) }
+    }
+
+    fn shard(&self, short_code: &str) -> Option<&Mutex<LruCache<String, Target>>> {
+        if self.shards.is_empty() {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        short_code.hash(&mut hasher);
+        Some(&self.shards[hasher.finish() as usize % self.shards.len()])
+    }
+
+    pub fn get(&self, short_code: &str) -> Option<Target> {
+        let mut shard = self.shard(short_code)?.lock().ok()?;
+        let target = shard.get(short_code).cloned();
+        let counter = if target.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        target
+    }
+
+    pub fn insert(&self, short_code: &str, target: Target) {
+        if let Some(mut shard) = self.shard(short_code).and_then(|shard| shard.lock().ok()) {
+            shard.put(short_code.to_string(), target);
+        }
+    }
+
+    /// Drops a cached entry, e.g. after its destination changed; returns whether one was cached
+    pub fn invalidate(&self, short_code: &str) -> bool {
+        self.shard(short_code)
+            .and_then(|shard| shard.lock().ok())
+            .is_some_and(|mut shard| shard.pop(short_code).is_some())
+    }
+
+    /// Drops every cached entry, returning how many there were
+    pub fn clear(&self) -> usize {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.lock().ok())
+            .map(|mut shard| {
+                let len = shard.len();
+                shard.clear();
+                len
+            })
+            .sum()
+    }
+
+    /// Whether every shard can be used; a shard whose lock was poisoned by a panic silently
+    /// stops caching its codes
+    pub fn is_healthy(&self) -> bool {
+        self.shards.iter().all(|shard| shard.lock().is_ok())
+    }
+
+    /// Entries the cache can hold
+    pub fn capacity(&self) -> usize {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.lock().ok().map(|shard| shard.cap().get()))
+            .sum()
+    }
+
+    /// Number of cached entries
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .filter_map(|shard| shard.lock().ok().map(|shard| shard.len()))
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lookups are only counted while the cache is enabled
+    pub fn stats(&self) -> CacheStats {
+        CacheStats::new(self.len(), self.capacity(), self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+/// Number of independently locked shards, to keep concurrent redirects from contending
+const SHARDS: usize = 16;
+
+/// Visitor and code pairs remembered per shard; the least recently seen are forgotten first
+const PAIRS_PER_SHARD: usize = 16_384;
+
+/// Remembers which visitor opened which link recently, so refreshes within the window are
+/// not counted again.
+///
+/// Pairs are kept as a hash of the anonymized visitor and the short code, never as the
+/// visitor itself. When more pairs are seen than fit, the oldest are forgotten early and
+/// their next visit counts again.
+pub struct VisitDeduplicator {
+    window: Duration,
+    shards: Vec<Mutex<LruCache<u64, Instant>>>,
+}
+
+impl VisitDeduplicator {
+    /// Counts one visit per visitor and code within `window`; `None` if the window is zero
+    pub fn new(window: Duration) -> Option<Self> {
+        let per_shard = NonZeroUsize::new(PAIRS_PER_SHARD).expect("shard capacity is non-zero");
+        (!window.is_zero()).then(|| Self {
+            window,
+            shards: (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+        })
+    }
+
+    /// Whether a visit of `short_code` by `visitor` should be counted, remembering it if so.
+    /// The window starts at the counted visit, so steady refreshing does not extend it.
+    pub fn is_first(&self, short_code: &str, visitor: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (visitor, short_code).hash(&mut hasher);
+        let key = hasher.finish();
+        let shard = &self.shards[key as usize % SHARDS];
+        let Ok(mut shard) = shard.lock() else { return true };
+
+        let now = Instant::now();
+        match shard.get(&key) {
+            Some(counted_at) if now.duration_since(*counted_at) < self.window => false,
+            _ => {
+                shard.put(key, now);
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use actix_web::http::header::{HeaderName, HeaderValue};
+
+/// Most extra headers a link may send with its redirects
+pub const MAX_REDIRECT_HEADERS: usize = 10;
+
+/// Longest value of an extra redirect header, in bytes
+pub const MAX_REDIRECT_HEADER_LENGTH: usize = 500;
+
+/// Standard headers links may send with their redirects; custom `X-` headers are allowed too.
+/// Anything that changes where or how a redirect goes, sets cookies or controls caching is not.
+pub const REDIRECT_HEADER_NAMES: &[&str] = &["link", "referrer-policy", "timing-allow-origin", "x-robots-tag"];
+
+/// Prefixes of `X-` headers that proxies and web servers act on, such as nginx's
+/// `X-Accel-Redirect` serving an internal location; links may not send these
+pub const DENIED_REDIRECT_HEADER_PREFIXES: &[&str] =
+    &["x-accel-", "x-sendfile", "x-lighttpd-", "x-forwarded-", "x-real-ip", "x-original-"];
+
+/// Why an extra redirect header is refused, for the validation error's message; `None` if it is
+/// allowed. Names are compared in lowercase.
+pub fn check_redirect_header(name: &str, value: &str) -> Option<String> {
+    let lowercase = name.to_ascii_lowercase();
+    if HeaderName::from_bytes(lowercase.as_bytes()).is_err() {
+        return Some(format!("{:?} is not a valid header name", name));
+    }
+    if DENIED_REDIRECT_HEADER_PREFIXES.iter().any(|prefix| lowercase.starts_with(prefix)) {
+        return Some(format!("{} cannot be set; proxies act on it", name));
+    }
+    let custom = lowercase.starts_with("x-") && lowercase.len() > 2;
+    if !custom && !REDIRECT_HEADER_NAMES.contains(&lowercase.as_str()) {
+        return Some(format!(
+            "{} cannot be set; allowed are {} and custom X- headers",
+            name,
+            REDIRECT_HEADER_NAMES.join(", ")
+        ));
+    }
+    if value.len() > MAX_REDIRECT_HEADER_LENGTH {
+        return Some(format!("{} must be at most {} bytes", name, MAX_REDIRECT_HEADER_LENGTH));
+    }
+    // Visible ASCII only, so the value is sent exactly as stored
+    if value.trim().is_empty() || !value.bytes().all(|b| b == b' ' || b == b'\t' || b.is_ascii_graphic()) {
+        return Some(format!("{} must be a non-empty value of visible ASCII characters", name));
+    }
+    HeaderValue::from_str(value).err().map(|_| format!("{} is not a valid header value", name))
+}
@@ -0,0 +1,57 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+
+/// Number of independently locked shards, to keep concurrent redirects from contending
+const SHARDS: usize = 16;
+
+/// Codes tracked per shard; the least recently hit are forgotten first
+const CODES_PER_SHARD: usize = 4_096;
+
+/// Length of each counting window
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Fixed-window redirect limit per short code, protecting destinations from floods
+pub struct HotLinkLimiter {
+    limit: u32,
+    shards: Vec<Mutex<LruCache<String, (Instant, u32)>>>,
+}
+
+impl HotLinkLimiter {
+    /// Allows `limit` redirects per second and short code
+    pub fn new(limit: u32) -> Self {
+        let per_shard = NonZeroUsize::new(CODES_PER_SHARD).expect("shard capacity is non-zero");
+        Self {
+            limit,
+            shards: (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+        }
+    }
+
+    /// Counts a redirect of `short_code` and returns whether it is within the limit
+    pub fn check(&self, short_code: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        short_code.hash(&mut hasher);
+        let shard = &self.shards[hasher.finish() as usize % SHARDS];
+        let Ok(mut shard) = shard.lock() else { return true };
+
+        let now = Instant::now();
+        match shard.get_mut(short_code) {
+            Some((started, count)) if now.duration_since(*started) < WINDOW => {
+                *count = count.saturating_add(1);
+                *count <= self.limit
+            }
+            Some(window) => {
+                *window = (now, 1);
+                self.limit >= 1
+            }
+            None => {
+                shard.put(short_code.to_string(), (now, 1));
+                self.limit >= 1
+            }
+        }
+    }
+}
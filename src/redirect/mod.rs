@@ -0,0 +1,642 @@
+//! Lean redirect path.
+//!
+//! Redirects are the hottest request by far, so they skip `UrlService`: [`Redirector`] serves
+//! destinations from an in-process LRU cache and counts visits in memory, flushing them to
+//! storage in the background instead of writing inside the request. The submodules hold the
+//! optional pieces it can be configured with, from rate limits to signed links and receipts.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::debug;
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{RedirectHeaders, ShortenedUrl};
+use crate::preview::escape_html;
+#[cfg(feature = "peer-cache")]
+use crate::peers::{PeerCache, PeerLookup};
+use crate::runaway::RunawayGuard;
+use crate::services::CodeLookup;
+use crate::storage::StorageRef;
+
+mod cache;
+mod dedup;
+mod headers;
+mod limiter;
+mod receipts;
+mod resolve;
+mod responses;
+mod signing;
+mod static_links;
+mod suggestions;
+mod visits;
+
+pub use cache::{CacheStats, RedirectCache, Target};
+pub use dedup::VisitDeduplicator;
+pub use headers::{
+    check_redirect_header, DENIED_REDIRECT_HEADER_PREFIXES, MAX_REDIRECT_HEADERS, MAX_REDIRECT_HEADER_LENGTH,
+    REDIRECT_HEADER_NAMES,
+};
+pub use limiter::HotLinkLimiter;
+pub use receipts::{Receipt, ReceiptSigner, RECEIPT_PARAM};
+pub use resolve::{Expansion, ResolveAccess};
+pub use responses::{CachedResponse, ResponseCache};
+pub use signing::{LinkSigner, SignedQuery, EXPIRY_PARAM, SIGNATURE_PARAM};
+pub use static_links::StaticLinks;
+pub use suggestions::{SuggestionScope, TypoIndex, MAX_SUGGESTIONS};
+pub use visits::{take_source, BufferedVisits, EventSampling, VisitRecorder, MAX_SOURCE_LENGTH, SOURCE_PARAM};
+
+/// Page served instead of redirecting while a link is over its rate limit
+pub const BUSY_PAGE: &str = include_str!("busy.html");
+
+/// Page served for unknown short codes under [`MissingCodePolicy::Page`]
+pub const NOT_FOUND_PAGE: &str = include_str!("not_found.html");
+
+/// Page forwarding visitors of links with an analytics snippet, see [`tracking_page`]
+const TRACKING_PAGE: &str = include_str!("tracking.html");
+
+/// Where a redirect sends its visitor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    pub url: Arc<str>,
+    /// Owner's analytics snippet to fire on the way, when tracking pages are enabled
+    pub tracking_snippet: Option<Arc<str>>,
+    /// Extra response headers the link sends with its redirects, see [`check_redirect_header`]
+    pub headers: Option<Arc<RedirectHeaders>>,
+    /// Ask the browser not to send the destination a referrer
+    pub hide_referrer: bool,
+}
+
+/// How the redirect route answers short codes that do not exist; the API always returns JSON
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum MissingCodePolicy {
+    /// The JSON 404 the API returns
+    #[default]
+    Json,
+    /// A static HTML 404 page
+    Page,
+    /// A redirect to this URL, with `{code}` replaced by the URL-encoded short code
+    Redirect(String),
+}
+
+impl MissingCodePolicy {
+    /// Where to send a visitor of an unknown short code, under [`MissingCodePolicy::Redirect`]
+    pub fn fallback_url(&self, short_code: &str) -> Option<String> {
+        let Self::Redirect(template) = self else { return None };
+        let code: String = url::form_urlencoded::byte_serialize(short_code.as_bytes()).collect();
+        Some(template.replace("{code}", &code))
+    }
+}
+
+/// The [`NOT_FOUND_PAGE`], listing `suggestions` as links when there are any
+pub fn not_found_page(suggestions: &[String]) -> Cow<'static, str> {
+    if suggestions.is_empty() {
+        return Cow::Borrowed(NOT_FOUND_PAGE);
+    }
+    let items: String = suggestions
+        .iter()
+        .map(|code| {
+            let code = escape_html(code);
+            format!("<li><a href=\"/{}\">{}</a></li>", code, code)
+        })
+        .collect();
+    let list = format!("<p>Did you mean:</p><ul>{}</ul>\n</body>", items);
+    Cow::Owned(NOT_FOUND_PAGE.replacen("</body>", &list, 1))
+}
+
+/// The [`TRACKING_PAGE`] for a destination, running the owner's `snippet` before it sends the
+/// visitor on once the page has loaded; without scripts, visitors are forwarded right away
+pub fn tracking_page(destination: &str, snippet: &str) -> String {
+    // Kept from closing the script it is embedded in
+    let quoted = serde_json::to_string(destination)
+        .unwrap_or_default()
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e");
+    let destination = escape_html(destination);
+    let values = [("{destination_js}", &*quoted), ("{destination}", &*destination), ("{snippet}", snippet)];
+
+    // One pass, so nothing filled in is taken for a placeholder
+    let mut page = String::with_capacity(TRACKING_PAGE.len() + snippet.len() + 3 * destination.len());
+    let mut rest = TRACKING_PAGE;
+    while let Some(at) = rest.find('{') {
+        page.push_str(&rest[..at]);
+        rest = &rest[at..];
+        match values.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                page.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                page.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    page.push_str(rest);
+    page
+}
+
+/// Tuning for the redirect path
+#[derive(Clone, Debug)]
+pub struct RedirectConfig {
+    /// Destinations kept in the in-process cache; 0 disables caching
+    pub cache_capacity: usize,
+    /// How often batched visit counts are written to storage
+    pub visit_flush_interval: Duration,
+    /// How short codes from the path are matched
+    pub code_lookup: CodeLookup,
+    /// Redirects allowed per second and short code; `None` is unlimited
+    pub rate_limit: Option<u32>,
+    /// How long finished redirect responses are replayed; zero disables the micro-cache
+    pub response_cache_ttl: Duration,
+    /// Send `X-Robots-Tag: noindex` with redirects
+    pub noindex: bool,
+    /// Answer to unknown short codes
+    pub missing_code: MissingCodePolicy,
+    /// Checks signed links; without it, signed-only links never redirect
+    pub signer: Option<LinkSigner>,
+    /// Visits of a link by the same visitor within this window count once; zero counts them all
+    pub visit_dedup_window: Duration,
+    /// Which visits of busy links are kept as visit events
+    pub event_sampling: EventSampling,
+    /// Count visits; off on instances that must not write to the database
+    pub record_visits: bool,
+    /// Serve links with an analytics snippet through a forwarding page that fires it
+    pub tracking_pages: bool,
+    /// Appends a signed receipt to destinations; none are appended without it
+    pub receipts: Option<ReceiptSigner>,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            cache_capacity: 10_000,
+            visit_flush_interval: Duration::from_secs(1),
+            code_lookup: CodeLookup::default(),
+            rate_limit: None,
+            response_cache_ttl: Duration::ZERO,
+            noindex: false,
+            missing_code: MissingCodePolicy::default(),
+            signer: None,
+            visit_dedup_window: Duration::ZERO,
+            event_sampling: EventSampling::default(),
+            record_visits: true,
+            tracking_pages: false,
+            receipts: None,
+        }
+    }
+}
+
+/// Resolves short codes for redirects.
+///
+/// Static links are looked up first, then the cache, then storage. Visits are counted through a
+/// [`VisitRecorder`], once per visitor within the dedup window if one is set. When configured,
+/// it also applies a per-code rate limit, disables runaway links, checks signed links, appends
+/// receipts and suggests near codes for unknown ones.
+pub struct Redirector {
+    storage: StorageRef,
+    cache: RedirectCache,
+    visits: Arc<VisitRecorder>,
+    dedup: Option<VisitDeduplicator>,
+    record_visits: bool,
+    code_lookup: CodeLookup,
+    limiter: Option<HotLinkLimiter>,
+    responses: Option<ResponseCache>,
+    noindex: bool,
+    missing_code: MissingCodePolicy,
+    signer: Option<LinkSigner>,
+    tracking_pages: bool,
+    receipts: Option<ReceiptSigner>,
+    runaway: Option<Arc<RunawayGuard>>,
+    static_links: StaticLinks,
+    typos: Option<Arc<TypoIndex>>,
+    #[cfg(feature = "peer-cache")]
+    peers: Option<PeerCache>,
+}
+
+impl Redirector {
+    /// Creates the redirector and starts its background visit flusher; requires a Tokio runtime
+    pub fn new(storage: StorageRef, config: RedirectConfig) -> Self {
+        let visits = Arc::new(VisitRecorder::new(storage.clone(), config.event_sampling));
+        if config.record_visits {
+            VisitRecorder::spawn_flusher(&visits, config.visit_flush_interval);
+        }
+
+        Self {
+            storage,
+            cache: RedirectCache::new(config.cache_capacity),
+            visits,
+            dedup: VisitDeduplicator::new(config.visit_dedup_window),
+            record_visits: config.record_visits,
+            code_lookup: config.code_lookup,
+            limiter: config.rate_limit.map(HotLinkLimiter::new),
+            responses: ResponseCache::new(config.response_cache_ttl, config.cache_capacity),
+            noindex: config.noindex,
+            missing_code: config.missing_code,
+            signer: config.signer,
+            tracking_pages: config.tracking_pages,
+            receipts: config.receipts,
+            runaway: None,
+            static_links: StaticLinks::default(),
+            typos: None,
+            #[cfg(feature = "peer-cache")]
+            peers: None,
+        }
+    }
+
+    /// Serves permanent links defined outside storage, ahead of stored links with the same code.
+    /// Visits of static links are never counted.
+    pub fn with_static_links(mut self, links: StaticLinks) -> Self {
+        self.static_links = links.normalized(&self.code_lookup);
+        self
+    }
+
+    /// Disables links whose redirects run far above their usual rate
+    pub fn with_runaway_guard(mut self, guard: Arc<RunawayGuard>) -> Self {
+        self.runaway = Some(guard);
+        self
+    }
+
+    /// Suggests the nearest existing codes for unknown ones
+    pub fn with_typo_index(mut self, index: Arc<TypoIndex>) -> Self {
+        self.typos = Some(index);
+        self
+    }
+
+    /// Existing codes close to an unknown one, nearest first; none without a typo index
+    pub fn suggestions(&self, short_code: &str) -> Vec<String> {
+        match &self.typos {
+            Some(index) => index.suggest(&self.code_lookup.normalize(short_code)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Asks the owning peer for codes missing from the cache before reading them from storage
+    #[cfg(feature = "peer-cache")]
+    pub fn with_peers(mut self, peers: PeerCache) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    #[cfg(feature = "peer-cache")]
+    pub fn peers(&self) -> Option<&PeerCache> {
+        self.peers.as_ref()
+    }
+
+    /// Returns the destination of a short code and counts the visit.
+    /// Fails with `RateLimitExceeded`, without touching storage, while the code is over its limit.
+    pub async fn resolve(&self, short_code: &str) -> UrlShortenerResult<Arc<str>> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        let (target, short_code) = self.target(short_code).await?;
+        self.check_signature(&short_code, &target, &SignedQuery::default())?;
+        self.count_visit(&short_code, None, None);
+        Ok(target.url)
+    }
+
+    /// Like [`resolve`](Self::resolve), for a request path that may continue after the short code.
+    /// Pass-through links get the rest of the path and the query appended to their destination;
+    /// other links only match when the rest is trailing slashes. Signature parameters in the
+    /// query are checked and never passed on, nor is the `src` tag the visit is attributed to.
+    /// With receipts enabled, the destination gets one for this redirect appended.
+    /// The visit is recorded as by `visitor`, an anonymized client address.
+    pub async fn resolve_path(
+        &self,
+        short_code: &str,
+        path: &str,
+        query: &str,
+        visitor: Option<&str>,
+    ) -> UrlShortenerResult<Redirect> {
+        // Trailing slashes belong to the code, which the code lookup may trim
+        let (short_code, path) = if !path.is_empty() && path.trim_start_matches('/').is_empty() {
+            (Cow::Owned(format!("{}{}", short_code, path)), "")
+        } else {
+            (Cow::Borrowed(short_code), path)
+        };
+        let short_code = &*self.code_lookup.normalize(&short_code);
+        let (target, short_code) = self.target(short_code).await?;
+        if !path.is_empty() && !target.pass_through {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        let query = SignedQuery::parse(query);
+        self.check_signature(&short_code, &target, &query)?;
+        let (source, rest) = take_source(&query.rest);
+
+        self.count_visit(&short_code, visitor, source.as_deref());
+        let mut url = target.with_path(path, &rest);
+        if let Some(receipts) = &self.receipts {
+            url = receipts.stamp(&short_code, &url, Utc::now());
+        }
+        Ok(Redirect {
+            url,
+            tracking_snippet: target.tracking_snippet.filter(|_| self.tracking_pages),
+            headers: target.redirect_headers,
+            hide_referrer: target.hide_referrer,
+        })
+    }
+
+    /// Signed-only links need a valid signature; other links are checked only when the
+    /// request carries one and signing is enabled
+    fn check_signature(&self, short_code: &str, target: &Target, query: &SignedQuery) -> UrlShortenerResult<()> {
+        match &self.signer {
+            Some(signer) if target.signed_only || query.is_signed() => {
+                signer.verify(short_code, query.signature, query.expires_at, Utc::now())
+            }
+            None if target.signed_only => {
+                Err(UrlShortenerErrorType::InvalidSignature("signed URLs are not enabled".to_string()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Signer for time-boxed links, when signing is enabled
+    pub fn signer(&self) -> Option<&LinkSigner> {
+        self.signer.as_ref()
+    }
+
+    /// Signer of redirect receipts, when they are enabled
+    pub fn receipts(&self) -> Option<&ReceiptSigner> {
+        self.receipts.as_ref()
+    }
+
+    /// Looks up a normalized short code through the cache, applying the rate limit and the
+    /// runaway guard. Returns the code the link answers to now, which differs for a retired code
+    /// during its grace period; retired codes are not cached, so they stop redirecting once it ends.
+    async fn target<'a>(&self, short_code: &'a str) -> UrlShortenerResult<(Target, Cow<'a, str>)> {
+        if let Some(limiter) = &self.limiter {
+            if !limiter.check(short_code) {
+                debug!(short_code = %short_code, "Redirect rate limit exceeded");
+                return Err(UrlShortenerErrorType::RateLimitExceeded.into());
+            }
+        }
+        if let Some(target) = self.static_links.get(short_code) {
+            return Ok((target.clone(), Cow::Borrowed(short_code)));
+        }
+        let (target, short_code) = self.lookup(short_code).await?;
+        if !self.within_baseline(&short_code) {
+            return Err(UrlShortenerErrorType::LinkDisabled.into());
+        }
+        Ok((target, short_code))
+    }
+
+    /// Looks up a normalized short code through the cache, then the owning peer, then storage
+    async fn lookup<'a>(&self, short_code: &'a str) -> UrlShortenerResult<(Target, Cow<'a, str>)> {
+        if let Some(target) = self.cache.get(short_code) {
+            return Ok((target, Cow::Borrowed(short_code)));
+        }
+
+        debug!(short_code = %short_code, "Redirect cache miss");
+        #[cfg(feature = "peer-cache")]
+        if let Some(found) = self.peer_target(short_code).await? {
+            return Ok(found);
+        }
+        self.load(short_code).await
+    }
+
+    /// Counts a redirect against the runaway guard, if there is one. A link over its limit is
+    /// dropped from the caches, so it is read from storage, where it is disabled, once this
+    /// instance forgets its rate.
+    fn within_baseline(&self, short_code: &str) -> bool {
+        let Some(guard) = &self.runaway else { return true };
+        if guard.check(short_code) {
+            return true;
+        }
+        self.cache.invalidate(short_code);
+        if let Some(responses) = &self.responses {
+            responses.invalidate(short_code);
+        }
+        false
+    }
+
+    /// Looks up a normalized short code through static links, the cache and storage only,
+    /// without the rate limit or asking peers, as for a peer's lookup
+    pub async fn local_target<'a>(&self, short_code: &'a str) -> UrlShortenerResult<(Target, Cow<'a, str>)> {
+        if let Some(target) = self.static_links.get(short_code) {
+            return Ok((target.clone(), Cow::Borrowed(short_code)));
+        }
+        if let Some(target) = self.cache.get(short_code) {
+            return Ok((target, Cow::Borrowed(short_code)));
+        }
+        self.load(short_code).await
+    }
+
+    /// Asks the peer owning a short code; `None` when this node owns it or the peer did not answer
+    #[cfg(feature = "peer-cache")]
+    async fn peer_target<'a>(&self, short_code: &'a str) -> UrlShortenerResult<Option<(Target, Cow<'a, str>)>> {
+        let Some(peers) = &self.peers else {
+            return Ok(None);
+        };
+        match peers.lookup(short_code).await {
+            PeerLookup::Found(found) if found.short_code != short_code => {
+                let current = found.short_code.clone();
+                Ok(Some((found.into(), Cow::Owned(current))))
+            }
+            PeerLookup::Found(found) => {
+                let target = Target::from(found);
+                self.cache.insert(short_code, target.clone());
+                Ok(Some((target, Cow::Borrowed(short_code))))
+            }
+            PeerLookup::Missing => Err(UrlShortenerErrorType::NotFound.into()),
+            PeerLookup::Unavailable => Ok(None),
+        }
+    }
+
+    /// Reads a normalized short code from storage, caching its destination
+    async fn load<'a>(&self, short_code: &'a str) -> UrlShortenerResult<(Target, Cow<'a, str>)> {
+        let url = self.storage.get_stats(short_code).await?;
+        if url.disabled_at.is_some() {
+            return Err(UrlShortenerErrorType::LinkDisabled.into());
+        }
+        let target = Target {
+            url: url.original_url.into(),
+            pass_through: url.pass_through,
+            signed_only: url.signed_only,
+            tracking_snippet: url.tracking_snippet.map(Into::into),
+            redirect_headers: url.redirect_headers.map(|headers| Arc::new(headers.0)),
+            hide_referrer: url.hide_referrer,
+        };
+        if url.short_url != short_code {
+            debug!(short_code = %short_code, current = %url.short_url, "Redirecting retired code");
+            return Ok((target, Cow::Owned(url.short_url)));
+        }
+        self.cache.insert(short_code, target.clone());
+        Ok((target, Cow::Borrowed(short_code)))
+    }
+
+    /// Reads a link without counting a visit or applying the rate limit, e.g. to show its preview
+    pub async fn link(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        let url = self.storage.get_stats(&self.code_lookup.normalize(short_code)).await?;
+        if url.disabled_at.is_some() {
+            return Err(UrlShortenerErrorType::LinkDisabled.into());
+        }
+        Ok(url)
+    }
+
+    /// The code a short code answers to now and its destination, without redirecting, counting a
+    /// visit or applying the rate limit, e.g. for tools expanding links before they are followed.
+    /// Signed-only links are reported as missing, as their destination is not meant to be seen.
+    pub async fn expand(&self, short_code: &str) -> UrlShortenerResult<(String, Arc<str>)> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        let (target, short_code) = self.local_target(short_code).await?;
+        if target.signed_only {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok((short_code.into_owned(), target.url))
+    }
+
+    /// Expands many short codes like [`expand`](Self::expand), in the order given, reading the
+    /// ones neither static nor cached from storage at once
+    pub async fn expand_all(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<Expansion>> {
+        let short_codes: Vec<String> = short_codes.iter().map(|code| self.code_lookup.normalize(code).into_owned()).collect();
+        let local: Vec<Option<Target>> = short_codes
+            .iter()
+            .map(|code| self.static_links.get(code).cloned().or_else(|| self.cache.get(code)))
+            .collect();
+        let wanted: Vec<String> = short_codes
+            .iter()
+            .zip(&local)
+            .filter(|(_, target)| target.is_none())
+            .map(|(code, _)| code.clone())
+            .collect();
+        let stored: HashMap<String, ShortenedUrl> = match wanted.is_empty() {
+            true => HashMap::new(),
+            false => self.storage.get_stats_many(&wanted).await?.into_iter().collect(),
+        };
+
+        Ok(short_codes
+            .into_iter()
+            .zip(local)
+            .map(|(short_code, target)| match (target, stored.get(&short_code)) {
+                (Some(target), _) if !target.signed_only => Expansion::Found { short_code, url: target.url },
+                (None, Some(url)) if url.disabled_at.is_some() => Expansion::Disabled,
+                (None, Some(url)) if !url.signed_only => Expansion::Found {
+                    short_code: url.short_url.clone(),
+                    url: url.original_url.as_str().into(),
+                },
+                _ => Expansion::Missing,
+            })
+            .collect())
+    }
+
+    /// Returns the stored redirect response for a short code and counts the visit.
+    /// `None` when nothing is stored, or when the code is over its rate limit or was stopped by
+    /// the runaway guard and the request has to go through [`resolve`](Self::resolve) to be
+    /// turned away.
+    pub fn replay(&self, short_code: &str, visitor: Option<&str>) -> Option<CachedResponse> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        let response = self.responses.as_ref()?.get(short_code)?;
+        if self.limiter.as_ref().is_some_and(|limiter| !limiter.check(short_code)) {
+            return None;
+        }
+        if !self.within_baseline(short_code) {
+            return None;
+        }
+        self.count_visit(short_code, visitor, None);
+        Some(response)
+    }
+
+    /// Records a visit unless the same visitor already had one counted within the dedup window.
+    /// Visits by unknown visitors are always counted; static links have nothing to count them on.
+    fn count_visit(&self, short_code: &str, visitor: Option<&str>, source: Option<&str>) {
+        if !self.record_visits || self.static_links.contains(short_code) {
+            return;
+        }
+        if let (Some(dedup), Some(visitor)) = (&self.dedup, visitor) {
+            if !dedup.is_first(short_code, visitor) {
+                debug!(short_code, "Not counting a repeat visit within the dedup window");
+                return;
+            }
+        }
+        self.visits.record(short_code, visitor, source);
+    }
+
+    /// Keeps a finished redirect response for replay; a no-op without the micro-cache
+    pub fn store_response(&self, short_code: &str, response: CachedResponse) {
+        if let Some(responses) = &self.responses {
+            responses.insert(&self.code_lookup.normalize(short_code), response);
+        }
+    }
+
+    /// Whether finished redirect responses are replayed
+    pub fn caches_responses(&self) -> bool {
+        self.responses.is_some()
+    }
+
+    /// Whether redirects ask search engines not to index them
+    pub fn noindex(&self) -> bool {
+        self.noindex
+    }
+
+    pub fn missing_code(&self) -> &MissingCodePolicy {
+        &self.missing_code
+    }
+
+    pub fn cache(&self) -> &RedirectCache {
+        &self.cache
+    }
+
+    /// Stats of the response micro-cache; `None` when it is disabled
+    pub fn response_cache_stats(&self) -> Option<CacheStats> {
+        self.responses.as_ref().map(ResponseCache::stats)
+    }
+
+    /// Visits waiting for the next flush
+    pub fn buffered_visits(&self) -> BufferedVisits {
+        self.visits.buffered()
+    }
+
+    /// Loads the destinations of the `limit` most visited links into the cache, so a new
+    /// instance does not send its first requests for popular codes to storage. Returns how
+    /// many were loaded, which is at most the cache's capacity.
+    pub async fn warm_up(&self, limit: usize) -> UrlShortenerResult<usize> {
+        let limit = limit.min(self.cache.capacity());
+        if limit == 0 {
+            return Ok(0);
+        }
+        let urls = self.storage.top_visited_urls(limit as i64).await?;
+        // Least visited first, so the most visited are the last to be evicted
+        for url in urls.iter().rev() {
+            let target = Target {
+                url: url.original_url.as_str().into(),
+                pass_through: url.pass_through,
+                signed_only: url.signed_only,
+                tracking_snippet: url.tracking_snippet.as_deref().map(Into::into),
+                redirect_headers: url.redirect_headers.as_ref().map(|headers| Arc::new(headers.0.clone())),
+                hide_referrer: url.hide_referrer,
+            };
+            self.cache.insert(&url.short_url, target);
+        }
+        Ok(urls.len())
+    }
+
+    /// Drops the cached destination and response of a short code, so the next redirect reads
+    /// it from storage, and forgets its rate if the runaway guard had stopped it
+    pub fn invalidate(&self, short_code: &str) -> bool {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        if let Some(guard) = &self.runaway {
+            guard.reset(short_code);
+        }
+        if let Some(responses) = &self.responses {
+            responses.invalidate(short_code);
+        }
+        self.cache.invalidate(short_code)
+    }
+
+    /// Drops every cached destination and response, returning how many destinations there were
+    pub fn clear_cache(&self) -> usize {
+        if let Some(responses) = &self.responses {
+            responses.clear();
+        }
+        self.cache.clear()
+    }
+
+    /// Writes pending visit counts to storage; call before shutdown or when stats must be current
+    pub async fn flush_visits(&self) -> u64 {
+        self.visits.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use url::Url;
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+
+/// Query parameter a receipt is appended to destinations as
+pub const RECEIPT_PARAM: &str = "ref";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a valid receipt vouches for: a redirect through `short_code` to `host` at `issued_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub short_code: String,
+    /// Host of the destination the visitor was sent to
+    pub host: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// Signs receipts appended to destinations on redirect, so a destination can check with us that
+/// a visitor really came through one of our links.
+///
+/// A receipt is `{payload}.{signature}`, both base64url: the payload holds the short code, the
+/// destination's host and the issue time, and the signature is an HMAC-SHA256 over it. A
+/// receipt cannot be moved to another site, and is only honored for `max_age` after its issue.
+#[derive(Clone)]
+pub struct ReceiptSigner {
+    secret: Vec<u8>,
+    max_age: Duration,
+}
+
+impl ReceiptSigner {
+    pub fn new(secret: &str, max_age: Duration) -> Self {
+        Self { secret: secret.as_bytes().to_vec(), max_age }
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    /// Receipt for a redirect through `short_code` to `host` at `now`
+    pub fn issue(&self, short_code: &str, host: &str, now: DateTime<Utc>) -> String {
+        let payload = format!("{}\n{}\n{}", short_code, host, now.timestamp());
+        let signature = self.mac(payload.as_bytes()).finalize().into_bytes();
+        format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// The destination with a receipt for a redirect through `short_code` appended to its query;
+    /// destinations that do not parse are returned as they are
+    pub fn stamp(&self, short_code: &str, destination: &str, now: DateTime<Utc>) -> Arc<str> {
+        let Ok(mut url) = Url::parse(destination) else {
+            return destination.into();
+        };
+        let receipt = self.issue(short_code, url.host_str().unwrap_or_default(), now);
+        url.query_pairs_mut().append_pair(RECEIPT_PARAM, &receipt);
+        url.as_str().into()
+    }
+
+    /// Checks a receipt at `now`, returning what it vouches for
+    pub fn verify(&self, receipt: &str, now: DateTime<Utc>) -> UrlShortenerResult<Receipt> {
+        let invalid = |reason: &str| UrlShortenerErrorType::InvalidSignature(reason.to_string()).into();
+
+        let (payload, signature) = receipt.split_once('.').ok_or_else(|| invalid("the receipt is malformed"))?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid("the receipt is malformed"))?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid("the receipt is malformed"))?;
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid("the receipt signature does not match"))?;
+
+        // Signed by us, so well formed unless the format changed
+        let payload = String::from_utf8(payload).map_err(|_| invalid("the receipt is malformed"))?;
+        let mut fields = payload.splitn(3, '\n');
+        let (Some(short_code), Some(host), Some(issued_at)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(invalid("the receipt is malformed"));
+        };
+        let issued_at = issued_at
+            .parse()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or_else(|| invalid("the receipt is malformed"))?;
+        if now.signed_duration_since(issued_at).to_std().is_ok_and(|age| age > self.max_age) {
+            return Err(invalid("the receipt has expired"));
+        }
+        Ok(Receipt { short_code: short_code.to_string(), host: host.to_string(), issued_at })
+    }
+}
+
+impl fmt::Debug for ReceiptSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReceiptSigner").field("max_age", &self.max_age).finish_non_exhaustive()
+    }
+}
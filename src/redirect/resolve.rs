@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use super::HotLinkLimiter;
+
+/// What a short code expands to through the resolve API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expansion {
+    /// The code the link answers to now and its destination
+    Found { short_code: String, url: Arc<str> },
+    Disabled,
+    /// No link has the code, or its destination is not meant to be seen
+    Missing,
+}
+
+/// Who may expand short links through the resolve API, and how often
+pub struct ResolveAccess {
+    require_api_key: bool,
+    limiter: Option<HotLinkLimiter>,
+}
+
+impl ResolveAccess {
+    /// Allows `rate_limit` lookups per second and client; `None` is unlimited. Lookups are
+    /// counted like the redirects of a hot link, with the client in place of the code.
+    pub fn new(require_api_key: bool, rate_limit: Option<u32>) -> Self {
+        Self { require_api_key, limiter: rate_limit.map(HotLinkLimiter::new) }
+    }
+
+    pub fn requires_api_key(&self) -> bool {
+        self.require_api_key
+    }
+
+    /// Counts a lookup by `client`, the owner of its API key or its address, and returns
+    /// whether it is within the limit
+    pub fn check(&self, client: &str) -> bool {
+        self.limiter.as_ref().is_none_or(|limiter| limiter.check(client))
+    }
+}
+
+impl Default for ResolveAccess {
+    /// Open to anyone, without a limit
+    fn default() -> Self {
+        Self::new(false, None)
+    }
+}
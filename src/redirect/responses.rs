@@ -0,0 +1,80 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::http::{header::HeaderMap, StatusCode};
+use lru::LruCache;
+
+use super::CacheStats;
+
+/// A redirect response as the handler produced it, without a body
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+}
+
+/// Finished redirect responses kept for a short TTL, keyed by short code.
+///
+/// Sits in front of [`RedirectCache`](super::RedirectCache): during a spike every request for a
+/// code within the TTL is answered with the stored response instead of running the handler.
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<String, (Instant, CachedResponse)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Creates a cache of at most `capacity` responses; `None` if the TTL or capacity is zero
+    pub fn new(ttl: Duration, capacity: usize) -> Option<Self> {
+        let capacity = NonZeroUsize::new(capacity)?;
+        (!ttl.is_zero()).then(|| Self {
+            ttl,
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the stored response for a short code unless it is older than the TTL
+    pub fn get(&self, short_code: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().ok()?;
+        let response = match entries.get(short_code) {
+            Some((stored_at, response)) if stored_at.elapsed() < self.ttl => Some(response.clone()),
+            Some(_) => {
+                entries.pop(short_code);
+                None
+            }
+            None => None,
+        };
+        let counter = if response.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    pub fn insert(&self, short_code: &str, response: CachedResponse) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.put(short_code.to_string(), (Instant::now(), response));
+        }
+    }
+
+    pub fn invalidate(&self, short_code: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.pop(short_code);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    /// Expired responses count as entries until they are next looked up or evicted
+    pub fn stats(&self) -> CacheStats {
+        let (len, capacity) = self.entries.lock().map(|entries| (entries.len(), entries.cap().get())).unwrap_or_default();
+        CacheStats::new(len, capacity, self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+
+/// Query parameter holding a signed link's signature
+pub const SIGNATURE_PARAM: &str = "sig";
+
+/// Query parameter holding a signed link's expiry, in seconds since the Unix epoch
+pub const EXPIRY_PARAM: &str = "exp";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs and checks time-boxed variants of short links, `/{code}?exp=...&sig=...`.
+///
+/// The signature is an HMAC-SHA256 over the stored short code and the expiry, so a signed
+/// link cannot be moved to another code or extended, and it stops working once the secret
+/// changes.
+#[derive(Clone)]
+pub struct LinkSigner {
+    secret: Vec<u8>,
+}
+
+impl LinkSigner {
+    pub fn new(secret: &str) -> Self {
+        Self { secret: secret.as_bytes().to_vec() }
+    }
+
+    fn mac(&self, short_code: &str, expires_at: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(short_code.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires_at.to_string().as_bytes());
+        mac
+    }
+
+    /// Signature for a short code that stays valid until `expires_at`
+    pub fn sign(&self, short_code: &str, expires_at: DateTime<Utc>) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(short_code, expires_at.timestamp()).finalize().into_bytes())
+    }
+
+    /// Query string of the signed variant of a short code
+    pub fn signed_query(&self, short_code: &str, expires_at: DateTime<Utc>) -> String {
+        format!(
+            "{EXPIRY_PARAM}={}&{SIGNATURE_PARAM}={}",
+            expires_at.timestamp(),
+            self.sign(short_code, expires_at)
+        )
+    }
+
+    /// Checks a request's signature parameters for a short code at `now`
+    pub fn verify(
+        &self,
+        short_code: &str,
+        signature: Option<&str>,
+        expires_at: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> UrlShortenerResult<()> {
+        let invalid = |reason: &str| UrlShortenerErrorType::InvalidSignature(reason.to_string()).into();
+
+        let (Some(signature), Some(expires_at)) = (signature, expires_at) else {
+            return Err(invalid("the link must be opened through a signed URL"));
+        };
+        let expires_at: i64 = expires_at.parse().map_err(|_| invalid("the expiry is malformed"))?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid("the signature is malformed"))?;
+        self.mac(short_code, expires_at)
+            .verify_slice(&signature)
+            .map_err(|_| invalid("the signature does not match"))?;
+        if expires_at <= now.timestamp() {
+            return Err(invalid("the signed URL has expired"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for LinkSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LinkSigner").finish_non_exhaustive()
+    }
+}
+
+/// Signature parameters taken off a request's query string
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SignedQuery<'a> {
+    pub signature: Option<&'a str>,
+    pub expires_at: Option<&'a str>,
+    /// The rest of the query, as sent
+    pub rest: String,
+}
+
+impl<'a> SignedQuery<'a> {
+    pub fn parse(query: &'a str) -> Self {
+        let mut parsed = Self::default();
+        let mut rest = Vec::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            match pair.split_once('=') {
+                Some((SIGNATURE_PARAM, value)) => parsed.signature = Some(value),
+                Some((EXPIRY_PARAM, value)) => parsed.expires_at = Some(value),
+                _ => rest.push(pair),
+            }
+        }
+        parsed.rest = rest.join("&");
+        parsed
+    }
+
+    /// Whether the request carries any signature parameter
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some() || self.expires_at.is_some()
+    }
+}
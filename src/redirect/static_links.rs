@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use super::Target;
+use crate::services::{CodeLookup, Namespaces};
+
+/// Permanent links defined in a file, served ahead of storage and reserved against the API.
+///
+/// The file is TOML with a `[links]` table mapping codes to destinations, or to a table with
+/// the destination and whether the rest of the path is passed through:
+///
+/// ```toml
+/// [links]
+/// docs = "https://docs.example.com"
+/// status = { url = "https://status.example.com", pass_through = true }
+/// "t/handbook" = "https://wiki.example.com/handbook"
+/// ```
+///
+/// Codes in a namespace are written in their stored form, `prefix/code`.
+#[derive(Debug, Clone, Default)]
+pub struct StaticLinks {
+    links: HashMap<String, Target>,
+}
+
+#[derive(Deserialize)]
+struct StaticLinksFile {
+    #[serde(default)]
+    links: BTreeMap<String, StaticLinkEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StaticLinkEntry {
+    Url(String),
+    Link {
+        url: String,
+        #[serde(default)]
+        pass_through: bool,
+    },
+}
+
+impl StaticLinks {
+    /// Reads the file at `path`; no links without one
+    pub fn load(path: Option<&str>) -> io::Result<Self> {
+        let Some(path) = path else { return Ok(Self::default()) };
+        let source = std::fs::read_to_string(path)?;
+        Self::parse(&source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+    }
+
+    /// Parses and checks the links of a file; every problem is reported, one per line
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let file: StaticLinksFile = toml::from_str(source).map_err(|e| e.message().to_string())?;
+        let mut links = HashMap::with_capacity(file.links.len());
+        let mut problems = Vec::new();
+        for (code, entry) in file.links {
+            let (url, pass_through) = match entry {
+                StaticLinkEntry::Url(url) => (url, false),
+                StaticLinkEntry::Link { url, pass_through } => (url, pass_through),
+            };
+            if let Err(problem) = check_code(&code) {
+                problems.push(format!("'{}': {}", code, problem));
+                continue;
+            }
+            match url::Url::parse(&url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+                    let target = Target {
+                        url: url.into(),
+                        pass_through,
+                        signed_only: false,
+                        tracking_snippet: None,
+                        redirect_headers: None,
+                        hide_referrer: false,
+                    };
+                    links.insert(code, target);
+                }
+                _ => problems.push(format!("'{}': {} is not an http(s) URL", code, url)),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(Self { links })
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+
+    /// The same links, with codes in the form `lookup` matches requests in
+    pub fn normalized(self, lookup: &CodeLookup) -> Self {
+        let links = self.links.into_iter().map(|(code, target)| (lookup.normalize(&code).into_owned(), target));
+        Self { links: links.collect() }
+    }
+
+    /// Reserves every code in its namespace, or at the root. Codes whose prefix is not a
+    /// configured namespace would never be routed, so they are logged and skipped.
+    pub fn reserve(&self, namespaces: Namespaces) -> Namespaces {
+        self.links.keys().fold(namespaces, |namespaces, code| match code.split_once('/') {
+            Some((prefix, rest)) if namespaces.get(prefix).is_some() => namespaces.with_reserved(Some(prefix), &[rest]),
+            Some((prefix, _)) => {
+                warn!(short_code = %code, namespace = %prefix, "Static link is in an unknown namespace and is not served");
+                namespaces
+            }
+            None => namespaces.with_reserved(None, &[code]),
+        })
+    }
+
+    pub fn get(&self, short_code: &str) -> Option<&Target> {
+        self.links.get(short_code)
+    }
+
+    pub fn contains(&self, short_code: &str) -> bool {
+        self.links.contains_key(short_code)
+    }
+
+    pub fn codes(&self) -> impl Iterator<Item = &str> {
+        self.links.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+}
+
+/// A code must be one path segment, or a namespace prefix and one segment
+fn check_code(code: &str) -> Result<(), &'static str> {
+    if code.split('/').count() > 2 || code.split('/').any(str::is_empty) {
+        return Err("a code is one path segment, optionally after a namespace prefix");
+    }
+    if code.chars().any(|c| c.is_whitespace() || matches!(c, '?' | '#' | '%')) {
+        return Err("a code may not contain whitespace, '?', '#' or '%'");
+    }
+    Ok(())
+}
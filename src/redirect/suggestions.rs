@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::errors::UrlShortenerResult;
+use crate::storage::StorageRef;
+
+/// Suggestions offered for an unknown code
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Codes kept in the index; beyond this, later codes are left out
+const MAX_INDEXED_CODES: usize = 100_000;
+
+/// Links read from storage per page while the index is rebuilt
+const PAGE_SIZE: i64 = 1_000;
+
+/// Which codes unknown codes are compared with for suggestions. Suggestions reveal codes
+/// similar to whatever a visitor types, so they are meant for internal deployments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SuggestionScope {
+    #[default]
+    Off,
+    /// Only codes in the keyword namespace, which people type by hand
+    Keywords,
+    /// Every code
+    All,
+}
+
+impl FromStr for SuggestionScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "keywords" => Ok(Self::Keywords),
+            "all" => Ok(Self::All),
+            other => Err(format!("unknown suggestion scope '{}'", other)),
+        }
+    }
+}
+
+/// Codes by namespace, each namespace's codes ordered by length
+type Index = HashMap<String, Vec<(usize, Box<str>)>>;
+
+/// Compact in-memory index of existing codes, for suggesting the nearest ones when a code is
+/// mistyped. Rebuilt from storage periodically, so new links are suggested after a refresh.
+pub struct TypoIndex {
+    storage: StorageRef,
+    prefix: String,
+    fixed: Vec<String>,
+    index: RwLock<Arc<Index>>,
+}
+
+impl TypoIndex {
+    /// Indexes the enabled codes starting with `prefix`, e.g. `go/`, and every `fixed` code,
+    /// like static links, that does
+    pub fn new(storage: StorageRef, prefix: String, fixed: Vec<String>) -> Self {
+        Self {
+            storage,
+            prefix,
+            fixed,
+            index: RwLock::new(Arc::default()),
+        }
+    }
+
+    /// Rebuilds the index from storage, returning how many codes it holds
+    pub async fn refresh(&self) -> UrlShortenerResult<usize> {
+        let mut codes: Vec<String> = self.fixed.iter().filter(|code| code.starts_with(&self.prefix)).cloned().collect();
+        let mut after = None;
+        'pages: loop {
+            let page = self.storage.export_urls(after.as_deref(), PAGE_SIZE).await?;
+            let last = page.last().map(|url| url.short_url.clone());
+            for url in page {
+                if codes.len() == MAX_INDEXED_CODES {
+                    warn!(limit = MAX_INDEXED_CODES, "Typo index is full, leaving out the remaining codes");
+                    break 'pages;
+                }
+                if url.disabled_at.is_none() && url.short_url.starts_with(&self.prefix) {
+                    codes.push(url.short_url);
+                }
+            }
+            match last {
+                Some(last) => after = Some(last),
+                None => break,
+            }
+        }
+
+        let count = codes.len();
+        let mut index = Index::new();
+        for code in codes {
+            let (namespace, local) = split(&code);
+            index
+                .entry(namespace.to_string())
+                .or_default()
+                .push((local.chars().count(), code.into_boxed_str()));
+        }
+        for codes in index.values_mut() {
+            codes.sort_unstable();
+            codes.dedup();
+        }
+        if let Ok(mut current) = self.index.write() {
+            *current = Arc::new(index);
+        }
+        debug!(codes = count, "Rebuilt typo index");
+        Ok(count)
+    }
+
+    /// Rebuilds every `interval` for the lifetime of the process, starting now
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.refresh().await {
+                    warn!(error = %e, "Failed to rebuild typo index");
+                }
+            }
+        });
+    }
+
+    /// Indexed codes in the namespace of `short_code` closest to it by edit distance, nearest
+    /// first. Short codes are too easily near others to suggest anything for.
+    pub fn suggest(&self, short_code: &str) -> Vec<String> {
+        let Ok(index) = self.index.read().map(|index| Arc::clone(&index)) else { return Vec::new() };
+        let (namespace, local) = split(short_code);
+        let Some(codes) = index.get(namespace) else { return Vec::new() };
+        let wanted: Vec<char> = local.chars().collect();
+        let max_distance = match wanted.len() {
+            0..=3 => return Vec::new(),
+            4..=7 => 1,
+            _ => 2,
+        };
+
+        let shortest = codes.partition_point(|(length, _)| *length + max_distance < wanted.len());
+        let mut found: Vec<(usize, &str)> = codes[shortest..]
+            .iter()
+            .take_while(|(length, _)| *length <= wanted.len() + max_distance)
+            .filter_map(|(_, code)| {
+                let candidate: Vec<char> = split(code).1.chars().collect();
+                distance(&wanted, &candidate, max_distance)
+                    .filter(|&distance| distance > 0)
+                    .map(|distance| (distance, &**code))
+            })
+            .collect();
+        found.sort_unstable();
+        found.into_iter().take(MAX_SUGGESTIONS).map(|(_, code)| code.to_string()).collect()
+    }
+}
+
+/// The namespace of a code and the rest of it; root codes have no namespace
+fn split(short_code: &str) -> (&str, &str) {
+    short_code.split_once('/').unwrap_or(("", short_code))
+}
+
+/// Levenshtein distance between `a` and `b`, or `None` once it exceeds `max`
+fn distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|&least| least > max) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
@@ -0,0 +1,613 @@
+use super::*;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crate::models::ShortenedUrl;
+use crate::runaway::{RunawayConfig, RunawayGuard};
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+use chrono::Utc;
+
+async fn storage_with(codes: &[&str]) -> Arc<MemoryStorage> {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    for code in codes {
+        storage
+            .save_url(ShortenedUrl {
+                original_url: format!("https://example.com/{}", code),
+                short_url: code.to_string(),
+                ..ShortenedUrl::default()
+            })
+            .await
+            .unwrap();
+    }
+    storage
+}
+
+fn target(url: &str) -> Target {
+    Target { url: Arc::from(url), pass_through: false, signed_only: false, tracking_snippet: None, redirect_headers: None, hide_referrer: false }
+}
+
+#[test]
+fn test_cache_is_bounded() {
+    let cache = RedirectCache::new(32);
+    for i in 0..1_000 {
+        cache.insert(&format!("code{}", i), target("https://example.com/"));
+    }
+    assert!(cache.len() <= 32);
+    assert!(cache.get("code999").is_some());
+
+    cache.invalidate("code999");
+    assert!(cache.get("code999").is_none());
+}
+
+#[test]
+fn test_cache_can_be_disabled() {
+    let cache = RedirectCache::new(0);
+    cache.insert("code", target("https://example.com/"));
+    assert!(cache.get("code").is_none());
+    assert!(cache.is_empty());
+}
+
+#[tokio::test]
+async fn test_resolve_caches_destination() {
+    let storage = storage_with(&["abc"]).await;
+    let redirector = Redirector::new(storage, RedirectConfig::default());
+
+    assert_eq!(&*redirector.resolve("abc").await.unwrap(), "https://example.com/abc");
+    assert!(redirector.cache().get("abc").is_some());
+    assert_eq!(&*redirector.resolve("abc").await.unwrap(), "https://example.com/abc");
+
+    assert!(redirector.resolve("missing").await.is_err());
+    assert!(redirector.cache().get("missing").is_none());
+}
+
+#[tokio::test]
+async fn test_warm_up_loads_most_visited_codes() {
+    let storage = storage_with(&["cold", "warm", "hot", "off"]).await;
+    storage.record_visits("warm", 5).await.unwrap();
+    storage.record_visits("hot", 50).await.unwrap();
+    storage.record_visits("off", 500).await.unwrap();
+    storage.disable_urls(&["off".to_string()]).await.unwrap();
+    let redirector = Redirector::new(storage.clone(), RedirectConfig::default());
+
+    assert_eq!(redirector.warm_up(2).await.unwrap(), 2);
+    assert!(redirector.cache().get("hot").is_some());
+    assert!(redirector.cache().get("warm").is_some());
+    assert!(redirector.cache().get("cold").is_none());
+    assert!(redirector.cache().get("off").is_none());
+
+    let uncached = Redirector::new(storage, RedirectConfig { cache_capacity: 0, ..RedirectConfig::default() });
+    assert_eq!(uncached.warm_up(2).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_visits_are_batched_until_flushed() {
+    let storage = storage_with(&["abc", "def"]).await;
+    let config = RedirectConfig { visit_flush_interval: Duration::from_secs(3600), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    for _ in 0..3 {
+        redirector.resolve("abc").await.unwrap();
+    }
+    redirector.resolve("def").await.unwrap();
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 0);
+
+    assert_eq!(redirector.flush_visits().await, 4);
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 3);
+    assert_eq!(storage.get_stats("def").await.unwrap().visits, 1);
+    assert_eq!(redirector.flush_visits().await, 0);
+}
+
+#[tokio::test]
+async fn test_visits_are_not_counted_when_recording_is_off() {
+    let storage = storage_with(&["abc"]).await;
+    let redirector = Redirector::new(storage.clone(), RedirectConfig { record_visits: false, ..RedirectConfig::default() });
+
+    assert_eq!(&*redirector.resolve("abc").await.unwrap(), "https://example.com/abc");
+    assert_eq!(redirector.buffered_visits().visits, 0);
+    assert_eq!(redirector.flush_visits().await, 0);
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 0);
+}
+
+#[tokio::test]
+async fn test_visits_flush_in_background() {
+    let storage = storage_with(&["abc"]).await;
+    let config = RedirectConfig { visit_flush_interval: Duration::from_millis(10), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    redirector.resolve("abc").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 1);
+}
+
+#[tokio::test]
+async fn test_rate_limit_is_per_code() {
+    let storage = storage_with(&["hot", "cold"]).await;
+    let config = RedirectConfig { rate_limit: Some(3), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    for _ in 0..3 {
+        redirector.resolve("hot").await.unwrap();
+    }
+    match redirector.resolve("hot").await.unwrap_err().error_type {
+        UrlShortenerErrorType::RateLimitExceeded => (),
+        error_type => panic!("Expected RateLimitExceeded, got {:?}", error_type),
+    }
+    redirector.resolve("cold").await.unwrap();
+
+    // Rejected redirects are not counted as visits
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("hot").await.unwrap().visits, 3);
+}
+
+#[tokio::test]
+async fn test_runaway_links_are_disabled_until_re_enabled() {
+    let storage = storage_with(&["flood", "calm"]).await;
+    let config = RunawayConfig { multiple: 2.0, min_rate: 1 };
+    let guard = Arc::new(RunawayGuard::new(config, storage.clone()));
+    let redirector = Redirector::new(storage.clone(), RedirectConfig::default()).with_runaway_guard(guard);
+
+    for _ in 0..2 {
+        redirector.resolve("flood").await.unwrap();
+    }
+    match redirector.resolve("flood").await.unwrap_err().error_type {
+        UrlShortenerErrorType::LinkDisabled => (),
+        error_type => panic!("Expected LinkDisabled, got {:?}", error_type),
+    }
+    assert!(redirector.cache().get("flood").is_none());
+    redirector.resolve("calm").await.unwrap();
+
+    // The link is disabled in storage in the background
+    for _ in 0..100 {
+        if storage.get_stats("flood").await.unwrap().disabled_at.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(storage.get_stats("flood").await.unwrap().disabled_at.is_some());
+
+    storage.enable_urls(&["flood".to_string()]).await.unwrap();
+    redirector.invalidate("flood");
+    redirector.resolve("flood").await.unwrap();
+}
+
+#[test]
+fn test_pass_through_appends_path_and_query() {
+    let target = Target {
+        url: Arc::from("https://example.com/docs/?ref=short#top"),
+        pass_through: true,
+        signed_only: false,
+        tracking_snippet: None,
+        redirect_headers: None,
+        hide_referrer: false,
+    };
+    assert_eq!(&*target.with_path("", ""), "https://example.com/docs/?ref=short#top");
+    assert_eq!(&*target.with_path("/guide/intro", ""), "https://example.com/docs/guide/intro?ref=short#top");
+    assert_eq!(&*target.with_path("/a%20b", "x=1&y"), "https://example.com/docs/a%20b?ref=short&x=1&y#top");
+
+    let plain = Target { pass_through: false, ..target };
+    assert_eq!(&*plain.with_path("/guide", "x=1"), "https://example.com/docs/?ref=short#top");
+}
+
+#[tokio::test]
+async fn test_resolve_path_only_extends_pass_through_links() {
+    let storage = storage_with(&["plain"]).await;
+    storage
+        .save_url(ShortenedUrl {
+            original_url: "https://example.com/base".to_string(),
+            short_url: "through".to_string(),
+            pass_through: true,
+            ..ShortenedUrl::default()
+        })
+        .await
+        .unwrap();
+    let config = RedirectConfig {
+        code_lookup: CodeLookup { trim: true, ..CodeLookup::default() },
+        ..RedirectConfig::default()
+    };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    let url = redirector.resolve_path("through", "/x/y", "q=1", None).await.unwrap().url;
+    assert_eq!(&*url, "https://example.com/base/x/y?q=1");
+    assert_eq!(&*redirector.resolve_path("plain", "", "q=1", None).await.unwrap().url, "https://example.com/plain");
+    // Trailing slashes are still trimmed rather than passed on
+    assert_eq!(&*redirector.resolve_path("through", "//", "", None).await.unwrap().url, "https://example.com/base");
+    match redirector.resolve_path("plain", "/x", "", None).await.unwrap_err().error_type {
+        UrlShortenerErrorType::NotFound => (),
+        error_type => panic!("Expected NotFound, got {:?}", error_type),
+    }
+
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("through").await.unwrap().visits, 2);
+    assert_eq!(storage.get_stats("plain").await.unwrap().visits, 1);
+}
+
+#[test]
+fn test_source_tags_are_taken_off_the_query() {
+    assert_eq!(take_source("src=Newsletter&q=1"), (Some("newsletter".to_string()), "q=1".to_string()));
+    assert_eq!(take_source("q=1&src=a%20b&src=other"), (Some("a b".to_string()), "q=1".to_string()));
+    assert_eq!(take_source("src=&source=x"), (None, "source=x".to_string()));
+    let (long, _) = take_source(&format!("src={}", "x".repeat(100)));
+    assert_eq!(long.unwrap().len(), MAX_SOURCE_LENGTH);
+}
+
+#[tokio::test]
+async fn test_visits_are_attributed_to_their_source() {
+    let storage = storage_with(&["plain"]).await;
+    let mut link = storage.get_url("plain").await.unwrap();
+    link.short_url = "through".to_string();
+    link.pass_through = true;
+    storage.save_url(link).await.unwrap();
+    let redirector = Redirector::new(storage.clone(), RedirectConfig::default());
+
+    // The tag never reaches the destination
+    let url = redirector.resolve_path("through", "", "src=newsletter&q=1", None).await.unwrap().url;
+    assert_eq!(&*url, "https://example.com/plain?q=1");
+    redirector.resolve_path("through", "", "", None).await.unwrap();
+    redirector.resolve_path("plain", "", "src=newsletter", None).await.unwrap();
+    redirector.flush_visits().await;
+
+    let since = Utc::now() - chrono::Duration::hours(1);
+    let sources = storage.visit_sources("through", since).await.unwrap();
+    let sources: Vec<_> = sources.iter().map(|source| (source.source.as_deref(), source.visits)).collect();
+    assert_eq!(sources, [(Some("newsletter"), 1), (None, 1)]);
+}
+
+#[tokio::test]
+async fn test_repeat_visits_within_the_dedup_window_count_once() {
+    let storage = storage_with(&["abc", "def"]).await;
+    let config = RedirectConfig { visit_dedup_window: Duration::from_millis(50), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    for _ in 0..3 {
+        redirector.resolve_path("abc", "", "", Some("visitor-a")).await.unwrap();
+    }
+    redirector.resolve_path("abc", "", "", Some("visitor-b")).await.unwrap();
+    redirector.resolve_path("def", "", "", Some("visitor-a")).await.unwrap();
+    // Visits without a known visitor cannot be told apart
+    redirector.resolve("abc").await.unwrap();
+    redirector.resolve("abc").await.unwrap();
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 4);
+    assert_eq!(storage.get_stats("def").await.unwrap().visits, 1);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    redirector.resolve_path("abc", "", "", Some("visitor-a")).await.unwrap();
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 5);
+}
+
+#[test]
+fn test_event_sampling_starts_after_the_threshold() {
+    let sampling = EventSampling { after: 2, rate: 3 };
+    let kept: Vec<_> = (1..=9).map(|nth| sampling.sample_rate(nth)).collect();
+    assert_eq!(kept, [Some(1), Some(1), None, None, Some(3), None, None, Some(3), None]);
+    assert_eq!(EventSampling::default().sample_rate(1_000), Some(1));
+}
+
+#[tokio::test]
+async fn test_sampled_events_are_scaled_in_stats() {
+    let storage = storage_with(&["busy"]).await;
+    let config = RedirectConfig {
+        visit_flush_interval: Duration::from_secs(3600),
+        event_sampling: EventSampling { after: 2, rate: 3 },
+        ..RedirectConfig::default()
+    };
+    let redirector = Redirector::new(storage.clone(), config);
+
+    for _ in 0..11 {
+        redirector.resolve("busy").await.unwrap();
+    }
+    redirector.flush_visits().await;
+    // The counter stays exact, while only some visits are kept as events
+    assert_eq!(storage.get_stats("busy").await.unwrap().visits, 11);
+    let since = Utc::now() - chrono::Duration::hours(1);
+    let events = storage.export_visit_events(since, Utc::now(), None, 100).await.unwrap();
+    assert_eq!(events.iter().map(|event| event.sample_rate).collect::<Vec<_>>(), [1, 1, 3, 3, 3]);
+    let timeline = storage.visit_timeline("busy", Utc::now()).await.unwrap();
+    assert_eq!(timeline.visits_last_24h, 11);
+}
+
+#[tokio::test]
+async fn test_replayed_responses_count_visits_and_expire() {
+    let storage = storage_with(&["abc"]).await;
+    let config = RedirectConfig { response_cache_ttl: Duration::from_millis(50), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+    assert!(redirector.replay("abc", None).is_none());
+
+    let response = CachedResponse {
+        status: actix_web::http::StatusCode::FOUND,
+        headers: actix_web::http::header::HeaderMap::new(),
+    };
+    redirector.store_response("abc", response);
+    assert!(redirector.replay("abc", None).is_some());
+    assert!(redirector.replay("abc", None).is_some());
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("abc").await.unwrap().visits, 2);
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(redirector.replay("abc", None).is_none());
+}
+
+#[test]
+fn test_response_cache_is_disabled_without_ttl() {
+    assert!(ResponseCache::new(Duration::ZERO, 100).is_none());
+    assert!(ResponseCache::new(Duration::from_secs(1), 0).is_none());
+}
+
+#[test]
+fn test_limiter_window_resets() {
+    let limiter = HotLinkLimiter::new(1);
+    assert!(limiter.check("code"));
+    assert!(!limiter.check("code"));
+    std::thread::sleep(Duration::from_millis(1_050));
+    assert!(limiter.check("code"));
+}
+
+#[test]
+fn test_signatures_are_bound_to_code_and_expiry() {
+    let signer = LinkSigner::new("secret");
+    let now = Utc::now();
+    let expires_at = now + chrono::Duration::hours(1);
+    let signature = signer.sign("abc", expires_at);
+    let exp = expires_at.timestamp().to_string();
+
+    assert!(signer.verify("abc", Some(&signature), Some(&exp), now).is_ok());
+    assert!(signer.verify("abd", Some(&signature), Some(&exp), now).is_err());
+    assert!(signer.verify("abc", Some(&signature), Some(&(expires_at.timestamp() + 60).to_string()), now).is_err());
+    assert!(signer.verify("abc", Some(&signature), Some(&exp), expires_at).is_err());
+    assert!(LinkSigner::new("other").verify("abc", Some(&signature), Some(&exp), now).is_err());
+    assert!(signer.verify("abc", None, Some(&exp), now).is_err());
+    assert!(signer.verify("abc", Some("not base64!"), Some(&exp), now).is_err());
+}
+
+#[test]
+fn test_signed_query_strips_signature_params() {
+    let query = SignedQuery::parse("utm=1&exp=123&sig=abc&x");
+    assert_eq!(query.signature, Some("abc"));
+    assert_eq!(query.expires_at, Some("123"));
+    assert_eq!(query.rest, "utm=1&x");
+    assert!(query.is_signed());
+    assert!(!SignedQuery::parse("utm=1&signal=2").is_signed());
+}
+
+#[tokio::test]
+async fn test_signed_only_links_need_a_valid_signature() {
+    let storage = storage_with(&["open"]).await;
+    let mut link = storage.get_url("open").await.unwrap();
+    link.short_url = "locked".to_string();
+    link.original_url = "https://example.com/locked".to_string();
+    link.visits = 0;
+    link.signed_only = true;
+    link.pass_through = true;
+    storage.save_url(link).await.unwrap();
+
+    let signer = LinkSigner::new("secret");
+    let config = RedirectConfig { signer: Some(signer.clone()), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+    fn rejected<T: std::fmt::Debug>(result: UrlShortenerResult<T>) -> bool {
+        matches!(result.unwrap_err().error_type, UrlShortenerErrorType::InvalidSignature(_))
+    }
+
+    assert!(rejected(redirector.resolve("locked").await));
+    assert!(rejected(redirector.resolve_path("locked", "", "exp=1&sig=AAAA", None).await));
+    let expired = signer.signed_query("locked", Utc::now() - chrono::Duration::seconds(1));
+    assert!(rejected(redirector.resolve_path("locked", "", &expired, None).await));
+
+    let signed = signer.signed_query("locked", Utc::now() + chrono::Duration::minutes(5));
+    let url = redirector.resolve_path("locked", "/docs", &format!("{}&ref=mail", signed), None).await.unwrap().url;
+    assert_eq!(&*url, "https://example.com/locked/docs?ref=mail");
+
+    // Plain links ignore signing unless a signature is presented
+    assert!(redirector.resolve("open").await.is_ok());
+    assert!(rejected(redirector.resolve_path("open", "", "exp=1&sig=AAAA", None).await));
+
+    redirector.flush_visits().await;
+    assert_eq!(storage.get_stats("locked").await.unwrap().visits, 1);
+
+    let unsigned = Redirector::new(storage, RedirectConfig::default());
+    assert!(rejected(unsigned.resolve_path("locked", "", &signed, None).await));
+}
+
+#[test]
+fn test_static_links_file_is_checked() {
+    let links = StaticLinks::parse(
+        r#"
+        [links]
+        docs = "https://docs.example.com"
+        status = { url = "https://status.example.com", pass_through = true }
+        "t/handbook" = "https://wiki.example.com/handbook"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(links.len(), 3);
+    assert!(links.get("status").unwrap().pass_through);
+    assert!(!links.get("docs").unwrap().pass_through);
+    assert!(StaticLinks::parse("").unwrap().is_empty());
+
+    let problems = StaticLinks::parse(
+        r#"
+        [links]
+        "a/b/c" = "https://example.com"
+        "two words" = "https://example.com"
+        mail = "mailto:ops@example.com"
+        "#,
+    )
+    .unwrap_err();
+    assert_eq!(problems.lines().count(), 3, "{}", problems);
+    assert!(StaticLinks::parse("[links]\ndocs = 1").is_err());
+}
+
+#[tokio::test]
+async fn test_static_links_are_served_ahead_of_storage_and_reserved() {
+    let storage = storage_with(&["docs", "other"]).await;
+    let links = StaticLinks::parse(
+        "[links]\nDocs = \"https://docs.example.com\"\n\"t/handbook\" = \"https://wiki.example.com\"\n\"x/y\" = \"https://example.com\"",
+    )
+    .unwrap();
+    let config = RedirectConfig {
+        code_lookup: CodeLookup { case_insensitive: true, trim: false },
+        visit_flush_interval: Duration::from_secs(3600),
+        ..RedirectConfig::default()
+    };
+    let redirector = Redirector::new(storage.clone(), config).with_static_links(links.clone());
+
+    assert_eq!(&*redirector.resolve("DOCS").await.unwrap(), "https://docs.example.com");
+    assert_eq!(&*redirector.resolve("t/handbook").await.unwrap(), "https://wiki.example.com");
+    assert_eq!(&*redirector.resolve("other").await.unwrap(), "https://example.com/other");
+    // Only the stored link's visit is counted
+    assert_eq!(redirector.buffered_visits().links, 1);
+
+    let namespaces = links.reserve(crate::services::Namespaces::new(&["t"]));
+    assert!(namespaces.is_reserved(None, "docs"));
+    assert!(namespaces.is_reserved(namespaces.get("t"), "handbook"));
+    assert!(!namespaces.is_reserved(None, "x/y"));
+}
+
+#[tokio::test]
+async fn test_expand_reads_destinations_without_counting() {
+    let storage = storage_with(&["stored"]).await;
+    let links = StaticLinks::parse("[links]\ndocs = \"https://docs.example.com\"").unwrap();
+    let redirector = Redirector::new(storage, RedirectConfig::default()).with_static_links(links);
+
+    let (short_code, url) = redirector.expand("stored").await.unwrap();
+    assert_eq!((short_code.as_str(), &*url), ("stored", "https://example.com/stored"));
+    assert_eq!(&*redirector.expand("docs").await.unwrap().1, "https://docs.example.com");
+    assert!(matches!(redirector.expand("missing").await.unwrap_err().error_type, UrlShortenerErrorType::NotFound));
+    assert_eq!(redirector.buffered_visits().links, 0);
+}
+
+#[tokio::test]
+async fn test_expand_all_reads_stored_codes_at_once() {
+    let storage = storage_with(&["stored", "off", "cached"]).await;
+    storage.disable_urls(&["off".to_string()]).await.unwrap();
+    let links = StaticLinks::parse("[links]\ndocs = \"https://docs.example.com\"").unwrap();
+    let redirector = Redirector::new(storage, RedirectConfig::default()).with_static_links(links);
+    redirector.expand("cached").await.unwrap();
+
+    let codes = ["stored", "docs", "off", "missing", "cached"].map(String::from);
+    let expansions = redirector.expand_all(&codes).await.unwrap();
+    let found = |code: &str, url: &str| Expansion::Found { short_code: code.to_string(), url: url.into() };
+    assert_eq!(expansions, [
+        found("stored", "https://example.com/stored"),
+        found("docs", "https://docs.example.com"),
+        Expansion::Disabled,
+        Expansion::Missing,
+        found("cached", "https://example.com/cached"),
+    ]);
+    assert_eq!(redirector.buffered_visits().links, 0);
+}
+
+#[tokio::test]
+async fn test_typo_index_suggests_near_codes_in_the_same_namespace() {
+    let storage = storage_with(&["go/payroll", "go/payments", "go/runbook", "handbook", "abc"]).await;
+    let index = TypoIndex::new(storage.clone(), String::new(), vec!["go/benefits".to_string()]);
+    assert_eq!(index.refresh().await.unwrap(), 6);
+
+    assert_eq!(index.suggest("go/payrol"), vec!["go/payroll"]);
+    assert_eq!(index.suggest("go/benefit"), vec!["go/benefits"]);
+    assert_eq!(index.suggest("hnadbook"), vec!["handbook"]);
+    // Existing codes, other namespaces and short codes get nothing
+    assert!(index.suggest("go/payroll").is_empty());
+    assert!(index.suggest("go/handbook").is_empty());
+    assert!(index.suggest("abd").is_empty());
+
+    let keywords = Arc::new(TypoIndex::new(storage.clone(), "go/".to_string(), Vec::new()));
+    assert_eq!(keywords.refresh().await.unwrap(), 3);
+    assert!(keywords.suggest("hnadbook").is_empty());
+
+    let config = RedirectConfig {
+        code_lookup: CodeLookup { case_insensitive: true, trim: false },
+        ..RedirectConfig::default()
+    };
+    let redirector = Redirector::new(storage, config).with_typo_index(keywords);
+    assert_eq!(redirector.suggestions("GO/RUNBOK"), vec!["go/runbook"]);
+
+    let page = not_found_page(&["go/a&b".to_string()]);
+    assert!(page.contains("<a href=\"/go/a&amp;b\">go/a&amp;b</a>"));
+    assert_eq!(not_found_page(&[]), NOT_FOUND_PAGE);
+}
+
+#[tokio::test]
+async fn test_tracking_snippets_only_come_with_tracking_pages() {
+    let storage = storage_with(&["plain"]).await;
+    let mut link = storage.get_url("plain").await.unwrap();
+    link.short_url = "tracked".to_string();
+    link.tracking_snippet = Some("<script>fire()</script>".to_string());
+    storage.save_url(link).await.unwrap();
+
+    let config = RedirectConfig { tracking_pages: true, ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage.clone(), config);
+    let redirect = redirector.resolve_path("tracked", "", "", None).await.unwrap();
+    assert_eq!(redirect.tracking_snippet.as_deref(), Some("<script>fire()</script>"));
+    assert!(redirector.resolve_path("plain", "", "", None).await.unwrap().tracking_snippet.is_none());
+
+    let redirector = Redirector::new(storage, RedirectConfig::default());
+    assert!(redirector.resolve_path("tracked", "", "", None).await.unwrap().tracking_snippet.is_none());
+}
+
+#[test]
+fn test_tracking_page_keeps_the_destination_out_of_markup() {
+    let page = tracking_page("https://example.com/?q=</script>{snippet}\"", "<img src=\"px.gif\">");
+    assert_eq!(page.matches("<img src=\"px.gif\">").count(), 1);
+    assert!(page.contains(r#"location.replace("https://example.com/?q=\u003c/script\u003e{snippet}\"")"#));
+    assert!(page.contains("url=https://example.com/?q=&lt;/script&gt;{snippet}&quot;\">"));
+    assert!(page.contains("body { font-family"));
+}
+
+#[test]
+fn test_receipts_verify_only_as_issued() {
+    let receipts = ReceiptSigner::new("secret", Duration::from_secs(300));
+    let now = Utc::now();
+    let receipt = receipts.issue("abc", "example.com", now);
+
+    let verified = receipts.verify(&receipt, now + chrono::Duration::seconds(60)).unwrap();
+    assert_eq!((verified.short_code.as_str(), verified.host.as_str()), ("abc", "example.com"));
+    assert_eq!(verified.issued_at.timestamp(), now.timestamp());
+
+    let rejected = |result: UrlShortenerResult<Receipt>| {
+        matches!(result.unwrap_err().error_type, UrlShortenerErrorType::InvalidSignature(_))
+    };
+    assert!(rejected(receipts.verify(&receipt, now + chrono::Duration::seconds(301))));
+    assert!(rejected(ReceiptSigner::new("other", Duration::from_secs(300)).verify(&receipt, now)));
+    let (_, signature) = receipt.split_once('.').unwrap();
+    let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(format!("abc\nevil.example\n{}", now.timestamp())), signature);
+    assert!(rejected(receipts.verify(&forged, now)));
+    assert!(rejected(receipts.verify("not-a-receipt", now)));
+}
+
+#[tokio::test]
+async fn test_resolve_path_appends_receipts() {
+    let storage = storage_with(&["abc"]).await;
+    let receipts = ReceiptSigner::new("secret", Duration::from_secs(300));
+    let config = RedirectConfig { receipts: Some(receipts.clone()), ..RedirectConfig::default() };
+    let redirector = Redirector::new(storage, config);
+
+    let url = redirector.resolve_path("abc", "", "", None).await.unwrap().url;
+    let url = url::Url::parse(&url).unwrap();
+    assert_eq!(url.path(), "/abc");
+    let (param, receipt) = url.query_pairs().next().unwrap();
+    assert_eq!(param, RECEIPT_PARAM);
+    let verified = receipts.verify(&receipt, Utc::now()).unwrap();
+    assert_eq!((verified.short_code.as_str(), verified.host.as_str()), ("abc", "example.com"));
+}
+
+#[test]
+fn test_headers_proxies_act_on_are_refused() {
+    for name in [
+        "X-Accel-Redirect",
+        "X-Accel-Buffering",
+        "x-accel-expires",
+        "X-Sendfile",
+        "X-Sendfile-Type",
+        "X-Lighttpd-Send-File",
+        "X-Forwarded-For",
+        "X-Forwarded-Host",
+        "X-Real-IP",
+        "X-Original-URL",
+        "X-Original-Forwarded-For",
+    ] {
+        assert!(check_redirect_header(name, "/internal/secret").is_some(), "{} was allowed", name);
+    }
+    assert_eq!(check_redirect_header("X-Campaign", "spring"), None);
+    assert_eq!(check_redirect_header("Referrer-Policy", "no-referrer"), None);
+    assert!(check_redirect_header("Location", "https://example.com/").is_some());
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::models::VisitEvent;
+use crate::storage::StorageRef;
+
+/// Query parameter of a short link tagging where it was shared, e.g. `/abc?src=newsletter`
+pub const SOURCE_PARAM: &str = "src";
+
+/// Longest source tag kept; longer ones are cut
+pub const MAX_SOURCE_LENGTH: usize = 64;
+
+/// Takes the source tag off a short link's query, returning it, lowercased, and the rest of
+/// the query as sent
+pub fn take_source(query: &str) -> (Option<String>, String) {
+    let mut source = None;
+    let mut rest = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        match url::form_urlencoded::parse(pair.as_bytes()).next() {
+            Some((name, value)) if name == SOURCE_PARAM => {
+                let value = value.trim().to_lowercase();
+                if source.is_none() && !value.is_empty() {
+                    source = Some(value.chars().take(MAX_SOURCE_LENGTH).collect());
+                }
+            }
+            _ => rest.push(pair),
+        }
+    }
+    (source, rest.join("&"))
+}
+
+/// Which visits of a busy link are kept as events. Visit counts stay exact either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventSampling {
+    /// Visits of a link per flush interval kept in full before sampling starts
+    pub after: u64,
+    /// One in this many of the remaining visits is kept; 1 keeps them all
+    pub rate: u32,
+}
+
+impl Default for EventSampling {
+    fn default() -> Self {
+        Self { after: 0, rate: 1 }
+    }
+}
+
+impl EventSampling {
+    /// The sample rate to store the `nth` visit of a link in a flush interval with, counting
+    /// from 1, or `None` when it is not kept
+    pub fn sample_rate(&self, nth: u64) -> Option<i32> {
+        let rate = self.rate.clamp(1, i32::MAX as u32);
+        match nth.checked_sub(self.after) {
+            None | Some(0) => Some(1),
+            Some(beyond) => (beyond % u64::from(rate) == 0).then_some(rate as i32),
+        }
+    }
+}
+
+/// Visits counted in memory that the next flush will write
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferedVisits {
+    pub visits: u64,
+    /// Links the buffered visits are to
+    pub links: usize,
+    pub events: usize,
+}
+
+/// Counts redirect visits in memory and writes them to storage in batches, along with an
+/// event per visit, so the redirect itself never waits on a database write
+pub struct VisitRecorder {
+    storage: StorageRef,
+    sampling: EventSampling,
+    pending: Mutex<HashMap<String, u64>>,
+    events: Mutex<Vec<VisitEvent>>,
+}
+
+impl VisitRecorder {
+    pub fn new(storage: StorageRef, sampling: EventSampling) -> Self {
+        Self {
+            storage,
+            sampling,
+            pending: Mutex::new(HashMap::new()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Counts one visit by an anonymized `visitor`, if known, tagged with its `source`; it is
+    /// persisted on the next flush. Its event may be left out under the event sampling.
+    pub fn record(&self, short_code: &str, visitor: Option<&str>, source: Option<&str>) {
+        let Ok(mut pending) = self.pending.lock() else { return };
+        let nth = match pending.get_mut(short_code) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                pending.insert(short_code.to_string(), 1);
+                1
+            }
+        };
+        drop(pending);
+
+        let Some(sample_rate) = self.sampling.sample_rate(nth) else { return };
+
+        let Ok(mut events) = self.events.lock() else { return };
+        events.push(VisitEvent {
+            short_code: short_code.to_string(),
+            visited_at: Utc::now(),
+            visitor: visitor.map(str::to_string),
+            source: source.map(str::to_string),
+            sample_rate,
+        });
+    }
+
+    pub fn buffered(&self) -> BufferedVisits {
+        let (visits, links) = self
+            .pending
+            .lock()
+            .map(|pending| (pending.values().sum(), pending.len()))
+            .unwrap_or_default();
+        let events = self.events.lock().map(|events| events.len()).unwrap_or_default();
+        BufferedVisits { visits, links, events }
+    }
+
+    /// Writes all pending visits to storage in one batch, returning how many were flushed
+    pub async fn flush(&self) -> u64 {
+        let pending: Vec<(String, u64)> = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending).into_iter().collect(),
+            Err(_) => return 0,
+        };
+        if pending.is_empty() {
+            return 0;
+        }
+
+        let visits: u64 = pending.iter().map(|(_, count)| count).sum();
+        let result = self.storage.record_visit_batch(&pending).await;
+        self.flush_events().await;
+        match result {
+            Ok(found) => {
+                if found < pending.len() as u64 {
+                    debug!(missing = pending.len() as u64 - found, "Dropped visits to links that no longer exist");
+                }
+                debug!(visits, links = pending.len(), "Flushed redirect visits");
+                visits
+            }
+            Err(e) => {
+                warn!(error = %e, links = pending.len(), visits, "Failed to record visits");
+                0
+            }
+        }
+    }
+
+    /// Writes the pending visit events; they only feed analytics, so failures are not retried
+    async fn flush_events(&self) {
+        let events = match self.events.lock() {
+            Ok(mut events) => std::mem::take(&mut *events),
+            Err(_) => return,
+        };
+        if let Err(e) = self.storage.record_visit_events(&events).await {
+            warn!(error = %e, events = events.len(), "Failed to record visit events");
+        }
+    }
+
+    /// Flushes every `interval` until the recorder is dropped
+    pub(crate) fn spawn_flusher(recorder: &Arc<Self>, interval: Duration) {
+        let recorder: Weak<Self> = Arc::downgrade(recorder);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match recorder.upgrade() {
+                    Some(recorder) => {
+                        recorder.flush().await;
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+}
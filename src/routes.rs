@@ -1,17 +1,240 @@
-use actix_web::web;
-use crate::handlers::{create_url, redirect, get_stats};
+use actix_cors::Cors;
+use actix_web::{dev::HttpServiceFactory, http::header, web};
+use crate::auth::API_KEY_HEADER;
+use crate::middleware::{AuditMutations, Idempotent, MicroCache};
+use crate::handlers::{
+    create_url, redirect, get_stats, update_link, sign_link, regenerate_code, add_alias, remove_alias, quick_shorten, recent_links, create_collection, list_collections,
+    get_collection, rename_collection, delete_collection, create_api_key, list_api_keys, revoke_api_key, rotate_api_key,
+    create_campaign, list_campaigns, delete_campaign, add_campaign_link, remove_campaign_link, campaign_stats,
+    dashboard, login, logout, flush_cache, invalidate_cached_code, statement_cache_stats, outbound_stats, throttle_stats, slo_stats,
+    slow_query_stats, panic_count, log_level, set_log_level, reset_visits, runtime_stats, visit_heatmap,
+    compare_links, visit_sources, link_preview, resolve_batch, resolve_link, verify_receipt, usage, usage_csv, create_go_link, search_go_links, popular_go_links, transfer_link,
+    adjust_visits, enable_link, ban_domain, blocked_domains, penalized_clients, clear_penalties, clear_client_penalty, import_links,
+    webhook_deliveries, replay_webhook_delivery, robots_txt, version, api_limits, list_urls, json_error_handler, query_error_handler, form_error_handler,
+    MAX_IMPORT_BYTES,
+};
+use crate::services::Namespaces;
+
+/// API path pattern of a short code: a root code, a namespaced one like `t/abc`, or a nested
+/// keyword like `go/team/runbook`
+pub(crate) const CODE_PATTERN: &str = "{short_code:[^/]+(?:/[^/]+){0,2}}";
+
+/// Redirect path pattern of a code within its namespace, or at the root
+const SEGMENT_PATTERN: &str = "{short_code:[^/]+}";
+
+/// Redirect path pattern of a keyword, up to [`MAX_KEYWORD_DEPTH`](crate::services::MAX_KEYWORD_DEPTH) levels
+const KEYWORD_PATTERN: &str = "{short_code:[^/]+(?:/[^/]+)?}";
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api")
-            // URL shortening endpoints
-            .service(web::resource("/shorten")
-                .route(web::post().to(create_url)))
-            // Stats endpoints
-            .service(web::resource("/stats/{short_code}")
-                .route(web::get().to(get_stats)))
-    )
-    // Redirect endpoint
-    .service(web::resource("/{short_code}")
-        .route(web::get().to(redirect)));
-}
\ No newline at end of file
+    // Extractor failures use the same error format as handler errors
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error_handler))
+        .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+        .app_data(web::FormConfig::default().error_handler(form_error_handler));
+
+    let api = web::scope("/api");
+
+    // Branded preview cards for social crawlers; ahead of the link endpoints, whose pattern
+    // would match their path too
+    #[cfg(feature = "og-image")]
+    let api = api.service(web::resource(format!("/links/{}/preview.png", CODE_PATTERN))
+        .route(web::get().to(crate::handlers::preview_image)));
+
+    // Destination favicons for dashboard listings
+    #[cfg(feature = "favicon-proxy")]
+    let api = api.service(web::resource("/favicon")
+        .route(web::get().to(crate::handlers::favicon)));
+
+    // Warehouse export of visit events, restricted to admin keys
+    #[cfg(feature = "parquet-export")]
+    let api = api.service(web::resource("/export/visits.parquet")
+        .route(web::get().to(crate::handlers::export_visits)));
+
+    // CPU flamegraphs of this instance, restricted to admin keys; ahead of the admin scope,
+    // which would match its path too
+    #[cfg(feature = "pprof")]
+    let api = api.service(web::resource("/admin/debug/pprof")
+        .route(web::get().to(crate::handlers::cpu_profile)));
+
+    let api = api
+        // Build details of this instance
+        .route("/version", web::get().to(version))
+        // Limits in effect for the caller
+        .route("/limits", web::get().to(api_limits))
+        // URL shortening endpoints
+        .service(web::resource("/shorten")
+            .wrap(Idempotent)
+            .route(web::post().to(create_url)))
+        // Destinations of links without redirecting, for tools expanding them; batches go
+        // first, as the code pattern would match their path too
+        .service(web::resource("/resolve/batch")
+            .route(web::post().to(resolve_batch)))
+        .service(web::resource(format!("/resolve/{}", CODE_PATTERN))
+            .route(web::get().to(resolve_link)))
+        // Checks of receipts appended to destinations, for the sites they lead to
+        .service(web::resource("/receipts/verify")
+            .route(web::get().to(verify_receipt)))
+        // What a link leads to, for chat bots unfurling it
+        .service(web::resource(format!("/preview/{}", CODE_PATTERN))
+            .route(web::get().to(link_preview)))
+        // Stats endpoints; comparisons and breakdowns go first, as the stats pattern would
+        // match their paths too
+        .service(web::resource("/stats/compare")
+            .route(web::get().to(compare_links)))
+        .service(web::resource(format!("/stats/{}/heatmap", CODE_PATTERN))
+            .route(web::get().to(visit_heatmap)))
+        .service(web::resource(format!("/stats/{}/sources", CODE_PATTERN))
+            .route(web::get().to(visit_sources)))
+        .service(web::resource(format!("/stats/{}", CODE_PATTERN))
+            .route(web::get().to(get_stats)))
+        // Link management endpoints; signing goes first, as the link pattern would match its path too
+        .service(web::resource(format!("/links/{}/sign", CODE_PATTERN))
+            .route(web::post().to(sign_link)))
+        .service(web::resource(format!("/links/{}/transfer", CODE_PATTERN))
+            .route(web::post().to(transfer_link)))
+        .service(web::resource(format!("/links/{}", CODE_PATTERN))
+            .route(web::patch().to(update_link)))
+        // The caller's links, sorted and filtered
+        .service(web::resource("/urls")
+            .route(web::get().to(list_urls)))
+        .service(web::resource(format!("/urls/{}/regenerate", CODE_PATTERN))
+            .route(web::post().to(regenerate_code)))
+        .service(web::resource(format!("/urls/{}/aliases", CODE_PATTERN))
+            .route(web::post().to(add_alias)))
+        .service(web::resource(format!("/urls/{}/aliases/{{alias:[^/]+(?:/[^/]+){{0,2}}}}", CODE_PATTERN))
+            .route(web::delete().to(remove_alias)))
+        // Go link endpoints, when a keyword namespace is configured
+        .service(web::resource("/go/popular")
+            .route(web::get().to(popular_go_links)))
+        .service(web::resource("/go")
+            .route(web::get().to(search_go_links))
+            .route(web::post().to(create_go_link)))
+        // Collection endpoints
+        .service(web::resource("/collections")
+            .route(web::get().to(list_collections))
+            .route(web::post().to(create_collection)))
+        .service(web::resource("/collections/{id}")
+            .route(web::get().to(get_collection))
+            .route(web::patch().to(rename_collection))
+            .route(web::delete().to(delete_collection)))
+        // Campaign endpoints
+        .service(web::resource("/campaigns")
+            .route(web::get().to(list_campaigns))
+            .route(web::post().to(create_campaign)))
+        .service(web::resource("/campaigns/{id}")
+            .route(web::delete().to(delete_campaign)))
+        .service(web::resource("/campaigns/{id}/stats")
+            .route(web::get().to(campaign_stats)))
+        .service(web::resource(format!("/campaigns/{{id}}/links/{}", CODE_PATTERN))
+            .route(web::put().to(add_campaign_link))
+            .route(web::delete().to(remove_campaign_link)))
+        // Import of other shorteners' exports
+        .service(web::resource("/import")
+            .wrap(Idempotent)
+            .app_data(web::PayloadConfig::new(MAX_IMPORT_BYTES))
+            .route(web::post().to(import_links)))
+        // API key management endpoints
+        .service(web::resource("/keys")
+            .route(web::get().to(list_api_keys))
+            .route(web::post().to(create_api_key)))
+        .service(web::resource("/keys/{id}")
+            .route(web::delete().to(revoke_api_key)))
+        .service(web::resource("/keys/{id}/rotate")
+            .route(web::post().to(rotate_api_key)))
+        // Webhook delivery log, restricted to admin keys
+        .service(web::resource("/webhooks/{webhook}/deliveries")
+            .route(web::get().to(webhook_deliveries)))
+        .service(web::resource("/webhooks/{webhook}/deliveries/{id}/replay")
+            .route(web::post().to(replay_webhook_delivery)))
+        // Operator endpoints, restricted to admin keys; changes are recorded in the audit log
+        .service(web::scope("/admin")
+            .wrap(AuditMutations)
+            .route("/cache/flush", web::post().to(flush_cache))
+            .route(&format!("/cache/invalidate/{}", CODE_PATTERN), web::post().to(invalidate_cached_code))
+            .route("/storage/statement-cache", web::get().to(statement_cache_stats))
+            .route("/storage/slow-queries", web::get().to(slow_query_stats))
+            .route("/outbound", web::get().to(outbound_stats))
+            .route("/throttle", web::get().to(throttle_stats))
+            .route("/slo", web::get().to(slo_stats))
+            .route("/panics", web::get().to(panic_count))
+            .route("/runtime", web::get().to(runtime_stats))
+            .route("/usage", web::get().to(usage))
+            .route("/usage.csv", web::get().to(usage_csv))
+            .service(web::resource("/log-level")
+                .route(web::get().to(log_level))
+                .route(web::put().to(set_log_level)))
+            .route("/urls/{short_code}/reset-visits", web::post().to(reset_visits))
+            .route("/urls/{short_code}/adjust-visits", web::post().to(adjust_visits))
+            .route("/urls/{short_code}/enable", web::post().to(enable_link))
+            .service(web::resource("/blocked-domains")
+                .route(web::get().to(blocked_domains))
+                .route(web::post().to(ban_domain)))
+            .service(web::resource("/penalized-clients")
+                .route(web::get().to(penalized_clients))
+                .route(web::delete().to(clear_penalties)))
+            .route("/penalized-clients/{ip}", web::delete().to(clear_client_penalty)))
+        // Bookmarklet/extension endpoints, callable cross-origin
+        .service(web::scope("/quick")
+            .wrap(quick_cors())
+            .route("", web::get().to(quick_shorten))
+            .route("/recent", web::get().to(recent_links)));
+
+    cfg.service(api);
+
+    // Dashboard, signed in with a session cookie
+    cfg.service(web::scope("/dashboard")
+        .route("", web::get().to(dashboard))
+        .route("/login", web::post().to(login))
+        .route("/logout", web::post().to(logout)));
+
+    // Chat integrations
+    #[cfg(feature = "telegram")]
+    cfg.service(web::resource("/integrations/telegram")
+        .route(web::post().to(crate::integrations::telegram::webhook)));
+
+    // Lookups from other nodes of the cluster
+    #[cfg(feature = "peer-cache")]
+    cfg.service(web::resource(format!("{}/{}", crate::peers::LOOKUP_PATH, CODE_PATTERN))
+        .route(web::get().to(crate::peers::lookup)));
+
+    // Crawler policy; registered ahead of the redirect route, which would match it too
+    cfg.service(web::resource("/robots.txt")
+        .route(web::get().to(robots_txt)));
+
+    cfg.service(redirect_resource(SEGMENT_PATTERN));
+}
+
+/// Redirects for the codes of each namespace, served under `/{prefix}/{code}`. Configure these
+/// before [`configure_routes`], whose root redirect route would match their paths too.
+pub fn configure_namespaces(namespaces: &Namespaces) -> impl FnOnce(&mut web::ServiceConfig) + '_ {
+    move |cfg| {
+        for namespace in namespaces.iter() {
+            // Keywords may be nested, like `team/runbook`
+            let code_pattern = match namespace.keywords() {
+                true => KEYWORD_PATTERN,
+                false => SEGMENT_PATTERN,
+            };
+            cfg.service(web::scope(&format!("/{}", namespace.prefix()))
+                .app_data(web::Data::new(namespace.clone()))
+                .service(redirect_resource(code_pattern)));
+        }
+    }
+}
+
+/// Redirect endpoint; the rest of the path is captured for pass-through links, and so
+/// lenient lookup can strip trailing slashes.
+/// The micro-cache replays recent responses per code when REDIRECT_RESPONSE_CACHE_TTL_MS is set
+fn redirect_resource(code_pattern: &str) -> impl HttpServiceFactory {
+    web::resource(format!("/{}{{rest:.*}}", code_pattern))
+        .wrap(MicroCache)
+        .route(web::get().to(redirect))
+}
+
+/// CORS policy for the quick API: any origin may call it, since access is gated by API key
+fn quick_cors() -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .allowed_methods(vec!["GET"])
+        .allowed_header(API_KEY_HEADER)
+        .allowed_header(header::AUTHORIZATION)
+        .max_age(3600)
+}
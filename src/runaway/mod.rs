@@ -0,0 +1,225 @@
+//! Runaway link guard.
+//!
+//! A link whose redirects suddenly run far above its usual rate is most likely being used to
+//! flood its destination, with the shortener as the reflector. The [`RunawayGuard`] counts
+//! redirects per link and minute and keeps a moving baseline of each link's rate; once a minute
+//! exceeds a configured multiple of the baseline, the link is disabled, an admin-visible audit
+//! entry is written, and its owner is notified through a webhook. Rates are kept per instance,
+//! so each instance judges the share of traffic it sees.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::errors::UrlShortenerResult;
+use crate::models::NewAuditEntry;
+use crate::storage::StorageRef;
+use crate::webhooks::{EventKind, LinkAutoDisabled, Notifier, WebhookEvent};
+
+/// Name of the webhook runaway link notifications are delivered to
+pub const RUNAWAY_WEBHOOK: &str = "runaway";
+
+/// Actor recorded in the audit log for links the guard disables
+pub const RUNAWAY_ACTOR: &str = "runaway-guard";
+
+/// Number of independently locked shards, to keep concurrent redirects from contending
+const SHARDS: usize = 16;
+
+/// Links tracked per shard; the least recently redirected are forgotten first
+const CODES_PER_SHARD: usize = 4_096;
+
+/// Length of each counting window
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Weight of the latest minute in a link's baseline
+const BASELINE_WEIGHT: f64 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct RunawayConfig {
+    /// A link is disabled once its redirects in a minute exceed this multiple of its baseline
+    pub multiple: f64,
+    /// Redirects per minute a baseline is never taken to be below, so quiet and newly seen
+    /// links are not disabled by a modest burst
+    pub min_rate: u32,
+}
+
+/// The outcome of counting a redirect
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Allowed,
+    /// This redirect pushed the link over its limit
+    Tripped { rate: u32, baseline: f64 },
+    /// The link was already over its limit
+    Disabled,
+}
+
+/// Redirects of a link in the current minute, and its usual rate
+struct LinkRate {
+    window_start: Instant,
+    count: u32,
+    /// `None` until the first minute has passed
+    baseline: Option<f64>,
+    tripped: bool,
+}
+
+/// Counts redirects per link and minute against each link's moving baseline
+pub struct RunawayDetector {
+    config: RunawayConfig,
+    shards: Vec<Mutex<LruCache<String, LinkRate>>>,
+}
+
+impl RunawayDetector {
+    pub fn new(config: RunawayConfig) -> Self {
+        let per_shard = NonZeroUsize::new(CODES_PER_SHARD).expect("shard capacity is non-zero");
+        Self {
+            config,
+            shards: (0..SHARDS).map(|_| Mutex::new(LruCache::new(per_shard))).collect(),
+        }
+    }
+
+    /// Counts a redirect of `short_code`
+    pub fn check(&self, short_code: &str) -> Verdict {
+        self.check_at(short_code, Instant::now())
+    }
+
+    fn check_at(&self, short_code: &str, now: Instant) -> Verdict {
+        let Ok(mut shard) = self.shard(short_code).lock() else { return Verdict::Allowed };
+        let rate = shard.get_or_insert_mut_ref(short_code, || LinkRate {
+            window_start: now,
+            count: 0,
+            baseline: None,
+            tripped: false,
+        });
+        if rate.tripped {
+            return Verdict::Disabled;
+        }
+
+        let elapsed = now.duration_since(rate.window_start);
+        if elapsed >= WINDOW {
+            // Minutes without redirects pull the baseline down too
+            let windows = (elapsed.as_secs() / WINDOW.as_secs()) as u32;
+            let count = f64::from(rate.count);
+            let baseline = rate.baseline.map_or(count, |baseline| baseline + (count - baseline) * BASELINE_WEIGHT);
+            rate.baseline = Some(baseline * (1.0 - BASELINE_WEIGHT).powi(windows as i32 - 1));
+            rate.window_start += WINDOW * windows;
+            rate.count = 0;
+        }
+        rate.count = rate.count.saturating_add(1);
+
+        let baseline = rate.baseline.unwrap_or_default();
+        let limit = self.config.multiple * baseline.max(f64::from(self.config.min_rate));
+        if f64::from(rate.count) > limit {
+            rate.tripped = true;
+            return Verdict::Tripped { rate: rate.count, baseline };
+        }
+        Verdict::Allowed
+    }
+
+    /// Forgets a link's rate, e.g. once an admin re-enabled it
+    pub fn reset(&self, short_code: &str) {
+        if let Ok(mut shard) = self.shard(short_code).lock() {
+            shard.pop(short_code);
+        }
+    }
+
+    fn shard(&self, short_code: &str) -> &Mutex<LruCache<String, LinkRate>> {
+        let mut hasher = DefaultHasher::new();
+        short_code.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+}
+
+/// Disables runaway links and tells their owners about it
+pub struct RunawayGuard {
+    detector: RunawayDetector,
+    storage: StorageRef,
+    notifier: Option<Arc<dyn Notifier>>,
+}
+
+impl RunawayGuard {
+    pub fn new(config: RunawayConfig, storage: StorageRef) -> Self {
+        Self {
+            detector: RunawayDetector::new(config),
+            storage,
+            notifier: None,
+        }
+    }
+
+    /// Sends a notification for every link the guard disables
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Counts a redirect of `short_code` and returns whether it may go ahead. The redirect that
+    /// trips the limit disables the link in the background; requires a Tokio runtime.
+    pub fn check(self: &Arc<Self>, short_code: &str) -> bool {
+        match self.detector.check(short_code) {
+            Verdict::Allowed => true,
+            Verdict::Disabled => false,
+            Verdict::Tripped { rate, baseline } => {
+                warn!(short_code, rate, baseline, "Redirect rate far above baseline, disabling link");
+                let guard = self.clone();
+                let short_code = short_code.to_string();
+                tokio::spawn(async move {
+                    if let Err(e) = guard.disable(&short_code, rate, baseline).await {
+                        warn!(error = %e, short_code = %short_code, "Failed to disable runaway link");
+                    }
+                });
+                false
+            }
+        }
+    }
+
+    /// Disables a link that ran away, records it in the audit log and notifies about it.
+    /// Returns false without a notification when the link was already disabled, e.g. by
+    /// another instance.
+    pub async fn disable(&self, short_code: &str, rate: u32, baseline: f64) -> UrlShortenerResult<bool> {
+        if self.storage.disable_urls(&[short_code.to_string()]).await?.is_empty() {
+            return Ok(false);
+        }
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: RUNAWAY_ACTOR.to_string(),
+                action: "link.auto_disable".to_string(),
+                target: Some(short_code.to_string()),
+                details: json!({
+                    "redirects_per_minute": rate,
+                    "baseline_per_minute": baseline,
+                    "multiple": self.detector.config.multiple,
+                }),
+            })
+            .await?;
+        info!(short_code, "Disabled runaway link");
+
+        if let Some(notifier) = &self.notifier {
+            let url = self.storage.get_stats(short_code).await?;
+            let event = WebhookEvent::new(EventKind::LinkAutoDisabled(LinkAutoDisabled {
+                short_code: short_code.to_string(),
+                original_url: url.original_url,
+                owner: url.owner,
+                redirects_per_minute: rate,
+                baseline_per_minute: baseline,
+            }));
+            // Failed deliveries are in the delivery log, from where they can be replayed
+            if let Err(e) = notifier.notify(&event).await {
+                warn!(error = %e, short_code, "Failed to notify about runaway link");
+            }
+        }
+        Ok(true)
+    }
+
+    /// Lets a link redirect again, once an admin re-enabled it
+    pub fn reset(&self, short_code: &str) {
+        self.detector.reset(short_code);
+    }
+}
+
+#[cfg(test)]
+mod tests;
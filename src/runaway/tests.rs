@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+
+use super::*;
+use crate::models::ShortenedUrl;
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+/// Records delivered events
+#[derive(Default)]
+struct RecordingNotifier {
+    events: Mutex<Vec<WebhookEvent>>,
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn notify(&self, event: &WebhookEvent) -> UrlShortenerResult<()> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+fn detector(multiple: f64, min_rate: u32) -> RunawayDetector {
+    RunawayDetector::new(RunawayConfig { multiple, min_rate })
+}
+
+/// Counts `count` redirects at `at`, returning the last verdict
+fn redirects(detector: &RunawayDetector, code: &str, count: u32, at: Instant) -> Verdict {
+    (0..count).fold(Verdict::Allowed, |_, _| detector.check_at(code, at))
+}
+
+#[test]
+fn test_new_links_trip_over_the_minimum_rate() {
+    let detector = detector(10.0, 5);
+    let start = Instant::now();
+    assert_eq!(redirects(&detector, "burst", 50, start), Verdict::Allowed);
+    assert_eq!(detector.check_at("burst", start), Verdict::Tripped { rate: 51, baseline: 0.0 });
+    // Stays stopped, without counting, until reset
+    assert_eq!(detector.check_at("burst", start + WINDOW * 10), Verdict::Disabled);
+    assert_eq!(detector.check_at("other", start), Verdict::Allowed);
+
+    detector.reset("burst");
+    assert_eq!(detector.check_at("burst", start), Verdict::Allowed);
+}
+
+#[test]
+fn test_busy_links_are_judged_against_their_baseline() {
+    let detector = detector(10.0, 20);
+    let start = Instant::now();
+    // Steady 100 a minute becomes the baseline, well above the minimum
+    for minute in 0..5 {
+        assert_eq!(redirects(&detector, "busy", 100, start + WINDOW * minute), Verdict::Allowed);
+    }
+    assert_eq!(redirects(&detector, "busy", 1000, start + WINDOW * 5), Verdict::Allowed);
+    assert_eq!(detector.check_at("busy", start + WINDOW * 5), Verdict::Tripped { rate: 1001, baseline: 100.0 });
+}
+
+#[test]
+fn test_quiet_minutes_lower_the_baseline() {
+    let detector = detector(4.0, 25);
+    let start = Instant::now();
+    assert_eq!(redirects(&detector, "spiky", 100, start), Verdict::Allowed);
+    // 100, then 10 empty minutes: 100 * 0.9^10 is about 35, so the 140th redirect trips it
+    let later = start + WINDOW * 11;
+    assert_eq!(redirects(&detector, "spiky", 139, later), Verdict::Allowed);
+    assert!(matches!(detector.check_at("spiky", later), Verdict::Tripped { rate: 140, .. }));
+}
+
+async fn link(storage: &MemoryStorage, code: &str) {
+    storage
+        .save_url(ShortenedUrl {
+            original_url: format!("https://example.com/{}", code),
+            short_url: code.to_string(),
+            owner: Some("alice".to_string()),
+            ..ShortenedUrl::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_disabling_audits_and_notifies_once() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    link(&storage, "flood").await;
+    let notifier = Arc::new(RecordingNotifier::default());
+    let guard = RunawayGuard::new(RunawayConfig { multiple: 10.0, min_rate: 5 }, storage.clone())
+        .with_notifier(notifier.clone());
+
+    assert!(guard.disable("flood", 51, 0.0).await.unwrap());
+    // Another instance tripping on the same link changes nothing
+    assert!(!guard.disable("flood", 60, 0.0).await.unwrap());
+
+    assert!(storage.get_stats("flood").await.unwrap().disabled_at.is_some());
+    let audit = storage.list_audit_entries(10).await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!((audit[0].actor.as_str(), audit[0].action.as_str()), (RUNAWAY_ACTOR, "link.auto_disable"));
+    assert_eq!(audit[0].details["redirects_per_minute"], 51);
+
+    let events = notifier.events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let EventKind::LinkAutoDisabled(disabled) = &events[0].kind else { panic!("expected a runaway event") };
+    assert_eq!((disabled.short_code.as_str(), disabled.owner.as_deref()), ("flood", Some("alice")));
+    assert_eq!(disabled.redirects_per_minute, 51);
+}
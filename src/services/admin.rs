@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use tracing::{info, instrument, warn};
+use url::Host;
+
+use crate::errors::UrlShortenerResult;
+use crate::models::{BlockedDomain, DomainBan, NewAuditEntry, UsageRecord, VisitChange, VisitCounts};
+use super::UrlService;
+
+/// Lowercase ASCII form of a domain name as it appears in normalized URLs, e.g. `EXAMPLE.com.`
+/// is `example.com` and `bücher.de` is `xn--bcher-kva.de`; `None` for IP addresses and
+/// anything that is not a hostname
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_end_matches('.');
+    match Host::parse(domain).ok()? {
+        Host::Domain(domain) if domain.contains('.') => Some(domain),
+        _ => None,
+    }
+}
+
+/// Operator actions; each one is recorded in the audit log under the acting admin
+impl UrlService {
+    /// Resets or adjusts a link's visit count, e.g. after a bot storm or when migrating counts
+    #[instrument(skip(self))]
+    pub async fn change_visits(
+        &self,
+        short_code: &str,
+        change: VisitChange,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> UrlShortenerResult<VisitCounts> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        let counts = self.storage.change_visits(short_code, change).await?;
+
+        let (action, delta) = match change {
+            VisitChange::Reset => ("visits.reset", None),
+            VisitChange::Adjust(delta) => ("visits.adjust", Some(delta)),
+        };
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: action.to_string(),
+                target: Some(short_code.to_string()),
+                details: json!({
+                    "delta": delta,
+                    "previous": counts.previous,
+                    "visits": counts.visits,
+                    "reason": reason,
+                }),
+            })
+            .await?;
+
+        info!(
+            short_code = %short_code,
+            action,
+            previous = counts.previous,
+            visits = counts.visits,
+            "Changed visit count"
+        );
+        Ok(counts)
+    }
+
+    /// Lets a disabled link redirect again, e.g. after the runaway guard stopped a legitimate
+    /// spike. Returns whether it was disabled; NotFound for unknown codes.
+    #[instrument(skip(self))]
+    pub async fn enable_link(&self, short_code: &str, actor: &str, reason: Option<&str>) -> UrlShortenerResult<bool> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+        if self.storage.enable_urls(&[short_code.to_string()]).await?.is_empty() {
+            self.storage.get_stats(short_code).await?;
+            return Ok(false);
+        }
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: "link.enable".to_string(),
+                target: Some(short_code.to_string()),
+                details: json!({ "reason": reason }),
+            })
+            .await?;
+
+        info!(short_code = %short_code, "Re-enabled link");
+        Ok(true)
+    }
+
+    /// Bans a destination domain and its subdomains for new links and disables the existing
+    /// ones, e.g. when a phishing campaign is discovered. `domain` must be normalized with
+    /// [`normalize_domain`].
+    #[instrument(skip(self))]
+    pub async fn ban_domain(&self, domain: &str, actor: &str, reason: Option<&str>) -> UrlShortenerResult<DomainBan> {
+        let ban = self.storage.ban_domain(domain, reason, actor).await?;
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: "domain.ban".to_string(),
+                target: Some(domain.to_string()),
+                details: json!({
+                    "disabled": ban.disabled,
+                    "reason": reason,
+                }),
+            })
+            .await?;
+
+        warn!(domain = %domain, disabled = ban.disabled.len(), "Banned destination domain");
+        Ok(ban)
+    }
+
+    pub async fn blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.storage.list_blocked_domains().await
+    }
+
+    /// Daily usage records of every owner from `from` until before `to`
+    pub async fn usage(&self, from: chrono::NaiveDate, to: chrono::NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.storage.list_usage(from, to).await
+    }
+
+    /// Records a state-changing admin API call with its redacted payloads, so destructive
+    /// actions can be reconstructed later
+    pub async fn record_admin_request(
+        &self,
+        actor: &str,
+        target: &str,
+        details: serde_json::Value,
+    ) -> UrlShortenerResult<()> {
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: "admin.request".to_string(),
+                target: Some(target.to_string()),
+                details,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes audit log entries older than `retention`, returning how many were removed
+    pub async fn purge_audit_log(&self, retention: chrono::Duration) -> UrlShortenerResult<u64> {
+        let purged = self.storage.purge_audit_entries(chrono::Utc::now() - retention).await?;
+        if purged > 0 {
+            info!(purged, "Purged expired audit log entries");
+        }
+        Ok(purged)
+    }
+
+    /// Purges expired audit log entries every hour for the lifetime of the process
+    pub fn spawn_audit_retention(self: Arc<Self>, retention: chrono::Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(60 * 60));
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.purge_audit_log(retention).await {
+                    warn!(error = %e, "Failed to purge expired audit log entries");
+                }
+            }
+        });
+    }
+
+    /// Every link's visit events from `from` until before `to`, streamed as a Parquet file
+    #[cfg(feature = "parquet-export")]
+    #[instrument(skip(self))]
+    pub async fn export_visits(
+        &self,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        actor: &str,
+    ) -> UrlShortenerResult<impl futures::Stream<Item = UrlShortenerResult<actix_web::web::Bytes>>> {
+        let export = crate::export::visits_parquet(self.storage.clone(), from, to)?;
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: "visits.export".to_string(),
+                target: None,
+                details: json!({ "from": from, "to": to }),
+            })
+            .await?;
+
+        info!(%from, %to, "Exporting visit events");
+        Ok(export)
+    }
+}
@@ -0,0 +1,87 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_script::{Script, UnicodeScript};
+use tracing::warn;
+
+use crate::validation::FieldError;
+
+/// Maximum length of a custom alias, in characters
+pub const MAX_ALIAS_LENGTH: usize = 64;
+
+/// Characters that would break or disguise a path segment: URL delimiters, and invisible
+/// formatting characters (bidi overrides, zero-width spaces) used to spoof how a link reads
+fn is_forbidden(c: char) -> bool {
+    c.is_whitespace()
+        || c.is_control()
+        || matches!(c, '/' | '\\' | '?' | '#' | '%' | '"' | '<' | '>' | '`' | '{' | '}' | '|' | '^')
+        || matches!(c, '\u{200B}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}')
+}
+
+/// Rules for custom aliases, which may use any Unicode script (including emoji)
+/// unless restricted to an allow-list of scripts
+#[derive(Debug, Clone, Default)]
+pub struct AliasPolicy {
+    /// Scripts an alias may use besides Common (digits, punctuation, emoji) and Inherited
+    /// (combining marks, joiners); `None` allows every script
+    allowed_scripts: Option<Vec<Script>>,
+}
+
+impl AliasPolicy {
+    /// Restricts aliases to the given scripts, by full (`Cyrillic`) or ISO 15924 (`Cyrl`) name.
+    /// Unknown names are logged and ignored; an empty list allows every script.
+    pub fn with_allowed_scripts<S: AsRef<str>>(names: &[S]) -> Self {
+        let scripts: Vec<Script> = names
+            .iter()
+            .map(AsRef::as_ref)
+            .filter_map(|name| {
+                let script = Script::from_full_name(name).or_else(|| Script::from_short_name(name));
+                if script.is_none() {
+                    warn!(script = %name, "Ignoring unknown alias script");
+                }
+                script
+            })
+            .collect();
+
+        Self {
+            allowed_scripts: (!scripts.is_empty()).then_some(scripts),
+        }
+    }
+
+    /// Validates an alias and returns its NFC-normalized form
+    pub fn normalize(&self, alias: &str) -> Result<String, FieldError> {
+        let alias: String = alias.nfc().collect();
+        let error = |code: &str, message: String| FieldError {
+            field: "custom_alias".to_string(),
+            code: code.to_string(),
+            message,
+        };
+
+        if alias.is_empty() {
+            return Err(error("required", "custom_alias must not be empty".to_string()));
+        }
+        if alias.chars().count() > MAX_ALIAS_LENGTH {
+            return Err(error(
+                "too_long",
+                format!("custom_alias must be at most {} characters", MAX_ALIAS_LENGTH),
+            ));
+        }
+        if let Some(c) = alias.chars().find(|&c| is_forbidden(c)) {
+            return Err(error(
+                "invalid_characters",
+                format!("custom_alias must not contain {:?}", c),
+            ));
+        }
+        if let Some(allowed) = &self.allowed_scripts {
+            let disallowed = alias.chars().map(|c| c.script()).find(|script| {
+                !matches!(script, Script::Common | Script::Inherited) && !allowed.contains(script)
+            });
+            if let Some(script) = disallowed {
+                return Err(error(
+                    "script_not_allowed",
+                    format!("custom_alias must not use {} script", script.full_name()),
+                ));
+            }
+        }
+
+        Ok(alias)
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{Duration, Utc};
+use tracing::{info, instrument, warn};
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{Campaign, CampaignStats};
+use super::UrlService;
+
+/// Campaigns are per owner like collections, and only take the owner's own links; other
+/// owners' campaigns are reported as not found.
+impl UrlService {
+    #[instrument(skip(self))]
+    pub async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        let name = name.trim();
+        let campaign = self.storage.create_campaign(owner, name).await.map_err(|e| match e.error_type {
+            UrlShortenerErrorType::Conflict(_) => {
+                UrlShortenerErrorType::Conflict(format!("Campaign '{}' already exists", name)).into()
+            }
+            _ => e,
+        })?;
+        info!(campaign_id = campaign.id, "Created campaign");
+        Ok(campaign)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.storage.list_campaigns(owner).await
+    }
+
+    /// Deletes a campaign, keeping its links
+    #[instrument(skip(self))]
+    pub async fn delete_campaign(&self, id: i64, owner: &str) -> UrlShortenerResult<()> {
+        self.owned_campaign(id, owner).await?;
+        self.storage.delete_campaign(id).await?;
+        info!(campaign_id = id, "Deleted campaign");
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn add_campaign_link(&self, id: i64, owner: &str, short_code: &str) -> UrlShortenerResult<()> {
+        self.owned_campaign(id, owner).await?;
+        let link = self.get_owned_link(short_code, owner).await?;
+        self.storage.add_campaign_link(id, &link.short_code).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn remove_campaign_link(&self, id: i64, owner: &str, short_code: &str) -> UrlShortenerResult<()> {
+        self.owned_campaign(id, owner).await?;
+        let link = self.get_owned_link(short_code, owner).await?;
+        self.storage.remove_campaign_link(id, &link.short_code).await
+    }
+
+    /// Visits across a campaign's links, with a daily series over the last `days` days
+    /// including today
+    #[instrument(skip(self))]
+    pub async fn campaign_stats(&self, id: i64, owner: &str, days: u32) -> UrlShortenerResult<CampaignStats> {
+        self.owned_campaign(id, owner).await?;
+        let first_day = Utc::now().date_naive() - Duration::days(i64::from(days.max(1)) - 1);
+        self.storage.campaign_stats(id, first_day.and_time(Default::default()).and_utc()).await
+    }
+
+    /// Looks up a campaign, failing with `NotFound` unless it belongs to `owner`
+    async fn owned_campaign(&self, id: i64, owner: &str) -> UrlShortenerResult<Campaign> {
+        let campaign = self.storage.get_campaign(id).await?;
+        if campaign.owner != owner {
+            warn!(campaign_id = id, "Refused access to another owner's campaign");
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(campaign)
+    }
+}
@@ -0,0 +1,106 @@
+use rand::Rng;
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Length of generated short codes
+pub const SHORT_CODE_LENGTH: usize = 10;
+
+/// Strategy for minting new short codes
+pub trait CodeGenerator: Send + Sync {
+    /// Generates a candidate short code; uniqueness is enforced by storage
+    fn generate(&self) -> String;
+}
+
+/// Generates random codes from nanoid's URL-safe alphabet
+#[derive(Debug, Clone)]
+pub struct RandomCodeGenerator {
+    length: usize,
+}
+
+impl RandomCodeGenerator {
+    pub fn new(length: usize) -> Self {
+        Self { length }
+    }
+}
+
+impl Default for RandomCodeGenerator {
+    fn default() -> Self {
+        Self::new(SHORT_CODE_LENGTH)
+    }
+}
+
+impl CodeGenerator for RandomCodeGenerator {
+    fn generate(&self) -> String {
+        let alphabet = &nanoid::alphabet::SAFE;
+        let mut rng = rand::thread_rng();
+        (0..self.length)
+            .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+            .collect()
+    }
+}
+
+/// How short codes taken from request paths are matched against stored codes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CodeLookup {
+    /// Codes are stored lowercase and matched regardless of case
+    pub case_insensitive: bool,
+    /// Trailing slashes and whitespace, as often left by chat apps, are ignored
+    pub trim: bool,
+}
+
+impl CodeLookup {
+    /// Brings a short code into its stored form; borrows when nothing changes.
+    /// Codes are always NFC-normalized so differently composed Unicode aliases match.
+    pub fn normalize<'a>(&self, short_code: &'a str) -> Cow<'a, str> {
+        let code = if self.trim {
+            short_code.trim().trim_end_matches(|c: char| c == '/' || c.is_whitespace())
+        } else {
+            short_code
+        };
+
+        let code = if is_nfc(code) {
+            Cow::Borrowed(code)
+        } else {
+            Cow::Owned(code.nfc().collect())
+        };
+
+        if self.case_insensitive && code.chars().any(char::is_uppercase) {
+            Cow::Owned(code.to_lowercase())
+        } else {
+            code
+        }
+    }
+}
+
+/// Base62 encoding of numeric ids, for compact codes derived from sequences or counters
+pub mod base62 {
+    const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+    /// Encodes a number using the shortest representation (no leading zeros)
+    pub fn encode(mut value: u64) -> String {
+        if value == 0 {
+            return "0".to_string();
+        }
+
+        let mut digits = Vec::new();
+        while value > 0 {
+            digits.push(ALPHABET[(value % 62) as usize]);
+            value /= 62;
+        }
+        digits.reverse();
+        String::from_utf8(digits).expect("base62 alphabet is ASCII")
+    }
+
+    /// Decodes a canonical base62 string; rejects empty input, leading zeros,
+    /// characters outside the alphabet and values that overflow `u64`
+    pub fn decode(encoded: &str) -> Option<u64> {
+        if encoded.is_empty() || (encoded.len() > 1 && encoded.starts_with('0')) {
+            return None;
+        }
+
+        encoded.bytes().try_fold(0u64, |value, byte| {
+            let digit = ALPHABET.iter().position(|&c| c == byte)? as u64;
+            value.checked_mul(62)?.checked_add(digit)
+        })
+    }
+}
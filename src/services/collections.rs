@@ -0,0 +1,74 @@
+use tracing::{info, instrument, warn};
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{Collection, CollectionStats};
+use super::UrlService;
+
+/// Collections are per owner; other owners' collections are reported as not found so their
+/// existence is not revealed.
+impl UrlService {
+    #[instrument(skip(self))]
+    pub async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        let name = name.trim();
+        let collection = self
+            .storage
+            .create_collection(owner, name)
+            .await
+            .map_err(|e| name_conflict(e, name))?;
+        info!(collection_id = collection.id, "Created collection");
+        Ok(collection)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_collection(&self, id: i64, owner: &str) -> UrlShortenerResult<CollectionStats> {
+        self.owned_collection(id, Some(owner)).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.storage.list_collections(owner).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn rename_collection(&self, id: i64, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        self.owned_collection(id, Some(owner)).await?;
+
+        let name = name.trim();
+        let collection = self
+            .storage
+            .rename_collection(id, name)
+            .await
+            .map_err(|e| name_conflict(e, name))?;
+        info!(collection_id = id, "Renamed collection");
+        Ok(collection)
+    }
+
+    /// Deletes a collection, leaving its links unassigned
+    #[instrument(skip(self))]
+    pub async fn delete_collection(&self, id: i64, owner: &str) -> UrlShortenerResult<()> {
+        self.owned_collection(id, Some(owner)).await?;
+        self.storage.delete_collection(id).await?;
+        info!(collection_id = id, "Deleted collection");
+        Ok(())
+    }
+
+    /// Looks up a collection, failing with `NotFound` unless it belongs to `owner`
+    pub(super) async fn owned_collection(&self, id: i64, owner: Option<&str>) -> UrlShortenerResult<CollectionStats> {
+        let collection = self.storage.get_collection(id).await?;
+        if Some(collection.owner.as_str()) != owner {
+            warn!(collection_id = id, "Refused access to another owner's collection");
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(collection)
+    }
+}
+
+/// Names the collection in the storage layer's generic conflict error
+fn name_conflict(error: crate::errors::UrlShortenerError, name: &str) -> crate::errors::UrlShortenerError {
+    match error.error_type {
+        UrlShortenerErrorType::Conflict(_) => {
+            UrlShortenerErrorType::Conflict(format!("Collection '{}' already exists", name)).into()
+        },
+        _ => error,
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{instrument, warn};
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use super::UrlService;
+
+/// Daily visits of several links over the same days, for charting them side by side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkComparison {
+    /// Days compared, oldest first, ending today (UTC)
+    pub dates: Vec<NaiveDate>,
+    /// One series per link, in the order the links were asked for
+    pub links: Vec<LinkSeries>,
+}
+
+/// One link's visits over the compared days
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkSeries {
+    pub short_url: String,
+    /// All-time visits
+    pub visits: u64,
+    /// Recorded visits over the compared days
+    pub period_visits: i64,
+    /// Recorded visits per day, aligned with the comparison's `dates`
+    pub daily: Vec<i64>,
+}
+
+impl UrlService {
+    /// Daily visits of the links with these codes over the last `days` days. Signed-only links
+    /// can only be compared by their owner, as with their other statistics.
+    #[instrument(skip(self))]
+    pub async fn compare_links(
+        &self,
+        short_codes: &[&str],
+        days: u32,
+        viewer: Option<&str>,
+    ) -> UrlShortenerResult<LinkComparison> {
+        let first_day = Utc::now().date_naive() - Duration::days(i64::from(days.max(1)) - 1);
+        let dates: Vec<NaiveDate> = first_day.iter_days().take(days.max(1) as usize).collect();
+
+        let mut links = Vec::with_capacity(short_codes.len());
+        for short_code in short_codes {
+            let link = self.get_url_stats(short_code).await?;
+            if link.signed_only && link.owner.as_deref() != viewer {
+                warn!(short_code = %link.short_code, "Refused to compare another owner's signed-only link");
+                return Err(UrlShortenerErrorType::NotFound.into());
+            }
+            // Aliases of one link are the same link
+            if !links.iter().any(|series: &LinkSeries| series.short_url == link.short_code) {
+                links.push(LinkSeries {
+                    short_url: link.short_code,
+                    visits: link.visits,
+                    period_visits: 0,
+                    daily: vec![0; dates.len()],
+                });
+            }
+        }
+
+        let codes: Vec<String> = links.iter().map(|series| series.short_url.clone()).collect();
+        let visits = self.storage.daily_link_visits(&codes, first_day.and_time(Default::default()).and_utc()).await?;
+
+        let day_index: HashMap<NaiveDate, usize> = dates.iter().enumerate().map(|(i, date)| (*date, i)).collect();
+        for day in visits {
+            let series = links.iter_mut().find(|series| series.short_url == day.short_code);
+            if let (Some(series), Some(&i)) = (series, day_index.get(&day.date)) {
+                series.daily[i] = day.visits;
+                series.period_visits += day.visits;
+            }
+        }
+        Ok(LinkComparison { dates, links })
+    }
+}
@@ -0,0 +1,221 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION, USER_AGENT};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::preview::{is_public_http, public_addrs};
+
+/// File extensions and content types refused when no denylist is configured: executables,
+/// scripts and installers
+pub const DEFAULT_CONTENT_DENYLIST: &[&str] = &[
+    ".exe",
+    ".scr",
+    ".apk",
+    ".msi",
+    ".bat",
+    ".cmd",
+    ".vbs",
+    ".ps1",
+    ".jar",
+    "application/x-msdownload",
+    "application/x-msdos-program",
+    "application/x-dosexec",
+    "application/x-msi",
+    "application/vnd.android.package-archive",
+    "application/java-archive",
+];
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+const CHECK_USER_AGENT: &str = concat!("url-map/", env!("CARGO_PKG_VERSION"), " (link check)");
+/// Redirects followed from the destination; each hop must resolve to public addresses
+const MAX_REDIRECTS: usize = 5;
+
+/// Looks at what a destination serves before it is shortened, refusing direct links to the
+/// file types on a denylist.
+///
+/// The path is checked first, then a `HEAD` request reports the content type, a download
+/// file name and where redirects lead. Every hop is only requested at the public addresses
+/// its host resolves to, so a destination cannot point the check at an internal service.
+/// Destinations that cannot be reached are let through
+/// unchecked rather than failing link creation.
+#[derive(Debug, Clone)]
+pub struct ContentCheck {
+    http: HttpClient,
+    /// Lowercased, without the leading dot
+    extensions: Vec<String>,
+    /// Lowercased MIME essences, e.g. `application/x-msdownload`
+    content_types: Vec<String>,
+}
+
+impl ContentCheck {
+    /// Builds a check from denylist entries: those starting with `.` are file extensions,
+    /// anything else a content type
+    pub fn with_denylist<S: AsRef<str>>(denylist: &[S]) -> Self {
+        let (extensions, content_types) = denylist
+            .iter()
+            .map(|entry| entry.as_ref().trim().to_ascii_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .partition::<Vec<_>, _>(|entry| entry.starts_with('.'));
+        Self {
+            http: HttpClient::default(),
+            extensions: extensions.into_iter().map(|extension| extension[1..].to_string()).collect(),
+            content_types,
+        }
+    }
+
+    /// Sends the check's requests through a shared client
+    pub fn with_http_client(mut self, http: &HttpClient) -> Self {
+        self.http = http.clone();
+        self
+    }
+
+    /// Checks a destination and returns the content type it reported, if any.
+    /// Fails with `BlockedUrl` when it points at a denied file type.
+    pub async fn inspect(&self, url: &Url) -> UrlShortenerResult<Option<String>> {
+        self.inspect_with(url, |url| async move { public_addrs(&url).await }).await
+    }
+
+    /// Checks a destination, requesting each hop only at the addresses `addrs_of` approves.
+    /// Redirects are followed one hop at a time so that every hop is checked.
+    pub(crate) async fn inspect_with<F, Fut>(&self, url: &Url, addrs_of: F) -> UrlShortenerResult<Option<String>>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = Result<Vec<SocketAddr>, String>>,
+    {
+        self.check_path(url)?;
+        if !is_public_http(url) {
+            return Ok(None);
+        }
+
+        let Some(response) = self.head(url.clone(), addrs_of).await? else {
+            return Ok(None);
+        };
+
+        // Download links often name the file in a header
+        let file_name = response
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(attachment_file_name);
+        if let Some(file_name) = file_name {
+            self.check_file_name(file_name)?;
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(mime_essence)
+            .filter(|content_type| !content_type.is_empty());
+        if let Some(content_type) = &content_type {
+            if self.content_types.contains(content_type) {
+                warn!(content_type = %content_type, "Blocked link to a denied content type");
+                return Err(UrlShortenerErrorType::BlockedUrl(format!(
+                    "Links to '{}' content are not allowed",
+                    content_type
+                )).into());
+            }
+        }
+        Ok(content_type)
+    }
+
+    /// Sends `HEAD` to a destination and the redirects it leads to, answering with the final
+    /// successful response, or `None` when there is none. Fails with `BlockedUrl` when a hop
+    /// points at a denied file type, as download links often redirect to the file itself.
+    async fn head<F, Fut>(&self, mut url: Url, addrs_of: F) -> UrlShortenerResult<Option<reqwest::Response>>
+    where
+        F: Fn(Url) -> Fut,
+        Fut: Future<Output = Result<Vec<SocketAddr>, String>>,
+    {
+        let no_answer = |reason: &dyn std::fmt::Display| {
+            debug!(reason = %reason, "Destination content check got no answer");
+            Ok(None)
+        };
+        for _ in 0..=MAX_REDIRECTS {
+            self.check_path(&url)?;
+            let addrs = match addrs_of(url.clone()).await {
+                Ok(addrs) => addrs,
+                Err(reason) => return no_answer(&reason),
+            };
+            let http = match self.http.pinned(url.host_str().unwrap_or_default(), &addrs) {
+                Ok(http) => http,
+                Err(e) => return no_answer(&e),
+            };
+            let request = http
+                .head(url.clone())
+                .timeout(http.timeout_within(CHECK_TIMEOUT))
+                .header(USER_AGENT, CHECK_USER_AGENT);
+            let response = match http.send("content_check", request).await {
+                Ok(response) => response,
+                Err(e) => return no_answer(&e),
+            };
+
+            if response.status().is_redirection() {
+                let next = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|location| url.join(location).ok());
+                match next {
+                    Some(next) => url = next,
+                    None => return no_answer(&"redirect without a usable location"),
+                }
+                continue;
+            }
+            if !response.status().is_success() {
+                return no_answer(&response.status());
+            }
+            return Ok(Some(response));
+        }
+        no_answer(&"too many redirects")
+    }
+
+    /// Checks the file name at the end of a URL's path
+    fn check_path(&self, url: &Url) -> UrlShortenerResult<()> {
+        let file_name = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default();
+        self.check_file_name(file_name)
+    }
+
+    fn check_file_name(&self, file_name: &str) -> UrlShortenerResult<()> {
+        let Some((_, extension)) = file_name.rsplit_once('.') else {
+            return Ok(());
+        };
+        let extension = extension.to_ascii_lowercase();
+        if self.extensions.contains(&extension) {
+            warn!(extension = %extension, "Blocked link to a denied file type");
+            return Err(UrlShortenerErrorType::BlockedUrl(format!("Links to '.{}' files are not allowed", extension)).into());
+        }
+        Ok(())
+    }
+}
+
+
+impl Default for ContentCheck {
+    fn default() -> Self {
+        Self::with_denylist(DEFAULT_CONTENT_DENYLIST)
+    }
+}
+
+/// Content type without parameters, lowercased: `Text/HTML; charset=utf-8` is `text/html`
+pub fn mime_essence(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase()
+}
+
+/// File name a `Content-Disposition` header suggests saving the response as
+pub fn attachment_file_name(disposition: &str) -> Option<&str> {
+    disposition.split(';').map(str::trim).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        let value = value.trim().trim_matches('"');
+        match name.trim().to_ascii_lowercase().as_str() {
+            // RFC 5987 form: charset'language'percent-encoded-name
+            "filename*" => value.rsplit_once("''").map(|(_, name)| name).or(Some(value)),
+            "filename" => Some(value),
+            _ => None,
+        }
+    })
+}
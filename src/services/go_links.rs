@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, instrument, warn};
+
+use crate::errors::{TakenKeyword, UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{NewAuditEntry, ShortenedUrl as StorageShortenedUrl};
+use crate::validation::FieldError;
+use super::{CreateOptions, Namespace, ShortenedUrl, UrlService, MAX_KEYWORD_DEPTH};
+
+/// Maximum number of go links returned by a search or listing
+pub const MAX_GO_LINKS: i64 = 100;
+
+/// Free keywords suggested when a keyword is taken
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Numbered variants of a taken keyword tried for suggestions, e.g. `payroll-2`
+const NUMBERED_SUGGESTIONS: usize = 6;
+
+/// A link in the keyword namespace, as go link deployments show it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoLink {
+    /// The code without its namespace, e.g. `payroll` for `go/payroll`
+    pub keyword: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub owner: Option<String>,
+    pub visits: u64,
+}
+
+impl GoLink {
+    fn from_stored(namespace: &Namespace, url: StorageShortenedUrl) -> Self {
+        Self {
+            keyword: Self::keyword_of(namespace, &url.short_url).to_string(),
+            url: url.original_url,
+            title: url.title,
+            owner: url.owner,
+            visits: url.visits as u64,
+        }
+    }
+
+    /// The keyword of a stored code, e.g. `team/runbook` for `go/team/runbook`
+    fn keyword_of<'a>(namespace: &Namespace, short_code: &'a str) -> &'a str {
+        short_code
+            .strip_prefix(namespace.prefix())
+            .and_then(|code| code.strip_prefix('/'))
+            .unwrap_or(short_code)
+    }
+}
+
+/// Go links: human keywords in the keyword namespace, found by searching rather than by
+/// remembering a code
+impl UrlService {
+    /// The keyword namespace; go links are not found while none is configured
+    fn go_namespace(&self) -> UrlShortenerResult<&Namespace> {
+        self.namespaces.keywords().ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    /// Creates the go link `keyword`, owned by `owner`. Keywords are written loosely, so
+    /// `Payroll Team` is stored as `payroll-team`.
+    #[instrument(skip(self, url))]
+    pub async fn create_go_link(
+        &self,
+        keyword: &str,
+        url: String,
+        title: Option<String>,
+        owner: &str,
+    ) -> UrlShortenerResult<GoLink> {
+        let namespace = self.go_namespace()?;
+        let options = CreateOptions {
+            owner: Some(owner.to_string()),
+            custom_alias: Some(keyword.to_string()),
+            title,
+            namespace: Some(namespace.prefix().to_string()),
+            ..CreateOptions::default()
+        };
+        let created = match self.create_short_url_with(url, options).await {
+            Ok(created) => created,
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::AliasTaken(_)) => {
+                return Err(self.keyword_taken(namespace, keyword, owner).await);
+            }
+            Err(e) => return Err(e),
+        };
+        info!(short_code = %created.short_code, "Created go link");
+        Ok(GoLink::from_stored(namespace, created.into()))
+    }
+
+    /// The error for a keyword in use: who owns it, and similar keywords that are free. The
+    /// caller's own name is offered as a parent first, then numbered variants.
+    async fn keyword_taken(&self, namespace: &Namespace, keyword: &str, owner: &str) -> UrlShortenerError {
+        let keyword = namespace.keyword(keyword).into_owned();
+        let current_owner = match self.storage.get_stats(&namespace.qualify(&keyword)).await {
+            Ok(url) => url.owner,
+            Err(_) => None,
+        };
+
+        let nested = (keyword.split('/').count() < MAX_KEYWORD_DEPTH).then(|| format!("{}/{}", owner, keyword));
+        let numbered = (2..).take(NUMBERED_SUGGESTIONS).map(|n| format!("{}-{}", keyword, n));
+        let mut suggestions = Vec::with_capacity(MAX_SUGGESTIONS);
+        for candidate in nested.into_iter().chain(numbered) {
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+            // Candidates that are not valid keywords, e.g. for owners with odd names, are skipped
+            let Ok(short_code) = self.keyword_code(&candidate, namespace) else { continue };
+            match self.storage.get_stats(&short_code).await {
+                Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => {
+                    suggestions.push(GoLink::keyword_of(namespace, &short_code).to_string());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(error = %e, "Failed to check a suggested keyword");
+                    break;
+                }
+            }
+        }
+
+        warn!(keyword = %keyword, "Go link keyword is taken");
+        UrlShortenerErrorType::KeywordTaken(Box::new(TakenKeyword { keyword, owner: current_owner, suggestions })).into()
+    }
+
+    /// Hands a link owned by `owner` to `to`, e.g. when a go link's maintainer moves teams.
+    /// The link leaves its collection, which stays with `owner`. Recorded in the audit log.
+    #[instrument(skip(self))]
+    pub async fn transfer_link(&self, short_code: &str, owner: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        let to = to.trim();
+        if to == owner {
+            return Err(UrlShortenerErrorType::ValidationFailed(vec![FieldError {
+                field: "to".to_string(),
+                code: "same_owner".to_string(),
+                message: "The link already belongs to you".to_string(),
+            }]).into());
+        }
+        let link = self.get_owned_link(short_code, owner).await?;
+        let transferred = self.storage.transfer_url(&link.short_code, owner, to).await?;
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: owner.to_string(),
+                action: "link.transfer".to_string(),
+                target: Some(link.short_code.clone()),
+                details: json!({ "from": owner, "to": to }),
+            })
+            .await?;
+
+        info!(short_code = %link.short_code, to = %to, "Transferred link");
+        Ok(transferred.into())
+    }
+
+    /// Go links whose keyword or title contains `query`, regardless of case, most used first
+    pub async fn search_go_links(&self, query: &str, limit: i64) -> UrlShortenerResult<Vec<GoLink>> {
+        self.find_go_links(Some(query.trim()), limit).await
+    }
+
+    /// The most used go links
+    pub async fn popular_go_links(&self, limit: i64) -> UrlShortenerResult<Vec<GoLink>> {
+        self.find_go_links(None, limit).await
+    }
+
+    async fn find_go_links(&self, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<GoLink>> {
+        let namespace = self.go_namespace()?;
+        let prefix = format!("{}/", namespace.prefix());
+        let limit = limit.clamp(1, MAX_GO_LINKS);
+        let links = self.storage.search_urls(&prefix, query, limit).await?;
+        Ok(links.into_iter().map(|url| GoLink::from_stored(namespace, url)).collect())
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::errors::UrlShortenerResult;
+use crate::import::{ImportedLink, ParsedExport, RejectedRow};
+use crate::models::{first_version, ShortenedUrl as StorageShortenedUrl};
+use super::{normalize_url_within, UrlService};
+
+/// Links written to storage per call
+const IMPORT_BATCH_SIZE: usize = 1_000;
+
+/// Outcome of an import
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    /// Links created
+    pub imported: u64,
+    /// Links left out because their short code is already taken
+    pub skipped: u64,
+    /// Rows that could not be mapped to a link, in file order
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl UrlService {
+    /// Creates the links read from another shortener's export, keeping their short codes,
+    /// creation dates and click counts. Existing links are never overwritten.
+    #[instrument(skip(self, parsed), fields(links = parsed.links.len()))]
+    pub async fn import_links(&self, parsed: ParsedExport, owner: Option<&str>) -> UrlShortenerResult<ImportSummary> {
+        let mut rejected = parsed.rejected;
+        let mut urls = Vec::with_capacity(parsed.links.len());
+        for link in parsed.links {
+            match self.imported_url(link, owner) {
+                Ok(url) => urls.push(url),
+                Err(row) => rejected.push(row),
+            }
+        }
+        rejected.sort_by_key(|row| row.line);
+
+        self.make_room(owner, urls.len() as u64).await?;
+        let mut imported = 0;
+        for batch in urls.chunks(IMPORT_BATCH_SIZE) {
+            imported += self.storage.import_urls(batch.to_vec()).await?;
+        }
+
+        let summary = ImportSummary {
+            imported,
+            skipped: urls.len() as u64 - imported,
+            rejected,
+        };
+        info!(
+            imported = summary.imported,
+            skipped = summary.skipped,
+            rejected = summary.rejected.len(),
+            "Imported links"
+        );
+        Ok(summary)
+    }
+
+    /// Applies the checks links created through the API go through
+    fn imported_url(&self, link: ImportedLink, owner: Option<&str>) -> Result<StorageShortenedUrl, RejectedRow> {
+        let line = link.line;
+        let reject = |reason: String| RejectedRow { line, reason };
+
+        let url = normalize_url_within(&link.original_url, self.url_length_limits().for_owner(owner))
+            .map_err(|e| reject(e.error_type.message()))?;
+        self.check_homographs(&url).map_err(|e| reject(e.error_type.message()))?;
+        let short_code = self
+            .alias_policy
+            .normalize(&link.short_code)
+            .map_err(|e| reject(format!("short code '{}': {}", link.short_code, e.message)))?;
+
+        Ok(StorageShortenedUrl {
+            id: 0,
+            original_url: url.to_string(),
+            short_url: self.code_lookup.normalize(&short_code).into_owned(),
+            created_at: link.created_at.unwrap_or_else(Utc::now),
+            visits: link.clicks.unwrap_or(0),
+            last_visited_at: None,
+            owner: owner.map(str::to_string),
+            title: link.title,
+            notes: None,
+            created_by: None,
+            collection_id: None,
+            click_goal: None,
+            goal_reached_at: None,
+            pass_through: false,
+            content_type: None,
+            disabled_at: None,
+            signed_only: false,
+            tracking_snippet: None,
+            redirect_headers: None,
+            hide_referrer: false,
+            version: first_version(),
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use tracing::{info, instrument, warn};
+
+use crate::auth::{issue_key, IssuedKey};
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::StoredApiKey;
+use super::UrlService;
+
+/// Self-service API keys. Only key hashes are stored, so the plaintext of a key is returned
+/// once, when it is issued. Other owners' keys are reported as not found.
+impl UrlService {
+    /// Issues a new key for `owner`, returning it alongside its plaintext
+    #[instrument(skip(self))]
+    pub async fn create_api_key(&self, owner: &str, name: Option<&str>) -> UrlShortenerResult<(StoredApiKey, String)> {
+        let IssuedKey { key, hash, prefix } = issue_key();
+        let stored = self.storage.create_api_key(owner, name, &hash, &prefix).await?;
+        info!(key_id = stored.id, "Issued API key");
+        Ok((stored, key))
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.storage.list_api_keys(owner).await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn revoke_api_key(&self, id: i64, owner: &str) -> UrlShortenerResult<()> {
+        self.owned_api_key(id, owner).await?;
+        self.storage.revoke_api_key(id).await?;
+        info!(key_id = id, "Revoked API key");
+        Ok(())
+    }
+
+    /// Replaces a key with a new one under the same name; the old key stops working immediately
+    #[instrument(skip(self))]
+    pub async fn rotate_api_key(&self, id: i64, owner: &str) -> UrlShortenerResult<(StoredApiKey, String)> {
+        self.owned_api_key(id, owner).await?;
+
+        let IssuedKey { key, hash, prefix } = issue_key();
+        let stored = self.storage.rotate_api_key(id, &hash, &prefix).await?;
+        info!(key_id = id, new_key_id = stored.id, "Rotated API key");
+        Ok((stored, key))
+    }
+
+    /// Looks up an active key, failing with `NotFound` unless it belongs to `owner`
+    async fn owned_api_key(&self, id: i64, owner: &str) -> UrlShortenerResult<StoredApiKey> {
+        let key = self.storage.get_api_key(id).await?;
+        if key.owner != owner {
+            warn!(key_id = id, "Refused access to another owner's API key");
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(key)
+    }
+}
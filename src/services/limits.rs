@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use super::MAX_URL_LENGTH;
+
+/// Longest original URL any tier may be allowed; anything longer is refused before it is parsed
+pub const MAX_URL_LENGTH_CEILING: usize = 32_768;
+
+/// Tier of callers without an API key
+pub const ANONYMOUS_TIER: &str = "anonymous";
+
+/// Tier of API key owners not assigned to one
+pub const DEFAULT_TIER: &str = "default";
+
+/// Longest original URL each tier of caller may shorten.
+///
+/// Owners are assigned to tiers by name; tiers without a limit of their own, including the
+/// `default` and `anonymous` ones unless configured, get `default`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlLengthLimits {
+    /// Limit of tiers that have none of their own
+    pub default: usize,
+    /// Limits by tier
+    pub tiers: HashMap<String, usize>,
+    /// Tier of each API key owner
+    pub owner_tiers: HashMap<String, String>,
+}
+
+impl Default for UrlLengthLimits {
+    fn default() -> Self {
+        Self { default: MAX_URL_LENGTH, tiers: HashMap::new(), owner_tiers: HashMap::new() }
+    }
+}
+
+impl UrlLengthLimits {
+    /// Tier of a caller, by the owner of its API key
+    pub fn tier(&self, owner: Option<&str>) -> &str {
+        match owner {
+            Some(owner) => self.owner_tiers.get(owner).map_or(DEFAULT_TIER, String::as_str),
+            None => ANONYMOUS_TIER,
+        }
+    }
+
+    /// Longest original URL a caller may shorten, never above [`MAX_URL_LENGTH_CEILING`]
+    pub fn for_owner(&self, owner: Option<&str>) -> usize {
+        let limit = self.tiers.get(self.tier(owner)).copied().unwrap_or(self.default);
+        limit.min(MAX_URL_LENGTH_CEILING)
+    }
+}
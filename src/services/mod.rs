@@ -1,10 +1,61 @@
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, instrument, warn};
 use url::Url;
-use crate::errors::{UrlShortenerResult, UrlShortenerErrorType};
-use crate::models::ShortenedUrl as StorageShortenedUrl;
-use crate::storage::StorageRef;
-use nanoid::nanoid;
+use crate::errors::{TakenAlias, UrlShortenerError, UrlShortenerResult, UrlShortenerErrorType};
+use crate::models::{
+    first_version, LinkAlias, MetadataUpdate, Page, PageCursor, RedirectHeaders, RetiredCode, ShortenedUrl as StorageShortenedUrl,
+    StatsOnRegenerate, SourceVisits, UrlCursor, UrlFilter, UrlSort, VisitHeatmap, VisitTimeline,
+};
+use crate::storage::{SlowQueryStats, StatementCacheStats, StorageRef};
+use crate::validation::{blocked_scheme, inspect_host, FieldError, HomographPolicy, UrlWarning};
+use sqlx::types::Json;
+use std::sync::Arc;
+
+mod admin;
+mod aliases;
+mod campaigns;
+mod codes;
+mod collections;
+mod comparison;
+mod content_check;
+mod go_links;
+mod import;
+mod keys;
+mod limits;
+mod namespaces;
+mod profanity;
+mod quota;
+mod safety;
+mod snowflake;
+mod words;
+
+pub use admin::normalize_domain;
+pub use aliases::{AliasPolicy, MAX_ALIAS_LENGTH};
+pub use codes::{base62, CodeGenerator, CodeLookup, RandomCodeGenerator, SHORT_CODE_LENGTH};
+pub use comparison::{LinkComparison, LinkSeries};
+pub use content_check::{attachment_file_name, mime_essence, ContentCheck, DEFAULT_CONTENT_DENYLIST};
+pub use go_links::{GoLink, MAX_GO_LINKS};
+pub use import::ImportSummary;
+pub use limits::{UrlLengthLimits, ANONYMOUS_TIER, DEFAULT_TIER, MAX_URL_LENGTH_CEILING};
+pub use namespaces::{Namespace, Namespaces, MAX_KEYWORD_DEPTH, MAX_NAMESPACE_LENGTH};
+pub use profanity::ProfanityFilter;
+pub use quota::{LinkQuota, QuotaPolicy};
+pub use safety::{Safety, Verdict};
+pub use snowflake::{SnowflakeCodeGenerator, SnowflakeId, MAX_REGIONS, MAX_WORKERS};
+pub use words::WordCodeGenerator;
+
+/// Attempts at minting a unique short code before giving up
+const MAX_CODE_GENERATION_ATTEMPTS: usize = 3;
+
+/// Candidates drawn per attempt while generated codes keep spelling blocked words
+const MAX_CLEAN_CODE_DRAWS: usize = 10;
+
+/// Free aliases suggested when a custom alias is taken
+const MAX_ALIAS_SUGGESTIONS: usize = 3;
+
+/// Numbered variants of a taken alias tried for suggestions, e.g. `launch-2`
+const NUMBERED_ALIAS_SUGGESTIONS: usize = 6;
 
 #[derive(Debug, Clone)]
 pub struct ShortenedUrl {
@@ -12,6 +63,33 @@ pub struct ShortenedUrl {
     pub original_url: String,
     pub created_at: DateTime<Utc>,
     pub visits: u64,
+    /// When a visit was last counted
+    pub last_visited_at: Option<DateTime<Utc>>,
+    pub owner: Option<String>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub created_by: Option<String>,
+    pub collection_id: Option<i64>,
+    pub click_goal: Option<i64>,
+    pub goal_reached_at: Option<DateTime<Utc>>,
+    /// Paths and queries after the code are appended to the destination
+    pub pass_through: bool,
+    /// Content type the destination reported when the link was created
+    pub content_type: Option<String>,
+    /// When an admin disabled the link
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// Redirects require a valid signature
+    pub signed_only: bool,
+    /// Analytics snippet served on a forwarding page instead of a bare redirect
+    pub tracking_snippet: Option<String>,
+    /// Extra response headers sent with redirects
+    pub redirect_headers: Option<RedirectHeaders>,
+    /// Redirects withhold the referrer from the destination
+    pub hide_referrer: bool,
+    /// Revision of the editable fields, for conditional updates
+    pub version: i64,
+    /// Non-fatal findings about the original URL, reported when it is created
+    pub warnings: Vec<UrlWarning>,
 }
 
 impl From<ShortenedUrl> for StorageShortenedUrl {
@@ -22,6 +100,22 @@ impl From<ShortenedUrl> for StorageShortenedUrl {
             short_url: url.short_code,
             created_at: url.created_at,
             visits: url.visits as i64,
+            last_visited_at: url.last_visited_at,
+            owner: url.owner,
+            title: url.title,
+            notes: url.notes,
+            created_by: url.created_by,
+            collection_id: url.collection_id,
+            click_goal: url.click_goal,
+            goal_reached_at: url.goal_reached_at,
+            pass_through: url.pass_through,
+            content_type: url.content_type,
+            disabled_at: url.disabled_at,
+            signed_only: url.signed_only,
+            tracking_snippet: url.tracking_snippet,
+            redirect_headers: url.redirect_headers.map(Json),
+            hide_referrer: url.hide_referrer,
+            version: url.version,
         }
     }
 }
@@ -33,52 +127,497 @@ impl From<StorageShortenedUrl> for ShortenedUrl {
             original_url: url.original_url,
             created_at: url.created_at,
             visits: url.visits as u64,
+            last_visited_at: url.last_visited_at,
+            owner: url.owner,
+            title: url.title,
+            notes: url.notes,
+            created_by: url.created_by,
+            collection_id: url.collection_id,
+            click_goal: url.click_goal,
+            goal_reached_at: url.goal_reached_at,
+            pass_through: url.pass_through,
+            content_type: url.content_type,
+            disabled_at: url.disabled_at,
+            signed_only: url.signed_only,
+            tracking_snippet: url.tracking_snippet,
+            redirect_headers: url.redirect_headers.map(|Json(headers)| headers),
+            hide_referrer: url.hide_referrer,
+            version: url.version,
+            warnings: Vec::new(),
         }
     }
 }
 
+/// Default maximum length of an original URL, see [`UrlLengthLimits`]
+pub const MAX_URL_LENGTH: usize = 2048;
+
+/// Parses and normalizes an original URL, rejecting malformed input, input over the default
+/// [`MAX_URL_LENGTH`] and script or inline-content schemes
+pub fn normalize_url(original_url: &str) -> UrlShortenerResult<Url> {
+    normalize_url_within(original_url, MAX_URL_LENGTH)
+}
+
+/// Like [`normalize_url`], for URLs of at most `max_length` characters
+pub fn normalize_url_within(original_url: &str, max_length: usize) -> UrlShortenerResult<Url> {
+    // Overly long input is refused before any work is spent on it
+    if original_url.len() > max_length {
+        warn!(length = original_url.len(), max_length, "URL exceeds maximum length");
+        return Err(url_too_long(max_length));
+    }
+
+    // Script and inline-content schemes are refused before parsing, so disguised ones are caught too
+    if let Some(scheme) = blocked_scheme(original_url) {
+        warn!(scheme = %scheme, "Blocked URL scheme");
+        return Err(UrlShortenerErrorType::BlockedScheme(format!("'{}:' URLs cannot be shortened", scheme)).into());
+    }
+
+    // Validate URL
+    let url = match Url::parse(original_url) {
+        Ok(url) => {
+            debug!(scheme = %url.scheme(), host = %url.host_str().unwrap_or("unknown"), "URL parsed successfully");
+            url
+        },
+        Err(e) => {
+            warn!(error = %e, "Invalid URL format");
+            return Err(UrlShortenerErrorType::InvalidUrl(e.to_string()).into());
+        }
+    };
+
+    // Check URL length; normalization percent-encodes, so the stored form can be longer than the input
+    let length = url.as_str().len();
+    if length > max_length {
+        warn!(length, max_length, "Normalized URL exceeds maximum length");
+        return Err(url_too_long(max_length));
+    }
+
+    Ok(url)
+}
+
+fn url_too_long(max_length: usize) -> UrlShortenerError {
+    UrlShortenerErrorType::UrlTooLong(format!("URL exceeds {} characters", max_length)).into()
+}
+
+/// Stored form of a code created in `namespace`
+fn qualify(namespace: Option<&Namespace>, short_code: String) -> String {
+    match namespace {
+        Some(namespace) => namespace.qualify(&short_code),
+        None => short_code,
+    }
+}
+
+/// Kind of short code to generate when no custom alias is given
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeStyle {
+    /// Compact random characters, e.g. `V1StGXR8_Z`
+    #[default]
+    Random,
+    /// Memorable words, e.g. `blue-tiger-42`
+    Words,
+}
+
+/// Per-request options for creating a short URL
+#[derive(Debug, Clone, Default)]
+pub struct CreateOptions {
+    /// Owner of the API key that created the URL
+    pub owner: Option<String>,
+    /// Client-chosen short code to use instead of a generated one
+    pub custom_alias: Option<String>,
+    /// Kind of code to generate when there is no custom alias
+    pub style: CodeStyle,
+    /// Human-readable name of the link
+    pub title: Option<String>,
+    /// Free-form description of the link
+    pub notes: Option<String>,
+    /// Person or team responsible for the link
+    pub created_by: Option<String>,
+    /// Collection of the owner to put the link in
+    pub collection_id: Option<i64>,
+    /// Append paths and queries after the code to the destination
+    pub pass_through: bool,
+    /// Only redirect requests carrying a valid signature
+    pub signed_only: bool,
+    /// Owner's analytics snippet to fire before forwarding visitors
+    pub tracking_snippet: Option<String>,
+    /// Extra response headers to send with redirects, by lowercase name
+    pub redirect_headers: Option<RedirectHeaders>,
+    /// Keep destinations from seeing where the link was clicked
+    pub hide_referrer: bool,
+    /// Namespace prefix whose code space the link is created in; the root without one
+    pub namespace: Option<String>,
+}
+
 pub struct UrlService {
     storage: StorageRef,
+    code_generator: Arc<dyn CodeGenerator>,
+    word_generator: Arc<dyn CodeGenerator>,
+    code_lookup: CodeLookup,
+    alias_policy: AliasPolicy,
+    homograph_policy: HomographPolicy,
+    profanity_filter: ProfanityFilter,
+    namespaces: Namespaces,
+    content_check: Option<ContentCheck>,
+    link_quota: LinkQuota,
+    url_length_limits: UrlLengthLimits,
 }
 
 impl UrlService {
     pub fn new(storage: StorageRef) -> Self {
+        Self::with_code_generator(storage, Arc::new(RandomCodeGenerator::default()))
+    }
+
+    pub fn with_code_generator(storage: StorageRef, code_generator: Arc<dyn CodeGenerator>) -> Self {
         debug!("Creating new UrlService instance");
-        Self { storage }
+        Self {
+            storage,
+            code_generator,
+            word_generator: Arc::new(WordCodeGenerator::default()),
+            code_lookup: CodeLookup::default(),
+            alias_policy: AliasPolicy::default(),
+            homograph_policy: HomographPolicy::default(),
+            profanity_filter: ProfanityFilter::default(),
+            namespaces: Namespaces::default(),
+            content_check: None,
+            link_quota: LinkQuota::default(),
+            url_length_limits: UrlLengthLimits::default(),
+        }
+    }
+
+    /// Sets the generator used for requests asking for [`CodeStyle::Words`]
+    pub fn with_word_generator(mut self, word_generator: Arc<dyn CodeGenerator>) -> Self {
+        self.word_generator = word_generator;
+        self
+    }
+
+    /// Sets how short codes are normalized when created and looked up
+    pub fn with_code_lookup(mut self, code_lookup: CodeLookup) -> Self {
+        self.code_lookup = code_lookup;
+        self
+    }
+
+    /// Sets the rules custom aliases must follow
+    pub fn with_alias_policy(mut self, alias_policy: AliasPolicy) -> Self {
+        self.alias_policy = alias_policy;
+        self
+    }
+
+    /// Sets how lookalike hostnames in original URLs are handled
+    pub fn with_homograph_policy(mut self, homograph_policy: HomographPolicy) -> Self {
+        self.homograph_policy = homograph_policy;
+        self
+    }
+
+    /// Sets the blocked words generated codes and custom aliases are checked against
+    pub fn with_profanity_filter(mut self, profanity_filter: ProfanityFilter) -> Self {
+        self.profanity_filter = profanity_filter;
+        self
+    }
+
+    /// Sets the namespaces links may be created in, and the codes reserved in each of them
+    pub fn with_namespaces(mut self, namespaces: Namespaces) -> Self {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Checks what destinations serve before they are shortened, refusing denied file types
+    pub fn with_content_check(mut self, content_check: ContentCheck) -> Self {
+        self.content_check = Some(content_check);
+        self
+    }
+
+    /// Caps how many links are stored, and what happens to creations over the cap
+    pub fn with_link_quota(mut self, link_quota: LinkQuota) -> Self {
+        self.link_quota = link_quota;
+        self
+    }
+
+    /// Sets the longest original URLs each tier of caller may shorten
+    pub fn with_url_length_limits(mut self, url_length_limits: UrlLengthLimits) -> Self {
+        self.url_length_limits = url_length_limits;
+        self
+    }
+
+    pub fn url_length_limits(&self) -> &UrlLengthLimits {
+        &self.url_length_limits
+    }
+
+    /// Prepared statement reuse reported by the storage backend, if it prepares statements
+    pub fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        self.storage.statement_cache_stats()
+    }
+
+    /// Storage calls over the slow query threshold, if calls are timed
+    pub fn slow_query_stats(&self) -> Option<SlowQueryStats> {
+        self.storage.slow_query_stats()
+    }
+
+    /// Name of the storage backend links are kept in
+    pub fn storage_backend(&self) -> String {
+        self.storage.backend()
     }
 
-    #[instrument(skip(self), fields(url_length = original_url.len()))]
     pub async fn create_short_url(&self, original_url: String) -> UrlShortenerResult<ShortenedUrl> {
+        self.create_owned_short_url(original_url, None).await
+    }
+
+    /// Creates a short URL attributed to the owner of the calling API key
+    pub async fn create_owned_short_url(
+        &self,
+        original_url: String,
+        owner: Option<String>,
+    ) -> UrlShortenerResult<ShortenedUrl> {
+        self.create_short_url_with(original_url, CreateOptions { owner, ..CreateOptions::default() }).await
+    }
+
+    #[instrument(skip(self), fields(url_length = original_url.len()))]
+    pub async fn create_short_url_with(
+        &self,
+        original_url: String,
+        options: CreateOptions,
+    ) -> UrlShortenerResult<ShortenedUrl> {
         debug!("Attempting to create short URL");
 
-        // Validate URL
-        let url = match Url::parse(&original_url) {
-            Ok(url) => {
-                debug!(scheme = %url.scheme(), host = %url.host_str().unwrap_or("unknown"), "URL parsed successfully");
-                url
-            },
-            Err(e) => {
-                warn!(error = %e, "Invalid URL format");
-                return Err(UrlShortenerErrorType::InvalidUrl(e.to_string()).into());
+        let url = normalize_url_within(&original_url, self.url_length_limits.for_owner(options.owner.as_deref()))?;
+        let warnings = self.check_homographs(&url)?;
+        self.check_blocked_domain(&url).await?;
+        let namespace = match options.namespace.as_deref() {
+            Some(prefix) => Some(self.namespaces.get(prefix).ok_or_else(|| {
+                UrlShortenerErrorType::ValidationFailed(vec![FieldError {
+                    field: "namespace".to_string(),
+                    code: "unknown_namespace".to_string(),
+                    message: format!("Namespace '{}' does not exist", prefix),
+                }])
+            })?),
+            None => None,
+        };
+        if let Some(collection_id) = options.collection_id {
+            self.owned_collection(collection_id, options.owner.as_deref()).await?;
+        }
+        let content_type = match &self.content_check {
+            Some(content_check) => content_check.inspect(&url).await?,
+            None => None,
+        };
+        self.make_room(options.owner.as_deref(), 1).await?;
+
+        if let Some(alias) = &options.custom_alias {
+            let short_code = self.alias_code(alias, namespace)?;
+
+            return match self.save(&url, short_code.clone(), &options, &content_type).await {
+                Ok(saved) => Ok(ShortenedUrl { warnings, ..saved }),
+                Err(e) if matches!(e.error_type, UrlShortenerErrorType::Conflict(_)) => {
+                    Err(self.alias_taken(&short_code, namespace).await)
+                },
+                Err(e) => Err(e),
+            };
+        }
+
+        // Generate short codes until one is free; collisions are rare but possible
+        let generator = match options.style {
+            CodeStyle::Random => &self.code_generator,
+            CodeStyle::Words => &self.word_generator,
+        };
+        for attempt in 1..=MAX_CODE_GENERATION_ATTEMPTS {
+            let short_code = qualify(namespace, self.clean_code(generator.as_ref(), namespace)?);
+            debug!(short_code = %short_code, attempt, "Generated short code");
+
+            match self.save(&url, short_code.clone(), &options, &content_type).await {
+                Ok(saved) => return Ok(ShortenedUrl { warnings, ..saved }),
+                Err(e) if matches!(e.error_type, UrlShortenerErrorType::Conflict(_)) => {
+                    warn!(short_code = %short_code, attempt, "Short code collision, regenerating");
+                },
+                Err(e) => return Err(e),
             }
+        }
+
+        error!(original_url = %url, "Exhausted short code generation attempts");
+        Err(UrlShortenerErrorType::InternalError(
+            "Could not generate a unique short code".to_string(),
+        ).into())
+    }
+
+    /// Stored code for a custom alias in `namespace`, after checking it against the alias
+    /// policy, the profanity filter and the namespace's reserved codes
+    fn alias_code(&self, alias: &str, namespace: Option<&Namespace>) -> UrlShortenerResult<String> {
+        if let Some(namespace) = namespace.filter(|namespace| namespace.keywords()) {
+            return self.keyword_code(alias, namespace);
+        }
+        let alias = self
+            .alias_policy
+            .normalize(alias)
+            .map_err(|e| UrlShortenerErrorType::ValidationFailed(vec![e]))?;
+        if self.profanity_filter.is_offensive(&alias) {
+            warn!("Rejected offensive custom alias");
+            return Err(UrlShortenerErrorType::ValidationFailed(vec![FieldError {
+                field: "custom_alias".to_string(),
+                code: "offensive".to_string(),
+                message: "Alias contains a blocked word".to_string(),
+            }]).into());
+        }
+        let short_code = self.code_lookup.normalize(&alias).into_owned();
+        if self.namespaces.is_reserved(namespace, &short_code) {
+            warn!(short_code = %short_code, "Rejected reserved custom alias");
+            return Err(UrlShortenerErrorType::ValidationFailed(vec![FieldError {
+                field: "custom_alias".to_string(),
+                code: "reserved".to_string(),
+                message: format!("Alias '{}' is reserved", short_code),
+            }]).into());
+        }
+        Ok(qualify(namespace, short_code))
+    }
+
+    /// The error for a custom alias whose stored code `short_code` is in use, with numbered
+    /// variants of it that are still free
+    async fn alias_taken(&self, short_code: &str, namespace: Option<&Namespace>) -> UrlShortenerError {
+        let alias = namespace
+            .and_then(|namespace| short_code.strip_prefix(namespace.prefix()))
+            .and_then(|code| code.strip_prefix('/'))
+            .unwrap_or(short_code);
+
+        let mut suggestions = Vec::with_capacity(MAX_ALIAS_SUGGESTIONS);
+        for candidate in (2..).take(NUMBERED_ALIAS_SUGGESTIONS).map(|n| format!("{}-{}", alias, n)) {
+            if suggestions.len() == MAX_ALIAS_SUGGESTIONS {
+                break;
+            }
+            // Variants the alias policy refuses, e.g. for aliases at the length limit, are skipped
+            let Ok(candidate_code) = self.alias_code(&candidate, namespace) else { continue };
+            match self.storage.get_stats(&candidate_code).await {
+                Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => suggestions.push(candidate),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(error = %e, "Failed to check a suggested alias");
+                    break;
+                }
+            }
+        }
+
+        warn!(short_code = %short_code, "Custom alias already taken");
+        UrlShortenerErrorType::AliasTaken(Box::new(TakenAlias { alias: alias.to_string(), suggestions })).into()
+    }
+
+    /// Stored code for a keyword in the keyword namespace. Each level is checked against the
+    /// alias policy, and the keyword and its top level against the reserved codes. Keywords
+    /// are chosen by colleagues, so they skip the profanity filter, whose substring matches
+    /// trip over ordinary words like `assessment`.
+    fn keyword_code(&self, keyword: &str, namespace: &Namespace) -> UrlShortenerResult<String> {
+        let invalid = |code: &str, message: String| -> UrlShortenerError {
+            UrlShortenerErrorType::ValidationFailed(vec![FieldError {
+                field: "keyword".to_string(),
+                code: code.to_string(),
+                message,
+            }]).into()
         };
+        let keyword = namespace.keyword(keyword);
+        if keyword.split('/').count() > MAX_KEYWORD_DEPTH {
+            return Err(invalid("too_deep", format!("keyword must have at most {} levels", MAX_KEYWORD_DEPTH)));
+        }
 
-        // Check URL length
-        if original_url.len() > 2048 {
-            warn!(length = original_url.len(), "URL exceeds maximum length");
-            return Err(UrlShortenerErrorType::UrlTooLong("URL exceeds 2048 characters".to_string()).into());
+        let mut levels = Vec::with_capacity(MAX_KEYWORD_DEPTH);
+        for (depth, level) in keyword.split('/').enumerate() {
+            let level = self
+                .alias_policy
+                .normalize(level)
+                .map_err(|e| UrlShortenerErrorType::ValidationFailed(vec![FieldError { field: "keyword".to_string(), ..e }]))?;
+            let level = self.code_lookup.normalize(&level).into_owned();
+            if depth > 0 && !Namespace::allows_nested(&level) {
+                return Err(invalid("reserved", format!("'{}' is reserved below the top level", level)));
+            }
+            levels.push(level);
+        }
+        let short_code = levels.join("/");
+        if self.namespaces.is_reserved(Some(namespace), &short_code) || self.namespaces.is_reserved(Some(namespace), &levels[0]) {
+            warn!(short_code = %short_code, "Rejected reserved keyword");
+            return Err(invalid("reserved", format!("Keyword '{}' is reserved", short_code)));
+        }
+        Ok(namespace.qualify(&short_code))
+    }
+
+    /// Draws codes from `generator` until one passes the profanity filter and is not reserved
+    /// in `namespace`
+    fn clean_code(&self, generator: &dyn CodeGenerator, namespace: Option<&Namespace>) -> UrlShortenerResult<String> {
+        for _ in 0..MAX_CLEAN_CODE_DRAWS {
+            let short_code = self.code_lookup.normalize(&generator.generate()).into_owned();
+            if self.namespaces.is_reserved(namespace, &short_code) {
+                debug!("Generated short code is reserved, regenerating");
+                continue;
+            }
+            if !self.profanity_filter.is_offensive(&short_code) {
+                return Ok(short_code);
+            }
+            debug!("Generated short code contains a blocked word, regenerating");
         }
 
-        // Generate short code
-        let short_code = nanoid!(10);
-        debug!(short_code = %short_code, "Generated short code");
+        error!("Generator kept producing blocked words");
+        Err(UrlShortenerErrorType::InternalError(
+            "Could not generate an acceptable short code".to_string(),
+        ).into())
+    }
 
-        // Create shortened URL
+    /// Applies the homograph policy to `url`, returning the warnings to report to the client
+    fn check_homographs(&self, url: &Url) -> UrlShortenerResult<Vec<UrlWarning>> {
+        if self.homograph_policy == HomographPolicy::Off {
+            return Ok(Vec::new());
+        }
+
+        let warnings = inspect_host(url);
+        if warnings.is_empty() {
+            return Ok(warnings);
+        }
+
+        let host = url.host_str().unwrap_or_default();
+        warn!(host = %host, findings = warnings.len(), policy = ?self.homograph_policy, "Lookalike hostname in original URL");
+        match self.homograph_policy {
+            HomographPolicy::Block => Err(UrlShortenerErrorType::BlockedUrl(format!(
+                "Host '{}' resembles another domain: {}",
+                host,
+                warnings[0].message,
+            )).into()),
+            HomographPolicy::Warn => Ok(warnings),
+            HomographPolicy::Flag | HomographPolicy::Off => Ok(Vec::new()),
+        }
+    }
+
+    /// Refuses destinations on a domain an admin banned
+    async fn check_blocked_domain(&self, url: &Url) -> UrlShortenerResult<()> {
+        let Some(host) = url.host_str() else {
+            return Ok(());
+        };
+        if self.storage.is_domain_blocked(host).await? {
+            warn!(host = %host, "Rejected link to a banned domain");
+            return Err(UrlShortenerErrorType::BlockedUrl(format!("Links to '{}' have been banned", host)).into());
+        }
+        Ok(())
+    }
+
+    /// Stores a new short URL under `short_code`
+    async fn save(
+        &self,
+        url: &Url,
+        short_code: String,
+        options: &CreateOptions,
+        content_type: &Option<String>,
+    ) -> UrlShortenerResult<ShortenedUrl> {
         let shortened_url = ShortenedUrl {
             short_code: short_code.clone(),
             original_url: url.to_string(),
             created_at: Utc::now(),
             visits: 0,
+            last_visited_at: None,
+            owner: options.owner.clone(),
+            title: options.title.clone(),
+            notes: options.notes.clone(),
+            created_by: options.created_by.clone(),
+            collection_id: options.collection_id,
+            click_goal: None,
+            goal_reached_at: None,
+            pass_through: options.pass_through,
+            content_type: content_type.clone(),
+            disabled_at: None,
+            signed_only: options.signed_only,
+            tracking_snippet: options.tracking_snippet.clone(),
+            redirect_headers: options.redirect_headers.clone(),
+            hide_referrer: options.hide_referrer,
+            version: first_version(),
+            warnings: Vec::new(),
         };
 
         // Store the URL using the storage layer
@@ -92,6 +631,7 @@ impl UrlService {
                 );
                 Ok(saved_url.into())
             },
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::Conflict(_)) => Err(e),
             Err(e) => {
                 error!(
                     error = %e,
@@ -107,8 +647,13 @@ impl UrlService {
     #[instrument(skip(self))]
     pub async fn get_original_url(&self, short_code: &str) -> UrlShortenerResult<String> {
         debug!(short_code = %short_code, "Looking up original URL");
+        let short_code = &*self.code_lookup.normalize(short_code);
         
         match self.storage.get_url(short_code).await {
+            Ok(url) if url.disabled_at.is_some() => Err(UrlShortenerErrorType::LinkDisabled.into()),
+            Ok(url) if url.signed_only => Err(UrlShortenerErrorType::InvalidSignature(
+                "the link must be opened through a signed URL".to_string(),
+            ).into()),
             Ok(url) => {
                 info!(
                     short_code = %short_code,
@@ -131,6 +676,7 @@ impl UrlService {
     #[instrument(skip(self))]
     pub async fn get_url_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
         debug!(short_code = %short_code, "Retrieving URL statistics");
+        let short_code = &*self.code_lookup.normalize(short_code);
         
         match self.storage.get_stats(short_code).await {
             Ok(url) => {
@@ -152,7 +698,193 @@ impl UrlService {
             }
         }
     }
+
+    /// Changes the title, notes, creator, collection or click goal of a link owned by `owner`.
+    /// Links of other owners are reported as not found so their existence is not revealed.
+    #[instrument(skip(self, update))]
+    pub async fn update_metadata(
+        &self,
+        short_code: &str,
+        owner: &str,
+        update: MetadataUpdate,
+    ) -> UrlShortenerResult<ShortenedUrl> {
+        let link = self.get_owned_link(short_code, owner).await?;
+        let short_code = &*link.short_code;
+        if let Some(Some(collection_id)) = update.collection_id {
+            self.owned_collection(collection_id, Some(owner)).await?;
+        }
+
+        let updated = self.storage.update_metadata(short_code, update).await?;
+        info!(short_code = %short_code, "Updated link metadata");
+        Ok(updated.into())
+    }
+
+    /// Reads a link owned by `owner`; links of other owners are reported as not found
+    pub async fn get_owned_link(&self, short_code: &str, owner: &str) -> UrlShortenerResult<ShortenedUrl> {
+        let short_code = &*self.code_lookup.normalize(short_code);
+
+        let url = self.storage.get_stats(short_code).await?;
+        if url.owner.as_deref() != Some(owner) {
+            warn!(short_code = %short_code, owner = %owner, "Refused access to another owner's link");
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(url.into())
+    }
+
+    /// Moves a link owned by `owner` to a newly generated code in the same namespace. The old
+    /// code keeps redirecting for `grace_period`, and keeps the visits so far if `stats` forks them.
+    #[instrument(skip(self))]
+    pub async fn regenerate_code(
+        &self,
+        short_code: &str,
+        owner: &str,
+        style: CodeStyle,
+        stats: StatsOnRegenerate,
+        grace_period: chrono::Duration,
+    ) -> UrlShortenerResult<(ShortenedUrl, RetiredCode)> {
+        let link = self.get_owned_link(short_code, owner).await?;
+        let namespace = self.namespace_of(&link.short_code);
+        let generator = match style {
+            CodeStyle::Random => &self.code_generator,
+            CodeStyle::Words => &self.word_generator,
+        };
+        let redirects_until = Utc::now() + grace_period;
+
+        for attempt in 1..=MAX_CODE_GENERATION_ATTEMPTS {
+            let new_code = qualify(namespace, self.clean_code(generator.as_ref(), namespace)?);
+            match self.storage.regenerate_code(&link.short_code, &new_code, stats, redirects_until).await {
+                Ok(regenerated) => {
+                    info!(
+                        short_code = %link.short_code,
+                        new_code = %new_code,
+                        stats = ?stats,
+                        redirects_until = %redirects_until,
+                        "Regenerated short code"
+                    );
+                    return Ok((regenerated.link.into(), regenerated.retired));
+                }
+                Err(e) if matches!(e.error_type, UrlShortenerErrorType::Conflict(_)) => {
+                    warn!(short_code = %new_code, attempt, "Short code collision, regenerating");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        error!(short_code = %link.short_code, "Exhausted short code generation attempts");
+        Err(UrlShortenerErrorType::InternalError(
+            "Could not generate a unique short code".to_string(),
+        ).into())
+    }
+
+    /// Namespace a stored code belongs to; `None` for root codes
+    fn namespace_of(&self, short_code: &str) -> Option<&Namespace> {
+        short_code.split_once('/').and_then(|(prefix, _)| self.namespaces.get(prefix))
+    }
+
+    /// Gives a link owned by `owner` an extra code in its namespace. The alias is validated
+    /// like a custom alias, and its visits count towards the link.
+    #[instrument(skip(self))]
+    pub async fn add_alias(&self, short_code: &str, owner: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        let link = self.get_owned_link(short_code, owner).await?;
+        let alias = self.alias_code(alias, self.namespace_of(&link.short_code))?;
+
+        match self.storage.add_alias(&link.short_code, &alias).await {
+            Ok(added) => {
+                info!(short_code = %link.short_code, alias = %alias, "Added link alias");
+                Ok(added)
+            }
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::Conflict(_)) => {
+                Err(self.alias_taken(&alias, self.namespace_of(&link.short_code)).await)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes an alias from a link owned by `owner`
+    #[instrument(skip(self))]
+    pub async fn remove_alias(&self, short_code: &str, owner: &str, alias: &str) -> UrlShortenerResult<()> {
+        let link = self.get_owned_link(short_code, owner).await?;
+        self.storage.remove_alias(&link.short_code, &self.code_lookup.normalize(alias)).await?;
+        info!(short_code = %link.short_code, alias = %alias, "Removed link alias");
+        Ok(())
+    }
+
+    /// Extra codes of a link, oldest first
+    pub async fn aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.storage.list_aliases(&self.code_lookup.normalize(short_code)).await
+    }
+
+    /// Codes a link was moved away from, most recently retired first
+    pub async fn retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.storage.list_retired_codes(&self.code_lookup.normalize(short_code)).await
+    }
+
+    /// When a link was first and last visited, and how often lately, from its visit events
+    pub async fn visit_timeline(&self, short_code: &str) -> UrlShortenerResult<VisitTimeline> {
+        self.storage.visit_timeline(&self.code_lookup.normalize(short_code), Utc::now()).await
+    }
+
+    /// A link's visits in the last `days` days, by weekday and hour
+    pub async fn visit_heatmap(&self, short_code: &str, days: u32) -> UrlShortenerResult<VisitHeatmap> {
+        let since = Utc::now() - chrono::Duration::days(i64::from(days.max(1)));
+        self.storage.visit_heatmap(&self.code_lookup.normalize(short_code), since).await
+    }
+
+    /// A link's visits in the last `days` days by their `src` tag, most visited first
+    pub async fn visit_sources(&self, short_code: &str, days: u32) -> UrlShortenerResult<Vec<SourceVisits>> {
+        let since = Utc::now() - chrono::Duration::days(i64::from(days.max(1)));
+        self.storage.visit_sources(&self.code_lookup.normalize(short_code), since).await
+    }
+
+    pub async fn list_recent_urls(&self, owner: &str, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        Ok(self.list_recent_urls_in(owner, None, None, limit).await?.items)
+    }
+
+    /// Lists a page of an owner's most recent links, optionally only those in one of their
+    /// collections, continuing after `after` when given
+    #[instrument(skip(self))]
+    pub async fn list_recent_urls_in(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Page<ShortenedUrl>> {
+        debug!(owner = %owner, limit = limit, "Listing recent URLs");
+        if let Some(collection_id) = collection_id {
+            self.owned_collection(collection_id, Some(owner)).await?;
+        }
+
+        // One more than asked for tells whether there is a next page
+        match self.storage.list_urls_by_owner(owner, collection_id, after, limit + 1).await {
+            Ok(urls) => {
+                let page = Page::from_fetched(urls, limit, |url| PageCursor::after(url.created_at, url.id));
+                info!(owner = %owner, count = page.items.len(), "Successfully listed recent URLs");
+                Ok(page.map(Into::into))
+            },
+            Err(e) => {
+                warn!(error = %e, owner = %owner, "Failed to list recent URLs");
+                Err(e)
+            }
+        }
+    }
+
+    /// Lists a page of an owner's links matching `filter` in `sort` order, continuing after
+    /// `after` when given
+    #[instrument(skip(self))]
+    pub async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Page<ShortenedUrl, UrlCursor>> {
+        // One more than asked for tells whether there is a next page
+        let urls = self.storage.list_urls(owner, sort, filter, after, limit + 1).await?;
+        Ok(Page::from_fetched(urls, limit, |url| UrlCursor::after(sort, url)).map(Into::into))
+    }
 }
 
 #[cfg(test)]
-mod tests; 
\ No newline at end of file
+mod tests;
@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use tracing::warn;
+
+/// Maximum length of a namespace prefix, in characters
+pub const MAX_NAMESPACE_LENGTH: usize = 32;
+
+/// Levels a keyword may have below its namespace, e.g. `team/runbook` in `go/team/runbook`
+pub const MAX_KEYWORD_DEPTH: usize = 2;
+
+/// Trailing segments of the API's per-link endpoints, like `/api/stats/{code}/heatmap`; below
+/// the top level of a keyword they would make those paths ambiguous
+const API_SEGMENTS: &[&str] = &["aliases", "heatmap", "preview.png", "regenerate", "sign", "sources", "transfer"];
+
+/// Top-level paths the app routes itself, which a namespace would shadow
+const ROUTED_PATHS: &[&str] = &["api", "dashboard", "integrations", "internal", "health", "robots.txt"];
+
+/// Root codes kept free for paths deployments commonly mount in front of the app, like a
+/// metrics exporter
+const RESERVED_PATHS: &[&str] = &["metrics"];
+
+/// A path prefix with its own code space: `abc` in namespace `t` is served at `/t/abc` and
+/// stored as `t/abc`, independent of a root code `abc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    prefix: String,
+    /// Codes are human keywords, as in go links, and written loosely
+    keywords: bool,
+}
+
+impl Namespace {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Stored form of a code in this namespace
+    pub fn qualify(&self, short_code: &str) -> String {
+        format!("{}/{}", self.prefix, short_code)
+    }
+
+    pub fn keywords(&self) -> bool {
+        self.keywords
+    }
+
+    /// The code a keyword is stored under: in a keyword namespace `Payroll Team` and
+    /// `payroll_team` are both `payroll-team`, and `Team / Runbook` is `team/runbook`; codes
+    /// in other namespaces are left alone
+    pub fn keyword<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        if !self.keywords {
+            return Cow::Borrowed(input);
+        }
+        let levels = input.split('/').map(|level| {
+            let words = level.split(|c: char| c.is_whitespace() || matches!(c, '_' | '-'));
+            words.filter(|word| !word.is_empty()).collect::<Vec<_>>().join("-").to_lowercase()
+        });
+        Cow::Owned(levels.filter(|level| !level.is_empty()).collect::<Vec<_>>().join("/"))
+    }
+
+    /// Whether `level` may be used below the top level of a keyword
+    pub fn allows_nested(level: &str) -> bool {
+        !API_SEGMENTS.contains(&level)
+    }
+}
+
+/// The configured namespaces, with the codes reserved in each of them and at the root
+#[derive(Debug, Clone, Default)]
+pub struct Namespaces {
+    namespaces: Vec<Namespace>,
+    /// Lowercased reserved codes per namespace prefix; `""` is the root
+    reserved: HashMap<String, HashSet<String>>,
+}
+
+impl Namespaces {
+    /// Sets up namespaces for the given prefixes, which are also reserved at the root.
+    /// Prefixes that are not a plain path segment or shadow the app's own routes are logged
+    /// and ignored.
+    pub fn new<S: AsRef<str>>(prefixes: &[S]) -> Self {
+        let mut namespaces = Self::default();
+        for prefix in prefixes {
+            namespaces.add(prefix.as_ref());
+        }
+        namespaces
+    }
+
+    fn add(&mut self, prefix: &str) {
+        let valid = !prefix.is_empty()
+            && prefix.chars().count() <= MAX_NAMESPACE_LENGTH
+            && prefix.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'))
+            && !ROUTED_PATHS.contains(&prefix);
+        if !valid {
+            warn!(namespace = %prefix, "Ignoring invalid code namespace");
+            return;
+        }
+        if self.get(prefix).is_none() {
+            self.namespaces.push(Namespace { prefix: prefix.to_string(), keywords: false });
+            self.reserve(None, prefix);
+        }
+    }
+
+    /// Makes `prefix` the namespace of keyword codes, adding it when it is not configured yet
+    pub fn with_keywords(mut self, prefix: &str) -> Self {
+        self.add(prefix);
+        for namespace in &mut self.namespaces {
+            namespace.keywords = namespace.prefix == prefix;
+        }
+        self
+    }
+
+    /// Reserves codes in a namespace, or at the root for `None`; matched regardless of case
+    pub fn with_reserved<S: AsRef<str>>(mut self, namespace: Option<&str>, codes: &[S]) -> Self {
+        for code in codes {
+            self.reserve(namespace, code.as_ref());
+        }
+        self
+    }
+
+    fn reserve(&mut self, namespace: Option<&str>, code: &str) {
+        self.reserved
+            .entry(namespace.unwrap_or_default().to_string())
+            .or_default()
+            .insert(code.to_lowercase());
+    }
+
+    pub fn get(&self, prefix: &str) -> Option<&Namespace> {
+        self.namespaces.iter().find(|namespace| namespace.prefix == prefix)
+    }
+
+    /// The namespace of keyword codes, if there is one
+    pub fn keywords(&self) -> Option<&Namespace> {
+        self.namespaces.iter().find(|namespace| namespace.keywords)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Namespace> {
+        self.namespaces.iter()
+    }
+
+    /// Whether `short_code` is reserved in `namespace`, or at the root for `None`. The app's
+    /// own top-level paths are always reserved at the root, where a link would shadow them.
+    pub fn is_reserved(&self, namespace: Option<&Namespace>, short_code: &str) -> bool {
+        let short_code = short_code.to_lowercase();
+        if namespace.is_none() && ROUTED_PATHS.iter().chain(RESERVED_PATHS).any(|path| *path == short_code) {
+            return true;
+        }
+        let prefix = namespace.map_or("", Namespace::prefix);
+        self.reserved
+            .get(prefix)
+            .is_some_and(|codes| codes.contains(&short_code))
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+const BLOCKED_WORDS: &str = include_str!("blocked_words.txt");
+
+/// Rejects short codes that spell offensive words.
+///
+/// Codes are folded before matching: lowercased, separators (`-`, `_`, `.`, `~`) dropped and
+/// common digit substitutions undone, so `Sh1t`, `s-h-i-t` and `5hit` all match `shit`. Matching
+/// is by substring, which over-blocks some innocent words; for generated codes that only costs
+/// a regeneration.
+#[derive(Debug, Clone)]
+pub struct ProfanityFilter {
+    patterns: Arc<[String]>,
+}
+
+impl ProfanityFilter {
+    /// Builds a filter from the embedded word list plus `additions`
+    pub fn with_additions<I, S>(additions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = BLOCKED_WORDS
+            .lines()
+            .map(fold)
+            .chain(additions.into_iter().map(|word| fold(word.as_ref())))
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+        Self { patterns }
+    }
+
+    /// Whether `code` contains a blocked word
+    pub fn is_offensive(&self, code: &str) -> bool {
+        let folded = fold(code);
+        self.patterns.iter().any(|pattern| folded.contains(pattern.as_str()))
+    }
+}
+
+impl Default for ProfanityFilter {
+    fn default() -> Self {
+        Self::with_additions(std::iter::empty::<&str>())
+    }
+}
+
+/// Reduces text to the form blocked words are matched in
+fn fold(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | '.' | '~'))
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            c => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use tracing::{info, warn};
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use super::UrlService;
+
+/// What to do with a creation that would go over a link quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// Refuse the new links
+    #[default]
+    Reject,
+    /// Delete the oldest links that were never visited to make room
+    Evict,
+}
+
+impl FromStr for QuotaPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "evict" => Ok(Self::Evict),
+            other => Err(format!("unknown quota policy '{}'", other)),
+        }
+    }
+}
+
+/// Caps on stored links; `0` leaves a cap off.
+///
+/// Quotas are soft: concurrent creations are not serialized, so a busy deployment can go
+/// slightly over before the next creation notices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkQuota {
+    /// Links stored across the deployment
+    pub total: u64,
+    /// Links stored by each API key owner
+    pub per_owner: u64,
+    pub policy: QuotaPolicy,
+}
+
+impl UrlService {
+    /// Makes sure `count` more links fit under the quotas of the deployment and of `owner`,
+    /// evicting unvisited links when the policy allows
+    pub(super) async fn make_room(&self, owner: Option<&str>, count: u64) -> UrlShortenerResult<()> {
+        let quota = self.link_quota;
+        if quota.total > 0 {
+            self.make_room_in(None, quota.total, count).await?;
+        }
+        if let (Some(owner), true) = (owner, quota.per_owner > 0) {
+            self.make_room_in(Some(owner), quota.per_owner, count).await?;
+        }
+        Ok(())
+    }
+
+    /// Makes room for `count` links under the `cap` on the links of `owner`, or of everyone
+    async fn make_room_in(&self, owner: Option<&str>, cap: u64, count: u64) -> UrlShortenerResult<()> {
+        let scope = if owner.is_some() { "owner" } else { "deployment" };
+        let stored = self.storage.count_urls(owner).await?;
+        let over = (stored + count).saturating_sub(cap);
+        if over == 0 {
+            return Ok(());
+        }
+
+        if self.link_quota.policy == QuotaPolicy::Evict {
+            let oldest = self.storage.oldest_unvisited_urls(owner, over as i64).await?;
+            let evicted = self.storage.delete_unvisited_urls(&oldest).await?;
+            info!(scope, evicted = evicted.len(), "Evicted unvisited links to stay under the link quota");
+            if evicted.len() as u64 >= over {
+                return Ok(());
+            }
+        }
+
+        warn!(scope, stored, cap, "Link quota reached");
+        Err(UrlShortenerErrorType::QuotaExceeded(format!("the {} allows at most {} links", scope, cap)).into())
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use url::Url;
+
+use crate::errors::UrlShortenerResult;
+use crate::validation::{inspect_host, UrlWarning};
+use super::UrlService;
+
+/// How a destination looks to a client about to show or follow it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Safe,
+    /// The host resembles another domain
+    Suspicious,
+    /// The domain was banned after the link was created
+    Blocked,
+}
+
+/// Verdict on a destination, with the findings behind it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Safety {
+    pub verdict: Verdict,
+    pub warnings: Vec<UrlWarning>,
+}
+
+impl UrlService {
+    /// Checks a stored destination again, as bans and lookalike checks may postdate the link.
+    /// Lookalike hosts are reported whatever the homograph policy is.
+    pub async fn destination_safety(&self, destination: &str) -> UrlShortenerResult<Safety> {
+        let Ok(url) = Url::parse(destination) else {
+            return Ok(unreadable());
+        };
+        let banned = match url.host_str() {
+            Some(host) => self.storage.is_domain_blocked(host).await?,
+            None => false,
+        };
+        Ok(assess(&url, banned))
+    }
+
+    /// Checks many destinations like [`destination_safety`](Self::destination_safety), in the
+    /// order given, looking each host up once
+    pub async fn destinations_safety(&self, destinations: &[&str]) -> UrlShortenerResult<Vec<Safety>> {
+        let mut banned_hosts: HashMap<String, bool> = HashMap::new();
+        let mut checked = Vec::with_capacity(destinations.len());
+        for destination in destinations {
+            let Ok(url) = Url::parse(destination) else {
+                checked.push(unreadable());
+                continue;
+            };
+            let banned = match url.host_str() {
+                Some(host) => match banned_hosts.get(host) {
+                    Some(&banned) => banned,
+                    None => {
+                        let banned = self.storage.is_domain_blocked(host).await?;
+                        banned_hosts.insert(host.to_string(), banned);
+                        banned
+                    }
+                },
+                None => false,
+            };
+            checked.push(assess(&url, banned));
+        }
+        Ok(checked)
+    }
+}
+
+/// Verdict on a destination that no longer parses
+fn unreadable() -> Safety {
+    Safety { verdict: Verdict::Suspicious, warnings: Vec::new() }
+}
+
+fn assess(url: &Url, banned: bool) -> Safety {
+    if banned {
+        let host = url.host_str().unwrap_or_default();
+        debug!(host = %host, "Destination domain is banned");
+        let warning = UrlWarning {
+            code: "banned_domain".to_string(),
+            message: format!("Links to '{}' have been banned", host),
+        };
+        return Safety { verdict: Verdict::Blocked, warnings: vec![warning] };
+    }
+    let warnings = inspect_host(url);
+    let verdict = if warnings.is_empty() { Verdict::Safe } else { Verdict::Suspicious };
+    Safety { verdict, warnings }
+}
@@ -0,0 +1,133 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use super::CodeGenerator;
+
+/// Start of the generator's clock, 2026-01-01T00:00:00Z in Unix milliseconds
+const EPOCH_MILLIS: i64 = 1_767_225_600_000;
+
+const REGION_BITS: u32 = 5;
+const WORKER_BITS: u32 = 5;
+const SEQUENCE_BITS: u32 = 12;
+
+/// Regions and workers per region that can be told apart
+pub const MAX_REGIONS: u16 = 1 << REGION_BITS;
+pub const MAX_WORKERS: u16 = 1 << WORKER_BITS;
+
+const SEQUENCE_MASK: u64 = (1 << SEQUENCE_BITS) - 1;
+
+const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const LOWERCASE_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Mints codes from Snowflake-style IDs, so instances in different regions never mint the same
+/// code without having to coordinate.
+///
+/// An ID is the milliseconds since 2026 (41 bits), then the region (5 bits), the worker within
+/// the region (5 bits) and a per-millisecond sequence (12 bits). Each region thereby owns a
+/// block of every millisecond's IDs, and each worker a block of its region's. IDs are written
+/// in base62, or base36 when codes are lowercased, as 9 to 11 characters. They grow over time,
+/// so unlike random codes they reveal when a link was created and are easier to guess.
+#[derive(Debug)]
+pub struct SnowflakeCodeGenerator {
+    region: u16,
+    worker: u16,
+    lowercase: bool,
+    /// Millisecond and sequence of the last ID
+    last: Mutex<(u64, u64)>,
+}
+
+/// Parts of a minted ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnowflakeId {
+    pub created_at: DateTime<Utc>,
+    pub region: u16,
+    pub worker: u16,
+    pub sequence: u16,
+}
+
+impl SnowflakeCodeGenerator {
+    /// `lowercase` keeps codes distinct when they are matched regardless of case.
+    ///
+    /// # Panics
+    /// If `region` is not below [`MAX_REGIONS`] or `worker` not below [`MAX_WORKERS`].
+    pub fn new(region: u16, worker: u16, lowercase: bool) -> Self {
+        assert!(region < MAX_REGIONS, "region must be below {}", MAX_REGIONS);
+        assert!(worker < MAX_WORKERS, "worker must be below {}", MAX_WORKERS);
+        Self { region, worker, lowercase, last: Mutex::new((0, 0)) }
+    }
+
+    fn alphabet(&self) -> &'static [u8] {
+        if self.lowercase { LOWERCASE_ALPHABET } else { ALPHABET }
+    }
+
+    /// Next ID for `now`. The clock never runs backwards for IDs: after the system clock does,
+    /// or once a millisecond's sequence is used up, IDs are taken from the following millisecond.
+    fn next_id(&self, now: DateTime<Utc>) -> u64 {
+        let millis = (now.timestamp_millis() - EPOCH_MILLIS).max(0) as u64;
+        let mut last = self.last.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (last_millis, last_sequence) = *last;
+
+        let next = if millis > last_millis {
+            (millis, 0)
+        } else if last_sequence < SEQUENCE_MASK {
+            (last_millis, last_sequence + 1)
+        } else {
+            (last_millis + 1, 0)
+        };
+        *last = next;
+
+        let (millis, sequence) = next;
+        millis << (REGION_BITS + WORKER_BITS + SEQUENCE_BITS)
+            | u64::from(self.region) << (WORKER_BITS + SEQUENCE_BITS)
+            | u64::from(self.worker) << SEQUENCE_BITS
+            | sequence
+    }
+
+    fn encode(&self, mut id: u64) -> String {
+        let alphabet = self.alphabet();
+        let radix = alphabet.len() as u64;
+        let mut digits = Vec::new();
+        loop {
+            digits.push(alphabet[(id % radix) as usize]);
+            id /= radix;
+            if id == 0 {
+                break;
+            }
+        }
+        digits.reverse();
+        String::from_utf8(digits).expect("code alphabet is ASCII")
+    }
+
+    /// Takes apart a code this generator's settings produce; `None` for other codes
+    pub fn decode(&self, code: &str) -> Option<SnowflakeId> {
+        if code.is_empty() {
+            return None;
+        }
+        let alphabet = self.alphabet();
+        let radix = alphabet.len() as u64;
+        let id = code.bytes().try_fold(0u64, |id, byte| {
+            let digit = alphabet.iter().position(|&c| c == byte)? as u64;
+            id.checked_mul(radix)?.checked_add(digit)
+        })?;
+
+        let millis = id >> (REGION_BITS + WORKER_BITS + SEQUENCE_BITS);
+        Some(SnowflakeId {
+            created_at: DateTime::from_timestamp_millis(EPOCH_MILLIS + millis as i64)?,
+            region: (id >> (WORKER_BITS + SEQUENCE_BITS)) as u16 & (MAX_REGIONS - 1),
+            worker: (id >> SEQUENCE_BITS) as u16 & (MAX_WORKERS - 1),
+            sequence: (id & SEQUENCE_MASK) as u16,
+        })
+    }
+
+    /// Code for the next ID at `now`, for tests that need a fixed clock
+    pub fn generate_at(&self, now: DateTime<Utc>) -> String {
+        self.encode(self.next_id(now))
+    }
+}
+
+impl CodeGenerator for SnowflakeCodeGenerator {
+    fn generate(&self) -> String {
+        self.generate_at(Utc::now())
+    }
+}
@@ -1,7 +1,8 @@
 use super::*;
 use crate::errors::UrlShortenerErrorType;
-use crate::storage::{MemoryStorage, StorageConfig};
-use std::sync::Arc;
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 async fn create_test_service() -> UrlService {
     let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
@@ -45,6 +46,59 @@ async fn test_create_short_url_too_long() {
     }
 }
 
+#[tokio::test]
+async fn test_url_length_limits_follow_the_callers_tier() {
+    let limits = UrlLengthLimits {
+        default: 100,
+        tiers: HashMap::from([("premium".to_string(), 4096), (ANONYMOUS_TIER.to_string(), 50)]),
+        owner_tiers: HashMap::from([("alice".to_string(), "premium".to_string())]),
+    };
+    let service = create_test_service().await.with_url_length_limits(limits);
+    let url = |length: usize| format!("https://example.com/{}", "a".repeat(length - 20));
+    let create = |url: String, owner: &str| {
+        let owner = Some(owner.to_string()).filter(|owner| !owner.is_empty());
+        service.create_short_url_with(url, CreateOptions { owner, ..CreateOptions::default() })
+    };
+
+    assert!(create(url(50), "").await.is_ok());
+    assert!(create(url(100), "bob").await.is_ok());
+    assert!(create(url(4096), "alice").await.is_ok());
+    for (length, owner) in [(51, ""), (101, "bob"), (4097, "alice")] {
+        match create(url(length), owner).await.unwrap_err().error_type {
+            UrlShortenerErrorType::UrlTooLong(message) => assert!(message.contains(&(length - 1).to_string())),
+            error_type => panic!("Expected UrlTooLong error, got {:?}", error_type),
+        }
+    }
+
+    // No tier goes past the ceiling
+    let unbounded = UrlLengthLimits { default: usize::MAX, ..UrlLengthLimits::default() };
+    assert_eq!(unbounded.for_owner(None), MAX_URL_LENGTH_CEILING);
+}
+
+#[tokio::test]
+async fn test_create_short_url_too_long_after_encoding() {
+    let service = create_test_service().await;
+    // Under the limit as typed, but each space expands to %20 when normalized
+    let url = format!("https://example.com/?q={}", "a b".repeat(500));
+    let result = service.create_short_url(url).await;
+
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::UrlTooLong(_) => (),
+        error_type => panic!("Expected UrlTooLong error, got {:?}", error_type),
+    }
+}
+
+#[tokio::test]
+async fn test_create_short_url_blocked_scheme() {
+    let service = create_test_service().await;
+    for url in ["javascript:alert(1)", "\u{0}java\tscript:alert(1)", "data:text/html,<script>alert(1)</script>"] {
+        match service.create_short_url(url.to_string()).await.unwrap_err().error_type {
+            UrlShortenerErrorType::BlockedScheme(_) => (),
+            error_type => panic!("Expected BlockedScheme error, got {:?}", error_type),
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_get_original_url_success() {
     let service = create_test_service().await;
@@ -109,3 +163,687 @@ async fn test_get_url_stats_not_found() {
         error_type => panic!("Expected NotFound error, got {:?}", error_type),
     }
 }
+
+#[tokio::test]
+async fn test_list_recent_urls_filters_by_owner() {
+    let service = create_test_service().await;
+    let first = service.create_owned_short_url("https://example.com/1".to_string(), Some("alice".to_string())).await.unwrap();
+    let second = service.create_owned_short_url("https://example.com/2".to_string(), Some("alice".to_string())).await.unwrap();
+    service.create_owned_short_url("https://example.com/3".to_string(), Some("bob".to_string())).await.unwrap();
+
+    let recent = service.list_recent_urls("alice", 10).await.unwrap();
+    assert_eq!(recent.len(), 2);
+    assert!(recent.iter().any(|url| url.short_code == first.short_code));
+    assert!(recent.iter().any(|url| url.short_code == second.short_code));
+
+    let limited = service.list_recent_urls("alice", 1).await.unwrap();
+    assert_eq!(limited.len(), 1);
+}
+
+/// Hands out a fixed sequence of codes, repeating the last one
+struct SequenceCodeGenerator(Mutex<Vec<&'static str>>);
+
+impl CodeGenerator for SequenceCodeGenerator {
+    fn generate(&self) -> String {
+        let mut codes = self.0.lock().unwrap();
+        if codes.len() > 1 {
+            codes.remove(0).to_string()
+        } else {
+            codes[0].to_string()
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_create_short_url_retries_on_collision() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["taken", "taken", "free"])));
+    let service = UrlService::with_code_generator(storage, generator);
+
+    let first = service.create_short_url("https://example.com/1".to_string()).await.unwrap();
+    assert_eq!(first.short_code, "taken");
+
+    let second = service.create_short_url("https://example.com/2".to_string()).await.unwrap();
+    assert_eq!(second.short_code, "free");
+}
+
+#[tokio::test]
+async fn test_create_short_url_gives_up_after_repeated_collisions() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["taken"])));
+    let service = UrlService::with_code_generator(storage, generator);
+
+    service.create_short_url("https://example.com/1".to_string()).await.unwrap();
+    let result = service.create_short_url("https://example.com/2".to_string()).await;
+
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::InternalError(_) => (),
+        error_type => panic!("Expected InternalError, got {:?}", error_type),
+    }
+}
+
+#[test]
+fn test_code_lookup_normalize() {
+    let strict = CodeLookup::default();
+    assert_eq!(strict.normalize("AbC/ "), "AbC/ ");
+
+    let lenient = CodeLookup { case_insensitive: true, trim: true };
+    assert_eq!(lenient.normalize("AbC"), "abc");
+    assert_eq!(lenient.normalize(" abc//\t"), "abc");
+    assert!(matches!(lenient.normalize("abc"), std::borrow::Cow::Borrowed(_)));
+}
+
+#[tokio::test]
+async fn test_case_insensitive_codes_are_stored_lowercase() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["MiXeD"])));
+    let service = UrlService::with_code_generator(storage, generator)
+        .with_code_lookup(CodeLookup { case_insensitive: true, trim: false });
+
+    let created = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    assert_eq!(created.short_code, "mixed");
+    assert_eq!(service.get_original_url("MIXED").await.unwrap(), "https://example.com/");
+    assert_eq!(service.get_url_stats("Mixed").await.unwrap().short_code, "mixed");
+}
+
+#[tokio::test]
+async fn test_create_with_unicode_alias() {
+    let service = create_test_service().await;
+    let options = |alias: &str| CreateOptions { custom_alias: Some(alias.to_string()), ..CreateOptions::default() };
+
+    // Decomposed input is stored in NFC form and found from either form
+    let created = service
+        .create_short_url_with("https://example.com".to_string(), options("cafe\u{301}-🎉"))
+        .await
+        .unwrap();
+    assert_eq!(created.short_code, "caf\u{e9}-🎉");
+    assert_eq!(service.get_original_url("cafe\u{301}-🎉").await.unwrap(), "https://example.com/");
+
+    // Taken aliases are a conflict rather than being silently regenerated
+    let result = service
+        .create_short_url_with("https://example.org".to_string(), options("caf\u{e9}-🎉"))
+        .await;
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::AliasTaken(taken) => assert_eq!(taken.alias, "caf\u{e9}-🎉"),
+        error_type => panic!("Expected AliasTaken, got {:?}", error_type),
+    }
+}
+
+#[tokio::test]
+async fn test_taken_alias_suggests_free_variants() {
+    let service = create_test_service().await;
+    let options = |alias: &str| CreateOptions { custom_alias: Some(alias.to_string()), ..CreateOptions::default() };
+    for alias in ["launch", "launch-3"] {
+        service.create_short_url_with("https://example.com".to_string(), options(alias)).await.unwrap();
+    }
+
+    let result = service.create_short_url_with("https://example.org".to_string(), options("launch")).await;
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::AliasTaken(taken) => {
+            assert_eq!(taken.alias, "launch");
+            assert_eq!(taken.suggestions, ["launch-2", "launch-4", "launch-5"]);
+        }
+        error_type => panic!("Expected AliasTaken, got {:?}", error_type),
+    }
+
+    // The app's own top-level paths cannot be taken by a link
+    for alias in ["api", "Health", "metrics", "dashboard"] {
+        let result = service.create_short_url_with("https://example.com".to_string(), options(alias)).await;
+        match result.unwrap_err().error_type {
+            UrlShortenerErrorType::ValidationFailed(errors) => assert_eq!(errors[0].code, "reserved"),
+            error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+        }
+    }
+}
+
+#[test]
+fn test_alias_policy() {
+    let any = AliasPolicy::default();
+    assert_eq!(any.normalize("привет-👋🏽").unwrap(), "привет-👋🏽");
+    assert_eq!(any.normalize("a/b").unwrap_err().code, "invalid_characters");
+    assert_eq!(any.normalize("a b").unwrap_err().code, "invalid_characters");
+    assert_eq!(any.normalize("evil\u{202E}gpj").unwrap_err().code, "invalid_characters");
+    assert_eq!(any.normalize("").unwrap_err().code, "required");
+    assert_eq!(any.normalize(&"a".repeat(MAX_ALIAS_LENGTH + 1)).unwrap_err().code, "too_long");
+
+    // Restricting to Latin still allows digits, punctuation and emoji
+    let latin = AliasPolicy::with_allowed_scripts(&["Latin", "NotAScript"]);
+    assert!(latin.normalize("launch-2024-🚀").is_ok());
+    // "pаypal" with a Cyrillic "а"
+    assert_eq!(latin.normalize("p\u{430}ypal").unwrap_err().code, "script_not_allowed");
+
+    let greek = AliasPolicy::with_allowed_scripts(&["Grek"]);
+    assert!(greek.normalize("αβγ").is_ok());
+    assert!(greek.normalize("abc").is_err());
+}
+
+/// URL-like strings with arbitrary characters in every component
+fn url_like() -> impl proptest::strategy::Strategy<Value = String> {
+    use proptest::prelude::*;
+
+    (
+        "(https?|HTTPS|ftp|mailto|[a-z][a-z0-9+.-]{0,8})",
+        "(://|:|:/|:///)",
+        "[\\PC]{0,20}",
+        "(/[\\PC]{0,12}){0,4}",
+        prop_oneof![Just(String::new()), "\\?[\\PC]{0,30}"],
+        prop_oneof![Just(String::new()), "#[\\PC]{0,12}"],
+    )
+        .prop_map(|(scheme, sep, host, path, query, fragment)| {
+            format!("{}{}{}{}{}{}", scheme, sep, host, path, query, fragment)
+        })
+}
+
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn normalized_urls_renormalize_to_themselves(input in prop_oneof![url_like(), any::<String>()]) {
+            if let Ok(url) = normalize_url(&input) {
+                let renormalized = normalize_url(url.as_str()).expect("normalized URL must stay valid");
+                prop_assert_eq!(renormalized.as_str(), url.as_str());
+            }
+        }
+
+        #[test]
+        fn generated_codes_match_alphabet_and_length(length in 1usize..64) {
+            let code = RandomCodeGenerator::new(length).generate();
+            prop_assert_eq!(code.chars().count(), length);
+            prop_assert!(code.chars().all(|c| nanoid::alphabet::SAFE.contains(&c)), "unexpected character in {}", code);
+        }
+
+        #[test]
+        fn base62_decode_inverts_encode(value in any::<u64>()) {
+            let encoded = base62::encode(value);
+            prop_assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric()));
+            prop_assert_eq!(base62::decode(&encoded), Some(value));
+        }
+
+        #[test]
+        fn base62_encode_inverts_decode(encoded in "[0-9A-Za-z]{1,12}") {
+            // The alphabet is in ASCII order, so equal-length strings compare like their values
+            let max = base62::encode(u64::MAX);
+            let overflows = encoded.len() > max.len() || (encoded.len() == max.len() && encoded > max);
+            let leading_zero = encoded.len() > 1 && encoded.starts_with('0');
+
+            match base62::decode(&encoded) {
+                Some(value) => prop_assert_eq!(base62::encode(value), encoded),
+                None => prop_assert!(overflows || leading_zero, "rejected canonical {}", encoded),
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_homograph_policy() {
+    let lookalike = "https://pаypal.com/login".to_string();
+
+    let created = create_test_service().await.create_short_url(lookalike.clone()).await.unwrap();
+    assert_eq!(created.warnings.len(), 1);
+    assert_eq!(created.warnings[0].code, "mixed_script_host");
+    assert!(create_test_service().await.create_short_url("https://paypal.com".to_string()).await.unwrap().warnings.is_empty());
+
+    let flagged = create_test_service().await.with_homograph_policy(HomographPolicy::Flag);
+    assert!(flagged.create_short_url(lookalike.clone()).await.unwrap().warnings.is_empty());
+
+    let blocked = create_test_service().await.with_homograph_policy(HomographPolicy::Block);
+    match blocked.create_short_url(lookalike).await.unwrap_err().error_type {
+        UrlShortenerErrorType::BlockedUrl(_) => (),
+        error_type => panic!("Expected BlockedUrl, got {:?}", error_type),
+    }
+}
+
+#[tokio::test]
+async fn test_destination_safety() {
+    let service = create_test_service().await;
+    let safe = service.destination_safety("https://paypal.com/").await.unwrap();
+    assert_eq!(safe, Safety { verdict: Verdict::Safe, warnings: Vec::new() });
+
+    // Reported even though the default policy lets such links be created
+    let lookalike = service.destination_safety("https://xn--pypal-4ve.com/login").await.unwrap();
+    assert_eq!(lookalike.verdict, Verdict::Suspicious);
+    assert_eq!(lookalike.warnings[0].code, "mixed_script_host");
+
+    service.ban_domain("paypal.com", "admin", None).await.unwrap();
+    let banned = service.destination_safety("https://www.paypal.com/").await.unwrap();
+    assert_eq!(banned.verdict, Verdict::Blocked);
+    assert_eq!(banned.warnings[0].code, "banned_domain");
+}
+
+#[tokio::test]
+async fn test_destinations_safety_keeps_order() {
+    let service = create_test_service().await;
+    service.ban_domain("banned.example", "admin", None).await.unwrap();
+
+    let destinations = ["https://banned.example/a", "https://xn--pypal-4ve.com/", "not a url", "https://banned.example/b"];
+    let verdicts: Vec<Verdict> = service
+        .destinations_safety(&destinations)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|safety| safety.verdict)
+        .collect();
+    assert_eq!(verdicts, [Verdict::Blocked, Verdict::Suspicious, Verdict::Suspicious, Verdict::Blocked]);
+    assert!(service.destinations_safety(&[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_create_with_word_style() {
+    let service = create_test_service().await;
+    let options = CreateOptions { style: CodeStyle::Words, ..CreateOptions::default() };
+
+    let created = service.create_short_url_with("https://example.com".to_string(), options).await.unwrap();
+    let parts: Vec<&str> = created.short_code.split('-').collect();
+    assert_eq!(parts.len(), 3, "unexpected word code {}", created.short_code);
+    assert!(parts[..2].iter().all(|word| word.chars().all(|c| c.is_ascii_lowercase())));
+    assert!(parts[2].parse::<u32>().unwrap() < 100);
+
+    // Word codes share the random codes' collision handling
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let words = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["blue-tiger-42", "blue-tiger-42", "red-fox-7"])));
+    let service = UrlService::new(storage).with_word_generator(words);
+    let options = CreateOptions { style: CodeStyle::Words, ..CreateOptions::default() };
+    service.create_short_url_with("https://example.com/1".to_string(), options.clone()).await.unwrap();
+    let second = service.create_short_url_with("https://example.com/2".to_string(), options).await.unwrap();
+    assert_eq!(second.short_code, "red-fox-7");
+}
+
+#[test]
+fn test_word_generator_filters_lists() {
+    let generator = WordCodeGenerator::new(vec![" Sunny ", "", "sh1ny", "classy"], vec!["otter"]);
+    // "classy" contains a blocked word, "sh1ny" is not plain letters
+    for _ in 0..20 {
+        assert!(generator.generate().starts_with("sunny-otter-"));
+    }
+}
+
+#[test]
+fn test_snowflake_codes_are_unique_across_regions() {
+    let now = Utc::now();
+    let regions: Vec<_> = (0..3).map(|region| SnowflakeCodeGenerator::new(region, 7, false)).collect();
+    let mut codes = HashSet::new();
+    for generator in &regions {
+        for _ in 0..5000 {
+            assert!(codes.insert(generator.generate_at(now)));
+        }
+    }
+
+    let generator = &regions[2];
+    let code = generator.generate_at(now);
+    let id = generator.decode(&code).unwrap();
+    assert_eq!((id.region, id.worker), (2, 7));
+    // 5000 codes overflow one millisecond's sequence, so the last ones borrow the next
+    let drift = (id.created_at - now).num_milliseconds();
+    assert!((0..=2).contains(&drift), "{}", drift);
+    assert!((9..=11).contains(&code.len()), "{}", code);
+}
+
+#[test]
+fn test_snowflake_ids_never_go_back_in_time() {
+    let generator = SnowflakeCodeGenerator::new(1, 0, true);
+    let now = Utc::now();
+    let first = generator.decode(&generator.generate_at(now)).unwrap();
+
+    // A clock stepping back keeps counting from the last ID
+    let after_step = generator.decode(&generator.generate_at(now - chrono::Duration::seconds(5))).unwrap();
+    assert_eq!(after_step.created_at, first.created_at);
+    assert_eq!(after_step.sequence, first.sequence + 1);
+
+    // A used-up millisecond moves on to the next one
+    let last = (0..4096).map(|_| generator.generate_at(now)).last().unwrap();
+    let last = generator.decode(&last).unwrap();
+    assert_eq!(last.created_at, first.created_at + chrono::Duration::milliseconds(1));
+
+    let code = generator.generate();
+    assert!(code.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()), "{}", code);
+    assert_eq!(generator.decode("not-a-code"), None);
+}
+
+#[test]
+fn test_profanity_filter() {
+    let filter = ProfanityFilter::default();
+    assert!(filter.is_offensive("xShitx"));
+    assert!(filter.is_offensive("aB-sh1t_9"));
+    assert!(filter.is_offensive("f-u_c-k"));
+    assert!(!filter.is_offensive("V1StGXR8_Z"));
+    assert!(!filter.is_offensive("blue-tiger-42"));
+
+    let extended = ProfanityFilter::with_additions(["Frak", " "]);
+    assert!(extended.is_offensive("xfr4kx"));
+    assert!(!filter.is_offensive("xfr4kx"));
+}
+
+#[tokio::test]
+async fn test_offensive_codes_are_regenerated() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["x5h1tx", "cl34n"])));
+    let service = UrlService::with_code_generator(storage, generator);
+
+    let created = service.create_short_url("https://example.com".to_string()).await.unwrap();
+    assert_eq!(created.short_code, "cl34n");
+
+    // A generator that only yields blocked words gives up instead of looping
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["shit"])));
+    let service = UrlService::with_code_generator(storage, generator);
+    match service.create_short_url("https://example.com".to_string()).await.unwrap_err().error_type {
+        UrlShortenerErrorType::InternalError(_) => (),
+        error_type => panic!("Expected InternalError, got {:?}", error_type),
+    }
+}
+
+#[tokio::test]
+async fn test_offensive_alias_is_rejected() {
+    let service = create_test_service().await.with_profanity_filter(ProfanityFilter::with_additions(["frak"]));
+    let options = |alias: &str| CreateOptions { custom_alias: Some(alias.to_string()), ..CreateOptions::default() };
+
+    for alias in ["Sh1t-happens", "frakking"] {
+        let result = service.create_short_url_with("https://example.com".to_string(), options(alias)).await;
+        match result.unwrap_err().error_type {
+            UrlShortenerErrorType::ValidationFailed(errors) => assert_eq!(errors[0].code, "offensive"),
+            error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_namespaces_have_independent_code_spaces() {
+    let namespaces = Namespaces::new(&["t", "team", "api", "bad/prefix"])
+        .with_reserved(Some("t"), &["Admin"])
+        .with_reserved(None, &["help"]);
+    assert!(namespaces.get("api").is_none() && namespaces.get("bad/prefix").is_none());
+    let service = create_test_service().await.with_namespaces(namespaces);
+    let options = |namespace: Option<&str>, alias: &str| CreateOptions {
+        namespace: namespace.map(str::to_string),
+        custom_alias: Some(alias.to_string()),
+        ..CreateOptions::default()
+    };
+
+    // The same alias can exist at the root and in each namespace
+    let root = service.create_short_url_with("https://example.com/root".to_string(), options(None, "launch")).await.unwrap();
+    let team = service.create_short_url_with("https://example.com/t".to_string(), options(Some("t"), "launch")).await.unwrap();
+    assert_eq!(root.short_code, "launch");
+    assert_eq!(team.short_code, "t/launch");
+    assert_eq!(service.get_original_url("t/launch").await.unwrap(), "https://example.com/t");
+
+    // Reserved words apply to their own namespace only; prefixes are reserved at the root
+    let reserved = [(Some("t"), "admin"), (None, "help"), (None, "team")];
+    for (namespace, alias) in reserved {
+        let result = service.create_short_url_with("https://example.com".to_string(), options(namespace, alias)).await;
+        match result.unwrap_err().error_type {
+            UrlShortenerErrorType::ValidationFailed(errors) => assert_eq!(errors[0].code, "reserved"),
+            error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+        }
+    }
+    assert!(service.create_short_url_with("https://example.com".to_string(), options(Some("t"), "help")).await.is_ok());
+
+    let result = service.create_short_url_with("https://example.com".to_string(), options(Some("nope"), "x")).await;
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::ValidationFailed(errors) => assert_eq!(errors[0].code, "unknown_namespace"),
+        error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+    }
+}
+
+#[tokio::test]
+async fn test_go_links_are_loose_keywords_found_by_search() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let namespaces = Namespaces::new(&["t"]).with_keywords("go").with_reserved(Some("go"), &["admin"]);
+    assert_eq!(namespaces.keywords().map(Namespace::prefix), Some("go"));
+    assert!(namespaces.is_reserved(None, "go"));
+    let service = UrlService::new(storage.clone()).with_namespaces(namespaces);
+    let create = |keyword: &'static str, title: Option<&'static str>| {
+        service.create_go_link(keyword, format!("https://example.com/{}", keyword.len()), title.map(str::to_string), "alice")
+    };
+
+    let payroll = create("  Payroll__Team ", Some("HR payroll portal")).await.unwrap();
+    assert_eq!((payroll.keyword.as_str(), payroll.owner.as_deref()), ("payroll-team", Some("alice")));
+    // Resolving counts a visit
+    assert_eq!(service.get_original_url("go/payroll-team").await.unwrap(), payroll.url);
+    create("oncall", Some("Pager rota")).await.unwrap();
+    create("wiki", None).await.unwrap();
+    // Keywords are ordinary words, not matched against the profanity filter
+    create("assessment", None).await.unwrap();
+
+    let result = create("Admin", None).await;
+    match result.unwrap_err().error_type {
+        UrlShortenerErrorType::ValidationFailed(errors) => assert_eq!(errors[0].code, "reserved"),
+        error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+    }
+
+    storage.record_visits("go/wiki", 5).await.unwrap();
+    storage.record_visits("go/oncall", 2).await.unwrap();
+    let keywords = |links: Vec<GoLink>| links.into_iter().map(|link| link.keyword).collect::<Vec<_>>();
+    assert_eq!(keywords(service.popular_go_links(3).await.unwrap()), ["wiki", "oncall", "payroll-team"]);
+    // Titles are searched too, regardless of case
+    assert_eq!(keywords(service.search_go_links("PAYROLL", 10).await.unwrap()), ["payroll-team"]);
+    assert_eq!(keywords(service.search_go_links("rota", 10).await.unwrap()), ["oncall"]);
+
+    // Links outside the namespace and disabled links are left out
+    service.create_short_url("https://example.com/wiki".to_string()).await.unwrap();
+    storage.disable_urls(&["go/oncall".to_string()]).await.unwrap();
+    assert_eq!(keywords(service.popular_go_links(10).await.unwrap()), ["wiki", "payroll-team", "assessment"]);
+
+    let plain = create_test_service().await;
+    assert!(plain.create_short_url_with("https://example.com".to_string(), CreateOptions {
+        custom_alias: Some("assessment".to_string()),
+        ..CreateOptions::default()
+    }).await.is_err());
+    let result = plain.popular_go_links(10).await;
+    assert!(matches!(result.unwrap_err().error_type, UrlShortenerErrorType::NotFound));
+}
+
+#[tokio::test]
+async fn test_nested_keywords_suggest_alternatives_and_change_hands() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let namespaces = Namespaces::default().with_keywords("go").with_reserved(Some("go"), &["admin"]);
+    let service = UrlService::new(storage.clone()).with_namespaces(namespaces);
+    let create = |keyword: &'static str, owner: &'static str| {
+        service.create_go_link(keyword, "https://example.com/".to_string(), None, owner)
+    };
+
+    let runbook = create("Team / On Call Runbook", "alice").await.unwrap();
+    assert_eq!(runbook.keyword, "team/on-call-runbook");
+    assert_eq!(storage.get_stats("go/team/on-call-runbook").await.unwrap().owner.as_deref(), Some("alice"));
+
+    let rejections = [
+        ("a/b/c", "too_deep"),
+        ("team/heatmap", "reserved"),
+        ("admin/tools", "reserved"),
+        ("team/wh?at", "invalid_characters"),
+    ];
+    for (keyword, code) in rejections {
+        match create(keyword, "alice").await.unwrap_err().error_type {
+            UrlShortenerErrorType::ValidationFailed(errors) => {
+                assert_eq!((errors[0].field.as_str(), errors[0].code.as_str()), ("keyword", code), "{}", keyword)
+            }
+            error_type => panic!("Expected ValidationFailed for {}, got {:?}", keyword, error_type),
+        }
+    }
+
+    // A taken keyword names its owner and offers free ones, under the caller's name first
+    create("payroll", "alice").await.unwrap();
+    create("payroll-2", "carol").await.unwrap();
+    let UrlShortenerErrorType::KeywordTaken(taken) = create("Payroll", "bob").await.unwrap_err().error_type else {
+        panic!("expected the keyword to be taken");
+    };
+    assert_eq!((taken.keyword.as_str(), taken.owner.as_deref()), ("payroll", Some("alice")));
+    assert_eq!(taken.suggestions, ["bob/payroll", "payroll-3", "payroll-4"]);
+    // Nested keywords are not nested further
+    let UrlShortenerErrorType::KeywordTaken(taken) = create("team/on-call-runbook", "bob").await.unwrap_err().error_type else {
+        panic!("expected the keyword to be taken");
+    };
+    assert_eq!(taken.suggestions[0], "team/on-call-runbook-2");
+
+    // Only the owner can hand a link over; it leaves their collection
+    let collection = service.create_collection("alice", "ops").await.unwrap();
+    let update = MetadataUpdate { collection_id: Some(Some(collection.id)), ..MetadataUpdate::default() };
+    storage.update_metadata("go/payroll", update).await.unwrap();
+    let result = service.transfer_link("go/payroll", "bob", "bob").await;
+    assert!(matches!(result.unwrap_err().error_type, UrlShortenerErrorType::ValidationFailed(_)));
+    let result = service.transfer_link("go/payroll", "carol", "bob").await;
+    assert!(matches!(result.unwrap_err().error_type, UrlShortenerErrorType::NotFound));
+
+    let transferred = service.transfer_link("go/payroll", "alice", " bob ").await.unwrap();
+    assert_eq!((transferred.owner.as_deref(), transferred.collection_id), (Some("bob"), None));
+    let audit = storage.list_audit_entries(10).await.unwrap();
+    assert_eq!((audit[0].actor.as_str(), audit[0].action.as_str()), ("alice", "link.transfer"));
+    assert_eq!(audit[0].details["to"], "bob");
+    assert!(service.transfer_link("go/payroll", "alice", "carol").await.is_err());
+}
+
+#[tokio::test]
+async fn test_reserved_generated_codes_are_regenerated() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let generator = Arc::new(SequenceCodeGenerator(Mutex::new(vec!["admin", "free"])));
+    let service = UrlService::with_code_generator(storage, generator)
+        .with_namespaces(Namespaces::new(&["t"]).with_reserved(Some("t"), &["admin"]));
+    let options = CreateOptions { namespace: Some("t".to_string()), ..CreateOptions::default() };
+
+    let created = service.create_short_url_with("https://example.com".to_string(), options).await.unwrap();
+    assert_eq!(created.short_code, "t/free");
+}
+
+#[test]
+fn test_content_check_helpers() {
+    assert_eq!(mime_essence("Application/X-MSDownload; charset=binary"), "application/x-msdownload");
+    assert_eq!(attachment_file_name(r#"attachment; filename="Setup.EXE""#), Some("Setup.EXE"));
+    assert_eq!(attachment_file_name("attachment; filename*=UTF-8''app%20v2.apk"), Some("app%20v2.apk"));
+    assert_eq!(attachment_file_name("inline"), None);
+}
+
+#[tokio::test]
+async fn test_content_check_blocks_denied_file_types() {
+    // Private hosts are never requested, so only the path is checked here
+    let check = ContentCheck::default();
+    let inspect = |url: &str| {
+        let url = Url::parse(url).unwrap();
+        let check = check.clone();
+        async move { check.inspect(&url).await }
+    };
+    for url in ["http://127.0.0.1/downloads/Setup.EXE", "http://localhost/app.apk?v=2", "http://10.0.0.1/x/screen.scr"] {
+        match inspect(url).await.unwrap_err().error_type {
+            UrlShortenerErrorType::BlockedUrl(_) => (),
+            error_type => panic!("Expected BlockedUrl for {}, got {:?}", url, error_type),
+        }
+    }
+    assert_eq!(inspect("http://127.0.0.1/exe/readme.txt").await.unwrap(), None);
+    assert_eq!(inspect("http://localhost/").await.unwrap(), None);
+
+    let custom = ContentCheck::with_denylist(&[".ZIP", "application/zip"]);
+    assert!(custom.inspect(&Url::parse("http://127.0.0.1/a.zip").unwrap()).await.is_err());
+    assert!(custom.inspect(&Url::parse("http://127.0.0.1/a.exe").unwrap()).await.is_ok());
+}
+
+#[actix_rt::test]
+async fn test_content_check_only_requests_public_addresses() {
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let downloads = Arc::new(AtomicUsize::new(0));
+    let counted = downloads.clone();
+    let server = HttpServer::new(move || {
+        let counted = counted.clone();
+        App::new()
+            .route("/setup", web::head().to(move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                async { HttpResponse::Ok().content_type("application/x-msdownload").finish() }
+            }))
+            .route("/moved", web::head().to(|| async {
+                HttpResponse::Found().insert_header(("Location", "/files/app.exe")).finish()
+            }))
+            .route("/internal", web::head().to(move || async move {
+                let location = format!("http://loopback.test:{}/setup", addr.port());
+                HttpResponse::Found().insert_header(("Location", location)).finish()
+            }))
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    let handle = server.handle();
+    tokio::spawn(server);
+
+    // The test server stands in for a public host; `loopback.test` is a public-looking name
+    // whose address is the loopback one, and goes through the usual address check
+    let addrs_of = |url: Url| async move {
+        match url.host_str() {
+            Some("public.test") => Ok(vec![addr]),
+            Some("loopback.test") => crate::preview::checked_addrs(&url, vec![addr]),
+            _ => crate::preview::public_addrs(&url).await,
+        }
+    };
+    let check = ContentCheck::default();
+    let inspect = |host: &str, path: &str| {
+        let url = Url::parse(&format!("http://{}:{}{}", host, addr.port(), path)).unwrap();
+        let check = check.clone();
+        async move { check.inspect_with(&url, addrs_of).await }
+    };
+
+    assert!(inspect("public.test", "/setup").await.is_err());
+    assert_eq!(downloads.swap(0, Ordering::SeqCst), 1);
+    assert!(inspect("public.test", "/moved").await.is_err());
+
+    assert_eq!(inspect("loopback.test", "/setup").await.unwrap(), None);
+    assert_eq!(inspect("public.test", "/internal").await.unwrap(), None);
+    assert_eq!(downloads.load(Ordering::SeqCst), 0);
+
+    handle.stop(false).await;
+}
+
+#[tokio::test]
+async fn test_links_to_denied_file_types_are_refused() {
+    let service = create_test_service().await.with_content_check(ContentCheck::default());
+    match service.create_short_url("http://127.0.0.1/setup.exe".to_string()).await.unwrap_err().error_type {
+        UrlShortenerErrorType::BlockedUrl(_) => (),
+        error_type => panic!("Expected BlockedUrl, got {:?}", error_type),
+    }
+
+    let created = service.create_short_url("http://127.0.0.1/setup.html".to_string()).await.unwrap();
+    assert_eq!(created.content_type, None);
+}
+
+#[tokio::test]
+async fn test_creations_over_the_link_quota_are_rejected() {
+    let quota = LinkQuota { total: 3, per_owner: 1, policy: QuotaPolicy::Reject };
+    let service = create_test_service().await.with_link_quota(quota);
+    let owned = |owner: &str| service.create_owned_short_url("https://example.com".to_string(), Some(owner.to_string()));
+
+    owned("alice").await.unwrap();
+    match owned("alice").await.unwrap_err().error_type {
+        UrlShortenerErrorType::QuotaExceeded(_) => (),
+        error_type => panic!("Expected QuotaExceeded, got {:?}", error_type),
+    }
+    owned("bob").await.unwrap();
+    service.create_short_url("https://example.com".to_string()).await.unwrap();
+    // The deployment is full, whoever asks
+    assert!(service.create_short_url("https://example.com".to_string()).await.is_err());
+    assert!(owned("carol").await.is_err());
+}
+
+#[tokio::test]
+async fn test_link_quota_evicts_the_oldest_unvisited_links() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let quota = LinkQuota { total: 2, per_owner: 0, policy: QuotaPolicy::Evict };
+    let service = UrlService::new(storage.clone()).with_link_quota(quota);
+    let create = || service.create_short_url("https://example.com".to_string());
+
+    let visited = create().await.unwrap();
+    let unvisited = create().await.unwrap();
+    storage.record_visits(&visited.short_code, 1).await.unwrap();
+
+    let newest = create().await.unwrap();
+    assert!(storage.get_url(&visited.short_code).await.is_ok());
+    assert!(storage.get_url(&unvisited.short_code).await.is_err());
+    assert_eq!(storage.count_urls(None).await.unwrap(), 2);
+
+    // Once every link has been visited, there is nothing left to evict
+    storage.record_visits(&newest.short_code, 1).await.unwrap();
+    match create().await.unwrap_err().error_type {
+        UrlShortenerErrorType::QuotaExceeded(_) => (),
+        error_type => panic!("Expected QuotaExceeded, got {:?}", error_type),
+    }
+}
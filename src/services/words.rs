@@ -0,0 +1,68 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::{CodeGenerator, ProfanityFilter};
+
+const ADJECTIVES: &str = include_str!("words/adjectives.txt");
+const NOUNS: &str = include_str!("words/nouns.txt");
+
+/// Exclusive upper bound of the number appended to word codes
+const WORD_CODE_NUMBER_RANGE: u32 = 100;
+
+/// Generates memorable codes like `blue-tiger-42` from an adjective, a noun and a number.
+///
+/// The embedded lists yield roughly 800k combinations, so collisions are more likely than with
+/// random codes and are left to the service's regeneration loop.
+#[derive(Debug, Clone)]
+pub struct WordCodeGenerator {
+    adjectives: Vec<String>,
+    nouns: Vec<String>,
+}
+
+impl WordCodeGenerator {
+    /// Builds a generator from custom word lists. Entries are trimmed and lowercased; blank
+    /// entries, entries that are not plain ASCII letters and entries containing a blocked word
+    /// are dropped.
+    ///
+    /// # Panics
+    /// If either list is empty after filtering.
+    pub fn new<I, S>(adjectives: I, nouns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let filter = ProfanityFilter::default();
+        let adjectives = clean_words(adjectives, &filter);
+        let nouns = clean_words(nouns, &filter);
+        assert!(!adjectives.is_empty() && !nouns.is_empty(), "word lists must not be empty");
+        Self { adjectives, nouns }
+    }
+}
+
+impl Default for WordCodeGenerator {
+    fn default() -> Self {
+        Self::new(ADJECTIVES.lines(), NOUNS.lines())
+    }
+}
+
+impl CodeGenerator for WordCodeGenerator {
+    fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        let adjective = self.adjectives.choose(&mut rng).expect("adjectives are non-empty");
+        let noun = self.nouns.choose(&mut rng).expect("nouns are non-empty");
+        format!("{}-{}-{}", adjective, noun, rng.gen_range(0..WORD_CODE_NUMBER_RANGE))
+    }
+}
+
+fn clean_words<I, S>(words: I, filter: &ProfanityFilter) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    words
+        .into_iter()
+        .map(|word| word.as_ref().trim().to_ascii_lowercase())
+        .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase()))
+        .filter(|word| !filter.is_offensive(word))
+        .collect()
+}
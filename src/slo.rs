@@ -0,0 +1,102 @@
+//! Latency objectives per route class.
+//!
+//! Every request of a [`RouteClass`] is counted, and it counts as good when it was answered
+//! within its class's latency threshold without a server error. The counters only grow, so
+//! the share of bad requests over any window, and with it the error budget burn rate, is the
+//! difference of two readings.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::throttle::RouteClass;
+
+/// Latency threshold of each route class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SloConfig {
+    pub redirect: Duration,
+    pub read_api: Duration,
+    pub write_api: Duration,
+    pub admin: Duration,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            redirect: Duration::from_millis(100),
+            read_api: Duration::from_millis(500),
+            write_api: Duration::from_millis(1000),
+            admin: Duration::from_millis(5000),
+        }
+    }
+}
+
+impl SloConfig {
+    pub fn threshold(&self, class: RouteClass) -> Duration {
+        match class {
+            RouteClass::Redirect => self.redirect,
+            RouteClass::ReadApi => self.read_api,
+            RouteClass::WriteApi => self.write_api,
+            RouteClass::Admin => self.admin,
+        }
+    }
+}
+
+/// Counters of one route class since startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassSlo {
+    pub class: RouteClass,
+    pub threshold_ms: u64,
+    /// Requests answered within the threshold, without a server error
+    pub good: u64,
+    pub total: u64,
+}
+
+#[derive(Default)]
+struct ClassCounters {
+    good: AtomicU64,
+    total: AtomicU64,
+}
+
+/// Good and total requests of each route class
+#[derive(Default)]
+pub struct SloCounters {
+    config: SloConfig,
+    classes: [ClassCounters; RouteClass::ALL.len()],
+}
+
+impl SloCounters {
+    pub fn new(config: SloConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Counts a request of `class` answered with `status` after `elapsed`
+    pub fn record(&self, class: RouteClass, status: StatusCode, elapsed: Duration) {
+        let counters = &self.classes[class as usize];
+        counters.total.fetch_add(1, Ordering::Relaxed);
+        if !status.is_server_error() && elapsed <= self.config.threshold(class) {
+            counters.good.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> Vec<ClassSlo> {
+        RouteClass::ALL
+            .iter()
+            .zip(&self.classes)
+            .map(|(class, counters)| ClassSlo {
+                class: *class,
+                threshold_ms: self.config.threshold(*class).as_millis() as u64,
+                good: counters.good.load(Ordering::Relaxed),
+                total: counters.total.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for SloCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SloCounters").field("config", &self.config).finish_non_exhaustive()
+    }
+}
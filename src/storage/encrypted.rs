@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use super::{in_domain, url_host, StatementCacheStats, Storage, StorageRef};
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DomainBan, ExportedApiKey,
+    IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink, RetiredCode, Session,
+    ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline, WebhookDelivery,
+};
+
+/// Length of an encryption key, in bytes
+pub const URL_KEY_LENGTH: usize = 32;
+
+/// Marks an encrypted destination; stored destinations without it are plaintext
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+const NONCE_LENGTH: usize = 12;
+
+/// Links decrypted per round trip when a domain ban or a link list filtered by domain scans for
+/// matching destinations
+const SCAN_BATCH_SIZE: i64 = 1000;
+
+/// AES-256-GCM encryption of destination URLs.
+///
+/// A destination is stored as `enc:v1:<key id>:<base64 of nonce and ciphertext>`, where the
+/// key ID is derived from the key itself. New destinations are encrypted with the current
+/// key; old keys are only used to decrypt, so a key can be rotated without rewriting links.
+#[derive(Clone)]
+pub struct UrlCipher {
+    key_id: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl UrlCipher {
+    /// Encrypts with `key` and decrypts destinations written with it or any of `old_keys`
+    pub fn new<K: AsRef<[u8]>>(key: &[u8], old_keys: &[K]) -> UrlShortenerResult<Self> {
+        let mut keys = HashMap::new();
+        for key in std::iter::once(key).chain(old_keys.iter().map(AsRef::as_ref)) {
+            let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(format!(
+                    "URL encryption keys must be {URL_KEY_LENGTH} bytes, got {}",
+                    key.len()
+                )))
+            })?;
+            keys.insert(key_id(key), cipher);
+        }
+        Ok(Self { key_id: key_id(key), keys })
+    }
+
+    /// Same as [`new`](Self::new), with base64-encoded keys
+    pub fn from_base64<S: AsRef<str>>(key: &str, old_keys: &[S]) -> UrlShortenerResult<Self> {
+        let decode = |key: &str| {
+            STANDARD.decode(key.trim()).map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "URL encryption key is not valid base64".to_string(),
+                ))
+            })
+        };
+        let old_keys = old_keys.iter().map(|key| decode(key.as_ref())).collect::<UrlShortenerResult<Vec<_>>>()?;
+        Self::new(&decode(key)?, &old_keys)
+    }
+
+    /// ID of the key new destinations are encrypted with
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    pub fn encrypt(&self, url: &str) -> String {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.keys[&self.key_id]
+            .encrypt(Nonce::from_slice(&nonce), url.as_bytes())
+            .expect("AES-GCM encryption of a URL cannot fail");
+
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        format!("{ENCRYPTED_PREFIX}{}:{}", self.key_id, STANDARD.encode(payload))
+    }
+
+    /// Decrypts a stored destination; plaintext from before encryption was enabled is returned as is
+    pub fn decrypt(&self, stored: &str) -> UrlShortenerResult<String> {
+        let Some(encrypted) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let undecryptable = |reason: &str| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(format!(
+                "Cannot decrypt destination URL: {reason}"
+            )))
+        };
+
+        let (key_id, payload) = encrypted.split_once(':').ok_or_else(|| undecryptable("malformed value"))?;
+        let cipher = self.keys.get(key_id).ok_or_else(|| undecryptable("unknown key"))?;
+        let payload = STANDARD.decode(payload).map_err(|_| undecryptable("malformed value"))?;
+        if payload.len() < NONCE_LENGTH {
+            return Err(undecryptable("malformed value"));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+        let url = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable("authentication failed"))?;
+        String::from_utf8(url).map_err(|_| undecryptable("not UTF-8"))
+    }
+
+    /// Encrypts a link's destination, leaving one that is already encrypted, e.g. from a dump
+    fn seal(&self, mut url: ShortenedUrl) -> ShortenedUrl {
+        if !Self::is_encrypted(&url.original_url) {
+            url.original_url = self.encrypt(&url.original_url);
+        }
+        url
+    }
+
+    fn open(&self, mut url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        url.original_url = self.decrypt(&url.original_url)?;
+        Ok(url)
+    }
+
+    fn open_all(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        urls.into_iter().map(|url| self.open(url)).collect()
+    }
+}
+
+impl fmt::Debug for UrlCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlCipher").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+/// First bytes of the key's SHA-256, enough to tell configured keys apart
+fn key_id(key: &[u8]) -> String {
+    hex::encode(&Sha256::digest(key)[..4])
+}
+
+/// Storage that keeps destination URLs encrypted in another backend.
+///
+/// Destinations are encrypted on the way in and decrypted on the way out, so the service
+/// layer and redirects only ever see plaintext. Everything else is passed through unchanged.
+pub struct EncryptedStorage {
+    inner: StorageRef,
+    cipher: UrlCipher,
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: StorageRef, cipher: UrlCipher) -> Self {
+        Self { inner, cipher }
+    }
+
+    /// Active links whose decrypted destination is on `domain` or one of its subdomains
+    async fn links_to_domain(&self, domain: &str) -> UrlShortenerResult<Vec<String>> {
+        let mut matching = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let page = self.inner.export_urls(after.as_deref(), SCAN_BATCH_SIZE).await?;
+            let Some(last) = page.last() else { break };
+            after = Some(last.short_url.clone());
+
+            for url in page.into_iter().filter(|url| url.disabled_at.is_none()) {
+                let url = self.cipher.open(url)?;
+                let host = url::Url::parse(&url.original_url).ok().and_then(|parsed| parsed.host_str().map(str::to_string));
+                if host.is_some_and(|host| in_domain(&host, domain)) {
+                    matching.push(url.short_url);
+                }
+            }
+        }
+        Ok(matching)
+    }
+
+    /// Up to `limit` of an owner's links matching `filter`, including its domain, which is only
+    /// known after decrypting, so links are decrypted a batch at a time until enough match
+    async fn scan_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        mut after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let unfiltered = UrlFilter { domain: None, ..filter.clone() };
+        let mut matching = Vec::new();
+        while (matching.len() as i64) < limit {
+            let batch = self.inner.list_urls(owner, sort, &unfiltered, after, SCAN_BATCH_SIZE).await?;
+            let Some(last) = batch.last() else { break };
+            after = Some(UrlCursor::after(sort, last));
+            let exhausted = (batch.len() as i64) < SCAN_BATCH_SIZE;
+
+            for url in self.cipher.open_all(batch)? {
+                if url_host(&url.original_url) == filter.domain {
+                    matching.push(url);
+                }
+            }
+            if exhausted {
+                break;
+            }
+        }
+        matching.truncate(limit.max(0) as usize);
+        Ok(matching)
+    }
+}
+
+#[async_trait]
+impl Storage for EncryptedStorage {
+    async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        let saved = self.inner.save_url(self.cipher.seal(url)).await?;
+        self.cipher.open(saved)
+    }
+
+    async fn get_url(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.cipher.open(self.inner.get_url(short_code).await?)
+    }
+
+    async fn get_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.cipher.open(self.inner.get_stats(short_code).await?)
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        self.inner
+            .get_stats_many(short_codes)
+            .await?
+            .into_iter()
+            .map(|(code, url)| Ok((code, self.cipher.open(url)?)))
+            .collect()
+    }
+
+    async fn bulk_insert(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let urls = urls.into_iter().map(|url| self.cipher.seal(url)).collect();
+        self.inner.bulk_insert(urls).await
+    }
+
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()> {
+        self.inner.record_visits(short_code, count).await
+    }
+
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        self.inner.record_visit_batch(visits).await
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.cipher.open_all(self.inner.list_urls_by_owner(owner, collection_id, after, limit).await?)
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        if filter.domain.is_some() {
+            return self.scan_urls(owner, sort, filter, after, limit).await;
+        }
+        self.cipher.open_all(self.inner.list_urls(owner, sort, filter, after, limit).await?)
+    }
+
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        self.cipher.open(self.inner.update_metadata(short_code, update).await?)
+    }
+
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.cipher.open(self.inner.transfer_url(short_code, from, to).await?)
+    }
+
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        self.inner.create_collection(owner, name).await
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        self.inner.get_collection(id).await
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.inner.list_collections(owner).await
+    }
+
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection> {
+        self.inner.rename_collection(id, name).await
+    }
+
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()> {
+        self.inner.delete_collection(id).await
+    }
+
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.cipher.open_all(self.inner.claim_reached_goals(limit).await?)
+    }
+
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()> {
+        self.inner.release_goal(short_code).await
+    }
+
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.create_api_key(owner, name, key_hash, prefix).await
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.get_api_key(id).await
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.inner.list_api_keys(owner).await
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()> {
+        self.inner.revoke_api_key(id).await
+    }
+
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.rotate_api_key(id, key_hash, prefix).await
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.authenticate_api_key(key_hash).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.find_api_key(key_hash).await
+    }
+
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()> {
+        self.inner.create_session(session).await
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.inner.touch_session(id, idle_cutoff).await
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.inner.find_session(id, idle_cutoff).await
+    }
+
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()> {
+        self.inner.delete_session(id).await
+    }
+
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.inner.purge_idle_sessions(idle_cutoff).await
+    }
+
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        self.inner.change_visits(short_code, change).await
+    }
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        self.inner.record_audit(entry).await
+    }
+
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let urls = urls.into_iter().map(|url| self.cipher.seal(url)).collect();
+        self.inner.import_urls(urls).await
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        self.inner.count_urls(owner).await
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        self.inner.oldest_unvisited_urls(owner, limit).await
+    }
+
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.inner.delete_unvisited_urls(short_codes).await
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        self.inner.list_audit_entries(limit).await
+    }
+
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.inner.purge_audit_entries(before).await
+    }
+
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        self.inner.record_webhook_delivery(delivery).await
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.inner.list_webhook_deliveries(webhook, before, limit).await
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        self.inner.get_webhook_delivery(webhook, id).await
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.cipher.open_all(self.inner.export_urls(after, limit).await?)
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.cipher.open_all(self.inner.top_visited_urls(limit).await?)
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.cipher.open_all(self.inner.search_urls(prefix, query, limit).await?)
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        self.inner.export_collections().await
+    }
+
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        self.inner.restore_collection(collection).await
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        self.inner.export_api_keys().await
+    }
+
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        self.inner.restore_api_key(key).await
+    }
+
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool> {
+        self.inner.copy_collection(collection).await
+    }
+
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        self.inner.copy_api_key(key).await
+    }
+
+    // Kept responses hold the destinations of the links they created, so their bodies are
+    // encrypted too
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        let Some(mut record) = self.inner.reserve_idempotency_key(scope, key, fingerprint, expired_before).await?
+        else {
+            return Ok(None);
+        };
+        if let Some(response) = record.response.as_mut() {
+            response.body = self.cipher.decrypt(&response.body)?;
+        }
+        Ok(Some(record))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        let body = self.cipher.encrypt(&response.body);
+        self.inner.complete_idempotency_key(scope, key, IdempotentResponse { body, ..response }).await
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        self.inner.release_idempotency_key(scope, key).await
+    }
+
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.inner.purge_idempotency_keys(before).await
+    }
+
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        // The backend can only match the plaintext destinations written before encryption was
+        // enabled; encrypted ones are decrypted and matched here. A link created between the
+        // two steps is still refused, as the domain is already blocked.
+        let mut ban = self.inner.ban_domain(domain, reason, blocked_by).await?;
+        let matching = self.links_to_domain(domain).await?;
+        ban.disabled.extend(self.inner.disable_urls(&matching).await?);
+        ban.disabled.sort();
+        Ok(ban)
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.inner.list_blocked_domains().await
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        self.inner.is_domain_blocked(host).await
+    }
+
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.inner.disable_urls(short_codes).await
+    }
+
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.inner.enable_urls(short_codes).await
+    }
+
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        let regenerated = self.inner.regenerate_code(short_code, new_code, stats, redirects_until).await?;
+        Ok(RegeneratedLink { link: self.cipher.open(regenerated.link)?, ..regenerated })
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.inner.list_retired_codes(short_code).await
+    }
+
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        self.inner.add_alias(short_code, alias).await
+    }
+
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()> {
+        self.inner.remove_alias(short_code, alias).await
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.inner.list_aliases(short_code).await
+    }
+
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        self.inner.record_visit_events(events).await
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        self.inner.export_visit_events(from, to, after, limit).await
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        self.inner.visit_timeline(short_code, now).await
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        self.inner.visit_heatmap(short_code, since).await
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        self.inner.daily_link_visits(short_codes, since).await
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        self.inner.visit_sources(short_code, since).await
+    }
+
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        self.inner.create_campaign(owner, name).await
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        self.inner.get_campaign(id).await
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.inner.list_campaigns(owner).await
+    }
+
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()> {
+        self.inner.delete_campaign(id).await
+    }
+
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.inner.add_campaign_link(id, short_code).await
+    }
+
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.inner.remove_campaign_link(id, short_code).await
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        self.inner.campaign_stats(id, since).await
+    }
+
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64> {
+        self.inner.roll_up_usage(day).await
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.inner.list_usage(from, to).await
+    }
+
+    fn backend(&self) -> String {
+        format!("encrypted({})", self.inner.backend())
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        self.inner.ping().await
+    }
+
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool> {
+        self.inner.copy_campaign(campaign).await
+    }
+
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        self.inner.statement_cache_stats()
+    }
+}
@@ -1,12 +1,60 @@
-use super::{Storage, StorageConfig};
-use crate::models::ShortenedUrl;
+use super::{in_domain, url_host, version_mismatch, Storage, StorageConfig};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DailyVisits, DomainBan,
+    ExportedApiKey, IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, LinkStatus, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink,
+    RetiredCode, Session, ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline,
+    WebhookDelivery,
+};
 use crate::errors::{UrlShortenerResult, UrlShortenerError, UrlShortenerErrorType};
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::RwLock;
+use sqlx::types::Json;
 
 /// In-memory storage implementation using a HashMap
 pub struct MemoryStorage {
     urls: RwLock<HashMap<String, ShortenedUrl>>,
+    collections: RwLock<HashMap<i64, Collection>>,
+    api_keys: RwLock<HashMap<i64, ApiKeyRecord>>,
+    sessions: RwLock<HashMap<String, Session>>,
+    audit_log: RwLock<Vec<AuditEntry>>,
+    webhook_deliveries: RwLock<Vec<WebhookDelivery>>,
+    blocked_domains: RwLock<HashMap<String, BlockedDomain>>,
+    retired_codes: RwLock<Vec<RetiredRecord>>,
+    aliases: RwLock<HashMap<String, AliasRecord>>,
+    campaigns: RwLock<HashMap<i64, Campaign>>,
+    /// Campaign IDs and the current codes of their links
+    campaign_links: RwLock<Vec<(i64, String)>>,
+    visit_events: RwLock<Vec<EventRecord>>,
+    /// Idempotency keys by scope and key
+    idempotency_keys: RwLock<HashMap<(String, String), IdempotencyRecord>>,
+    /// Usage records by owner and day
+    usage_records: RwLock<BTreeMap<(String, NaiveDate), UsageRecord>>,
+}
+
+/// A retired code and the code its link answers to now
+struct RetiredRecord {
+    code: RetiredCode,
+    current: String,
+}
+
+/// An alias and the code of the link it belongs to
+struct AliasRecord {
+    alias: LinkAlias,
+    current: String,
+}
+
+/// A visit event and the code its link answers to now
+struct EventRecord {
+    event: VisitEvent,
+    current: String,
+}
+
+/// An API key with the fields hidden from `StoredApiKey`
+struct ApiKeyRecord {
+    key: StoredApiKey,
+    key_hash: String,
+    revoked: bool,
 }
 
 impl MemoryStorage {
@@ -14,10 +62,100 @@ impl MemoryStorage {
     pub fn new(_config: StorageConfig) -> Self {
         Self {
             urls: RwLock::new(HashMap::new()),
+            collections: RwLock::new(HashMap::new()),
+            api_keys: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+            audit_log: RwLock::new(Vec::new()),
+            webhook_deliveries: RwLock::new(Vec::new()),
+            blocked_domains: RwLock::new(HashMap::new()),
+            retired_codes: RwLock::new(Vec::new()),
+            aliases: RwLock::new(HashMap::new()),
+            campaigns: RwLock::new(HashMap::new()),
+            campaign_links: RwLock::new(Vec::new()),
+            visit_events: RwLock::new(Vec::new()),
+            idempotency_keys: RwLock::new(HashMap::new()),
+            usage_records: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Current code of the link an alias, or a retired code that still redirects, belongs to
+    fn code_redirect(&self, short_code: &str) -> UrlShortenerResult<Option<String>> {
+        let aliases = self.aliases.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        if let Some(record) = aliases.get(short_code) {
+            return Ok(Some(record.current.clone()));
+        }
+        drop(aliases);
+
+        let retired_codes = self.retired_codes.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let now = Utc::now();
+        Ok(retired_codes
+            .iter()
+            .find(|record| record.code.short_code == short_code && record.code.redirects_until > now)
+            .map(|record| record.current.clone()))
+    }
+
+    /// Stores a new active key, rejecting duplicate hashes like the unique index does
+    fn insert_api_key(
+        keys: &mut HashMap<i64, ApiKeyRecord>,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        if keys.values().any(|record| record.key_hash == key_hash) {
+            return Err(UrlShortenerErrorType::Conflict("API key already exists".to_string()).into());
         }
+
+        let key = StoredApiKey {
+            id: keys.keys().max().copied().unwrap_or(0) + 1,
+            owner: owner.to_string(),
+            name: name.map(str::to_string),
+            prefix: prefix.to_string(),
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        keys.insert(key.id, ApiKeyRecord { key: key.clone(), key_hash: key_hash.to_string(), revoked: false });
+        Ok(key)
+    }
+
+    /// Adds aggregates over the collection's links
+    fn collection_stats(&self, collection: Collection) -> UrlShortenerResult<CollectionStats> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let members = urls.values().filter(|url| url.collection_id == Some(collection.id));
+        let (links, visits) = members.fold((0, 0), |(links, visits), url| (links + 1, visits + url.visits));
+        Ok(CollectionStats {
+            id: collection.id,
+            owner: collection.owner,
+            name: collection.name,
+            created_at: collection.created_at,
+            links,
+            visits,
+        })
     }
 }
 
+fn collection_name_taken() -> UrlShortenerError {
+    UrlShortenerErrorType::Conflict("Collection already exists".to_string()).into()
+}
+
+fn campaign_name_taken() -> UrlShortenerError {
+    UrlShortenerErrorType::Conflict("Campaign already exists".to_string()).into()
+}
+
 #[async_trait::async_trait]
 impl Storage for MemoryStorage {
     async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
@@ -27,6 +165,10 @@ impl Storage for MemoryStorage {
             ))
         })?;
 
+        if urls.contains_key(&url.short_url) || self.code_redirect(&url.short_url)?.is_some() {
+            return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+        }
+
         urls.insert(url.short_url.clone(), url.clone());
         Ok(url)
     }
@@ -38,8 +180,13 @@ impl Storage for MemoryStorage {
             ))
         })?;
 
-        if let Some(url) = urls.get_mut(short_code) {
+        let current = match urls.contains_key(short_code) {
+            true => None,
+            false => self.code_redirect(short_code)?,
+        };
+        if let Some(url) = urls.get_mut(current.as_deref().unwrap_or(short_code)) {
             url.visits += 1;
+            url.last_visited_at = Some(Utc::now());
             Ok(url.clone())
         } else {
             Err(UrlShortenerErrorType::NotFound.into())
@@ -53,8 +200,1529 @@ impl Storage for MemoryStorage {
             ))
         })?;
 
-        urls.get(short_code)
+        if let Some(url) = urls.get(short_code) {
+            return Ok(url.clone());
+        }
+        self.code_redirect(short_code)?
+            .and_then(|current| urls.get(&current).cloned())
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut found = Vec::new();
+        for short_code in short_codes {
+            let url = match urls.get(short_code) {
+                Some(url) => Some(url.clone()),
+                None => self.code_redirect(short_code)?.and_then(|current| urls.get(&current).cloned()),
+            };
+            if let Some(url) = url {
+                found.push((short_code.clone(), url));
+            }
+        }
+        Ok(found)
+    }
+
+    async fn bulk_insert(&self, new_urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let count = new_urls.len() as u64;
+        for url in new_urls {
+            urls.insert(url.short_url.clone(), url);
+        }
+        Ok(count)
+    }
+
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let url = urls.get_mut(short_code).ok_or(UrlShortenerErrorType::NotFound)?;
+        url.visits += count as i64;
+        url.last_visited_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let mut found = 0;
+        for (short_code, count) in visits {
+            if let Some(url) = urls.get_mut(short_code) {
+                url.visits += *count as i64;
+                url.last_visited_at = Some(Utc::now());
+                found += 1;
+            }
+        }
+        Ok(found)
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut owned: Vec<ShortenedUrl> = urls
+            .values()
+            .filter(|url| url.owner.as_deref() == Some(owner))
+            .filter(|url| collection_id.is_none() || url.collection_id == collection_id)
+            .filter(|url| {
+                after.is_none_or(|after| {
+                    (url.created_at.timestamp_micros(), url.id) < (after.created_at.timestamp_micros(), after.id)
+                })
+            })
+            .cloned()
+            .collect();
+        owned.sort_by_key(|url| std::cmp::Reverse((url.created_at.timestamp_micros(), url.id)));
+        owned.truncate(limit.max(0) as usize);
+        Ok(owned)
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let position = |url: &ShortenedUrl| {
+            let cursor = UrlCursor::after(sort, url);
+            (cursor.key, cursor.id)
+        };
+        let mut listed: Vec<ShortenedUrl> = urls
+            .values()
+            .filter(|url| url.owner.as_deref() == Some(owner))
+            .filter(|url| filter.created_after.is_none_or(|after| url.created_at > after))
+            .filter(|url| filter.domain.as_deref().is_none_or(|domain| url_host(&url.original_url).as_deref() == Some(domain)))
+            .filter(|url| filter.min_visits.is_none_or(|min| url.visits >= min))
+            .filter(|url| filter.status.is_none_or(|status| (status == LinkStatus::Active) == url.disabled_at.is_none()))
+            .filter(|url| after.is_none_or(|after| position(url) < (after.key, after.id)))
+            .cloned()
+            .collect();
+        listed.sort_by_key(|url| std::cmp::Reverse(position(url)));
+        listed.truncate(limit.max(0) as usize);
+        Ok(listed)
+    }
+
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let url = urls.get_mut(short_code).ok_or(UrlShortenerErrorType::NotFound)?;
+        if let Some(expected) = update.expected_version.filter(|&expected| expected != url.version) {
+            return Err(version_mismatch(expected));
+        }
+        if let Some(title) = update.title {
+            url.title = title;
+        }
+        if let Some(notes) = update.notes {
+            url.notes = notes;
+        }
+        if let Some(created_by) = update.created_by {
+            url.created_by = created_by;
+        }
+        if let Some(collection_id) = update.collection_id {
+            url.collection_id = collection_id;
+        }
+        if let Some(click_goal) = update.click_goal {
+            url.click_goal = click_goal;
+            url.goal_reached_at = None;
+        }
+        if let Some(pass_through) = update.pass_through {
+            url.pass_through = pass_through;
+        }
+        if let Some(signed_only) = update.signed_only {
+            url.signed_only = signed_only;
+        }
+        if let Some(tracking_snippet) = update.tracking_snippet {
+            url.tracking_snippet = tracking_snippet;
+        }
+        if let Some(redirect_headers) = update.redirect_headers {
+            url.redirect_headers = redirect_headers.map(Json);
+        }
+        if let Some(hide_referrer) = update.hide_referrer {
+            url.hide_referrer = hide_referrer;
+        }
+        url.version += 1;
+        Ok(url.clone())
+    }
+
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let url = urls
+            .get_mut(short_code)
+            .filter(|url| url.owner.as_deref() == Some(from))
+            .ok_or(UrlShortenerErrorType::NotFound)?;
+        url.owner = Some(to.to_string());
+        url.collection_id = None;
+        url.version += 1;
+        Ok(url.clone())
+    }
+
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        let mut collections = self.collections.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        if collections.values().any(|c| c.owner == owner && c.name == name) {
+            return Err(collection_name_taken());
+        }
+
+        let collection = Collection {
+            id: collections.keys().max().copied().unwrap_or(0) + 1,
+            owner: owner.to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+        };
+        collections.insert(collection.id, collection.clone());
+        Ok(collection)
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        let collection = {
+            let collections = self.collections.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            collections.get(&id).cloned().ok_or(UrlShortenerErrorType::NotFound)?
+        };
+        self.collection_stats(collection)
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        let mut owned: Vec<Collection> = {
+            let collections = self.collections.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            collections.values().filter(|c| c.owner == owner).cloned().collect()
+        };
+        owned.sort_by(|a, b| a.name.cmp(&b.name));
+        owned.into_iter().map(|c| self.collection_stats(c)).collect()
+    }
+
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection> {
+        let mut collections = self.collections.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let owner = collections.get(&id).ok_or(UrlShortenerErrorType::NotFound)?.owner.clone();
+        if collections.values().any(|c| c.id != id && c.owner == owner && c.name == name) {
+            return Err(collection_name_taken());
+        }
+
+        let collection = collections.get_mut(&id).ok_or(UrlShortenerErrorType::NotFound)?;
+        collection.name = name.to_string();
+        Ok(collection.clone())
+    }
+
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()> {
+        let mut collections = self.collections.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        collections.remove(&id).ok_or(UrlShortenerErrorType::NotFound)?;
+        drop(collections);
+
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        for url in urls.values_mut().filter(|url| url.collection_id == Some(id)) {
+            url.collection_id = None;
+        }
+        Ok(())
+    }
+
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let now = Utc::now();
+        let reached = urls
+            .values_mut()
+            .filter(|url| url.goal_reached_at.is_none() && url.click_goal.is_some_and(|goal| url.visits >= goal))
+            .take(limit.max(0) as usize)
+            .map(|url| {
+                url.goal_reached_at = Some(now);
+                url.clone()
+            })
+            .collect();
+        Ok(reached)
+    }
+
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let url = urls.get_mut(short_code).ok_or(UrlShortenerErrorType::NotFound)?;
+        url.goal_reached_at = None;
+        Ok(())
+    }
+
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        Self::insert_api_key(&mut keys, owner, name, key_hash, prefix)
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        let keys = self.api_keys.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        keys.get(&id)
+            .filter(|record| !record.revoked)
+            .map(|record| record.key.clone())
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        let keys = self.api_keys.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        let mut owned: Vec<StoredApiKey> = keys
+            .values()
+            .filter(|record| !record.revoked && record.key.owner == owner)
+            .map(|record| record.key.clone())
+            .collect();
+        owned.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        Ok(owned)
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let record = keys
+            .get_mut(&id)
+            .filter(|record| !record.revoked)
+            .ok_or(UrlShortenerErrorType::NotFound)?;
+        record.revoked = true;
+        Ok(())
+    }
+
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let old = keys
+            .get(&id)
+            .filter(|record| !record.revoked)
+            .map(|record| record.key.clone())
+            .ok_or(UrlShortenerErrorType::NotFound)?;
+
+        let replacement = Self::insert_api_key(&mut keys, &old.owner, old.name.as_deref(), key_hash, prefix)?;
+        if let Some(record) = keys.get_mut(&id) {
+            record.revoked = true;
+        }
+        Ok(replacement)
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let record = keys
+            .values_mut()
+            .find(|record| !record.revoked && record.key_hash == key_hash)
+            .ok_or(UrlShortenerErrorType::NotFound)?;
+        record.key.last_used_at = Some(Utc::now());
+        Ok(record.key.clone())
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        let keys = self.api_keys.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        keys.values()
+            .find(|record| !record.revoked && record.key_hash == key_hash)
+            .map(|record| record.key.clone())
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()> {
+        let mut sessions = self.sessions.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        if sessions.contains_key(&session.id) {
+            return Err(UrlShortenerErrorType::Conflict("Session already exists".to_string()).into());
+        }
+        sessions.insert(session.id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        let mut sessions = self.sessions.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let session = sessions
+            .get_mut(id)
+            .filter(|session| session.last_seen_at > idle_cutoff)
+            .ok_or(UrlShortenerErrorType::NotFound)?;
+        session.last_seen_at = Utc::now();
+        Ok(session.clone())
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        let sessions = self.sessions.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        sessions
+            .get(id)
+            .filter(|session| session.last_seen_at > idle_cutoff)
             .cloned()
             .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
     }
-} 
\ No newline at end of file
+
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()> {
+        let mut sessions = self.sessions.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        sessions.remove(id);
+        Ok(())
+    }
+
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let mut sessions = self.sessions.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let before = sessions.len();
+        sessions.retain(|_, session| session.last_seen_at > idle_cutoff);
+        Ok((before - sessions.len()) as u64)
+    }
+
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let url = urls.get_mut(short_code).ok_or(UrlShortenerErrorType::NotFound)?;
+        let previous = url.visits;
+        url.visits = match change {
+            VisitChange::Reset => 0,
+            VisitChange::Adjust(delta) => previous.saturating_add(delta).max(0),
+        };
+        Ok(VisitCounts { previous, visits: url.visits })
+    }
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        let mut audit_log = self.audit_log.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let entry = AuditEntry {
+            // Entries may have been purged, so the next ID follows the last one instead of the count
+            id: audit_log.last().map_or(1, |last| last.id + 1),
+            actor: entry.actor,
+            action: entry.action,
+            target: entry.target,
+            details: entry.details,
+            created_at: Utc::now(),
+        };
+        audit_log.push(entry.clone());
+        Ok(entry)
+    }
+
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        let mut webhook_deliveries = self.webhook_deliveries.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let delivery = WebhookDelivery {
+            id: webhook_deliveries.len() as i64 + 1,
+            webhook: delivery.webhook,
+            event: delivery.event,
+            payload: delivery.payload,
+            status_code: delivery.status_code,
+            latency_ms: delivery.latency_ms,
+            response: delivery.response,
+            delivered: delivery.delivered,
+            replay_of: delivery.replay_of,
+            attempted_at: Utc::now(),
+        };
+        webhook_deliveries.push(delivery.clone());
+        Ok(delivery)
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        let webhook_deliveries = self.webhook_deliveries.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        Ok(webhook_deliveries
+            .iter()
+            .rev()
+            .filter(|delivery| delivery.webhook == webhook && before.is_none_or(|before| delivery.id < before))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        let webhook_deliveries = self.webhook_deliveries.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        webhook_deliveries
+            .iter()
+            .find(|delivery| delivery.id == id && delivery.webhook == webhook)
+            .cloned()
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        let audit_log = self.audit_log.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        Ok(audit_log.iter().rev().take(limit.max(0) as usize).cloned().collect())
+    }
+
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let mut audit_log = self.audit_log.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let count = audit_log.len();
+        audit_log.retain(|entry| entry.created_at >= before);
+        Ok((count - audit_log.len()) as u64)
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        let mut idempotency_keys = self.idempotency_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let id = (scope.to_string(), key.to_string());
+        match idempotency_keys.get(&id) {
+            Some(record) if record.created_at >= expired_before => Ok(Some(record.clone())),
+            _ => {
+                let record = IdempotencyRecord {
+                    fingerprint: fingerprint.to_string(),
+                    response: None,
+                    created_at: Utc::now(),
+                };
+                idempotency_keys.insert(id, record);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        let mut idempotency_keys = self.idempotency_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        if let Some(record) = idempotency_keys.get_mut(&(scope.to_string(), key.to_string())) {
+            record.response = Some(response);
+        }
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        let mut idempotency_keys = self.idempotency_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let id = (scope.to_string(), key.to_string());
+        if idempotency_keys.get(&id).is_some_and(|record| record.response.is_none()) {
+            idempotency_keys.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let mut idempotency_keys = self.idempotency_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let count = idempotency_keys.len();
+        idempotency_keys.retain(|_, record| record.created_at >= before);
+        Ok((count - idempotency_keys.len()) as u64)
+    }
+
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        // Both locks are held throughout, so no link to the domain is created halfway
+        let mut blocked_domains = self.blocked_domains.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let blocked = blocked_domains
+            .entry(domain.to_string())
+            .or_insert_with(|| BlockedDomain {
+                domain: domain.to_string(),
+                reason: reason.map(str::to_string),
+                blocked_by: blocked_by.to_string(),
+                created_at: Utc::now(),
+            })
+            .clone();
+
+        let now = Utc::now();
+        let mut disabled = Vec::new();
+        for url in urls.values_mut().filter(|url| url.disabled_at.is_none()) {
+            let host = url::Url::parse(&url.original_url).ok().and_then(|parsed| parsed.host_str().map(str::to_string));
+            if host.is_some_and(|host| in_domain(&host, domain)) {
+                url.disabled_at = Some(now);
+                disabled.push(url.short_url.clone());
+            }
+        }
+        disabled.sort();
+        Ok(DomainBan { blocked, disabled })
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        let blocked_domains = self.blocked_domains.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut domains: Vec<BlockedDomain> = blocked_domains.values().cloned().collect();
+        domains.sort_by(|a, b| a.domain.cmp(&b.domain));
+        Ok(domains)
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        let blocked_domains = self.blocked_domains.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        Ok(blocked_domains.keys().any(|domain| in_domain(host, domain)))
+    }
+
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let now = Utc::now();
+        let mut disabled = Vec::new();
+        for short_code in short_codes {
+            if let Some(url) = urls.get_mut(short_code).filter(|url| url.disabled_at.is_none()) {
+                url.disabled_at = Some(now);
+                disabled.push(short_code.clone());
+            }
+        }
+        Ok(disabled)
+    }
+
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let mut enabled = Vec::new();
+        for short_code in short_codes {
+            if let Some(url) = urls.get_mut(short_code).filter(|url| url.disabled_at.is_some()) {
+                url.disabled_at = None;
+                enabled.push(short_code.clone());
+            }
+        }
+        Ok(enabled)
+    }
+
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        let mut urls = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        if !urls.contains_key(short_code) {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        if urls.contains_key(new_code) || self.code_redirect(new_code)?.is_some() {
+            return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+        }
+        let mut retired_codes = self.retired_codes.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let mut link = urls.remove(short_code).ok_or(UrlShortenerErrorType::NotFound)?;
+        let visits = match stats {
+            StatsOnRegenerate::Keep => 0,
+            StatsOnRegenerate::Fork => std::mem::take(&mut link.visits),
+        };
+        link.short_url = new_code.to_string();
+        urls.insert(link.short_url.clone(), link.clone());
+
+        // Aliases and codes retired earlier follow the link to its new code
+        for record in retired_codes.iter_mut().filter(|record| record.current == short_code) {
+            record.current = new_code.to_string();
+        }
+        let mut aliases = self.aliases.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        for record in aliases.values_mut().filter(|record| record.current == short_code) {
+            record.current = new_code.to_string();
+        }
+        let mut campaign_links = self.campaign_links.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        for (_, code) in campaign_links.iter_mut().filter(|(_, code)| code == short_code) {
+            *code = new_code.to_string();
+        }
+        let mut visit_events = self.visit_events.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        for record in visit_events.iter_mut().filter(|record| record.current == short_code) {
+            record.current = new_code.to_string();
+        }
+        let retired = RetiredCode {
+            short_code: short_code.to_string(),
+            visits,
+            retired_at: Utc::now(),
+            redirects_until,
+        };
+        retired_codes.push(RetiredRecord { code: retired.clone(), current: new_code.to_string() });
+        Ok(RegeneratedLink { link, retired })
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        let retired_codes = self.retired_codes.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        Ok(retired_codes
+            .iter()
+            .rev()
+            .filter(|record| record.current == short_code)
+            .map(|record| record.code.clone())
+            .collect())
+    }
+
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        if !urls.contains_key(short_code) {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        if urls.contains_key(alias) || self.code_redirect(alias)?.is_some() {
+            return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+        }
+        let mut aliases = self.aliases.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let added = LinkAlias { short_code: alias.to_string(), created_at: Utc::now() };
+        aliases.insert(alias.to_string(), AliasRecord { alias: added.clone(), current: short_code.to_string() });
+        Ok(added)
+    }
+
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()> {
+        let mut aliases = self.aliases.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        match aliases.get(alias) {
+            Some(record) if record.current == short_code => {
+                aliases.remove(alias);
+                Ok(())
+            }
+            _ => Err(UrlShortenerErrorType::NotFound.into()),
+        }
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        let aliases = self.aliases.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut listed: Vec<LinkAlias> = aliases
+            .values()
+            .filter(|record| record.current == short_code)
+            .map(|record| record.alias.clone())
+            .collect();
+        listed.sort_by(|a, b| (a.created_at, &a.short_code).cmp(&(b.created_at, &b.short_code)));
+        Ok(listed)
+    }
+
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let mut stored = self.urls.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let mut inserted = 0;
+        for url in urls {
+            if !stored.contains_key(&url.short_url) {
+                stored.insert(url.short_url.clone(), url);
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        Ok(urls.values().filter(|url| owner.is_none() || url.owner.as_deref() == owner).count() as u64)
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut unvisited: Vec<&ShortenedUrl> = urls
+            .values()
+            .filter(|url| url.visits == 0 && (owner.is_none() || url.owner.as_deref() == owner))
+            .collect();
+        unvisited.sort_by_key(|url| (url.created_at, url.id));
+        Ok(unvisited.into_iter().take(limit.max(0) as usize).map(|url| url.short_url.clone()).collect())
+    }
+
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        // Each lock is released before the next is taken, as elsewhere in this storage
+        let deleted: HashSet<String> = {
+            let mut urls = self.urls.write().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire write lock".to_string(),
+                ))
+            })?;
+            let mut deleted = HashSet::new();
+            for code in short_codes {
+                if urls.get(code.as_str()).is_some_and(|url| url.visits == 0) {
+                    urls.remove(code.as_str());
+                    deleted.insert(code.clone());
+                }
+            }
+            deleted
+        };
+        {
+            let mut aliases = self.aliases.write().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire write lock".to_string(),
+                ))
+            })?;
+            aliases.retain(|_, record| !deleted.contains(&record.current));
+        }
+        {
+            let mut retired_codes = self.retired_codes.write().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire write lock".to_string(),
+                ))
+            })?;
+            retired_codes.retain(|record| !deleted.contains(&record.current));
+        }
+        {
+            let mut campaign_links = self.campaign_links.write().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire write lock".to_string(),
+                ))
+            })?;
+            campaign_links.retain(|(_, code)| !deleted.contains(code));
+        }
+        Ok(short_codes.iter().filter(|code| deleted.contains(*code)).cloned().collect())
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut page: Vec<ShortenedUrl> = urls
+            .values()
+            .filter(|url| after.is_none_or(|after| url.short_url.as_str() > after))
+            .cloned()
+            .collect();
+        page.sort_by(|a, b| a.short_url.cmp(&b.short_url));
+        page.truncate(limit.max(0) as usize);
+        Ok(page)
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut top: Vec<ShortenedUrl> = urls.values().filter(|url| url.disabled_at.is_none()).cloned().collect();
+        top.sort_by(|a, b| b.visits.cmp(&a.visits).then_with(|| a.short_url.cmp(&b.short_url)));
+        top.truncate(limit.max(0) as usize);
+        Ok(top)
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let query = query.map(str::to_lowercase);
+        let contains = |text: &str| query.as_deref().is_none_or(|query| text.to_lowercase().contains(query));
+        let mut found: Vec<ShortenedUrl> = urls
+            .values()
+            .filter(|url| url.disabled_at.is_none() && url.short_url.starts_with(prefix))
+            .filter(|url| contains(&url.short_url) || url.title.as_deref().is_some_and(contains))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| b.visits.cmp(&a.visits).then_with(|| a.short_url.cmp(&b.short_url)));
+        found.truncate(limit.max(0) as usize);
+        Ok(found)
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        let collections = self.collections.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut all: Vec<Collection> = collections.values().cloned().collect();
+        all.sort_by_key(|collection| collection.id);
+        Ok(all)
+    }
+
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        let mut collections = self.collections.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        if let Some(existing) = collections
+            .values()
+            .find(|c| c.owner == collection.owner && c.name == collection.name)
+        {
+            return Ok((existing.clone(), false));
+        }
+
+        let restored = Collection {
+            id: collections.keys().max().copied().unwrap_or(0) + 1,
+            ..collection.clone()
+        };
+        collections.insert(restored.id, restored.clone());
+        Ok((restored, true))
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        let keys = self.api_keys.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut active: Vec<ExportedApiKey> = keys
+            .values()
+            .filter(|record| !record.revoked)
+            .map(|record| ExportedApiKey {
+                id: record.key.id,
+                owner: record.key.owner.clone(),
+                name: record.key.name.clone(),
+                key_hash: record.key_hash.clone(),
+                prefix: record.key.prefix.clone(),
+                created_at: record.key.created_at,
+                last_used_at: record.key.last_used_at,
+            })
+            .collect();
+        active.sort_by_key(|key| key.id);
+        Ok(active)
+    }
+
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        if keys.values().any(|record| record.key_hash == key.key_hash) {
+            return Ok(false);
+        }
+
+        let mut stored = Self::insert_api_key(&mut keys, &key.owner, key.name.as_deref(), &key.key_hash, &key.prefix)?;
+        stored.created_at = key.created_at;
+        stored.last_used_at = key.last_used_at;
+        if let Some(record) = keys.get_mut(&stored.id) {
+            record.key = stored;
+        }
+        Ok(true)
+    }
+
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool> {
+        let mut collections = self.collections.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let taken = collections.contains_key(&collection.id)
+            || collections.values().any(|c| c.owner == collection.owner && c.name == collection.name);
+        if taken {
+            return Ok(false);
+        }
+        collections.insert(collection.id, collection.clone());
+        Ok(true)
+    }
+
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        let mut keys = self.api_keys.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        if keys.contains_key(&key.id) || keys.values().any(|record| record.key_hash == key.key_hash) {
+            return Ok(false);
+        }
+        let stored = StoredApiKey {
+            id: key.id,
+            owner: key.owner.clone(),
+            name: key.name.clone(),
+            prefix: key.prefix.clone(),
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+        };
+        keys.insert(key.id, ApiKeyRecord { key: stored, key_hash: key.key_hash.clone(), revoked: false });
+        Ok(true)
+    }
+
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        let mut visit_events = self.visit_events.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let mut stored = 0;
+        for event in events.iter().filter(|event| urls.contains_key(&event.short_code)) {
+            visit_events.push(EventRecord { event: event.clone(), current: event.short_code.clone() });
+            stored += 1;
+        }
+        Ok(stored)
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        // Events are numbered by their position, as they are never removed
+        let mut exported: Vec<RecordedVisit> = visit_events
+            .iter()
+            .zip(1..)
+            .filter(|(record, _)| record.event.visited_at >= from && record.event.visited_at < to)
+            .map(|(record, id)| RecordedVisit {
+                id,
+                short_code: record.current.clone(),
+                visited_at: record.event.visited_at,
+                visitor: record.event.visitor.clone(),
+                source: record.event.source.clone(),
+                sample_rate: record.event.sample_rate,
+            })
+            .filter(|visit| after.is_none_or(|after| (visit.visited_at, visit.id) > after))
+            .collect();
+        exported.sort_by_key(|visit| (visit.visited_at, visit.id));
+        exported.truncate(limit.max(0) as usize);
+        Ok(exported)
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut timeline = VisitTimeline::default();
+        let visits = visit_events
+            .iter()
+            .filter(|record| record.current == short_code && record.event.visited_at <= now)
+            .map(|record| (record.event.visited_at, i64::from(record.event.sample_rate)));
+        for (visited_at, weight) in visits {
+            timeline.first_visited_at = Some(timeline.first_visited_at.map_or(visited_at, |first| first.min(visited_at)));
+            timeline.last_visited_at = Some(timeline.last_visited_at.map_or(visited_at, |last| last.max(visited_at)));
+            if visited_at > now - Duration::days(1) {
+                timeline.visits_last_24h += weight;
+            }
+            if visited_at > now - Duration::days(7) {
+                timeline.visits_last_7d += weight;
+            }
+        }
+        Ok(timeline)
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut heatmap = VisitHeatmap::default();
+        let visits = visit_events
+            .iter()
+            .filter(|record| record.current == short_code && record.event.visited_at >= since)
+            .map(|record| (record.event.visited_at, i64::from(record.event.sample_rate)));
+        for (visited_at, weight) in visits {
+            heatmap.visits[visited_at.weekday().num_days_from_monday() as usize][visited_at.hour() as usize] += weight;
+        }
+        Ok(heatmap)
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut days: BTreeMap<(&str, NaiveDate), i64> = BTreeMap::new();
+        let events = visit_events
+            .iter()
+            .filter(|record| record.event.visited_at >= since && short_codes.contains(&record.current));
+        for record in events {
+            *days.entry((record.current.as_str(), record.event.visited_at.date_naive())).or_default() += i64::from(record.event.sample_rate);
+        }
+        Ok(days
+            .into_iter()
+            .map(|((short_code, date), visits)| LinkDailyVisits { short_code: short_code.to_string(), date, visits })
+            .collect())
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut sources: HashMap<Option<&str>, i64> = HashMap::new();
+        let events = visit_events
+            .iter()
+            .filter(|record| record.current == short_code && record.event.visited_at >= since);
+        for record in events {
+            *sources.entry(record.event.source.as_deref()).or_default() += i64::from(record.event.sample_rate);
+        }
+        let mut sources: Vec<SourceVisits> = sources
+            .into_iter()
+            .map(|(source, visits)| SourceVisits { source: source.map(str::to_string), visits })
+            .collect();
+        sources.sort_by(|a, b| b.visits.cmp(&a.visits).then_with(|| (a.source.is_none(), &a.source).cmp(&(b.source.is_none(), &b.source))));
+        Ok(sources)
+    }
+
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        let mut campaigns = self.campaigns.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        if campaigns.values().any(|c| c.owner == owner && c.name == name) {
+            return Err(campaign_name_taken());
+        }
+
+        let campaign = Campaign {
+            id: campaigns.keys().max().copied().unwrap_or(0) + 1,
+            owner: owner.to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+        };
+        campaigns.insert(campaign.id, campaign.clone());
+        Ok(campaign)
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        let campaigns = self.campaigns.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        campaigns.get(&id).cloned().ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        let campaigns = self.campaigns.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut owned: Vec<Campaign> = campaigns.values().filter(|c| c.owner == owner).cloned().collect();
+        owned.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(owned)
+    }
+
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()> {
+        let mut campaigns = self.campaigns.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        campaigns.remove(&id).ok_or(UrlShortenerErrorType::NotFound)?;
+        drop(campaigns);
+
+        let mut campaign_links = self.campaign_links.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        campaign_links.retain(|(campaign_id, _)| *campaign_id != id);
+        Ok(())
+    }
+
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        let campaigns = self.campaigns.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        let urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        if !campaigns.contains_key(&id) || !urls.contains_key(short_code) {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        let mut campaign_links = self.campaign_links.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let link = (id, short_code.to_string());
+        if !campaign_links.contains(&link) {
+            campaign_links.push(link);
+        }
+        Ok(())
+    }
+
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        let mut campaign_links = self.campaign_links.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let before = campaign_links.len();
+        campaign_links.retain(|(campaign_id, code)| !(*campaign_id == id && code == short_code));
+        if campaign_links.len() == before {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        let campaign = self.get_campaign(id).await?;
+        let codes: HashSet<String> = {
+            let campaign_links = self.campaign_links.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            campaign_links.iter().filter(|(campaign_id, _)| *campaign_id == id).map(|(_, code)| code.clone()).collect()
+        };
+        let visits = {
+            let urls = self.urls.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            codes.iter().filter_map(|code| urls.get(code)).map(|url| url.visits).sum()
+        };
+        let visit_events = self.visit_events.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+
+        let mut days: BTreeMap<NaiveDate, (i64, HashSet<&str>)> = BTreeMap::new();
+        let mut visitors = HashSet::new();
+        let mut period_visits = 0;
+        let events = visit_events
+            .iter()
+            .filter(|record| record.event.visited_at >= since && codes.contains(&record.current))
+            .map(|record| &record.event);
+        for event in events {
+            let day = days.entry(event.visited_at.date_naive()).or_default();
+            day.0 += i64::from(event.sample_rate);
+            period_visits += i64::from(event.sample_rate);
+            if let Some(visitor) = event.visitor.as_deref() {
+                day.1.insert(visitor);
+                visitors.insert(visitor);
+            }
+        }
+
+        Ok(CampaignStats {
+            campaign,
+            links: codes.len() as i64,
+            visits,
+            period_visits,
+            uniques: visitors.len() as i64,
+            daily: days
+                .into_iter()
+                .map(|(date, (visits, visitors))| DailyVisits { date, visits, uniques: visitors.len() as i64 })
+                .collect(),
+        })
+    }
+
+    fn backend(&self) -> String {
+        "memory".to_string()
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        let _urls = self.urls.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        Ok(())
+    }
+
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool> {
+        let mut campaigns = self.campaigns.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+
+        let taken = campaigns.contains_key(&campaign.id)
+            || campaigns.values().any(|c| c.owner == campaign.owner && c.name == campaign.name);
+        if taken {
+            return Ok(false);
+        }
+        campaigns.insert(campaign.id, campaign.clone());
+        Ok(true)
+    }
+
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64> {
+        let mut usage: HashMap<String, UsageRecord> = HashMap::new();
+        {
+            let urls = self.urls.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            for url in urls.values().filter(|url| url.created_at.date_naive() <= day) {
+                let Some(owner) = &url.owner else { continue };
+                let record = usage.entry(owner.clone()).or_insert_with(|| UsageRecord {
+                    day,
+                    owner: owner.clone(),
+                    creations: 0,
+                    redirects: 0,
+                    stored_links: 0,
+                });
+                record.stored_links += 1;
+                if url.created_at.date_naive() == day {
+                    record.creations += 1;
+                }
+            }
+
+            let visit_events = self.visit_events.read().map_err(|_| {
+                UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                    "Failed to acquire read lock".to_string(),
+                ))
+            })?;
+            for record in visit_events.iter().filter(|record| record.event.visited_at.date_naive() == day) {
+                let owner = urls.get(&record.current).and_then(|url| url.owner.as_ref());
+                if let Some(usage) = owner.and_then(|owner| usage.get_mut(owner)) {
+                    usage.redirects += i64::from(record.event.sample_rate);
+                }
+            }
+        }
+
+        let mut usage_records = self.usage_records.write().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire write lock".to_string(),
+            ))
+        })?;
+        let owners = usage.len() as u64;
+        for (owner, record) in usage {
+            usage_records.insert((owner, day), record);
+        }
+        Ok(owners)
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        let usage_records = self.usage_records.read().map_err(|_| {
+            UrlShortenerError::from(UrlShortenerErrorType::InternalError(
+                "Failed to acquire read lock".to_string(),
+            ))
+        })?;
+        Ok(usage_records
+            .values()
+            .filter(|record| record.day >= from && record.day < to)
+            .cloned()
+            .collect())
+    }
+}
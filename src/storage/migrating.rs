@@ -0,0 +1,634 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use tracing::{debug, info, warn};
+
+use super::{StatementCacheStats, Storage, StorageRef};
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DomainBan, ExportedApiKey,
+    IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink, RetiredCode, Session,
+    ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline, WebhookDelivery,
+};
+
+/// Links copied per round trip during backfill
+const BACKFILL_BATCH_SIZE: i64 = 1000;
+
+/// Storage that moves an instance from one backend to another while it keeps serving.
+///
+/// The old backend stays the source of truth until cutover: every write goes to it first,
+/// and its result (including any ID it assigns) is what callers see. The write is then
+/// mirrored to the new backend under the same IDs; mirroring failures are logged rather
+/// than returned, and a later [`backfill`](Self::backfill) repairs them. Lookups of a single
+/// record try the new backend first and fall back to the old one for records the backfill
+/// has not reached, so the new backend carries real read traffic before cutover. Listings
+/// and exports read the old backend, which is the only complete one until backfill finishes.
+pub struct MigratingStorage {
+    old: StorageRef,
+    new: StorageRef,
+}
+
+/// Records copied by a backfill
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackfillSummary {
+    pub collections: u64,
+    pub api_keys: u64,
+    pub links: u64,
+    /// Records the new backend already had
+    pub skipped: u64,
+}
+
+impl MigratingStorage {
+    pub fn new(old: StorageRef, new: StorageRef) -> Self {
+        Self { old, new }
+    }
+
+    /// Copies collections, active API keys and links the new backend does not have yet.
+    ///
+    /// Records already there are left alone, so this can run while the instance is serving
+    /// and be repeated right before cutover. Visits counted on a link while its page is
+    /// being copied may be missed by the new backend.
+    pub async fn backfill(&self) -> UrlShortenerResult<BackfillSummary> {
+        let mut summary = BackfillSummary::default();
+
+        for collection in self.old.export_collections().await? {
+            tally(&mut summary.collections, &mut summary.skipped, self.new.copy_collection(&collection).await?);
+        }
+        for key in self.old.export_api_keys().await? {
+            tally(&mut summary.api_keys, &mut summary.skipped, self.new.copy_api_key(&key).await?);
+        }
+
+        let mut after: Option<String> = None;
+        loop {
+            let page = self.old.export_urls(after.as_deref(), BACKFILL_BATCH_SIZE).await?;
+            let Some(last) = page.last() else { break };
+            after = Some(last.short_url.clone());
+
+            let total = page.len() as u64;
+            let inserted = self.new.import_urls(page).await?;
+            summary.links += inserted;
+            summary.skipped += total - inserted;
+            debug!(after = ?after, inserted, "Backfilled page of links");
+        }
+
+        info!(
+            collections = summary.collections,
+            api_keys = summary.api_keys,
+            links = summary.links,
+            skipped = summary.skipped,
+            "Backfill finished"
+        );
+        Ok(summary)
+    }
+}
+
+fn tally(copied: &mut u64, skipped: &mut u64, inserted: bool) {
+    if inserted {
+        *copied += 1;
+    } else {
+        *skipped += 1;
+    }
+}
+
+fn is_not_found(e: &UrlShortenerError) -> bool {
+    matches!(e.error_type, UrlShortenerErrorType::NotFound)
+}
+
+/// Logs a write the new backend did not take; the old backend has it, so backfill can repair it
+fn mirrored<T>(operation: &str, result: UrlShortenerResult<T>) {
+    match result {
+        Ok(_) => {}
+        // The record has not been backfilled yet; backfill will copy its current state
+        Err(e) if is_not_found(&e) => debug!(operation, "Skipped mirroring write for record missing from new storage"),
+        Err(e) => warn!(operation, error = %e, "Failed to mirror write to new storage"),
+    }
+}
+
+#[async_trait]
+impl Storage for MigratingStorage {
+    async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        let saved = self.old.save_url(url).await?;
+        // Importing keeps the creation date the old backend assigned
+        mirrored("save_url", self.new.import_urls(vec![saved.clone()]).await);
+        Ok(saved)
+    }
+
+    async fn get_url(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        match self.new.get_url(short_code).await {
+            Err(e) if is_not_found(&e) => self.old.get_url(short_code).await,
+            Err(e) => Err(e),
+            Ok(url) => {
+                mirrored("get_url", self.old.record_visits(short_code, 1).await);
+                Ok(url)
+            }
+        }
+    }
+
+    async fn get_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        match self.new.get_stats(short_code).await {
+            Err(e) if is_not_found(&e) => self.old.get_stats(short_code).await,
+            result => result,
+        }
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        let mut found = self.new.get_stats_many(short_codes).await?;
+        let missing: Vec<String> = short_codes
+            .iter()
+            .filter(|code| !found.iter().any(|(found, _)| found == *code))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            found.extend(self.old.get_stats_many(&missing).await?);
+        }
+        Ok(found)
+    }
+
+    async fn bulk_insert(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let inserted = self.old.bulk_insert(urls.clone()).await?;
+        mirrored("bulk_insert", self.new.bulk_insert(urls).await);
+        Ok(inserted)
+    }
+
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()> {
+        self.old.record_visits(short_code, count).await?;
+        mirrored("record_visits", self.new.record_visits(short_code, count).await);
+        Ok(())
+    }
+
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        let found = self.old.record_visit_batch(visits).await?;
+        mirrored("record_visit_batch", self.new.record_visit_batch(visits).await);
+        Ok(found)
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.old.list_urls_by_owner(owner, collection_id, after, limit).await
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.old.list_urls(owner, sort, filter, after, limit).await
+    }
+
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        let updated = self.old.update_metadata(short_code, update.clone()).await?;
+        // The old database has checked the version; the copy's may lag behind it
+        let update = MetadataUpdate { expected_version: None, ..update };
+        mirrored("update_metadata", self.new.update_metadata(short_code, update).await);
+        Ok(updated)
+    }
+
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        let transferred = self.old.transfer_url(short_code, from, to).await?;
+        mirrored("transfer_url", self.new.transfer_url(short_code, from, to).await);
+        Ok(transferred)
+    }
+
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        let collection = self.old.create_collection(owner, name).await?;
+        mirrored("create_collection", self.new.copy_collection(&collection).await);
+        Ok(collection)
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        // Aggregates over the collection's links are only complete on the old backend
+        self.old.get_collection(id).await
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.old.list_collections(owner).await
+    }
+
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection> {
+        let collection = self.old.rename_collection(id, name).await?;
+        mirrored("rename_collection", self.new.rename_collection(id, name).await);
+        Ok(collection)
+    }
+
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()> {
+        self.old.delete_collection(id).await?;
+        mirrored("delete_collection", self.new.delete_collection(id).await);
+        Ok(())
+    }
+
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        // Claims are not mirrored, so goals reached during the migration may be notified again after cutover
+        self.old.claim_reached_goals(limit).await
+    }
+
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()> {
+        self.old.release_goal(short_code).await?;
+        mirrored("release_goal", self.new.release_goal(short_code).await);
+        Ok(())
+    }
+
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        let key = self.old.create_api_key(owner, name, key_hash, prefix).await?;
+        mirrored("create_api_key", self.new.copy_api_key(&exported(&key, key_hash)).await);
+        Ok(key)
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        match self.new.get_api_key(id).await {
+            Err(e) if is_not_found(&e) => self.old.get_api_key(id).await,
+            result => result,
+        }
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.old.list_api_keys(owner).await
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()> {
+        self.old.revoke_api_key(id).await?;
+        mirrored("revoke_api_key", self.new.revoke_api_key(id).await);
+        Ok(())
+    }
+
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        let replacement = self.old.rotate_api_key(id, key_hash, prefix).await?;
+        // Rotating on the new backend would give the replacement a different ID there
+        mirrored("rotate_api_key", self.new.revoke_api_key(id).await);
+        mirrored("rotate_api_key", self.new.copy_api_key(&exported(&replacement, key_hash)).await);
+        Ok(replacement)
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        match self.new.authenticate_api_key(key_hash).await {
+            Err(e) if is_not_found(&e) => self.old.authenticate_api_key(key_hash).await,
+            result => result,
+        }
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        match self.new.find_api_key(key_hash).await {
+            Err(e) if is_not_found(&e) => self.old.find_api_key(key_hash).await,
+            result => result,
+        }
+    }
+
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()> {
+        self.old.create_session(session).await?;
+        mirrored("create_session", self.new.create_session(session).await);
+        Ok(())
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        match self.new.touch_session(id, idle_cutoff).await {
+            Err(e) if is_not_found(&e) => self.old.touch_session(id, idle_cutoff).await,
+            Err(e) => Err(e),
+            Ok(session) => {
+                mirrored("touch_session", self.old.touch_session(id, idle_cutoff).await);
+                Ok(session)
+            }
+        }
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        match self.new.find_session(id, idle_cutoff).await {
+            Err(e) if is_not_found(&e) => self.old.find_session(id, idle_cutoff).await,
+            result => result,
+        }
+    }
+
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()> {
+        self.old.delete_session(id).await?;
+        mirrored("delete_session", self.new.delete_session(id).await);
+        Ok(())
+    }
+
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let purged = self.old.purge_idle_sessions(idle_cutoff).await?;
+        mirrored("purge_idle_sessions", self.new.purge_idle_sessions(idle_cutoff).await);
+        Ok(purged)
+    }
+
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        let counts = self.old.change_visits(short_code, change).await?;
+        mirrored("change_visits", self.new.change_visits(short_code, change).await);
+        Ok(counts)
+    }
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        let recorded = self.old.record_audit(entry.clone()).await?;
+        mirrored("record_audit", self.new.record_audit(entry).await);
+        Ok(recorded)
+    }
+
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let inserted = self.old.import_urls(urls.clone()).await?;
+        mirrored("import_urls", self.new.import_urls(urls).await);
+        Ok(inserted)
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        self.old.count_urls(owner).await
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        self.old.oldest_unvisited_urls(owner, limit).await
+    }
+
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        let deleted = self.old.delete_unvisited_urls(short_codes).await?;
+        // Only what old storage deleted, so a link visited there in the meantime stays in both
+        mirrored("delete_unvisited_urls", self.new.delete_unvisited_urls(&deleted).await);
+        Ok(deleted)
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        self.old.list_audit_entries(limit).await
+    }
+
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let purged = self.old.purge_audit_entries(before).await?;
+        mirrored("purge_audit_entries", self.new.purge_audit_entries(before).await);
+        Ok(purged)
+    }
+
+    // Delivery logs are a debugging aid; they are not mirrored and start over after cutover
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        self.old.record_webhook_delivery(delivery).await
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.old.list_webhook_deliveries(webhook, before, limit).await
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        self.old.get_webhook_delivery(webhook, id).await
+    }
+
+    // Idempotency keys only matter for a day or so; they are not mirrored and start over after
+    // cutover
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        self.old.reserve_idempotency_key(scope, key, fingerprint, expired_before).await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        self.old.complete_idempotency_key(scope, key, response).await
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        self.old.release_idempotency_key(scope, key).await
+    }
+
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.old.purge_idempotency_keys(before).await
+    }
+
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        let ban = self.old.ban_domain(domain, reason, blocked_by).await?;
+        mirrored("ban_domain", self.new.ban_domain(domain, reason, blocked_by).await);
+        Ok(ban)
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.old.list_blocked_domains().await
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        self.old.is_domain_blocked(host).await
+    }
+
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        let disabled = self.old.disable_urls(short_codes).await?;
+        mirrored("disable_urls", self.new.disable_urls(short_codes).await);
+        Ok(disabled)
+    }
+
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        let enabled = self.old.enable_urls(short_codes).await?;
+        mirrored("enable_urls", self.new.enable_urls(short_codes).await);
+        Ok(enabled)
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.old.export_urls(after, limit).await
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.old.top_visited_urls(limit).await
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.old.search_urls(prefix, query, limit).await
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        self.old.export_collections().await
+    }
+
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        let (restored, created) = self.old.restore_collection(collection).await?;
+        if created {
+            mirrored("restore_collection", self.new.copy_collection(&restored).await);
+        }
+        Ok((restored, created))
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        self.old.export_api_keys().await
+    }
+
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        // The ID the old backend assigns is not returned, so backfill copies the key later
+        self.old.restore_api_key(key).await
+    }
+
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool> {
+        let copied = self.old.copy_collection(collection).await?;
+        mirrored("copy_collection", self.new.copy_collection(collection).await);
+        Ok(copied)
+    }
+
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        let copied = self.old.copy_api_key(key).await?;
+        mirrored("copy_api_key", self.new.copy_api_key(key).await);
+        Ok(copied)
+    }
+
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        let regenerated = self.old.regenerate_code(short_code, new_code, stats, redirects_until).await?;
+        mirrored("regenerate_code", self.new.regenerate_code(short_code, new_code, stats, redirects_until).await);
+        Ok(regenerated)
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.old.list_retired_codes(short_code).await
+    }
+
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        let added = self.old.add_alias(short_code, alias).await?;
+        mirrored("add_alias", self.new.add_alias(short_code, alias).await);
+        Ok(added)
+    }
+
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()> {
+        self.old.remove_alias(short_code, alias).await?;
+        mirrored("remove_alias", self.new.remove_alias(short_code, alias).await);
+        Ok(())
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.old.list_aliases(short_code).await
+    }
+
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        let stored = self.old.record_visit_events(events).await?;
+        mirrored("record_visit_events", self.new.record_visit_events(events).await);
+        Ok(stored)
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        // Visit events from before the migration are only on the old backend
+        self.old.export_visit_events(from, to, after, limit).await
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        // Visit events from before the migration are only on the old backend
+        self.old.visit_timeline(short_code, now).await
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        // Visit events from before the migration are only on the old backend
+        self.old.visit_heatmap(short_code, since).await
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        // Visit events from before the migration are only on the old backend
+        self.old.daily_link_visits(short_codes, since).await
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        // Visit events from before the migration are only on the old backend
+        self.old.visit_sources(short_code, since).await
+    }
+
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        let campaign = self.old.create_campaign(owner, name).await?;
+        mirrored("create_campaign", self.new.copy_campaign(&campaign).await);
+        Ok(campaign)
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        self.old.get_campaign(id).await
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.old.list_campaigns(owner).await
+    }
+
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()> {
+        self.old.delete_campaign(id).await?;
+        mirrored("delete_campaign", self.new.delete_campaign(id).await);
+        Ok(())
+    }
+
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.old.add_campaign_link(id, short_code).await?;
+        mirrored("add_campaign_link", self.new.add_campaign_link(id, short_code).await);
+        Ok(())
+    }
+
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.old.remove_campaign_link(id, short_code).await?;
+        mirrored("remove_campaign_link", self.new.remove_campaign_link(id, short_code).await);
+        Ok(())
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        // Visit events from before the migration are only on the old backend
+        self.old.campaign_stats(id, since).await
+    }
+
+    // Usage is rolled up from visit events, which are only complete on the old backend; records
+    // are not copied, so past months have to be exported before cutover
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64> {
+        self.old.roll_up_usage(day).await
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.old.list_usage(from, to).await
+    }
+
+    fn backend(&self) -> String {
+        format!("migrating({} -> {})", self.old.backend(), self.new.backend())
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        // Only the old database serves reads; the new one failing is logged as for writes
+        self.old.ping().await?;
+        mirrored("ping", self.new.ping().await);
+        Ok(())
+    }
+
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool> {
+        let copied = self.old.copy_campaign(campaign).await?;
+        mirrored("copy_campaign", self.new.copy_campaign(campaign).await);
+        Ok(copied)
+    }
+
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        // Redirect lookups are served by the new backend
+        self.new.statement_cache_stats()
+    }
+}
+
+fn exported(key: &StoredApiKey, key_hash: &str) -> ExportedApiKey {
+    ExportedApiKey {
+        id: key.id,
+        owner: key.owner.clone(),
+        name: key.name.clone(),
+        key_hash: key_hash.to_string(),
+        prefix: key.prefix.clone(),
+        created_at: key.created_at,
+        last_used_at: key.last_used_at,
+    }
+}
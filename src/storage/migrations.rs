@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use super::postgres::PostgresStorage;
+use super::StorageConfig;
+
+/// Migrations embedded from the `migrations/` directory at compile time
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// State of a single migration relative to the database
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationState {
+    /// Applied and matching the embedded SQL
+    Applied,
+    /// Not yet applied
+    Pending,
+    /// Applied, but the embedded SQL changed since
+    ChecksumMismatch,
+}
+
+/// Status report entry for one migration
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub reversible: bool,
+    pub state: MigrationState,
+}
+
+/// How the database schema compares to the migrations this binary was built with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaCompatibility {
+    /// Exactly the embedded migrations are applied
+    Current,
+    /// Migrations this binary needs are not applied yet, so its queries would fail
+    Behind(Vec<i64>),
+    /// Migrations this binary does not know are applied, or applied ones differ from the
+    /// embedded SQL, as when a newer release migrated the database during a rollout
+    Ahead(Vec<i64>),
+}
+
+/// What the server does when the database schema is ahead of it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaMismatch {
+    /// Refuse to start
+    #[default]
+    Refuse,
+    /// Start, but reject every write and stop counting visits
+    ReadOnly,
+}
+
+impl FromStr for SchemaMismatch {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "refuse" => Ok(Self::Refuse),
+            "read-only" | "readonly" => Ok(Self::ReadOnly),
+            other => Err(format!("unknown schema mismatch policy '{}'", other)),
+        }
+    }
+}
+
+/// Explicit migration management for the PostgreSQL schema
+pub struct Migrations {
+    pool: PgPool,
+}
+
+impl Migrations {
+    /// Connects to the database without applying any migrations
+    pub async fn connect(config: &StorageConfig) -> UrlShortenerResult<Self> {
+        Ok(Self {
+            pool: PostgresStorage::connect_pool(config).await?,
+        })
+    }
+
+    pub(crate) fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Applies all pending migrations
+    pub async fn up(&self) -> UrlShortenerResult<()> {
+        MIGRATOR.run(&self.pool).await.map_err(Self::handle_error)?;
+        info!("Database migrations are up to date");
+        Ok(())
+    }
+
+    /// Reverts the most recently applied migration, returning its version.
+    ///
+    /// Returns `None` when no migrations are applied.
+    pub async fn down(&self) -> UrlShortenerResult<Option<i64>> {
+        let mut applied: Vec<i64> = self.applied().await?.into_keys().collect();
+        applied.sort_unstable();
+
+        let Some(latest) = applied.pop() else {
+            return Ok(None);
+        };
+
+        let reversible = MIGRATOR
+            .iter()
+            .any(|m| m.version == latest && m.migration_type.is_down_migration());
+        if !reversible {
+            return Err(UrlShortenerErrorType::InvalidInput(format!(
+                "Migration {} is not reversible",
+                latest
+            ))
+            .into());
+        }
+
+        let target = applied.last().copied().unwrap_or(0);
+        MIGRATOR
+            .undo(&self.pool, target)
+            .await
+            .map_err(Self::handle_error)?;
+        info!(version = latest, "Reverted database migration");
+        Ok(Some(latest))
+    }
+
+    /// Reports every embedded migration and whether it has been applied
+    pub async fn status(&self) -> UrlShortenerResult<Vec<MigrationStatus>> {
+        let applied = self.applied().await?;
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration())
+            .map(|m| MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                reversible: m.migration_type.is_reversible(),
+                state: match applied.get(&m.version) {
+                    Some(checksum) if *checksum == *m.checksum => MigrationState::Applied,
+                    Some(_) => MigrationState::ChecksumMismatch,
+                    None => MigrationState::Pending,
+                },
+            })
+            .collect())
+    }
+
+    /// Compares the applied migrations with the embedded ones. Missing migrations win over
+    /// unknown ones, as a binary cannot run against a schema lacking what it needs.
+    pub async fn compatibility(&self) -> UrlShortenerResult<SchemaCompatibility> {
+        let mut applied = self.applied_without_setup().await?;
+
+        let mut missing = Vec::new();
+        let mut unknown = Vec::new();
+        for migration in MIGRATOR.iter().filter(|m| m.migration_type.is_up_migration()) {
+            match applied.remove(&migration.version) {
+                Some(checksum) if checksum == *migration.checksum => {}
+                Some(_) => unknown.push(migration.version),
+                None => missing.push(migration.version),
+            }
+        }
+        unknown.extend(applied.into_keys());
+        unknown.sort_unstable();
+
+        Ok(if !missing.is_empty() {
+            SchemaCompatibility::Behind(missing)
+        } else if !unknown.is_empty() {
+            SchemaCompatibility::Ahead(unknown)
+        } else {
+            SchemaCompatibility::Current
+        })
+    }
+
+    /// Loads applied migration versions and their checksums
+    async fn applied(&self) -> UrlShortenerResult<HashMap<i64, Vec<u8>>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await.map_err(Self::handle_error)?;
+        let applied = conn
+            .list_applied_migrations()
+            .await
+            .map_err(Self::handle_error)?;
+
+        Ok(applied
+            .into_iter()
+            .map(|m| (m.version, m.checksum.into_owned()))
+            .collect())
+    }
+
+    /// Same as [`applied`](Self::applied), but without creating the bookkeeping table, so a
+    /// read-only database can be checked too
+    async fn applied_without_setup(&self) -> UrlShortenerResult<HashMap<i64, Vec<u8>>> {
+        let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        if !exists {
+            return Ok(HashMap::new());
+        }
+
+        let applied: Vec<(i64, Vec<u8>)> = sqlx::query_as("SELECT version, checksum FROM _sqlx_migrations WHERE success")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(applied.into_iter().collect())
+    }
+
+    fn handle_error(error: MigrateError) -> UrlShortenerError {
+        UrlShortenerError::with_source(
+            UrlShortenerErrorType::DatabaseError(error.to_string()),
+            error,
+        )
+    }
+}
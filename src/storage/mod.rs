@@ -1,25 +1,391 @@
+mod encrypted;
 mod memory;
+mod migrating;
+mod migrations;
 mod postgres;
+mod read_only;
+mod retry;
+mod timed;
 
+pub use encrypted::{EncryptedStorage, UrlCipher};
 pub use memory::MemoryStorage;
-pub use postgres::PostgresStorage;
+pub use migrating::{BackfillSummary, MigratingStorage};
+pub use migrations::{MigrationState, Migrations, SchemaCompatibility, SchemaMismatch};
+pub use postgres::{PostgresStorage, DEFAULT_STATEMENT_CACHE_CAPACITY};
+pub use read_only::ReadOnlyStorage;
+pub use retry::{RetryPolicy, DEFAULT_MAX_ATTEMPTS};
+pub use timed::{SlowQueryStats, TimedStorage};
 
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use crate::errors::UrlShortenerResult;
-use crate::models::ShortenedUrl;
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DomainBan, ExportedApiKey,
+    IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink, RetiredCode, Session,
+    ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline,
+    WebhookDelivery,
+};
 
 /// The main storage trait that defines the interface for all storage backends
 #[async_trait]
 pub trait Storage: Send + Sync {
-    /// Saves a shortened URL to storage
+    /// Saves a shortened URL to storage, failing with `Conflict` if the short code is taken,
+    /// including by an alias or a retired code that still redirects
     async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl>;
     
-    /// Retrieves a shortened URL by its short code and increments the visit count
+    /// Retrieves a shortened URL by its short code and increments the visit count. Aliases and
+    /// retired codes that still redirect resolve to their link.
     async fn get_url(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl>;
     
-    /// Gets statistics for a shortened URL without incrementing the visit count
+    /// Gets statistics for a shortened URL without incrementing the visit count; resolves
+    /// aliases and retired codes like [`get_url`](Self::get_url)
     async fn get_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl>;
+
+    /// Gets the links with these codes in one read, without counting visits, each paired with the
+    /// code it was found by. Resolves aliases and retired codes like [`get_stats`](Self::get_stats);
+    /// codes that match nothing are left out.
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>>;
+
+    /// Inserts many URLs at once, keeping their creation time and visit count as given.
+    /// Returns the number of rows written.
+    async fn bulk_insert(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64>;
+
+    /// Adds `count` visits to a URL without reading it
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()>;
+
+    /// Adds visits to several URLs at once, returning how many of them exist; used to flush
+    /// batched redirect visits
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64>;
+
+    /// Lists the most recently created URLs of an owner, newest first with ties broken by ID,
+    /// optionally only those in one collection and only those after a previous page's cursor
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Lists an owner's URLs matching `filter` in `sort` order, ties broken by ID, only those
+    /// after a previous page's cursor when given
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Changes a URL's title, notes, creator or collection, returning the updated URL
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl>;
+
+    /// Hands a URL owned by `from` to `to`, taking it out of its collection, which stays with
+    /// `from`; fails with `NotFound` unless `from` owns it
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl>;
+
+    /// Creates a collection, failing with `Conflict` if the owner already has one by that name
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection>;
+
+    /// Gets a collection with aggregates over its links
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats>;
+
+    /// Lists an owner's collections by name, with aggregates over their links
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>>;
+
+    /// Renames a collection, failing with `Conflict` if the owner already has one by that name
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection>;
+
+    /// Deletes a collection; its links are kept and become unassigned
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()>;
+
+    /// Marks up to `limit` links whose visits reached their click goal as notified and returns
+    /// them. Each reached goal is claimed exactly once, even with several instances checking.
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Returns a claimed goal to pending, e.g. after its notification could not be delivered
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()>;
+
+    /// Stores a new API key by its hash
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey>;
+
+    /// Gets an active API key
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey>;
+
+    /// Lists an owner's active API keys, newest first
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>>;
+
+    /// Revokes an active API key
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()>;
+
+    /// Atomically revokes an active API key and stores its replacement under the same owner and name
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey>;
+
+    /// Looks up the active API key with the given hash and records that it was used
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey>;
+
+    /// Looks up the active API key with the given hash without recording its use
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey>;
+
+    /// Stores a new session
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()>;
+
+    /// Gets a session last seen after `idle_cutoff` and marks it as seen now
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session>;
+
+    /// Gets a session last seen after `idle_cutoff` without marking it as seen
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session>;
+
+    /// Deletes a session, if it exists
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()>;
+
+    /// Deletes sessions last seen before `idle_cutoff`, returning how many were removed
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64>;
+
+    /// Atomically applies a manual change to a link's visit count
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts>;
+
+    /// Appends an entry to the audit log
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry>;
+
+    /// Inserts links brought over from elsewhere, keeping all their fields except the ID.
+    /// Links whose short code is already taken are skipped; returns how many were inserted.
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64>;
+
+    /// Counts stored links, only those of `owner` when given
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64>;
+
+    /// Short codes of the oldest links that were never visited, only those of `owner` when given
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>>;
+
+    /// Deletes the links with these codes that are still unvisited, with their aliases, retired
+    /// codes and campaign memberships; returns the codes of the deleted links
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>>;
+
+    /// Lists the most recent audit log entries, newest first
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>>;
+
+    /// Deletes audit log entries recorded before `before`, returning how many were removed
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64>;
+
+    /// Records an attempt to deliver a webhook event
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery>;
+
+    /// Lists a webhook's delivery attempts older than `before`, newest first
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>>;
+
+    /// One of a webhook's delivery attempts; NotFound if it belongs to another webhook
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery>;
+
+    /// Reserves an `Idempotency-Key` for a request, taking over a reservation made before
+    /// `expired_before`. Returns `None` when the key was reserved, or the record holding it.
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>>;
+
+    /// Keeps the response to a reserved key's request, to be replayed for repeats
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()>;
+
+    /// Frees a reserved key whose request failed, so the client can retry it
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()>;
+
+    /// Deletes idempotency keys reserved before `before`, returning how many were removed
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64>;
+
+    /// Lists links ordered by short code, starting after `after`, for paging through all of them
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Lists the most visited links that are not disabled, most visited first
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Lists links whose code starts with `prefix` that are not disabled, most visited first.
+    /// With a `query`, only links whose code or title contains it, regardless of case.
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>>;
+
+    /// Lists every owner's collections
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>>;
+
+    /// Stores a collection from another instance under a new ID, keeping its creation date.
+    /// If the owner already has one with that name, returns it instead, with `false`.
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)>;
+
+    /// Lists every active API key, including its hash
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>>;
+
+    /// Stores an API key from another instance; returns false if its hash is already stored
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool>;
+
+    /// Stores a collection under its existing ID, to keep a second backend in step during a
+    /// migration; returns false if the ID or the owner's name is already taken
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool>;
+
+    /// Stores an active API key under its existing ID; returns false if the ID or hash is already taken
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool>;
+
+    /// Adds a domain to the blocklist and disables every active link to it or its subdomains,
+    /// atomically; the ban lists the short codes it disabled
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan>;
+
+    /// Lists the blocked domains, alphabetically
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>>;
+
+    /// Whether `host` or one of its parent domains is blocked
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool>;
+
+    /// Disables the given links that are still active, returning the short codes it disabled
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>>;
+
+    /// Re-enables the given disabled links, returning the short codes it re-enabled
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>>;
+
+    /// Moves a link to `new_code`, failing with `Conflict` if it is taken. The old code is
+    /// retired: it keeps redirecting to the link until `redirects_until`, and keeps the
+    /// link's visits so far if they are forked.
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink>;
+
+    /// Lists the codes a link was moved away from, most recently retired first
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>>;
+
+    /// Gives a link an extra code, failing with `Conflict` if it is taken
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias>;
+
+    /// Removes an alias of a link; `NotFound` if the link has no such alias
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()>;
+
+    /// Lists a link's aliases, oldest first
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>>;
+
+    /// Stores redirect events, skipping those of links that no longer exist; returns how many
+    /// were stored
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64>;
+
+    /// Lists visit events of every link from `from` until before `to`, ordered by time and ID,
+    /// starting after the `after` event's time and ID, for paging through all of them
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>>;
+
+    /// First and last visit events of the link with this code, and how many there were in the
+    /// day and the week before `now`
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline>;
+
+    /// Visit events of the link with this code since `since`, counted by weekday and hour
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap>;
+
+    /// Visit events of the links with these codes since `since`, counted per link and day,
+    /// ordered by code and day; days without visits are left out
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>>;
+
+    /// Visit events of the link with this code since `since`, counted per source tag, most
+    /// visited first
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>>;
+
+    /// Creates a campaign, failing with `Conflict` if the owner already has one by that name
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign>;
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign>;
+
+    /// Lists an owner's campaigns by name
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>>;
+
+    /// Deletes a campaign; its links are kept
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()>;
+
+    /// Adds a link to a campaign; adding it again changes nothing
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()>;
+
+    /// Removes a link from a campaign; `NotFound` if it is not in it
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()>;
+
+    /// Aggregates the visits of a campaign's links: their all-time counts, and the visit events
+    /// since `since` as totals and per day
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats>;
+
+    /// Stores a campaign under its existing ID, to keep a second backend in step during a
+    /// migration; returns false if the ID or the owner's name is already taken
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool>;
+
+    /// Recomputes every owner's usage record for a UTC day from their links and visit
+    /// events, returning how many owners have one
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64>;
+
+    /// Usage records of the days from `from` until before `to`, by owner and then day
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>>;
+
+    /// Name of the backend, with the backends it wraps, e.g. `encrypted(postgres)`
+    fn backend(&self) -> String;
+
+    /// Checks that the backend answers, for readiness probes; not retried
+    async fn ping(&self) -> UrlShortenerResult<()>;
+
+    /// Prepared statement reuse on the redirect path, for backends that prepare statements
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        None
+    }
+
+    /// Storage calls over the slow query threshold, when calls are timed
+    fn slow_query_stats(&self) -> Option<SlowQueryStats> {
+        None
+    }
+}
+
+/// How often the redirect path's queries found their prepared statement cached on the connection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatementCacheStats {
+    /// Prepared statements kept per connection
+    pub capacity: usize,
+    pub hits: u64,
+    /// Queries that had to be prepared first. Once a connection's cache is full, a statement
+    /// that evicts another is counted as a hit, so a cache at capacity warrants raising it.
+    pub misses: u64,
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+pub(crate) fn in_domain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain).is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+}
+
+/// Host of a destination URL, lowercase and in ASCII form, as the `url_host` SQL function finds it
+pub(crate) fn url_host(url: &str) -> Option<String> {
+    url::Url::parse(url).ok()?.host_str().map(str::to_ascii_lowercase)
+}
+
+/// Error for a conditional update of a link that is no longer at the `expected` version
+pub(crate) fn version_mismatch(expected: i64) -> UrlShortenerError {
+    UrlShortenerErrorType::PreconditionFailed(format!("The link is no longer at version {}", expected)).into()
 }
 
 /// A type alias for a shared storage reference
@@ -34,6 +400,14 @@ pub struct StorageConfig {
     pub max_connections: Option<u32>,
     /// The connection timeout in seconds
     pub connection_timeout_secs: Option<u64>,
+    /// Whether pending migrations are applied when the storage is initialized
+    pub run_migrations: bool,
+    /// Attempts per operation when the database is temporarily unreachable
+    pub max_attempts: Option<u32>,
+    /// Rows each link's visit count is spread over; 1 updates the link row directly
+    pub visit_counter_shards: u8,
+    /// Prepared statements cached per connection
+    pub statement_cache_capacity: Option<usize>,
 }
 
 impl Default for StorageConfig {
@@ -42,6 +416,13 @@ impl Default for StorageConfig {
             connection_string: "memory".to_string(),
             max_connections: None,
             connection_timeout_secs: None,
+            run_migrations: true,
+            max_attempts: None,
+            visit_counter_shards: 1,
+            statement_cache_capacity: None,
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
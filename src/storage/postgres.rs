@@ -1,32 +1,159 @@
 use async_trait::async_trait;
-use chrono::Utc;
-use sqlx::{PgPool, postgres::PgPoolOptions, Transaction, Postgres};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use sqlx::{Connection, PgPool, postgres::{PgConnectOptions, PgPoolOptions}, types::Json, Transaction, Postgres};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
 use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
-use crate::models::ShortenedUrl;
-use super::{Storage, StorageConfig};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DailyVisits, DomainBan,
+    ExportedApiKey, IdempotencyRecord, IdempotentResponse, LinkAlias, LinkStatus, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RedirectHeaders,
+    RegeneratedLink, RetiredCode, Session, ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline,
+    WebhookDelivery,
+};
+use super::migrations::{Migrations, SchemaCompatibility};
+use super::{version_mismatch, RetryPolicy, StatementCacheStats, Storage, StorageConfig};
+
+/// Prepared statements cached per connection unless configured otherwise
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 100;
+
+/// Links per visit flush statement
+const VISIT_BATCH_SIZE: usize = 1000;
 
 pub struct PostgresStorage {
     pool: PgPool,
+    retry: RetryPolicy,
+    /// Rows visit counts are spread over per link; 1 updates the link row directly
+    counter_shards: i16,
+    statement_cache_capacity: usize,
+    statement_hits: AtomicU64,
+    statement_misses: AtomicU64,
 }
 
 impl PostgresStorage {
     pub async fn new(config: StorageConfig) -> UrlShortenerResult<Self> {
-        let pool = PgPoolOptions::new()
-            .max_connections(config.max_connections.unwrap_or(5))
-            .acquire_timeout(Duration::from_secs(config.connection_timeout_secs.unwrap_or(30)))
-            .connect(&config.connection_string)
-            .await
-            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::ConnectionError(e.to_string())))?;
+        let pool = Self::connect_pool(&config).await?;
+
+        // Run migrations unless operators apply them explicitly via `url-map migrate`
+        // A newer release may already have migrated the database; leave its schema alone
+        if config.run_migrations {
+            let migrations = Migrations::from_pool(pool.clone());
+            match migrations.compatibility().await? {
+                SchemaCompatibility::Ahead(versions) => {
+                    warn!(?versions, "Database schema is ahead of this release; not migrating")
+                }
+                _ => migrations.up().await?,
+            }
+        } else {
+            info!("Skipping database migrations at startup");
+        }
+
+        let retry = config
+            .max_attempts
+            .map(|attempts| RetryPolicy::new(attempts, RetryPolicy::default().base_delay))
+            .unwrap_or_default();
+
+        Ok(Self {
+            pool,
+            retry,
+            counter_shards: i16::from(config.visit_counter_shards.max(1)),
+            statement_cache_capacity: config.statement_cache_capacity.unwrap_or(DEFAULT_STATEMENT_CACHE_CAPACITY),
+            statement_hits: AtomicU64::new(0),
+            statement_misses: AtomicU64::new(0),
+        })
+    }
+
+    /// How the schema compares to the migrations this binary was built with
+    pub async fn schema_compatibility(&self) -> UrlShortenerResult<SchemaCompatibility> {
+        Migrations::from_pool(self.pool.clone()).compatibility().await
+    }
+
+    /// Counts a hot-path query as a statement cache hit or miss, from the connection's cache
+    /// size before and after running it
+    fn count_statement(&self, cached_before: usize, cached_after: usize) {
+        let counter = if cached_after > cached_before { &self.statement_misses } else { &self.statement_hits };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
 
-        // Run migrations
-        sqlx::migrate!("./migrations")
-            .run(&pool)
+    /// Moves sharded visit counts into their links' rows, returning how many links changed.
+    ///
+    /// Point lookups add outstanding shard counts themselves; listings, collection
+    /// aggregates and click goals only see them once folded.
+    pub async fn fold_visit_shards(&self) -> UrlShortenerResult<u64> {
+        let result = self.retry.run("fold_visit_shards", || async {
+            sqlx::query!(
+                r#"
+                WITH drained AS (
+                    DELETE FROM visit_counter_shards RETURNING short_url, visits
+                )
+                UPDATE shortened_urls u
+                SET visits = u.visits + d.visits, last_visited_at = NOW()
+                FROM (SELECT short_url, SUM(visits)::BIGINT AS visits FROM drained GROUP BY short_url) d
+                WHERE u.short_url = d.short_url
+                "#
+            )
+            .execute(&self.pool)
             .await
-            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(e.to_string())))?;
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Folds sharded visit counts every `interval` until the process exits
+    pub fn spawn_shard_folding(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match self.fold_visit_shards().await {
+                    Ok(0) => {}
+                    Ok(links) => debug!(links, "Folded sharded visit counts"),
+                    Err(e) => error!(error = %e, "Failed to fold sharded visit counts"),
+                }
+            }
+        });
+    }
+
+    /// Adds visits to a random shard of the link's counter; false if the link does not exist
+    async fn increment_shard<'e, E>(executor: E, short_url: &str, count: i64, shards: i16) -> UrlShortenerResult<bool>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO visit_counter_shards (short_url, shard, visits)
+            SELECT short_url, floor(random() * $3)::SMALLINT, $2
+            FROM shortened_urls
+            WHERE short_url = $1
+            ON CONFLICT (short_url, shard) DO UPDATE SET visits = visit_counter_shards.visits + EXCLUDED.visits
+            "#,
+            short_url,
+            count,
+            f64::from(shards)
+        )
+        .execute(executor)
+        .await
+        .map_err(Self::handle_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Creates the connection pool described by the storage configuration
+    pub(crate) async fn connect_pool(config: &StorageConfig) -> UrlShortenerResult<PgPool> {
+        // Queries are prepared once per connection and reused while they stay in this cache
+        let options = PgConnectOptions::from_str(&config.connection_string)
+            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::ConnectionError(e.to_string())))?
+            .statement_cache_capacity(config.statement_cache_capacity.unwrap_or(DEFAULT_STATEMENT_CACHE_CAPACITY));
 
-        Ok(Self { pool })
+        PgPoolOptions::new()
+            .max_connections(config.max_connections.unwrap_or(5))
+            .acquire_timeout(Duration::from_secs(config.connection_timeout_secs.unwrap_or(30)))
+            .connect_with(options)
+            .await
+            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::ConnectionError(e.to_string())))
     }
 
     /// Helper function to handle database errors consistently
@@ -37,20 +164,27 @@ impl PostgresStorage {
             }
             sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
                 // Unique violation error code
-                UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(
+                UrlShortenerError::from(UrlShortenerErrorType::Conflict(
                     "Short URL already exists".to_string(),
                 ))
             }
+            // Connection-level failures are transient and classified as retryable
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::WorkerCrashed => UrlShortenerError::from(error),
             _ => UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(error.to_string())),
         }
     }
 
+    /// Quotes a value for PostgreSQL's CSV COPY format
+    fn csv_field(value: &str) -> String {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+
     /// Helper function to begin a transaction
     async fn begin_tx(&self) -> UrlShortenerResult<Transaction<'_, Postgres>> {
-        self.pool
-            .begin()
-            .await
-            .map_err(|e| UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(e.to_string())))
+        self.pool.begin().await.map_err(Self::handle_error)
     }
 
     /// Helper function to save URL within a transaction
@@ -58,17 +192,36 @@ impl PostgresStorage {
         tx: &mut Transaction<'_, Postgres>,
         url: &ShortenedUrl,
     ) -> UrlShortenerResult<ShortenedUrl> {
+        if Self::redirects_elsewhere_tx(tx, &url.short_url).await? {
+            return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+        }
+
         sqlx::query_as!(
             ShortenedUrl,
             r#"
-            INSERT INTO shortened_urls (original_url, short_url, created_at, visits)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, original_url, short_url, created_at, visits
+            INSERT INTO shortened_urls (original_url, short_url, created_at, visits, owner, title, notes, created_by, collection_id, click_goal,
+                                        pass_through, content_type, signed_only, tracking_snippet, redirect_headers, hide_referrer)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            RETURNING id, original_url, short_url, created_at,
+                      visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = shortened_urls.short_url), 0)::BIGINT AS "visits!",
+                      owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
             "#,
             url.original_url,
             url.short_url,
             Utc::now(),
-            0i64
+            0i64,
+            url.owner,
+            url.title,
+            url.notes,
+            url.created_by,
+            url.collection_id,
+            url.click_goal,
+            url.pass_through,
+            url.content_type,
+            url.signed_only,
+            url.tracking_snippet,
+            url.redirect_headers as _,
+            url.hide_referrer
         )
         .fetch_one(&mut **tx)
         .await
@@ -80,50 +233,100 @@ impl PostgresStorage {
         tx: &mut Transaction<'_, Postgres>,
         short_url: &str,
         increment_visits: bool,
+        shards: i16,
     ) -> UrlShortenerResult<ShortenedUrl> {
-        if increment_visits {
-            sqlx::query_as!(
-                ShortenedUrl,
-                r#"
-                UPDATE shortened_urls 
-                SET visits = visits + 1
-                WHERE short_url = $1
-                RETURNING id, original_url, short_url, created_at, visits
-                "#,
-                short_url
-            )
-            .fetch_one(&mut **tx)
-            .await
-            .map_err(Self::handle_error)
-        } else {
-            sqlx::query_as!(
-                ShortenedUrl,
-                r#"
-                SELECT id, original_url, short_url, created_at, visits
-                FROM shortened_urls
-                WHERE short_url = $1
-                "#,
-                short_url
-            )
-            .fetch_one(&mut **tx)
-            .await
-            .map_err(Self::handle_error)
+        if increment_visits && shards > 1 {
+            if !Self::increment_shard(&mut **tx, short_url, 1, shards).await? {
+                return Err(UrlShortenerErrorType::NotFound.into());
+            }
+        } else if increment_visits {
+            sqlx::query!("UPDATE shortened_urls SET visits = visits + 1, last_visited_at = NOW() WHERE short_url = $1", short_url)
+                .execute(&mut **tx)
+                .await
+                .map_err(Self::handle_error)?;
+        }
+
+        // Counts still in shards are part of the total
+        sqlx::query_as!(
+            ShortenedUrl,
+            r#"
+            SELECT id, original_url, short_url, created_at,
+                   visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                   owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+            FROM shortened_urls u
+            WHERE short_url = $1
+            "#,
+            short_url
+        )
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(Self::handle_error)
+    }
+
+    /// Looks up a URL within a transaction, following an alias or a retired code that still
+    /// redirects to its link
+    async fn resolve_url_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        short_url: &str,
+        increment_visits: bool,
+        shards: i16,
+    ) -> UrlShortenerResult<ShortenedUrl> {
+        match Self::get_url_tx(tx, short_url, increment_visits, shards).await {
+            Err(e) if matches!(e.error_type, UrlShortenerErrorType::NotFound) => {
+                let current = sqlx::query_scalar!(
+                    r#"
+                    SELECT u.short_url AS "short_url!"
+                    FROM link_aliases a
+                    JOIN shortened_urls u ON u.id = a.url_id
+                    WHERE a.short_code = $1
+                    UNION ALL
+                    SELECT u.short_url
+                    FROM retired_codes r
+                    JOIN shortened_urls u ON u.id = r.url_id
+                    WHERE r.short_code = $1 AND r.redirects_until > CURRENT_TIMESTAMP
+                    LIMIT 1
+                    "#,
+                    short_url
+                )
+                .fetch_optional(&mut **tx)
+                .await
+                .map_err(Self::handle_error)?;
+
+                match current {
+                    Some(current) => Self::get_url_tx(tx, &current, increment_visits, shards).await,
+                    None => Err(e),
+                }
+            }
+            result => result,
         }
     }
-}
 
-#[async_trait]
-impl Storage for PostgresStorage {
-    async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+    /// Whether a short code is an alias or a retired code that still redirects, so it cannot be
+    /// given to another link
+    async fn redirects_elsewhere_tx(tx: &mut Transaction<'_, Postgres>, short_code: &str) -> UrlShortenerResult<bool> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM link_aliases WHERE short_code = $1)
+                OR EXISTS (
+                    SELECT 1 FROM retired_codes WHERE short_code = $1 AND redirects_until > CURRENT_TIMESTAMP
+                ) AS "taken!"
+            "#,
+            short_code
+        )
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(Self::handle_error)
+    }
+
+    /// Inserts a URL in its own transaction
+    async fn save_url_once(&self, url: &ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
         let mut tx = self.begin_tx().await?;
         
-        let result = Self::save_url_tx(&mut tx, &url).await;
+        let result = Self::save_url_tx(&mut tx, url).await;
         
         match result {
             Ok(saved_url) => {
-                tx.commit().await.map_err(|e| {
-                    UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(e.to_string()))
-                })?;
+                tx.commit().await.map_err(Self::handle_error)?;
                 Ok(saved_url)
             }
             Err(e) => {
@@ -139,16 +342,17 @@ impl Storage for PostgresStorage {
         }
     }
 
-    async fn get_url(&self, short_url: &str) -> UrlShortenerResult<ShortenedUrl> {
+    /// Looks up a URL in its own transaction, optionally counting a visit
+    async fn get_url_once(&self, short_url: &str, increment_visits: bool) -> UrlShortenerResult<ShortenedUrl> {
         let mut tx = self.begin_tx().await?;
         
-        let result = Self::get_url_tx(&mut tx, short_url, true).await;
+        let cached = tx.cached_statements_size();
+        let result = Self::resolve_url_tx(&mut tx, short_url, increment_visits, self.counter_shards).await;
+        self.count_statement(cached, tx.cached_statements_size());
         
         match result {
             Ok(url) => {
-                tx.commit().await.map_err(|e| {
-                    UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(e.to_string()))
-                })?;
+                tx.commit().await.map_err(Self::handle_error)?;
                 Ok(url)
             }
             Err(e) => {
@@ -164,28 +368,2108 @@ impl Storage for PostgresStorage {
         }
     }
 
-    async fn get_stats(&self, short_url: &str) -> UrlShortenerResult<ShortenedUrl> {
+    /// Reserves an idempotency key in its own transaction. A key that is taken stays locked
+    /// by the insert until commit, so it is still there to be read back.
+    async fn reserve_idempotency_key_once(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
         let mut tx = self.begin_tx().await?;
-        
-        let result = Self::get_url_tx(&mut tx, short_url, false).await;
-        
-        match result {
-            Ok(url) => {
-                tx.commit().await.map_err(|e| {
-                    UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(e.to_string()))
-                })?;
-                Ok(url)
+        let reserved = sqlx::query_scalar!(
+            r#"
+            INSERT INTO idempotency_keys (scope, key, fingerprint)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (scope, key) DO UPDATE
+            SET fingerprint = EXCLUDED.fingerprint, status_code = NULL, content_type = NULL, body = NULL,
+                created_at = CURRENT_TIMESTAMP
+            WHERE idempotency_keys.created_at < $4
+            RETURNING key
+            "#,
+            scope,
+            key,
+            fingerprint,
+            expired_before
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(Self::handle_error)?;
+
+        let existing = match reserved {
+            Some(_) => None,
+            None => {
+                let row = sqlx::query!(
+                    r#"
+                    SELECT fingerprint, status_code, content_type, body, created_at
+                    FROM idempotency_keys
+                    WHERE scope = $1 AND key = $2
+                    "#,
+                    scope,
+                    key
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(Self::handle_error)?;
+                let response = row.status_code.zip(row.body).map(|(status_code, body)| IdempotentResponse {
+                    status_code,
+                    content_type: row.content_type,
+                    body,
+                });
+                Some(IdempotencyRecord { fingerprint: row.fingerprint, response, created_at: row.created_at })
             }
-            Err(e) => {
-                tx.rollback().await.map_err(|rollback_err| {
-                    UrlShortenerError::from(UrlShortenerErrorType::DatabaseError(format!(
-                        "Error: {}. Rollback failed: {}",
-                        e,
-                        rollback_err
-                    )))
-                })?;
-                Err(e)
+        };
+        tx.commit().await.map_err(Self::handle_error)?;
+        Ok(existing)
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        self.retry.run("save_url", || self.save_url_once(&url)).await
+    }
+
+    async fn get_url(&self, short_url: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.retry.run("get_url", || self.get_url_once(short_url, true)).await
+    }
+
+    async fn get_stats(&self, short_url: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.retry.run("get_stats", || self.get_url_once(short_url, false)).await
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        // Each code matches its link, else the link of an alias or a retired code that still
+        // redirects, in that order
+        let rows = self.retry.run("get_stats_many", || async {
+            sqlx::query!(
+                r#"
+                SELECT c.code AS "code!", u.id AS "id!", u.original_url AS "original_url!", u.short_url AS "short_url!",
+                       u.created_at AS "created_at!",
+                       u.visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                       u.owner AS "owner?", u.title AS "title?", u.notes AS "notes?", u.created_by AS "created_by?",
+                       u.collection_id AS "collection_id?", u.click_goal AS "click_goal?", u.goal_reached_at AS "goal_reached_at?",
+                       u.pass_through AS "pass_through!", u.content_type AS "content_type?", u.disabled_at AS "disabled_at?",
+                       u.signed_only AS "signed_only!", u.tracking_snippet AS "tracking_snippet?",
+                       u.redirect_headers AS "redirect_headers?: Json<RedirectHeaders>",
+                       u.hide_referrer AS "hide_referrer!", u.last_visited_at AS "last_visited_at?", u.version AS "version!"
+                FROM (SELECT DISTINCT code FROM UNNEST($1::TEXT[]) AS code) c
+                JOIN LATERAL (
+                    SELECT l.* FROM shortened_urls l WHERE l.short_url = c.code
+                    UNION ALL
+                    SELECT l.* FROM link_aliases a JOIN shortened_urls l ON l.id = a.url_id WHERE a.short_code = c.code
+                    UNION ALL
+                    SELECT l.* FROM retired_codes r JOIN shortened_urls l ON l.id = r.url_id
+                    WHERE r.short_code = c.code AND r.redirects_until > CURRENT_TIMESTAMP
+                    LIMIT 1
+                ) u ON TRUE
+                "#,
+                short_codes
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let url = ShortenedUrl {
+                    id: row.id,
+                    original_url: row.original_url,
+                    short_url: row.short_url,
+                    created_at: row.created_at,
+                    visits: row.visits,
+                    last_visited_at: row.last_visited_at,
+                    owner: row.owner,
+                    title: row.title,
+                    notes: row.notes,
+                    created_by: row.created_by,
+                    collection_id: row.collection_id,
+                    click_goal: row.click_goal,
+                    goal_reached_at: row.goal_reached_at,
+                    pass_through: row.pass_through,
+                    content_type: row.content_type,
+                    disabled_at: row.disabled_at,
+                    signed_only: row.signed_only,
+                    tracking_snippet: row.tracking_snippet,
+                    redirect_headers: row.redirect_headers,
+                    hide_referrer: row.hide_referrer,
+                    version: row.version,
+                };
+                (row.code, url)
+            })
+            .collect())
+    }
+
+    async fn bulk_insert(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let mut data = String::new();
+        for url in &urls {
+            data.push_str(&Self::csv_field(&url.original_url));
+            data.push(',');
+            data.push_str(&Self::csv_field(&url.short_url));
+            data.push(',');
+            data.push_str(&url.created_at.to_rfc3339());
+            data.push(',');
+            data.push_str(&url.visits.to_string());
+            // Unquoted empty fields are NULL in CSV mode
+            for field in [&url.owner, &url.title, &url.notes, &url.created_by] {
+                data.push(',');
+                if let Some(value) = field {
+                    data.push_str(&Self::csv_field(value));
+                }
+            }
+            for field in [url.collection_id, url.click_goal] {
+                data.push(',');
+                if let Some(value) = field {
+                    data.push_str(&value.to_string());
+                }
+            }
+            data.push(',');
+            data.push_str(if url.pass_through { "t" } else { "f" });
+            data.push(',');
+            if let Some(content_type) = &url.content_type {
+                data.push_str(&Self::csv_field(content_type));
+            }
+            data.push(',');
+            data.push_str(if url.signed_only { "t" } else { "f" });
+            data.push(',');
+            if let Some(snippet) = &url.tracking_snippet {
+                data.push_str(&Self::csv_field(snippet));
+            }
+            data.push(',');
+            if let Some(Json(headers)) = &url.redirect_headers {
+                data.push_str(&Self::csv_field(&serde_json::to_string(headers).unwrap_or_default()));
+            }
+            data.push(',');
+            data.push_str(if url.hide_referrer { "t" } else { "f" });
+            data.push('\n');
+        }
+
+        let mut conn = self.pool.acquire().await.map_err(Self::handle_error)?;
+        let mut copy = conn
+            .copy_in_raw(
+                "COPY shortened_urls (original_url, short_url, created_at, visits, owner, title, notes, created_by, collection_id, \
+                 click_goal, pass_through, content_type, signed_only, tracking_snippet, redirect_headers, hide_referrer) \
+                 FROM STDIN WITH (FORMAT csv)",
+            )
+            .await
+            .map_err(Self::handle_error)?;
+        if let Err(e) = copy.send(data.into_bytes()).await {
+            copy.abort(e.to_string()).await.ok();
+            return Err(Self::handle_error(e));
+        }
+        copy.finish().await.map_err(Self::handle_error)
+    }
+
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()> {
+        if self.counter_shards > 1 {
+            let found = self.retry.run("record_visits", || {
+                Self::increment_shard(&self.pool, short_code, count as i64, self.counter_shards)
+            }).await?;
+            return if found { Ok(()) } else { Err(UrlShortenerErrorType::NotFound.into()) };
+        }
+
+        let result = self.retry.run("record_visits", || async {
+            sqlx::query!(
+                "UPDATE shortened_urls SET visits = visits + $2, last_visited_at = NOW() WHERE short_url = $1",
+                short_code,
+                count as i64
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        let mut found = 0;
+        // Fixed-size array parameters keep this one prepared statement whatever the batch size
+        for chunk in visits.chunks(VISIT_BATCH_SIZE) {
+            let codes: Vec<&str> = chunk.iter().map(|(code, _)| code.as_str()).collect();
+            let counts: Vec<i64> = chunk.iter().map(|(_, count)| *count as i64).collect();
+
+            found += self.retry.run("record_visit_batch", || async {
+                let mut conn = self.pool.acquire().await.map_err(Self::handle_error)?;
+                let cached = conn.cached_statements_size();
+                let result = if self.counter_shards > 1 {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO visit_counter_shards (short_url, shard, visits)
+                        SELECT u.short_url, floor(random() * $3)::SMALLINT, v.visits
+                        FROM (
+                            SELECT short_url, SUM(visits)::BIGINT AS visits
+                            FROM UNNEST($1::TEXT[], $2::BIGINT[]) AS t(short_url, visits)
+                            GROUP BY short_url
+                        ) v
+                        JOIN shortened_urls u ON u.short_url = v.short_url
+                        ORDER BY u.short_url
+                        ON CONFLICT (short_url, shard) DO UPDATE SET visits = visit_counter_shards.visits + EXCLUDED.visits
+                        "#,
+                        &codes as &[&str],
+                        &counts,
+                        f64::from(self.counter_shards)
+                    )
+                    .execute(&mut *conn)
+                    .await
+                } else {
+                    // Rows are locked in ID order so concurrent flushes from several instances can't deadlock
+                    sqlx::query!(
+                        r#"
+                        WITH v AS (
+                            SELECT short_url, SUM(visits)::BIGINT AS visits
+                            FROM UNNEST($1::TEXT[], $2::BIGINT[]) AS t(short_url, visits)
+                            GROUP BY short_url
+                        ),
+                        locked AS (
+                            SELECT u.id, v.visits
+                            FROM shortened_urls u
+                            JOIN v ON v.short_url = u.short_url
+                            ORDER BY u.id
+                            FOR UPDATE OF u
+                        )
+                        UPDATE shortened_urls u
+                        SET visits = u.visits + locked.visits, last_visited_at = NOW()
+                        FROM locked
+                        WHERE u.id = locked.id
+                        "#,
+                        &codes as &[&str],
+                        &counts
+                    )
+                    .execute(&mut *conn)
+                    .await
+                };
+                self.count_statement(cached, conn.cached_statements_size());
+                result.map(|result| result.rows_affected()).map_err(Self::handle_error)
+            }).await?;
+        }
+        Ok(found)
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.retry.run("list_urls_by_owner", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                SELECT id, original_url, short_url, created_at,
+                       visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                       owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                FROM shortened_urls u
+                WHERE owner = $1 AND ($2::BIGINT IS NULL OR collection_id = $2)
+                    AND ($3::TIMESTAMPTZ IS NULL OR (created_at, id) < ($3, $4::BIGINT))
+                ORDER BY created_at DESC, id DESC
+                LIMIT $5
+                "#,
+                owner,
+                collection_id,
+                after.map(|cursor| cursor.created_at),
+                after.map(|cursor| cursor.id),
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        let active = filter.status.map(|status| status == LinkStatus::Active);
+        // One query per order. Visits include those still in counter shards, as stats count
+        // them; the other orders match an index on the owner and their sort key.
+        self.retry.run("list_urls", || async {
+            match sort {
+                UrlSort::CreatedAt => sqlx::query_as!(
+                    ShortenedUrl,
+                    r#"
+                    SELECT id, original_url, short_url, created_at, t.visits AS "visits!", owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                    FROM shortened_urls u
+                    CROSS JOIN LATERAL (
+                        SELECT u.visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS visits
+                    ) t
+                    WHERE owner = $1
+                        AND ($2::TIMESTAMPTZ IS NULL OR created_at > $2)
+                        AND ($3::TEXT IS NULL OR url_host(original_url) = $3)
+                        AND ($4::BIGINT IS NULL OR t.visits >= $4)
+                        AND ($5::BOOLEAN IS NULL OR (disabled_at IS NULL) = $5)
+                        AND ($6::BIGINT IS NULL OR (created_at, id) < ($7::TIMESTAMPTZ, $6))
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT $8
+                    "#,
+                    owner,
+                    filter.created_after,
+                    filter.domain,
+                    filter.min_visits,
+                    active,
+                    after.map(|cursor| cursor.id),
+                    after.map(|cursor| cursor.time()),
+                    limit
+                )
+                .fetch_all(&self.pool)
+                .await,
+                UrlSort::Visits => sqlx::query_as!(
+                    ShortenedUrl,
+                    r#"
+                    SELECT id, original_url, short_url, created_at, t.visits AS "visits!", owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                    FROM shortened_urls u
+                    CROSS JOIN LATERAL (
+                        SELECT u.visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS visits
+                    ) t
+                    WHERE owner = $1
+                        AND ($2::TIMESTAMPTZ IS NULL OR created_at > $2)
+                        AND ($3::TEXT IS NULL OR url_host(original_url) = $3)
+                        AND ($4::BIGINT IS NULL OR t.visits >= $4)
+                        AND ($5::BOOLEAN IS NULL OR (disabled_at IS NULL) = $5)
+                        AND ($6::BIGINT IS NULL OR (t.visits, id) < ($7::BIGINT, $6))
+                    ORDER BY t.visits DESC, id DESC
+                    LIMIT $8
+                    "#,
+                    owner,
+                    filter.created_after,
+                    filter.domain,
+                    filter.min_visits,
+                    active,
+                    after.map(|cursor| cursor.id),
+                    after.map(|cursor| cursor.key),
+                    limit
+                )
+                .fetch_all(&self.pool)
+                .await,
+                UrlSort::LastVisited => sqlx::query_as!(
+                    ShortenedUrl,
+                    r#"
+                    SELECT id, original_url, short_url, created_at, t.visits AS "visits!", owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                    FROM shortened_urls u
+                    CROSS JOIN LATERAL (
+                        SELECT u.visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS visits
+                    ) t
+                    WHERE owner = $1
+                        AND ($2::TIMESTAMPTZ IS NULL OR created_at > $2)
+                        AND ($3::TEXT IS NULL OR url_host(original_url) = $3)
+                        AND ($4::BIGINT IS NULL OR t.visits >= $4)
+                        AND ($5::BOOLEAN IS NULL OR (disabled_at IS NULL) = $5)
+                        AND ($6::BIGINT IS NULL OR (COALESCE(last_visited_at, to_timestamp(0)), id) < ($7::TIMESTAMPTZ, $6))
+                    ORDER BY COALESCE(last_visited_at, to_timestamp(0)) DESC, id DESC
+                    LIMIT $8
+                    "#,
+                    owner,
+                    filter.created_after,
+                    filter.domain,
+                    filter.min_visits,
+                    active,
+                    after.map(|cursor| cursor.id),
+                    after.map(|cursor| cursor.time()),
+                    limit
+                )
+                .fetch_all(&self.pool)
+                .await,
             }
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        let updated = self.retry.run("update_metadata", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                UPDATE shortened_urls
+                SET title = CASE WHEN $2 THEN $3 ELSE title END,
+                    notes = CASE WHEN $4 THEN $5 ELSE notes END,
+                    created_by = CASE WHEN $6 THEN $7 ELSE created_by END,
+                    collection_id = CASE WHEN $8 THEN $9 ELSE collection_id END,
+                    click_goal = CASE WHEN $10 THEN $11 ELSE click_goal END,
+                    goal_reached_at = CASE WHEN $10 THEN NULL ELSE goal_reached_at END,
+                    pass_through = COALESCE($12, pass_through),
+                    signed_only = COALESCE($13, signed_only),
+                    tracking_snippet = CASE WHEN $15 THEN $16 ELSE tracking_snippet END,
+                    redirect_headers = CASE WHEN $17 THEN $18 ELSE redirect_headers END,
+                    hide_referrer = COALESCE($19, hide_referrer),
+                    version = version + 1
+                WHERE short_url = $1 AND ($14::BIGINT IS NULL OR version = $14)
+                RETURNING id, original_url, short_url, created_at,
+                          visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = shortened_urls.short_url), 0)::BIGINT AS "visits!",
+                          owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                "#,
+                short_code,
+                update.title.is_some(),
+                update.title.clone().flatten(),
+                update.notes.is_some(),
+                update.notes.clone().flatten(),
+                update.created_by.is_some(),
+                update.created_by.clone().flatten(),
+                update.collection_id.is_some(),
+                update.collection_id.flatten(),
+                update.click_goal.is_some(),
+                update.click_goal.flatten(),
+                update.pass_through,
+                update.signed_only,
+                update.expected_version,
+                update.tracking_snippet.is_some(),
+                update.tracking_snippet.clone().flatten(),
+                update.redirect_headers.is_some(),
+                update.redirect_headers.clone().flatten().map(Json) as _,
+                update.hide_referrer
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        // The service has checked that the link exists, so a miss on a conditional update means
+        // it was changed in the meantime
+        match (updated, update.expected_version) {
+            (Some(updated), _) => Ok(updated),
+            (None, Some(version)) => Err(version_mismatch(version)),
+            (None, None) => Err(UrlShortenerErrorType::NotFound.into()),
+        }
+    }
+
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.retry.run("transfer_url", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                UPDATE shortened_urls
+                SET owner = $3, collection_id = NULL, version = version + 1
+                WHERE short_url = $1 AND owner = $2
+                RETURNING id, original_url, short_url, created_at,
+                          visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = shortened_urls.short_url), 0)::BIGINT AS "visits!",
+                          owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                "#,
+                short_code,
+                from,
+                to
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Self::handle_error)?
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+        }).await
+    }
+
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        self.retry.run("create_collection", || async {
+            sqlx::query_as!(
+                Collection,
+                r#"
+                INSERT INTO collections (owner, name)
+                VALUES ($1, $2)
+                RETURNING id, owner, name, created_at
+                "#,
+                owner,
+                name
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        self.retry.run("get_collection", || async {
+            sqlx::query_as!(
+                CollectionStats,
+                r#"
+                SELECT c.id, c.owner, c.name, c.created_at,
+                       COUNT(u.id) AS "links!", COALESCE(SUM(u.visits), 0)::BIGINT AS "visits!"
+                FROM collections c
+                LEFT JOIN shortened_urls u ON u.collection_id = c.id
+                WHERE c.id = $1
+                GROUP BY c.id
+                "#,
+                id
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.retry.run("list_collections", || async {
+            sqlx::query_as!(
+                CollectionStats,
+                r#"
+                SELECT c.id, c.owner, c.name, c.created_at,
+                       COUNT(u.id) AS "links!", COALESCE(SUM(u.visits), 0)::BIGINT AS "visits!"
+                FROM collections c
+                LEFT JOIN shortened_urls u ON u.collection_id = c.id
+                WHERE c.owner = $1
+                GROUP BY c.id
+                ORDER BY c.name
+                "#,
+                owner
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection> {
+        self.retry.run("rename_collection", || async {
+            sqlx::query_as!(
+                Collection,
+                r#"
+                UPDATE collections
+                SET name = $2
+                WHERE id = $1
+                RETURNING id, owner, name, created_at
+                "#,
+                id,
+                name
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()> {
+        let result = self.retry.run("delete_collection", || async {
+            sqlx::query!("DELETE FROM collections WHERE id = $1", id)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
         }
+        Ok(())
+    }
+
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.retry.run("claim_reached_goals", || async {
+            // SKIP LOCKED lets concurrent checkers claim disjoint links
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                UPDATE shortened_urls
+                SET goal_reached_at = NOW()
+                WHERE id IN (
+                    SELECT id FROM shortened_urls u
+                    WHERE click_goal IS NOT NULL AND goal_reached_at IS NULL
+                      AND visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0) >= click_goal
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, original_url, short_url, created_at,
+                          visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = shortened_urls.short_url), 0)::BIGINT AS "visits!",
+                          owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                "#,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()> {
+        self.retry.run("release_goal", || async {
+            sqlx::query!("UPDATE shortened_urls SET goal_reached_at = NULL WHERE short_url = $1", short_code)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+        Ok(())
+    }
+
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        self.retry.run("create_api_key", || async {
+            sqlx::query_as!(
+                StoredApiKey,
+                r#"
+                INSERT INTO api_keys (owner, name, key_hash, prefix)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, owner, name, prefix, created_at, last_used_at
+                "#,
+                owner,
+                name,
+                key_hash,
+                prefix
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        self.retry.run("get_api_key", || async {
+            sqlx::query_as!(
+                StoredApiKey,
+                r#"
+                SELECT id, owner, name, prefix, created_at, last_used_at
+                FROM api_keys
+                WHERE id = $1 AND revoked_at IS NULL
+                "#,
+                id
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.retry.run("list_api_keys", || async {
+            sqlx::query_as!(
+                StoredApiKey,
+                r#"
+                SELECT id, owner, name, prefix, created_at, last_used_at
+                FROM api_keys
+                WHERE owner = $1 AND revoked_at IS NULL
+                ORDER BY created_at DESC, id DESC
+                "#,
+                owner
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
     }
-} 
\ No newline at end of file
+
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()> {
+        let result = self.retry.run("revoke_api_key", || async {
+            sqlx::query!(
+                "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+                id
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        let mut tx = self.begin_tx().await?;
+
+        let revoked = sqlx::query!(
+            r#"
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE id = $1 AND revoked_at IS NULL
+            RETURNING owner, name
+            "#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(Self::handle_error)?;
+
+        let replacement = sqlx::query_as!(
+            StoredApiKey,
+            r#"
+            INSERT INTO api_keys (owner, name, key_hash, prefix)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, owner, name, prefix, created_at, last_used_at
+            "#,
+            revoked.owner,
+            revoked.name,
+            key_hash,
+            prefix
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(Self::handle_error)?;
+
+        tx.commit().await.map_err(Self::handle_error)?;
+        Ok(replacement)
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.retry.run("authenticate_api_key", || async {
+            sqlx::query_as!(
+                StoredApiKey,
+                r#"
+                UPDATE api_keys SET last_used_at = NOW()
+                WHERE key_hash = $1 AND revoked_at IS NULL
+                RETURNING id, owner, name, prefix, created_at, last_used_at
+                "#,
+                key_hash
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.retry.run("find_api_key", || async {
+            sqlx::query_as!(
+                StoredApiKey,
+                r#"
+                SELECT id, owner, name, prefix, created_at, last_used_at
+                FROM api_keys
+                WHERE key_hash = $1 AND revoked_at IS NULL
+                "#,
+                key_hash
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()> {
+        self.retry.run("create_session", || async {
+            sqlx::query!(
+                r#"
+                INSERT INTO sessions (id, owner, csrf_token, created_at, last_seen_at)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                session.id,
+                session.owner,
+                session.csrf_token,
+                session.created_at,
+                session.last_seen_at
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(())
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.retry.run("touch_session", || async {
+            sqlx::query_as!(
+                Session,
+                r#"
+                UPDATE sessions SET last_seen_at = NOW()
+                WHERE id = $1 AND last_seen_at > $2
+                RETURNING id, owner, csrf_token, created_at, last_seen_at
+                "#,
+                id,
+                idle_cutoff
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.retry.run("find_session", || async {
+            sqlx::query_as!(
+                Session,
+                r#"
+                SELECT id, owner, csrf_token, created_at, last_seen_at
+                FROM sessions
+                WHERE id = $1 AND last_seen_at > $2
+                "#,
+                id,
+                idle_cutoff
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()> {
+        self.retry.run("delete_session", || async {
+            sqlx::query!("DELETE FROM sessions WHERE id = $1", id)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+        Ok(())
+    }
+
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let result = self.retry.run("purge_idle_sessions", || async {
+            sqlx::query!("DELETE FROM sessions WHERE last_seen_at <= $1", idle_cutoff)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        // NULL resets the count; the `old` snapshot is taken before the update
+        let delta = match change {
+            VisitChange::Reset => None,
+            VisitChange::Adjust(delta) => Some(delta),
+        };
+
+        self.retry.run("change_visits", || async {
+            // Outstanding shard counts are folded in first, so they are reset or adjusted too
+            sqlx::query_as!(
+                VisitCounts,
+                r#"
+                WITH drained AS (
+                    DELETE FROM visit_counter_shards WHERE short_url = $1 RETURNING visits
+                ),
+                old AS (
+                    SELECT id, visits + COALESCE((SELECT SUM(visits) FROM drained), 0)::BIGINT AS visits
+                    FROM shortened_urls
+                    WHERE short_url = $1
+                    FOR UPDATE
+                )
+                UPDATE shortened_urls u
+                SET visits = CASE WHEN $2::BIGINT IS NULL THEN 0 ELSE GREATEST(old.visits + $2, 0) END
+                FROM old
+                WHERE u.id = old.id
+                RETURNING old.visits AS "previous!", u.visits AS "visits!"
+                "#,
+                short_code,
+                delta
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        self.retry.run("record_audit", || async {
+            sqlx::query_as!(
+                AuditEntry,
+                r#"
+                INSERT INTO audit_log (actor, action, target, details)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, actor, action, target, details, created_at
+                "#,
+                entry.actor,
+                entry.action,
+                entry.target,
+                entry.details
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        self.retry.run("record_webhook_delivery", || async {
+            sqlx::query_as!(
+                WebhookDelivery,
+                r#"
+                INSERT INTO webhook_deliveries
+                    (webhook, event, payload, status_code, latency_ms, response, delivered, replay_of)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING id, webhook, event, payload, status_code, latency_ms, response, delivered, replay_of,
+                    attempted_at
+                "#,
+                delivery.webhook,
+                delivery.event,
+                delivery.payload,
+                delivery.status_code,
+                delivery.latency_ms,
+                delivery.response,
+                delivery.delivered,
+                delivery.replay_of
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.retry.run("list_webhook_deliveries", || async {
+            sqlx::query_as!(
+                WebhookDelivery,
+                r#"
+                SELECT id, webhook, event, payload, status_code, latency_ms, response, delivered, replay_of,
+                    attempted_at
+                FROM webhook_deliveries
+                WHERE webhook = $1 AND ($2::BIGINT IS NULL OR id < $2)
+                ORDER BY id DESC
+                LIMIT $3
+                "#,
+                webhook,
+                before,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        self.retry.run("get_webhook_delivery", || async {
+            sqlx::query_as!(
+                WebhookDelivery,
+                r#"
+                SELECT id, webhook, event, payload, status_code, latency_ms, response, delivered, replay_of,
+                    attempted_at
+                FROM webhook_deliveries
+                WHERE webhook = $1 AND id = $2
+                "#,
+                webhook,
+                id
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        self.retry.run("reserve_idempotency_key", || {
+            self.reserve_idempotency_key_once(scope, key, fingerprint, expired_before)
+        }).await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        self.retry.run("complete_idempotency_key", || async {
+            sqlx::query!(
+                r#"
+                UPDATE idempotency_keys
+                SET status_code = $3, content_type = $4, body = $5
+                WHERE scope = $1 AND key = $2
+                "#,
+                scope,
+                key,
+                response.status_code,
+                response.content_type.as_deref(),
+                response.body
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        self.retry.run("release_idempotency_key", || async {
+            sqlx::query!(
+                "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2 AND status_code IS NULL",
+                scope,
+                key
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(())
+    }
+
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let result = self.retry.run("purge_idempotency_keys", || async {
+            sqlx::query!("DELETE FROM idempotency_keys WHERE created_at < $1", before)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        self.retry.run("list_audit_entries", || async {
+            sqlx::query_as!(
+                AuditEntry,
+                r#"
+                SELECT id, actor, action, target, details, created_at
+                FROM audit_log
+                ORDER BY id DESC
+                LIMIT $1
+                "#,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        let result = self.retry.run("purge_audit_entries", || async {
+            sqlx::query!("DELETE FROM audit_log WHERE created_at < $1", before)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        let original_urls: Vec<&str> = urls.iter().map(|url| url.original_url.as_str()).collect();
+        let short_urls: Vec<&str> = urls.iter().map(|url| url.short_url.as_str()).collect();
+        let created_at: Vec<DateTime<Utc>> = urls.iter().map(|url| url.created_at).collect();
+        let visits: Vec<i64> = urls.iter().map(|url| url.visits).collect();
+        let owners: Vec<Option<&str>> = urls.iter().map(|url| url.owner.as_deref()).collect();
+        let titles: Vec<Option<&str>> = urls.iter().map(|url| url.title.as_deref()).collect();
+        let notes: Vec<Option<&str>> = urls.iter().map(|url| url.notes.as_deref()).collect();
+        let created_by: Vec<Option<&str>> = urls.iter().map(|url| url.created_by.as_deref()).collect();
+        let collection_ids: Vec<Option<i64>> = urls.iter().map(|url| url.collection_id).collect();
+        let click_goals: Vec<Option<i64>> = urls.iter().map(|url| url.click_goal).collect();
+        let goals_reached_at: Vec<Option<DateTime<Utc>>> = urls.iter().map(|url| url.goal_reached_at).collect();
+        let pass_through: Vec<bool> = urls.iter().map(|url| url.pass_through).collect();
+        let content_types: Vec<Option<&str>> = urls.iter().map(|url| url.content_type.as_deref()).collect();
+        let disabled_at: Vec<Option<DateTime<Utc>>> = urls.iter().map(|url| url.disabled_at).collect();
+        let signed_only: Vec<bool> = urls.iter().map(|url| url.signed_only).collect();
+        let tracking_snippets: Vec<Option<&str>> = urls.iter().map(|url| url.tracking_snippet.as_deref()).collect();
+        let redirect_headers: Vec<Option<Json<&RedirectHeaders>>> =
+            urls.iter().map(|url| url.redirect_headers.as_ref().map(|Json(headers)| Json(headers))).collect();
+        let hide_referrer: Vec<bool> = urls.iter().map(|url| url.hide_referrer).collect();
+        let last_visited_at: Vec<Option<DateTime<Utc>>> = urls.iter().map(|url| url.last_visited_at).collect();
+        let versions: Vec<i64> = urls.iter().map(|url| url.version).collect();
+
+        let result = self.retry.run("import_urls", || async {
+            sqlx::query!(
+                r#"
+                INSERT INTO shortened_urls (original_url, short_url, created_at, visits, owner, title, notes, created_by,
+                                            collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only,
+                                            version, tracking_snippet, redirect_headers, hide_referrer, last_visited_at)
+                SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TIMESTAMPTZ[], $4::BIGINT[], $5::TEXT[], $6::TEXT[],
+                                     $7::TEXT[], $8::TEXT[], $9::BIGINT[], $10::BIGINT[], $11::TIMESTAMPTZ[], $12::BOOLEAN[],
+                                     $13::TEXT[], $14::TIMESTAMPTZ[], $15::BOOLEAN[], $16::BIGINT[], $17::TEXT[],
+                                     $18::JSONB[], $19::BOOLEAN[], $20::TIMESTAMPTZ[])
+                ON CONFLICT (short_url) DO NOTHING
+                "#,
+                &original_urls as &[&str],
+                &short_urls as &[&str],
+                &created_at,
+                &visits,
+                &owners as &[Option<&str>],
+                &titles as &[Option<&str>],
+                &notes as &[Option<&str>],
+                &created_by as &[Option<&str>],
+                &collection_ids as &[Option<i64>],
+                &click_goals as &[Option<i64>],
+                &goals_reached_at as &[Option<DateTime<Utc>>],
+                &pass_through,
+                &content_types as &[Option<&str>],
+                &disabled_at as &[Option<DateTime<Utc>>],
+                &signed_only,
+                &versions,
+                &tracking_snippets as &[Option<&str>],
+                &redirect_headers as _,
+                &hide_referrer,
+                &last_visited_at as &[Option<DateTime<Utc>>]
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        let count = self.retry.run("count_urls", || async {
+            sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!" FROM shortened_urls WHERE $1::TEXT IS NULL OR owner = $1"#,
+                owner
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(count as u64)
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        self.retry.run("oldest_unvisited_urls", || async {
+            sqlx::query_scalar!(
+                r#"
+                SELECT short_url FROM shortened_urls u
+                WHERE ($1::TEXT IS NULL OR owner = $1)
+                  AND visits = 0
+                  AND NOT EXISTS (SELECT 1 FROM visit_counter_shards s WHERE s.short_url = u.short_url AND s.visits > 0)
+                ORDER BY created_at, id
+                LIMIT $2
+                "#,
+                owner,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        // Visits are checked again, as a link may have been visited since it was picked
+        self.retry.run("delete_unvisited_urls", || async {
+            sqlx::query_scalar!(
+                r#"
+                DELETE FROM shortened_urls u
+                WHERE short_url = ANY($1)
+                  AND visits = 0
+                  AND NOT EXISTS (SELECT 1 FROM visit_counter_shards s WHERE s.short_url = u.short_url AND s.visits > 0)
+                RETURNING short_url
+                "#,
+                short_codes
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.retry.run("export_urls", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                SELECT id, original_url, short_url, created_at,
+                       visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                       owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                FROM shortened_urls u
+                WHERE $1::TEXT IS NULL OR short_url > $1
+                ORDER BY short_url
+                LIMIT $2
+                "#,
+                after,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.retry.run("top_visited_urls", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                SELECT id, original_url, short_url, created_at,
+                       visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                       owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                FROM shortened_urls u
+                WHERE disabled_at IS NULL
+                ORDER BY 5 DESC, short_url
+                LIMIT $1
+                "#,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.retry.run("search_urls", || async {
+            sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                SELECT id, original_url, short_url, created_at,
+                       visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)::BIGINT AS "visits!",
+                       owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                FROM shortened_urls u
+                WHERE disabled_at IS NULL
+                  AND starts_with(short_url, $1)
+                  AND ($2::TEXT IS NULL
+                       OR strpos(lower(short_url), lower($2)) > 0
+                       OR strpos(lower(COALESCE(title, '')), lower($2)) > 0)
+                ORDER BY 5 DESC, short_url
+                LIMIT $3
+                "#,
+                prefix,
+                query,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        self.retry.run("export_collections", || async {
+            sqlx::query_as!(
+                Collection,
+                "SELECT id, owner, name, created_at FROM collections ORDER BY id"
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        self.retry.run("restore_collection", || async {
+            let inserted = sqlx::query_as!(
+                Collection,
+                r#"
+                INSERT INTO collections (owner, name, created_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (owner, name) DO NOTHING
+                RETURNING id, owner, name, created_at
+                "#,
+                collection.owner,
+                collection.name,
+                collection.created_at
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Self::handle_error)?;
+            if let Some(inserted) = inserted {
+                return Ok((inserted, true));
+            }
+
+            let existing = sqlx::query_as!(
+                Collection,
+                "SELECT id, owner, name, created_at FROM collections WHERE owner = $1 AND name = $2",
+                collection.owner,
+                collection.name
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)?;
+            Ok((existing, false))
+        }).await
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        self.retry.run("export_api_keys", || async {
+            sqlx::query_as!(
+                ExportedApiKey,
+                r#"
+                SELECT id, owner, name, key_hash, prefix, created_at, last_used_at
+                FROM api_keys
+                WHERE revoked_at IS NULL
+                ORDER BY id
+                "#
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        let result = self.retry.run("restore_api_key", || async {
+            sqlx::query!(
+                r#"
+                INSERT INTO api_keys (owner, name, key_hash, prefix, created_at, last_used_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (key_hash) DO NOTHING
+                "#,
+                key.owner,
+                key.name,
+                key.key_hash,
+                key.prefix,
+                key.created_at,
+                key.last_used_at
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool> {
+        self.retry.run("copy_collection", || async {
+            let mut tx = self.begin_tx().await?;
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO collections (id, owner, name, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                "#,
+                collection.id,
+                collection.owner,
+                collection.name,
+                collection.created_at
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            // Explicit IDs bypass the sequence; move it past them so later inserts don't collide
+            sqlx::query!("SELECT setval(pg_get_serial_sequence('collections', 'id'), (SELECT MAX(id) FROM collections))")
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(Self::handle_error)?;
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(result.rows_affected() > 0)
+        }).await
+    }
+
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        self.retry.run("copy_api_key", || async {
+            let mut tx = self.begin_tx().await?;
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO api_keys (id, owner, name, key_hash, prefix, created_at, last_used_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT DO NOTHING
+                "#,
+                key.id,
+                key.owner,
+                key.name,
+                key.key_hash,
+                key.prefix,
+                key.created_at,
+                key.last_used_at
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            sqlx::query!("SELECT setval(pg_get_serial_sequence('api_keys', 'id'), (SELECT MAX(id) FROM api_keys))")
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(Self::handle_error)?;
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(result.rows_affected() > 0)
+        }).await
+    }
+
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        self.retry.run("ban_domain", || async {
+            let mut tx = self.begin_tx().await?;
+            // A repeated ban keeps the original entry but still disables links created since
+            let blocked = sqlx::query_as!(
+                BlockedDomain,
+                r#"
+                INSERT INTO blocked_domains (domain, reason, blocked_by)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (domain) DO UPDATE SET domain = EXCLUDED.domain
+                RETURNING domain, reason, blocked_by, created_at
+                "#,
+                domain,
+                reason,
+                blocked_by
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            // Stored URLs are normalized, so the host is lowercase ASCII between the scheme and the path
+            let disabled = sqlx::query_scalar!(
+                r#"
+                WITH hosts AS (
+                    SELECT id, substring(original_url from '^[^:]+://(?:[^@/?#]*@)?([^/?#:]+)') AS host
+                    FROM shortened_urls
+                    WHERE disabled_at IS NULL
+                )
+                UPDATE shortened_urls u
+                SET disabled_at = CURRENT_TIMESTAMP
+                FROM hosts
+                WHERE u.id = hosts.id
+                  AND (hosts.host = $1 OR right(hosts.host, length($1) + 1) = '.' || $1)
+                RETURNING u.short_url
+                "#,
+                domain
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(DomainBan { blocked, disabled })
+        }).await
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.retry.run("list_blocked_domains", || async {
+            sqlx::query_as!(
+                BlockedDomain,
+                "SELECT domain, reason, blocked_by, created_at FROM blocked_domains ORDER BY domain"
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        // The host itself and each parent domain, e.g. `a.evil.com`, `evil.com` and `com`
+        let candidates: Vec<&str> =
+            std::iter::successors(Some(host), |domain| domain.split_once('.').map(|(_, parent)| parent)).collect();
+
+        self.retry.run("is_domain_blocked", || async {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM blocked_domains WHERE domain = ANY($1)) AS "blocked!""#,
+                &candidates as &[&str]
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.retry.run("disable_urls", || async {
+            sqlx::query_scalar!(
+                r#"
+                UPDATE shortened_urls
+                SET disabled_at = CURRENT_TIMESTAMP
+                WHERE short_url = ANY($1) AND disabled_at IS NULL
+                RETURNING short_url
+                "#,
+                short_codes
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.retry.run("enable_urls", || async {
+            sqlx::query_scalar!(
+                r#"
+                UPDATE shortened_urls
+                SET disabled_at = NULL
+                WHERE short_url = ANY($1) AND disabled_at IS NOT NULL
+                RETURNING short_url
+                "#,
+                short_codes
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        self.retry.run("regenerate_code", || async {
+            let mut tx = self.begin_tx().await?;
+            if Self::redirects_elsewhere_tx(&mut tx, new_code).await? {
+                return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+            }
+
+            let old = sqlx::query!(
+                "SELECT id, visits FROM shortened_urls WHERE short_url = $1 FOR UPDATE",
+                short_code
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+            // Shards are keyed by the code, so outstanding counts are folded in before it changes
+            let drained = sqlx::query_scalar!(
+                r#"
+                WITH drained AS (
+                    DELETE FROM visit_counter_shards WHERE short_url = $1 RETURNING visits
+                )
+                SELECT COALESCE(SUM(visits), 0)::BIGINT AS "visits!" FROM drained
+                "#,
+                short_code
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            let visits = old.visits + drained;
+            let (kept, forked) = match stats {
+                StatsOnRegenerate::Keep => (visits, 0),
+                StatsOnRegenerate::Fork => (0, visits),
+            };
+            let link = sqlx::query_as!(
+                ShortenedUrl,
+                r#"
+                UPDATE shortened_urls
+                SET short_url = $2, visits = $3
+                WHERE id = $1
+                RETURNING id, original_url, short_url, created_at, visits, owner, title, notes, created_by, collection_id, click_goal, goal_reached_at, pass_through, content_type, disabled_at, signed_only, tracking_snippet, redirect_headers AS "redirect_headers: Json<RedirectHeaders>", hide_referrer, last_visited_at, version
+                "#,
+                old.id,
+                new_code,
+                kept
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            let retired = sqlx::query_as!(
+                RetiredCode,
+                r#"
+                INSERT INTO retired_codes (short_code, url_id, visits, redirects_until)
+                VALUES ($1, $2, $3, $4)
+                RETURNING short_code, visits, retired_at, redirects_until
+                "#,
+                short_code,
+                old.id,
+                forked,
+                redirects_until
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(RegeneratedLink { link, retired })
+        }).await
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.retry.run("list_retired_codes", || async {
+            sqlx::query_as!(
+                RetiredCode,
+                r#"
+                SELECT r.short_code, r.visits, r.retired_at, r.redirects_until
+                FROM retired_codes r
+                JOIN shortened_urls u ON u.id = r.url_id
+                WHERE u.short_url = $1
+                ORDER BY r.retired_at DESC, r.id DESC
+                "#,
+                short_code
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        self.retry.run("add_alias", || async {
+            let mut tx = self.begin_tx().await?;
+            let taken = sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM shortened_urls WHERE short_url = $1) AS "taken!""#,
+                alias
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+            if taken || Self::redirects_elsewhere_tx(&mut tx, alias).await? {
+                return Err(UrlShortenerErrorType::Conflict("Short URL already exists".to_string()).into());
+            }
+
+            let added = sqlx::query_as!(
+                LinkAlias,
+                r#"
+                INSERT INTO link_aliases (short_code, url_id)
+                SELECT $2, id FROM shortened_urls WHERE short_url = $1
+                RETURNING short_code, created_at
+                "#,
+                short_code,
+                alias
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(added)
+        }).await
+    }
+
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()> {
+        let result = self.retry.run("remove_alias", || async {
+            sqlx::query!(
+                r#"
+                DELETE FROM link_aliases a
+                USING shortened_urls u
+                WHERE a.url_id = u.id AND u.short_url = $1 AND a.short_code = $2
+                "#,
+                short_code,
+                alias
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.retry.run("list_aliases", || async {
+            sqlx::query_as!(
+                LinkAlias,
+                r#"
+                SELECT a.short_code, a.created_at
+                FROM link_aliases a
+                JOIN shortened_urls u ON u.id = a.url_id
+                WHERE u.short_url = $1
+                ORDER BY a.created_at, a.short_code
+                "#,
+                short_code
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        let mut stored = 0;
+        for chunk in events.chunks(VISIT_BATCH_SIZE) {
+            let codes: Vec<&str> = chunk.iter().map(|event| event.short_code.as_str()).collect();
+            let visited_at: Vec<DateTime<Utc>> = chunk.iter().map(|event| event.visited_at).collect();
+            let visitors: Vec<Option<String>> = chunk.iter().map(|event| event.visitor.clone()).collect();
+            let sources: Vec<Option<String>> = chunk.iter().map(|event| event.source.clone()).collect();
+            let sample_rates: Vec<i32> = chunk.iter().map(|event| event.sample_rate).collect();
+
+            let result = self.retry.run("record_visit_events", || async {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO visit_events (url_id, visited_at, visitor, source, sample_rate)
+                    SELECT u.id, e.visited_at, e.visitor, e.source, e.sample_rate
+                    FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[], $5::INT[])
+                        AS e(short_url, visited_at, visitor, source, sample_rate)
+                    JOIN shortened_urls u ON u.short_url = e.short_url
+                    "#,
+                    &codes as &[&str],
+                    &visited_at,
+                    &visitors as &[Option<String>],
+                    &sources as &[Option<String>],
+                    &sample_rates
+                )
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+            }).await?;
+            stored += result.rows_affected();
+        }
+        Ok(stored)
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        let (after_time, after_id) = after.unwrap_or((from, 0));
+        self.retry.run("export_visit_events", || async {
+            sqlx::query_as!(
+                RecordedVisit,
+                r#"
+                SELECT e.id, u.short_url AS short_code, e.visited_at, e.visitor, e.source, e.sample_rate
+                FROM visit_events e
+                JOIN shortened_urls u ON u.id = e.url_id
+                WHERE e.visited_at >= $1 AND e.visited_at < $2 AND (e.visited_at, e.id) > ($3, $4)
+                ORDER BY e.visited_at, e.id
+                LIMIT $5
+                "#,
+                from,
+                to,
+                after_time,
+                after_id,
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        self.retry.run("visit_timeline", || async {
+            sqlx::query_as!(
+                VisitTimeline,
+                r#"
+                SELECT MIN(e.visited_at) AS first_visited_at, MAX(e.visited_at) AS last_visited_at,
+                       COALESCE(SUM(e.sample_rate) FILTER (WHERE e.visited_at > $2::TIMESTAMPTZ - INTERVAL '1 day'), 0) AS "visits_last_24h!",
+                       COALESCE(SUM(e.sample_rate) FILTER (WHERE e.visited_at > $2::TIMESTAMPTZ - INTERVAL '7 days'), 0) AS "visits_last_7d!"
+                FROM shortened_urls u
+                JOIN visit_events e ON e.url_id = u.id
+                WHERE u.short_url = $1 AND e.visited_at <= $2
+                "#,
+                short_code,
+                now
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        let cells = self.retry.run("visit_heatmap", || async {
+            sqlx::query!(
+                r#"
+                SELECT EXTRACT(ISODOW FROM e.visited_at AT TIME ZONE 'UTC')::INT AS "weekday!",
+                       EXTRACT(HOUR FROM e.visited_at AT TIME ZONE 'UTC')::INT AS "hour!",
+                       SUM(e.sample_rate) AS "visits!"
+                FROM shortened_urls u
+                JOIN visit_events e ON e.url_id = u.id
+                WHERE u.short_url = $1 AND e.visited_at >= $2
+                GROUP BY 1, 2
+                "#,
+                short_code,
+                since
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        let mut heatmap = VisitHeatmap::default();
+        for cell in cells {
+            // ISO weekdays run from 1 for Monday to 7 for Sunday
+            heatmap.visits[(cell.weekday - 1) as usize][cell.hour as usize] = cell.visits;
+        }
+        Ok(heatmap)
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        self.retry.run("daily_link_visits", || async {
+            sqlx::query_as!(
+                LinkDailyVisits,
+                r#"
+                SELECT u.short_url AS short_code, (e.visited_at AT TIME ZONE 'UTC')::DATE AS "date!", SUM(e.sample_rate) AS "visits!"
+                FROM shortened_urls u
+                JOIN visit_events e ON e.url_id = u.id
+                WHERE u.short_url = ANY($1) AND e.visited_at >= $2
+                GROUP BY 1, 2
+                ORDER BY 1, 2
+                "#,
+                short_codes,
+                since
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        self.retry.run("visit_sources", || async {
+            sqlx::query_as!(
+                SourceVisits,
+                r#"
+                SELECT e.source, SUM(e.sample_rate) AS "visits!"
+                FROM shortened_urls u
+                JOIN visit_events e ON e.url_id = u.id
+                WHERE u.short_url = $1 AND e.visited_at >= $2
+                GROUP BY 1
+                ORDER BY 2 DESC, 1 NULLS LAST
+                "#,
+                short_code,
+                since
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        self.retry.run("create_campaign", || async {
+            sqlx::query_as!(
+                Campaign,
+                r#"
+                INSERT INTO campaigns (owner, name)
+                VALUES ($1, $2)
+                RETURNING id, owner, name, created_at
+                "#,
+                owner,
+                name
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        self.retry.run("get_campaign", || async {
+            sqlx::query_as!(Campaign, "SELECT id, owner, name, created_at FROM campaigns WHERE id = $1", id)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.retry.run("list_campaigns", || async {
+            sqlx::query_as!(
+                Campaign,
+                "SELECT id, owner, name, created_at FROM campaigns WHERE owner = $1 ORDER BY name",
+                owner
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()> {
+        let result = self.retry.run("delete_campaign", || async {
+            sqlx::query!("DELETE FROM campaigns WHERE id = $1", id)
+                .execute(&self.pool)
+                .await
+                .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        let found = self.retry.run("add_campaign_link", || async {
+            sqlx::query_scalar!(
+                r#"
+                WITH link AS (
+                    SELECT c.id AS campaign_id, u.id AS url_id
+                    FROM campaigns c, shortened_urls u
+                    WHERE c.id = $1 AND u.short_url = $2
+                ),
+                added AS (
+                    INSERT INTO campaign_links (campaign_id, url_id)
+                    SELECT campaign_id, url_id FROM link
+                    ON CONFLICT DO NOTHING
+                )
+                SELECT EXISTS (SELECT 1 FROM link) AS "found!"
+                "#,
+                id,
+                short_code
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        if !found {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        let result = self.retry.run("remove_campaign_link", || async {
+            sqlx::query!(
+                r#"
+                DELETE FROM campaign_links cl
+                USING shortened_urls u
+                WHERE cl.url_id = u.id AND cl.campaign_id = $1 AND u.short_url = $2
+                "#,
+                id,
+                short_code
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(UrlShortenerErrorType::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        self.retry.run("campaign_stats", || async {
+            let mut tx = self.begin_tx().await?;
+            // Counts still in shards are part of the totals
+            let totals = sqlx::query!(
+                r#"
+                SELECT c.id, c.owner, c.name, c.created_at,
+                       COUNT(u.id) AS "links!",
+                       COALESCE(SUM(u.visits + COALESCE((SELECT SUM(s.visits) FROM visit_counter_shards s WHERE s.short_url = u.short_url), 0)), 0)::BIGINT AS "visits!"
+                FROM campaigns c
+                LEFT JOIN campaign_links cl ON cl.campaign_id = c.id
+                LEFT JOIN shortened_urls u ON u.id = cl.url_id
+                WHERE c.id = $1
+                GROUP BY c.id
+                "#,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            // One pass over the events gives every day and, in the rollup row without a date,
+            // the whole period; visitors seen on several days count once there
+            let rows = sqlx::query!(
+                r#"
+                SELECT (e.visited_at AT TIME ZONE 'UTC')::DATE AS date,
+                       COALESCE(SUM(e.sample_rate), 0) AS "visits!", COUNT(DISTINCT e.visitor) AS "uniques!"
+                FROM campaign_links cl
+                JOIN visit_events e ON e.url_id = cl.url_id
+                WHERE cl.campaign_id = $1 AND e.visited_at >= $2
+                GROUP BY ROLLUP (1)
+                ORDER BY 1
+                "#,
+                id,
+                since
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+            tx.commit().await.map_err(Self::handle_error)?;
+
+            let mut stats = CampaignStats {
+                campaign: Campaign {
+                    id: totals.id,
+                    owner: totals.owner,
+                    name: totals.name,
+                    created_at: totals.created_at,
+                },
+                links: totals.links,
+                visits: totals.visits,
+                period_visits: 0,
+                uniques: 0,
+                daily: Vec::new(),
+            };
+            for row in rows {
+                match row.date {
+                    Some(date) => stats.daily.push(DailyVisits { date, visits: row.visits, uniques: row.uniques }),
+                    None => (stats.period_visits, stats.uniques) = (row.visits, row.uniques),
+                }
+            }
+            Ok(stats)
+        }).await
+    }
+
+    fn backend(&self) -> String {
+        "postgres".to_string()
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        sqlx::query!("SELECT 1 AS one")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(Self::handle_error)?;
+        Ok(())
+    }
+
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool> {
+        self.retry.run("copy_campaign", || async {
+            let mut tx = self.begin_tx().await?;
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO campaigns (id, owner, name, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                "#,
+                campaign.id,
+                campaign.owner,
+                campaign.name,
+                campaign.created_at
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Self::handle_error)?;
+
+            // Explicit IDs bypass the sequence; move it past them so later inserts don't collide
+            sqlx::query!("SELECT setval(pg_get_serial_sequence('campaigns', 'id'), (SELECT MAX(id) FROM campaigns))")
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(Self::handle_error)?;
+            tx.commit().await.map_err(Self::handle_error)?;
+            Ok(result.rows_affected() > 0)
+        }).await
+    }
+
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64> {
+        let start = day.and_time(NaiveTime::MIN).and_utc();
+        let end = start + chrono::Duration::days(1);
+        let result = self.retry.run("roll_up_usage", || async {
+            sqlx::query!(
+                r#"
+                WITH links AS (
+                    SELECT owner,
+                           COUNT(*) FILTER (WHERE created_at >= $2) AS creations,
+                           COUNT(*) AS stored_links
+                    FROM shortened_urls
+                    WHERE owner IS NOT NULL AND created_at < $3
+                    GROUP BY owner
+                ), visits AS (
+                    SELECT u.owner, SUM(e.sample_rate)::BIGINT AS redirects
+                    FROM visit_events e
+                    JOIN shortened_urls u ON u.id = e.url_id
+                    WHERE u.owner IS NOT NULL AND e.visited_at >= $2 AND e.visited_at < $3
+                    GROUP BY u.owner
+                )
+                INSERT INTO usage_records (day, owner, creations, redirects, stored_links)
+                SELECT $1, l.owner, l.creations, COALESCE(v.redirects, 0), l.stored_links
+                FROM links l
+                LEFT JOIN visits v ON v.owner = l.owner
+                ON CONFLICT (day, owner) DO UPDATE
+                SET creations = EXCLUDED.creations,
+                    redirects = EXCLUDED.redirects,
+                    stored_links = EXCLUDED.stored_links,
+                    updated_at = NOW()
+                "#,
+                day,
+                start,
+                end
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.retry.run("list_usage", || async {
+            sqlx::query_as!(
+                UsageRecord,
+                r#"
+                SELECT day, owner, creations, redirects, stored_links
+                FROM usage_records
+                WHERE day >= $1 AND day < $2
+                ORDER BY owner, day
+                "#,
+                from,
+                to
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Self::handle_error)
+        }).await
+    }
+
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        Some(StatementCacheStats {
+            capacity: self.statement_cache_capacity,
+            hits: self.statement_hits.load(Ordering::Relaxed),
+            misses: self.statement_misses.load(Ordering::Relaxed),
+        })
+    }
+}
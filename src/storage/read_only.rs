@@ -0,0 +1,414 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::{StatementCacheStats, Storage, StorageRef};
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DomainBan, ExportedApiKey,
+    IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink, RetiredCode, Session,
+    ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline, WebhookDelivery,
+};
+
+/// Serves reads from another backend and rejects every write with
+/// [`ReadOnly`](UrlShortenerErrorType::ReadOnly), for instances that must not change the
+/// database, or cannot, like those on a read replica.
+///
+/// Links, API keys and sessions are still looked up, without counting a visit or recording
+/// when they were last used.
+pub struct ReadOnlyStorage {
+    inner: StorageRef,
+}
+
+impl ReadOnlyStorage {
+    pub fn new(inner: StorageRef) -> Self {
+        Self { inner }
+    }
+}
+
+fn read_only() -> UrlShortenerError {
+    UrlShortenerErrorType::ReadOnly.into()
+}
+
+#[async_trait]
+impl Storage for ReadOnlyStorage {
+    async fn save_url(&self, _url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        Err(read_only())
+    }
+
+    /// The same lookup, without counting a visit
+    async fn get_url(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.inner.get_stats(short_code).await
+    }
+
+    async fn get_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.inner.get_stats(short_code).await
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        self.inner.get_stats_many(short_codes).await
+    }
+
+    async fn bulk_insert(&self, _urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn record_visits(&self, _short_code: &str, _count: u64) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn record_visit_batch(&self, _visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.inner.list_urls_by_owner(owner, collection_id, after, limit).await
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.inner.list_urls(owner, sort, filter, after, limit).await
+    }
+
+    async fn update_metadata(&self, _short_code: &str, _update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        Err(read_only())
+    }
+
+    async fn transfer_url(&self, _short_code: &str, _from: &str, _to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        Err(read_only())
+    }
+
+    async fn create_collection(&self, _owner: &str, _name: &str) -> UrlShortenerResult<Collection> {
+        Err(read_only())
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        self.inner.get_collection(id).await
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.inner.list_collections(owner).await
+    }
+
+    async fn rename_collection(&self, _id: i64, _name: &str) -> UrlShortenerResult<Collection> {
+        Err(read_only())
+    }
+
+    async fn delete_collection(&self, _id: i64) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn claim_reached_goals(&self, _limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        Err(read_only())
+    }
+
+    async fn release_goal(&self, _short_code: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn create_api_key(
+        &self,
+        _owner: &str,
+        _name: Option<&str>,
+        _key_hash: &str,
+        _prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        Err(read_only())
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.get_api_key(id).await
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.inner.list_api_keys(owner).await
+    }
+
+    async fn revoke_api_key(&self, _id: i64) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn rotate_api_key(&self, _id: i64, _key_hash: &str, _prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        Err(read_only())
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.find_api_key(key_hash).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.inner.find_api_key(key_hash).await
+    }
+
+    async fn create_session(&self, _session: &Session) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.inner.find_session(id, idle_cutoff).await
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.inner.find_session(id, idle_cutoff).await
+    }
+
+    async fn delete_session(&self, _id: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn purge_idle_sessions(&self, _idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn change_visits(&self, _short_code: &str, _change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        Err(read_only())
+    }
+
+    async fn record_audit(&self, _entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        Err(read_only())
+    }
+
+    async fn import_urls(&self, _urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        self.inner.count_urls(owner).await
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        self.inner.oldest_unvisited_urls(owner, limit).await
+    }
+
+    async fn delete_unvisited_urls(&self, _short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        Err(read_only())
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        self.inner.list_audit_entries(limit).await
+    }
+
+    async fn purge_audit_entries(&self, _before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn record_webhook_delivery(&self, _delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        Err(read_only())
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.inner.list_webhook_deliveries(webhook, before, limit).await
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        self.inner.get_webhook_delivery(webhook, id).await
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.inner.export_urls(after, limit).await
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.inner.top_visited_urls(limit).await
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.inner.search_urls(prefix, query, limit).await
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        self.inner.export_collections().await
+    }
+
+    async fn restore_collection(&self, _collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        Err(read_only())
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        self.inner.export_api_keys().await
+    }
+
+    async fn restore_api_key(&self, _key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        Err(read_only())
+    }
+
+    async fn copy_collection(&self, _collection: &Collection) -> UrlShortenerResult<bool> {
+        Err(read_only())
+    }
+
+    async fn copy_api_key(&self, _key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        Err(read_only())
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        _scope: &str,
+        _key: &str,
+        _fingerprint: &str,
+        _expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        Err(read_only())
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        _scope: &str,
+        _key: &str,
+        _response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn release_idempotency_key(&self, _scope: &str, _key: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn purge_idempotency_keys(&self, _before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn ban_domain(&self, _domain: &str, _reason: Option<&str>, _blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        Err(read_only())
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.inner.list_blocked_domains().await
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        self.inner.is_domain_blocked(host).await
+    }
+
+    async fn disable_urls(&self, _short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        Err(read_only())
+    }
+
+    async fn enable_urls(&self, _short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        Err(read_only())
+    }
+
+    async fn regenerate_code(
+        &self,
+        _short_code: &str,
+        _new_code: &str,
+        _stats: StatsOnRegenerate,
+        _redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        Err(read_only())
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.inner.list_retired_codes(short_code).await
+    }
+
+    async fn add_alias(&self, _short_code: &str, _alias: &str) -> UrlShortenerResult<LinkAlias> {
+        Err(read_only())
+    }
+
+    async fn remove_alias(&self, _short_code: &str, _alias: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.inner.list_aliases(short_code).await
+    }
+
+    async fn record_visit_events(&self, _events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        self.inner.export_visit_events(from, to, after, limit).await
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        self.inner.visit_timeline(short_code, now).await
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        self.inner.visit_heatmap(short_code, since).await
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        self.inner.daily_link_visits(short_codes, since).await
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        self.inner.visit_sources(short_code, since).await
+    }
+
+    async fn create_campaign(&self, _owner: &str, _name: &str) -> UrlShortenerResult<Campaign> {
+        Err(read_only())
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        self.inner.get_campaign(id).await
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.inner.list_campaigns(owner).await
+    }
+
+    async fn delete_campaign(&self, _id: i64) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn add_campaign_link(&self, _id: i64, _short_code: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn remove_campaign_link(&self, _id: i64, _short_code: &str) -> UrlShortenerResult<()> {
+        Err(read_only())
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        self.inner.campaign_stats(id, since).await
+    }
+
+    async fn roll_up_usage(&self, _day: NaiveDate) -> UrlShortenerResult<u64> {
+        Err(read_only())
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.inner.list_usage(from, to).await
+    }
+
+    /// Rejecting writes is not a backend of its own
+    fn backend(&self) -> String {
+        self.inner.backend()
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        self.inner.ping().await
+    }
+
+    async fn copy_campaign(&self, _campaign: &Campaign) -> UrlShortenerResult<bool> {
+        Err(read_only())
+    }
+
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        self.inner.statement_cache_stats()
+    }
+}
@@ -0,0 +1,77 @@
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::deadline::current_deadline;
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+
+/// Default attempts per storage operation, including the first one
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Retries storage operations that fail with a retryable error, backing off exponentially.
+///
+/// Permanent errors (not found, conflicts, validation) are returned immediately; only errors
+/// classified by [`UrlShortenerErrorType::is_retryable`](crate::errors::UrlShortenerErrorType::is_retryable)
+/// are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per operation, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further retry
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self { max_attempts: max_attempts.max(1), base_delay }
+    }
+
+    /// Runs `operation`, retrying it while it fails with a retryable error. Inside a request
+    /// with a deadline, an attempt still running at the deadline is cancelled and the operation
+    /// fails with `DeadlineExceeded`.
+    pub async fn run<T, F, Fut>(&self, name: &str, mut operation: F) -> UrlShortenerResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = UrlShortenerResult<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = match current_deadline() {
+                Some(deadline) => match tokio::time::timeout_at(deadline, operation()).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        // Clients may retry with a new deadline, but another attempt within
+                        // this request would be cancelled straight away
+                        warn!(operation = name, attempt, "Storage operation cancelled at the request deadline");
+                        return Err(UrlShortenerErrorType::DeadlineExceeded.into());
+                    }
+                },
+                None => operation().await,
+            };
+            match result {
+                Err(e) if e.error_type.is_retryable() && attempt < self.max_attempts => {
+                    let delay = self.base_delay * 2u32.saturating_pow(attempt - 1);
+                    warn!(
+                        operation = name,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "Retrying storage operation"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
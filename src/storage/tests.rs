@@ -0,0 +1,365 @@
+use super::*;
+use crate::errors::UrlShortenerErrorType;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn fast_policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy::new(max_attempts, Duration::from_millis(1))
+}
+
+#[tokio::test]
+async fn test_retry_policy_retries_transient_errors() {
+    let calls = AtomicU32::new(0);
+    let result = fast_policy(3)
+        .run("test", || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(UrlShortenerErrorType::ConnectionError("down".to_string()).into())
+            } else {
+                Ok("saved")
+            }
+        })
+        .await;
+
+    assert_eq!(result.unwrap(), "saved");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_policy_cancels_operations_at_the_request_deadline() {
+    use crate::deadline::with_deadline;
+    use tokio::time::Instant;
+
+    let calls = AtomicU32::new(0);
+    let slow = || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok(())
+    };
+    let started = Instant::now();
+    let result = with_deadline(started + Duration::from_millis(50), fast_policy(3).run("slow", slow)).await;
+    assert!(matches!(result.unwrap_err().error_type, UrlShortenerErrorType::DeadlineExceeded));
+    assert!(started.elapsed() < Duration::from_secs(1));
+    // Retryable for clients, but not retried within the request whose deadline passed
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Operations finishing in time are unaffected
+    let result = with_deadline(Instant::now() + Duration::from_secs(5), fast_policy(3).run("fast", || async { Ok(7) })).await;
+    assert_eq!(result.unwrap(), 7);
+}
+
+#[tokio::test]
+async fn test_retry_policy_gives_up_after_max_attempts() {
+    let calls = AtomicU32::new(0);
+    let result: crate::errors::UrlShortenerResult<()> = fast_policy(2)
+        .run("test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(UrlShortenerErrorType::ConnectionError("down".to_string()).into())
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_retry_policy_returns_permanent_errors_immediately() {
+    let calls = AtomicU32::new(0);
+    let result: crate::errors::UrlShortenerResult<()> = fast_policy(3)
+        .run("test", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(UrlShortenerErrorType::NotFound.into())
+        })
+        .await;
+
+    assert!(matches!(result.unwrap_err().error_type, UrlShortenerErrorType::NotFound));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+fn test_url(short_url: &str) -> crate::models::ShortenedUrl {
+    crate::models::ShortenedUrl {
+        original_url: format!("https://example.com/{}", short_url),
+        short_url: short_url.to_string(),
+        owner: Some("alice".to_string()),
+        ..crate::models::ShortenedUrl::default()
+    }
+}
+
+fn migrating_pair() -> (Arc<MemoryStorage>, Arc<MemoryStorage>, MigratingStorage) {
+    let old = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let new = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let migrating = MigratingStorage::new(old.clone(), new.clone());
+    (old, new, migrating)
+}
+
+#[tokio::test]
+async fn test_migrating_storage_writes_both_backends() {
+    let (old, new, migrating) = migrating_pair();
+    migrating.save_url(test_url("both")).await.unwrap();
+    migrating.record_visits("both", 3).await.unwrap();
+    let collection = migrating.create_collection("alice", "Launch").await.unwrap();
+
+    assert_eq!(old.get_stats("both").await.unwrap().visits, 3);
+    assert_eq!(new.get_stats("both").await.unwrap().visits, 3);
+    assert_eq!(new.get_collection(collection.id).await.unwrap().name, "Launch");
+}
+
+#[tokio::test]
+async fn test_migrating_storage_reads_fall_back_to_old_backend() {
+    let (old, new, migrating) = migrating_pair();
+    old.save_url(test_url("legacy")).await.unwrap();
+
+    // Writes to records the new backend lacks only reach the old one
+    migrating.record_visits("legacy", 2).await.unwrap();
+    assert_eq!(migrating.get_stats("legacy").await.unwrap().visits, 2);
+    assert!(new.get_stats("legacy").await.is_err());
+
+    let err = migrating.get_stats("missing").await.unwrap_err();
+    assert!(matches!(err.error_type, UrlShortenerErrorType::NotFound));
+}
+
+#[tokio::test]
+async fn test_backfill_copies_missing_records_under_the_same_ids() {
+    let (old, new, migrating) = migrating_pair();
+    old.create_collection("bob", "Misc").await.unwrap();
+    let collection = old.create_collection("alice", "Launch").await.unwrap();
+    let key = old.create_api_key("alice", None, "hash-1", "um_1234abcd").await.unwrap();
+    old.save_url(crate::models::ShortenedUrl { collection_id: Some(collection.id), ..test_url("legacy") })
+        .await
+        .unwrap();
+    migrating.save_url(test_url("recent")).await.unwrap();
+
+    let summary = migrating.backfill().await.unwrap();
+    assert_eq!(summary, BackfillSummary { collections: 2, api_keys: 1, links: 1, skipped: 1 });
+    assert_eq!(new.get_stats("legacy").await.unwrap().collection_id, Some(collection.id));
+    assert_eq!(new.get_api_key(key.id).await.unwrap().owner, "alice");
+
+    // Once backfilled, writes by ID reach the new backend too
+    migrating.rename_collection(collection.id, "Relaunch").await.unwrap();
+    assert_eq!(new.get_collection(collection.id).await.unwrap().name, "Relaunch");
+    assert_eq!(migrating.backfill().await.unwrap().skipped, 5);
+}
+
+fn encrypted_storage() -> (Arc<MemoryStorage>, EncryptedStorage) {
+    let inner = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let cipher = UrlCipher::new(&[7u8; 32], &[] as &[&[u8]]).unwrap();
+    (inner.clone(), EncryptedStorage::new(inner, cipher))
+}
+
+#[tokio::test]
+async fn test_encrypted_storage_stores_ciphertext_and_returns_plaintext() {
+    let (inner, encrypted) = encrypted_storage();
+    let saved = encrypted.save_url(test_url("secret")).await.unwrap();
+    assert_eq!(saved.original_url, "https://example.com/secret");
+
+    let stored = inner.get_stats("secret").await.unwrap().original_url;
+    assert!(stored.starts_with("enc:v1:"));
+    assert!(!stored.contains("example.com"));
+    assert_eq!(encrypted.get_url("secret").await.unwrap().original_url, "https://example.com/secret");
+
+    // Links stored before encryption was enabled are still readable
+    inner.save_url(test_url("legacy")).await.unwrap();
+    assert_eq!(encrypted.get_stats("legacy").await.unwrap().original_url, "https://example.com/legacy");
+}
+
+#[tokio::test]
+async fn test_timed_storage_counts_calls_over_the_threshold() {
+    let inner: StorageRef = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let timed = TimedStorage::new(inner.clone(), Duration::ZERO);
+    timed.save_url(test_url("timed")).await.unwrap();
+    timed.get_url("timed").await.unwrap();
+    assert!(timed.get_stats("missing").await.is_err());
+
+    let stats = timed.slow_query_stats().unwrap();
+    assert_eq!(stats.slow_queries_total, 3);
+    assert_eq!(stats.by_operation.get("get_stats"), Some(&1));
+    assert_eq!(stats.by_operation.get("save_url"), Some(&1));
+    assert_eq!(inner.slow_query_stats(), None);
+
+    let patient = TimedStorage::new(inner, Duration::from_secs(3600));
+    patient.get_url("timed").await.unwrap();
+    assert_eq!(patient.slow_query_stats().unwrap().slow_queries_total, 0);
+}
+
+#[test]
+fn test_url_cipher_decrypts_with_retired_keys() {
+    let old = UrlCipher::new(&[1u8; 32], &[] as &[&[u8]]).unwrap();
+    let rotated = UrlCipher::new(&[2u8; 32], &[[1u8; 32]]).unwrap();
+    let stored = old.encrypt("https://intranet.example.com/doc");
+
+    assert_ne!(old.key_id(), rotated.key_id());
+    assert_ne!(stored, old.encrypt("https://intranet.example.com/doc"));
+    assert_eq!(rotated.decrypt(&stored).unwrap(), "https://intranet.example.com/doc");
+
+    let unknown = UrlCipher::new(&[3u8; 32], &[] as &[&[u8]]).unwrap();
+    assert!(unknown.decrypt(&stored).is_err());
+    let mut tampered = stored.clone();
+    tampered.replace_range(tampered.len() - 4.., "AAAA");
+    assert!(rotated.decrypt(&tampered).is_err());
+    assert!(UrlCipher::new(&[1u8; 16], &[] as &[&[u8]]).is_err());
+}
+
+#[tokio::test]
+async fn test_encrypted_storage_bans_domains_by_decrypted_destination() {
+    let (_, encrypted) = encrypted_storage();
+    encrypted.save_url(test_url("a")).await.unwrap();
+    encrypted
+        .save_url(crate::models::ShortenedUrl {
+            original_url: "https://other.org/".to_string(),
+            ..test_url("b")
+        })
+        .await
+        .unwrap();
+
+    let ban = encrypted.ban_domain("example.com", None, "ops").await.unwrap();
+    assert_eq!(ban.disabled, vec!["a".to_string()]);
+    assert!(encrypted.get_stats("a").await.unwrap().disabled_at.is_some());
+    assert!(encrypted.get_stats("b").await.unwrap().disabled_at.is_none());
+}
+
+#[tokio::test]
+async fn test_regenerated_codes_redirect_until_their_grace_period_ends() {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    storage.save_url(crate::models::ShortenedUrl { visits: 5, ..test_url("first") }).await.unwrap();
+    let later = Utc::now() + chrono::Duration::hours(1);
+
+    let kept = storage.regenerate_code("first", "second", StatsOnRegenerate::Keep, later).await.unwrap();
+    assert_eq!((kept.link.short_url.as_str(), kept.link.visits, kept.retired.visits), ("second", 5, 0));
+    assert_eq!(storage.get_url("first").await.unwrap().short_url, "second");
+    assert_eq!(storage.get_stats("second").await.unwrap().visits, 6);
+    let taken = storage.save_url(test_url("first")).await.unwrap_err();
+    assert!(matches!(taken.error_type, UrlShortenerErrorType::Conflict(_)));
+
+    // Forked visits stay with the retired code, and earlier codes follow the link
+    let forked = storage.regenerate_code("second", "third", StatsOnRegenerate::Fork, Utc::now()).await.unwrap();
+    assert_eq!((forked.link.visits, forked.retired.visits), (0, 6));
+    assert_eq!(storage.get_stats("first").await.unwrap().short_url, "third");
+    assert!(storage.get_stats("second").await.is_err());
+    storage.save_url(test_url("second")).await.unwrap();
+
+    let retired = storage.list_retired_codes("third").await.unwrap();
+    let codes: Vec<&str> = retired.iter().map(|code| code.short_code.as_str()).collect();
+    assert_eq!(codes, ["second", "first"]);
+    let conflict = storage.regenerate_code("third", "second", StatsOnRegenerate::Keep, later).await.unwrap_err();
+    assert!(matches!(conflict.error_type, UrlShortenerErrorType::Conflict(_)));
+}
+
+#[tokio::test]
+async fn test_get_stats_many_resolves_aliases_and_retired_codes() {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    storage.save_url(test_url("main")).await.unwrap();
+    storage.save_url(test_url("old")).await.unwrap();
+    storage.add_alias("main", "promo").await.unwrap();
+    storage.regenerate_code("old", "new", StatsOnRegenerate::Keep, Utc::now() + chrono::Duration::hours(1)).await.unwrap();
+
+    let codes = ["main", "promo", "old", "missing"].map(String::from);
+    let found = storage.get_stats_many(&codes).await.unwrap();
+    let pairs: Vec<(&str, &str)> = found.iter().map(|(code, url)| (code.as_str(), url.short_url.as_str())).collect();
+    assert_eq!(pairs, [("main", "main"), ("promo", "main"), ("old", "new")]);
+    assert_eq!(storage.get_stats("main").await.unwrap().visits, 0);
+}
+
+#[tokio::test]
+async fn test_aliases_resolve_to_their_link_and_follow_regeneration() {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    storage.save_url(test_url("main")).await.unwrap();
+    storage.save_url(test_url("other")).await.unwrap();
+
+    storage.add_alias("main", "promo").await.unwrap();
+    storage.add_alias("main", "launch").await.unwrap();
+    assert_eq!(storage.get_url("promo").await.unwrap().short_url, "main");
+    assert_eq!(storage.get_stats("main").await.unwrap().visits, 1);
+    for taken in ["other", "launch"] {
+        let conflict = storage.add_alias("main", taken).await.unwrap_err();
+        assert!(matches!(conflict.error_type, UrlShortenerErrorType::Conflict(_)));
+    }
+    assert!(storage.save_url(test_url("promo")).await.is_err());
+    assert!(storage.add_alias("missing", "spare").await.is_err());
+
+    storage.regenerate_code("main", "fresh", StatsOnRegenerate::Keep, Utc::now()).await.unwrap();
+    assert_eq!(storage.get_stats("promo").await.unwrap().short_url, "fresh");
+    let aliases = storage.list_aliases("fresh").await.unwrap();
+    let codes: Vec<&str> = aliases.iter().map(|alias| alias.short_code.as_str()).collect();
+    assert_eq!(codes, ["promo", "launch"]);
+
+    assert!(storage.remove_alias("other", "promo").await.is_err());
+    storage.remove_alias("fresh", "promo").await.unwrap();
+    assert!(storage.get_url("promo").await.is_err());
+    storage.save_url(test_url("promo")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_campaign_stats_aggregate_member_links() {
+    let storage = MemoryStorage::new(StorageConfig::default());
+    for code in ["spring", "summer", "other"] {
+        storage.save_url(crate::models::ShortenedUrl { visits: 2, ..test_url(code) }).await.unwrap();
+    }
+    let campaign = storage.create_campaign("alice", "Launch").await.unwrap();
+    for code in ["spring", "summer", "spring"] {
+        storage.add_campaign_link(campaign.id, code).await.unwrap();
+    }
+    assert!(storage.add_campaign_link(campaign.id, "missing").await.is_err());
+
+    let now = Utc::now();
+    let event = |code: &str, days_ago: i64, visitor: Option<&str>| crate::models::VisitEvent {
+        short_code: code.to_string(),
+        visited_at: now - chrono::Duration::days(days_ago),
+        visitor: visitor.map(str::to_string),
+        source: None,
+        sample_rate: 1,
+    };
+    let events = [
+        event("spring", 0, Some("a")),
+        event("summer", 0, Some("a")),
+        event("summer", 1, Some("b")),
+        event("spring", 1, None),
+        event("other", 0, Some("c")),
+        event("spring", 40, Some("d")),
+        event("gone", 0, Some("e")),
+    ];
+    assert_eq!(storage.record_visit_events(&events).await.unwrap(), 6);
+
+    // Events follow their link to a new code
+    storage.regenerate_code("summer", "autumn", StatsOnRegenerate::Keep, now).await.unwrap();
+    let stats = storage.campaign_stats(campaign.id, now - chrono::Duration::days(30)).await.unwrap();
+    assert_eq!((stats.links, stats.visits, stats.period_visits, stats.uniques), (2, 4, 4, 2));
+    let daily: Vec<_> = stats.daily.iter().map(|day| (day.date, day.visits, day.uniques)).collect();
+    let today = now.date_naive();
+    assert_eq!(daily, [(today - chrono::Duration::days(1), 2, 1), (today, 2, 1)]);
+
+    storage.remove_campaign_link(campaign.id, "autumn").await.unwrap();
+    assert!(storage.remove_campaign_link(campaign.id, "autumn").await.is_err());
+    assert_eq!(storage.campaign_stats(campaign.id, now).await.unwrap().links, 1);
+    storage.delete_campaign(campaign.id).await.unwrap();
+    assert!(storage.campaign_stats(campaign.id, now).await.is_err());
+}
+
+#[tokio::test]
+async fn test_read_only_storage_serves_reads_and_rejects_writes() {
+    let inner = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    inner.save_url(test_url("promo")).await.unwrap();
+    let storage = ReadOnlyStorage::new(inner.clone());
+
+    assert_eq!(storage.get_url("promo").await.unwrap().short_url, "promo");
+    assert_eq!(inner.get_stats("promo").await.unwrap().visits, 0);
+    assert_eq!(storage.count_urls(None).await.unwrap(), 1);
+
+    let is_read_only = |result: UrlShortenerResult<()>| matches!(result.unwrap_err().error_type, UrlShortenerErrorType::ReadOnly);
+    assert!(is_read_only(storage.save_url(test_url("other")).await.map(|_| ())));
+    assert!(is_read_only(storage.record_visits("promo", 1).await));
+    assert!(is_read_only(storage.disable_urls(&["promo".to_string()]).await.map(|_| ())));
+    assert!(is_read_only(storage.create_collection("alice", "Launch").await.map(|_| ())));
+    assert!(inner.get_url("other").await.is_err());
+    assert!(inner.get_url("promo").await.unwrap().disabled_at.is_none());
+
+    // Keys authenticate without recording their use
+    inner.create_api_key("alice", None, "hash", "um_1").await.unwrap();
+    assert_eq!(storage.authenticate_api_key("hash").await.unwrap().owner, "alice");
+    assert!(inner.find_api_key("hash").await.unwrap().last_used_at.is_none());
+}
+
+#[test]
+fn test_schema_mismatch_policy_parses() {
+    assert_eq!("refuse".parse::<SchemaMismatch>(), Ok(SchemaMismatch::Refuse));
+    assert_eq!(" Read-Only ".parse::<SchemaMismatch>(), Ok(SchemaMismatch::ReadOnly));
+    assert!("ignore".parse::<SchemaMismatch>().is_err());
+}
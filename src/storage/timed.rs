@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{StatementCacheStats, Storage, StorageRef};
+use crate::errors::UrlShortenerResult;
+use crate::models::{
+    AuditEntry, BlockedDomain, Campaign, CampaignStats, Collection, CollectionStats, DomainBan, ExportedApiKey,
+    IdempotencyRecord, IdempotentResponse, LinkAlias, LinkDailyVisits, MetadataUpdate, NewAuditEntry, NewWebhookDelivery, PageCursor, RecordedVisit, RegeneratedLink, RetiredCode, Session,
+    ShortenedUrl, SourceVisits, StatsOnRegenerate, StoredApiKey, UrlCursor, UrlFilter, UrlSort, UsageRecord, VisitChange, VisitCounts, VisitEvent, VisitHeatmap, VisitTimeline, WebhookDelivery,
+};
+
+/// Storage calls that took at least the threshold, since startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlowQueryStats {
+    pub threshold_ms: u64,
+    pub slow_queries_total: u64,
+    /// Slow calls per storage operation
+    pub by_operation: BTreeMap<String, u64>,
+}
+
+/// Times every call to another backend, logging and counting the ones that take at least
+/// `threshold`. Calls are timed as a whole, retries and decryption included.
+pub struct TimedStorage {
+    inner: StorageRef,
+    threshold: Duration,
+    slow_queries_total: AtomicU64,
+    by_operation: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl TimedStorage {
+    pub fn new(inner: StorageRef, threshold: Duration) -> Self {
+        Self {
+            inner,
+            threshold,
+            slow_queries_total: AtomicU64::new(0),
+            by_operation: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    async fn timed<T>(&self, operation: &'static str, short_code: Option<&str>, call: impl Future<Output = T>) -> T {
+        let started = Instant::now();
+        let result = call.await;
+        let elapsed = started.elapsed();
+        if elapsed >= self.threshold {
+            self.record(operation, short_code, elapsed);
+        }
+        result
+    }
+
+    fn record(&self, operation: &'static str, short_code: Option<&str>, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_millis() as u64;
+        match short_code {
+            Some(short_code) => warn!(operation, short_code = %short_code, elapsed_ms, "Slow storage call"),
+            None => warn!(operation, elapsed_ms, "Slow storage call"),
+        }
+        self.slow_queries_total.fetch_add(1, Ordering::Relaxed);
+        let mut by_operation = self.by_operation.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *by_operation.entry(operation).or_default() += 1;
+    }
+}
+
+#[async_trait]
+impl Storage for TimedStorage {
+    async fn save_url(&self, url: ShortenedUrl) -> UrlShortenerResult<ShortenedUrl> {
+        let short_code = url.short_url.clone();
+        self.timed("save_url", Some(&short_code), self.inner.save_url(url)).await
+    }
+
+    async fn get_url(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.timed("get_url", Some(short_code), self.inner.get_url(short_code)).await
+    }
+
+    async fn get_stats(&self, short_code: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.timed("get_stats", Some(short_code), self.inner.get_stats(short_code)).await
+    }
+
+    async fn get_stats_many(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<(String, ShortenedUrl)>> {
+        self.timed("get_stats_many", None, self.inner.get_stats_many(short_codes)).await
+    }
+
+    async fn bulk_insert(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        self.timed("bulk_insert", None, self.inner.bulk_insert(urls)).await
+    }
+
+    async fn record_visits(&self, short_code: &str, count: u64) -> UrlShortenerResult<()> {
+        self.timed("record_visits", Some(short_code), self.inner.record_visits(short_code, count)).await
+    }
+
+    async fn record_visit_batch(&self, visits: &[(String, u64)]) -> UrlShortenerResult<u64> {
+        self.timed("record_visit_batch", None, self.inner.record_visit_batch(visits)).await
+    }
+
+    async fn list_urls_by_owner(
+        &self,
+        owner: &str,
+        collection_id: Option<i64>,
+        after: Option<PageCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("list_urls_by_owner", None, self.inner.list_urls_by_owner(owner, collection_id, after, limit)).await
+    }
+
+    async fn list_urls(
+        &self,
+        owner: &str,
+        sort: UrlSort,
+        filter: &UrlFilter,
+        after: Option<UrlCursor>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("list_urls", None, self.inner.list_urls(owner, sort, filter, after, limit)).await
+    }
+
+    async fn update_metadata(&self, short_code: &str, update: MetadataUpdate) -> UrlShortenerResult<ShortenedUrl> {
+        self.timed("update_metadata", Some(short_code), self.inner.update_metadata(short_code, update)).await
+    }
+
+    async fn transfer_url(&self, short_code: &str, from: &str, to: &str) -> UrlShortenerResult<ShortenedUrl> {
+        self.timed("transfer_url", Some(short_code), self.inner.transfer_url(short_code, from, to)).await
+    }
+
+    async fn create_collection(&self, owner: &str, name: &str) -> UrlShortenerResult<Collection> {
+        self.timed("create_collection", None, self.inner.create_collection(owner, name)).await
+    }
+
+    async fn get_collection(&self, id: i64) -> UrlShortenerResult<CollectionStats> {
+        self.timed("get_collection", None, self.inner.get_collection(id)).await
+    }
+
+    async fn list_collections(&self, owner: &str) -> UrlShortenerResult<Vec<CollectionStats>> {
+        self.timed("list_collections", None, self.inner.list_collections(owner)).await
+    }
+
+    async fn rename_collection(&self, id: i64, name: &str) -> UrlShortenerResult<Collection> {
+        self.timed("rename_collection", None, self.inner.rename_collection(id, name)).await
+    }
+
+    async fn delete_collection(&self, id: i64) -> UrlShortenerResult<()> {
+        self.timed("delete_collection", None, self.inner.delete_collection(id)).await
+    }
+
+    async fn claim_reached_goals(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("claim_reached_goals", None, self.inner.claim_reached_goals(limit)).await
+    }
+
+    async fn release_goal(&self, short_code: &str) -> UrlShortenerResult<()> {
+        self.timed("release_goal", Some(short_code), self.inner.release_goal(short_code)).await
+    }
+
+    async fn create_api_key(
+        &self,
+        owner: &str,
+        name: Option<&str>,
+        key_hash: &str,
+        prefix: &str,
+    ) -> UrlShortenerResult<StoredApiKey> {
+        self.timed("create_api_key", None, self.inner.create_api_key(owner, name, key_hash, prefix)).await
+    }
+
+    async fn get_api_key(&self, id: i64) -> UrlShortenerResult<StoredApiKey> {
+        self.timed("get_api_key", None, self.inner.get_api_key(id)).await
+    }
+
+    async fn list_api_keys(&self, owner: &str) -> UrlShortenerResult<Vec<StoredApiKey>> {
+        self.timed("list_api_keys", None, self.inner.list_api_keys(owner)).await
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> UrlShortenerResult<()> {
+        self.timed("revoke_api_key", None, self.inner.revoke_api_key(id)).await
+    }
+
+    async fn rotate_api_key(&self, id: i64, key_hash: &str, prefix: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.timed("rotate_api_key", None, self.inner.rotate_api_key(id, key_hash, prefix)).await
+    }
+
+    async fn authenticate_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.timed("authenticate_api_key", None, self.inner.authenticate_api_key(key_hash)).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> UrlShortenerResult<StoredApiKey> {
+        self.timed("find_api_key", None, self.inner.find_api_key(key_hash)).await
+    }
+
+    async fn create_session(&self, session: &Session) -> UrlShortenerResult<()> {
+        self.timed("create_session", None, self.inner.create_session(session)).await
+    }
+
+    async fn touch_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.timed("touch_session", None, self.inner.touch_session(id, idle_cutoff)).await
+    }
+
+    async fn find_session(&self, id: &str, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<Session> {
+        self.timed("find_session", None, self.inner.find_session(id, idle_cutoff)).await
+    }
+
+    async fn delete_session(&self, id: &str) -> UrlShortenerResult<()> {
+        self.timed("delete_session", None, self.inner.delete_session(id)).await
+    }
+
+    async fn purge_idle_sessions(&self, idle_cutoff: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.timed("purge_idle_sessions", None, self.inner.purge_idle_sessions(idle_cutoff)).await
+    }
+
+    async fn change_visits(&self, short_code: &str, change: VisitChange) -> UrlShortenerResult<VisitCounts> {
+        self.timed("change_visits", Some(short_code), self.inner.change_visits(short_code, change)).await
+    }
+
+    async fn record_audit(&self, entry: NewAuditEntry) -> UrlShortenerResult<AuditEntry> {
+        self.timed("record_audit", None, self.inner.record_audit(entry)).await
+    }
+
+    async fn import_urls(&self, urls: Vec<ShortenedUrl>) -> UrlShortenerResult<u64> {
+        self.timed("import_urls", None, self.inner.import_urls(urls)).await
+    }
+
+    async fn count_urls(&self, owner: Option<&str>) -> UrlShortenerResult<u64> {
+        self.timed("count_urls", None, self.inner.count_urls(owner)).await
+    }
+
+    async fn oldest_unvisited_urls(&self, owner: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<String>> {
+        self.timed("oldest_unvisited_urls", None, self.inner.oldest_unvisited_urls(owner, limit)).await
+    }
+
+    async fn delete_unvisited_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.timed("delete_unvisited_urls", None, self.inner.delete_unvisited_urls(short_codes)).await
+    }
+
+    async fn list_audit_entries(&self, limit: i64) -> UrlShortenerResult<Vec<AuditEntry>> {
+        self.timed("list_audit_entries", None, self.inner.list_audit_entries(limit)).await
+    }
+
+    async fn purge_audit_entries(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.timed("purge_audit_entries", None, self.inner.purge_audit_entries(before)).await
+    }
+
+    async fn record_webhook_delivery(&self, delivery: NewWebhookDelivery) -> UrlShortenerResult<WebhookDelivery> {
+        self.timed("record_webhook_delivery", None, self.inner.record_webhook_delivery(delivery)).await
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.timed("list_webhook_deliveries", None, self.inner.list_webhook_deliveries(webhook, before, limit)).await
+    }
+
+    async fn get_webhook_delivery(&self, webhook: &str, id: i64) -> UrlShortenerResult<WebhookDelivery> {
+        self.timed("get_webhook_delivery", None, self.inner.get_webhook_delivery(webhook, id)).await
+    }
+
+    async fn export_urls(&self, after: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("export_urls", None, self.inner.export_urls(after, limit)).await
+    }
+
+    async fn top_visited_urls(&self, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("top_visited_urls", None, self.inner.top_visited_urls(limit)).await
+    }
+
+    async fn search_urls(&self, prefix: &str, query: Option<&str>, limit: i64) -> UrlShortenerResult<Vec<ShortenedUrl>> {
+        self.timed("search_urls", None, self.inner.search_urls(prefix, query, limit)).await
+    }
+
+    async fn export_collections(&self) -> UrlShortenerResult<Vec<Collection>> {
+        self.timed("export_collections", None, self.inner.export_collections()).await
+    }
+
+    async fn restore_collection(&self, collection: &Collection) -> UrlShortenerResult<(Collection, bool)> {
+        self.timed("restore_collection", None, self.inner.restore_collection(collection)).await
+    }
+
+    async fn export_api_keys(&self) -> UrlShortenerResult<Vec<ExportedApiKey>> {
+        self.timed("export_api_keys", None, self.inner.export_api_keys()).await
+    }
+
+    async fn restore_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        self.timed("restore_api_key", None, self.inner.restore_api_key(key)).await
+    }
+
+    async fn copy_collection(&self, collection: &Collection) -> UrlShortenerResult<bool> {
+        self.timed("copy_collection", None, self.inner.copy_collection(collection)).await
+    }
+
+    async fn copy_api_key(&self, key: &ExportedApiKey) -> UrlShortenerResult<bool> {
+        self.timed("copy_api_key", None, self.inner.copy_api_key(key)).await
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expired_before: DateTime<Utc>,
+    ) -> UrlShortenerResult<Option<IdempotencyRecord>> {
+        self.timed(
+            "reserve_idempotency_key",
+            None,
+            self.inner.reserve_idempotency_key(scope, key, fingerprint, expired_before),
+        )
+        .await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: IdempotentResponse,
+    ) -> UrlShortenerResult<()> {
+        self.timed("complete_idempotency_key", None, self.inner.complete_idempotency_key(scope, key, response)).await
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> UrlShortenerResult<()> {
+        self.timed("release_idempotency_key", None, self.inner.release_idempotency_key(scope, key)).await
+    }
+
+    async fn purge_idempotency_keys(&self, before: DateTime<Utc>) -> UrlShortenerResult<u64> {
+        self.timed("purge_idempotency_keys", None, self.inner.purge_idempotency_keys(before)).await
+    }
+
+    async fn ban_domain(&self, domain: &str, reason: Option<&str>, blocked_by: &str) -> UrlShortenerResult<DomainBan> {
+        self.timed("ban_domain", None, self.inner.ban_domain(domain, reason, blocked_by)).await
+    }
+
+    async fn list_blocked_domains(&self) -> UrlShortenerResult<Vec<BlockedDomain>> {
+        self.timed("list_blocked_domains", None, self.inner.list_blocked_domains()).await
+    }
+
+    async fn is_domain_blocked(&self, host: &str) -> UrlShortenerResult<bool> {
+        self.timed("is_domain_blocked", None, self.inner.is_domain_blocked(host)).await
+    }
+
+    async fn disable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.timed("disable_urls", None, self.inner.disable_urls(short_codes)).await
+    }
+
+    async fn enable_urls(&self, short_codes: &[String]) -> UrlShortenerResult<Vec<String>> {
+        self.timed("enable_urls", None, self.inner.enable_urls(short_codes)).await
+    }
+
+    async fn regenerate_code(
+        &self,
+        short_code: &str,
+        new_code: &str,
+        stats: StatsOnRegenerate,
+        redirects_until: DateTime<Utc>,
+    ) -> UrlShortenerResult<RegeneratedLink> {
+        let call = self.inner.regenerate_code(short_code, new_code, stats, redirects_until);
+        self.timed("regenerate_code", Some(short_code), call).await
+    }
+
+    async fn list_retired_codes(&self, short_code: &str) -> UrlShortenerResult<Vec<RetiredCode>> {
+        self.timed("list_retired_codes", Some(short_code), self.inner.list_retired_codes(short_code)).await
+    }
+
+    async fn add_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<LinkAlias> {
+        self.timed("add_alias", Some(short_code), self.inner.add_alias(short_code, alias)).await
+    }
+
+    async fn remove_alias(&self, short_code: &str, alias: &str) -> UrlShortenerResult<()> {
+        self.timed("remove_alias", Some(short_code), self.inner.remove_alias(short_code, alias)).await
+    }
+
+    async fn list_aliases(&self, short_code: &str) -> UrlShortenerResult<Vec<LinkAlias>> {
+        self.timed("list_aliases", Some(short_code), self.inner.list_aliases(short_code)).await
+    }
+
+    async fn record_visit_events(&self, events: &[VisitEvent]) -> UrlShortenerResult<u64> {
+        self.timed("record_visit_events", None, self.inner.record_visit_events(events)).await
+    }
+
+    async fn export_visit_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, i64)>,
+        limit: i64,
+    ) -> UrlShortenerResult<Vec<RecordedVisit>> {
+        self.timed("export_visit_events", None, self.inner.export_visit_events(from, to, after, limit)).await
+    }
+
+    async fn visit_timeline(&self, short_code: &str, now: DateTime<Utc>) -> UrlShortenerResult<VisitTimeline> {
+        self.timed("visit_timeline", Some(short_code), self.inner.visit_timeline(short_code, now)).await
+    }
+
+    async fn visit_heatmap(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<VisitHeatmap> {
+        self.timed("visit_heatmap", Some(short_code), self.inner.visit_heatmap(short_code, since)).await
+    }
+
+    async fn daily_link_visits(&self, short_codes: &[String], since: DateTime<Utc>) -> UrlShortenerResult<Vec<LinkDailyVisits>> {
+        self.timed("daily_link_visits", None, self.inner.daily_link_visits(short_codes, since)).await
+    }
+
+    async fn visit_sources(&self, short_code: &str, since: DateTime<Utc>) -> UrlShortenerResult<Vec<SourceVisits>> {
+        self.timed("visit_sources", Some(short_code), self.inner.visit_sources(short_code, since)).await
+    }
+
+    async fn create_campaign(&self, owner: &str, name: &str) -> UrlShortenerResult<Campaign> {
+        self.timed("create_campaign", None, self.inner.create_campaign(owner, name)).await
+    }
+
+    async fn get_campaign(&self, id: i64) -> UrlShortenerResult<Campaign> {
+        self.timed("get_campaign", None, self.inner.get_campaign(id)).await
+    }
+
+    async fn list_campaigns(&self, owner: &str) -> UrlShortenerResult<Vec<Campaign>> {
+        self.timed("list_campaigns", None, self.inner.list_campaigns(owner)).await
+    }
+
+    async fn delete_campaign(&self, id: i64) -> UrlShortenerResult<()> {
+        self.timed("delete_campaign", None, self.inner.delete_campaign(id)).await
+    }
+
+    async fn add_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.timed("add_campaign_link", Some(short_code), self.inner.add_campaign_link(id, short_code)).await
+    }
+
+    async fn remove_campaign_link(&self, id: i64, short_code: &str) -> UrlShortenerResult<()> {
+        self.timed("remove_campaign_link", Some(short_code), self.inner.remove_campaign_link(id, short_code)).await
+    }
+
+    async fn campaign_stats(&self, id: i64, since: DateTime<Utc>) -> UrlShortenerResult<CampaignStats> {
+        self.timed("campaign_stats", None, self.inner.campaign_stats(id, since)).await
+    }
+
+    async fn roll_up_usage(&self, day: NaiveDate) -> UrlShortenerResult<u64> {
+        self.timed("roll_up_usage", None, self.inner.roll_up_usage(day)).await
+    }
+
+    async fn list_usage(&self, from: NaiveDate, to: NaiveDate) -> UrlShortenerResult<Vec<UsageRecord>> {
+        self.timed("list_usage", None, self.inner.list_usage(from, to)).await
+    }
+
+    /// Timing is not a backend of its own
+    fn backend(&self) -> String {
+        self.inner.backend()
+    }
+
+    async fn ping(&self) -> UrlShortenerResult<()> {
+        self.timed("ping", None, self.inner.ping()).await
+    }
+
+    async fn copy_campaign(&self, campaign: &Campaign) -> UrlShortenerResult<bool> {
+        self.timed("copy_campaign", None, self.inner.copy_campaign(campaign)).await
+    }
+
+    fn statement_cache_stats(&self) -> Option<StatementCacheStats> {
+        self.inner.statement_cache_stats()
+    }
+
+    fn slow_query_stats(&self) -> Option<SlowQueryStats> {
+        let by_operation = self.by_operation.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Some(SlowQueryStats {
+            threshold_ms: self.threshold.as_millis() as u64,
+            slow_queries_total: self.slow_queries_total.load(Ordering::Relaxed),
+            by_operation: by_operation.iter().map(|(operation, count)| (operation.to_string(), *count)).collect(),
+        })
+    }
+}
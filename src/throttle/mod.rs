@@ -0,0 +1,244 @@
+//! Request budgets per route class.
+//!
+//! Every request is put into a [`RouteClass`], and each class can be given its own limit on
+//! requests in flight and requests per second. The classes do not share budgets, so a burst
+//! of expensive admin exports or API writes cannot take the capacity redirects need.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::http::Method;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Length of the window requests per second are counted in
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// The kind of work a request asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteClass {
+    /// Short link redirects, and anything else served outside the API
+    Redirect,
+    /// API and dashboard reads
+    ReadApi,
+    /// API and dashboard requests that change something
+    WriteApi,
+    /// Operator endpoints: admin, exports and webhook deliveries
+    Admin,
+}
+
+impl RouteClass {
+    pub const ALL: [RouteClass; 4] = [Self::Redirect, Self::ReadApi, Self::WriteApi, Self::Admin];
+
+    /// The class of a request; `None` for health checks, which are never throttled
+    pub fn of(method: &Method, path: &str) -> Option<Self> {
+        const ADMIN_PREFIXES: &[&str] = &["/api/admin", "/api/export", "/api/webhooks"];
+        const API_PREFIXES: &[&str] = &["/api/", "/dashboard", "/integrations/"];
+
+        if is_under(path, "/health") {
+            return None;
+        }
+        if ADMIN_PREFIXES.iter().any(|prefix| is_under(path, prefix)) {
+            return Some(Self::Admin);
+        }
+        if !API_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+            return Some(Self::Redirect);
+        }
+        // Quick shortening creates links from a GET
+        let reads = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) && path != "/api/quick";
+        Some(if reads { Self::ReadApi } else { Self::WriteApi })
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for RouteClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Redirect => "redirect",
+            Self::ReadApi => "read_api",
+            Self::WriteApi => "write_api",
+            Self::Admin => "admin",
+        })
+    }
+}
+
+/// `path` is `prefix` or below it
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Limits of one route class; `None` leaves a dimension unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassBudget {
+    /// Requests handled at once, until their response body is sent
+    pub max_concurrency: Option<u32>,
+    /// Requests started per second
+    pub max_rate: Option<u32>,
+}
+
+impl ClassBudget {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_concurrency.is_none() && self.max_rate.is_none()
+    }
+}
+
+/// Parses `concurrency/rate`, e.g. `4/20`; either may be `0` or left out for no limit, and a
+/// bare number only limits concurrency
+impl FromStr for ClassBudget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let limit = |part: &str| -> Result<Option<u32>, String> {
+            match part.trim() {
+                "" => Ok(None),
+                part => part
+                    .parse::<u32>()
+                    .map(|limit| (limit > 0).then_some(limit))
+                    .map_err(|_| format!("invalid request budget '{}'", value.trim())),
+            }
+        };
+        let (concurrency, rate) = value.split_once('/').unwrap_or((value, ""));
+        Ok(Self { max_concurrency: limit(concurrency)?, max_rate: limit(rate)? })
+    }
+}
+
+/// Budgets of each route class
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThrottleConfig {
+    pub redirect: ClassBudget,
+    pub read_api: ClassBudget,
+    pub write_api: ClassBudget,
+    pub admin: ClassBudget,
+}
+
+impl ThrottleConfig {
+    pub fn budget(&self, class: RouteClass) -> ClassBudget {
+        match class {
+            RouteClass::Redirect => self.redirect,
+            RouteClass::ReadApi => self.read_api,
+            RouteClass::WriteApi => self.write_api,
+            RouteClass::Admin => self.admin,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        RouteClass::ALL.iter().all(|class| self.budget(*class).is_unlimited())
+    }
+}
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The class already has its maximum of requests in flight
+    Concurrency,
+    /// The class used up this second's requests
+    Rate,
+}
+
+/// Counters of one route class since startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassStats {
+    pub class: RouteClass,
+    #[serde(flatten)]
+    pub budget: ClassBudget,
+    /// Requests holding a slot; only tracked when concurrency is limited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_flight: Option<u32>,
+    pub rejected_concurrency: u64,
+    pub rejected_rate: u64,
+}
+
+struct ClassState {
+    budget: ClassBudget,
+    slots: Option<Arc<Semaphore>>,
+    window: Mutex<(Instant, u32)>,
+    rejected_concurrency: AtomicU64,
+    rejected_rate: AtomicU64,
+}
+
+impl ClassState {
+    fn new(budget: ClassBudget) -> Self {
+        Self {
+            budget,
+            slots: budget.max_concurrency.map(|limit| Arc::new(Semaphore::new(limit as usize))),
+            window: Mutex::new((Instant::now(), 0)),
+            rejected_concurrency: AtomicU64::new(0),
+            rejected_rate: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a request in the current window and returns whether it is within the rate
+    fn within_rate(&self, limit: u32) -> bool {
+        let mut window = self.window.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        if now.duration_since(window.0) >= RATE_WINDOW {
+            *window = (now, 0);
+        }
+        window.1 = window.1.saturating_add(1);
+        window.1 <= limit
+    }
+}
+
+/// Admits requests within their route class's budget
+pub struct RouteThrottle {
+    classes: Vec<ClassState>,
+}
+
+impl RouteThrottle {
+    pub fn new(config: &ThrottleConfig) -> Self {
+        Self {
+            classes: RouteClass::ALL.iter().map(|class| ClassState::new(config.budget(*class))).collect(),
+        }
+    }
+
+    /// Admits a request of `class`, returning the slot it holds while in flight if the class
+    /// limits concurrency
+    pub fn admit(&self, class: RouteClass) -> Result<Option<OwnedSemaphorePermit>, Rejection> {
+        let state = &self.classes[class.index()];
+        if state.budget.max_rate.is_some_and(|limit| !state.within_rate(limit)) {
+            state.rejected_rate.fetch_add(1, Ordering::Relaxed);
+            return Err(Rejection::Rate);
+        }
+        let Some(slots) = &state.slots else {
+            return Ok(None);
+        };
+        slots.clone().try_acquire_owned().map(Some).map_err(|_| {
+            state.rejected_concurrency.fetch_add(1, Ordering::Relaxed);
+            Rejection::Concurrency
+        })
+    }
+
+    pub fn stats(&self) -> Vec<ClassStats> {
+        RouteClass::ALL
+            .iter()
+            .zip(&self.classes)
+            .map(|(class, state)| ClassStats {
+                class: *class,
+                budget: state.budget,
+                in_flight: state
+                    .slots
+                    .as_ref()
+                    .zip(state.budget.max_concurrency)
+                    .map(|(slots, limit)| limit - slots.available_permits() as u32),
+                rejected_concurrency: state.rejected_concurrency.load(Ordering::Relaxed),
+                rejected_rate: state.rejected_rate.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for RouteThrottle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RouteThrottle").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,55 @@
+use super::*;
+
+#[test]
+fn test_requests_are_classified_by_path_and_method() {
+    let class = |method: Method, path: &str| RouteClass::of(&method, path);
+    assert_eq!(class(Method::GET, "/abc123"), Some(RouteClass::Redirect));
+    assert_eq!(class(Method::GET, "/t/abc123"), Some(RouteClass::Redirect));
+    assert_eq!(class(Method::GET, "/robots.txt"), Some(RouteClass::Redirect));
+    assert_eq!(class(Method::GET, "/api/stats/abc123"), Some(RouteClass::ReadApi));
+    assert_eq!(class(Method::GET, "/dashboard"), Some(RouteClass::ReadApi));
+    assert_eq!(class(Method::POST, "/api/shorten"), Some(RouteClass::WriteApi));
+    assert_eq!(class(Method::GET, "/api/quick"), Some(RouteClass::WriteApi));
+    assert_eq!(class(Method::POST, "/dashboard/login"), Some(RouteClass::WriteApi));
+    assert_eq!(class(Method::GET, "/api/export/visits.parquet"), Some(RouteClass::Admin));
+    assert_eq!(class(Method::POST, "/api/admin/cache/flush"), Some(RouteClass::Admin));
+    assert_eq!(class(Method::GET, "/api/webhooks/goals/deliveries"), Some(RouteClass::Admin));
+    assert_eq!(class(Method::GET, "/api/administrators"), Some(RouteClass::ReadApi));
+    assert_eq!(class(Method::GET, "/health"), None);
+    assert_eq!(class(Method::GET, "/health/ready"), None);
+}
+
+#[test]
+fn test_budgets_parse() {
+    let budget = |max_concurrency, max_rate| ClassBudget { max_concurrency, max_rate };
+    assert_eq!("4/20".parse(), Ok(budget(Some(4), Some(20))));
+    assert_eq!(" 4 ".parse(), Ok(budget(Some(4), None)));
+    assert_eq!("/20".parse(), Ok(budget(None, Some(20))));
+    assert_eq!("0/0".parse(), Ok(ClassBudget::default()));
+    assert!("four".parse::<ClassBudget>().is_err());
+    assert!("4/-1".parse::<ClassBudget>().is_err());
+}
+
+#[test]
+fn test_classes_have_separate_budgets() {
+    let throttle = RouteThrottle::new(&ThrottleConfig {
+        redirect: "/2".parse().unwrap(),
+        admin: "1".parse().unwrap(),
+        ..ThrottleConfig::default()
+    });
+
+    let export = throttle.admit(RouteClass::Admin).unwrap();
+    assert!(export.is_some());
+    assert_eq!(throttle.admit(RouteClass::Admin).unwrap_err(), Rejection::Concurrency);
+    for _ in 0..2 {
+        assert!(throttle.admit(RouteClass::Redirect).unwrap().is_none());
+    }
+    assert_eq!(throttle.admit(RouteClass::Redirect).unwrap_err(), Rejection::Rate);
+    assert!(throttle.admit(RouteClass::WriteApi).is_ok());
+
+    drop(export);
+    assert!(throttle.admit(RouteClass::Admin).is_ok());
+    let stats = throttle.stats();
+    assert_eq!((stats[0].rejected_rate, stats[0].in_flight), (1, None));
+    assert_eq!((stats[3].rejected_concurrency, stats[3].in_flight), (1, Some(0)));
+}
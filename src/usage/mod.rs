@@ -0,0 +1,84 @@
+//! Usage metering.
+//!
+//! Link creations, redirects and stored links are metered per owner into daily usage records,
+//! for feeding a billing system. Records are rolled up from the links and visit events that
+//! are stored anyway, so metering adds no work to redirects. Rolling a day up again replaces
+//! its records, so any number of instances may do it.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Days, Months, NaiveDate, Utc};
+use tracing::{debug, warn};
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType, UrlShortenerResult};
+use crate::models::UsageRecord;
+use crate::storage::StorageRef;
+
+/// Rolls up daily usage records in the background
+pub struct UsageMeter {
+    storage: StorageRef,
+}
+
+impl UsageMeter {
+    pub fn new(storage: StorageRef) -> Self {
+        Self { storage }
+    }
+
+    /// Rolls up the UTC day of `now` and the day before, so visits flushed after midnight
+    /// still count towards the day they happened on. Returns how many owners had usage today.
+    pub async fn roll_up(&self, now: DateTime<Utc>) -> u64 {
+        let today = now.date_naive();
+        let mut owners = 0;
+        for day in [today - Days::new(1), today] {
+            match self.storage.roll_up_usage(day).await {
+                Ok(count) => owners = count,
+                Err(e) => warn!(error = %e, %day, "Failed to roll up usage"),
+            }
+        }
+        debug!(owners, "Rolled up usage");
+        owners
+    }
+
+    /// Rolls up every `interval` for the lifetime of the process
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                self.roll_up(Utc::now()).await;
+            }
+        });
+    }
+}
+
+/// First day of a month written as `YYYY-MM`, and of the month after
+pub fn month_range(month: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (year, month) = month.trim().split_once('-')?;
+    let first = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+    Some((first, first.checked_add_months(Months::new(1))?))
+}
+
+/// Usage records as CSV, one row per owner and day under a header row
+pub fn to_csv(records: &[UsageRecord]) -> UrlShortenerResult<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.serialize(record).map_err(csv_error)?;
+    }
+    // Without records no header is written; billing imports still expect one
+    if records.is_empty() {
+        writer.write_record(["day", "owner", "creations", "redirects", "stored_links"]).map_err(csv_error)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| csv_error(e.into_error().into()))?;
+    String::from_utf8(bytes).map_err(|e| {
+        UrlShortenerError::with_source(UrlShortenerErrorType::InternalError("Usage CSV is not UTF-8".to_string()), e)
+    })
+}
+
+fn csv_error(e: csv::Error) -> UrlShortenerError {
+    UrlShortenerError::with_source(UrlShortenerErrorType::InternalError("Failed to write usage CSV".to_string()), e)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,66 @@
+use chrono::TimeZone;
+
+use super::*;
+use crate::models::{ShortenedUrl, VisitEvent};
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+fn link(code: &str, owner: Option<&str>, created_at: DateTime<Utc>) -> ShortenedUrl {
+    ShortenedUrl {
+        original_url: format!("https://example.com/{}", code),
+        short_url: code.to_string(),
+        created_at,
+        owner: owner.map(str::to_string),
+        ..ShortenedUrl::default()
+    }
+}
+
+fn visit(code: &str, visited_at: DateTime<Utc>, sample_rate: i32) -> VisitEvent {
+    VisitEvent { short_code: code.to_string(), visited_at, visitor: None, source: None, sample_rate }
+}
+
+#[test]
+fn test_month_range() {
+    let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    assert_eq!(month_range("2026-10"), Some((day(2026, 10, 1), day(2026, 11, 1))));
+    assert_eq!(month_range("2026-12"), Some((day(2026, 12, 1), day(2027, 1, 1))));
+    assert_eq!(month_range("2026-13"), None);
+    assert_eq!(month_range("October"), None);
+}
+
+#[tokio::test]
+async fn test_roll_up_meters_each_owner_per_day() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let now = Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap();
+    let yesterday = now - chrono::Duration::days(1);
+    storage.save_url(link("old", Some("alice"), yesterday)).await.unwrap();
+    storage.save_url(link("new", Some("alice"), now)).await.unwrap();
+    storage.save_url(link("bobs", Some("bob"), yesterday)).await.unwrap();
+    storage.save_url(link("anon", None, now)).await.unwrap();
+    storage
+        .record_visit_events(&[visit("old", now, 1), visit("new", now, 10), visit("old", yesterday, 1), visit("anon", now, 1)])
+        .await
+        .unwrap();
+
+    let meter = UsageMeter::new(storage.clone());
+    assert_eq!(meter.roll_up(now).await, 2);
+
+    let today = now.date_naive();
+    let records = storage.list_usage(today - Days::new(1), today + Days::new(1)).await.unwrap();
+    let rows: Vec<_> = records
+        .iter()
+        .map(|r| (r.owner.as_str(), r.day, r.creations, r.redirects, r.stored_links))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("alice", today - Days::new(1), 1, 1, 1),
+            ("alice", today, 1, 11, 2),
+            ("bob", today - Days::new(1), 1, 0, 1),
+            ("bob", today, 0, 0, 1),
+        ]
+    );
+
+    let csv = to_csv(&records[..1]).unwrap();
+    assert_eq!(csv, "day,owner,creations,redirects,stored_links\n2026-10-15,alice,1,1,1\n");
+    assert_eq!(to_csv(&[]).unwrap(), "day,owner,creations,redirects,stored_links\n");
+}
@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use unicode_security::{skeleton, MixedScript};
+use url::Url;
+
+/// What to do with target URLs whose hostname looks like a homograph of another domain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HomographPolicy {
+    /// Skip detection
+    Off,
+    /// Log suspicious hosts server-side only
+    Flag,
+    /// Log and return warnings in the create response
+    #[default]
+    Warn,
+    /// Reject the URL
+    Block,
+}
+
+impl FromStr for HomographPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "flag" => Ok(Self::Flag),
+            "warn" => Ok(Self::Warn),
+            "block" => Ok(Self::Block),
+            other => Err(format!("unknown homograph policy '{}'", other)),
+        }
+    }
+}
+
+/// A non-fatal finding about a target URL, returned as response metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UrlWarning {
+    /// Machine-readable finding, e.g. `mixed_script_host`
+    pub code: String,
+    pub message: String,
+}
+
+/// Both forms of an internationalized hostname, so clients can render it safely
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IdnHost {
+    /// As stored and sent on redirects, e.g. `xn--bcher-kva.example`
+    pub punycode: String,
+    /// For display, e.g. `bücher.example`
+    pub unicode: String,
+    /// The host imitates another domain; show `punycode` rather than `unicode`
+    pub suspicious_idn: bool,
+}
+
+/// The punycode and Unicode forms of a URL's host, if it has internationalized labels
+pub fn idn_host(url: &Url) -> Option<IdnHost> {
+    let host = url.host_str()?;
+    if !host.split('.').any(|label| label.starts_with("xn--")) {
+        return None;
+    }
+    let (unicode, result) = idna::domain_to_unicode(host);
+    // Labels that do not decode would be shown as replacement characters
+    result.ok()?;
+    Some(IdnHost { punycode: host.to_string(), unicode, suspicious_idn: !inspect_host(url).is_empty() })
+}
+
+/// Inspects the hostname of a target URL for lookalike-domain tricks.
+///
+/// Internationalized labels (punycode `xn--` in the parsed URL) are decoded and flagged when
+/// they mix scripts (`pаypal` with a Cyrillic `а`) or when every character is confusable with
+/// ASCII (an all-Cyrillic `аррӏе` posing as `apple`). Legitimate single-script IDNs such as
+/// `bücher.example` are not flagged.
+pub fn inspect_host(url: &Url) -> Vec<UrlWarning> {
+    let Some(host) = url.host_str() else { return Vec::new() };
+    if !host.split('.').any(|label| label.starts_with("xn--")) {
+        return Vec::new();
+    }
+
+    let (unicode_host, _) = idna::domain_to_unicode(host);
+    let mut warnings = Vec::new();
+    for label in unicode_host.split('.').filter(|label| !label.is_ascii()) {
+        if !label.is_single_script() {
+            warnings.push(UrlWarning {
+                code: "mixed_script_host".to_string(),
+                message: format!("Host label '{}' mixes characters from different scripts", label),
+            });
+        } else if skeleton(label).all(|c| c.is_ascii()) {
+            let lookalike: String = skeleton(label).collect();
+            warnings.push(UrlWarning {
+                code: "confusable_host".to_string(),
+                message: format!("Host label '{}' looks like '{}'", label, lookalike),
+            });
+        }
+    }
+    warnings
+}
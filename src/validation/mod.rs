@@ -0,0 +1,117 @@
+//! Request validation shared by the HTTP handlers.
+//!
+//! Handlers call [`Validate::validate`] on incoming payloads before touching the service layer,
+//! so every rule violation is reported at once with the offending field, as a 422 response.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::errors::{UrlShortenerError, UrlShortenerErrorType};
+use crate::models::PageCursor;
+use crate::services::MAX_URL_LENGTH_CEILING;
+
+mod homograph;
+mod schemes;
+
+pub use homograph::{idn_host, inspect_host, HomographPolicy, IdnHost, UrlWarning};
+pub use schemes::blocked_scheme;
+
+/// A single rule violation on a request field
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldError {
+    /// Name of the offending field, as it appears in the request
+    pub field: String,
+    /// Machine-readable rule identifier, e.g. `required` or `too_long`
+    pub code: String,
+    /// Human-readable description of the violation
+    pub message: String,
+}
+
+/// Collects field errors while a request is being validated
+#[derive(Debug, Default)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a violation of `code` on `field`
+    pub fn add(&mut self, field: &str, code: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Finishes validation, failing if any violation was recorded
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl From<ValidationErrors> for UrlShortenerError {
+    fn from(errors: ValidationErrors) -> Self {
+        UrlShortenerErrorType::ValidationFailed(errors.errors).into()
+    }
+}
+
+/// Implemented by request payloads that need checking before they reach the service layer
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Requires a non-empty value
+pub fn check_required(errors: &mut ValidationErrors, field: &str, value: &str) -> bool {
+    if value.trim().is_empty() {
+        errors.add(field, "required", format!("{} is required", field));
+        return false;
+    }
+    true
+}
+
+/// Caps a value's length in characters
+pub fn check_max_length(errors: &mut ValidationErrors, field: &str, value: &str, max: usize) -> bool {
+    if value.chars().count() > max {
+        errors.add(field, "too_long", format!("{} must be at most {} characters", field, max));
+        return false;
+    }
+    true
+}
+
+/// Requires an absolute URL no longer than any tier may shorten; the caller's own limit is up to
+/// the service layer
+pub fn check_url(errors: &mut ValidationErrors, field: &str, value: &str) {
+    if !check_required(errors, field, value) || !check_max_length(errors, field, value, MAX_URL_LENGTH_CEILING) {
+        return;
+    }
+    // Left to the service layer, which rejects these with their own error
+    if blocked_scheme(value).is_some() {
+        return;
+    }
+
+    if let Err(e) = Url::parse(value) {
+        errors.add(field, "invalid_url", format!("{} is not a valid URL: {}", field, e));
+    }
+}
+
+/// Requires a `cursor` handed out as a previous page's `next_cursor`, when one is given
+pub fn check_cursor(errors: &mut ValidationErrors, cursor: Option<&str>) {
+    if cursor.is_some_and(|cursor| PageCursor::decode(cursor).is_none()) {
+        errors.add("cursor", "invalid_cursor", "cursor must be the next_cursor of a previous page");
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,41 @@
+/// Schemes that run script or render inline content instead of navigating to a resource
+const BLOCKED_SCHEMES: &[&str] = &[
+    "javascript",
+    "vbscript",
+    "livescript",
+    "data",
+    "blob",
+    "file",
+    "filesystem",
+    "about",
+    "view-source",
+    "jar",
+];
+
+/// Longest scheme considered; anything longer is not one of the blocked schemes
+const MAX_SCHEME_LENGTH: usize = 16;
+
+/// Invisible characters some browsers and mail clients drop when reading a URL
+fn is_ignorable(c: char) -> bool {
+    c.is_whitespace() || c.is_control() || matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Returns the scheme of `input` if it is blocked.
+///
+/// Whitespace, control and zero-width characters are skipped before the scheme is read, so
+/// disguises like `java\tscript:`, `\u{0}javascript:` or `JaVaScRiPt:` are caught even where the
+/// URL parser would reject or rewrite them.
+pub fn blocked_scheme(input: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for c in input.chars().filter(|&c| !is_ignorable(c)) {
+        if c == ':' {
+            let scheme = scheme.to_ascii_lowercase();
+            return BLOCKED_SCHEMES.contains(&scheme.as_str()).then_some(scheme);
+        }
+        if scheme.len() >= MAX_SCHEME_LENGTH {
+            return None;
+        }
+        scheme.push(c);
+    }
+    None
+}
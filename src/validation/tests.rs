@@ -0,0 +1,135 @@
+use super::*;
+
+struct Payload {
+    url: String,
+    name: String,
+}
+
+impl Validate for Payload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        check_url(&mut errors, "url", &self.url);
+        check_max_length(&mut errors, "name", &self.name, 5);
+        errors.into_result()
+    }
+}
+
+fn field_errors(payload: Payload) -> Vec<FieldError> {
+    match UrlShortenerError::from(payload.validate().unwrap_err()).error_type {
+        UrlShortenerErrorType::ValidationFailed(errors) => errors,
+        error_type => panic!("Expected ValidationFailed, got {:?}", error_type),
+    }
+}
+
+#[test]
+fn test_valid_payload() {
+    let payload = Payload { url: "https://example.com".to_string(), name: "ok".to_string() };
+    assert!(payload.validate().is_ok());
+}
+
+#[test]
+fn test_reports_every_invalid_field() {
+    let errors = field_errors(Payload { url: " ".to_string(), name: "too long".to_string() });
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!((errors[0].field.as_str(), errors[0].code.as_str()), ("url", "required"));
+    assert_eq!((errors[1].field.as_str(), errors[1].code.as_str()), ("name", "too_long"));
+}
+
+#[test]
+fn test_url_rules() {
+    let errors = field_errors(Payload { url: "not-a-url".to_string(), name: String::new() });
+    assert_eq!(errors[0].code, "invalid_url");
+
+    let long_url = format!("https://example.com/{}", "a".repeat(MAX_URL_LENGTH_CEILING));
+    let errors = field_errors(Payload { url: long_url, name: String::new() });
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, "too_long");
+}
+
+#[test]
+fn test_blocks_script_and_inline_content_schemes() {
+    let bypasses = [
+        "javascript:alert(1)",
+        "JaVaScRiPt:alert(1)",
+        "  javascript:alert(1)",
+        "\tjava\nscript:alert(1)",
+        "java\rscript:alert(1)",
+        "java\u{0}script:alert(1)",
+        "\u{1}\u{1f}javascript:alert(1)",
+        "java\u{200B}script:alert(1)",
+        "\u{FEFF}javascript:alert(1)",
+        "\u{A0}javascript:alert(1)",
+        "javascript :alert(1)",
+        "vbscript:msgbox(1)",
+        "data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==",
+        "DATA:text/html,<script>alert(1)</script>",
+        "file:///etc/passwd",
+        "blob:https://example.com/uuid",
+        "view-source:https://example.com",
+    ];
+    for input in bypasses {
+        assert!(blocked_scheme(input).is_some(), "{:?} was not blocked", input);
+    }
+    assert_eq!(blocked_scheme("\tJava\0Script:void(0)").as_deref(), Some("javascript"));
+
+    // Blocked scheme names elsewhere in a URL are fine
+    for input in [
+        "https://example.com/javascript:alert(1)",
+        "https://example.com/?next=data:text/html,x",
+        "mailto:javascript@example.com",
+        "https://javascript.info/",
+        "javascript-tutorial.example",
+    ] {
+        assert_eq!(blocked_scheme(input), None, "{:?} was blocked", input);
+    }
+}
+
+#[test]
+fn test_blocked_schemes_are_left_to_the_service() {
+    let payload = Payload { url: "java\u{0}script:alert(1)".to_string(), name: String::new() };
+    assert!(payload.validate().is_ok());
+}
+
+fn host_warnings(url: &str) -> Vec<String> {
+    inspect_host(&Url::parse(url).unwrap()).into_iter().map(|w| w.code).collect()
+}
+
+#[test]
+fn test_flags_known_confusable_hosts() {
+    // Latin with a single Cyrillic letter swapped in
+    assert_eq!(host_warnings("https://pаypal.com/login"), ["mixed_script_host"]);
+    assert_eq!(host_warnings("https://gооgle.com"), ["mixed_script_host"]);
+    // Entirely Cyrillic, yet reads as "apple"; also caught when given as punycode
+    assert_eq!(host_warnings("https://аррӏе.com"), ["confusable_host"]);
+    assert_eq!(host_warnings("https://xn--80ak6aa92e.com"), ["confusable_host"]);
+    // Only the suspicious label is reported
+    assert_eq!(host_warnings("https://login.pаypal.com"), ["mixed_script_host"]);
+}
+
+#[test]
+fn test_ignores_ordinary_hosts() {
+    assert!(host_warnings("https://paypal.com").is_empty());
+    assert!(host_warnings("https://bücher.de").is_empty());
+    assert!(host_warnings("https://例え.テスト").is_empty());
+    assert!(host_warnings("https://пример.рф").is_empty());
+    assert!(host_warnings("http://127.0.0.1:8080").is_empty());
+}
+
+#[test]
+fn test_idn_hosts_come_in_both_forms() {
+    let idn = |url: &str| idn_host(&Url::parse(url).unwrap());
+    let books = idn("https://bücher.de/neu").unwrap();
+    assert_eq!((books.punycode.as_str(), books.unicode.as_str(), books.suspicious_idn), ("xn--bcher-kva.de", "bücher.de", false));
+    let apple = idn("https://xn--80ak6aa92e.com").unwrap();
+    assert_eq!((apple.unicode.as_str(), apple.suspicious_idn), ("аррӏе.com", true));
+    assert!(idn("https://paypal.com").is_none());
+    assert!(idn("http://127.0.0.1:8080").is_none());
+}
+
+#[test]
+fn test_parse_homograph_policy() {
+    assert_eq!("Block".parse(), Ok(HomographPolicy::Block));
+    assert_eq!(" off ".parse(), Ok(HomographPolicy::Off));
+    assert!("deny".parse::<HomographPolicy>().is_err());
+}
@@ -0,0 +1,228 @@
+//! Outgoing event notifications.
+//!
+//! Events are delivered through a [`Notifier`]; [`WebhookNotifier`] POSTs them as JSON to a
+//! configured URL and records every attempt, so failed deliveries can be inspected and replayed.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::errors::{UrlShortenerErrorType, UrlShortenerResult};
+use crate::http_client::HttpClient;
+use crate::models::{NewAuditEntry, NewWebhookDelivery, WebhookDelivery};
+use crate::storage::StorageRef;
+
+/// Bytes of a response body kept in the delivery log
+const RESPONSE_SNIPPET_BYTES: usize = 1024;
+
+/// A link reached the click goal its owner set
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GoalReached {
+    pub short_code: String,
+    pub original_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub click_goal: i64,
+    /// Visits when the goal was detected; may already exceed the goal
+    pub visits: i64,
+}
+
+/// A link was disabled because its redirects ran far above its usual rate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkAutoDisabled {
+    pub short_code: String,
+    pub original_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Redirects in the minute the link was disabled in
+    pub redirects_per_minute: u32,
+    /// The link's usual redirects per minute, as this instance had learned it
+    pub baseline_per_minute: f64,
+}
+
+/// Event payloads, serialized as `{"event": "...", "occurred_at": "...", "data": {...}}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "event", content = "data")]
+pub enum EventKind {
+    #[serde(rename = "link.goal_reached")]
+    GoalReached(GoalReached),
+    #[serde(rename = "link.auto_disabled")]
+    LinkAutoDisabled(LinkAutoDisabled),
+}
+
+impl EventKind {
+    /// The event type, as sent in the `event` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GoalReached(_) => "link.goal_reached",
+            Self::LinkAutoDisabled(_) => "link.auto_disabled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookEvent {
+    #[serde(flatten)]
+    pub kind: EventKind,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl WebhookEvent {
+    pub fn new(kind: EventKind) -> Self {
+        Self {
+            kind,
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+/// Delivers events to whoever is listening
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &WebhookEvent) -> UrlShortenerResult<()>;
+}
+
+/// POSTs events as JSON to a fixed URL; any non-2xx response is a failed delivery.
+/// Each attempt is recorded under the webhook's name.
+pub struct WebhookNotifier {
+    name: String,
+    url: String,
+    http: HttpClient,
+    storage: StorageRef,
+}
+
+impl WebhookNotifier {
+    pub fn new(name: &str, url: String, http: HttpClient, storage: StorageRef) -> Self {
+        Self {
+            name: name.to_string(),
+            url,
+            http,
+            storage,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// POSTs a payload once, returning the attempt to be recorded
+    async fn attempt(&self, event: &str, payload: serde_json::Value, replay_of: Option<i64>) -> NewWebhookDelivery {
+        let started = Instant::now();
+        let outcome = self.post(&payload).await;
+        let latency_ms = started.elapsed().as_millis() as i64;
+
+        let (status_code, response) = match outcome {
+            Ok((status, body)) => (Some(status), Some(body)),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        NewWebhookDelivery {
+            webhook: self.name.clone(),
+            event: event.to_string(),
+            payload,
+            status_code: status_code.map(i32::from),
+            latency_ms,
+            response,
+            delivered: status_code.is_some_and(|status| (200..300).contains(&status)),
+            replay_of,
+        }
+    }
+
+    /// Status and the start of the body of the receiver's response
+    async fn post(&self, payload: &serde_json::Value) -> reqwest::Result<(u16, String)> {
+        let mut resp = self.http.send("webhook", self.http.post(&self.url).json(payload)).await?;
+        let status = resp.status().as_u16();
+
+        let mut body = Vec::new();
+        while body.len() < RESPONSE_SNIPPET_BYTES {
+            match resp.chunk().await? {
+                Some(chunk) => body.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        body.truncate(RESPONSE_SNIPPET_BYTES);
+        Ok((status, String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    /// Delivery attempts, newest first, starting before the delivery with ID `before`
+    pub async fn deliveries(&self, before: Option<i64>, limit: i64) -> UrlShortenerResult<Vec<WebhookDelivery>> {
+        self.storage.list_webhook_deliveries(&self.name, before, limit).await
+    }
+
+    /// Sends a recorded delivery's payload again, as it was first sent, and records the new attempt
+    pub async fn replay(&self, id: i64, actor: &str) -> UrlShortenerResult<WebhookDelivery> {
+        let original = self.storage.get_webhook_delivery(&self.name, id).await?;
+        let attempt = self.attempt(&original.event, original.payload, Some(original.id)).await;
+        let replayed = self.storage.record_webhook_delivery(attempt).await?;
+
+        self.storage
+            .record_audit(NewAuditEntry {
+                actor: actor.to_string(),
+                action: "webhook.replay".to_string(),
+                target: Some(self.name.clone()),
+                details: json!({
+                    "delivery": original.id,
+                    "replay": replayed.id,
+                    "delivered": replayed.delivered,
+                }),
+            })
+            .await?;
+
+        info!(webhook = %self.name, delivery = original.id, delivered = replayed.delivered, "Replayed webhook delivery");
+        Ok(replayed)
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &WebhookEvent) -> UrlShortenerResult<()> {
+        let payload = serde_json::to_value(event).expect("Webhook events serialize to JSON");
+        let attempt = self.attempt(event.kind.name(), payload, None).await;
+        let (delivered, response) = (attempt.delivered, attempt.response.clone());
+
+        // A lost log entry must not turn a delivered event into a retried one
+        if let Err(e) = self.storage.record_webhook_delivery(attempt).await {
+            warn!(error = %e, webhook = %self.name, "Failed to record webhook delivery");
+        }
+
+        if delivered {
+            Ok(())
+        } else {
+            Err(UrlShortenerErrorType::InternalError(format!(
+                "Failed to deliver webhook: {}",
+                response.unwrap_or_default()
+            ))
+            .into())
+        }
+    }
+}
+
+/// The configured webhooks, by name
+#[derive(Default)]
+pub struct Webhooks {
+    notifiers: HashMap<String, Arc<WebhookNotifier>>,
+}
+
+impl Webhooks {
+    pub fn register(&mut self, notifier: Arc<WebhookNotifier>) {
+        self.notifiers.insert(notifier.name().to_string(), notifier);
+    }
+
+    /// A configured webhook; NotFound for unknown names
+    pub fn get(&self, name: &str) -> UrlShortenerResult<&WebhookNotifier> {
+        self.notifiers
+            .get(name)
+            .map(|notifier| &**notifier)
+            .ok_or_else(|| UrlShortenerErrorType::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use actix_web::{http::StatusCode, web, App, HttpResponse, HttpServer};
+
+use super::*;
+use crate::storage::{MemoryStorage, Storage, StorageConfig};
+
+/// Starts a receiver answering every request with the status currently in `status`
+fn receiver(status: web::Data<AtomicU16>) -> String {
+    let server = HttpServer::new(move || {
+        App::new().app_data(status.clone()).default_service(web::to(|status: web::Data<AtomicU16>| async move {
+            let status = StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap();
+            HttpResponse::build(status).body("upstream down ".repeat(100))
+        }))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let url = format!("http://{}/hook", server.addrs()[0]);
+    actix_rt::spawn(server.run());
+    url
+}
+
+fn goal_event() -> WebhookEvent {
+    WebhookEvent::new(EventKind::GoalReached(GoalReached {
+        short_code: "launch".to_string(),
+        original_url: "https://example.com/launch".to_string(),
+        title: None,
+        owner: Some("alice".to_string()),
+        click_goal: 10,
+        visits: 12,
+    }))
+}
+
+#[actix_rt::test]
+async fn test_failed_delivery_is_recorded_and_replayed() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let status = web::Data::new(AtomicU16::new(502));
+    let notifier = WebhookNotifier::new("goals", receiver(status.clone()), HttpClient::default(), storage.clone());
+
+    assert!(notifier.notify(&goal_event()).await.is_err());
+    let failed = notifier.deliveries(None, 10).await.unwrap().remove(0);
+    assert_eq!((failed.event.as_str(), failed.status_code, failed.delivered), ("link.goal_reached", Some(502), false));
+    assert_eq!(failed.payload["data"]["short_code"], "launch");
+    assert_eq!(failed.response.unwrap().len(), RESPONSE_SNIPPET_BYTES);
+
+    status.store(204, Ordering::SeqCst);
+    let replayed = notifier.replay(failed.id, "ops").await.unwrap();
+    assert_eq!((replayed.status_code, replayed.delivered, replayed.replay_of), (Some(204), true, Some(failed.id)));
+    assert_eq!(replayed.payload["occurred_at"], failed.payload["occurred_at"]);
+
+    let ids: Vec<i64> = notifier.deliveries(None, 10).await.unwrap().iter().map(|d| d.id).collect();
+    assert_eq!(ids, [replayed.id, failed.id]);
+    assert_eq!(notifier.deliveries(Some(replayed.id), 10).await.unwrap().len(), 1);
+    let audit = storage.list_audit_entries(1).await.unwrap().remove(0);
+    assert_eq!((audit.actor.as_str(), audit.action.as_str()), ("ops", "webhook.replay"));
+}
+
+#[actix_rt::test]
+async fn test_unreachable_receiver_is_recorded_without_status() {
+    let storage = Arc::new(MemoryStorage::new(StorageConfig::default()));
+    let notifier = Arc::new(WebhookNotifier::new("goals", "http://127.0.0.1:9/hook".to_string(), HttpClient::default(), storage.clone()));
+    assert!(notifier.notify(&goal_event()).await.is_err());
+
+    let failed = notifier.deliveries(None, 10).await.unwrap().remove(0);
+    assert_eq!((failed.status_code, failed.delivered), (None, false));
+    assert!(!failed.response.unwrap().is_empty());
+
+    let mut webhooks = Webhooks::default();
+    webhooks.register(notifier);
+    assert!(webhooks.get("goals").is_ok());
+    assert!(webhooks.get("other").is_err());
+    // Deliveries are only found under the webhook that made them
+    let other = WebhookNotifier::new("other", "http://127.0.0.1:9/hook".to_string(), HttpClient::default(), storage);
+    assert!(other.replay(failed.id, "ops").await.is_err());
+}
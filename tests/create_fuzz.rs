@@ -0,0 +1,116 @@
+//! Property-based fuzzing of URL creation, through both the service and the JSON handler.
+//!
+//! Inputs mix arbitrary bytes with pathological URLs (huge query strings, odd schemes,
+//! IDN hosts, control characters). Whatever the input, creation must either succeed with a
+//! valid, bounded URL or fail with a client error; it must never panic or return a 5xx.
+//! Raise the case count with `PROPTEST_CASES=100000 cargo test --test create_fuzz`.
+
+use std::sync::Arc;
+
+use actix_web::{test as actix_test, web, App};
+use proptest::prelude::*;
+use url::Url;
+
+use url_map::errors::UrlShortenerErrorType;
+use url_map::handlers::create_url;
+use url_map::services::{UrlService, MAX_URL_LENGTH};
+use url_map::storage::{MemoryStorage, StorageConfig};
+use url_map::validation::blocked_scheme;
+
+fn service() -> UrlService {
+    UrlService::new(Arc::new(MemoryStorage::new(StorageConfig::default())))
+}
+
+fn runtime() -> actix_rt::Runtime {
+    actix_rt::Runtime::new().expect("Failed to build runtime")
+}
+
+/// URL-ish strings assembled from hostile building blocks
+fn pathological_url() -> impl Strategy<Value = String> {
+    let scheme = prop_oneof![
+        Just("https://"), Just("http://"), Just("HTTPS://"), Just("ftp://"), Just("javascript:"),
+        Just("data:text/html,"), Just(" https://"), Just("\u{0}https://"), Just("//"), Just(""),
+        Just("java\tscript:"), Just("\u{200B}vbscript:"),
+    ];
+    let host = prop_oneof![
+        Just("example.com".to_string()),
+        Just("xn--80ak6aa92e.com".to_string()),
+        Just("例え.テスト".to_string()),
+        Just("ex\u{0}ample.com".to_string()),
+        Just("[::1]".to_string()),
+        Just("user:pa%ss@host:99999".to_string()),
+        "[a-z0-9.-]{0,70}",
+    ];
+    let path = "(/[\\PC\\s%\u{0}]{0,20}){0,5}";
+    let query = prop_oneof![
+        Just(String::new()),
+        "\\?[a-z=&%]{0,50}",
+        (1usize..3_000).prop_map(|n| format!("?q={}", "a".repeat(n))),
+        (1usize..1_200).prop_map(|n| format!("?q={}", " ".repeat(n))),
+    ];
+    (scheme, host, path, query).prop_map(|(s, h, p, q)| format!("{}{}{}{}", s, h, p, q))
+}
+
+fn any_input() -> impl Strategy<Value = String> {
+    prop_oneof![
+        pathological_url(),
+        any::<String>(),
+        proptest::collection::vec(any::<u8>(), 0..256)
+            .prop_map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn create_short_url_never_panics_and_bounds_output(input in any_input()) {
+        let result = runtime().block_on(service().create_short_url(input));
+
+        match result {
+            Ok(created) => {
+                prop_assert!(Url::parse(&created.original_url).is_ok());
+                prop_assert!(created.original_url.len() <= MAX_URL_LENGTH);
+                prop_assert!(!created.original_url.contains('\0'), "raw NUL in stored URL");
+                prop_assert!(blocked_scheme(&created.original_url).is_none(), "blocked scheme stored");
+            }
+            Err(e) => prop_assert!(
+                matches!(
+                    e.error_type,
+                    UrlShortenerErrorType::InvalidUrl(_)
+                        | UrlShortenerErrorType::UrlTooLong(_)
+                        | UrlShortenerErrorType::BlockedScheme(_)
+                ),
+                "unexpected error {:?}",
+                e.error_type
+            ),
+        }
+    }
+
+    #[test]
+    fn create_handler_only_returns_client_errors(body in proptest::collection::vec(any::<u8>(), 0..512), url in any_input()) {
+        runtime().block_on(async {
+            let app = actix_test::init_service(
+                App::new()
+                    .app_data(web::Data::new(service()))
+                    .service(web::resource("/api/shorten").route(web::post().to(create_url)))
+            ).await;
+
+            // Raw bytes claiming to be JSON
+            let req = actix_test::TestRequest::post()
+                .uri("/api/shorten")
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload(body)
+                .to_request();
+            let status = actix_test::call_service(&app, req).await.status();
+            prop_assert!(status.is_success() || status.is_client_error(), "status {}", status);
+
+            // Well-formed JSON carrying a hostile URL
+            let req = actix_test::TestRequest::post()
+                .uri("/api/shorten")
+                .set_json(serde_json::json!({ "original_url": url }))
+                .to_request();
+            let status = actix_test::call_service(&app, req).await.status();
+            prop_assert!(status.is_success() || status.is_client_error(), "status {}", status);
+            Ok(())
+        })?;
+    }
+}